@@ -0,0 +1,335 @@
+use anyhow::{Context, Result};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+
+use super::{format_anomaly, DeliverySender};
+use crate::broadcast::EventBroadcaster;
+use crate::config::EmailAlertConfig;
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, Event};
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+// How often to look for a digest that has come due
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// Warnings held for the next digest, past this the newest are counted rather than kept
+const MAX_PENDING: usize = 500;
+
+// Critical anomalies go straight out on their own, Warnings wait for a digest that's sent
+// at most once per interval. Info is never emailed. A Critical of the same kind as one sent
+// within the interval goes in the digest too, so one that fires every tick isn't a flood.
+struct AlertBatcher {
+    interval: Duration,
+    pending: Vec<Anomaly>,
+    dropped: usize,
+    last_digest: Instant,
+    last_critical: HashMap<AnomalyKind, Instant>,
+}
+
+struct Digest {
+    anomalies: Vec<Anomaly>,
+    dropped: usize, // Warnings that didn't fit in the queue
+}
+
+impl AlertBatcher {
+    fn new(interval: Duration, now: Instant) -> Self {
+        Self { interval, pending: Vec::new(), dropped: 0, last_digest: now, last_critical: HashMap::new() }
+    }
+
+    /// The anomaly if it's to be sent now, otherwise it's queued for the digest or ignored
+    fn add(&mut self, anomaly: Anomaly, now: Instant) -> Option<Anomaly> {
+        match anomaly.severity {
+            AnomalySeverity::Critical
+                if self
+                    .last_critical
+                    .get(&anomaly.kind)
+                    .is_none_or(|sent| now.duration_since(*sent) >= self.interval) =>
+            {
+                self.last_critical.insert(anomaly.kind.clone(), now);
+                Some(anomaly)
+            }
+            AnomalySeverity::Critical | AnomalySeverity::Warning => {
+                if self.pending.len() < MAX_PENDING {
+                    self.pending.push(anomaly);
+                } else {
+                    self.dropped += 1;
+                }
+                None
+            }
+            AnomalySeverity::Info => None,
+        }
+    }
+
+    /// Put back anomalies that failed to send, ahead of anything queued since, for the next digest
+    fn requeue(&mut self, mut anomalies: Vec<Anomaly>, dropped: usize) {
+        anomalies.append(&mut self.pending);
+        self.dropped += dropped + anomalies.len().saturating_sub(MAX_PENDING);
+        anomalies.truncate(MAX_PENDING);
+        self.pending = anomalies;
+    }
+
+    /// Everything queued, once the interval has passed since the last digest
+    fn take_digest(&mut self, now: Instant) -> Option<Digest> {
+        if self.pending.is_empty() || now.duration_since(self.last_digest) < self.interval {
+            return None;
+        }
+        self.last_digest = now;
+        Some(Digest {
+            anomalies: std::mem::take(&mut self.pending),
+            dropped: std::mem::take(&mut self.dropped),
+        })
+    }
+}
+
+fn critical_email(host: &str, anomaly: &Anomaly) -> (String, String) {
+    let subject = format!("[Black Box] Critical on {}: {}", host, anomaly.message);
+    let body = format!(
+        "Black Box on {} recorded a critical anomaly:\n\n{}\n",
+        host,
+        format_anomaly(anomaly)
+    );
+    (subject, body)
+}
+
+fn digest_email(host: &str, digest: &Digest) -> (String, String) {
+    let count = digest.anomalies.len() + digest.dropped;
+    let plural = if count == 1 { "" } else { "s" };
+    let subject = format!("[Black Box] {} warning{} on {}", count, plural, host);
+    let mut body = format!("Black Box on {} recorded {} warning{} since the last digest:\n\n", host, count, plural);
+    for anomaly in &digest.anomalies {
+        body.push_str(&format_anomaly(anomaly));
+        body.push('\n');
+    }
+    if digest.dropped > 0 {
+        body.push_str(&format!("\n{} more weren't kept, see the recording for all of them.\n", digest.dropped));
+    }
+    (subject, body)
+}
+
+struct EmailSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailSender {
+    fn new(config: &EmailAlertConfig) -> Result<Self> {
+        let builder = if config.starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+                .with_context(|| format!("Failed to set up TLS for {}", config.smtp_host))?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+        };
+        let mut builder = builder.port(config.smtp_port).timeout(Some(SMTP_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        // Checked by EmailAlertConfig::validate
+        let from = config.from.parse().context("Invalid from address")?;
+        let to = config
+            .to
+            .iter()
+            .map(|address| address.parse().with_context(|| format!("Invalid to address {:?}", address)))
+            .collect::<Result<_>>()?;
+        Ok(Self { transport: builder.build(), from, to })
+    }
+
+    async fn send(&self, subject: &str, body: String) -> Result<()> {
+        let mut message = Message::builder().from(self.from.clone()).subject(subject).header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.body(body).context("Failed to build email")?;
+        // lettre's errors already include their cause
+        self.transport.send(message).await.map_err(|e| anyhow::anyhow!("Failed to send email: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Email anomalies from the broadcaster until it closes. A config reload sends new settings,
+/// or None to stop; warnings waiting for a digest are kept across the change.
+pub async fn run_email_alerts(
+    broadcaster: Arc<EventBroadcaster>,
+    mut settings: watch::Receiver<Option<EmailAlertConfig>>,
+    deliveries: DeliverySender,
+) {
+    let host = crate::syslog::local_hostname();
+    let mut rx = broadcaster.subscribe();
+    let mut batcher = AlertBatcher::new(Duration::ZERO, Instant::now());
+    let mut sender = configure(settings.borrow_and_update().as_ref(), &mut batcher, &deliveries);
+    let mut digest_check = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            received = rx.recv() => match received {
+                Ok(Event::Anomaly(anomaly)) => {
+                    let Some(sender) = &sender else {
+                        continue;
+                    };
+                    let Some(critical) = batcher.add(anomaly, Instant::now()) else {
+                        continue;
+                    };
+                    let (subject, body) = critical_email(&host, &critical);
+                    let result = sender.send(&subject, body).await;
+                    if result.is_err() {
+                        // Retried with the next digest
+                        batcher.requeue(vec![critical], 0);
+                    }
+                    report(&deliveries, result);
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("⚠ Email alerts fell behind, {} events skipped", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = digest_check.tick() => {
+                let Some(sender) = &sender else {
+                    continue;
+                };
+                let Some(digest) = batcher.take_digest(Instant::now()) else {
+                    continue;
+                };
+                let (subject, body) = digest_email(&host, &digest);
+                let result = sender.send(&subject, body).await;
+                if result.is_err() {
+                    batcher.requeue(digest.anomalies, digest.dropped);
+                }
+                report(&deliveries, result);
+            }
+            changed = settings.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                sender = configure(settings.borrow_and_update().as_ref(), &mut batcher, &deliveries);
+            }
+        }
+    }
+}
+
+// Sender for the settings, None when alerts are off or can't be set up. Pending warnings are
+// dropped when alerts are turned off.
+fn configure(
+    config: Option<&EmailAlertConfig>,
+    batcher: &mut AlertBatcher,
+    deliveries: &DeliverySender,
+) -> Option<EmailSender> {
+    let Some(config) = config else {
+        batcher.pending.clear();
+        batcher.dropped = 0;
+        return None;
+    };
+    let sender = match EmailSender::new(config) {
+        Ok(sender) => sender,
+        Err(e) => {
+            eprintln!("⚠ Email alerts disabled: {:#}", e);
+            let _ = deliveries.send(Err(format!("{:#}", e)));
+            return None;
+        }
+    };
+    println!(
+        "✓ Email alerts enabled: {} via {}:{}",
+        config.to.join(", "),
+        config.smtp_host,
+        config.smtp_port
+    );
+    batcher.interval = Duration::from_secs(config.digest_interval_minutes * 60);
+    Some(sender)
+}
+
+fn report(deliveries: &DeliverySender, result: Result<()>) {
+    if let Err(e) = &result {
+        eprintln!("⚠ Email alert not delivered: {:#}", e);
+    }
+    let _ = deliveries.send(result.map_err(|e| format!("{:#}", e)));
+}
+
+/// Send one message to check the settings, for `config setup-email --test`
+pub async fn send_test_email(config: &EmailAlertConfig) -> Result<()> {
+    let sender = EmailSender::new(config)?;
+    let host = crate::syslog::local_hostname();
+    let body = format!(
+        "Black Box on {} can send email alerts. Critical anomalies are sent as they happen, \
+         warnings in a digest at most every {} minutes.\n",
+        host, config.digest_interval_minutes
+    );
+    sender.send(&format!("[Black Box] Test alert from {}", host), body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::AnomalyKind;
+    use time::OffsetDateTime;
+
+    fn anomaly(severity: AnomalySeverity, message: &str) -> Anomaly {
+        Anomaly {
+            ts: OffsetDateTime::from_unix_timestamp(1_760_608_800).unwrap(),
+            severity,
+            kind: AnomalyKind::DiskFull,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_critical_sent_now_and_warnings_batched() {
+        let start = Instant::now();
+        let mut batcher = AlertBatcher::new(Duration::from_secs(900), start);
+        let critical = batcher.add(anomaly(AnomalySeverity::Critical, "Disk usage on /: 97.0% (1.2 GB free)"), start);
+        assert!(critical.is_some());
+        assert!(batcher.add(anomaly(AnomalySeverity::Warning, "first"), start).is_none());
+        assert!(batcher.add(anomaly(AnomalySeverity::Info, "ignored"), start).is_none());
+        // The same Critical again so soon is only in the digest
+        let repeat = anomaly(AnomalySeverity::Critical, "Disk usage on /: 98.0% (0.8 GB free)");
+        assert!(batcher.add(repeat, start + Duration::from_secs(1)).is_none());
+        assert!(batcher.add(anomaly(AnomalySeverity::Warning, "second"), start).is_none());
+
+        // Not before the interval is up, and then everything queued at once
+        assert!(batcher.take_digest(start + Duration::from_secs(600)).is_none());
+        let digest = batcher.take_digest(start + Duration::from_secs(900)).unwrap();
+        let messages: Vec<_> = digest.anomalies.iter().map(|a| a.message.as_str()).collect();
+        assert_eq!(messages, ["first", "Disk usage on /: 98.0% (0.8 GB free)", "second"]);
+        let repeat = anomaly(AnomalySeverity::Critical, "Disk usage on /: 99.0% (0.4 GB free)");
+        assert!(batcher.add(repeat, start + Duration::from_secs(900)).is_some());
+
+        // The next one waits a full interval from that digest
+        batcher.add(anomaly(AnomalySeverity::Warning, "third"), start);
+        assert!(batcher.take_digest(start + Duration::from_secs(1200)).is_none());
+        assert!(batcher.take_digest(start + Duration::from_secs(1800)).is_some());
+        assert!(batcher.take_digest(start + Duration::from_secs(3600)).is_none());
+
+        let (subject, body) = critical_email("web-01", &critical.unwrap());
+        assert_eq!(subject, "[Black Box] Critical on web-01: Disk usage on /: 97.0% (1.2 GB free)");
+        assert!(body.contains("2025-10-16T10:00:00Z  DiskFull  Disk usage on /: 97.0% (1.2 GB free)"), "{}", body);
+    }
+
+    #[test]
+    fn test_failed_digest_requeued_and_overflow_counted() {
+        let start = Instant::now();
+        let mut batcher = AlertBatcher::new(Duration::from_secs(60), start);
+        for i in 0..MAX_PENDING + 3 {
+            batcher.add(anomaly(AnomalySeverity::Warning, &format!("warning {}", i)), start);
+        }
+        let digest = batcher.take_digest(start + Duration::from_secs(60)).unwrap();
+        assert_eq!(digest.anomalies.len(), MAX_PENDING);
+        assert_eq!(digest.dropped, 3);
+
+        // Sending failed: it goes out with the next digest, ahead of anything newer
+        batcher.add(anomaly(AnomalySeverity::Warning, "newer"), start);
+        batcher.requeue(digest.anomalies, digest.dropped);
+        let digest = batcher.take_digest(start + Duration::from_secs(120)).unwrap();
+        assert_eq!(digest.anomalies[0].message, "warning 0");
+        assert_eq!(digest.anomalies.len(), MAX_PENDING);
+        assert_eq!(digest.dropped, 4);
+
+        let (subject, body) = digest_email("web-01", &digest);
+        assert_eq!(subject, format!("[Black Box] {} warnings on web-01", MAX_PENDING + 4));
+        assert!(body.ends_with("\n4 more weren't kept, see the recording for all of them.\n"), "{}", body);
+    }
+}
@@ -0,0 +1,24 @@
+// Notifications sent out as anomalies are recorded, configured under [alerts]
+mod email;
+mod webhook;
+
+pub use email::{run_email_alerts, send_test_email};
+pub use webhook::{redact, run_webhook_alerts, send_test_webhooks};
+
+use time::format_description::well_known::Rfc3339;
+
+use crate::event::Anomaly;
+
+// Result of each delivery attempt, drained by the main loop into collector health so a
+// failing channel shows up like any other failing collector
+pub type DeliverySender = crossbeam_channel::Sender<Result<(), String>>;
+
+// One line per anomaly: "2026-10-16T10:00:00Z  DiskFull  Disk usage on /: 95.1% (2 GB free)"
+fn format_anomaly(anomaly: &Anomaly) -> String {
+    format!(
+        "{}  {:?}  {}",
+        anomaly.ts.format(&Rfc3339).unwrap_or_else(|_| anomaly.ts.to_string()),
+        anomaly.kind,
+        anomaly.message
+    )
+}
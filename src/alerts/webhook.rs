@@ -0,0 +1,310 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+
+use super::DeliverySender;
+use crate::broadcast::EventBroadcaster;
+use crate::config::{AlertSeverity, WebhookAlertConfig};
+use crate::event::{AnomalySeverity, Event};
+use crate::syslog;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Tries per webhook, waiting 1s, 2s then 4s between them
+const MAX_ATTEMPTS: u32 = 4;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// An anomaly or security event as it's sent
+#[derive(Debug, Clone)]
+struct Alert {
+    ts: OffsetDateTime,
+    event_type: &'static str,
+    kind: String,
+    severity: AlertSeverity,
+    message: String,
+}
+
+impl Alert {
+    fn from_event(event: &Event) -> Option<Self> {
+        let (kind, severity, message) = match event {
+            Event::Anomaly(a) => {
+                let severity = match a.severity {
+                    AnomalySeverity::Critical => AlertSeverity::Critical,
+                    AnomalySeverity::Warning => AlertSeverity::Warning,
+                    AnomalySeverity::Info => AlertSeverity::Info,
+                };
+                (format!("{:?}", a.kind), severity, a.message.clone())
+            }
+            // Security events have no severity of their own, use the one they're sent to syslog with
+            Event::SecurityEvent(s) => {
+                let severity = match syslog::severity(event) {
+                    level if level <= syslog::SEVERITY_CRITICAL => AlertSeverity::Critical,
+                    level if level <= syslog::SEVERITY_WARNING => AlertSeverity::Warning,
+                    _ => AlertSeverity::Info,
+                };
+                (format!("{:?}", s.kind), severity, s.message.clone())
+            }
+            _ => return None,
+        };
+        Some(Self { ts: event.timestamp(), event_type: syslog::event_type(event), kind, severity, message })
+    }
+
+    // Slack shows "text", other receivers can use the fields
+    fn payload(&self, host: &str) -> serde_json::Value {
+        serde_json::json!({
+            "text": format!("[Black Box] {} on {}: {}", severity_label(self.severity), host, self.message),
+            "host": host,
+            "type": self.event_type,
+            "kind": self.kind,
+            "severity": severity_label(self.severity).to_lowercase(),
+            "timestamp": self.ts.format(&Rfc3339).unwrap_or_else(|_| self.ts.to_string()),
+            "message": self.message,
+        })
+    }
+}
+
+fn severity_label(severity: AlertSeverity) -> &'static str {
+    match severity {
+        AlertSeverity::Critical => "Critical",
+        AlertSeverity::Warning => "Warning",
+        AlertSeverity::Info => "Info",
+    }
+}
+
+// Which alerts are sent: those at least min_severity or of a listed kind, each kind at most
+// once per cooldown so a brute force attempt logged on every failure is one message
+struct WebhookFilter {
+    min_severity: AlertSeverity,
+    kinds: Vec<String>,
+    cooldown: Duration,
+    last_sent: HashMap<String, Instant>,
+}
+
+impl WebhookFilter {
+    fn new(config: &WebhookAlertConfig) -> Self {
+        Self {
+            min_severity: config.min_severity,
+            kinds: config.kinds.clone(),
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    fn should_send(&mut self, alert: &Alert, now: Instant) -> bool {
+        let wanted = alert.severity >= self.min_severity || self.kinds.iter().any(|kind| kind.eq_ignore_ascii_case(&alert.kind));
+        if !wanted {
+            return false;
+        }
+        if self.last_sent.get(&alert.kind).is_some_and(|sent| now.duration_since(*sent) < self.cooldown) {
+            return false;
+        }
+        self.last_sent.insert(alert.kind.clone(), now);
+        true
+    }
+}
+
+/// Scheme and host only, the path of a Slack webhook is its secret
+pub fn redact(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => format!("{}://{}", scheme, rest.split('/').next().unwrap_or_default()),
+        None => "webhook".to_string(),
+    }
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().context("Failed to create HTTP client")
+}
+
+// POST the payload, retrying with backoff while the receiver is unreachable, rate limiting
+// or failing. Any other refusal won't change on a retry.
+async fn post(client: &reqwest::Client, url: &str, payload: &serde_json::Value, attempts: u32) -> Result<()> {
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        let error = match client.post(url).json(payload).send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                let error = anyhow::anyhow!("{} answered {}", redact(url), status);
+                if !status.is_server_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    return Err(error);
+                }
+                error
+            }
+            // reqwest's errors include the full URL
+            Err(e) => anyhow::anyhow!("Failed to reach {}: {}", redact(url), e.without_url()),
+        };
+        if attempt >= attempts {
+            return Err(if attempts > 1 { error.context(format!("Gave up after {} attempts", attempt)) } else { error });
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// POST matching anomalies and security events to every webhook until the broadcaster closes.
+/// A config reload sends new settings, or None to stop.
+pub async fn run_webhook_alerts(
+    broadcaster: Arc<EventBroadcaster>,
+    mut settings: watch::Receiver<Option<WebhookAlertConfig>>,
+    deliveries: DeliverySender,
+) {
+    let client = match http_client() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("⚠ Webhook alerts disabled: {:#}", e);
+            return;
+        }
+    };
+    let host = syslog::local_hostname();
+    let mut rx = broadcaster.subscribe();
+    let mut config = configure(settings.borrow_and_update().clone());
+
+    loop {
+        tokio::select! {
+            received = rx.recv() => match received {
+                Ok(event) => {
+                    let Some((webhook, filter)) = &mut config else {
+                        continue;
+                    };
+                    let Some(alert) = Alert::from_event(&event) else {
+                        continue;
+                    };
+                    if !filter.should_send(&alert, Instant::now()) {
+                        continue;
+                    }
+                    // Each delivery retries on its own so a slow receiver doesn't hold up the stream
+                    let payload = alert.payload(&host);
+                    for url in &webhook.urls {
+                        let (client, url, payload, deliveries) = (client.clone(), url.clone(), payload.clone(), deliveries.clone());
+                        tokio::spawn(async move {
+                            let result = post(&client, &url, &payload, MAX_ATTEMPTS).await;
+                            if let Err(e) = &result {
+                                eprintln!("⚠ Webhook alert not delivered: {:#}", e);
+                            }
+                            let _ = deliveries.send(result.map_err(|e| format!("{:#}", e)));
+                        });
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("⚠ Webhook alerts fell behind, {} events skipped", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            changed = settings.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                config = configure(settings.borrow_and_update().clone());
+            }
+        }
+    }
+}
+
+fn configure(config: Option<WebhookAlertConfig>) -> Option<(WebhookAlertConfig, WebhookFilter)> {
+    let config = config?;
+    let hosts: Vec<String> = config.urls.iter().map(|url| redact(url)).collect();
+    println!(
+        "✓ Webhook alerts enabled: {} ({} and above{})",
+        hosts.join(", "),
+        severity_label(config.min_severity).to_lowercase(),
+        if config.kinds.is_empty() { String::new() } else { format!(", plus {}", config.kinds.join(", ")) }
+    );
+    let filter = WebhookFilter::new(&config);
+    Some((config, filter))
+}
+
+/// Send a synthetic Critical anomaly to each webhook once, for `alerts test`. The result for
+/// each URL is returned in order.
+pub async fn send_test_webhooks(config: &WebhookAlertConfig) -> Result<Vec<(String, Result<()>)>> {
+    let client = http_client()?;
+    let host = syslog::local_hostname();
+    let alert = Alert {
+        ts: OffsetDateTime::now_utc(),
+        event_type: "Anomaly",
+        kind: "Test".to_string(),
+        severity: AlertSeverity::Critical,
+        message: "Test alert, webhook alerts are working".to_string(),
+    };
+    let payload = alert.payload(&host);
+    let mut results = Vec::new();
+    for url in &config.urls {
+        results.push((redact(url), post(&client, url, &payload, 1).await));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Anomaly, AnomalyKind, SecurityEvent, SecurityEventKind};
+
+    fn config(min_severity: AlertSeverity, kinds: &[&str]) -> WebhookAlertConfig {
+        WebhookAlertConfig {
+            enabled: true,
+            urls: vec!["https://hooks.example.com/services/T000/B000/secret".to_string()],
+            min_severity,
+            kinds: kinds.iter().map(|kind| kind.to_string()).collect(),
+            cooldown_secs: 300,
+        }
+    }
+
+    fn anomaly(severity: AnomalySeverity, kind: AnomalyKind, message: &str) -> Event {
+        Event::Anomaly(Anomaly {
+            ts: OffsetDateTime::from_unix_timestamp(1_760_608_800).unwrap(),
+            severity,
+            kind,
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_severity_or_listed_kind_sent_once_per_cooldown() {
+        let start = Instant::now();
+        let mut filter = WebhookFilter::new(&config(AlertSeverity::Critical, &["BruteForceAttempt", "SudoersModified"]));
+        let mut send = |event: &Event, at: u64| {
+            let alert = Alert::from_event(event).unwrap();
+            filter.should_send(&alert, start + Duration::from_secs(at))
+        };
+
+        let disk_full = anomaly(AnomalySeverity::Critical, AnomalyKind::DiskFull, "Disk usage on /: 97.0%");
+        let brute_force = anomaly(AnomalySeverity::Warning, AnomalyKind::BruteForceAttempt, "Brute force attempt from 10.0.0.5: 5 failures");
+        assert!(send(&disk_full, 0));
+        assert!(!send(&anomaly(AnomalySeverity::Warning, AnomalyKind::CpuSpike, "CPU spike started"), 0));
+        assert!(send(&brute_force, 0));
+
+        // Another failure from the same attempt is held back until the cooldown is over
+        assert!(!send(&brute_force, 60));
+        assert!(!send(&disk_full, 299));
+        assert!(send(&brute_force, 300));
+
+        let sudoers = Event::SecurityEvent(SecurityEvent {
+            ts: OffsetDateTime::from_unix_timestamp(1_760_608_800).unwrap(),
+            kind: SecurityEventKind::SudoersModified,
+            user: "root".to_string(),
+            source_ip: None,
+            message: "/etc/sudoers modified".to_string(),
+        });
+        assert!(send(&sudoers, 0));
+        assert!(Alert::from_event(&Event::SystemMetrics(crate::event::sample_metrics())).is_none());
+    }
+
+    #[test]
+    fn test_payload_and_redacted_url() {
+        let event = anomaly(AnomalySeverity::Critical, AnomalyKind::DiskFull, "Disk usage on /: 97.0% (1.2 GB free)");
+        let payload = Alert::from_event(&event).unwrap().payload("web-01");
+        assert_eq!(payload["text"], "[Black Box] Critical on web-01: Disk usage on /: 97.0% (1.2 GB free)");
+        assert_eq!(payload["type"], "Anomaly");
+        assert_eq!(payload["kind"], "DiskFull");
+        assert_eq!(payload["severity"], "critical");
+        assert_eq!(payload["timestamp"], "2025-10-16T10:00:00Z");
+
+        assert_eq!(redact("https://hooks.slack.com/services/T000/B000/secret"), "https://hooks.slack.com");
+        assert_eq!(redact("http://localhost:9000"), "http://localhost:9000");
+    }
+}
@@ -0,0 +1,879 @@
+use time::OffsetDateTime;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::collector::{FilesystemStats, ProcessFdUsage};
+use crate::config::AnomalyConfig;
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, FilesystemStatus, NetworkTraffic, PressureMetrics, ProcessInfo};
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+// Throttling only counts while the CPU is busy (idle cores clock down on purpose),
+// and has to last this many consecutive samples before it's reported
+const THROTTLE_MIN_CPU_PERCENT: f32 = 50.0;
+const THROTTLE_SUSTAINED_SAMPLES: u32 = 10;
+
+// The TCP retransmit ratio is taken over this many samples, and only once enough
+// segments were sent in them for a ratio to mean something
+const RETRANSMIT_WINDOW_SAMPLES: usize = 30;
+const RETRANSMIT_MIN_SEGMENTS: u64 = 1000;
+
+// Full pressure has to stay over the threshold this many consecutive samples before it's
+// reported, since the 10 second average lags a stall that has already passed
+const PRESSURE_SUSTAINED_SAMPLES: u32 = 5;
+
+// A spike is only resolved once it has stayed under the hysteresis margin this many consecutive
+// samples, so a metric hovering around its threshold isn't reported over and over
+const RESOLVE_SUSTAINED_SAMPLES: u32 = 5;
+
+// Values sampled each tick that the threshold checks look at
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSample {
+    pub cpu_usage_percent: f32,
+    pub mem_usage_percent: f32,
+    pub swap_usage_percent: Option<f32>, // None when there is no swap
+    pub disk_write_bytes_per_sec: u64,
+    pub own_write_bytes_per_sec: u64, // Black Box's own writes, left out of the disk spike check
+    pub net_recv_bytes_per_sec: u64,
+    pub net_send_bytes_per_sec: u64,
+    pub net_recv_errors_per_sec: u64,
+    pub net_send_errors_per_sec: u64,
+    pub net_recv_drops_per_sec: u64,
+    pub net_send_drops_per_sec: u64,
+    pub context_switches_per_sec: u64,
+    pub cpu_freq_percent_of_max: Option<f32>, // None without cpufreq sysfs
+    pub tcp_out_segs_per_sec: u64,
+    pub tcp_retrans_per_sec: u64,
+    pub file_handles: Option<(u64, u64)>, // (allocated, max), None without /proc/sys/fs/file-nr
+    pub remote_traffic: Option<NetworkTraffic>, // Latest traffic accounting interval, when enabled
+    pub pressure: Option<PressureMetrics>, // None without PSI
+}
+
+// Threshold-based anomaly detection for system metrics
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    throttled_samples: u32,
+    retransmits: VecDeque<(u64, u64)>, // (retransmitted, sent) segments per sample
+    full_mounts: HashSet<(String, AnomalyKind)>, // Over the DiskFull or InodeExhaustion threshold
+    file_handles_full: bool,
+    fd_limited_pids: HashSet<u32>, // Over the process_fds threshold
+    reported_traffic_at: Option<OffsetDateTime>, // Accounting interval last recorded with a spike
+    spike_traffic: Option<NetworkTraffic>,
+    stalled_samples: [u32; 3], // Consecutive samples over pressure_stall, per PressureMetrics::resources
+    spikes: HashMap<Spike, SpikeState>, // Started and not yet resolved
+}
+
+// Per-sample threshold conditions, reported when they start, every ongoing_interval_secs while
+// they last, and when they resolve, rather than on every sample they are over for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Spike {
+    Cpu,
+    Memory,
+    Swap,
+    DiskWrite,
+    Network,
+    ContextSwitch,
+    NetworkErrors,
+    NetworkDrops,
+}
+
+impl Spike {
+    fn label(self) -> &'static str {
+        match self {
+            Spike::Cpu => "CPU spike",
+            Spike::Memory => "Memory spike",
+            Spike::Swap => "Swap usage",
+            Spike::DiskWrite => "Disk write spike",
+            Spike::Network => "Network spike",
+            Spike::ContextSwitch => "Context switch spike",
+            Spike::NetworkErrors => "Network errors",
+            Spike::NetworkDrops => "Network packet drops",
+        }
+    }
+
+    fn kind(self) -> AnomalyKind {
+        match self {
+            Spike::Cpu => AnomalyKind::CpuSpike,
+            Spike::Memory => AnomalyKind::MemorySpike,
+            Spike::Swap => AnomalyKind::SwapUsage,
+            Spike::DiskWrite => AnomalyKind::DiskSpike,
+            Spike::Network | Spike::NetworkErrors | Spike::NetworkDrops => AnomalyKind::NetworkSpike,
+            Spike::ContextSwitch => AnomalyKind::ContextSwitchSpike,
+        }
+    }
+
+    // The threshold in the unit the sample is in, None when switched off
+    fn limit(self, cfg: &AnomalyConfig) -> Option<f64> {
+        let (entry, scale) = match self {
+            Spike::Cpu => (&cfg.cpu_spike, 1.0),
+            Spike::Memory => (&cfg.mem_spike, 1.0),
+            Spike::Swap => (&cfg.swap_usage, 1.0),
+            Spike::DiskWrite => (&cfg.disk_spike, BYTES_PER_MB),
+            Spike::Network => (&cfg.network_spike, BYTES_PER_MB),
+            Spike::ContextSwitch => (&cfg.context_switch_spike, 1.0),
+            Spike::NetworkErrors => (&cfg.network_errors, 1.0),
+            Spike::NetworkDrops => (&cfg.network_drops, 1.0),
+        };
+        entry.enabled.then_some(entry.threshold * scale)
+    }
+
+    fn severity(self) -> AnomalySeverity {
+        match self {
+            Spike::Memory => AnomalySeverity::Critical,
+            _ => AnomalySeverity::Warning,
+        }
+    }
+}
+
+struct SpikeState {
+    started: OffsetDateTime,
+    last_reported: OffsetDateTime,
+    clear_since: Option<(OffsetDateTime, u32)>, // First sample back under the margin, and how many since
+}
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config,
+            throttled_samples: 0,
+            retransmits: VecDeque::with_capacity(RETRANSMIT_WINDOW_SAMPLES),
+            full_mounts: HashSet::new(),
+            file_handles_full: false,
+            fd_limited_pids: HashSet::new(),
+            reported_traffic_at: None,
+            spike_traffic: None,
+            stalled_samples: [0; 3],
+            spikes: HashMap::new(),
+        }
+    }
+
+    /// Swap in thresholds from a config reload, keeping what has already been reported
+    pub fn set_config(&mut self, config: AnomalyConfig) {
+        self.config = config;
+    }
+
+    pub fn check(&mut self, sample: &MetricsSample) -> Vec<Anomaly> {
+        self.check_at(sample, OffsetDateTime::now_utc())
+    }
+
+    fn check_at(&mut self, sample: &MetricsSample, now: OffsetDateTime) -> Vec<Anomaly> {
+        let cfg = &self.config;
+        let spikes = &mut self.spikes;
+        let mut anomalies = Vec::new();
+
+        anomalies.extend(track_spike(spikes, cfg, now, Spike::Cpu, sample.cpu_usage_percent as f64, || {
+            format!("{:.1}%", sample.cpu_usage_percent)
+        }));
+
+        anomalies.extend(track_spike(spikes, cfg, now, Spike::Memory, sample.mem_usage_percent as f64, || {
+            format!("{:.1}%", sample.mem_usage_percent)
+        }));
+
+        // No swap counts as none in use, which resolves a spike if swap is switched off
+        let swap_usage_percent = sample.swap_usage_percent.unwrap_or(0.0);
+        anomalies.extend(track_spike(spikes, cfg, now, Spike::Swap, swap_usage_percent as f64, || {
+            format!("{:.1}%", swap_usage_percent)
+        }));
+
+        // Device counters include our own segment writes, which shouldn't set off a spike
+        let disk_write = sample.disk_write_bytes_per_sec.saturating_sub(sample.own_write_bytes_per_sec);
+        anomalies.extend(track_spike(spikes, cfg, now, Spike::DiskWrite, disk_write as f64, || {
+            let mut message = format!("{}/s", crate::format_bytes(disk_write));
+            if sample.own_write_bytes_per_sec > 0 {
+                message.push_str(&format!(
+                    " (not counting {}/s written by Black Box)",
+                    crate::format_bytes(sample.own_write_bytes_per_sec)
+                ));
+            }
+            message
+        }));
+
+        let network_rate = sample.net_send_bytes_per_sec.max(sample.net_recv_bytes_per_sec);
+        let network_spike = track_spike(spikes, cfg, now, Spike::Network, network_rate as f64, || {
+            let mut message = format!(
+                "RX={}/s TX={}/s",
+                crate::format_bytes(sample.net_recv_bytes_per_sec),
+                crate::format_bytes(sample.net_send_bytes_per_sec)
+            );
+            if let Some(traffic) = &sample.remote_traffic
+                && !traffic.top_remotes.is_empty()
+            {
+                message.push_str(&format!(" (top remotes over {}s: {})", traffic.interval_secs, crate::traffic::summarize(traffic)));
+            }
+            message
+        });
+        if let Some(spike) = network_spike {
+            // The breakdown is recorded with the spike being reported, once per accounting interval
+            if spike.severity != AnomalySeverity::Info
+                && let Some(traffic) = &sample.remote_traffic
+                && self.reported_traffic_at != Some(traffic.ts)
+            {
+                self.reported_traffic_at = Some(traffic.ts);
+                self.spike_traffic = Some(NetworkTraffic { ts: spike.ts, ..traffic.clone() });
+            }
+            anomalies.push(spike);
+        }
+
+        anomalies.extend(track_spike(
+            spikes,
+            cfg,
+            now,
+            Spike::ContextSwitch,
+            sample.context_switches_per_sec as f64,
+            || format!("{}/s", sample.context_switches_per_sec),
+        ));
+
+        // Network errors/drops detection
+        let errors = sample.net_recv_errors_per_sec.max(sample.net_send_errors_per_sec);
+        anomalies.extend(track_spike(spikes, cfg, now, Spike::NetworkErrors, errors as f64, || {
+            format!("RX={}/s TX={}/s", sample.net_recv_errors_per_sec, sample.net_send_errors_per_sec)
+        }));
+
+        let drops = sample.net_recv_drops_per_sec.max(sample.net_send_drops_per_sec);
+        anomalies.extend(track_spike(spikes, cfg, now, Spike::NetworkDrops, drops as f64, || {
+            format!("RX={}/s TX={}/s", sample.net_recv_drops_per_sec, sample.net_send_drops_per_sec)
+        }));
+
+        if let Some(freq_percent) = sample.cpu_freq_percent_of_max
+            && cfg.cpu_throttling.enabled
+            && sample.cpu_usage_percent >= THROTTLE_MIN_CPU_PERCENT
+            && (freq_percent as f64) < cfg.cpu_throttling.threshold
+        {
+            self.throttled_samples += 1;
+            // Report once per sustained period rather than every sample
+            if self.throttled_samples >= THROTTLE_SUSTAINED_SAMPLES {
+                self.throttled_samples = 0;
+                anomalies.push(anomaly(
+                    AnomalySeverity::Warning,
+                    AnomalyKind::CpuThrottling,
+                    format!(
+                        "CPU throttling: running at {:.0}% of max frequency with {:.1}% CPU usage",
+                        freq_percent, sample.cpu_usage_percent
+                    ),
+                ));
+            }
+        } else {
+            self.throttled_samples = 0;
+        }
+
+        if self.retransmits.len() == RETRANSMIT_WINDOW_SAMPLES {
+            self.retransmits.pop_front();
+        }
+        self.retransmits.push_back((sample.tcp_retrans_per_sec, sample.tcp_out_segs_per_sec));
+        let (retransmitted, sent) = self
+            .retransmits
+            .iter()
+            .fold((0, 0), |(r, s), &(retransmitted, sent)| (r + retransmitted, s + sent));
+        if cfg.tcp_retransmits.enabled
+            && self.retransmits.len() == RETRANSMIT_WINDOW_SAMPLES
+            && sent >= RETRANSMIT_MIN_SEGMENTS
+            && retransmitted as f64 / sent as f64 * 100.0 > cfg.tcp_retransmits.threshold
+        {
+            // Start a fresh window so a sustained problem is reported once per window
+            self.retransmits.clear();
+            anomalies.push(anomaly(
+                AnomalySeverity::Warning,
+                AnomalyKind::TcpRetransmits,
+                format!(
+                    "TCP retransmits: {:.1}% of segments sent over {}s ({}/s)",
+                    retransmitted as f64 / sent as f64 * 100.0,
+                    RETRANSMIT_WINDOW_SAMPLES,
+                    retransmitted / RETRANSMIT_WINDOW_SAMPLES as u64
+                ),
+            ));
+        }
+
+        // Reported when it goes over, and not again until it has dropped back under
+        let handles_percent = sample
+            .file_handles
+            .filter(|&(_, max)| max > 0)
+            .map(|(allocated, max)| allocated as f64 / max as f64 * 100.0);
+        let handles_full = cfg.file_handles.enabled && handles_percent.is_some_and(|percent| percent > cfg.file_handles.threshold);
+        if let Some((percent, (allocated, max))) = handles_percent.zip(sample.file_handles)
+            && handles_full
+            && !self.file_handles_full
+        {
+            anomalies.push(anomaly(
+                AnomalySeverity::Warning,
+                AnomalyKind::FileHandleExhaustion,
+                format!("File handles: {:.1}% of fs.file-max in use ({} of {})", percent, allocated, max),
+            ));
+        }
+        self.file_handles_full = handles_full;
+
+        // Reported once a stall has lasted, and not again until it has cleared
+        let resources = sample.pressure.as_ref().map(PressureMetrics::resources);
+        for (i, stalled_samples) in self.stalled_samples.iter_mut().enumerate() {
+            let stall = resources.as_ref().and_then(|resources| {
+                let (resource, some, full) = resources[i];
+                full.filter(|&full| cfg.pressure_stall.enabled && full as f64 > cfg.pressure_stall.threshold)
+                    .map(|full| (resource, some, full))
+            });
+            let Some((resource, some, full)) = stall else {
+                *stalled_samples = 0;
+                continue;
+            };
+            *stalled_samples += 1;
+            if *stalled_samples == PRESSURE_SUSTAINED_SAMPLES {
+                anomalies.push(anomaly(
+                    AnomalySeverity::Warning,
+                    AnomalyKind::PressureStall,
+                    format!(
+                        "{} pressure stall: every task stalled {:.1}% of the last 10s, some {:.1}%",
+                        resource, full, some
+                    ),
+                ));
+            }
+        }
+
+        anomalies
+    }
+
+    // Bytes and inodes used on every mount against its threshold. A mount is reported when it
+    // goes over, and not again until it has dropped back under. Also returns each mount's status.
+    pub fn check_filesystems(&mut self, filesystems: &[FilesystemStats]) -> (Vec<Anomaly>, Vec<FilesystemStatus>) {
+        let cfg = &self.config;
+        let mut anomalies = Vec::new();
+        let mut statuses = Vec::with_capacity(filesystems.len());
+        let mut full_mounts = HashSet::new();
+
+        for fs in filesystems {
+            let usage_percent = fs.usage_percent();
+            let threshold = cfg.disk_full_threshold(&fs.mount_point);
+            let bytes_full = cfg.disk_full.enabled && usage_percent as f64 > threshold;
+            if bytes_full && !self.full_mounts.contains(&(fs.mount_point.clone(), AnomalyKind::DiskFull)) {
+                anomalies.push(anomaly(
+                    AnomalySeverity::Critical,
+                    AnomalyKind::DiskFull,
+                    format!(
+                        "Disk usage on {}: {:.1}% ({} free)",
+                        fs.mount_point,
+                        usage_percent,
+                        crate::format_bytes(fs.available_bytes)
+                    ),
+                ));
+            }
+
+            let inode_percent = fs.inode_usage_percent();
+            let inodes_full = cfg.inode_usage.enabled
+                && inode_percent.is_some_and(|percent| percent as f64 > cfg.inode_usage.threshold);
+            if let Some(inode_percent) = inode_percent
+                && inodes_full
+                && !self.full_mounts.contains(&(fs.mount_point.clone(), AnomalyKind::InodeExhaustion))
+            {
+                anomalies.push(anomaly(
+                    AnomalySeverity::Critical,
+                    AnomalyKind::InodeExhaustion,
+                    format!(
+                        "Inode usage on {}: {:.1}% ({} of {} free)",
+                        fs.mount_point, inode_percent, fs.inodes_free, fs.inodes_total
+                    ),
+                ));
+            }
+
+            if bytes_full {
+                full_mounts.insert((fs.mount_point.clone(), AnomalyKind::DiskFull));
+            }
+            if inodes_full {
+                full_mounts.insert((fs.mount_point.clone(), AnomalyKind::InodeExhaustion));
+            }
+            statuses.push(FilesystemStatus {
+                mount_point: fs.mount_point.clone(),
+                inodes_total: fs.inodes_total,
+                inodes_free: fs.inodes_free,
+                critical: bytes_full || inodes_full,
+            });
+        }
+
+        self.full_mounts = full_mounts;
+        (anomalies, statuses)
+    }
+
+    /// Where the traffic went for the latest network spike, to be recorded alongside it
+    pub fn take_spike_traffic(&mut self) -> Option<NetworkTraffic> {
+        self.spike_traffic.take()
+    }
+
+    // Names each process over the process_fds share of its open file limit. A process is reported
+    // when it goes over, and not again until it has dropped back under.
+    pub fn check_process_fds(&mut self, usage: &[ProcessFdUsage]) -> Vec<Anomaly> {
+        let cfg = &self.config.process_fds;
+        let mut anomalies = Vec::new();
+        let mut fd_limited_pids = HashSet::new();
+
+        for process in usage {
+            let percent = process.usage_percent();
+            if !cfg.enabled || percent as f64 <= cfg.threshold {
+                continue;
+            }
+            if !self.fd_limited_pids.contains(&process.pid) {
+                anomalies.push(anomaly(
+                    AnomalySeverity::Warning,
+                    AnomalyKind::FdExhaustion,
+                    format!(
+                        "Process {} (pid {}) has {} of its {} file descriptors open ({:.1}%)",
+                        process.name, process.pid, process.open_fds, process.limit, percent
+                    ),
+                ));
+            }
+            fd_limited_pids.insert(process.pid);
+        }
+
+        self.fd_limited_pids = fd_limited_pids;
+        anomalies
+    }
+
+    // Names the process responsible when the disk write rate, summed over every process
+    // since the last process snapshot, is over the disk spike threshold
+    pub fn check_disk_writer(&self, total_write_bytes_per_sec: u64, top_writer: Option<&ProcessInfo>) -> Option<Anomaly> {
+        let cfg = &self.config.disk_spike;
+        if !cfg.enabled || (total_write_bytes_per_sec as f64) <= cfg.threshold * BYTES_PER_MB {
+            return None;
+        }
+        let writer = top_writer?;
+
+        Some(anomaly(
+            AnomalySeverity::Warning,
+            AnomalyKind::ProcessDiskWrite,
+            format!(
+                "Disk writes {}/s, top writer {} (pid {}) at {}/s",
+                crate::format_bytes(total_write_bytes_per_sec),
+                writer.name,
+                writer.pid,
+                crate::format_bytes(writer.write_bytes_per_sec)
+            ),
+        ))
+    }
+}
+
+// Reports `spike` when `value` goes over its threshold, again every ongoing_interval_secs while it
+// stays over, and once it has been back under the hysteresis margin for RESOLVE_SUSTAINED_SAMPLES
+fn track_spike(
+    spikes: &mut HashMap<Spike, SpikeState>,
+    cfg: &AnomalyConfig,
+    now: OffsetDateTime,
+    spike: Spike,
+    value: f64,
+    detail: impl FnOnce() -> String,
+) -> Option<Anomaly> {
+    let Some(limit) = spike.limit(cfg) else {
+        spikes.remove(&spike);
+        return None;
+    };
+
+    if value > limit {
+        let Some(state) = spikes.get_mut(&spike) else {
+            spikes.insert(spike, SpikeState { started: now, last_reported: now, clear_since: None });
+            return Some(anomaly(spike.severity(), spike.kind(), format!("{} started: {}", spike.label(), detail())));
+        };
+        state.clear_since = None;
+        let interval = cfg.ongoing_interval_secs as i64;
+        if interval == 0 || (now - state.last_reported).whole_seconds() < interval {
+            return None;
+        }
+        state.last_reported = now;
+        let message = format!("{} ongoing for {}: {}", spike.label(), format_duration(now - state.started), detail());
+        return Some(anomaly(spike.severity(), spike.kind(), message));
+    }
+
+    let state = spikes.get_mut(&spike)?;
+    if value > limit * (1.0 - cfg.resolve_hysteresis_percent / 100.0) {
+        state.clear_since = None;
+        return None;
+    }
+    let (cleared, samples) = state.clear_since.get_or_insert((now, 0));
+    *samples += 1;
+    if *samples < RESOLVE_SUSTAINED_SAMPLES {
+        return None;
+    }
+    let message = format!("{} resolved after {}", spike.label(), format_duration(*cleared - state.started));
+    spikes.remove(&spike);
+    Some(anomaly(AnomalySeverity::Info, spike.kind(), message))
+}
+
+// "4m12s", to the second
+fn format_duration(duration: time::Duration) -> String {
+    let seconds = duration.whole_seconds().max(0);
+    match (seconds / 3600, seconds % 3600 / 60, seconds % 60) {
+        (0, 0, secs) => format!("{}s", secs),
+        (0, mins, secs) => format!("{}m{}s", mins, secs),
+        (hours, mins, secs) => format!("{}h{}m{}s", hours, mins, secs),
+    }
+}
+
+fn anomaly(severity: AnomalySeverity, kind: AnomalyKind, message: String) -> Anomaly {
+    Anomaly {
+        ts: OffsetDateTime::now_utc(),
+        severity,
+        kind,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quiet_sample_has_no_anomalies() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let sample = MetricsSample {
+            cpu_usage_percent: 20.0,
+            mem_usage_percent: 40.0,
+            swap_usage_percent: Some(0.0),
+            ..Default::default()
+        };
+        assert!(detector.check(&sample).is_empty());
+    }
+
+    #[test]
+    fn test_cpu_spike_respects_threshold_and_enabled() {
+        let sample = MetricsSample {
+            cpu_usage_percent: 95.0,
+            ..Default::default()
+        };
+
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let anomalies = detector.check(&sample);
+        assert_eq!(anomalies.len(), 1);
+        assert!(matches!(anomalies[0].kind, AnomalyKind::CpuSpike));
+
+        let mut config = AnomalyConfig::default();
+        config.cpu_spike.threshold = 98.0;
+        assert!(AnomalyDetector::new(config).check(&sample).is_empty());
+
+        let mut config = AnomalyConfig::default();
+        config.cpu_spike.enabled = false;
+        assert!(AnomalyDetector::new(config).check(&sample).is_empty());
+    }
+
+    #[test]
+    fn test_disk_spike_leaves_out_own_writes() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let sample = |own_write_bytes_per_sec| MetricsSample {
+            disk_write_bytes_per_sec: 120 * 1024 * 1024,
+            own_write_bytes_per_sec,
+            ..Default::default()
+        };
+        assert!(detector.check(&sample(30 * 1024 * 1024)).is_empty());
+
+        let anomalies = detector.check(&sample(1024 * 1024));
+        assert_eq!(anomalies.len(), 1);
+        assert!(matches!(anomalies[0].kind, AnomalyKind::DiskSpike));
+        assert_eq!(anomalies[0].message, "Disk write spike started: 119.0MB/s (not counting 1.0MB/s written by Black Box)");
+    }
+
+    #[test]
+    fn test_network_spike_threshold_in_megabytes() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let below = MetricsSample {
+            net_recv_bytes_per_sec: 400 * 1024 * 1024,
+            ..Default::default()
+        };
+        assert!(detector.check(&below).is_empty());
+
+        let above = MetricsSample {
+            net_send_bytes_per_sec: 600 * 1024 * 1024,
+            ..Default::default()
+        };
+        let anomalies = detector.check(&above);
+        assert_eq!(anomalies.len(), 1);
+        assert!(matches!(anomalies[0].kind, AnomalyKind::NetworkSpike));
+    }
+
+    #[test]
+    fn test_network_spike_names_top_remotes_once_per_interval() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let traffic = NetworkTraffic {
+            ts: OffsetDateTime::now_utc(),
+            interval_secs: 10,
+            bytes_counted: true,
+            top_remotes: vec![crate::event::RemoteTraffic {
+                remote: "203.0.113.0/24".to_string(),
+                bytes_sent: 6 * 1024 * 1024 * 1024,
+                bytes_received: 0,
+                connections: 3,
+            }],
+        };
+        let spike = MetricsSample {
+            net_send_bytes_per_sec: 600 * 1024 * 1024,
+            remote_traffic: Some(traffic.clone()),
+            ..Default::default()
+        };
+
+        let anomalies = detector.check(&spike);
+        assert!(anomalies[0].message.ends_with("(top remotes over 10s: 203.0.113.0/24 6.0GB)"));
+        let recorded = detector.take_spike_traffic().unwrap();
+        assert_eq!(recorded.ts, anomalies[0].ts);
+        assert_eq!(recorded.top_remotes, traffic.top_remotes);
+
+        // Reported once while the spike lasts, and the same interval isn't recorded again
+        assert!(detector.check(&spike).is_empty());
+        assert!(detector.take_spike_traffic().is_none());
+    }
+
+    #[test]
+    fn test_spike_reported_at_start_while_ongoing_and_when_resolved() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig { ongoing_interval_secs: 60, ..Default::default() });
+        let start = OffsetDateTime::now_utc();
+        let at = |secs: i64| start + time::Duration::seconds(secs);
+        let cpu = |percent: f32| MetricsSample { cpu_usage_percent: percent, ..Default::default() };
+
+        let started = detector.check_at(&cpu(95.0), at(0));
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].message, "CPU spike started: 95.0%");
+        for secs in 1..60 {
+            assert!(detector.check_at(&cpu(97.0), at(secs)).is_empty());
+        }
+        let ongoing = detector.check_at(&cpu(97.0), at(60));
+        assert_eq!(ongoing[0].message, "CPU spike ongoing for 1m0s: 97.0%");
+        assert_eq!(ongoing[0].severity, AnomalySeverity::Warning);
+
+        // Under the threshold but within the 10% hysteresis margin isn't resolved
+        for secs in 61..80 {
+            assert!(detector.check_at(&cpu(85.0), at(secs)).is_empty());
+        }
+        // Nor is a dip that doesn't last
+        assert!(detector.check_at(&cpu(50.0), at(80)).is_empty());
+        assert!(detector.check_at(&cpu(92.0), at(81)).is_empty());
+
+        for secs in 252..256 {
+            assert!(detector.check_at(&cpu(50.0), at(secs)).is_empty());
+        }
+        let resolved = detector.check_at(&cpu(50.0), at(256));
+        assert_eq!(resolved[0].message, "CPU spike resolved after 4m12s");
+        assert_eq!(resolved[0].severity, AnomalySeverity::Info);
+        assert!(matches!(resolved[0].kind, AnomalyKind::CpuSpike));
+
+        // Reported as a new spike after resolving
+        assert_eq!(detector.check_at(&cpu(95.0), at(300))[0].message, "CPU spike started: 95.0%");
+    }
+
+    #[test]
+    fn test_cpu_throttling_must_be_sustained_under_load() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let throttled = MetricsSample {
+            cpu_usage_percent: 80.0,
+            cpu_freq_percent_of_max: Some(40.0),
+            ..Default::default()
+        };
+
+        for _ in 0..THROTTLE_SUSTAINED_SAMPLES - 1 {
+            assert!(detector.check(&throttled).is_empty());
+        }
+        let anomalies = detector.check(&throttled);
+        assert_eq!(anomalies.len(), 1);
+        assert!(matches!(anomalies[0].kind, AnomalyKind::CpuThrottling));
+
+        // A full-speed sample resets the streak
+        for _ in 0..THROTTLE_SUSTAINED_SAMPLES - 1 {
+            assert!(detector.check(&throttled).is_empty());
+        }
+        let recovered = MetricsSample {
+            cpu_freq_percent_of_max: Some(95.0),
+            ..throttled.clone()
+        };
+        assert!(detector.check(&recovered).is_empty());
+        assert!(detector.check(&throttled).is_empty());
+
+        // Low clocks on an idle CPU are normal power saving
+        let idle = MetricsSample {
+            cpu_usage_percent: 5.0,
+            ..throttled.clone()
+        };
+        for _ in 0..THROTTLE_SUSTAINED_SAMPLES * 2 {
+            assert!(detector.check(&idle).is_empty());
+        }
+
+        // Systems without cpufreq never report throttling
+        let unknown = MetricsSample {
+            cpu_freq_percent_of_max: None,
+            ..throttled
+        };
+        for _ in 0..THROTTLE_SUSTAINED_SAMPLES * 2 {
+            assert!(detector.check(&unknown).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_disk_writer_named_over_disk_spike_threshold() {
+        let detector = AnomalyDetector::new(AnomalyConfig::default());
+        let writer = ProcessInfo {
+            pid: 4242,
+            name: "postgres".to_string(),
+            cmdline: "postgres -D /var/lib/postgresql".to_string(),
+            state: "D".to_string(),
+            user: "postgres".to_string(),
+            cpu_percent: 12.0,
+            mem_bytes: 512 * 1024 * 1024,
+            read_bytes: 0,
+            write_bytes: 40 * 1024 * 1024 * 1024,
+            read_bytes_per_sec: 0,
+            write_bytes_per_sec: 150 * 1024 * 1024,
+            num_fds: 64,
+            num_threads: 8,
+            connection_count: 0,
+            connections: Vec::new(),
+            container_id: None,
+            container_name: None,
+        };
+
+        assert!(detector.check_disk_writer(50 * 1024 * 1024, Some(&writer)).is_none());
+
+        let anomaly = detector.check_disk_writer(180 * 1024 * 1024, Some(&writer)).unwrap();
+        assert!(matches!(anomaly.kind, AnomalyKind::ProcessDiskWrite));
+        assert!(anomaly.message.contains("postgres (pid 4242)"), "{}", anomaly.message);
+
+        // Nothing to name when the writers have all exited
+        assert!(detector.check_disk_writer(180 * 1024 * 1024, None).is_none());
+    }
+
+    fn filesystem(mount_point: &str, used_percent: u64, inodes_used_percent: u64) -> FilesystemStats {
+        FilesystemStats {
+            filesystem: "/dev/sda1".to_string(),
+            mount_point: mount_point.to_string(),
+            total_bytes: 100 * 1024 * 1024 * 1024,
+            used_bytes: used_percent * 1024 * 1024 * 1024,
+            available_bytes: (100 - used_percent) * 1024 * 1024 * 1024,
+            inodes_total: 1000,
+            inodes_free: 1000 - inodes_used_percent * 10,
+        }
+    }
+
+    #[test]
+    fn test_disk_full_checks_every_mount_with_overrides() {
+        let mut config = AnomalyConfig::default();
+        config.disk_full_mounts.insert("/data".to_string(), 97.0);
+        let mut detector = AnomalyDetector::new(config);
+
+        let (anomalies, statuses) = detector.check_filesystems(&[
+            filesystem("/", 40, 10),
+            filesystem("/var", 95, 10),
+            filesystem("/data", 95, 10),
+        ]);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::DiskFull);
+        assert!(anomalies[0].message.starts_with("Disk usage on /var: 95.0%"), "{}", anomalies[0].message);
+        let critical: Vec<&str> = statuses.iter().filter(|s| s.critical).map(|s| s.mount_point.as_str()).collect();
+        assert_eq!(critical, vec!["/var"]);
+
+        // Still full: marked critical, but not reported again
+        let (anomalies, statuses) = detector.check_filesystems(&[filesystem("/var", 96, 10)]);
+        assert!(anomalies.is_empty());
+        assert!(statuses[0].critical);
+
+        // Reported again once it has recovered and filled up a second time
+        assert!(detector.check_filesystems(&[filesystem("/var", 50, 10)]).0.is_empty());
+        assert_eq!(detector.check_filesystems(&[filesystem("/var", 95, 10)]).0.len(), 1);
+    }
+
+    #[test]
+    fn test_inode_exhaustion_with_free_space() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+
+        let (anomalies, statuses) = detector.check_filesystems(&[filesystem("/var", 30, 95)]);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::InodeExhaustion);
+        assert_eq!(anomalies[0].message, "Inode usage on /var: 95.0% (50 of 1000 free)");
+        assert!(statuses[0].critical);
+        assert_eq!(statuses[0].inodes_free, 50);
+
+        // Filesystems without an inode table never report it
+        let no_inodes = FilesystemStats { inodes_total: 0, inodes_free: 0, ..filesystem("/srv", 30, 0) };
+        let (anomalies, statuses) = detector.check_filesystems(&[no_inodes]);
+        assert!(anomalies.is_empty());
+        assert!(!statuses[0].critical);
+    }
+
+    #[test]
+    fn test_tcp_retransmits_sustained_over_window() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let sample = |retrans, sent| MetricsSample {
+            tcp_retrans_per_sec: retrans,
+            tcp_out_segs_per_sec: sent,
+            ..Default::default()
+        };
+
+        // A burst of retransmits in an otherwise clean window stays under 5%
+        assert!(detector.check(&sample(200, 1000)).is_empty());
+        for _ in 1..RETRANSMIT_WINDOW_SAMPLES {
+            assert!(detector.check(&sample(0, 1000)).is_empty());
+        }
+
+        // Sustained 8% is reported once the window is mostly bad, then not again until a new window fills
+        let reported: Vec<usize> = (0..RETRANSMIT_WINDOW_SAMPLES * 2)
+            .filter(|_| !detector.check(&sample(80, 1000)).is_empty())
+            .collect();
+        assert_eq!(reported.len(), 2);
+        assert!(reported[1] - reported[0] == RETRANSMIT_WINDOW_SAMPLES, "{:?}", reported);
+
+        // Too little traffic for a ratio to mean anything
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        for _ in 0..RETRANSMIT_WINDOW_SAMPLES * 2 {
+            assert!(detector.check(&sample(5, 10)).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_file_handle_and_process_fd_exhaustion() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let handles = |allocated| MetricsSample { file_handles: Some((allocated, 100_000)), ..Default::default() };
+        assert!(detector.check(&handles(79_000)).is_empty());
+        let anomalies = detector.check(&handles(85_000));
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::FileHandleExhaustion);
+        assert_eq!(anomalies[0].message, "File handles: 85.0% of fs.file-max in use (85000 of 100000)");
+        // Once until it drops back under
+        assert!(detector.check(&handles(90_000)).is_empty());
+        assert!(detector.check(&handles(50_000)).is_empty());
+        assert_eq!(detector.check(&handles(85_000)).len(), 1);
+
+        let process = |pid, name: &str, open_fds| ProcessFdUsage { pid, name: name.to_string(), open_fds, limit: 1024 };
+        let anomalies = detector.check_process_fds(&[process(812, "nginx", 1000), process(900, "postgres", 500)]);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::FdExhaustion);
+        assert_eq!(anomalies[0].severity, AnomalySeverity::Warning);
+        assert_eq!(anomalies[0].message, "Process nginx (pid 812) has 1000 of its 1024 file descriptors open (97.7%)");
+        assert!(detector.check_process_fds(&[process(812, "nginx", 1010)]).is_empty());
+        assert!(detector.check_process_fds(&[process(812, "nginx", 400)]).is_empty());
+        assert_eq!(detector.check_process_fds(&[process(812, "nginx", 1000)]).len(), 1);
+    }
+
+    #[test]
+    fn test_pressure_stall_must_be_sustained() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let pressure = |io_full| MetricsSample {
+            pressure: Some(PressureMetrics {
+                cpu_some_avg10: 30.0,
+                io_some_avg10: io_full + 10.0,
+                io_full_avg10: io_full,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        for _ in 0..PRESSURE_SUSTAINED_SAMPLES - 1 {
+            assert!(detector.check(&pressure(25.0)).is_empty());
+        }
+        let anomalies = detector.check(&pressure(25.0));
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::PressureStall);
+        assert_eq!(anomalies[0].message, "IO pressure stall: every task stalled 25.0% of the last 10s, some 35.0%");
+
+        // Not again while it lasts, and a clear sample starts the streak over
+        for _ in 0..PRESSURE_SUSTAINED_SAMPLES * 2 {
+            assert!(detector.check(&pressure(25.0)).is_empty());
+        }
+        assert!(detector.check(&pressure(2.0)).is_empty());
+        for _ in 0..PRESSURE_SUSTAINED_SAMPLES - 1 {
+            assert!(detector.check(&pressure(25.0)).is_empty());
+        }
+        assert_eq!(detector.check(&pressure(25.0)).len(), 1);
+
+        // "some" pressure alone, or no PSI at all, is never reported
+        for _ in 0..PRESSURE_SUSTAINED_SAMPLES * 2 {
+            assert!(detector.check(&pressure(0.0)).is_empty());
+            assert!(detector.check(&MetricsSample::default()).is_empty());
+        }
+    }
+}
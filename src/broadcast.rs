@@ -28,21 +28,18 @@ impl EventBroadcaster {
     }
 
     // Run the broadcaster loop (bridges crossbeam → tokio broadcast)
-    // This should be spawned in an async task
+    // This should be spawned in an async task. The blocking receive runs on a thread of its
+    // own, so it doesn't hold up a runtime worker (the only one on a single CPU machine).
     pub async fn run(self) {
-        loop {
-            match self.receiver.recv() {
-                Ok(event) => {
-                    // Broadcast to all WebSocket subscribers
-                    // Ignore send errors (happens when no subscribers)
-                    let _ = self.tokio_broadcast.send(event);
-                }
-                Err(_) => {
-                    // Channel closed, exit loop
-                    break;
-                }
+        let _ = tokio::task::spawn_blocking(move || {
+            // Exits when the channel closes
+            while let Ok(event) = self.receiver.recv() {
+                // Broadcast to all WebSocket subscribers
+                // Ignore send errors (happens when no subscribers)
+                let _ = self.tokio_broadcast.send(event);
             }
-        }
+        })
+        .await;
     }
 
     // Subscribe to events (for WebSocket clients)
@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "black-box")]
@@ -19,9 +20,21 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub port: Option<u16>,
 
+    /// Override the ring buffer size in MB (server.max_storage_mb)
+    #[arg(long, value_name = "MB")]
+    pub max_storage: Option<u64>,
+
     /// Config file path
     #[arg(long, global = true, default_value = "./config.toml")]
     pub config: String,
+
+    /// Export a compressed JSON archive when the recorder shuts down
+    #[arg(long)]
+    pub export_on_stop: bool,
+
+    /// Directory for the shutdown export
+    #[arg(long, default_value = "/var/backups/black-box")]
+    pub export_dir: String,
 }
 
 #[derive(Subcommand)]
@@ -31,7 +44,7 @@ pub enum Commands {
 
     /// Export recorded events
     Export {
-        /// Output file path (default: stdout)
+        /// Output file path (default: stdout), or a directory for CSV
         #[arg(short, long)]
         output: Option<String>,
 
@@ -47,25 +60,95 @@ pub enum Commands {
         #[arg(long)]
         event_type: Option<String>,
 
-        /// Start time (RFC3339 or Unix timestamp)
-        #[arg(long)]
+        /// Start time (RFC3339, Unix timestamp, or a time ago like -2h)
+        #[arg(long, allow_hyphen_values = true)]
         start: Option<String>,
 
-        /// End time (RFC3339 or Unix timestamp)
-        #[arg(long)]
+        /// End time (RFC3339, Unix timestamp, or a time ago like -30m)
+        #[arg(long, allow_hyphen_values = true)]
         end: Option<String>,
 
+        /// Only events at or above this severity (anomaly severity, or the syslog
+        /// severity of other events: failed logins and service failures are warnings)
+        #[arg(long)]
+        severity: Option<FollowSeverity>,
+
+        /// Only events whose JSON matches this regex (process names, messages, paths...)
+        #[arg(long = "match", value_name = "REGEX")]
+        pattern: Option<String>,
+
+        /// Stream newline-delimited JSON (same as --format jsonl), with per-type counts on stderr
+        #[arg(long)]
+        ndjson: bool,
+
         /// Data directory to read from
         #[arg(short, long)]
         data_dir: Option<String>,
+
+        /// Check segment checksums and signatures, report each segment's integrity to
+        /// stderr, and exit with an error if any record fails
+        #[arg(long)]
+        verify: bool,
+
+        /// Write timestamps as RFC3339 in the configured timezone ([server] timezone, or TZ)
+        /// instead of UTC
+        #[arg(long)]
+        local_time: bool,
+    },
+
+    /// Print the events recorded in a time range straight from a data directory, no server needed
+    Query {
+        /// Data directory to read, e.g. one copied off a machine that died (default: ./data)
+        #[arg(short, long)]
+        data_dir: Option<String>,
+
+        /// Start time (RFC3339, Unix timestamp, or a time ago like -2h)
+        #[arg(long, allow_hyphen_values = true)]
+        start: String,
+
+        /// End time (RFC3339, Unix timestamp, or a time ago like -30m), default now
+        #[arg(long, allow_hyphen_values = true)]
+        end: Option<String>,
+
+        /// Filter by event type
+        #[arg(long = "type")]
+        event_type: Option<String>,
+
+        /// Only events whose JSON matches this regex (process names, messages, paths...)
+        #[arg(long, value_name = "REGEX")]
+        grep: Option<String>,
+
+        /// Print each event as a line of JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Check every segment's checksums and signature chain, reporting the first record that
+    /// was changed or cut off and any segment deleted out of order
+    Verify {
+        /// Data directory to read from (default: server.data_dir)
+        #[arg(short, long)]
+        data_dir: Option<String>,
+    },
+
+    /// Rewrite segments recorded by older versions in the current format, keeping each original
+    /// as .dat.bak. A segment with a record that doesn't decode is left as it is.
+    Migrate {
+        /// Data directory to migrate (default: server.data_dir)
+        #[arg(short, long)]
+        data_dir: Option<String>,
     },
 
     /// Watch remote black box instance for health and auto-export on failure
     Watch {
-        /// Black box server URL
+        /// Black box server URL (http:// or https://)
         #[arg(default_value = "http://localhost:8080")]
         url: String,
 
+        /// Connect to the server's Unix socket (unix_socket_path) instead of the URL
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+
         /// Username for authentication
         #[arg(short, long)]
         username: Option<String>,
@@ -85,6 +168,61 @@ pub enum Commands {
         /// Auto-export on every check (not just on failure)
         #[arg(long)]
         continuous: bool,
+
+        /// Skip TLS certificate verification (for self-signed certificates)
+        #[arg(long)]
+        insecure: bool,
+
+        /// Stream events live over the WebSocket and print one line per event instead of polling
+        #[arg(long)]
+        follow: bool,
+
+        /// With --follow, only show these event types (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<FollowEventType>,
+
+        /// With --follow, hide anomalies below this severity
+        #[arg(long)]
+        min_severity: Option<FollowSeverity>,
+
+        /// With --follow, only show events containing this text (like the web UI search box)
+        #[arg(long)]
+        filter: Option<String>,
+    },
+
+    /// Print events as they're recorded, from a running instance or the local data directory
+    Tail {
+        /// Black box server URL (http:// or https://)
+        #[arg(default_value = "http://localhost:8080")]
+        url: String,
+
+        /// Follow the recording in this data directory instead of connecting to a server
+        #[arg(short, long, conflicts_with_all = ["username", "password", "insecure"])]
+        data_dir: Option<String>,
+
+        /// Username for authentication
+        #[arg(short, long)]
+        username: Option<String>,
+
+        /// Password for authentication
+        #[arg(short, long)]
+        password: Option<String>,
+
+        /// Skip TLS certificate verification (for self-signed certificates)
+        #[arg(long)]
+        insecure: bool,
+
+        /// Only show these event types (comma-separated)
+        #[arg(long = "type", value_delimiter = ',')]
+        event_type: Vec<FollowEventType>,
+
+        /// Only show events containing this text (like the web UI search box)
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Print each event as a line of JSON instead of coloured text
+        #[arg(long)]
+        json: bool,
     },
 
     /// Generate systemd service files
@@ -96,10 +234,14 @@ pub enum Commands {
 
     /// Check status of running black box
     Status {
-        /// Black box server URL
+        /// Black box server URL (http:// or https://)
         #[arg(default_value = "http://localhost:8080")]
         url: String,
 
+        /// Connect to the server's Unix socket (unix_socket_path) instead of the URL
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+
         /// Username for authentication
         #[arg(short, long)]
         username: Option<String>,
@@ -111,6 +253,46 @@ pub enum Commands {
         /// Output format
         #[arg(short, long, default_value = "human")]
         format: StatusFormat,
+
+        /// Skip TLS certificate verification (for self-signed certificates)
+        #[arg(long)]
+        insecure: bool,
+
+        /// Health check mode: print one line and exit non-zero if a threshold is breached
+        #[arg(long)]
+        check: bool,
+
+        /// With --check, fail if the last recorded event is older than this many seconds
+        #[arg(long, default_value = "120")]
+        max_event_age: u64,
+
+        /// With --check, fail if a Critical anomaly occurred in the last N minutes
+        #[arg(long, default_value = "15")]
+        critical_window: u64,
+
+        /// With --format nagios, WARNING above this CPU usage percent
+        #[arg(long, value_name = "PERCENT")]
+        cpu_warn: Option<f32>,
+
+        /// With --format nagios, CRITICAL above this CPU usage percent
+        #[arg(long, value_name = "PERCENT")]
+        cpu_crit: Option<f32>,
+
+        /// With --format nagios, WARNING above this memory usage percent
+        #[arg(long, value_name = "PERCENT")]
+        mem_warn: Option<f32>,
+
+        /// With --format nagios, CRITICAL above this memory usage percent
+        #[arg(long, value_name = "PERCENT")]
+        mem_crit: Option<f32>,
+
+        /// With --format nagios, WARNING above this disk usage percent
+        #[arg(long, value_name = "PERCENT")]
+        disk_warn: Option<f32>,
+
+        /// With --format nagios, CRITICAL above this disk usage percent
+        #[arg(long, value_name = "PERCENT")]
+        disk_crit: Option<f32>,
     },
 
     /// Configuration management
@@ -118,6 +300,18 @@ pub enum Commands {
         #[command(subcommand)]
         command: ConfigCommands,
     },
+
+    /// Webhook alert tools
+    Alerts {
+        #[command(subcommand)]
+        command: AlertsCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AlertsCommands {
+    /// Send a test alert to each webhook in [alerts.webhook]
+    Test,
 }
 
 #[derive(Subcommand)]
@@ -181,37 +375,136 @@ pub enum ConfigCommands {
     },
 
     /// Set up remote syslog streaming
-    SetupRemote {
-        /// Remote syslog server host
-        #[arg(long)]
-        host: String,
+    SetupRemote(SetupRemoteArgs),
 
-        /// Remote syslog server port
-        #[arg(long, default_value = "514")]
-        port: u16,
+    /// Set up email alerts, asking for any settings not given
+    SetupEmail(SetupEmailArgs),
 
-        /// Protocol (tcp or udp)
-        #[arg(long, default_value = "tcp")]
-        protocol: String,
+    /// Create a read-only API token, printed once and stored hashed
+    AddToken {
+        /// Name recorded when the token is used, e.g. grafana
+        name: String,
     },
 }
 
+#[derive(Args)]
+pub struct SetupRemoteArgs {
+    /// Remote syslog server host
+    #[arg(long)]
+    pub host: String,
+
+    /// Remote syslog server port (usually 6514 with --tls)
+    #[arg(long, default_value = "514")]
+    pub port: u16,
+
+    /// Protocol (tcp or udp)
+    #[arg(long, default_value = "tcp")]
+    pub protocol: String,
+
+    /// Message format (json, rfc3164 or rfc5424)
+    #[arg(long, default_value = "rfc5424")]
+    pub format: String,
+
+    /// Syslog facility code (0-23, 16 = local0)
+    #[arg(long, default_value = "16")]
+    pub facility: u8,
+
+    /// TCP framing for syslog formats (octet_counting or lf)
+    #[arg(long, default_value = "octet_counting")]
+    pub framing: String,
+
+    /// Connect over TLS (TCP only)
+    #[arg(long)]
+    pub tls: bool,
+
+    /// PEM file of CA certificates to verify the server with, instead of the public roots
+    #[arg(long, requires = "tls")]
+    pub ca_cert: Option<String>,
+
+    /// PEM client certificate to present, for servers that require mutual TLS
+    #[arg(long, requires_all = ["tls", "client_key"])]
+    pub client_cert: Option<String>,
+
+    /// PEM private key for --client-cert
+    #[arg(long, requires = "client_cert")]
+    pub client_key: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SetupEmailArgs {
+    /// SMTP server host
+    #[arg(long)]
+    pub smtp_host: Option<String>,
+
+    /// SMTP server port
+    #[arg(long, default_value = "587")]
+    pub port: u16,
+
+    /// Send without STARTTLS, only for a relay on a trusted network
+    #[arg(long)]
+    pub no_starttls: bool,
+
+    /// SMTP username, the password is asked for (leave out for no authentication)
+    #[arg(long)]
+    pub username: Option<String>,
+
+    /// Sender address, e.g. "Black Box <blackbox@example.com>"
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Recipient address, repeat for more than one
+    #[arg(long)]
+    pub to: Vec<String>,
+
+    /// Minutes between digests of warnings
+    #[arg(long, default_value = "15")]
+    pub digest_minutes: u64,
+
+    /// Send a test email once the settings are saved
+    #[arg(long)]
+    pub test: bool,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum ExportFormat {
     /// Pretty-printed JSON
     Json,
     /// Newline-delimited JSON (JSONL)
     Jsonl,
-    /// CSV format
+    /// CSV, a file per event type in the --output directory (or one file with --event-type)
     Csv,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum FollowEventType {
+    /// Process start/exit
+    #[value(alias = "processes")]
+    Process,
+    /// Security events (logins, sudo, ...)
+    Security,
+    /// Anomalies
+    #[value(alias = "anomalies")]
+    Anomaly,
+    /// File system changes
+    #[value(alias = "files")]
+    Filesystem,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum FollowSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum StatusFormat {
     /// Human-readable output
     Human,
     /// JSON output
     Json,
+    /// One Nagios plugin status line, exiting 0 (OK), 1 (WARNING), 2 (CRITICAL) or 3 (UNKNOWN)
+    Nagios,
 }
 
 impl Cli {
@@ -10,6 +10,19 @@ pub fn read_system_uptime() -> Result<u64> {
     Ok(uptime_secs as u64)
 }
 
+/// When the machine booted, from the btime line of /proc/stat
+pub fn read_boot_time() -> Result<time::OffsetDateTime> {
+    let content = fs::read_to_string("/proc/stat")?;
+    let btime = parse_boot_time(&content).context("No btime in /proc/stat")?;
+    Ok(time::OffsetDateTime::from_unix_timestamp(btime)?)
+}
+
+fn parse_boot_time(stat: &str) -> Option<i64> {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse().ok())
+}
+
 // ===== Kernel Version =====
 
 pub fn read_kernel_version() -> String {
@@ -50,30 +63,249 @@ pub fn read_cpu_info() -> CpuInfo {
     CpuInfo { model, mhz }
 }
 
-// ===== GPU Info =====
+// ===== CPU Frequency =====
 
-use crate::event::GpuInfo;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoreFrequency {
+    pub cur_mhz: u32,
+    pub max_mhz: u32, // scaling_max_freq, the policy limit rather than the hardware max
+}
 
-pub fn read_gpu_info() -> GpuInfo {
-    // Try nvidia-smi first
-    if let Ok(output) = std::process::Command::new("nvidia-smi")
-        .args(["--query-gpu=clocks.gr,clocks.mem,temperature.gpu,power.draw", "--format=csv,noheader,nounits"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let parts: Vec<&str> = stdout.trim().split(", ").collect();
-            if parts.len() >= 4 {
-                return GpuInfo {
-                    gpu_freq_mhz: parts.get(0).and_then(|s| s.trim().parse().ok()),
-                    mem_freq_mhz: parts.get(1).and_then(|s| s.trim().parse().ok()),
-                    gpu_temp_celsius: parts.get(2).and_then(|s| s.trim().parse().ok()),
-                    power_watts: parts.get(3).and_then(|s| s.trim().parse().ok()),
-                };
-            }
+// Per-core frequencies from cpufreq sysfs, ordered by core number.
+// None when cpufreq isn't exposed (most VMs and containers).
+pub fn read_core_frequencies() -> Option<Vec<CoreFrequency>> {
+    read_core_frequencies_from(std::path::Path::new("/sys/devices/system/cpu"))
+}
+
+fn read_core_frequencies_from(cpu_dir: &std::path::Path) -> Option<Vec<CoreFrequency>> {
+    let read_khz = |path: std::path::PathBuf| -> Option<u64> {
+        fs::read_to_string(path).ok()?.trim().parse().ok()
+    };
+
+    let mut cores = Vec::new();
+    for entry in fs::read_dir(cpu_dir).ok()?.flatten() {
+        let name = entry.file_name();
+        let Some(core_id) = name
+            .to_str()
+            .and_then(|n| n.strip_prefix("cpu"))
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        // Offline cores have no cpufreq directory, matching their absence from /proc/stat
+        let cpufreq = entry.path().join("cpufreq");
+        if let (Some(cur), Some(max)) = (
+            read_khz(cpufreq.join("scaling_cur_freq")),
+            read_khz(cpufreq.join("scaling_max_freq")),
+        ) {
+            cores.push((core_id, CoreFrequency {
+                cur_mhz: (cur / 1000) as u32,
+                max_mhz: (max / 1000) as u32,
+            }));
         }
     }
-    GpuInfo::default()
+
+    if cores.is_empty() {
+        return None;
+    }
+    cores.sort_by_key(|(core_id, _)| *core_id);
+    Some(cores.into_iter().map(|(_, freq)| freq).collect())
+}
+
+// Average current frequency as a percentage of each core's max
+pub fn freq_percent_of_max(freqs: &[CoreFrequency]) -> Option<f32> {
+    let ratios: Vec<f32> = freqs
+        .iter()
+        .filter(|f| f.max_mhz > 0)
+        .map(|f| f.cur_mhz as f32 / f.max_mhz as f32 * 100.0)
+        .collect();
+    if ratios.is_empty() {
+        return None;
+    }
+    Some(ratios.iter().sum::<f32>() / ratios.len() as f32)
+}
+
+// ===== GPU Info =====
+
+use crate::event::{ContainerUsage, GpuDevice, UserUsage};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GpuSource {
+    NvidiaSmi,
+    RocmSmi,
+    AmdgpuSysfs,
+    None,
+}
+
+static GPU_SOURCE: OnceLock<GpuSource> = OnceLock::new();
+
+// Every GPU on the machine: NVIDIA through nvidia-smi, AMD through rocm-smi, or straight
+// from the amdgpu driver where ROCm isn't installed. The first source that finds a GPU is
+// kept, so machines without one don't spawn the tools every interval.
+pub fn read_gpus() -> Vec<GpuDevice> {
+    let source = *GPU_SOURCE.get_or_init(|| {
+        [GpuSource::NvidiaSmi, GpuSource::RocmSmi, GpuSource::AmdgpuSysfs]
+            .into_iter()
+            .find(|&source| !read_gpus_from(source).is_empty())
+            .unwrap_or(GpuSource::None)
+    });
+    read_gpus_from(source)
+}
+
+fn read_gpus_from(source: GpuSource) -> Vec<GpuDevice> {
+    match source {
+        GpuSource::NvidiaSmi => execute_command_timeout(
+            "nvidia-smi",
+            &[
+                "--query-gpu=index,name,clocks.gr,clocks.mem,temperature.gpu,power.draw,memory.used,memory.total",
+                "--format=csv,noheader,nounits",
+            ],
+        )
+        .map(|output| parse_nvidia_smi_gpus(&output))
+        .unwrap_or_default(),
+        GpuSource::RocmSmi => execute_command_timeout(
+            "rocm-smi",
+            &["--showproductname", "--showclocks", "--showtemp", "--showpower", "--showmeminfo", "vram", "--json"],
+        )
+        .map(|output| parse_rocm_smi_gpus(&output))
+        .unwrap_or_default(),
+        GpuSource::AmdgpuSysfs => read_amdgpu_sysfs(std::path::Path::new("/sys/class/drm")),
+        GpuSource::None => Vec::new(),
+    }
+}
+
+// One CSV row per GPU. Names can contain commas, so the numeric columns are taken from the
+// end. Unsupported readings come through as "[N/A]" or "[Not Supported]" and become None.
+fn parse_nvidia_smi_gpus(output: &str) -> Vec<GpuDevice> {
+    const MIB: u64 = 1024 * 1024;
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() < 8 {
+                return None;
+            }
+            let values = &fields[fields.len() - 6..];
+            Some(GpuDevice {
+                index: fields[0].parse().ok()?,
+                name: fields[1..fields.len() - 6].join(", "),
+                gpu_freq_mhz: values[0].parse().ok(),
+                mem_freq_mhz: values[1].parse().ok(),
+                temp_celsius: values[2].parse().ok(),
+                power_watts: values[3].parse().ok(),
+                vram_used_bytes: values[4].parse::<u64>().ok().map(|mib| mib * MIB),
+                vram_total_bytes: values[5].parse::<u64>().ok().map(|mib| mib * MIB),
+            })
+        })
+        .collect()
+}
+
+// `rocm-smi --json` keys each GPU as "card0", "card1", ... Key names vary between ROCm
+// releases, so readings are found by the part of the key that stays the same.
+fn parse_rocm_smi_gpus(output: &str) -> Vec<GpuDevice> {
+    let Ok(serde_json::Value::Object(cards)) = serde_json::from_str::<serde_json::Value>(output) else {
+        return Vec::new();
+    };
+
+    let mut gpus: Vec<GpuDevice> = cards
+        .iter()
+        .filter_map(|(card, fields)| {
+            let index = card.strip_prefix("card")?.parse().ok()?;
+            let fields = fields.as_object()?;
+            let find = |matches: &dyn Fn(&str) -> bool| {
+                fields.iter().find(|(key, _)| matches(key)).and_then(|(_, value)| value.as_str())
+            };
+            let clock = |prefix: &str| find(&|k| k.starts_with(prefix)).and_then(parse_mhz);
+            let temp = find(&|k| k.contains("Temperature") && k.contains("edge"))
+                .or_else(|| find(&|k| k.contains("Temperature")));
+
+            Some(GpuDevice {
+                index,
+                name: find(&|k| k == "Card series")
+                    .or_else(|| find(&|k| k == "Card SKU"))
+                    .or_else(|| find(&|k| k == "Card model"))
+                    .unwrap_or("AMD GPU")
+                    .to_string(),
+                gpu_freq_mhz: clock("sclk clock speed"),
+                mem_freq_mhz: clock("mclk clock speed"),
+                temp_celsius: temp.and_then(|t| t.trim().parse().ok()),
+                power_watts: find(&|k| k.contains("Power (W)")).and_then(|p| p.trim().parse().ok()),
+                vram_used_bytes: find(&|k| k.contains("VRAM") && k.contains("Used")).and_then(|v| v.trim().parse().ok()),
+                vram_total_bytes: find(&|k| k.contains("VRAM") && k.contains("Total") && !k.contains("Used"))
+                    .and_then(|v| v.trim().parse().ok()),
+            })
+        })
+        .collect();
+    gpus.sort_by_key(|gpu| gpu.index);
+    gpus
+}
+
+// "(1500Mhz)" or "1500Mhz" -> 1500
+fn parse_mhz(value: &str) -> Option<u32> {
+    let digits: String = value
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+// amdgpu cards under /sys/class/drm (vendor 0x1002). Clocks come from the active DPM level,
+// temperature and power from the card's hwmon.
+fn read_amdgpu_sysfs(drm: &std::path::Path) -> Vec<GpuDevice> {
+    let Ok(entries) = fs::read_dir(drm) else {
+        return Vec::new();
+    };
+
+    let mut gpus: Vec<GpuDevice> = entries
+        .flatten()
+        .filter_map(|entry| {
+            // card0, not connectors like card0-DP-1
+            let index: u32 = entry.file_name().to_str()?.strip_prefix("card")?.parse().ok()?;
+            let device = entry.path().join("device");
+            if fs::read_to_string(device.join("vendor")).ok()?.trim() != "0x1002" {
+                return None;
+            }
+
+            let read_u64 = |path: std::path::PathBuf| fs::read_to_string(path).ok().and_then(|s| s.trim().parse::<u64>().ok());
+            let hwmon = fs::read_dir(device.join("hwmon"))
+                .ok()
+                .and_then(|mut dirs| dirs.next())
+                .and_then(|dir| dir.ok())
+                .map(|dir| dir.path());
+            let hwmon_value = |file: &str| hwmon.as_ref().and_then(|dir| read_u64(dir.join(file)));
+
+            Some(GpuDevice {
+                index,
+                name: fs::read_to_string(device.join("product_name"))
+                    .map(|name| name.trim().to_string())
+                    .ok()
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| "AMD GPU".to_string()),
+                gpu_freq_mhz: fs::read_to_string(device.join("pp_dpm_sclk")).ok().and_then(|s| current_dpm_mhz(&s)),
+                mem_freq_mhz: fs::read_to_string(device.join("pp_dpm_mclk")).ok().and_then(|s| current_dpm_mhz(&s)),
+                temp_celsius: hwmon_value("temp1_input").map(|millidegrees| millidegrees as f32 / 1000.0),
+                power_watts: hwmon_value("power1_average")
+                    .or_else(|| hwmon_value("power1_input"))
+                    .map(|microwatts| microwatts as f32 / 1_000_000.0),
+                vram_used_bytes: read_u64(device.join("mem_info_vram_used")),
+                vram_total_bytes: read_u64(device.join("mem_info_vram_total")),
+            })
+        })
+        .collect();
+    gpus.sort_by_key(|gpu| gpu.index);
+    gpus
+}
+
+// pp_dpm_* lists the clock levels with the active one starred: "1: 1000Mhz *"
+fn current_dpm_mhz(levels: &str) -> Option<u32> {
+    levels
+        .lines()
+        .find(|line| line.trim_end().ends_with('*'))
+        .and_then(|line| line.split_once(':'))
+        .and_then(|(_, clock)| parse_mhz(clock))
 }
 
 // ===== CPU Stats =====
@@ -191,6 +423,10 @@ pub struct MemoryStats {
     pub available_kb: u64,
     pub buffers_kb: u64,
     pub cached_kb: u64,
+    pub dirty_kb: u64,
+    pub writeback_kb: u64,
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
 }
 
 impl MemoryStats {
@@ -209,13 +445,20 @@ impl MemoryStats {
 
 pub fn read_memory_stats() -> Result<MemoryStats> {
     let content = fs::read_to_string("/proc/meminfo").context("Failed to read /proc/meminfo")?;
+    parse_meminfo(&content)
+}
 
+fn parse_meminfo(content: &str) -> Result<MemoryStats> {
     let mut stats = MemoryStats {
         total_kb: 0,
         free_kb: 0,
         available_kb: 0,
         buffers_kb: 0,
         cached_kb: 0,
+        dirty_kb: 0,
+        writeback_kb: 0,
+        hugepages_total: 0,
+        hugepages_free: 0,
     };
 
     for line in content.lines() {
@@ -229,6 +472,14 @@ pub fn read_memory_stats() -> Result<MemoryStats> {
             stats.buffers_kb = parse_meminfo_value(value)?;
         } else if let Some(value) = line.strip_prefix("Cached:") {
             stats.cached_kb = parse_meminfo_value(value)?;
+        } else if let Some(value) = line.strip_prefix("Dirty:") {
+            stats.dirty_kb = parse_meminfo_value(value)?;
+        } else if let Some(value) = line.strip_prefix("Writeback:") {
+            stats.writeback_kb = parse_meminfo_value(value)?;
+        } else if let Some(value) = line.strip_prefix("HugePages_Total:") {
+            stats.hugepages_total = parse_meminfo_value(value)?; // Page counts, no unit
+        } else if let Some(value) = line.strip_prefix("HugePages_Free:") {
+            stats.hugepages_free = parse_meminfo_value(value)?;
         }
     }
 
@@ -446,73 +697,142 @@ pub struct FilesystemStats {
     pub total_bytes: u64,
     pub used_bytes: u64,
     pub available_bytes: u64,
+    pub inodes_total: u64, // 0 where the filesystem has no fixed inode table (btrfs, some network mounts)
+    pub inodes_free: u64,
+}
+
+impl FilesystemStats {
+    pub fn usage_percent(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        self.used_bytes as f32 / self.total_bytes as f32 * 100.0
+    }
+
+    pub fn inode_usage_percent(&self) -> Option<f32> {
+        if self.inodes_total == 0 {
+            return None;
+        }
+        Some(self.inodes_total.saturating_sub(self.inodes_free) as f32 / self.inodes_total as f32 * 100.0)
+    }
 }
 
 pub fn read_disk_space() -> Result<DiskSpaceStats> {
-    // Simple approach: use df for root
-    let output = std::process::Command::new("df")
-        .arg("-B1") // 1-byte blocks
-        .arg("/")
-        .output()
-        .context("Failed to run df")?;
+    let usage = statvfs("/")?;
+    Ok(DiskSpaceStats {
+        total_bytes: usage.total_bytes,
+        used_bytes: usage.used_bytes,
+    })
+}
 
-    let content = String::from_utf8_lossy(&output.stdout);
+// Every mounted storage filesystem, sized with statvfs rather than by running df
+pub fn read_all_filesystems() -> Result<Vec<FilesystemStats>> {
+    let content = fs::read_to_string("/proc/self/mounts").context("Failed to read /proc/self/mounts")?;
+    let mut filesystems = Vec::new();
 
-    for line in content.lines().skip(1) {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 3 {
-            let total = parts[1].parse().unwrap_or(0);
-            let used = parts[2].parse().unwrap_or(0);
-            return Ok(DiskSpaceStats {
-                total_bytes: total,
-                used_bytes: used,
-            });
+    for (filesystem, mount_point) in parse_mounts(&content) {
+        // Unreachable network mounts and mount points we can't read are left out, as df does
+        let Ok(usage) = statvfs(&mount_point) else {
+            continue;
+        };
+        if usage.total_bytes == 0 {
+            continue;
         }
+        filesystems.push(FilesystemStats {
+            filesystem,
+            mount_point,
+            total_bytes: usage.total_bytes,
+            used_bytes: usage.used_bytes,
+            available_bytes: usage.available_bytes,
+            inodes_total: usage.inodes_total,
+            inodes_free: usage.inodes_free,
+        });
     }
 
-    anyhow::bail!("Failed to parse df output")
+    Ok(filesystems)
 }
 
-pub fn read_all_filesystems() -> Result<Vec<FilesystemStats>> {
-    let output = std::process::Command::new("df")
-        .arg("-B1") // 1-byte blocks
-        .arg("-x").arg("tmpfs")
-        .arg("-x").arg("devtmpfs")
-        .arg("-x").arg("squashfs")
-        .arg("-x").arg("overlay")
-        .output()
-        .context("Failed to run df")?;
+// Filesystem types that aren't storage, plus autofs, which statvfs would trigger a mount on
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "tmpfs", "devtmpfs", "squashfs", "overlay", "proc", "sysfs", "cgroup", "cgroup2", "devpts",
+    "mqueue", "debugfs", "tracefs", "securityfs", "pstore", "bpf", "configfs", "fusectl", "autofs",
+    "binfmt_misc", "hugetlbfs", "nsfs", "ramfs", "efivarfs", "rpc_pipefs", "selinuxfs",
+];
 
-    let content = String::from_utf8_lossy(&output.stdout);
-    let mut filesystems = Vec::new();
+// (device, mount point) of storage mounts in /proc/self/mounts, one per device
+fn parse_mounts(content: &str) -> Vec<(String, String)> {
+    let mut mounts: Vec<(String, String)> = Vec::new();
 
-    for line in content.lines().skip(1) {
+    for line in content.lines() {
         let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 6 {
-            let filesystem = parts[0].to_string();
-            let total: u64 = parts[1].parse().unwrap_or(0);
-            let used: u64 = parts[2].parse().unwrap_or(0);
-            let available: u64 = parts[3].parse().unwrap_or(0);
-            let mount_point = parts[5].to_string();
-
-            // Skip if total is 0 or mount point is system-related
-            if total == 0 {
-                continue;
+        if parts.len() < 3 || PSEUDO_FILESYSTEMS.contains(&parts[2]) {
+            continue;
+        }
+        let device = unescape_mount_field(parts[0]);
+        let mount_point = unescape_mount_field(parts[1]);
+
+        // Bind mounts and btrfs subvolumes repeat a device; like df, keep its shortest mount point
+        if device.starts_with('/')
+            && let Some(existing) = mounts.iter_mut().find(|(d, _)| *d == device)
+        {
+            if mount_point.len() < existing.1.len() {
+                existing.1 = mount_point;
             }
+            continue;
+        }
+        // A later mount on the same path hides the earlier one
+        mounts.retain(|(_, m)| *m != mount_point);
+        mounts.push((device, mount_point));
+    }
 
-            filesystems.push(FilesystemStats {
-                filesystem,
-                mount_point,
-                total_bytes: total,
-                used_bytes: used,
-                available_bytes: available,
-            });
+    mounts
+}
+
+// /proc/self/mounts writes space, tab, newline and backslash as octal escapes (\040 etc.)
+fn unescape_mount_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        unescaped.push_str(&rest[..i]);
+        match rest.get(i + 1..i + 4).and_then(|octal| u8::from_str_radix(octal, 8).ok()) {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[i + 1..];
+            }
         }
     }
+    unescaped.push_str(rest);
+    unescaped
+}
 
-    Ok(filesystems)
+struct FsUsage {
+    total_bytes: u64,
+    used_bytes: u64,
+    available_bytes: u64, // To unprivileged users, so excludes blocks reserved for root
+    inodes_total: u64,
+    inodes_free: u64,
 }
 
+fn statvfs(path: &str) -> Result<FsUsage> {
+    let c_path = std::ffi::CString::new(path).context("Mount point contains a NUL byte")?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| format!("statvfs {} failed", path));
+    }
+
+    let block_size = stat.f_frsize as u64;
+    Ok(FsUsage {
+        total_bytes: stat.f_blocks as u64 * block_size,
+        used_bytes: (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64) * block_size,
+        available_bytes: stat.f_bavail as u64 * block_size,
+        inodes_total: stat.f_files as u64,
+        inodes_free: stat.f_ffree as u64,
+    })
+}
 
 // ===== Network I/O Stats =====
 
@@ -527,17 +847,21 @@ pub struct NetworkStats {
     pub primary_interface: String,
 }
 
-pub fn read_network_stats() -> Result<NetworkStats> {
-    let content = fs::read_to_string("/proc/net/dev").context("Failed to read /proc/net/dev")?;
+// Per-interface counters from /proc/net/dev
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InterfaceStats {
+    pub recv_bytes: u64,
+    pub send_bytes: u64,
+    pub recv_packets: u64,
+    pub send_packets: u64,
+    pub recv_errors: u64,
+    pub send_errors: u64,
+    pub recv_drops: u64,
+    pub send_drops: u64,
+}
 
-    let mut total_recv = 0u64;
-    let mut total_send = 0u64;
-    let mut total_recv_errors = 0u64;
-    let mut total_send_errors = 0u64;
-    let mut total_recv_drops = 0u64;
-    let mut total_send_drops = 0u64;
-    let mut primary_interface = String::from("net");
-    let mut max_bytes = 0u64;
+fn parse_net_dev(content: &str) -> HashMap<String, InterfaceStats> {
+    let mut interfaces = HashMap::new();
 
     for line in content.lines().skip(2) {
         // Skip header lines
@@ -553,39 +877,98 @@ pub fn read_network_stats() -> Result<NetworkStats> {
         }
 
         // Parse all network stats
-        if let (Ok(recv), Ok(send), Ok(recv_err), Ok(recv_drop), Ok(send_err), Ok(send_drop)) = (
+        if let (Ok(recv), Ok(send), Ok(recv_packets), Ok(send_packets), Ok(recv_err), Ok(recv_drop), Ok(send_err), Ok(send_drop)) = (
             parts[1].parse::<u64>(),   // recv bytes
             parts[9].parse::<u64>(),   // transmit bytes
+            parts[2].parse::<u64>(),   // recv packets
+            parts[10].parse::<u64>(),  // transmit packets
             parts[3].parse::<u64>(),   // recv errors
             parts[4].parse::<u64>(),   // recv drop
             parts[11].parse::<u64>(),  // transmit errors
             parts[12].parse::<u64>(),  // transmit drop
         ) {
-            total_recv += recv;
-            total_send += send;
-            total_recv_errors += recv_err;
-            total_send_errors += send_err;
-            total_recv_drops += recv_drop;
-            total_send_drops += send_drop;
-
-            // Track the interface with the most traffic as primary
-            let total_bytes = recv + send;
-            if total_bytes > max_bytes {
-                max_bytes = total_bytes;
-                primary_interface = parts[0].trim_end_matches(':').to_string();
-            }
+            interfaces.insert(parts[0].trim_end_matches(':').to_string(), InterfaceStats {
+                recv_bytes: recv,
+                send_bytes: send,
+                recv_packets,
+                send_packets,
+                recv_errors: recv_err,
+                send_errors: send_err,
+                recv_drops: recv_drop,
+                send_drops: send_drop,
+            });
         }
     }
 
-    Ok(NetworkStats {
-        recv_bytes: total_recv,
-        send_bytes: total_send,
-        recv_errors: total_recv_errors,
-        send_errors: total_send_errors,
-        recv_drops: total_recv_drops,
-        send_drops: total_send_drops,
-        primary_interface,
-    })
+    interfaces
+}
+
+pub fn read_network_stats_per_interface() -> Result<HashMap<String, InterfaceStats>> {
+    let content = fs::read_to_string("/proc/net/dev").context("Failed to read /proc/net/dev")?;
+    Ok(parse_net_dev(&content))
+}
+
+pub fn read_network_stats() -> Result<NetworkStats> {
+    let interfaces = read_network_stats_per_interface()?;
+
+    let mut stats = NetworkStats {
+        recv_bytes: 0,
+        send_bytes: 0,
+        recv_errors: 0,
+        send_errors: 0,
+        recv_drops: 0,
+        send_drops: 0,
+        primary_interface: String::from("net"),
+    };
+    let mut max_bytes = 0u64;
+
+    for (name, iface) in &interfaces {
+        stats.recv_bytes += iface.recv_bytes;
+        stats.send_bytes += iface.send_bytes;
+        stats.recv_errors += iface.recv_errors;
+        stats.send_errors += iface.send_errors;
+        stats.recv_drops += iface.recv_drops;
+        stats.send_drops += iface.send_drops;
+
+        // Track the interface with the most traffic as primary
+        let total_bytes = iface.recv_bytes + iface.send_bytes;
+        if total_bytes > max_bytes {
+            max_bytes = total_bytes;
+            stats.primary_interface = name.clone();
+        }
+    }
+
+    Ok(stats)
+}
+
+// Per-interface rates: (name, stats-per-second), sorted by interface name
+pub fn per_interface_rates(
+    current: &HashMap<String, InterfaceStats>,
+    prev: &HashMap<String, InterfaceStats>,
+    interval_secs: f32,
+) -> Vec<(String, InterfaceStats)> {
+    let per_sec = |cur: u64, old: u64| (cur.saturating_sub(old) as f32 / interval_secs) as u64;
+
+    let mut results: Vec<(String, InterfaceStats)> = current
+        .iter()
+        .filter_map(|(name, cur)| {
+            prev.get(name).map(|old| {
+                (name.clone(), InterfaceStats {
+                    recv_bytes: per_sec(cur.recv_bytes, old.recv_bytes),
+                    send_bytes: per_sec(cur.send_bytes, old.send_bytes),
+                    recv_packets: per_sec(cur.recv_packets, old.recv_packets),
+                    send_packets: per_sec(cur.send_packets, old.send_packets),
+                    recv_errors: per_sec(cur.recv_errors, old.recv_errors),
+                    send_errors: per_sec(cur.send_errors, old.send_errors),
+                    recv_drops: per_sec(cur.recv_drops, old.recv_drops),
+                    send_drops: per_sec(cur.send_drops, old.send_drops),
+                })
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    results
 }
 
 impl NetworkStats {
@@ -674,24 +1057,39 @@ pub fn get_default_gateway() -> Option<String> {
     None
 }
 
-pub fn get_dns_server() -> Option<String> {
-    // Read from /etc/resolv.conf
-    let content = fs::read_to_string("/etc/resolv.conf").ok()?;
+// systemd-resolved points /etc/resolv.conf at its local stub (127.0.0.53);
+// the servers it actually forwards to are listed here instead
+const RESOLVED_STUB_TARGET: &str = "stub-resolv.conf";
+const RESOLVED_UPSTREAM_CONF: &str = "/run/systemd/resolve/resolv.conf";
 
-    for line in content.lines() {
-        let line = line.trim();
-        if line.starts_with("nameserver ") {
-            if let Some(dns) = line.strip_prefix("nameserver ") {
-                let dns = dns.trim();
-                // Skip localhost addresses
-                if dns != "127.0.0.1" && dns != "::1" && dns != "127.0.0.53" {
-                    return Some(dns.to_string());
-                }
-            }
-        }
+// Contents of the resolv.conf that names the real DNS servers
+fn read_resolv_conf() -> Option<String> {
+    if let Ok(target) = fs::read_link("/etc/resolv.conf")
+        && target.ends_with(RESOLVED_STUB_TARGET)
+        && let Ok(content) = fs::read_to_string(RESOLVED_UPSTREAM_CONF)
+    {
+        return Some(content);
     }
+    fs::read_to_string("/etc/resolv.conf").ok()
+}
 
-    None
+fn parse_nameservers(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map(|rest| rest.trim().to_string())
+        .filter(|dns| !dns.is_empty())
+        .collect()
+}
+
+pub fn get_dns_server() -> Option<String> {
+    let content = read_resolv_conf()?;
+
+    // Skip localhost addresses
+    parse_nameservers(&content)
+        .into_iter()
+        .find(|dns| dns != "127.0.0.1" && dns != "::1" && dns != "127.0.0.53")
 }
 
 // ===== Context Switch Stats =====
@@ -767,6 +1165,243 @@ pub fn read_tcp_stats() -> Result<TcpStats> {
     })
 }
 
+// ===== Socket Protocol Stats =====
+
+// Cumulative TCP/UDP counters from /proc/net/snmp and /proc/net/netstat, and socket counts
+// from /proc/net/sockstat
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SocketStats {
+    pub tcp_in_segs: u64,
+    pub tcp_out_segs: u64,
+    pub tcp_retrans_segs: u64,
+    pub tcp_listen_overflows: u64, // Connections refused because a listener's accept queue was full
+    pub tcp_listen_drops: u64,     // Every SYN or connection dropped at a listener, overflows included
+    pub tcp_syn_drops: u64,        // SYNs dropped because the SYN backlog was full (TCPReqQFullDrop)
+    pub udp_in_datagrams: u64,
+    pub udp_out_datagrams: u64,
+    pub udp_errors: u64, // InErrors, which includes receive buffer overflows
+    pub sockets_used: u64,
+    pub tcp_orphaned: u64,
+    pub socket_mem_bytes: u64, // TCP and UDP buffers
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SocketRates {
+    pub tcp_in_segs: u64,
+    pub tcp_out_segs: u64,
+    pub tcp_retrans_segs: u64,
+    pub tcp_listen_overflows: u64,
+    pub tcp_listen_drops: u64,
+    pub tcp_syn_drops: u64,
+    pub udp_in_datagrams: u64,
+    pub udp_out_datagrams: u64,
+    pub udp_errors: u64,
+}
+
+pub fn read_socket_stats() -> Result<SocketStats> {
+    let snmp = fs::read_to_string("/proc/net/snmp").context("Failed to read /proc/net/snmp")?;
+    let netstat = fs::read_to_string("/proc/net/netstat").unwrap_or_default();
+    let sockstat = fs::read_to_string("/proc/net/sockstat").unwrap_or_default();
+    Ok(parse_socket_stats(&snmp, &netstat, &sockstat))
+}
+
+fn parse_socket_stats(snmp: &str, netstat: &str, sockstat: &str) -> SocketStats {
+    // Each protocol is a header line of names followed by a line of values with the same prefix.
+    // netstat has the same layout for the kernel's extended counters (TcpExt, IpExt...).
+    let mut counters: HashMap<(&str, &str), u64> = HashMap::new();
+    let mut headers: HashMap<&str, &str> = HashMap::new();
+    for line in snmp.lines().chain(netstat.lines()) {
+        let Some((protocol, fields)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(names) = headers.remove(protocol) else {
+            headers.insert(protocol, fields);
+            continue;
+        };
+        for (name, value) in names.split_whitespace().zip(fields.split_whitespace()) {
+            // Negative values (Tcp MaxConn is -1) aren't counters
+            if let Ok(value) = value.parse() {
+                counters.insert((protocol, name), value);
+            }
+        }
+    }
+    let counter = |protocol, name| counters.get(&(protocol, name)).copied().unwrap_or(0);
+
+    // "TCP: inuse 5 orphan 0 tw 2 alloc 7 mem 1", with mem in pages
+    let mut sockstat_fields: HashMap<(&str, &str), u64> = HashMap::new();
+    for line in sockstat.lines() {
+        let Some((protocol, fields)) = line.split_once(':') else {
+            continue;
+        };
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        for field in fields.chunks_exact(2) {
+            if let Ok(value) = field[1].parse() {
+                sockstat_fields.insert((protocol, field[0]), value);
+            }
+        }
+    }
+    let sockstat_field = |protocol, name| sockstat_fields.get(&(protocol, name)).copied().unwrap_or(0);
+
+    SocketStats {
+        tcp_in_segs: counter("Tcp", "InSegs"),
+        tcp_out_segs: counter("Tcp", "OutSegs"),
+        tcp_retrans_segs: counter("Tcp", "RetransSegs"),
+        tcp_listen_overflows: counter("TcpExt", "ListenOverflows"),
+        tcp_listen_drops: counter("TcpExt", "ListenDrops"),
+        tcp_syn_drops: counter("TcpExt", "TCPReqQFullDrop"),
+        udp_in_datagrams: counter("Udp", "InDatagrams"),
+        udp_out_datagrams: counter("Udp", "OutDatagrams"),
+        udp_errors: counter("Udp", "InErrors"),
+        sockets_used: sockstat_field("sockets", "used"),
+        tcp_orphaned: sockstat_field("TCP", "orphan"),
+        socket_mem_bytes: (sockstat_field("TCP", "mem") + sockstat_field("UDP", "mem")) * 4096, // Pages to bytes
+    }
+}
+
+impl SocketStats {
+    pub fn per_sec(&self, prev: &SocketStats, interval_secs: f32) -> SocketRates {
+        let rate = |current: u64, previous: u64| (current.saturating_sub(previous) as f32 / interval_secs) as u64;
+        SocketRates {
+            tcp_in_segs: rate(self.tcp_in_segs, prev.tcp_in_segs),
+            tcp_out_segs: rate(self.tcp_out_segs, prev.tcp_out_segs),
+            tcp_retrans_segs: rate(self.tcp_retrans_segs, prev.tcp_retrans_segs),
+            tcp_listen_overflows: rate(self.tcp_listen_overflows, prev.tcp_listen_overflows),
+            tcp_listen_drops: rate(self.tcp_listen_drops, prev.tcp_listen_drops),
+            tcp_syn_drops: rate(self.tcp_syn_drops, prev.tcp_syn_drops),
+            udp_in_datagrams: rate(self.udp_in_datagrams, prev.udp_in_datagrams),
+            udp_out_datagrams: rate(self.udp_out_datagrams, prev.udp_out_datagrams),
+            udp_errors: rate(self.udp_errors, prev.udp_errors),
+        }
+    }
+}
+
+// ===== Kernel Limits =====
+
+pub fn read_kernel_limits() -> Result<crate::event::KernelLimits> {
+    let file_nr = fs::read_to_string("/proc/sys/fs/file-nr").context("Failed to read /proc/sys/fs/file-nr")?;
+    let pid_max = fs::read_to_string("/proc/sys/kernel/pid_max").context("Failed to read /proc/sys/kernel/pid_max")?;
+    let loadavg = fs::read_to_string("/proc/loadavg").context("Failed to read /proc/loadavg")?;
+    let entropy = fs::read_to_string("/proc/sys/kernel/random/entropy_avail").ok();
+    parse_kernel_limits(&file_nr, &pid_max, &loadavg, entropy.as_deref())
+}
+
+fn parse_kernel_limits(
+    file_nr: &str,
+    pid_max: &str,
+    loadavg: &str,
+    entropy: Option<&str>,
+) -> Result<crate::event::KernelLimits> {
+    // "allocated unused max". Unused is always 0 on current kernels, but older ones kept freed handles.
+    let fields: Vec<u64> = file_nr.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+    let [allocated, unused, max] = fields[..] else {
+        anyhow::bail!("Invalid /proc/sys/fs/file-nr format");
+    };
+    // The fourth field of /proc/loadavg is "runnable/total" tasks
+    let tasks = loadavg
+        .split_whitespace()
+        .nth(3)
+        .and_then(|field| field.split_once('/'))
+        .and_then(|(_, total)| total.parse().ok())
+        .context("Invalid /proc/loadavg format")?;
+
+    Ok(crate::event::KernelLimits {
+        file_handles_allocated: allocated.saturating_sub(unused),
+        file_handles_max: max,
+        tasks,
+        pid_max: pid_max.trim().parse().context("Invalid /proc/sys/kernel/pid_max")?,
+        entropy_available: entropy.and_then(|e| e.trim().parse().ok()),
+    })
+}
+
+// ===== Pressure Stall Information =====
+
+/// avg10 pressure for CPU, memory and IO. None on kernels without PSI (before 4.20, or booted with psi=0).
+pub fn read_pressure() -> Option<crate::event::PressureMetrics> {
+    let read = |resource: &str| fs::read_to_string(format!("/proc/pressure/{}", resource)).ok();
+    let (cpu_some, cpu_full) = parse_pressure(&read("cpu")?)?;
+    let (memory_some, memory_full) = parse_pressure(&read("memory")?)?;
+    let (io_some, io_full) = parse_pressure(&read("io")?)?;
+    Some(crate::event::PressureMetrics {
+        cpu_some_avg10: cpu_some,
+        cpu_full_avg10: cpu_full,
+        memory_some_avg10: memory_some,
+        memory_full_avg10: memory_full?,
+        io_some_avg10: io_some,
+        io_full_avg10: io_full?,
+    })
+}
+
+// "some avg10=5.80 avg60=5.50 avg300=4.39 total=693736970" and the same for "full", which
+// older kernels leave out for CPU. Returns the avg10 of each.
+fn parse_pressure(content: &str) -> Option<(f32, Option<f32>)> {
+    let avg10 = |kind: &str| {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(kind)?.strip_prefix(' '))
+            .and_then(|fields| fields.split_whitespace().find_map(|field| field.strip_prefix("avg10=")))
+            .and_then(|value| value.parse().ok())
+    };
+    Some((avg10("some")?, avg10("full")))
+}
+
+// ===== Per-Process File Descriptor Limits =====
+
+// A process holding fewer fds than this is nowhere near any limit worth checking,
+// so its limits aren't read
+const FD_LIMIT_MIN_FDS: u32 = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessFdUsage {
+    pub pid: u32,
+    pub name: String,
+    pub open_fds: u32,
+    pub limit: u64, // Soft RLIMIT_NOFILE
+}
+
+impl ProcessFdUsage {
+    pub fn usage_percent(&self) -> f32 {
+        self.open_fds as f32 / self.limit.max(1) as f32 * 100.0
+    }
+}
+
+/// The `n` processes using the largest share of their open file limit. Every process's fd
+/// directory is counted, limits are only read for the ones holding FD_LIMIT_MIN_FDS or more.
+pub fn read_fd_usage(n: usize) -> Result<Vec<ProcessFdUsage>> {
+    let mut usage = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        // Exited, or another user's process without the privileges to look
+        let Ok(open_fds) = count_process_fds(pid) else {
+            continue;
+        };
+        if open_fds < FD_LIMIT_MIN_FDS {
+            continue;
+        }
+        let Some(limit) = fs::read_to_string(format!("/proc/{}/limits", pid))
+            .ok()
+            .and_then(|limits| parse_nofile_limit(&limits))
+        else {
+            continue;
+        };
+        let Ok(name) = read_process_name(pid) else {
+            continue;
+        };
+        usage.push(ProcessFdUsage { pid, name, open_fds, limit });
+    }
+    usage.sort_by(|a, b| b.usage_percent().total_cmp(&a.usage_percent()));
+    usage.truncate(n);
+    Ok(usage)
+}
+
+// Soft limit from the "Max open files" line of /proc/[pid]/limits, None if unlimited
+fn parse_nofile_limit(limits: &str) -> Option<u64> {
+    let line = limits.lines().find_map(|line| line.strip_prefix("Max open files"))?;
+    line.split_whitespace().next()?.parse().ok()
+}
+
 // ===== Per-Process Details =====
 
 #[derive(Debug, Clone)]
@@ -776,12 +1411,18 @@ pub struct ProcessDetail {
     pub cmdline: String,
     pub state: String,
     pub user: String,
-    pub cpu_time_jiffies: u64, // Total CPU time (user + system)
+    pub cpu_percent: f32, // Since the previous top processes sample, 0 for a new process
     pub mem_bytes: u64,
     pub read_bytes: u64,
     pub write_bytes: u64,
+    pub read_bytes_per_sec: u64,  // Since the previous top processes sample, like cpu_percent
+    pub write_bytes_per_sec: u64,
     pub num_fds: u32,
     pub num_threads: u32,
+    pub connection_count: u32,
+    pub connections: Vec<SocketConnection>, // Capped at MAX_CONNECTIONS_PER_PROCESS
+    pub container_id: Option<String>,
+    pub container_name: Option<String>,
 }
 
 pub fn read_process_details(pid: u32) -> Result<ProcessDetail> {
@@ -792,6 +1433,8 @@ pub fn read_process_details(pid: u32) -> Result<ProcessDetail> {
     let num_fds = count_process_fds(pid).unwrap_or(0);
     let num_threads = stat.num_threads;
     let user = read_process_user(pid).unwrap_or_else(|_| String::from("unknown"));
+    let container_id = read_process_container_id(pid);
+    let container_name = container_id.as_deref().and_then(resolve_container_name);
 
     Ok(ProcessDetail {
         pid,
@@ -799,12 +1442,18 @@ pub fn read_process_details(pid: u32) -> Result<ProcessDetail> {
         cmdline,
         state: stat.state,
         user,
-        cpu_time_jiffies: stat.utime + stat.stime,
+        cpu_percent: 0.0,
         mem_bytes: stat.rss_bytes,
         read_bytes: io.read_bytes,
         write_bytes: io.write_bytes,
+        read_bytes_per_sec: 0,
+        write_bytes_per_sec: 0,
         num_fds,
         num_threads,
+        connection_count: 0,
+        connections: Vec::new(),
+        container_id,
+        container_name,
     })
 }
 
@@ -853,21 +1502,16 @@ fn read_process_user(pid: u32) -> Result<String> {
     Ok("unknown".to_string())
 }
 
-fn read_process_uid(pid: u32) -> Result<u32> {
-    let status_path = format!("/proc/{}/status", pid);
-    let content = fs::read_to_string(&status_path).context("Failed to read status")?;
-
-    // Find Uid line: "Uid:\t1000\t1000\t1000\t1000"
-    for line in content.lines() {
-        if line.starts_with("Uid:") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                return parts[1].parse::<u32>().context("Parse UID");
-            }
-        }
-    }
-
-    anyhow::bail!("UID not found")
+// PPid and real Uid from /proc/[pid]/status
+fn parse_process_status(content: &str) -> (Option<u32>, Option<u32>) {
+    let field = |name: &str| {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|value| value.parse::<u32>().ok())
+    };
+    (field("PPid:"), field("Uid:"))
 }
 
 fn read_process_working_dir(pid: u32) -> Result<String> {
@@ -876,28 +1520,121 @@ fn read_process_working_dir(pid: u32) -> Result<String> {
     Ok(cwd.to_string_lossy().to_string())
 }
 
-fn resolve_uid_to_username(uid: u32) -> String {
-    // Cache for UID -> username mappings
-    static UID_CACHE: OnceLock<std::collections::HashMap<u32, String>> = OnceLock::new();
-
-    let cache = UID_CACHE.get_or_init(|| {
-        let mut map = std::collections::HashMap::new();
-        if let Ok(content) = fs::read_to_string("/etc/passwd") {
-            for line in content.lines() {
-                let parts: Vec<&str> = line.split(':').collect();
-                if parts.len() >= 3 {
-                    if let Ok(id) = parts[2].parse::<u32>() {
-                        map.insert(id, parts[0].to_string());
-                    }
-                }
+// ===== Containers =====
+
+// Container ID from /proc/[pid]/cgroup, None for processes on the host
+pub fn read_process_container_id(pid: u32) -> Option<String> {
+    let content = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    parse_container_id(&content)
+}
+
+// Recognises docker, podman (libpod), containerd and cri-o cgroup paths under
+// both cgroup v1 ("12:memory:/docker/<id>") and v2 ("0::/system.slice/docker-<id>.scope")
+fn parse_container_id(cgroup: &str) -> Option<String> {
+    for line in cgroup.lines() {
+        let Some(path) = line.splitn(3, ':').nth(2) else {
+            continue;
+        };
+
+        // Innermost matching path component wins
+        for component in path.rsplit('/') {
+            if component.starts_with("libpod-conmon-") {
+                continue;
+            }
+            let id = component.strip_suffix(".scope").unwrap_or(component);
+            let id = ["docker-", "libpod-", "cri-containerd-", "crio-"]
+                .iter()
+                .find_map(|prefix| id.strip_prefix(prefix))
+                .unwrap_or(id);
+            if id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Some(id.to_string());
             }
         }
-        map
-    });
+    }
+    None
+}
+
+static CONTAINER_NAMES: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+
+// Container name from the docker or podman state on disk (needs read access, usually root)
+pub fn resolve_container_name(container_id: &str) -> Option<String> {
+    let cache = CONTAINER_NAMES.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = cache.lock().ok()?;
+    if let Some(name) = cache.get(container_id) {
+        return name.clone();
+    }
+
+    let name = read_docker_container_name(container_id)
+        .or_else(|| read_podman_container_name(container_id));
+
+    // Containers come and go, don't let the cache grow without bound
+    if cache.len() >= 1024 {
+        cache.clear();
+    }
+    cache.insert(container_id.to_string(), name.clone());
+    name
+}
+
+fn read_docker_container_name(container_id: &str) -> Option<String> {
+    let path = format!("/var/lib/docker/containers/{}/config.v2.json", container_id);
+    let config: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    let name = config.get("Name")?.as_str()?.trim_start_matches('/');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn read_podman_container_name(container_id: &str) -> Option<String> {
+    let path = "/var/lib/containers/storage/overlay-containers/containers.json";
+    let containers: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    containers
+        .as_array()?
+        .iter()
+        .find(|c| c.get("id").and_then(|id| id.as_str()) == Some(container_id))?
+        .get("names")?
+        .as_array()?
+        .first()?
+        .as_str()
+        .map(|s| s.to_string())
+}
 
+static UID_NAMES: OnceLock<Mutex<HashMap<u32, String>>> = OnceLock::new();
+
+fn resolve_uid_to_username(uid: u32) -> String {
+    let cache = UID_NAMES.get_or_init(|| Mutex::new(read_passwd_names()));
+    let Ok(cache) = cache.lock() else {
+        return uid.to_string();
+    };
     cache.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
 }
 
+/// Reload the uid -> username cache, for when /etc/passwd has changed
+pub fn refresh_uid_names() {
+    let names = read_passwd_names();
+    let cache = UID_NAMES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Ok(mut cache) = cache.lock() {
+        *cache = names;
+    }
+}
+
+fn read_passwd_names() -> HashMap<u32, String> {
+    fs::read_to_string("/etc/passwd")
+        .map(|content| parse_passwd_names(&content))
+        .unwrap_or_default()
+}
+
+fn parse_passwd_names(content: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in content.lines() {
+        let parts: Vec<&str> = line.split(':').collect();
+        if parts.len() >= 3
+            && let Ok(id) = parts[2].parse::<u32>()
+        {
+            // First entry wins, like getpwuid
+            names.entry(id).or_insert_with(|| parts[0].to_string());
+        }
+    }
+    names
+}
+
 struct ProcessStat {
     ppid: u32,
     state: String,
@@ -905,6 +1642,8 @@ struct ProcessStat {
     stime: u64,
     rss_bytes: u64,
     num_threads: u32,
+    start_time: u64, // Clock ticks after boot, tells a reused pid apart
+    exit_status: Option<i32>, // Wait status, only meaningful once the process is a zombie (Linux 3.5+)
 }
 
 fn read_process_stat(pid: u32) -> Result<ProcessStat> {
@@ -927,7 +1666,9 @@ fn read_process_stat(pid: u32) -> Result<ProcessStat> {
         utime: parts[11].parse().unwrap_or(0),                   // Field 14
         stime: parts[12].parse().unwrap_or(0),                   // Field 15
         num_threads: parts[17].parse().unwrap_or(1),             // Field 20
+        start_time: parts[19].parse().unwrap_or(0),              // Field 22
         rss_bytes: parts[21].parse::<u64>().unwrap_or(0) * 4096, // Field 24 (pages to bytes)
+        exit_status: parts.get(49).and_then(|s| s.parse().ok()),  // Field 52
     })
 }
 
@@ -954,6 +1695,11 @@ fn read_process_io(pid: u32) -> Result<ProcessIo> {
     Ok(io)
 }
 
+// Bytes Black Box has written itself, mostly segments, so they can be left out of the disk spike check
+pub fn read_own_write_bytes() -> Option<u64> {
+    read_process_io(std::process::id()).ok().map(|io| io.write_bytes)
+}
+
 fn count_process_fds(pid: u32) -> Result<u32> {
     let fd_path = format!("/proc/{}/fd", pid);
     let count = fs::read_dir(&fd_path)
@@ -962,6 +1708,133 @@ fn count_process_fds(pid: u32) -> Result<u32> {
     Ok(count)
 }
 
+// ===== Per-Process Sockets =====
+
+pub const MAX_CONNECTIONS_PER_PROCESS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SocketConnection {
+    pub protocol: String, // tcp, tcp6, udp, udp6
+    pub local_port: u16,
+    pub remote_addr: String,
+    pub state: String,
+}
+
+// Socket inode -> connection, from /proc/net/{tcp,tcp6,udp,udp6}.
+// Built once per snapshot and shared across processes.
+pub fn read_socket_table() -> HashMap<u64, SocketConnection> {
+    let mut table = HashMap::new();
+    for protocol in ["tcp", "tcp6", "udp", "udp6"] {
+        if let Ok(content) = fs::read_to_string(format!("/proc/net/{}", protocol)) {
+            for line in content.lines().skip(1) {
+                if let Some((inode, conn)) = parse_socket_line(line, protocol) {
+                    table.insert(inode, conn);
+                }
+            }
+        }
+    }
+    table
+}
+
+fn parse_socket_line(line: &str, protocol: &str) -> Option<(u64, SocketConnection)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 10 {
+        return None;
+    }
+
+    let (_, local_port) = parse_proc_net_addr(parts[1])?;
+    let (remote_ip, remote_port) = parse_proc_net_addr(parts[2])?;
+    let inode: u64 = parts[9].parse().ok()?;
+    if inode == 0 {
+        return None;
+    }
+
+    let state = match (protocol.starts_with("udp"), parts[3]) {
+        (true, "07") => "UNCONN",
+        (_, "01") => "ESTABLISHED",
+        (_, "02") => "SYN_SENT",
+        (_, "03") => "SYN_RECV",
+        (_, "04") => "FIN_WAIT1",
+        (_, "05") => "FIN_WAIT2",
+        (_, "06") => "TIME_WAIT",
+        (_, "07") => "CLOSE",
+        (_, "08") => "CLOSE_WAIT",
+        (_, "09") => "LAST_ACK",
+        (_, "0A") => "LISTEN",
+        (_, "0B") => "CLOSING",
+        _ => "UNKNOWN",
+    };
+
+    Some((inode, SocketConnection {
+        protocol: protocol.to_string(),
+        local_port,
+        remote_addr: format!("{}:{}", remote_ip, remote_port),
+        state: state.to_string(),
+    }))
+}
+
+// Parse "hex_ip:hex_port" from /proc/net/* (IPv4 or IPv6, kernel byte order)
+fn parse_proc_net_addr(addr: &str) -> Option<(String, u16)> {
+    let (ip_hex, port_hex) = addr.split_once(':')?;
+    let port = u16::from_str_radix(port_hex, 16).ok()?;
+
+    let ip = match ip_hex.len() {
+        8 => {
+            let n = u32::from_str_radix(ip_hex, 16).ok()?;
+            std::net::Ipv4Addr::from(n.swap_bytes()).to_string()
+        }
+        32 => {
+            // Four 32-bit words, each in host (little-endian) order
+            let mut bytes = [0u8; 16];
+            for word in 0..4 {
+                let n = u32::from_str_radix(&ip_hex[word * 8..(word + 1) * 8], 16).ok()?;
+                bytes[word * 4..(word + 1) * 4].copy_from_slice(&n.swap_bytes().to_be_bytes());
+            }
+            std::net::Ipv6Addr::from(bytes).to_string()
+        }
+        _ => return None,
+    };
+
+    Some((ip, port))
+}
+
+// Match a process's socket fds against the socket table: (total, first few connections)
+pub fn read_process_connections(
+    pid: u32,
+    sockets: &HashMap<u64, SocketConnection>,
+) -> (u32, Vec<SocketConnection>) {
+    let entries = match fs::read_dir(format!("/proc/{}/fd", pid)) {
+        Ok(entries) => entries,
+        Err(_) => return (0, Vec::new()),
+    };
+
+    let mut count = 0u32;
+    let mut connections = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        let target = target.to_string_lossy();
+        let Some(inode) = target
+            .strip_prefix("socket:[")
+            .and_then(|s| s.strip_suffix(']'))
+            .and_then(|s| s.parse::<u64>().ok())
+        else {
+            continue;
+        };
+
+        // Unix domain and other non-inet sockets aren't in the table
+        if let Some(conn) = sockets.get(&inode) {
+            count += 1;
+            if connections.len() < MAX_CONNECTIONS_PER_PROCESS {
+                connections.push(conn.clone());
+            }
+        }
+    }
+
+    (count, connections)
+}
+
 // ===== Process Tracking =====
 
 #[derive(Debug, Clone)]
@@ -974,10 +1847,107 @@ pub struct ProcessInfo {
     pub user: Option<String>,
     pub uid: Option<u32>,
     pub state: String,
+    pub container_id: Option<String>,
+    pub container_name: Option<String>,
+    pub exit_code: Option<i32>, // Known when the process was last seen as a zombie
+    pub start_time: Option<u64>, // Clock ticks after boot, None for a fork only known from its parent
 }
 
 pub type ProcessSnapshot = HashMap<u32, ProcessInfo>;
 
+// Processes left out of lifecycle events and top process lists: Black Box itself, the commands
+// it runs (df, nvidia-smi, journalctl...) and anything in [process_tracking] ignore_processes
+#[derive(Debug, Clone)]
+pub struct ProcessFilter {
+    own_pid: u32,
+    ignore: Vec<String>,
+}
+
+impl ProcessFilter {
+    pub fn new(ignore: &[String]) -> Self {
+        Self::with_own_pid(std::process::id(), ignore)
+    }
+
+    pub fn with_own_pid(own_pid: u32, ignore: &[String]) -> Self {
+        Self { own_pid, ignore: ignore.iter().map(|entry| entry.trim().to_string()).collect() }
+    }
+
+    pub fn own_pid(&self) -> u32 {
+        self.own_pid
+    }
+
+    pub fn ignores(&self, info: &ProcessInfo) -> bool {
+        self.ignores_process(info.pid, info.ppid, &info.name, &info.cmdline)
+    }
+
+    // An entry matches the name, or the command line up to a space so "df" doesn't match "dfc"
+    fn ignores_process(&self, pid: u32, ppid: Option<u32>, name: &str, cmdline: &str) -> bool {
+        if pid == self.own_pid || ppid == Some(self.own_pid) {
+            return true;
+        }
+        self.ignore.iter().any(|entry| {
+            entry == name || cmdline.strip_prefix(entry.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        })
+    }
+}
+
+// Exit code from a wait status, with signals reported as 128+n like the shell does
+pub fn exit_code_from_wait_status(status: i32) -> i32 {
+    match status & 0x7f {
+        0 => (status >> 8) & 0xff,
+        signal => 128 + signal,
+    }
+}
+
+// Details captured once when a process is first seen
+#[derive(Debug, Clone, Default)]
+struct ProcessOrigin {
+    ppid: Option<u32>,
+    uid: Option<u32>,
+    user: Option<String>,
+    working_dir: Option<String>,
+}
+
+// Start-time ppid, uid/user and working directory for live processes. /proc/[pid]/status
+// and cwd are only read when a pid first appears, and the values stay with the process so
+// its Exited event reports what it started with.
+#[derive(Default)]
+pub struct ProcessOrigins {
+    origins: HashMap<u32, ProcessOrigin>,
+}
+
+impl ProcessOrigins {
+    pub fn apply(&mut self, snapshot: &mut ProcessSnapshot) {
+        // Exited processes keep their details in the previous snapshot
+        self.origins.retain(|pid, _| snapshot.contains_key(pid));
+
+        for (pid, info) in snapshot.iter_mut() {
+            let origin = self
+                .origins
+                .entry(*pid)
+                .or_insert_with(|| read_process_origin(*pid, info.ppid));
+            info.ppid = origin.ppid;
+            info.uid = origin.uid;
+            info.user = origin.user.clone();
+            info.working_dir = origin.working_dir.clone();
+        }
+    }
+}
+
+// Best effort: a process that has already exited just gets what the stat line gave us
+fn read_process_origin(pid: u32, stat_ppid: Option<u32>) -> ProcessOrigin {
+    let (status_ppid, uid) = fs::read_to_string(format!("/proc/{}/status", pid))
+        .map(|content| parse_process_status(&content))
+        .unwrap_or_default();
+
+    ProcessOrigin {
+        ppid: status_ppid.or(stat_ppid),
+        uid,
+        user: uid.map(resolve_uid_to_username),
+        working_dir: read_process_working_dir(pid).ok(),
+    }
+}
+
 pub fn read_processes() -> Result<ProcessSnapshot> {
     let mut processes = HashMap::new();
 
@@ -986,38 +1956,59 @@ pub fn read_processes() -> Result<ProcessSnapshot> {
         let name = entry.file_name();
         let name_str = name.to_string_lossy();
 
-        if let Ok(pid) = name_str.parse::<u32>() {
-            if let Ok(name) = read_process_name(pid) {
-                if let Ok(stat) = read_process_stat(pid) {
-                    // Read full command line (fallback to name if unavailable)
-                    let cmdline = read_process_cmdline(pid).unwrap_or_else(|_| name.clone());
-
-                    // Read additional process metadata (best effort)
-                    let working_dir = read_process_working_dir(pid).ok();
-                    let user = read_process_user(pid).ok();
-                    let uid = read_process_uid(pid).ok();
-
-                    processes.insert(
-                        pid,
-                        ProcessInfo {
-                            pid,
-                            ppid: Some(stat.ppid),
-                            name,
-                            cmdline,
-                            working_dir,
-                            user,
-                            uid,
-                            state: stat.state,
-                        },
-                    );
-                }
-            }
+        if let Ok(pid) = name_str.parse::<u32>()
+            && let Some(info) = read_process_info(pid)
+        {
+            processes.insert(pid, info);
         }
     }
 
     Ok(processes)
 }
 
+// One process from /proc, None if it has already gone
+fn read_process_info(pid: u32) -> Option<ProcessInfo> {
+    let name = read_process_name(pid).ok()?;
+    let stat = read_process_stat(pid).ok()?;
+
+    // Read full command line (fallback to name if unavailable)
+    let cmdline = read_process_cmdline(pid).unwrap_or_else(|_| name.clone());
+
+    // Read additional process metadata (best effort). The uid, user and
+    // working directory are filled in by ProcessOrigins for new processes only.
+    let container_id = read_process_container_id(pid);
+    let container_name = container_id.as_deref().and_then(resolve_container_name);
+
+    Some(ProcessInfo {
+        pid,
+        ppid: Some(stat.ppid),
+        name,
+        cmdline,
+        working_dir: None,
+        user: None,
+        uid: None,
+        exit_code: stat
+            .exit_status
+            .filter(|_| stat.state == "Z")
+            .map(exit_code_from_wait_status),
+        state: stat.state,
+        container_id,
+        container_name,
+        start_time: Some(stat.start_time),
+    })
+}
+
+// A newly started process with its start-time details, for event-driven tracking
+pub fn read_started_process(pid: u32) -> Option<ProcessInfo> {
+    let mut info = read_process_info(pid)?;
+    let origin = read_process_origin(pid, info.ppid);
+    info.ppid = origin.ppid;
+    info.uid = origin.uid;
+    info.user = origin.user;
+    info.working_dir = origin.working_dir;
+    Some(info)
+}
+
 #[derive(Debug)]
 pub struct ProcessDiff {
     pub started: Vec<ProcessInfo>,
@@ -1026,7 +2017,7 @@ pub struct ProcessDiff {
     pub zombie: Vec<ProcessInfo>,   // Z state
 }
 
-pub fn diff_processes(prev: &ProcessSnapshot, current: &ProcessSnapshot) -> ProcessDiff {
+pub fn diff_processes(prev: &ProcessSnapshot, current: &ProcessSnapshot, filter: &ProcessFilter) -> ProcessDiff {
     let mut started = Vec::new();
     let mut exited = Vec::new();
     let mut stuck = Vec::new();
@@ -1034,6 +2025,9 @@ pub fn diff_processes(prev: &ProcessSnapshot, current: &ProcessSnapshot) -> Proc
 
     // Find newly started processes and state changes
     for (pid, info) in current {
+        if filter.ignores(info) {
+            continue;
+        }
         if !prev.contains_key(pid) {
             started.push(info.clone());
         } else if let Some(prev_info) = prev.get(pid) {
@@ -1048,7 +2042,7 @@ pub fn diff_processes(prev: &ProcessSnapshot, current: &ProcessSnapshot) -> Proc
 
     // Find exited processes
     for (pid, info) in prev {
-        if !current.contains_key(pid) {
+        if !current.contains_key(pid) && !filter.ignores(info) {
             exited.push(info.clone());
         }
     }
@@ -1070,52 +2064,56 @@ pub struct LoggedInUser {
     pub remote_host: Option<String>,
 }
 
+// glibc's struct utmp on Linux, the same layout on 32 and 64-bit (times are 32-bit in both)
+const UTMP_RECORD_SIZE: usize = 384;
+const UTMP_USER_PROCESS: i32 = 7;
+
 pub fn read_logged_in_users() -> Result<Vec<LoggedInUser>> {
-    // Use 'w' command as it's more reliable than 'who' on some systems
-    let output = std::process::Command::new("w")
-        .args(["-h"]) // no header
-        .output()
-        .context("Failed to run w")?;
+    use std::os::unix::fs::MetadataExt;
 
-    let content = String::from_utf8_lossy(&output.stdout);
-    let mut users = Vec::new();
+    let data = match fs::read("/run/utmp").or_else(|_| fs::read("/var/run/utmp")) {
+        Ok(data) => data,
+        // Minimal containers have no utmp, and so nobody logged in
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read utmp"),
+    };
 
-    for line in content.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        // w output: USER TTY FROM LOGIN@ IDLE JCPU PCPU WHAT
-        if parts.len() >= 4 {
-            let terminal = parts[1].to_string();
-            let from = parts[2].to_string();
-
-            // Get full username via stat on the tty device (w truncates usernames)
-            let tty_path = if terminal.starts_with("pts/") {
-                format!("/dev/{}", terminal)
-            } else {
-                format!("/dev/{}", terminal)
-            };
-            let username = std::process::Command::new("stat")
-                .args(["-c", "%U", &tty_path])
-                .output()
-                .ok()
-                .and_then(|o| String::from_utf8(o.stdout).ok())
-                .map(|s| s.trim().to_string())
-                .unwrap_or_else(|| parts[0].to_string());
-
-            let remote_host = if from != "-" && !from.is_empty() {
-                Some(from)
-            } else {
-                None
-            };
+    let mut users = parse_utmp(&data);
+    for user in &mut users {
+        // The tty's owner, as w reported it; utmp keeps the name given at login
+        if let Ok(metadata) = fs::metadata(format!("/dev/{}", user.terminal)) {
+            user.username = resolve_uid_to_username(metadata.uid());
+        }
+    }
+
+    Ok(users)
+}
+
+// Login sessions (USER_PROCESS records) in a utmp file
+fn parse_utmp(data: &[u8]) -> Vec<LoggedInUser> {
+    // NUL-padded fixed-width strings
+    let field = |bytes: &[u8]| {
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+    };
 
-            users.push(LoggedInUser {
-                username,
-                terminal,
-                remote_host,
-            });
+    let mut users = Vec::new();
+    for record in data.chunks_exact(UTMP_RECORD_SIZE) {
+        let ut_type = i32::from_ne_bytes([record[0], record[1], record[2], record[3]]);
+        let username = field(&record[44..76]);
+        if ut_type != UTMP_USER_PROCESS || username.is_empty() {
+            continue;
         }
+        let remote_host = field(&record[76..332]);
+
+        users.push(LoggedInUser {
+            username,
+            terminal: field(&record[8..40]),
+            remote_host: (!remote_host.is_empty()).then_some(remote_host),
+        });
     }
 
-    Ok(users)
+    users
 }
 
 #[derive(Debug, Clone)]
@@ -1181,8 +2179,11 @@ fn parse_auth_log_line(line: &str) -> Option<AuthLogEntry> {
         return None;
     }
 
-    let rest = parts[3];
+    parse_auth_message(parts[3])
+}
 
+// Parse the "host ident[pid]: message" part of an auth log line (also used for journal entries)
+pub fn parse_auth_message(rest: &str) -> Option<AuthLogEntry> {
     let (event_type, user, source_ip) = if rest.contains("sshd") {
         if rest.contains("Accepted password") || rest.contains("Accepted publickey") {
             let user = extract_after(rest, "for ")?;
@@ -1236,103 +2237,375 @@ fn extract_after(text: &str, marker: &str) -> Option<String> {
 
 // ===== Port Scan Detection =====
 
+use crate::config::{Cidr, SecurityConfig};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const MAX_SAMPLED_SCAN_PORTS: usize = 10;
+
+// Tracks which local listening ports each remote IP has connected to over a sliding
+// window, and reports IPs that reach the configured number of distinct ports
 #[derive(Debug)]
 pub struct ConnectionTracker {
-    // Track connections per source IP to detect scanning
-    connections_per_ip: HashMap<String, Vec<u16>>, // IP -> ports attempted
+    threshold: usize,
+    window: Duration,
+    allowlist: Vec<Cidr>,
+    ports_per_ip: HashMap<IpAddr, HashMap<u16, Instant>>, // Remote IP -> local port -> last seen
+    reported: HashMap<IpAddr, Instant>, // So one scan is one alert per window, not one per tick
 }
 
 impl ConnectionTracker {
-    pub fn new() -> Self {
+    pub fn new(config: &SecurityConfig) -> Self {
         Self {
-            connections_per_ip: HashMap::new(),
+            threshold: config.port_scan_threshold,
+            window: Duration::from_secs(config.port_scan_window_secs),
+            allowlist: config.allowlist(),
+            ports_per_ip: HashMap::new(),
+            reported: HashMap::new(),
         }
     }
 
     pub fn update(&mut self) -> Result<Vec<String>> {
-        // Read current TCP connections
-        let mut new_connections: HashMap<String, Vec<u16>> = HashMap::new();
+        let tables: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .collect();
+        if tables.is_empty() {
+            anyhow::bail!("Failed to read /proc/net/tcp");
+        }
+        Ok(self.observe(&inbound_connections(&tables), Instant::now()))
+    }
 
-        if let Ok(content) = fs::read_to_string("/proc/net/tcp") {
-            for line in content.lines().skip(1) {
-                if let Some((src_ip, src_port)) = parse_tcp_line(line) {
-                    new_connections.entry(src_ip.clone())
-                        .or_insert_with(Vec::new)
-                        .push(src_port);
-                }
+    fn observe(&mut self, connections: &[(IpAddr, u16)], now: Instant) -> Vec<String> {
+        for &(ip, port) in connections {
+            // Loopback is this machine talking to itself
+            if ip.is_loopback() || self.allowlist.iter().any(|cidr| cidr.contains(ip)) {
+                continue;
             }
+            self.ports_per_ip.entry(ip).or_default().insert(port, now);
         }
 
-        // Detect potential port scans (many ports from same IP)
+        let window = self.window;
+        self.ports_per_ip.retain(|_, ports| {
+            ports.retain(|_, seen| now.duration_since(*seen) < window);
+            !ports.is_empty()
+        });
+        self.reported.retain(|_, at| now.duration_since(*at) < window);
+
         let mut alerts = Vec::new();
-        for (ip, ports) in &new_connections {
-            if ports.len() > 20 {
-                // Same IP connecting to 20+ different ports
-                alerts.push(format!("Potential port scan from {}: {} ports", ip, ports.len()));
+        for (ip, ports) in &self.ports_per_ip {
+            if ports.len() < self.threshold || self.reported.contains_key(ip) {
+                continue;
             }
+            let mut sampled: Vec<u16> = ports.keys().copied().collect();
+            sampled.sort_unstable();
+            let mut list: Vec<String> = sampled.iter().take(MAX_SAMPLED_SCAN_PORTS).map(|p| p.to_string()).collect();
+            if sampled.len() > MAX_SAMPLED_SCAN_PORTS {
+                list.push(format!("+{} more", sampled.len() - MAX_SAMPLED_SCAN_PORTS));
+            }
+            alerts.push(format!(
+                "Potential port scan from {}: {} local ports in {}s ({})",
+                ip,
+                ports.len(),
+                window.as_secs(),
+                list.join(", ")
+            ));
+            self.reported.insert(*ip, now);
         }
+        alerts.sort();
+        alerts
+    }
+}
+
+// (remote IP, local port) of inbound SYN_RECV and ESTABLISHED connections to local listening
+// ports, from /proc/net/tcp and tcp6 contents. Outbound connections come from ephemeral ports
+// nothing listens on, so a busy client talking to many remote ports isn't counted.
+fn inbound_connections(tables: &[String]) -> Vec<(IpAddr, u16)> {
+    let mut listening = std::collections::HashSet::new();
+    let mut wildcard_ports = std::collections::HashSet::new();
+    let mut candidates = Vec::new();
 
-        self.connections_per_ip = new_connections;
-        Ok(alerts)
+    for content in tables {
+        for line in content.lines().skip(1) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 4 {
+                continue;
+            }
+            let (Some(local), Some((remote_ip, _))) = (parse_socket_addr(parts[1]), parse_socket_addr(parts[2])) else {
+                continue;
+            };
+            match parts[3] {
+                "0A" if local.0.is_unspecified() => {
+                    wildcard_ports.insert(local.1);
+                }
+                "0A" => {
+                    listening.insert(local);
+                }
+                // 01 = ESTABLISHED, 03 = SYN_RECV (half-open, which is what most scanners leave)
+                "01" | "03" => candidates.push((local, remote_ip)),
+                _ => {}
+            }
+        }
     }
+
+    candidates
+        .into_iter()
+        .filter(|(local, _)| wildcard_ports.contains(&local.1) || listening.contains(local))
+        .map(|(local, remote_ip)| (remote_ip, local.1))
+        .collect()
 }
 
-fn parse_tcp_line(line: &str) -> Option<(String, u16)> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() < 3 {
-        return None;
+// IPv4-mapped IPv6 addresses on dual-stack sockets are folded back to IPv4
+pub fn parse_socket_addr(addr: &str) -> Option<(IpAddr, u16)> {
+    let (ip, port) = parse_proc_net_addr(addr)?;
+    Some((ip.parse::<IpAddr>().ok()?.to_canonical(), port))
+}
+
+// ===== Top Processes =====
+
+// USER_HZ, clock ticks per second in /proc/[pid]/stat. 100 on every mainstream Linux platform.
+const CLOCK_TICKS_PER_SEC: f32 = 100.0;
+
+// CPU usage between two readings of a process's cumulative CPU time, where 100 is one
+// core fully busy. Capped at all cores busy in case of clock skew between readings.
+pub fn process_cpu_percent(prev_jiffies: u64, jiffies: u64, elapsed_secs: f32, num_cpus: f32) -> f32 {
+    if elapsed_secs <= 0.0 {
+        return 0.0;
     }
+    let cpu_secs = jiffies.saturating_sub(prev_jiffies) as f32 / CLOCK_TICKS_PER_SEC;
+    ((cpu_secs / elapsed_secs) * 100.0).min(100.0 * num_cpus)
+}
 
-    // Remote address is in format: hex_ip:hex_port
-    let remote_addr = parts[2];
-    let addr_parts: Vec<&str> = remote_addr.split(':').collect();
-    if addr_parts.len() != 2 {
-        return None;
+// Bytes per second between two readings of a cumulative counter
+pub fn bytes_per_sec(prev: u64, current: u64, elapsed_secs: f32) -> u64 {
+    if elapsed_secs <= 0.0 {
+        return 0;
     }
+    (current.saturating_sub(prev) as f32 / elapsed_secs) as u64
+}
 
-    // Parse hex IP (stored in reverse byte order for IPv4)
-    let ip_hex = addr_parts[0];
-    if ip_hex.len() == 8 {
-        // IPv4
-        if let Ok(ip_num) = u32::from_str_radix(ip_hex, 16) {
-            let ip = format!(
-                "{}.{}.{}.{}",
-                ip_num & 0xFF,
-                (ip_num >> 8) & 0xFF,
-                (ip_num >> 16) & 0xFF,
-                (ip_num >> 24) & 0xFF
-            );
+// Cumulative counters of one process at the last top processes sample
+#[derive(Debug, Clone)]
+pub struct ProcessSample {
+    jiffies: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+    at: std::time::Instant,
+    start_time: u64,
+    container_id: Option<String>, // Kept while the same process has the pid, a cgroup doesn't change
+    ignored: bool, // Matched by the ProcessFilter, also kept for the life of the process
+}
 
-            let port = u16::from_str_radix(addr_parts[1], 16).ok()?;
-            return Some((ip, port));
-        }
+pub type ProcessSamples = HashMap<u32, ProcessSample>;
+
+pub struct TopProcesses {
+    pub processes: Vec<ProcessDetail>, // Union of the lists below, each process once
+    pub top_cpu_pids: Vec<u32>,        // Highest CPU first
+    pub top_mem_pids: Vec<u32>,        // Highest memory first
+    pub top_writer_pid: Option<u32>,   // Highest disk write rate, None if nothing wrote
+    pub total_write_bytes_per_sec: u64, // Summed over every process
+    pub per_user: Vec<UserUsage>,       // Every process grouped by owner, highest CPU first
+    pub per_container: Vec<ContainerUsage>, // Processes in containers grouped by container, highest CPU first
+}
+
+// Usage of one process, used to pick the top processes before reading their details
+struct ProcessUsage {
+    pid: u32,
+    uid: Option<u32>, // Owner of /proc/[pid], the effective uid
+    container_id: Option<String>,
+    cpu_percent: f32,
+    mem_bytes: u64,
+    read_bytes_per_sec: u64,
+    write_bytes_per_sec: u64,
+}
+
+// Pids of the top n processes by CPU and the top n by memory
+fn select_top_processes(usage: &mut [ProcessUsage], n: usize) -> (Vec<u32>, Vec<u32>) {
+    // Ties (mostly idle processes) go to the larger process so the lists overlap
+    usage.sort_by(|a, b| {
+        b.cpu_percent.total_cmp(&a.cpu_percent).then(b.mem_bytes.cmp(&a.mem_bytes))
+    });
+    let top_cpu = usage.iter().take(n).map(|u| u.pid).collect();
+
+    usage.sort_by_key(|u| std::cmp::Reverse(u.mem_bytes));
+    let top_mem = usage.iter().take(n).map(|u| u.pid).collect();
+
+    (top_cpu, top_mem)
+}
+
+// CPU, memory and process count summed per owner. Processes whose owner couldn't be read
+// are left out rather than lumped together.
+fn aggregate_user_usage(usage: &[ProcessUsage]) -> Vec<UserUsage> {
+    let mut by_uid: HashMap<u32, UserUsage> = HashMap::new();
+    for u in usage {
+        let Some(uid) = u.uid else {
+            continue;
+        };
+        let totals = by_uid.entry(uid).or_insert_with(|| UserUsage {
+            user: resolve_uid_to_username(uid),
+            uid,
+            cpu_percent: 0.0,
+            mem_bytes: 0,
+            process_count: 0,
+        });
+        totals.cpu_percent += u.cpu_percent;
+        totals.mem_bytes += u.mem_bytes;
+        totals.process_count += 1;
     }
 
-    None
+    let mut per_user: Vec<UserUsage> = by_uid.into_values().collect();
+    per_user.sort_by(|a, b| {
+        b.cpu_percent.total_cmp(&a.cpu_percent).then(b.mem_bytes.cmp(&a.mem_bytes)).then(a.uid.cmp(&b.uid))
+    });
+    per_user
 }
 
-// ===== Top Processes =====
+// CPU, memory and process count summed per container, host processes left out
+fn aggregate_container_usage(usage: &[ProcessUsage]) -> Vec<ContainerUsage> {
+    let mut by_id: HashMap<&str, ContainerUsage> = HashMap::new();
+    for u in usage {
+        let Some(id) = u.container_id.as_deref() else {
+            continue;
+        };
+        let totals = by_id.entry(id).or_insert_with(|| ContainerUsage {
+            container_id: id.to_string(),
+            container_name: resolve_container_name(id),
+            cpu_percent: 0.0,
+            mem_bytes: 0,
+            process_count: 0,
+        });
+        totals.cpu_percent += u.cpu_percent;
+        totals.mem_bytes += u.mem_bytes;
+        totals.process_count += 1;
+    }
+
+    let mut per_container: Vec<ContainerUsage> = by_id.into_values().collect();
+    per_container.sort_by(|a, b| {
+        b.cpu_percent
+            .total_cmp(&a.cpu_percent)
+            .then(b.mem_bytes.cmp(&a.mem_bytes))
+            .then_with(|| a.container_id.cmp(&b.container_id))
+    });
+    per_container
+}
 
-pub fn get_top_processes(n: usize) -> Result<Vec<ProcessDetail>> {
-    let mut processes = Vec::new();
+fn top_writer(usage: &[ProcessUsage]) -> Option<u32> {
+    usage
+        .iter()
+        .filter(|u| u.write_bytes_per_sec > 0)
+        .max_by_key(|u| u.write_bytes_per_sec)
+        .map(|u| u.pid)
+}
+
+// Top n processes by CPU and by memory, plus the busiest disk writer. CPU and I/O rates
+// are measured against `prev`, the previous sample, for every process; `prev` is
+// replaced with this sample.
+pub fn get_top_processes(n: usize, prev: &mut ProcessSamples, num_cpus: f32, filter: &ProcessFilter) -> Result<TopProcesses> {
+    use std::os::unix::fs::MetadataExt;
 
+    let now = std::time::Instant::now();
+    let mut usage = Vec::new();
+    let mut samples = ProcessSamples::new();
+
+    // Only stat and io for every process, the rest is read for the ones we keep
     for entry in fs::read_dir("/proc")? {
         let entry = entry?;
-        let name = entry.file_name();
-        let name_str = name.to_string_lossy();
-
-        if let Ok(pid) = name_str.parse::<u32>() {
-            if let Ok(detail) = read_process_details(pid) {
-                processes.push(detail);
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let Ok(stat) = read_process_stat(pid) else {
+            continue;
+        };
+        let io = read_process_io(pid).unwrap_or_default();
+        let uid = entry.metadata().ok().map(|m| m.uid());
+        let (container_id, ignored) = match prev.get(&pid) {
+            Some(prev_sample) if prev_sample.start_time == stat.start_time => {
+                (prev_sample.container_id.clone(), prev_sample.ignored)
             }
+            _ => {
+                let name = read_process_name(pid).unwrap_or_default();
+                let cmdline = read_process_cmdline(pid).unwrap_or_else(|_| name.clone());
+                (read_process_container_id(pid), filter.ignores_process(pid, Some(stat.ppid), &name, &cmdline))
+            }
+        };
+
+        let sample = ProcessSample {
+            jiffies: stat.utime + stat.stime,
+            read_bytes: io.read_bytes,
+            write_bytes: io.write_bytes,
+            at: now,
+            start_time: stat.start_time,
+            container_id: container_id.clone(),
+            ignored,
+        };
+        // Still sampled so the match isn't repeated, but left out of every total and list
+        if ignored {
+            samples.insert(pid, sample);
+            continue;
+        }
+        let mut process_usage = ProcessUsage {
+            pid,
+            uid,
+            container_id,
+            cpu_percent: 0.0,
+            mem_bytes: stat.rss_bytes,
+            read_bytes_per_sec: 0,
+            write_bytes_per_sec: 0,
+        };
+        if let Some(prev_sample) = prev.get(&pid) {
+            let elapsed_secs = now.duration_since(prev_sample.at).as_secs_f32();
+            process_usage.cpu_percent =
+                process_cpu_percent(prev_sample.jiffies, sample.jiffies, elapsed_secs, num_cpus);
+            process_usage.read_bytes_per_sec =
+                bytes_per_sec(prev_sample.read_bytes, sample.read_bytes, elapsed_secs);
+            process_usage.write_bytes_per_sec =
+                bytes_per_sec(prev_sample.write_bytes, sample.write_bytes, elapsed_secs);
         }
+        samples.insert(pid, sample);
+        usage.push(process_usage);
     }
-
-    // Sort by memory usage (descending)
-    processes.sort_by(|a, b| b.mem_bytes.cmp(&a.mem_bytes));
-    processes.truncate(n);
-
-    Ok(processes)
+    *prev = samples;
+
+    let total_write_bytes_per_sec = usage.iter().map(|u| u.write_bytes_per_sec).sum();
+    let per_user = aggregate_user_usage(&usage);
+    let per_container = aggregate_container_usage(&usage);
+    let top_writer_pid = top_writer(&usage);
+    let (mut top_cpu_pids, mut top_mem_pids) = select_top_processes(&mut usage, n);
+    let usage_by_pid: HashMap<u32, &ProcessUsage> = usage.iter().map(|u| (u.pid, u)).collect();
+
+    // Only resolve details and sockets for the processes we keep
+    let sockets = read_socket_table();
+    let mut processes: Vec<ProcessDetail> = Vec::new();
+    for &pid in top_cpu_pids.iter().chain(&top_mem_pids).chain(&top_writer_pid) {
+        if processes.iter().any(|p| p.pid == pid) {
+            continue;
+        }
+        // Exited since its stat was read
+        let Ok(mut detail) = read_process_details(pid) else {
+            continue;
+        };
+        if let Some(u) = usage_by_pid.get(&pid) {
+            detail.cpu_percent = u.cpu_percent;
+            detail.read_bytes_per_sec = u.read_bytes_per_sec;
+            detail.write_bytes_per_sec = u.write_bytes_per_sec;
+        }
+        let (count, connections) = read_process_connections(pid, &sockets);
+        detail.connection_count = count;
+        detail.connections = connections;
+        processes.push(detail);
+    }
+    top_cpu_pids.retain(|pid| processes.iter().any(|p| p.pid == *pid));
+    top_mem_pids.retain(|pid| processes.iter().any(|p| p.pid == *pid));
+
+    Ok(TopProcesses {
+        top_writer_pid: top_writer_pid.filter(|pid| processes.iter().any(|p| p.pid == *pid)),
+        processes,
+        top_cpu_pids,
+        top_mem_pids,
+        total_write_bytes_per_sec,
+        per_user,
+        per_container,
+    })
 }
 
 // ===== Temperature Monitoring =====
@@ -1567,7 +2840,7 @@ struct CachedDiskTemp {
     last_update: std::time::Instant,
 }
 
-fn get_physical_disks() -> Result<Vec<String>> {
+pub fn get_physical_disks() -> Result<Vec<String>> {
     let content = fs::read_to_string("/proc/diskstats")?;
     let mut disks = Vec::new();
 
@@ -1772,6 +3045,49 @@ pub fn check_sudoers_changes() -> Result<Option<String>> {
     Ok(None)
 }
 
+// ===== Network Configuration Monitoring =====
+
+// Hash of the resolv.conf contents and the nameservers parsed from it
+static DNS_CONFIG: OnceLock<Mutex<(u64, Vec<String>)>> = OnceLock::new();
+static DEFAULT_GATEWAY: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+
+// Returns the (old, new) nameserver lists when they change. Edits that leave the
+// servers alone (comments, search domains) only update the stored hash.
+pub fn check_dns_changes() -> Result<Option<(Vec<String>, Vec<String>)>> {
+    let Some(content) = read_resolv_conf() else {
+        return Ok(None); // File not readable, skip check
+    };
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    let current_hash = hasher.finish();
+
+    let mutex = DNS_CONFIG.get_or_init(|| Mutex::new((current_hash, parse_nameservers(&content))));
+    let mut last = mutex.lock().unwrap();
+
+    if last.0 == current_hash {
+        return Ok(None);
+    }
+    let servers = parse_nameservers(&content);
+    let previous = std::mem::replace(&mut *last, (current_hash, servers.clone())).1;
+
+    Ok((previous != servers).then_some((previous, servers)))
+}
+
+// Returns the (old, new) default gateway when it changes, None meaning no default route
+pub fn check_gateway_changes() -> Option<(Option<String>, Option<String>)> {
+    let current = get_default_gateway();
+
+    let mutex = DEFAULT_GATEWAY.get_or_init(|| Mutex::new(current.clone()));
+    let mut last = mutex.lock().unwrap();
+
+    if *last != current {
+        let previous = std::mem::replace(&mut *last, current.clone());
+        return Some((previous, current));
+    }
+
+    None
+}
+
 // ===== Listening Port Monitoring =====
 
 static LISTENING_PORTS: OnceLock<Mutex<std::collections::HashSet<(String, u16)>>> = OnceLock::new();
@@ -2187,6 +3503,20 @@ pub fn is_sensitive_file_path(path: &str) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_nameservers() {
+        let content = "# Generated by NetworkManager\nsearch lan\nnameserver 192.168.1.1\n  nameserver\t1.1.1.1\nnameservers 9.9.9.9\n#nameserver 8.8.8.8\nnameserver 2606:4700:4700::1111\noptions edns0\n";
+        assert_eq!(parse_nameservers(content), vec!["192.168.1.1", "1.1.1.1", "2606:4700:4700::1111"]);
+        assert!(parse_nameservers("search lan\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_boot_time() {
+        let stat = "cpu  4705 356 584 3699 23 23 0 0 0 0\nintr 1462898\nctxt 3196381\nbtime 1767604080\nprocesses 12345\n";
+        assert_eq!(parse_boot_time(stat), Some(1_767_604_080));
+        assert_eq!(parse_boot_time("cpu 1 2 3\n"), None);
+    }
+
     #[test]
     fn test_parse_auth_log_line_ssh_success_password() {
         let line = "Jan 15 10:23:45 server sshd[1234]: Accepted password for ubuntu from 192.168.1.100 port 54321 ssh2";
@@ -2264,46 +3594,116 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_tcp_line_valid() {
-        // Format: local_address:port remote_address:port state...
-        // 0100007F = 127.0.0.1 in hex (reversed bytes)
-        // 1F90 = 8080 in hex
-        let line = "   1: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 12345 1 0000000000000000 100 0 0 10 0";
-        let result = parse_tcp_line(line);
+    fn test_parse_socket_addr_valid() {
+        // 0100007F = 127.0.0.1 in hex (reversed bytes), 1F90 = 8080
+        assert_eq!(parse_socket_addr("0100007F:1F90"), Some(("127.0.0.1".parse().unwrap(), 8080)));
+        assert_eq!(parse_socket_addr("00000000:0000"), Some(("0.0.0.0".parse().unwrap(), 0)));
+    }
+
+    #[test]
+    fn test_parse_socket_addr_specific_ip() {
+        // C0A80164 read in reverse byte order
+        assert_eq!(parse_socket_addr("C0A80164:01BB"), Some(("100.1.168.192".parse().unwrap(), 443)));
+        // ::ffff:192.168.1.100 from a dual-stack socket in /proc/net/tcp6
+        assert_eq!(
+            parse_socket_addr("0000000000000000FFFF00006401A8C0:01BB"),
+            Some(("192.168.1.100".parse().unwrap(), 443))
+        );
+    }
 
-        assert!(result.is_some());
-        let (ip, port) = result.unwrap();
-        assert_eq!(ip, "0.0.0.0");
-        assert_eq!(port, 0);
+    #[test]
+    fn test_parse_socket_addr_invalid() {
+        assert_eq!(parse_socket_addr("invalid"), None);
+        assert_eq!(parse_socket_addr("0100007F"), None);
+        assert_eq!(parse_socket_addr("0100007F:ZZZZ"), None);
     }
 
     #[test]
-    fn test_parse_tcp_line_specific_ip() {
-        // C0A80164 = 192.168.1.100 in hex (reversed bytes: 100.1.168.192 -> reverse each byte)
-        let line = "   1: 0100007F:1F90 C0A80164:01BB 01 00000000:00000000 00:00000000 00000000     0        0 12345 1 0000000000000000 100 0 0 10 0";
-        let result = parse_tcp_line(line);
+    fn test_parse_net_dev_per_interface() {
+        let content = "Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  123456     100    0    0    0     0          0         0   123456     100    0    0    0     0       0          0
+  eth0: 1000000    2000    1    2    0     0          0         0   500000    1500    3    4    0     0       0          0
+   wg0:    4000      40    0    0    0     0          0         0     8000      80    0    1    0     0       0          0
+";
+        let interfaces = parse_net_dev(content);
+
+        assert_eq!(interfaces.len(), 2);
+        assert!(!interfaces.contains_key("lo"));
+        let eth0 = &interfaces["eth0"];
+        assert_eq!(eth0.recv_bytes, 1000000);
+        assert_eq!(eth0.send_bytes, 500000);
+        assert_eq!(eth0.recv_packets, 2000);
+        assert_eq!(eth0.send_packets, 1500);
+        assert_eq!(eth0.recv_errors, 1);
+        assert_eq!(eth0.recv_drops, 2);
+        assert_eq!(eth0.send_errors, 3);
+        assert_eq!(eth0.send_drops, 4);
+        assert_eq!(interfaces["wg0"].send_drops, 1);
+    }
 
-        assert!(result.is_some());
-        let (ip, port) = result.unwrap();
-        // The function parses in reverse byte order
-        assert_eq!(ip, "100.1.168.192");
-        assert_eq!(port, 443); // 01BB = 443
+    #[test]
+    fn test_per_interface_rates() {
+        let mut prev = HashMap::new();
+        prev.insert("eth0".to_string(), InterfaceStats { recv_bytes: 1000, send_bytes: 2000, recv_packets: 10, ..Default::default() });
+        let mut current = HashMap::new();
+        current.insert("eth0".to_string(), InterfaceStats { recv_bytes: 3000, send_bytes: 2500, recv_packets: 30, ..Default::default() });
+        // New interface without a previous sample is skipped
+        current.insert("wg0".to_string(), InterfaceStats { recv_bytes: 10, ..Default::default() });
+
+        let rates = per_interface_rates(&current, &prev, 2.0);
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].0, "eth0");
+        assert_eq!(rates[0].1.recv_bytes, 1000);
+        assert_eq!(rates[0].1.send_bytes, 250);
+        assert_eq!(rates[0].1.recv_packets, 10);
     }
 
     #[test]
-    fn test_parse_tcp_line_invalid() {
-        let line = "invalid tcp line";
-        let result = parse_tcp_line(line);
+    fn test_parse_container_id() {
+        let id = "3f4e8a1b2c3d4e5f60718293a4b5c6d7e8f90123456789abcdef0123456789ab";
+
+        let docker_v2 = format!("0::/system.slice/docker-{}.scope\n", id);
+        assert_eq!(parse_container_id(&docker_v2).as_deref(), Some(id));
 
-        assert!(result.is_none());
+        let docker_v1 = format!("12:pids:/docker/{}\n11:memory:/docker/{}\n", id, id);
+        assert_eq!(parse_container_id(&docker_v1).as_deref(), Some(id));
+
+        let podman = format!("0::/user.slice/user-1000.slice/user@1000.service/user.slice/libpod-{}.scope/container\n", id);
+        assert_eq!(parse_container_id(&podman).as_deref(), Some(id));
+
+        let kube = format!("0::/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod1234.slice/cri-containerd-{}.scope\n", id);
+        assert_eq!(parse_container_id(&kube).as_deref(), Some(id));
+
+        assert_eq!(parse_container_id("0::/user.slice/user-1000.slice/session-2.scope\n"), None);
+        assert_eq!(parse_container_id(""), None);
+    }
+
+    #[test]
+    fn test_parse_socket_line_ipv4() {
+        let line = "   1: 0100007F:1F90 0A01A8C0:C350 01 00000000:00000000 00:00000000 00000000  1000        0 123456 1 0000000000000000 20 4 30 10 -1";
+        let (inode, conn) = parse_socket_line(line, "tcp").unwrap();
+        assert_eq!(inode, 123456);
+        assert_eq!(conn.local_port, 8080);
+        assert_eq!(conn.remote_addr, "192.168.1.10:50000");
+        assert_eq!(conn.state, "ESTABLISHED");
     }
 
     #[test]
-    fn test_parse_tcp_line_insufficient_fields() {
-        let line = "   1: 0100007F:1F90";
-        let result = parse_tcp_line(line);
+    fn test_parse_socket_line_ipv6_listen() {
+        let line = "   0: 00000000000000000000000001000000:0016 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2222 1 0000000000000000 100 0 0 10 0";
+        let (inode, conn) = parse_socket_line(line, "tcp6").unwrap();
+        assert_eq!(inode, 2222);
+        assert_eq!(conn.local_port, 22);
+        assert_eq!(conn.remote_addr, ":::0");
+        assert_eq!(conn.state, "LISTEN");
+    }
 
-        assert!(result.is_none());
+    #[test]
+    fn test_parse_proc_net_addr_ipv6_loopback() {
+        let (ip, port) = parse_proc_net_addr("00000000000000000000000001000000:1F90").unwrap();
+        assert_eq!(ip, "::1");
+        assert_eq!(port, 8080);
     }
 
     #[test]
@@ -2387,6 +3787,10 @@ mod tests {
             available_kb: 10000000,
             buffers_kb: 1000000,
             cached_kb: 3000000,
+            dirty_kb: 0,
+            writeback_kb: 0,
+            hugepages_total: 0,
+            hugepages_free: 0,
         };
 
         // Used = total - (free + buffers + cached)
@@ -2394,6 +3798,34 @@ mod tests {
         assert_eq!(stats.used_kb(), 10000000);
     }
 
+    #[test]
+    fn test_parse_meminfo_breakdown() {
+        let content = "MemTotal:       16318256 kB
+MemFree:         1184320 kB
+MemAvailable:    9876544 kB
+Buffers:          412304 kB
+Cached:          8012736 kB
+SwapCached:         1024 kB
+Active:          6254080 kB
+Dirty:            183452 kB
+Writeback:          2048 kB
+WritebackTmp:          0 kB
+HugePages_Total:     512
+HugePages_Free:      100
+HugePages_Rsvd:        0
+Hugepagesize:       2048 kB
+";
+        let stats = parse_meminfo(content).unwrap();
+        assert_eq!(stats.total_kb, 16318256);
+        assert_eq!(stats.available_kb, 9876544);
+        assert_eq!(stats.buffers_kb, 412304);
+        assert_eq!(stats.cached_kb, 8012736);
+        assert_eq!(stats.dirty_kb, 183452);
+        assert_eq!(stats.writeback_kb, 2048);
+        assert_eq!(stats.hugepages_total, 512);
+        assert_eq!(stats.hugepages_free, 100);
+    }
+
     #[test]
     fn test_memory_stats_usage_percent() {
         let stats = MemoryStats {
@@ -2402,6 +3834,10 @@ mod tests {
             available_kb: 5000,
             buffers_kb: 1000,
             cached_kb: 2000,
+            dirty_kb: 0,
+            writeback_kb: 0,
+            hugepages_total: 0,
+            hugepages_free: 0,
         };
 
         // Used = 10000 - (2000 + 1000 + 2000) = 5000
@@ -2409,4 +3845,592 @@ mod tests {
         let usage = stats.usage_percent();
         assert!((usage - 50.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_read_core_frequencies_from_sysfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_core = |name: &str, cur: &str, max: &str| {
+            let cpufreq = dir.path().join(name).join("cpufreq");
+            fs::create_dir_all(&cpufreq).unwrap();
+            fs::write(cpufreq.join("scaling_cur_freq"), cur).unwrap();
+            fs::write(cpufreq.join("scaling_max_freq"), max).unwrap();
+        };
+        write_core("cpu10", "1200000\n", "4000000\n");
+        write_core("cpu2", "4000000\n", "4000000\n");
+        // Siblings of the core directories that must be skipped
+        fs::create_dir_all(dir.path().join("cpufreq")).unwrap();
+        fs::create_dir_all(dir.path().join("cpu3")).unwrap();
+
+        let freqs = read_core_frequencies_from(dir.path()).unwrap();
+        assert_eq!(freqs, vec![
+            CoreFrequency { cur_mhz: 4000, max_mhz: 4000 },
+            CoreFrequency { cur_mhz: 1200, max_mhz: 4000 },
+        ]);
+        assert_eq!(freq_percent_of_max(&freqs), Some(65.0));
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(read_core_frequencies_from(empty.path()).is_none());
+    }
+
+    #[test]
+    fn test_process_filter_matches_name_cmdline_and_own_children() {
+        let filter = ProcessFilter::with_own_pid(50, &["df".to_string(), "/usr/bin/python3 /opt/poller.py".to_string()]);
+        let process = |pid, ppid, name: &str, cmdline: &str| ProcessInfo {
+            pid,
+            ppid: Some(ppid),
+            name: name.to_string(),
+            cmdline: cmdline.to_string(),
+            working_dir: None,
+            user: None,
+            uid: None,
+            state: "S".to_string(),
+            container_id: None,
+            container_name: None,
+            exit_code: None,
+            start_time: None,
+        };
+
+        assert!(filter.ignores(&process(50, 1, "black-box", "./black-box")));
+        assert!(filter.ignores(&process(60, 50, "nvidia-smi", "nvidia-smi --query-gpu=name")));
+        assert!(filter.ignores(&process(70, 1, "df", "/usr/bin/df -B1 /")));
+        assert!(filter.ignores(&process(71, 1, "python3", "/usr/bin/python3 /opt/poller.py --once")));
+        assert!(!filter.ignores(&process(72, 1, "python3", "/usr/bin/python3 /opt/poller.py.bak")));
+        assert!(!filter.ignores(&process(73, 1, "dfc", "dfc -a")));
+
+        let mut prev = ProcessSnapshot::new();
+        prev.insert(70, process(70, 1, "df", "df -h"));
+        prev.insert(80, process(80, 1, "sleep", "sleep 5"));
+        let mut current = ProcessSnapshot::new();
+        current.insert(60, process(60, 50, "df", "df -B1 /"));
+        current.insert(90, process(90, 1, "bash", "bash"));
+        let diff = diff_processes(&prev, &current, &filter);
+        assert_eq!(diff.started.iter().map(|p| p.pid).collect::<Vec<_>>(), vec![90]);
+        assert_eq!(diff.exited.iter().map(|p| p.pid).collect::<Vec<_>>(), vec![80]);
+    }
+
+    #[test]
+    fn test_process_origin_fields() {
+        let status = "Name:\tbash\nUmask:\t0022\nState:\tS (sleeping)\nTgid:\t4242\nPid:\t4242\nPPid:\t4100\nTracerPid:\t0\nUid:\t1000\t1000\t1000\t1000\n";
+        assert_eq!(parse_process_status(status), (Some(4100), Some(1000)));
+        assert_eq!(parse_process_status(""), (None, None));
+
+        assert_eq!(exit_code_from_wait_status(0), 0);
+        assert_eq!(exit_code_from_wait_status(3 << 8), 3);
+        assert_eq!(exit_code_from_wait_status(9), 137); // SIGKILL
+
+        // Our own process: origin is read once and sticks even if the snapshot is re-read
+        let pid = std::process::id();
+        let mut origins = ProcessOrigins::default();
+        let mut snapshot = read_processes().unwrap();
+        origins.apply(&mut snapshot);
+        let me = &snapshot[&pid];
+        assert!(me.uid.is_some());
+        assert_eq!(me.working_dir, std::env::current_dir().ok().map(|d| d.to_string_lossy().to_string()));
+
+        let mut next = read_processes().unwrap();
+        origins.apply(&mut next);
+        assert_eq!(next[&pid].working_dir, me.working_dir);
+    }
+
+    #[test]
+    fn test_process_cpu_percent() {
+        // 50 jiffies over one second is half a core
+        assert_eq!(process_cpu_percent(1000, 1050, 1.0, 4.0), 50.0);
+        // Multi-threaded processes can use more than one core, up to all of them
+        assert_eq!(process_cpu_percent(0, 300, 1.0, 4.0), 300.0);
+        assert_eq!(process_cpu_percent(0, 1000, 1.0, 4.0), 400.0);
+        // Pid reused by a process with less CPU time, or no time elapsed
+        assert_eq!(process_cpu_percent(500, 100, 1.0, 4.0), 0.0);
+        assert_eq!(process_cpu_percent(0, 100, 0.0, 4.0), 0.0);
+    }
+
+    #[test]
+    fn test_select_top_processes_by_cpu_and_memory() {
+        let usage = |pid, cpu_percent, mem_bytes| ProcessUsage {
+            pid,
+            uid: None,
+            container_id: None,
+            cpu_percent,
+            mem_bytes,
+            read_bytes_per_sec: 0,
+            write_bytes_per_sec: 0,
+        };
+        let mut processes = vec![
+            usage(1, 0.0, 900),
+            usage(2, 95.0, 10), // Small but busy, missed when only memory was considered
+            usage(3, 0.0, 800),
+            usage(4, 20.0, 50),
+            usage(5, 0.0, 700),
+        ];
+
+        let (top_cpu, top_mem) = select_top_processes(&mut processes, 3);
+        assert_eq!(top_cpu, vec![2, 4, 1]);
+        assert_eq!(top_mem, vec![1, 3, 5]);
+        assert_eq!(top_writer(&processes), None);
+
+        for p in &mut processes {
+            p.write_bytes_per_sec = if p.pid == 5 { 5_000_000 } else { 1_000 };
+        }
+        assert_eq!(top_writer(&processes), Some(5));
+    }
+
+    #[test]
+    fn test_aggregate_user_usage() {
+        let usage = |pid, uid, cpu_percent, mem_bytes| ProcessUsage {
+            pid,
+            uid,
+            container_id: None,
+            cpu_percent,
+            mem_bytes,
+            read_bytes_per_sec: 0,
+            write_bytes_per_sec: 0,
+        };
+        // Uids far outside any real passwd so they resolve to themselves
+        let processes = vec![
+            usage(1, Some(4_000_000_001), 10.0, 100),
+            usage(2, Some(4_000_000_002), 50.0, 10),
+            usage(3, Some(4_000_000_001), 5.0, 300),
+            usage(4, None, 99.0, 999),
+        ];
+
+        let per_user = aggregate_user_usage(&processes);
+        let summary: Vec<(&str, f32, u64, u32)> = per_user
+            .iter()
+            .map(|u| (u.user.as_str(), u.cpu_percent, u.mem_bytes, u.process_count))
+            .collect();
+        assert_eq!(summary, vec![("4000000002", 50.0, 10, 1), ("4000000001", 15.0, 400, 2)]);
+    }
+
+    #[test]
+    fn test_aggregate_container_usage() {
+        // Ids no docker or podman state will have a name for
+        let (web, db) = ("a".repeat(64), "b".repeat(64));
+        let usage = |pid, container_id: Option<&String>, cpu_percent, mem_bytes| ProcessUsage {
+            pid,
+            uid: Some(0),
+            container_id: container_id.cloned(),
+            cpu_percent,
+            mem_bytes,
+            read_bytes_per_sec: 0,
+            write_bytes_per_sec: 0,
+        };
+        let processes = vec![
+            usage(1, Some(&web), 5.0, 100),
+            usage(2, Some(&db), 40.0, 2000),
+            usage(3, Some(&web), 20.0, 300),
+            usage(4, None, 99.0, 999),
+        ];
+
+        let per_container = aggregate_container_usage(&processes);
+        let summary: Vec<(&str, f32, u64, u32)> = per_container
+            .iter()
+            .map(|c| (c.container_id.as_str(), c.cpu_percent, c.mem_bytes, c.process_count))
+            .collect();
+        assert_eq!(summary, vec![(db.as_str(), 40.0, 2000, 1), (web.as_str(), 25.0, 400, 2)]);
+        assert!(per_container.iter().all(|c| c.container_name.is_none()));
+    }
+
+    #[test]
+    fn test_parse_passwd_names() {
+        let names = parse_passwd_names(
+            "root:x:0:0:root:/root:/bin/bash\n# comment\nalice:x:1000:1000::/home/alice:/bin/sh\ntoor:x:0:0::/root:/bin/sh\n",
+        );
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[&0], "root");
+        assert_eq!(names[&1000], "alice");
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi_gpus() {
+        // nvidia-smi --query-gpu=index,name,clocks.gr,clocks.mem,temperature.gpu,power.draw,memory.used,memory.total
+        let output = "0, NVIDIA GeForce RTX 3090, 1695, 9751, 45, 120.52, 1024, 24576\n\
+                      1, NVIDIA RTX A6000, 210, 405, 31, [N/A], 3, 49140\n";
+        let gpus = parse_nvidia_smi_gpus(output);
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(
+            gpus[0],
+            GpuDevice {
+                index: 0,
+                name: "NVIDIA GeForce RTX 3090".to_string(),
+                gpu_freq_mhz: Some(1695),
+                mem_freq_mhz: Some(9751),
+                temp_celsius: Some(45.0),
+                power_watts: Some(120.52),
+                vram_used_bytes: Some(1024 * 1024 * 1024),
+                vram_total_bytes: Some(24576 * 1024 * 1024),
+            }
+        );
+        assert_eq!(gpus[1].name, "NVIDIA RTX A6000");
+        assert_eq!(gpus[1].power_watts, None);
+        assert!(parse_nvidia_smi_gpus("NVIDIA-SMI has failed because it couldn't communicate with the NVIDIA driver.").is_empty());
+    }
+
+    #[test]
+    fn test_parse_rocm_smi_gpus() {
+        // rocm-smi --showproductname --showclocks --showtemp --showpower --showmeminfo vram --json
+        let output = r#"{
+            "card1": {"Temperature (Sensor edge) (C)": "52.0", "Temperature (Sensor junction) (C)": "60.0",
+                      "Current Socket Graphics Package Power (W)": "210.0", "sclk clock speed:": "(2250Mhz)",
+                      "mclk clock speed:": "(1000Mhz)", "VRAM Total Memory (B)": "25753026560",
+                      "VRAM Total Used Memory (B)": "8589934592", "Card series": "Navi 31 [Radeon RX 7900 XT/7900 XTX]"},
+            "card0": {"Temperature (Sensor edge) (C)": "35.0", "Temperature (Sensor junction) (C)": "38.0",
+                      "Average Graphics Package Power (W)": "9.0", "sclk clock speed:": "(500Mhz)", "sclk clock level:": "0",
+                      "mclk clock speed:": "(96Mhz)", "fclk clock speed:": "(400Mhz)", "VRAM Total Memory (B)": "17163091968",
+                      "VRAM Total Used Memory (B)": "27570176", "Card series": "Navi 21 [Radeon RX 6800/6800 XT / 6900 XT]",
+                      "Card model": "0x73bf", "Card vendor": "Advanced Micro Devices, Inc. [AMD/ATI]"},
+            "system": {"Driver version": "6.2.4"}
+        }"#;
+        let gpus = parse_rocm_smi_gpus(output);
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(
+            gpus[0],
+            GpuDevice {
+                index: 0,
+                name: "Navi 21 [Radeon RX 6800/6800 XT / 6900 XT]".to_string(),
+                gpu_freq_mhz: Some(500),
+                mem_freq_mhz: Some(96),
+                temp_celsius: Some(35.0),
+                power_watts: Some(9.0),
+                vram_used_bytes: Some(27570176),
+                vram_total_bytes: Some(17163091968),
+            }
+        );
+        assert_eq!((gpus[1].index, gpus[1].gpu_freq_mhz, gpus[1].power_watts), (1, Some(2250), Some(210.0)));
+        assert!(parse_rocm_smi_gpus("ERROR: No AMD GPUs specified").is_empty());
+    }
+
+    #[test]
+    fn test_read_amdgpu_sysfs() {
+        let drm = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = drm.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("card0/device/vendor", "0x1002\n");
+        write("card0/device/pp_dpm_sclk", "0: 500Mhz\n1: 1850Mhz *\n2: 2250Mhz\n");
+        write("card0/device/pp_dpm_mclk", "0: 96Mhz *\n1: 1000Mhz\n");
+        write("card0/device/mem_info_vram_used", "27570176\n");
+        write("card0/device/mem_info_vram_total", "17163091968\n");
+        write("card0/device/hwmon/hwmon4/temp1_input", "41000\n");
+        write("card0/device/hwmon/hwmon4/power1_average", "23000000\n");
+        write("card0-DP-1/status", "connected\n");
+        write("card1/device/vendor", "0x10de\n"); // NVIDIA, read through nvidia-smi instead
+
+        let gpus = read_amdgpu_sysfs(drm.path());
+        assert_eq!(
+            gpus,
+            vec![GpuDevice {
+                index: 0,
+                name: "AMD GPU".to_string(),
+                gpu_freq_mhz: Some(1850),
+                mem_freq_mhz: Some(96),
+                temp_celsius: Some(41.0),
+                power_watts: Some(23.0),
+                vram_used_bytes: Some(27570176),
+                vram_total_bytes: Some(17163091968),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bytes_per_sec() {
+        assert_eq!(bytes_per_sec(1_000, 11_000, 5.0), 2_000);
+        // Counter reset by pid reuse
+        assert_eq!(bytes_per_sec(11_000, 1_000, 5.0), 0);
+        assert_eq!(bytes_per_sec(0, 1_000, 0.0), 0);
+    }
+
+    // A /proc/net/tcp table from (local, remote, state) rows of IPv4 "a.b.c.d:port"
+    fn proc_net_tcp(rows: &[(String, String, &str)]) -> String {
+        let hex = |addr: &str| {
+            let (ip, port) = addr.split_once(':').unwrap();
+            let ip: std::net::Ipv4Addr = ip.parse().unwrap();
+            format!("{:08X}:{:04X}", u32::from(ip).swap_bytes(), port.parse::<u16>().unwrap())
+        };
+        let mut table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n".to_string();
+        for (i, (local, remote, state)) in rows.iter().enumerate() {
+            table.push_str(&format!(
+                "{:4}: {} {} {} 00000000:00000000 00:00000000 00000000  1000        0 {} 1 0000000000000000 20 4 30 10 -1\n",
+                i, hex(local), hex(remote), state, 40000 + i
+            ));
+        }
+        table
+    }
+
+    fn security(threshold: usize, allowlist: &[&str]) -> SecurityConfig {
+        SecurityConfig {
+            port_scan_threshold: threshold,
+            port_scan_window_secs: 60,
+            port_scan_allowlist: allowlist.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_port_scan_ignores_outbound_connections() {
+        // sshd listening, plus a crawler on this host holding connections to 30 ports of one server
+        let mut rows = vec![("0.0.0.0:22".to_string(), "0.0.0.0:0".to_string(), "0A")];
+        for i in 0..30u16 {
+            rows.push((format!("192.168.1.50:{}", 45000 + i), format!("203.0.113.9:{}", 8000 + i), "01"));
+            rows.push((format!("192.168.1.50:{}", 46000 + i), format!("203.0.113.9:{}", 9000 + i), "02"));
+        }
+        let table = proc_net_tcp(&rows);
+
+        assert!(inbound_connections(std::slice::from_ref(&table)).is_empty());
+        let mut tracker = ConnectionTracker::new(&security(20, &[]));
+        assert!(tracker.observe(&inbound_connections(&[table]), Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn test_port_scan_counts_inbound_ports_per_remote_ip() {
+        let mut rows = Vec::new();
+        for port in 1000..1025u16 {
+            rows.push((format!("0.0.0.0:{}", port), "0.0.0.0:0".to_string(), "0A"));
+            rows.push((format!("192.168.1.50:{}", port), format!("198.51.100.7:{}", 50000 + port), "03"));
+            rows.push((format!("192.168.1.50:{}", port), format!("10.20.0.5:{}", 50000 + port), "01"));
+        }
+        // Listening on one address only, so the connection to the other address isn't to a listener
+        rows.push(("127.0.0.1:5432".to_string(), "0.0.0.0:0".to_string(), "0A"));
+        rows.push(("192.168.1.50:5432".to_string(), "198.51.100.7:60000".to_string(), "03"));
+        // TIME_WAIT leftovers aren't live connections
+        rows.push(("192.168.1.50:1000".to_string(), "198.51.100.8:60001".to_string(), "06"));
+
+        let connections = inbound_connections(&[proc_net_tcp(&rows)]);
+        assert_eq!(connections.len(), 50);
+
+        let mut tracker = ConnectionTracker::new(&security(20, &["10.20.0.0/16"]));
+        let now = Instant::now();
+        let alerts = tracker.observe(&connections, now);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(
+            alerts[0],
+            "Potential port scan from 198.51.100.7: 25 local ports in 60s \
+             (1000, 1001, 1002, 1003, 1004, 1005, 1006, 1007, 1008, 1009, +15 more)"
+        );
+
+        // Still connected a tick later, but already reported for this window
+        assert!(tracker.observe(&connections, now + Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_port_scan_uses_sliding_window() {
+        let scanner: IpAddr = "198.51.100.7".parse().unwrap();
+        let start = Instant::now();
+
+        // One new port every 10s: the fifth lands inside the minute and triggers
+        let mut tracker = ConnectionTracker::new(&security(5, &[]));
+        for i in 0..4u16 {
+            assert!(tracker.observe(&[(scanner, 1000 + i)], start + Duration::from_secs(10 * i as u64)).is_empty());
+        }
+        let alerts = tracker.observe(&[(scanner, 1004)], start + Duration::from_secs(40));
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("5 local ports in 60s"), "{}", alerts[0]);
+
+        // One every 20s: earlier ports age out before a fifth arrives
+        let mut tracker = ConnectionTracker::new(&security(5, &[]));
+        for i in 0..10u16 {
+            assert!(tracker.observe(&[(scanner, 1000 + i)], start + Duration::from_secs(20 * i as u64)).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_mounts() {
+        let content = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/sda1 / ext4 rw,relatime 0 0
+tmpfs /run tmpfs rw,nosuid,nodev,size=812344k,mode=755 0 0
+/dev/sda2 /var ext4 rw,relatime 0 0
+systemd-1 /mnt/auto autofs rw,relatime,fd=29 0 0
+/dev/sda2 /var/lib/docker/bind ext4 rw,relatime 0 0
+/dev/sdb1 /media/usb\\040disk vfat rw,relatime 0 0
+nas:/export /mnt/nas nfs4 rw,relatime 0 0
+/dev/sdc1 /mnt/nas ext4 rw,relatime 0 0
+";
+        assert_eq!(
+            parse_mounts(content),
+            vec![
+                ("/dev/sda1".to_string(), "/".to_string()),
+                ("/dev/sda2".to_string(), "/var".to_string()),
+                ("/dev/sdb1".to_string(), "/media/usb disk".to_string()),
+                ("/dev/sdc1".to_string(), "/mnt/nas".to_string()),
+            ]
+        );
+        assert_eq!(unescape_mount_field(r"a\134b\011c\0"), "a\\b\tc\\0");
+    }
+
+    #[test]
+    fn test_read_all_filesystems_uses_statvfs() {
+        let root = read_disk_space().unwrap();
+        assert!(root.total_bytes > 0);
+        assert!(root.used_bytes <= root.total_bytes);
+
+        let filesystems = read_all_filesystems().unwrap();
+        for fs in &filesystems {
+            assert!(fs.used_bytes <= fs.total_bytes, "{:?}", fs);
+            assert!(fs.inodes_free <= fs.inodes_total, "{:?}", fs);
+        }
+
+        // Sizes agree with df, which these used to run, wherever df is installed
+        let Ok(output) = std::process::Command::new("df").args(["-B1", "/"]).output() else {
+            return;
+        };
+        let df = String::from_utf8_lossy(&output.stdout);
+        let Some(total) = df.lines().nth(1).and_then(|line| line.split_whitespace().nth(1)) else {
+            return;
+        };
+        assert_eq!(total.parse::<u64>().unwrap(), root.total_bytes);
+    }
+
+    // A utmp record as glibc lays it out
+    fn utmp_record(ut_type: i32, line: &str, user: &str, host: &str) -> Vec<u8> {
+        let mut record = vec![0u8; UTMP_RECORD_SIZE];
+        record[0..4].copy_from_slice(&ut_type.to_ne_bytes());
+        record[4..8].copy_from_slice(&4242i32.to_ne_bytes());
+        record[8..8 + line.len()].copy_from_slice(line.as_bytes());
+        record[44..44 + user.len()].copy_from_slice(user.as_bytes());
+        record[76..76 + host.len()].copy_from_slice(host.as_bytes());
+        record
+    }
+
+    #[test]
+    fn test_parse_utmp() {
+        let mut data = Vec::new();
+        data.extend(utmp_record(2, "~", "reboot", "6.8.0-generic")); // BOOT_TIME
+        data.extend(utmp_record(6, "tty1", "LOGIN", "")); // LOGIN_PROCESS
+        data.extend(utmp_record(UTMP_USER_PROCESS, "tty1", "alice", ""));
+        data.extend(utmp_record(UTMP_USER_PROCESS, "pts/0", "a_rather_long_username", "203.0.113.5"));
+        data.extend(utmp_record(8, "pts/1", "", "")); // DEAD_PROCESS, session ended
+        data.extend([0u8; 100]); // Torn trailing record
+
+        let users = parse_utmp(&data);
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[0].username, "alice");
+        assert_eq!(users[0].terminal, "tty1");
+        assert_eq!(users[0].remote_host, None);
+        assert_eq!(users[1].username, "a_rather_long_username");
+        assert_eq!(users[1].terminal, "pts/0");
+        assert_eq!(users[1].remote_host.as_deref(), Some("203.0.113.5"));
+    }
+
+    #[test]
+    fn test_read_logged_in_users_matches_w() {
+        let users = read_logged_in_users().unwrap();
+
+        // Same sessions as `w`, which this used to run, wherever w is installed
+        let Ok(output) = std::process::Command::new("w").arg("-h").output() else {
+            return;
+        };
+        let mut from_w: Vec<(String, Option<String>)> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .filter(|parts| parts.len() >= 4)
+            .map(|parts| (parts[1].to_string(), Some(parts[2].to_string()).filter(|from| from != "-")))
+            .collect();
+        let mut from_utmp: Vec<(String, Option<String>)> =
+            users.into_iter().map(|u| (u.terminal, u.remote_host)).collect();
+        from_w.sort();
+        from_utmp.sort();
+        assert_eq!(from_utmp, from_w);
+    }
+
+    #[test]
+    fn test_parse_kernel_limits() {
+        let limits = parse_kernel_limits(
+            "12345\t0\t1048576\n",
+            "4194304\n",
+            "0.52 0.58 0.59 3/1187 48210\n",
+            Some("256\n"),
+        )
+        .unwrap();
+        assert_eq!(limits, crate::event::KernelLimits {
+            file_handles_allocated: 12345,
+            file_handles_max: 1048576,
+            tasks: 1187,
+            pid_max: 4194304,
+            entropy_available: Some(256),
+        });
+
+        // Older kernels count freed handles they kept as allocated
+        let limits = parse_kernel_limits("5000 1200 100000", "32768", "0.00 0.01 0.05 1/90 300", None).unwrap();
+        assert_eq!(limits.file_handles_allocated, 3800);
+        assert_eq!(limits.entropy_available, None);
+        assert!(parse_kernel_limits("5000", "32768", "0.00 0.01 0.05 1/90 300", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_pressure() {
+        let memory = "some avg10=12.50 avg60=4.10 avg300=1.00 total=123456\nfull avg10=8.25 avg60=2.00 avg300=0.40 total=65432\n";
+        assert_eq!(parse_pressure(memory), Some((12.5, Some(8.25))));
+        // CPU has no "full" line before Linux 5.13
+        assert_eq!(parse_pressure("some avg10=5.80 avg60=5.50 avg300=4.39 total=693736970\n"), Some((5.8, None)));
+        assert_eq!(parse_pressure(""), None);
+    }
+
+    #[test]
+    fn test_parse_nofile_limit() {
+        let limits = "\
+Limit                     Soft Limit           Hard Limit           Units
+Max cpu time              unlimited            unlimited            seconds
+Max open files            1024                 524288               files
+Max locked memory         8388608              8388608              bytes
+";
+        assert_eq!(parse_nofile_limit(limits), Some(1024));
+        assert_eq!(parse_nofile_limit("Max open files            unlimited            unlimited            files"), None);
+        assert_eq!(parse_nofile_limit(""), None);
+
+        let usage = ProcessFdUsage { pid: 1, name: "nginx".to_string(), open_fds: 950, limit: 1024 };
+        assert!((usage.usage_percent() - 92.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_parse_socket_stats() {
+        let snmp = "\
+Ip: Forwarding DefaultTTL InReceives
+Ip: 2 64 23269
+Tcp: RtoAlgorithm RtoMin RtoMax MaxConn ActiveOpens PassiveOpens AttemptFails EstabResets CurrEstab InSegs OutSegs RetransSegs InErrs OutRsts InCsumErrors
+Tcp: 1 200 120000 -1 71 69 0 68 2 23261 23262 4 0 33 0
+Udp: InDatagrams NoPorts InErrors OutDatagrams RcvbufErrors SndbufErrors InCsumErrors IgnoredMulti MemErrors
+Udp: 812 3 7 640 5 0 0 0 0
+UdpLite: InDatagrams NoPorts InErrors OutDatagrams RcvbufErrors SndbufErrors InCsumErrors IgnoredMulti MemErrors
+UdpLite: 0 0 0 0 0 0 0 0 0
+";
+        let netstat = "\
+TcpExt: SyncookiesSent SyncookiesRecv SyncookiesFailed ListenOverflows ListenDrops TCPReqQFullDoCookies TCPReqQFullDrop
+TcpExt: 0 0 0 12 15 0 3
+IpExt: InNoRoutes InTruncatedPkts
+IpExt: 0 0
+";
+        let sockstat = "\
+sockets: used 290
+TCP: inuse 5 orphan 2 tw 9 alloc 7 mem 3
+UDP: inuse 3 mem 1
+FRAG: inuse 0 memory 0
+";
+        let stats = parse_socket_stats(snmp, netstat, sockstat);
+        assert_eq!(stats, SocketStats {
+            tcp_in_segs: 23261,
+            tcp_out_segs: 23262,
+            tcp_retrans_segs: 4,
+            tcp_listen_overflows: 12,
+            tcp_listen_drops: 15,
+            tcp_syn_drops: 3,
+            udp_in_datagrams: 812,
+            udp_out_datagrams: 640,
+            udp_errors: 7,
+            sockets_used: 290,
+            tcp_orphaned: 2,
+            socket_mem_bytes: 4 * 4096,
+        });
+
+        let later = SocketStats { tcp_out_segs: 25262, tcp_retrans_segs: 104, ..stats.clone() };
+        let rates = later.per_sec(&stats, 2.0);
+        assert_eq!(rates.tcp_out_segs, 1000);
+        assert_eq!(rates.tcp_retrans_segs, 50);
+        // Counters reset (netns recreated) rather than going negative
+        assert_eq!(stats.per_sec(&later, 1.0).tcp_retrans_segs, 0);
+
+        // Without /proc/net/netstat the extended counters stay at zero
+        assert_eq!(parse_socket_stats(snmp, "", sockstat).tcp_listen_drops, 0);
+    }
 }
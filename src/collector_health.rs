@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::{Arc, RwLock};
+use time::OffsetDateTime;
+
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity};
+
+// Latest state of every collector, published by the main loop for /api/health
+pub type SharedCollectorHealth = Arc<RwLock<Vec<CollectorStatus>>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectorStatus {
+    pub name: String,
+    pub last_success: Option<OffsetDateTime>,
+    pub consecutive_failures: u64,
+    pub total_failures: u64,
+    pub last_error: Option<String>,
+}
+
+// Success and failure of each data source the main loop reads, so one that starts failing
+// raises an anomaly instead of its data quietly going missing
+#[derive(Default)]
+pub struct CollectorHealth {
+    collectors: BTreeMap<&'static str, CollectorStatus>,
+    anomalies: Vec<Anomaly>,
+    changed: bool,
+}
+
+impl CollectorHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note the outcome of one read. The first failure after a success queues a Warning
+    /// anomaly, and the first success after failures an Info one.
+    pub fn record<T, E: Display>(&mut self, name: &'static str, result: &Result<T, E>) {
+        let now = OffsetDateTime::now_utc();
+        let status = self.collectors.entry(name).or_insert_with(|| CollectorStatus {
+            name: name.to_string(),
+            last_success: None,
+            consecutive_failures: 0,
+            total_failures: 0,
+            last_error: None,
+        });
+        match result {
+            Ok(_) => {
+                if status.consecutive_failures > 0 {
+                    self.anomalies.push(Anomaly {
+                        ts: now,
+                        severity: AnomalySeverity::Info,
+                        kind: AnomalyKind::CollectorRecovered,
+                        message: format!(
+                            "Collector {} recovered after {} failed reads",
+                            name, status.consecutive_failures
+                        ),
+                    });
+                    status.consecutive_failures = 0;
+                    self.changed = true;
+                }
+                // Only worth republishing once a second's resolution has moved on
+                if status.last_success.is_none_or(|last| (now - last).whole_seconds() >= 1) {
+                    status.last_success = Some(now);
+                    self.changed = true;
+                }
+            }
+            Err(e) => {
+                let error = format!("{:#}", e);
+                // Collectors that have never worked (no sensors, no auth log) only show in
+                // /api/health, rather than warning on every start
+                if status.consecutive_failures == 0 && status.last_success.is_some() {
+                    self.anomalies.push(Anomaly {
+                        ts: now,
+                        severity: AnomalySeverity::Warning,
+                        kind: AnomalyKind::CollectorFailing,
+                        message: format!("Collector {} failing: {}", name, error),
+                    });
+                }
+                status.consecutive_failures += 1;
+                status.total_failures += 1;
+                status.last_error = Some(error);
+                self.changed = true;
+            }
+        }
+    }
+
+    /// Anomalies queued since the last call, to be recorded
+    pub fn take_anomalies(&mut self) -> Vec<Anomaly> {
+        std::mem::take(&mut self.anomalies)
+    }
+
+    /// Copy the current state out for the web server, if anything has changed
+    pub fn publish(&mut self, shared: &SharedCollectorHealth) {
+        if !std::mem::take(&mut self.changed) {
+            return;
+        }
+        if let Ok(mut guard) = shared.write() {
+            *guard = self.collectors.values().cloned().collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok() -> Result<(), String> {
+        Ok(())
+    }
+
+    fn err(message: &str) -> Result<(), String> {
+        Err(message.to_string())
+    }
+
+    #[test]
+    fn test_failure_and_recovery_raise_one_anomaly_each() {
+        let mut health = CollectorHealth::new();
+        health.record("auth_log", &ok());
+        assert!(health.take_anomalies().is_empty());
+
+        health.record("auth_log", &err("Permission denied"));
+        health.record("auth_log", &err("Permission denied"));
+        health.record("auth_log", &err("Permission denied"));
+        let anomalies = health.take_anomalies();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::CollectorFailing);
+        assert_eq!(anomalies[0].severity, AnomalySeverity::Warning);
+        assert_eq!(anomalies[0].message, "Collector auth_log failing: Permission denied");
+
+        let shared = SharedCollectorHealth::default();
+        health.publish(&shared);
+        let status = shared.read().unwrap()[0].clone();
+        assert_eq!(status.name, "auth_log");
+        assert_eq!(status.consecutive_failures, 3);
+        assert_eq!(status.total_failures, 3);
+        assert!(status.last_success.is_some());
+
+        health.record("auth_log", &ok());
+        let anomalies = health.take_anomalies();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::CollectorRecovered);
+        assert_eq!(anomalies[0].severity, AnomalySeverity::Info);
+        assert_eq!(anomalies[0].message, "Collector auth_log recovered after 3 failed reads");
+
+        health.publish(&shared);
+        assert_eq!(shared.read().unwrap()[0].consecutive_failures, 0);
+        assert_eq!(shared.read().unwrap()[0].total_failures, 3);
+    }
+
+    #[test]
+    fn test_collector_that_never_worked_is_tracked_quietly() {
+        let mut health = CollectorHealth::new();
+        health.record("temperatures", &err("no sensor readings"));
+        health.record("temperatures", &err("no sensor readings"));
+        assert!(health.take_anomalies().is_empty());
+
+        let shared = SharedCollectorHealth::default();
+        health.publish(&shared);
+        let status = shared.read().unwrap()[0].clone();
+        assert_eq!(status.last_success, None);
+        assert_eq!(status.consecutive_failures, 2);
+        assert_eq!(status.last_error.as_deref(), Some("no sensor readings"));
+    }
+}
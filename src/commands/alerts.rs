@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+
+use crate::config::Config;
+
+// Send a test alert to every configured webhook, failing if any of them didn't take it
+pub fn test_alerts() -> Result<()> {
+    let config = Config::load()?;
+    let Some(webhook) = config.alerts.webhook else {
+        anyhow::bail!("No webhooks configured, add an [alerts.webhook] section to config.toml");
+    };
+    if !webhook.enabled {
+        println!("Note: [alerts.webhook] is disabled, the recorder won't send alerts until enabled = true");
+    }
+
+    println!("Sending a test alert to {} webhook(s)...", webhook.urls.len());
+    let runtime = tokio::runtime::Runtime::new().context("Failed to create Tokio runtime")?;
+    let results = runtime.block_on(crate::alerts::send_test_webhooks(&webhook))?;
+
+    let mut failed = 0;
+    for (url, result) in results {
+        match result {
+            Ok(()) => println!("✓ {}", url),
+            Err(e) => {
+                println!("✗ {}: {:#}", url, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} webhook(s) did not accept the test alert", failed);
+    }
+    Ok(())
+}
@@ -1,25 +1,72 @@
 use anyhow::{Context, Result};
 use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::os::unix::fs::PermissionsExt;
 
-use crate::config::{Config, RemoteSyslogConfig};
+use crate::cli::{SetupEmailArgs, SetupRemoteArgs};
+use crate::config::{generate_api_token, ApiToken, Config, EmailAlertConfig, RemoteSyslogConfig};
 
 pub fn show_config() -> Result<()> {
     let config = Config::load()?;
-    let toml_content = toml::to_string_pretty(&config)
+    let mut shown = config.clone();
+    if let Some(password) = shown.alerts.email.as_mut().and_then(|email| email.password.as_mut()) {
+        *password = "********".to_string();
+    }
+    if let Some(webhook) = shown.alerts.webhook.as_mut() {
+        for url in &mut webhook.urls {
+            *url = format!("{}/********", crate::alerts::redact(url));
+        }
+    }
+    let toml_content = toml::to_string_pretty(&shown)
         .context("Failed to serialize config")?;
 
     println!("Current Configuration");
     println!("=====================");
     println!();
     println!("{}", toml_content);
+    print_web_server(&config);
+    print_watch_profiles(&config);
 
     Ok(())
 }
 
+// Where the web UI is reached, bind address and base path included even when left at defaults
+fn print_web_server(config: &Config) {
+    let server = &config.server;
+    println!("Web server:");
+    match &server.unix_socket_path {
+        Some(path) => println!("  Bind address: unix:{} ({} unused)", path, server.bind_address),
+        None => println!("  Bind address: {}:{}", server.bind_address, server.port),
+    }
+    let base_path = server.base_path();
+    println!("  Base path: {}", if base_path.is_empty() { "/" } else { base_path });
+    println!();
+}
+
+// Effective file watch profiles, watch_dirs included
+fn print_watch_profiles(config: &Config) {
+    let profiles = config.file_watch.profiles();
+    println!("File watch profiles{}:", if config.file_watch.enabled { "" } else { " (disabled)" });
+    if profiles.is_empty() {
+        println!("  none");
+    }
+    for entry in profiles {
+        let kinds: Vec<String> = entry.kinds.iter().map(|k| k.to_string()).collect();
+        print!("  {}{}: {}", entry.path, if entry.recursive { " (recursive)" } else { "" }, kinds.join(", "));
+        if !entry.include.is_empty() {
+            print!("; only {}", entry.include.join(", "));
+        }
+        if !entry.ignore.is_empty() {
+            print!("; ignore {}", entry.ignore.join(", "));
+        }
+        println!();
+    }
+}
+
 pub fn validate_config() -> Result<()> {
     println!("Validating config.toml...");
 
-    match Config::load() {
+    match Config::load_checked() {
         Ok(config) => {
             println!("✓ Configuration is valid");
             println!();
@@ -31,8 +78,14 @@ pub fn validate_config() -> Result<()> {
             println!("  Enabled: {}", config.auth.enabled);
             if config.auth.enabled {
                 println!("  Username: {}", config.auth.username);
-                println!("  Password hash: {}...", &config.auth.password_hash[..20]);
+                let hash = &config.auth.password_hash;
+                println!("  Password hash: {}...", hash.get(..20).unwrap_or(hash));
+                if !config.auth.api_tokens.is_empty() {
+                    let names: Vec<&str> = config.auth.api_tokens.iter().map(|t| t.name.as_str()).collect();
+                    println!("  Read-only API tokens: {}", names.join(", "));
+                }
             }
+            println!("  Process signals from the UI: {}", if config.actions.allow_kill { "allowed" } else { "off" });
             println!();
             println!("Protection:");
             println!("  Append-only: {}", config.protection.append_only);
@@ -46,6 +99,41 @@ pub fn validate_config() -> Result<()> {
             } else {
                 println!("  Remote syslog: not configured");
             }
+            println!();
+            println!("Alerts:");
+            match &config.alerts.email {
+                Some(email) => println!("  Email: {} (to {} via {}:{})",
+                    if email.enabled { "enabled" } else { "disabled" },
+                    email.to.join(", "),
+                    email.smtp_host,
+                    email.smtp_port
+                ),
+                None => println!("  Email: not configured"),
+            }
+            match &config.alerts.webhook {
+                Some(webhook) => println!("  Webhook: {} ({} URL(s), {:?} and above{})",
+                    if webhook.enabled { "enabled" } else { "disabled" },
+                    webhook.urls.len(),
+                    webhook.min_severity,
+                    if webhook.kinds.is_empty() { String::new() } else { format!(", plus {}", webhook.kinds.join(", ")) }
+                ),
+                None => println!("  Webhook: not configured"),
+            }
+            println!();
+            println!("Anomaly thresholds:");
+            for (name, entry, unit) in config.anomalies.entries() {
+                println!("  {}: {} {}{}",
+                    name,
+                    if entry.enabled { "enabled " } else { "disabled" },
+                    entry.threshold,
+                    unit
+                );
+            }
+            for (mount_point, threshold) in &config.anomalies.disk_full_mounts {
+                println!("  disk_full on {}: {}%", mount_point, threshold);
+            }
+            println!();
+            print_watch_profiles(&config);
 
             Ok(())
         }
@@ -88,15 +176,14 @@ pub fn init_config(force: bool) -> Result<()> {
     println!();
     println!("PLEASE CHANGE THE DEFAULT PASSWORD IMMEDIATELY!");
     println!();
-    println!("To generate a password hash:");
-    println!("  Run: echo -n 'your-password' | bcrypt");
-    println!("  Or use an online bcrypt generator");
-    println!("  Then update the password_hash in config.toml");
+    println!("To change it, set password = \"your-password\" under [auth] in {}.", config_path);
+    println!("It is replaced with an argon2 hash the next time Black Box starts.");
 
     Ok(())
 }
 
-pub fn setup_remote_syslog(host: String, port: u16, protocol: String) -> Result<()> {
+pub fn setup_remote_syslog(args: SetupRemoteArgs) -> Result<()> {
+    let SetupRemoteArgs { host, port, protocol, format, facility, framing, tls, ca_cert, client_cert, client_key } = args;
     let config_path = "./config.toml";
 
     // Load existing config
@@ -114,14 +201,38 @@ pub fn setup_remote_syslog(host: String, port: u16, protocol: String) -> Result<
         anyhow::bail!("Protocol must be 'tcp' or 'udp', got '{}'", protocol);
     }
 
-    // Update remote syslog config
-    config.protection.remote_syslog = Some(RemoteSyslogConfig {
+    let syslog = RemoteSyslogConfig {
         enabled: true,
         host: host.clone(),
         port,
         protocol: protocol.clone(),
+        format: format.parse()?,
+        facility,
+        framing: framing.parse()?,
+        tls,
+        ca_cert,
+        client_cert,
+        client_key,
+        spool_max_mb: config
+            .protection
+            .remote_syslog
+            .as_ref()
+            .map_or(64, |existing| existing.spool_max_mb),
+    };
+    syslog.validate()?;
+    // Bad certificate paths are caught now rather than when streaming starts
+    crate::syslog::tls_client_config(&syslog)?;
+    let tls_summary = syslog.tls.then(|| {
+        let verify = syslog.ca_cert.clone().unwrap_or_else(|| "the public root certificates".to_string());
+        match &syslog.client_cert {
+            Some(cert) => format!("verifying with {}, presenting {}", verify, cert),
+            None => format!("verifying with {}", verify),
+        }
     });
 
+    // Update remote syslog config
+    config.protection.remote_syslog = Some(syslog);
+
     // Save config
     let toml_content = toml::to_string_pretty(&config)
         .context("Failed to serialize config")?;
@@ -135,6 +246,14 @@ pub fn setup_remote_syslog(host: String, port: u16, protocol: String) -> Result<
     println!("  Host: {}", host);
     println!("  Port: {}", port);
     println!("  Protocol: {}", protocol);
+    println!("  Format: {}", format);
+    println!("  Facility: {}", facility);
+    if protocol == "tcp" {
+        println!("  Framing: {}", framing);
+    }
+    if let Some(tls) = &tls_summary {
+        println!("  TLS: {}", tls);
+    }
     println!();
     println!("Remote syslog streaming will be enabled when running in");
     println!("--protected or --hardened mode.");
@@ -146,3 +265,175 @@ pub fn setup_remote_syslog(host: String, port: u16, protocol: String) -> Result<
 
     Ok(())
 }
+
+pub fn setup_email(args: SetupEmailArgs) -> Result<()> {
+    let config_path = "./config.toml";
+
+    let mut config = if std::path::Path::new(config_path).exists() {
+        let content = fs::read_to_string(config_path)
+            .context("Failed to read config.toml")?;
+        toml::from_str(&content).context("Failed to parse config.toml")?
+    } else {
+        println!("Config file not found, creating new one...");
+        Config::load()?
+    };
+    let existing = config.alerts.email.take();
+
+    // Anything not given on the command line is asked for
+    let smtp_host = match args.smtp_host {
+        Some(host) => host,
+        None => prompt("SMTP host", existing.as_ref().map(|e| e.smtp_host.as_str()))?,
+    };
+    let from = match args.from {
+        Some(from) => from,
+        None => prompt("From address", existing.as_ref().map(|e| e.from.as_str()))?,
+    };
+    let to = if args.to.is_empty() {
+        let current = existing.as_ref().map(|e| e.to.join(", "));
+        prompt("To addresses (comma separated)", current.as_deref())?
+            .split(',')
+            .map(|address| address.trim().to_string())
+            .filter(|address| !address.is_empty())
+            .collect()
+    } else {
+        args.to
+    };
+
+    // The password is never taken from the command line, where it would end up in shell history
+    let password = match &args.username {
+        Some(username) => {
+            let kept = existing
+                .as_ref()
+                .filter(|e| e.username.as_ref() == Some(username))
+                .and_then(|e| e.password.clone());
+            let entered = prompt_password(if kept.is_some() {
+                "SMTP password (blank to keep the current one): "
+            } else {
+                "SMTP password: "
+            })?;
+            match kept {
+                Some(kept) if entered.is_empty() => Some(kept),
+                _ => Some(entered),
+            }
+        }
+        None => None,
+    };
+
+    let email = EmailAlertConfig {
+        enabled: true,
+        smtp_host,
+        smtp_port: args.port,
+        starttls: !args.no_starttls,
+        username: args.username,
+        password,
+        from,
+        to,
+        digest_interval_minutes: args.digest_minutes,
+    };
+    email.validate()?;
+    config.alerts.email = Some(email.clone());
+
+    let toml_content = toml::to_string_pretty(&config)
+        .context("Failed to serialize config")?;
+    fs::write(config_path, toml_content)
+        .context("Failed to write config file")?;
+    if email.password.is_some() {
+        // The SMTP password is stored as given
+        fs::set_permissions(config_path, fs::Permissions::from_mode(0o600))
+            .context("Failed to restrict config.toml permissions")?;
+    }
+
+    println!("✓ Email alerts configured");
+    println!();
+    println!("Configuration:");
+    println!("  SMTP server: {}:{} ({})", email.smtp_host, email.smtp_port,
+        if email.starttls { "STARTTLS" } else { "no TLS" });
+    println!("  Username: {}", email.username.as_deref().unwrap_or("(none)"));
+    println!("  From: {}", email.from);
+    println!("  To: {}", email.to.join(", "));
+    println!("  Warning digest: at most every {} minutes", email.digest_interval_minutes);
+    println!();
+    println!("Critical anomalies are emailed as they are recorded, warnings are");
+    println!("collected into a digest. Restart Black Box to start sending.");
+
+    if args.test {
+        println!();
+        println!("Sending a test email...");
+        let runtime = tokio::runtime::Runtime::new().context("Failed to create Tokio runtime")?;
+        runtime.block_on(crate::alerts::send_test_email(&email))?;
+        println!("✓ Test email sent to {}", email.to.join(", "));
+    }
+
+    Ok(())
+}
+
+pub fn add_token(name: String) -> Result<()> {
+    let config_path = "./config.toml";
+
+    let mut config = if std::path::Path::new(config_path).exists() {
+        let content = fs::read_to_string(config_path)
+            .context("Failed to read config.toml")?;
+        toml::from_str(&content).context("Failed to parse config.toml")?
+    } else {
+        println!("Config file not found, creating new one...");
+        Config::load()?
+    };
+
+    let name = name.trim().to_string();
+    if config.auth.api_tokens.iter().any(|t| t.name == name) {
+        anyhow::bail!("There is already an API token named '{}', remove it from config.toml first", name);
+    }
+    let (token, token_hash) = generate_api_token();
+    config.auth.api_tokens.push(ApiToken { name: name.clone(), token_hash });
+    config.auth.validate()?;
+
+    let toml_content = toml::to_string_pretty(&config)
+        .context("Failed to serialize config")?;
+    fs::write(config_path, toml_content)
+        .context("Failed to write config file")?;
+
+    println!("✓ API token '{}' added", name);
+    println!();
+    println!("  {}", token);
+    println!();
+    println!("Only its hash is kept in config.toml, so copy it now; it can't be shown again.");
+    println!("Send it as \"Authorization: Bearer <token>\" to read the API (GET requests only).");
+    println!("Restart Black Box for it to be accepted. Remove its [[auth.api_tokens]] entry to revoke it.");
+
+    Ok(())
+}
+
+fn prompt(label: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) => print!("{} [{}]: ", label, default),
+        None => print!("{}: ", label),
+    }
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let line = line.trim();
+    Ok(if line.is_empty() { default.unwrap_or_default().to_string() } else { line.to_string() })
+}
+
+// Read a line without echoing it when stdin is a terminal
+fn prompt_password(label: &str) -> Result<String> {
+    print!("{}", label);
+    std::io::stdout().flush()?;
+
+    let stdin = std::io::stdin();
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    let hide = stdin.is_terminal() && unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } == 0;
+    if hide {
+        let mut silent = original;
+        silent.c_lflag &= !libc::ECHO;
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) };
+    }
+    let mut line = String::new();
+    let read = stdin.lock().read_line(&mut line);
+    if hide {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original) };
+        println!();
+    }
+    read.context("Failed to read password")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
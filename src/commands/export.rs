@@ -1,94 +1,250 @@
 use anyhow::{Context, Result};
 use flate2::write::GzEncoder;
 use flate2::Compression;
+use regex::Regex;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{self, Write};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
-use crate::cli::ExportFormat;
-use crate::event::Event;
+use crate::cli::{ExportFormat, FollowSeverity};
+use crate::config::Config;
+use crate::event::{Event, FileSystemEventKind, SystemEventKind, SystemMetrics};
+use crate::integrity::SigningKey;
 use crate::reader::LogReader;
+use crate::syslog;
+use crate::timezone;
+
+// Which recorded events to export
+#[derive(Default)]
+pub struct ExportFilter {
+    pub event_type: Option<String>,
+    pub start: Option<String>, // RFC3339 or Unix timestamp
+    pub end: Option<String>,
+    pub severity: Option<FollowSeverity>, // Minimum, by the event's syslog severity
+    pub pattern: Option<String>, // Regex over the event's JSON
+}
+
+// ExportFilter parsed and ready to test events against while they stream from the reader
+struct EventMatcher {
+    event_type: Option<String>,
+    start: Option<i64>,
+    end: Option<i64>,
+    max_level: Option<u8>, // Syslog levels count down as severity goes up
+    pattern: Option<Regex>,
+}
+
+impl EventMatcher {
+    fn new(filter: ExportFilter) -> Result<Self> {
+        Ok(Self {
+            event_type: filter.event_type,
+            start: filter.start.as_deref().map(parse_timestamp).transpose()?,
+            end: filter.end.as_deref().map(parse_timestamp).transpose()?,
+            max_level: filter.severity.map(|severity| match severity {
+                FollowSeverity::Critical => syslog::SEVERITY_CRITICAL,
+                FollowSeverity::Warning => syslog::SEVERITY_WARNING,
+                FollowSeverity::Info => syslog::SEVERITY_INFO,
+            }),
+            pattern: filter
+                .pattern
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .context("Invalid --match pattern")?,
+        })
+    }
+
+    // Everything but the pattern, which needs the JSON
+    fn matches(&self, event: &Event) -> bool {
+        let ts = event.timestamp().unix_timestamp();
+        self.start.is_none_or(|start| ts >= start)
+            && self.end.is_none_or(|end| ts <= end)
+            && self.event_type.as_deref().is_none_or(|t| matches_event_type(event, t))
+            && self.max_level.is_none_or(|max| syslog::severity(event) <= max)
+    }
+
+    fn matches_json(&self, json: &str) -> bool {
+        self.pattern.as_ref().is_none_or(|pattern| pattern.is_match(json))
+    }
+}
 
 pub fn run_export(
     output: Option<String>,
     format: ExportFormat,
     compress: bool,
-    event_type: Option<String>,
-    start: Option<String>,
-    end: Option<String>,
+    filter: ExportFilter,
     data_dir: Option<String>,
+    verify: bool,
+    local_time: bool,
 ) -> Result<()> {
+    let narrowed = filter.event_type.is_some();
+    let matcher = EventMatcher::new(filter)?;
     let data_dir = data_dir.unwrap_or_else(|| "./data".to_string());
 
+    // Signatures can only be checked with the key from config.toml, which also sets the timezone
+    let config = if verify || local_time { Some(Config::load()?) } else { None };
+    if local_time {
+        timezone::init(config.as_ref().and_then(|c| c.server.timezone.as_deref()));
+    }
+    let signing_key = if verify { config.as_ref().and_then(|c| SigningKey::from_config(&c.protection)) } else { None };
+    let has_key = signing_key.is_some();
+
     // Read events from ring buffer
-    let reader = LogReader::new(&data_dir);
+    let reader = LogReader::new(&data_dir).with_signing_key(signing_key);
 
-    let mut events = if start.is_some() || end.is_some() {
-        // Parse time range
-        let start_ts = start.as_ref().map(|s| parse_timestamp(s)).transpose()?;
-        let end_ts = end.as_ref().map(|s| parse_timestamp(s)).transpose()?;
-        reader.read_events_range(start_ts, end_ts)?
-    } else {
-        reader.read_all_events()?
-    };
+    let failed_segments = if verify { super::verify::report_integrity(&reader, has_key)? } else { 0 };
+
+    // Export in requested format. NDJSON is written as events stream from the reader, so memory
+    // stays flat however much is recorded; the others need every matching event first.
+    match format {
+        ExportFormat::Jsonl => {
+            let mut writer = open_writer(output.as_deref(), compress)?;
+            let counts = export_ndjson(&reader, &matcher, &mut writer, local_time)?;
+            eprintln!("{}", summary_line(&counts));
+            writer.flush()?;
+        }
+        ExportFormat::Json => {
+            let events = collect_matching(&reader, &matcher)?;
+            eprintln!("Found {} events", events.len());
+            let mut writer = open_writer(output.as_deref(), compress)?;
+            export_json(&events, &mut writer, local_time)?;
+            writer.flush()?;
+        }
+        ExportFormat::Csv => {
+            let events = collect_matching(&reader, &matcher)?;
+            eprintln!("Found {} events", events.len());
+
+            let mut by_type: BTreeMap<&'static str, Vec<&Event>> = BTreeMap::new();
+            for event in &events {
+                by_type.entry(csv_name(event)).or_default().push(event);
+            }
+
+            // A type picked with --event-type goes to the output itself, anything more gets a
+            // file per type in the output directory
+            if narrowed && by_type.len() <= 1 {
+                let mut writer = open_writer(output.as_deref(), compress)?;
+                for events in by_type.values() {
+                    export_csv(events, &mut writer, local_time)?;
+                    let metrics = system_metrics(events);
+                    if metrics.is_empty() {
+                        continue;
+                    }
+                    match output.as_deref() {
+                        Some(path) => {
+                            let disks_path = sibling_csv_path(path, DISKS_CSV, compress);
+                            let mut disks_writer = open_writer(Some(&disks_path.to_string_lossy()), compress)?;
+                            export_disks_csv(&metrics, &mut disks_writer, local_time)?;
+                            disks_writer.flush()?;
+                            eprintln!("Wrote per-disk metrics to {}", disks_path.display());
+                        }
+                        None => eprintln!("Skipped per-disk metrics (use --output to also write {}.csv)", DISKS_CSV),
+                    }
+                }
+                writer.flush()?;
+            } else {
+                let dir = output.as_deref().context(
+                    "CSV export writes a file per event type, give a directory with --output or pick one type with --event-type",
+                )?;
+                std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir))?;
+                let extension = if compress { "csv.gz" } else { "csv" };
+                for (name, events) in &by_type {
+                    let path = Path::new(dir).join(format!("{}.{}", name, extension));
+                    let mut writer = open_writer(Some(&path.to_string_lossy()), compress)?;
+                    export_csv(events, &mut writer, local_time)?;
+                    writer.flush()?;
+                    eprintln!("Wrote {} events to {}", events.len(), path.display());
 
-    // Filter by event type if specified
-    if let Some(ref filter_type) = event_type {
-        events.retain(|e| matches_event_type(e, filter_type));
+                    let metrics = system_metrics(events);
+                    if !metrics.is_empty() {
+                        let path = Path::new(dir).join(format!("{}.{}", DISKS_CSV, extension));
+                        let mut writer = open_writer(Some(&path.to_string_lossy()), compress)?;
+                        export_disks_csv(&metrics, &mut writer, local_time)?;
+                        writer.flush()?;
+                        eprintln!("Wrote per-disk metrics to {}", path.display());
+                    }
+                }
+            }
+        }
     }
 
-    eprintln!("Found {} events", events.len());
+    eprintln!("Export complete");
+
+    if failed_segments > 0 {
+        anyhow::bail!("Integrity check failed for {} segment(s)", failed_segments);
+    }
+    Ok(())
+}
 
-    // Create output writer
-    let writer: Box<dyn Write> = if let Some(path) = output {
+// Create the output writer (file or stdout), wrapped in gzip if requested
+fn open_writer(path: Option<&str>, compress: bool) -> Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if let Some(path) = path {
         if compress && !path.ends_with(".gz") {
             eprintln!("Warning: compress flag set but output doesn't end with .gz");
         }
-        Box::new(File::create(&path).context("Failed to create output file")?)
+        Box::new(BufWriter::new(File::create(path).context("Failed to create output file")?))
     } else {
         if compress {
             eprintln!("Warning: compress flag ignored when writing to stdout");
         }
-        Box::new(io::stdout())
+        Box::new(BufWriter::new(io::stdout()))
     };
 
     // Wrap in gzip if needed
-    let mut writer: Box<dyn Write> = if compress {
+    Ok(if compress {
         Box::new(GzEncoder::new(writer, Compression::default()))
     } else {
         writer
-    };
+    })
+}
 
-    // Export in requested format
-    match format {
-        ExportFormat::Json => export_json(&events, &mut writer)?,
-        ExportFormat::Jsonl => export_jsonl(&events, &mut writer)?,
-        ExportFormat::Csv => export_csv(&events, &mut writer)?,
+// `name`.csv next to the main output (.csv.gz when compressing)
+fn sibling_csv_path(output: &str, name: &str, compress: bool) -> PathBuf {
+    let name = if compress { format!("{}.csv.gz", name) } else { format!("{}.csv", name) };
+    let output = Path::new(output);
+    let mut path = output.with_file_name(&name);
+    if path == output {
+        path = output.with_file_name(format!("other_{}", name));
     }
+    path
+}
 
-    // Flush and finish compression if needed
-    writer.flush()?;
-    drop(writer);
-
-    eprintln!("Export complete");
-    Ok(())
+// Unix seconds, RFC3339, or a time ago such as -2h, -30m, -90s or -1d
+pub(super) fn parse_timestamp(s: &str) -> Result<i64> {
+    parse_timestamp_at(s, OffsetDateTime::now_utc().unix_timestamp())
 }
 
-fn parse_timestamp(s: &str) -> Result<i64> {
+fn parse_timestamp_at(s: &str, now: i64) -> Result<i64> {
     // Try parsing as Unix timestamp first
     if let Ok(ts) = s.parse::<i64>() {
         return Ok(ts);
     }
 
-    // Try parsing as RFC3339
-    use time::format_description::well_known::Rfc3339;
-    use time::OffsetDateTime;
+    if let Some(ago) = s.strip_prefix('-').and_then(parse_duration_secs) {
+        return Ok(now - ago);
+    }
 
+    // Try parsing as RFC3339
     let dt = OffsetDateTime::parse(s, &Rfc3339)
-        .context("Invalid timestamp format. Use Unix timestamp or RFC3339")?;
+        .context("Invalid timestamp format. Use Unix timestamp, RFC3339 or a time ago like -2h")?;
     Ok(dt.unix_timestamp())
 }
 
-fn matches_event_type(event: &Event, filter: &str) -> bool {
+// "90s", "30m", "2h" or "1d" in seconds
+fn parse_duration_secs(s: &str) -> Option<i64> {
+    let unit = match s.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    let count: i64 = s[..s.len() - 1].parse().ok().filter(|count| *count >= 0)?;
+    count.checked_mul(unit)
+}
+
+pub(super) fn matches_event_type(event: &Event, filter: &str) -> bool {
     let filter_lower = filter.to_lowercase();
     match event {
         Event::SystemMetrics(_) => filter_lower.contains("system") || filter_lower.contains("metrics"),
@@ -97,10 +253,48 @@ fn matches_event_type(event: &Event, filter: &str) -> bool {
         Event::SecurityEvent(_) => filter_lower.contains("security") || filter_lower.contains("sec"),
         Event::Anomaly(_) => filter_lower.contains("anomaly") || filter_lower.contains("alert"),
         Event::FileSystemEvent(_) => filter_lower.contains("file") || filter_lower.contains("fs"),
+        Event::Annotation(_) => filter_lower.contains("annotation") || filter_lower.contains("note"),
+        Event::DiskHealth(_) => filter_lower.contains("disk") || filter_lower.contains("smart"),
+        Event::ServiceStateChange(_) => filter_lower.contains("service"),
+        Event::SystemEvent(_) => {
+            filter_lower.contains("boot")
+                || filter_lower.contains("shutdown")
+                || filter_lower.contains("reload")
+                || (filter_lower.contains("system") && filter_lower.contains("event"))
+        }
+        Event::OperatorAction(_) => filter_lower.contains("operator") || filter_lower.contains("action"),
+        Event::NetworkTraffic(_) => filter_lower.contains("traffic") || filter_lower.contains("network"),
+    }
+}
+
+// The event as JSON, with its ts as an RFC3339 string in the configured timezone for --local-time
+fn event_json(event: &Event, local_time: bool) -> Result<serde_json::Value> {
+    let mut json = serde_json::to_value(event).context("Failed to serialize event to JSON")?;
+    if local_time
+        && let Some(fields) = json.as_object_mut().and_then(|variant| variant.values_mut().next()).and_then(|v| v.as_object_mut())
+    {
+        fields.insert("ts".to_string(), local_timestamp(event.timestamp()).into());
     }
+    Ok(json)
 }
 
-fn export_json(events: &[Event], writer: &mut dyn Write) -> Result<()> {
+fn local_timestamp(ts: OffsetDateTime) -> String {
+    let local = timezone::to_local(ts);
+    local.format(&Rfc3339).unwrap_or_else(|_| local.to_string())
+}
+
+// RFC3339 to the second, in UTC or the configured timezone for --local-time
+fn csv_timestamp(ts: OffsetDateTime, local_time: bool) -> String {
+    let ts = ts.replace_nanosecond(0).unwrap_or(ts);
+    if local_time {
+        local_timestamp(ts)
+    } else {
+        ts.format(&Rfc3339).unwrap_or_else(|_| ts.to_string())
+    }
+}
+
+fn export_json(events: &[Event], writer: &mut dyn Write, local_time: bool) -> Result<()> {
+    let events = events.iter().map(|e| event_json(e, local_time)).collect::<Result<Vec<_>>>()?;
     let json = serde_json::to_string_pretty(&events)
         .context("Failed to serialize events to JSON")?;
     writer.write_all(json.as_bytes())?;
@@ -108,72 +302,528 @@ fn export_json(events: &[Event], writer: &mut dyn Write) -> Result<()> {
     Ok(())
 }
 
-fn export_jsonl(events: &[Event], writer: &mut dyn Write) -> Result<()> {
-    for event in events {
-        let json = serde_json::to_string(&event)
-            .context("Failed to serialize event to JSON")?;
-        writer.write_all(json.as_bytes())?;
-        writer.write_all(b"\n")?;
+fn collect_matching(reader: &LogReader, matcher: &EventMatcher) -> Result<Vec<Event>> {
+    let mut events = Vec::new();
+    reader.for_each_event(|event| {
+        if matcher.matches(&event)
+            && (matcher.pattern.is_none() || matcher.matches_json(&serde_json::to_string(&event)?))
+        {
+            events.push(event);
+        }
+        Ok(())
+    })?;
+    Ok(events)
+}
+
+// One JSON object per line, returning how many of each event type were written
+fn export_ndjson(
+    reader: &LogReader,
+    matcher: &EventMatcher,
+    writer: &mut dyn Write,
+    local_time: bool,
+) -> Result<BTreeMap<&'static str, u64>> {
+    let mut counts = BTreeMap::new();
+    reader.for_each_event(|event| {
+        if !matcher.matches(&event) {
+            return Ok(());
+        }
+        let json = event_json(&event, local_time)?.to_string();
+        if matcher.matches_json(&json) {
+            writer.write_all(json.as_bytes())?;
+            writer.write_all(b"\n")?;
+            *counts.entry(syslog::event_type(&event)).or_insert(0) += 1;
+        }
+        Ok(())
+    })?;
+    Ok(counts)
+}
+
+/// An export written as events stream from the reader, for downloads from the web UI. Unlike
+/// run_export nothing is collected first, so JSON is written an event at a time and CSV is a
+/// single table.
+pub struct ExportStream {
+    matcher: EventMatcher,
+    format: ExportFormat,
+}
+
+impl ExportStream {
+    pub fn new(filter: ExportFilter, format: ExportFormat) -> Result<Self> {
+        if matches!(format, ExportFormat::Csv) && filter.event_type.is_none() {
+            anyhow::bail!("CSV export is one table, pick an event type");
+        }
+        Ok(Self { matcher: EventMatcher::new(filter)?, format })
     }
+
+    pub fn write(&self, reader: &LogReader, writer: &mut dyn Write) -> Result<()> {
+        match self.format {
+            ExportFormat::Jsonl => {
+                export_ndjson(reader, &self.matcher, writer, false)?;
+            }
+            ExportFormat::Json => stream_json(reader, &self.matcher, writer)?,
+            ExportFormat::Csv => stream_csv(reader, &self.matcher, writer)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// A JSON array with an event per line
+fn stream_json(reader: &LogReader, matcher: &EventMatcher, writer: &mut dyn Write) -> Result<()> {
+    let mut first = true;
+    writer.write_all(b"[")?;
+    reader.for_each_event(|event| {
+        if !matcher.matches(&event) {
+            return Ok(());
+        }
+        let json = event_json(&event, false)?.to_string();
+        if matcher.matches_json(&json) {
+            writer.write_all(if first { b"\n" } else { b",\n" })?;
+            writer.write_all(json.as_bytes())?;
+            first = false;
+        }
+        Ok(())
+    })?;
+    writer.write_all(b"\n]\n")?;
     Ok(())
 }
 
-fn export_csv(events: &[Event], writer: &mut dyn Write) -> Result<()> {
-    // Write CSV header
-    writeln!(writer, "timestamp,event_type,details")?;
+// The table of the first matching event's type, others are left out. The SystemMetrics columns
+// depend on the most cores any sample has, so they take a first pass to count them.
+fn stream_csv(reader: &LogReader, matcher: &EventMatcher, writer: &mut dyn Write) -> Result<()> {
+    let mut table: Option<&'static str> = None;
+    let mut metrics_csv: Option<MetricsCsv> = None;
+    reader.for_each_event(|event| {
+        if !matcher.matches(&event) || table.is_some_and(|name| name != csv_name(&event)) {
+            return Ok(());
+        }
+        if matcher.pattern.is_some() && !matcher.matches_json(&serde_json::to_string(&event)?) {
+            return Ok(());
+        }
+        if table.is_none() {
+            table = Some(csv_name(&event));
+            if let Event::SystemMetrics(_) = event {
+                let csv = MetricsCsv::new(max_core_count(reader, matcher)?);
+                writeln!(writer, "{}", csv.header())?;
+                metrics_csv = Some(csv);
+            } else {
+                writeln!(writer, "{}", csv_header(&event).join(","))?;
+            }
+        }
+        match (&event, metrics_csv.as_mut()) {
+            (Event::SystemMetrics(m), Some(csv)) => writeln!(writer, "{}", csv.row(m, false))?,
+            _ => {
+                for row in csv_rows(&event, false) {
+                    writeln!(writer, "{}", row.join(","))?;
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn max_core_count(reader: &LogReader, matcher: &EventMatcher) -> Result<usize> {
+    let mut core_count = 0;
+    reader.for_each_event(|event| {
+        if let Event::SystemMetrics(m) = &event
+            && matcher.matches(&event)
+        {
+            core_count = core_count.max(m.per_core_usage.len());
+        }
+        Ok(())
+    })?;
+    Ok(core_count)
+}
 
-    for event in events {
-        let (ts, event_type, details) = match event {
-            Event::SystemMetrics(m) => (
-                m.ts.unix_timestamp(),
-                "system_metrics",
-                format!(
-                    "CPU:{:.1}% Mem:{:.1}% Disk:{:.0}% Load:{:.2}",
-                    m.cpu_usage_percent,
-                    m.mem_usage_percent,
-                    m.disk_usage_percent,
-                    m.load_avg_1m
-                ),
-            ),
-            Event::ProcessLifecycle(p) => (
-                p.ts.unix_timestamp(),
-                "process_lifecycle",
-                format!("{:?}: {} (pid {})", p.kind, p.name, p.pid),
-            ),
-            Event::ProcessSnapshot(s) => (
-                s.ts.unix_timestamp(),
-                "process_snapshot",
-                format!("{} processes", s.processes.len()),
-            ),
-            Event::SecurityEvent(s) => (
-                s.ts.unix_timestamp(),
-                "security",
-                format!("{:?}: {}", s.kind, s.message),
-            ),
-            Event::Anomaly(a) => (
-                a.ts.unix_timestamp(),
-                "anomaly",
-                format!("{:?} - {:?}: {}", a.severity, a.kind, a.message),
-            ),
-            Event::FileSystemEvent(f) => (
-                f.ts.unix_timestamp(),
-                "filesystem",
-                format!("{:?}: {}", f.kind, f.path),
-            ),
-        };
+// e.g. "Exported 1203 events (Anomaly: 3, SystemMetrics: 1200)"
+fn summary_line(counts: &BTreeMap<&'static str, u64>) -> String {
+    let total: u64 = counts.values().sum();
+    let by_type: Vec<String> = counts.iter().map(|(t, n)| format!("{}: {}", t, n)).collect();
+    if by_type.is_empty() {
+        format!("Exported {} events", total)
+    } else {
+        format!("Exported {} events ({})", total, by_type.join(", "))
+    }
+}
+
+// Quote a CSV field if it contains a delimiter, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn opt_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+// SystemMetrics as CSV rows. Static fields are only recorded periodically, so they're filled
+// forward from the last sample that had them.
+struct MetricsCsv {
+    core_count: usize,
+    kernel_version: Option<String>,
+    cpu_model: Option<String>,
+    cpu_mhz: Option<u32>,
+    mem_total: Option<u64>,
+    swap_total: Option<u64>,
+    disk_total: Option<u64>,
+}
 
-        // Escape CSV fields
-        let details_escaped = details.replace('"', "\"\"");
-        writeln!(writer, "{},\"{}\",\"{}\"", ts, event_type, details_escaped)?;
+impl MetricsCsv {
+    fn new(core_count: usize) -> Self {
+        Self {
+            core_count,
+            kernel_version: None,
+            cpu_model: None,
+            cpu_mhz: None,
+            mem_total: None,
+            swap_total: None,
+            disk_total: None,
+        }
+    }
+
+    fn header(&self) -> String {
+        let mut header = vec![
+            "timestamp".to_string(),
+            "kernel_version".to_string(),
+            "cpu_model".to_string(),
+            "cpu_mhz".to_string(),
+            "cpu_usage_percent".to_string(),
+        ];
+        header.extend((0..self.core_count).map(|i| format!("core{}", i)));
+        header.extend([
+            "mem_used_bytes",
+            "mem_total_bytes",
+            "mem_usage_percent",
+            "swap_used_bytes",
+            "swap_total_bytes",
+            "swap_usage_percent",
+            "load_avg_1m",
+            "load_avg_5m",
+            "load_avg_15m",
+            "disk_read_bytes_per_sec",
+            "disk_write_bytes_per_sec",
+            "disk_used_bytes",
+            "disk_total_bytes",
+            "disk_usage_percent",
+            "net_recv_bytes_per_sec",
+            "net_send_bytes_per_sec",
+            "tcp_connections",
+            "tcp_time_wait",
+            "context_switches_per_sec",
+            "cpu_temp_celsius",
+            "gpu_temp_celsius",
+            "motherboard_temp_celsius",
+        ].map(String::from));
+        header.join(",")
+    }
+
+    fn row(&mut self, m: &SystemMetrics, local_time: bool) -> String {
+        if m.kernel_version.is_some() {
+            self.kernel_version = m.kernel_version.clone();
+        }
+        if m.cpu_model.is_some() {
+            self.cpu_model = m.cpu_model.clone();
+        }
+        self.cpu_mhz = m.cpu_mhz.or(self.cpu_mhz);
+        self.mem_total = m.mem_total_bytes.or(self.mem_total);
+        self.swap_total = m.swap_total_bytes.or(self.swap_total);
+        self.disk_total = m.disk_total_bytes.or(self.disk_total);
+
+        let mut row = vec![
+            csv_timestamp(m.ts, local_time),
+            csv_field(self.kernel_version.as_deref().unwrap_or("")),
+            csv_field(self.cpu_model.as_deref().unwrap_or("")),
+            opt_to_string(self.cpu_mhz),
+            format!("{:.1}", m.cpu_usage_percent),
+        ];
+        row.extend((0..self.core_count).map(|i| {
+            m.per_core_usage.get(i).map(|u| format!("{:.1}", u)).unwrap_or_default()
+        }));
+        row.extend([
+            m.mem_used_bytes.to_string(),
+            opt_to_string(self.mem_total),
+            format!("{:.1}", m.mem_usage_percent),
+            m.swap_used_bytes.to_string(),
+            opt_to_string(self.swap_total),
+            format!("{:.1}", m.swap_usage_percent),
+            format!("{:.2}", m.load_avg_1m),
+            format!("{:.2}", m.load_avg_5m),
+            format!("{:.2}", m.load_avg_15m),
+            m.disk_read_bytes_per_sec.to_string(),
+            m.disk_write_bytes_per_sec.to_string(),
+            m.disk_used_bytes.to_string(),
+            opt_to_string(self.disk_total),
+            format!("{:.1}", m.disk_usage_percent),
+            m.net_recv_bytes_per_sec.to_string(),
+            m.net_send_bytes_per_sec.to_string(),
+            m.tcp_connections.to_string(),
+            m.tcp_time_wait.to_string(),
+            m.context_switches_per_sec.to_string(),
+            opt_to_string(m.temps.cpu_temp_celsius),
+            opt_to_string(m.temps.gpu_temp_celsius),
+            opt_to_string(m.temps.motherboard_temp_celsius),
+        ]);
+        row.join(",")
+    }
+}
+
+fn export_metrics_csv(metrics: &[&SystemMetrics], writer: &mut dyn Write, local_time: bool) -> Result<()> {
+    // Core count can change between recordings (e.g. after a hardware change)
+    let core_count = metrics.iter().map(|m| m.per_core_usage.len()).max().unwrap_or(0);
+
+    let mut csv = MetricsCsv::new(core_count);
+    writeln!(writer, "{}", csv.header())?;
+    for m in metrics {
+        writeln!(writer, "{}", csv.row(m, local_time))?;
+    }
+
+    Ok(())
+}
+
+// Per-disk rows for SystemMetrics, written next to system_metrics.csv
+const DISKS_CSV: &str = "system_metrics_disks";
+
+// Name of the CSV file each event type is exported to
+fn csv_name(event: &Event) -> &'static str {
+    match event {
+        Event::SystemMetrics(_) => "system_metrics",
+        Event::ProcessLifecycle(_) => "process_lifecycle",
+        Event::ProcessSnapshot(_) => "process_snapshot",
+        Event::SecurityEvent(_) => "security_event",
+        Event::Anomaly(_) => "anomaly",
+        Event::FileSystemEvent(_) => "filesystem_event",
+        Event::Annotation(_) => "annotation",
+        Event::DiskHealth(_) => "disk_health",
+        Event::ServiceStateChange(_) => "service_state_change",
+        Event::SystemEvent(_) => "system_event",
+        Event::OperatorAction(_) => "operator_action",
+        Event::NetworkTraffic(_) => "network_traffic",
+    }
+}
+
+fn system_metrics<'a>(events: &[&'a Event]) -> Vec<&'a SystemMetrics> {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            Event::SystemMetrics(m) => Some(m),
+            _ => None,
+        })
+        .collect()
+}
+
+fn export_disks_csv(metrics: &[&SystemMetrics], writer: &mut dyn Write, local_time: bool) -> Result<()> {
+    writeln!(writer, "timestamp,device,read_bytes_per_sec,write_bytes_per_sec,temp_celsius")?;
+    for m in metrics {
+        for disk in &m.per_disk_metrics {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                csv_timestamp(m.ts, local_time),
+                csv_field(&disk.device_name),
+                disk.read_bytes_per_sec,
+                disk.write_bytes_per_sec,
+                opt_to_string(disk.temp_celsius)
+            )?;
+        }
     }
+    Ok(())
+}
 
+// Events of one type (see csv_name) as CSV, one column per field. Optional fields are empty
+// cells and lists are joined with ';', so every row has the header's column count.
+fn export_csv(events: &[&Event], writer: &mut dyn Write, local_time: bool) -> Result<()> {
+    let Some(first) = events.first() else {
+        return Ok(());
+    };
+    // Flattened with a column per core and static fields filled forward
+    if let Event::SystemMetrics(_) = first {
+        return export_metrics_csv(&system_metrics(events), writer, local_time);
+    }
+    writeln!(writer, "{}", csv_header(first).join(","))?;
+    for event in events {
+        for row in csv_rows(event, local_time) {
+            writeln!(writer, "{}", row.join(","))?;
+        }
+    }
     Ok(())
 }
 
+// Columns of an event type's CSV, other than SystemMetrics whose columns depend on the core count
+fn csv_header(event: &Event) -> &'static [&'static str] {
+    match event {
+        Event::SystemMetrics(_) => &[], // See MetricsCsv
+        Event::ProcessLifecycle(_) => &[
+            "timestamp", "kind", "pid", "ppid", "name", "cmdline", "working_dir", "user", "uid", "exit_code",
+            "container_id", "container_name",
+        ],
+        Event::ProcessSnapshot(_) => &[
+            "timestamp", "total_processes", "running_processes", "recorded_processes", "top_cpu_pids", "top_mem_pids",
+        ],
+        Event::SecurityEvent(_) => &["timestamp", "kind", "user", "source_ip", "message"],
+        Event::Anomaly(_) => &["timestamp", "severity", "kind", "message"],
+        Event::FileSystemEvent(_) => &[
+            "timestamp", "kind", "path", "renamed_from", "renamed_to", "size", "old_hash", "new_hash", "count", "diff",
+        ],
+        Event::Annotation(_) => &["timestamp", "author", "text", "tags"],
+        Event::DiskHealth(_) => &[
+            "timestamp", "device", "model", "health_passed", "failing_attributes", "reallocated_sectors",
+            "pending_sectors", "offline_uncorrectable", "crc_errors", "percentage_used", "available_spare",
+            "available_spare_threshold", "media_errors", "critical_warning", "power_on_hours", "temperature_celsius",
+        ],
+        Event::ServiceStateChange(_) => &["timestamp", "unit", "old_state", "new_state", "n_restarts"],
+        Event::SystemEvent(_) => &[
+            "timestamp", "kind", "message", "boot_time", "last_event", "clean_shutdown", "applied", "requires_restart",
+        ],
+        Event::OperatorAction(_) => &["timestamp", "user", "action", "pid", "name", "cmdline", "success", "outcome"],
+        // One row per remote
+        Event::NetworkTraffic(_) => &[
+            "timestamp", "interval_secs", "bytes_counted", "remote", "bytes_sent", "bytes_received", "connections",
+        ],
+    }
+}
+
+// An event's rows under its csv_header, usually one
+fn csv_rows(event: &Event, local_time: bool) -> Vec<Vec<String>> {
+    let opt_text = |value: &Option<String>| csv_field(value.as_deref().unwrap_or(""));
+    let list = |values: &[String]| csv_field(&values.join(";"));
+    let pids = |pids: &[u32]| pids.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(";");
+
+    let ts = csv_timestamp(event.timestamp(), local_time);
+    match event {
+        Event::SystemMetrics(_) => Vec::new(), // See MetricsCsv
+        Event::ProcessLifecycle(p) => vec![vec![
+            ts,
+            format!("{:?}", p.kind),
+            p.pid.to_string(),
+            opt_to_string(p.ppid),
+            csv_field(&p.name),
+            csv_field(&p.cmdline),
+            opt_text(&p.working_dir),
+            opt_text(&p.user),
+            opt_to_string(p.uid),
+            opt_to_string(p.exit_code),
+            opt_text(&p.container_id),
+            opt_text(&p.container_name),
+        ]],
+        Event::ProcessSnapshot(s) => vec![vec![
+            ts,
+            s.total_processes.to_string(),
+            s.running_processes.to_string(),
+            s.processes.len().to_string(),
+            pids(&s.top_cpu_pids),
+            pids(&s.top_mem_pids),
+        ]],
+        Event::SecurityEvent(s) => vec![vec![
+            ts,
+            format!("{:?}", s.kind),
+            csv_field(&s.user),
+            opt_text(&s.source_ip),
+            csv_field(&s.message),
+        ]],
+        Event::Anomaly(a) => vec![vec![ts, format!("{:?}", a.severity), format!("{:?}", a.kind), csv_field(&a.message)]],
+        Event::FileSystemEvent(f) => {
+            let (kind, from, to) = match &f.kind {
+                FileSystemEventKind::Renamed { from, to } => ("Renamed".to_string(), csv_field(from), csv_field(to)),
+                kind => (format!("{:?}", kind), String::new(), String::new()),
+            };
+            vec![vec![
+                ts,
+                kind,
+                csv_field(&f.path),
+                from,
+                to,
+                opt_to_string(f.size),
+                opt_text(&f.old_hash),
+                opt_text(&f.new_hash),
+                f.count.to_string(),
+                opt_text(&f.diff),
+            ]]
+        }
+        Event::Annotation(a) => vec![vec![ts, csv_field(&a.author), csv_field(&a.text), list(&a.tags)]],
+        Event::DiskHealth(d) => vec![vec![
+            ts,
+            csv_field(&d.device),
+            opt_text(&d.model),
+            opt_to_string(d.health_passed),
+            list(&d.failing_attributes),
+            opt_to_string(d.reallocated_sectors),
+            opt_to_string(d.pending_sectors),
+            opt_to_string(d.offline_uncorrectable),
+            opt_to_string(d.crc_errors),
+            opt_to_string(d.percentage_used),
+            opt_to_string(d.available_spare),
+            opt_to_string(d.available_spare_threshold),
+            opt_to_string(d.media_errors),
+            opt_to_string(d.critical_warning),
+            opt_to_string(d.power_on_hours),
+            opt_to_string(d.temperature_celsius),
+        ]],
+        Event::ServiceStateChange(s) => vec![vec![
+            ts,
+            csv_field(&s.unit),
+            csv_field(&s.old_state),
+            csv_field(&s.new_state),
+            opt_to_string(s.n_restarts),
+        ]],
+        Event::SystemEvent(s) => {
+            let mut row = vec![ts, s.kind.name().to_string(), csv_field(&s.message)];
+            match &s.kind {
+                SystemEventKind::Rebooted { boot_time, last_event, clean_shutdown } => row.extend([
+                    csv_timestamp(*boot_time, local_time),
+                    csv_timestamp(*last_event, local_time),
+                    clean_shutdown.to_string(),
+                    String::new(),
+                    String::new(),
+                ]),
+                SystemEventKind::Shutdown => row.extend(std::iter::repeat_n(String::new(), 5)),
+                SystemEventKind::ConfigReloaded { applied, requires_restart } => row.extend([
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    list(applied),
+                    list(requires_restart),
+                ]),
+            }
+            vec![row]
+        }
+        Event::OperatorAction(a) => vec![vec![
+            ts,
+            csv_field(&a.user),
+            csv_field(&a.action),
+            a.pid.to_string(),
+            opt_text(&a.name),
+            opt_text(&a.cmdline),
+            a.success.to_string(),
+            csv_field(&a.outcome),
+        ]],
+        Event::NetworkTraffic(t) => t
+            .top_remotes
+            .iter()
+            .map(|r| {
+                vec![
+                    ts.clone(),
+                    t.interval_secs.to_string(),
+                    t.bytes_counted.to_string(),
+                    csv_field(&r.remote),
+                    r.bytes_sent.to_string(),
+                    r.bytes_received.to_string(),
+                    r.connections.to_string(),
+                ]
+            })
+            .collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::event::{sample_metrics, Anomaly, AnomalyKind, AnomalySeverity};
+    use crate::recorder::Recorder;
+    use crate::storage::RetentionPolicy;
+
     #[test]
     fn test_parse_timestamp() {
         // Unix timestamp
@@ -182,63 +832,217 @@ mod tests {
         // RFC3339
         let result = parse_timestamp("2024-01-01T00:00:00Z");
         assert!(result.is_ok());
+
+        // Relative to now
+        let now = 1_767_600_000;
+        assert_eq!(parse_timestamp_at("-2h", now).unwrap(), now - 7200);
+        assert_eq!(parse_timestamp_at("-90s", now).unwrap(), now - 90);
+        assert_eq!(parse_timestamp_at("-1d", now).unwrap(), now - 86_400);
+        assert!(parse_timestamp_at("-2x", now).is_err());
+        assert!(parse_timestamp_at("--2h", now).is_err());
     }
 
     #[test]
     fn test_matches_event_type() {
-        use crate::event::{GpuInfo, SystemMetrics, TemperatureReadings};
-        use time::OffsetDateTime;
-
-        let event = Event::SystemMetrics(SystemMetrics {
-            ts: OffsetDateTime::now_utc(),
-            kernel_version: Some("6.0.0-test on x86_64".to_string()),
-            cpu_model: Some("Test CPU".to_string()),
-            cpu_mhz: Some(3000),
-            mem_total_bytes: Some(0),
-            swap_total_bytes: Some(0),
-            disk_total_bytes: Some(0),
-            filesystems: Some(vec![]),
-            net_interface: None,
-            net_ip_address: None,
-            net_gateway: None,
-            net_dns: None,
-            fans: Some(vec![]),
-            logged_in_users: Some(vec![]),
-            system_uptime_seconds: 0,
-            cpu_usage_percent: 50.0,
-            per_core_usage: vec![],
-            mem_used_bytes: 0,
-            mem_usage_percent: 0.0,
-            swap_used_bytes: 0,
-            swap_usage_percent: 0.0,
-            load_avg_1m: 0.0,
-            load_avg_5m: 0.0,
-            load_avg_15m: 0.0,
-            disk_read_bytes_per_sec: 0,
-            disk_write_bytes_per_sec: 0,
-            disk_used_bytes: 0,
-            disk_usage_percent: 0.0,
-            per_disk_metrics: vec![],
-            net_recv_bytes_per_sec: 0,
-            net_send_bytes_per_sec: 0,
-            net_recv_errors_per_sec: 0,
-            net_send_errors_per_sec: 0,
-            net_recv_drops_per_sec: 0,
-            net_send_drops_per_sec: 0,
-            tcp_connections: 0,
-            tcp_time_wait: 0,
-            context_switches_per_sec: 0,
-            temps: TemperatureReadings {
-                cpu_temp_celsius: None,
-                per_core_temps: vec![],
-                gpu_temp_celsius: None,
-                motherboard_temp_celsius: None,
-            },
-            gpu: GpuInfo::default(),
-        });
+        let event = Event::SystemMetrics(sample_metrics());
 
         assert!(matches_event_type(&event, "system"));
         assert!(matches_event_type(&event, "metrics"));
         assert!(!matches_event_type(&event, "security"));
     }
+
+    fn anomaly(severity: AnomalySeverity, message: &str) -> Event {
+        Event::Anomaly(Anomaly {
+            ts: OffsetDateTime::now_utc(),
+            severity,
+            kind: AnomalyKind::CpuSpike,
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_event_matcher() {
+        let critical = anomaly(AnomalySeverity::Critical, "postgres using 99% CPU");
+        let warning = anomaly(AnomalySeverity::Warning, "nginx using 80% CPU");
+        let metrics = Event::SystemMetrics(sample_metrics());
+
+        let matcher = EventMatcher::new(ExportFilter { severity: Some(FollowSeverity::Critical), ..Default::default() }).unwrap();
+        assert!(matcher.matches(&critical));
+        assert!(!matcher.matches(&warning));
+        assert!(!matcher.matches(&metrics));
+
+        let matcher = EventMatcher::new(ExportFilter { severity: Some(FollowSeverity::Warning), ..Default::default() }).unwrap();
+        assert!(matcher.matches(&critical) && matcher.matches(&warning));
+
+        let matcher = EventMatcher::new(ExportFilter { pattern: Some("post(gres|master)".to_string()), ..Default::default() }).unwrap();
+        assert!(matcher.matches_json(&serde_json::to_string(&critical).unwrap()));
+        assert!(!matcher.matches_json(&serde_json::to_string(&warning).unwrap()));
+
+        assert!(EventMatcher::new(ExportFilter { pattern: Some("(".to_string()), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_export_ndjson_streams_matching_events() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&Event::SystemMetrics(sample_metrics())).unwrap();
+        recorder.append(&anomaly(AnomalySeverity::Critical, "disk full on /var")).unwrap();
+        recorder.append(&anomaly(AnomalySeverity::Critical, "postgres using 99% CPU")).unwrap();
+        recorder.append(&anomaly(AnomalySeverity::Warning, "postgres using 80% CPU")).unwrap();
+        recorder.close().unwrap();
+
+        let filter = ExportFilter {
+            severity: Some(FollowSeverity::Critical),
+            pattern: Some("postgres".to_string()),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let counts = export_ndjson(&LogReader::new(temp_dir.path()), &EventMatcher::new(filter).unwrap(), &mut out, false).unwrap();
+
+        let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("postgres using 99% CPU"));
+        assert_eq!(summary_line(&counts), "Exported 1 events (Anomaly: 1)");
+    }
+
+    #[test]
+    fn test_local_time_timestamps() {
+        let event = anomaly(AnomalySeverity::Warning, "nginx using 80% CPU");
+        let ts = event.timestamp();
+
+        // Stored form by default, an RFC3339 string for the same instant with --local-time
+        assert!(event_json(&event, false).unwrap()["Anomaly"]["ts"].is_array());
+        let json = event_json(&event, true).unwrap();
+        let local = OffsetDateTime::parse(json["Anomaly"]["ts"].as_str().unwrap(), &Rfc3339).unwrap();
+        assert_eq!(local.unix_timestamp(), ts.unix_timestamp());
+        assert_eq!(json["Anomaly"]["message"], "nginx using 80% CPU");
+
+        let utc = csv_timestamp(ts, false);
+        assert!(utc.ends_with('Z'), "{}", utc);
+        assert_eq!(OffsetDateTime::parse(&utc, &Rfc3339).unwrap().unix_timestamp(), ts.unix_timestamp());
+        let local = OffsetDateTime::parse(&csv_timestamp(ts, true), &Rfc3339).unwrap();
+        assert_eq!(local.unix_timestamp(), ts.unix_timestamp());
+    }
+
+    #[test]
+    fn test_export_metrics_csv_forward_fills_static_fields() {
+        let mut first = sample_metrics();
+        first.per_core_usage = vec![10.0, 20.0];
+        first.mem_total_bytes = Some(8192);
+        let mut second = sample_metrics();
+        second.kernel_version = None;
+        second.mem_total_bytes = None;
+        second.per_core_usage = vec![30.0];
+
+        let mut out = Vec::new();
+        export_metrics_csv(&[&first, &second], &mut out, false).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+
+        let header: Vec<&str> = lines[0].split(',').collect();
+        assert!(header.contains(&"core0") && header.contains(&"core1"));
+        let col = |name: &str| header.iter().position(|h| *h == name).unwrap();
+
+        let row: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!(row.len(), header.len());
+        assert_eq!(row[col("kernel_version")], "6.0.0-test on x86_64");
+        assert_eq!(row[col("mem_total_bytes")], "8192");
+        assert_eq!(row[col("core0")], "30.0");
+        assert_eq!(row[col("core1")], "");
+    }
+
+    #[test]
+    fn test_sibling_csv_path() {
+        assert_eq!(sibling_csv_path("out/metrics.csv", DISKS_CSV, false), PathBuf::from("out/system_metrics_disks.csv"));
+        assert_eq!(sibling_csv_path("metrics.csv.gz", DISKS_CSV, true), PathBuf::from("system_metrics_disks.csv.gz"));
+        assert_eq!(
+            sibling_csv_path("out/system_metrics_disks.csv", DISKS_CSV, false),
+            PathBuf::from("out/other_system_metrics_disks.csv")
+        );
+    }
+
+    #[test]
+    fn test_export_csv_flattens_each_type_with_stable_columns() {
+        let ts = OffsetDateTime::from_unix_timestamp(1_768_471_200).unwrap();
+        let events = [
+            anomaly(AnomalySeverity::Warning, "Disk write spike started: 120MB/s, sda"),
+            anomaly(AnomalySeverity::Info, "CPU spike resolved after 4m12s"),
+        ];
+        let mut out = Vec::new();
+        export_csv(&events.iter().collect::<Vec<_>>(), &mut out, false).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "timestamp,severity,kind,message");
+        assert!(lines[1].ends_with(",Warning,CpuSpike,\"Disk write spike started: 120MB/s, sda\""), "{}", lines[1]);
+        assert_eq!(lines.len(), 3);
+
+        // Missing optional fields are empty cells rather than left out
+        let renamed = Event::FileSystemEvent(crate::event::FileSystemEvent {
+            ts,
+            kind: FileSystemEventKind::Renamed { from: "/etc/a".to_string(), to: "/etc/b".to_string() },
+            path: "/etc/b".to_string(),
+            size: None,
+            old_hash: None,
+            new_hash: None,
+            diff: None,
+            count: 1,
+            old_attributes: None,
+            new_attributes: None,
+        });
+        let mut out = Vec::new();
+        export_csv(&[&renamed], &mut out, false).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[1], "2026-01-15T10:00:00Z,Renamed,/etc/b,/etc/a,/etc/b,,,,1,");
+        assert_eq!(lines[1].split(',').count(), lines[0].split(',').count());
+
+        // Traffic gets a row per remote
+        let traffic = Event::NetworkTraffic(crate::event::NetworkTraffic {
+            ts,
+            interval_secs: 10,
+            bytes_counted: true,
+            top_remotes: ["203.0.113.0/24", "198.51.100.7"]
+                .map(|remote| crate::event::RemoteTraffic {
+                    remote: remote.to_string(),
+                    bytes_sent: 100,
+                    bytes_received: 50,
+                    connections: 2,
+                })
+                .to_vec(),
+        });
+        let mut out = Vec::new();
+        export_csv(&[&traffic], &mut out, false).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(csv.lines().nth(2), Some("2026-01-15T10:00:00Z,10,true,198.51.100.7,100,50,2"));
+    }
+
+    #[test]
+    fn test_export_disks_csv_has_a_row_per_disk() {
+        let mut metrics = sample_metrics();
+        metrics.ts = OffsetDateTime::from_unix_timestamp(1_768_471_200).unwrap();
+        metrics.per_disk_metrics = ["sda", "nvme0n1"]
+            .map(|device| crate::event::PerDiskMetrics {
+                device_name: device.to_string(),
+                read_bytes_per_sec: 1024,
+                write_bytes_per_sec: 2048,
+                temp_celsius: None,
+            })
+            .to_vec();
+
+        let mut out = Vec::new();
+        export_disks_csv(&[&metrics, &metrics], &mut out, false).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2], "2026-01-15T10:00:00Z,nvme0n1,1024,2048,");
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
 }
@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::integrity::{self, RecordChain, SegmentFormat};
+use crate::process_delta::{self, Payload};
+use crate::storage::{self, find_segment_files, load_segment, RecordHeader, SegmentData, SEGMENT_HEADER_LEN};
+
+// Migrated segments get checksums. Only the recorder signs, with the chain it started itself:
+// signing old records now would vouch for whatever happened to them before.
+const TARGET_FORMAT: SegmentFormat = SegmentFormat::Checksummed;
+
+// A segment rewritten in the current format
+struct Migrated {
+    data: Vec<u8>,
+    records: usize,
+    trailing_bytes: usize, // A partial final record left by a crash, which readers skip too
+}
+
+// Rewrite segments from older format versions in the current one, so they're checked like
+// everything recorded since. Segments already in a current format are left alone, and so is
+// any segment with a record that doesn't decode. The original of each segment rewritten is
+// kept alongside it as .dat.bak.
+pub fn run_migrate(data_dir: Option<String>) -> Result<()> {
+    let config = Config::load()?;
+    let data_dir = data_dir.unwrap_or_else(|| config.server.data_dir.clone());
+    if !Path::new(&data_dir).is_dir() {
+        anyhow::bail!("Data directory {} not found", data_dir);
+    }
+
+    let (mut migrated, mut failed) = (0, 0);
+    for (id, path) in find_segment_files(Path::new(&data_dir)) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match migrate_file(id, &path) {
+            Ok(Some((from, segment))) => {
+                eprintln!(
+                    "  {}  version {} ({}) -> {} ({})  {} records{}",
+                    name,
+                    from.version(),
+                    from.name(),
+                    TARGET_FORMAT.version(),
+                    TARGET_FORMAT.name(),
+                    segment.records,
+                    match segment.trailing_bytes {
+                        0 => String::new(),
+                        bytes => format!(", {} bytes of a partial final record dropped", bytes),
+                    }
+                );
+                migrated += 1;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("  {}  FAILED: {:#}", name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    eprintln!("Migrated {} segment(s)", migrated);
+    if migrated > 0 {
+        eprintln!("Originals are kept as .dat.bak, delete them once the migrated segments have been checked");
+    }
+    if failed > 0 {
+        anyhow::bail!("{} segment(s) could not be migrated", failed);
+    }
+    Ok(())
+}
+
+// Migrate one segment file in place, keeping its compression and mtime, after copying the
+// original to .dat.bak. Returns the format it was in, None if it's already current.
+fn migrate_file(segment_id: u64, path: &Path) -> Result<Option<(SegmentFormat, Migrated)>> {
+    let data = load_segment(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let format = SegmentFormat::detect(&data)?;
+    let Some(segment) = migrate_segment(&data, format, segment_id)? else {
+        return Ok(None);
+    };
+
+    let level = match data {
+        SegmentData::Decompressed(_) => zstd::DEFAULT_COMPRESSION_LEVEL,
+        SegmentData::Mapped(_) => 0,
+    };
+    drop(data);
+    let backup = path.with_extension("dat.bak");
+    std::fs::copy(path, &backup).with_context(|| format!("Failed to back up to {}", backup.display()))?;
+    storage::rewrite_segment(path, &segment.data, level)?;
+    // Offsets have moved, the index is rebuilt on the next read
+    let _ = std::fs::remove_file(path.with_extension("idx"));
+    Ok(Some((format, segment)))
+}
+
+// One step per format older than the current ones. A format bump adds a step taking the
+// format it replaces up to the new one, rather than leaving older recordings unreadable.
+fn migrate_segment(data: &[u8], format: SegmentFormat, segment_id: u64) -> Result<Option<Migrated>> {
+    match format {
+        SegmentFormat::Plain => from_plain(data, segment_id).map(Some),
+        SegmentFormat::Checksummed | SegmentFormat::Signed => Ok(None),
+    }
+}
+
+// Segments from before checksums. Events have only had fields appended since, so each payload
+// decodes in the current layout with the fields it lacks as None, empty or 0 (see Event::decode),
+// and is written again in full. Fails on the first record that doesn't decode rather than lose it.
+fn from_plain(data: &[u8], segment_id: u64) -> Result<Migrated> {
+    let mut migrated = Migrated { data: TARGET_FORMAT.magic().to_le_bytes().to_vec(), records: 0, trailing_bytes: 0 };
+    let mut chain = RecordChain::new(TARGET_FORMAT, None, segment_id);
+    let mut end = SEGMENT_HEADER_LEN as usize;
+    for record in integrity::records(data, end, SegmentFormat::Plain) {
+        let payload = match process_delta::decode_payload(record.payload) {
+            Ok(Payload::Event(event)) => bincode::serialize(&event)?,
+            Ok(Payload::Delta(delta)) => process_delta::encode_delta(&delta)?,
+            Err(e) => anyhow::bail!("record at offset {} doesn't decode ({}), segment left as it was", record.offset, e),
+        };
+        end = record.offset as usize + record.bytes.len();
+        let header = RecordHeader { timestamp_unix_ns: record.header.timestamp_unix_ns, payload_len: payload.len() as u32 };
+        let mut bytes = bincode::serialize(&header)?;
+        bytes.extend_from_slice(&payload);
+        let trailer = chain.seal(&bytes);
+        migrated.data.extend_from_slice(&bytes);
+        migrated.data.extend_from_slice(&trailer);
+        migrated.records += 1;
+    }
+    migrated.trailing_bytes = data.len() - end;
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::LogReader;
+
+    // Recorded by the first release, before checksums or any field appended to an event
+    const BASELINE_SEGMENT: &[u8] = include_bytes!("../testdata/baseline_segment.dat");
+
+    fn describe_all(dir: &Path) -> Vec<String> {
+        LogReader::new(dir).read_all_events().unwrap().iter().map(|event| format!("{:?}", event)).collect()
+    }
+
+    #[test]
+    fn test_migrate_first_release_segment() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("segment_00000.dat");
+        std::fs::write(&path, BASELINE_SEGMENT).unwrap();
+        let before = describe_all(dir.path());
+        assert_eq!(before.len(), 7);
+
+        let (from, segment) = migrate_file(0, &path).unwrap().unwrap();
+        assert_eq!(from, SegmentFormat::Plain);
+        assert_eq!((segment.records, segment.trailing_bytes), (7, 0));
+        assert_eq!(std::fs::read(path.with_extension("dat.bak")).unwrap(), BASELINE_SEGMENT);
+        assert!(migrate_file(0, &path).unwrap().is_none());
+
+        let reports = LogReader::new(dir.path()).verify_segments().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].format, Some(SegmentFormat::Checksummed));
+        assert!(reports[0].violations.is_empty() && reports[0].trailing_bytes == 0);
+        assert_eq!(describe_all(dir.path()), before);
+    }
+
+    #[test]
+    fn test_segment_with_undecodable_record_is_left_alone() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("segment_00000.dat");
+        let garbage = [0xFF; 12];
+        let mut data = BASELINE_SEGMENT.to_vec();
+        data.extend(bincode::serialize(&RecordHeader { timestamp_unix_ns: 0, payload_len: garbage.len() as u32 }).unwrap());
+        data.extend(garbage);
+        std::fs::write(&path, &data).unwrap();
+
+        let Err(err) = migrate_file(0, &path) else {
+            panic!("expected the segment to be refused");
+        };
+        assert!(err.to_string().contains("doesn't decode"), "{:#}", err);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert!(!path.with_extension("dat.bak").exists());
+    }
+
+    #[test]
+    fn test_newer_version_is_not_migrated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("segment_00000.dat");
+        let mut data = (storage::MAGIC_PREFIX | 0x00FF).to_le_bytes().to_vec();
+        data.extend([0; 16]);
+        std::fs::write(&path, &data).unwrap();
+
+        let Err(err) = migrate_file(0, &path) else {
+            panic!("expected the segment to be refused");
+        };
+        assert_eq!(err.downcast_ref(), Some(&integrity::UnsupportedVersion { version: 0xFF }));
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+    }
+}
@@ -1,8 +1,29 @@
+pub mod alerts;
 pub mod config;
 pub mod export;
+pub mod migrate;
 pub mod monitor;
+pub mod query;
 pub mod status;
+pub mod verify;
 pub mod systemd;
+pub mod tail;
+mod uds;
+
+use anyhow::Context;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// HTTP client for talking to a black box server over http:// or https://.
+pub fn http_client(timeout: std::time::Duration, insecure: bool) -> anyhow::Result<reqwest::blocking::Client> {
+    if insecure {
+        eprintln!("Warning: TLS certificate verification is disabled (--insecure)");
+    }
+    Ok(reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .danger_accept_invalid_certs(insecure)
+        .build()?)
+}
 
 /// Apply optional HTTP basic auth to a request builder.
 pub fn with_auth(
@@ -16,3 +37,65 @@ pub fn with_auth(
         req
     }
 }
+
+/// Black box server a command talks to, at a URL or on the Unix socket given with --socket
+pub enum ServerApi {
+    Http { client: reqwest::blocking::Client, base_url: String },
+    Unix { socket: PathBuf, timeout: Duration },
+}
+
+pub struct ApiResponse {
+    pub status: reqwest::StatusCode,
+    body: Vec<u8>,
+}
+
+impl ApiResponse {
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> anyhow::Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+impl ServerApi {
+    pub fn new(url: &str, socket: Option<PathBuf>, timeout: Duration, insecure: bool) -> anyhow::Result<Self> {
+        Ok(match socket {
+            Some(socket) => ServerApi::Unix { socket, timeout },
+            None => ServerApi::Http {
+                client: http_client(timeout, insecure)?,
+                base_url: url.trim_end_matches('/').to_string(),
+            },
+        })
+    }
+
+    /// The URL or socket, for display
+    pub fn target(&self) -> String {
+        match self {
+            ServerApi::Http { base_url, .. } => base_url.clone(),
+            ServerApi::Unix { socket, .. } => format!("unix:{}", socket.display()),
+        }
+    }
+
+    /// Whether the server is on this machine, so its data directory can be read directly
+    pub fn is_local(&self) -> bool {
+        match self {
+            ServerApi::Http { base_url, .. } => base_url.contains("localhost") || base_url.contains("127.0.0.1"),
+            ServerApi::Unix { .. } => true,
+        }
+    }
+
+    /// GET `path` (with any query string), with basic auth if both credentials are given
+    pub fn get(&self, path: &str, username: &Option<String>, password: &Option<String>) -> anyhow::Result<ApiResponse> {
+        match self {
+            ServerApi::Http { client, base_url } => {
+                let response = with_auth(client.get(format!("{}{}", base_url, path)), username, password).send()?;
+                Ok(ApiResponse { status: response.status(), body: response.bytes()?.to_vec() })
+            }
+            ServerApi::Unix { socket, timeout } => {
+                let auth = username.as_deref().zip(password.as_deref());
+                let response = uds::get(socket, path, auth, *timeout)?;
+                let status = reqwest::StatusCode::from_u16(response.status)
+                    .with_context(|| format!("Invalid HTTP status {}", response.status))?;
+                Ok(ApiResponse { status, body: response.body })
+            }
+        }
+    }
+}
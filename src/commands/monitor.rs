@@ -1,10 +1,40 @@
 use anyhow::{Context, Result};
-use reqwest::blocking::Client;
+use base64::{engine::general_purpose, Engine as _};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
 use serde::Deserialize;
 use std::fs;
+use std::io::{ErrorKind, IsTerminal};
+use std::net::TcpStream;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Connector, HandshakeError, Message, WebSocket};
+
+use crate::cli::{FollowEventType, FollowSeverity};
+
+const FOLLOW_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const FOLLOW_MAX_BACKOFF: Duration = Duration::from_secs(60);
+// How often a blocked socket read wakes up to check for Ctrl+C
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// ANSI colours, only used when stdout is a terminal
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+type FollowSocket = WebSocket<MaybeTlsStream<TcpStream>>;
 
 #[derive(Deserialize)]
 struct HealthResponse {
@@ -14,7 +44,7 @@ struct HealthResponse {
 }
 
 pub fn run_monitor(
-    url: String,
+    server: super::ServerApi,
     username: Option<String>,
     password: Option<String>,
     interval: u64,
@@ -22,7 +52,7 @@ pub fn run_monitor(
     continuous: bool,
 ) -> Result<()> {
     println!("Black Box Monitor");
-    println!("Target: {}", url);
+    println!("Target: {}", server.target());
     println!("Check interval: {}s", interval);
     println!("Export directory: {}", export_dir);
     println!("Mode: {}", if continuous { "continuous" } else { "failure-only" });
@@ -31,13 +61,6 @@ pub fn run_monitor(
     // Create export directory if it doesn't exist
     fs::create_dir_all(&export_dir).context("Failed to create export directory")?;
 
-    let client = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()?;
-
-    let health_url = format!("{}/health", url.trim_end_matches('/'));
-    let api_url = format!("{}/api/events", url.trim_end_matches('/'));
-
     let mut last_event_count = 0;
     let mut consecutive_failures = 0;
 
@@ -45,8 +68,8 @@ pub fn run_monitor(
         let check_time = chrono::Utc::now();
 
         // Check health
-        match super::with_auth(client.get(&health_url), &username, &password).send() {
-            Ok(response) if response.status().is_success() => {
+        match server.get("/health", &username, &password) {
+            Ok(response) if response.status.is_success() => {
                 match response.json::<HealthResponse>() {
                     Ok(health) => {
                         consecutive_failures = 0;
@@ -65,14 +88,14 @@ pub fn run_monitor(
                                 "  WARNING: Event count decreased from {} to {} (possible data loss or rotation)",
                                 last_event_count, health.event_count
                             );
-                            perform_export(&client, &api_url, &export_dir, &username, &password, "event-count-decrease")?;
+                            perform_export(&server, &export_dir, &username, &password, "event-count-decrease")?;
                         }
 
                         last_event_count = health.event_count;
 
                         // Export if in continuous mode
                         if continuous {
-                            perform_export(&client, &api_url, &export_dir, &username, &password, "scheduled")?;
+                            perform_export(&server, &export_dir, &username, &password, "scheduled")?;
                         }
                     }
                     Err(e) => {
@@ -86,10 +109,10 @@ pub fn run_monitor(
                 eprintln!(
                     "[{}] ERROR: Server returned status {} - performing emergency export",
                     check_time.format("%Y-%m-%d %H:%M:%S"),
-                    response.status()
+                    response.status
                 );
                 consecutive_failures += 1;
-                perform_export(&client, &api_url, &export_dir, &username, &password, "error")?;
+                perform_export(&server, &export_dir, &username, &password, "error")?;
             }
             Err(e) => {
                 eprintln!(
@@ -100,7 +123,7 @@ pub fn run_monitor(
                 consecutive_failures += 1;
 
                 // Try to export via direct file access if on same machine
-                if url.contains("localhost") || url.contains("127.0.0.1") {
+                if server.is_local() {
                     eprintln!("  Attempting direct file access for local server...");
                     if let Err(e) = perform_direct_export(&export_dir) {
                         eprintln!("  Direct export failed: {}", e);
@@ -118,9 +141,349 @@ pub fn run_monitor(
     }
 }
 
+// The subset of the WebSocket JSON (see webui/websocket.rs) that --follow prints
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum StreamEvent {
+    ProcessLifecycle {
+        timestamp: i64, // Unix milliseconds
+        kind: String,
+        pid: u32,
+        name: String,
+    },
+    SecurityEvent {
+        timestamp: i64,
+        kind: String,
+        user: String,
+        source_ip: Option<String>,
+    },
+    Anomaly {
+        timestamp: i64,
+        severity: String,
+        kind: String,
+        message: String,
+    },
+    FileSystemEvent {
+        timestamp: i64,
+        kind: String,
+        path: String,
+        count: Option<u32>,
+    },
+    // Metrics, snapshots and metadata
+    #[serde(other)]
+    Other,
+}
+
+// Event selection for --follow, mirroring the web UI's type and search filters
+pub struct FollowFilter {
+    types: Vec<FollowEventType>, // Empty means all
+    min_severity: Option<FollowSeverity>,
+    text: Option<String>, // Lowercased
+}
+
+impl FollowFilter {
+    pub fn new(types: Vec<FollowEventType>, min_severity: Option<FollowSeverity>, text: Option<String>) -> Self {
+        Self {
+            types,
+            min_severity,
+            text: text.map(|t| t.to_lowercase()).filter(|t| !t.is_empty()),
+        }
+    }
+
+    fn matches(&self, event: &StreamEvent, raw: &str) -> bool {
+        let (event_type, severity) = match event {
+            StreamEvent::ProcessLifecycle { .. } => (FollowEventType::Process, None),
+            StreamEvent::SecurityEvent { .. } => (FollowEventType::Security, None),
+            StreamEvent::Anomaly { severity, .. } => (FollowEventType::Anomaly, Some(severity.as_str())),
+            StreamEvent::FileSystemEvent { .. } => (FollowEventType::Filesystem, None),
+            StreamEvent::Other => return false,
+        };
+
+        if !self.types.is_empty() && !self.types.contains(&event_type) {
+            return false;
+        }
+        if let (Some(min), Some(severity)) = (self.min_severity, severity) {
+            let severity = match severity {
+                "Critical" => FollowSeverity::Critical,
+                "Warning" => FollowSeverity::Warning,
+                _ => FollowSeverity::Info,
+            };
+            if severity < min {
+                return false;
+            }
+        }
+        self.text.as_ref().is_none_or(|text| raw.to_lowercase().contains(text))
+    }
+}
+
+// How followed events are printed: a coloured line each, or the event's JSON as received
+pub struct FollowOutput {
+    filter: FollowFilter,
+    json: bool,
+    color: bool,
+}
+
+impl FollowOutput {
+    pub fn new(filter: FollowFilter, json: bool) -> Self {
+        Self { filter, json, color: !json && std::io::stdout().is_terminal() }
+    }
+
+    // `raw` is the message `value` was parsed from, searched by --filter and printed by --json
+    pub fn print(&self, value: serde_json::Value, raw: &str) {
+        let Ok(event) = serde_json::from_value::<StreamEvent>(value) else {
+            return;
+        };
+        if !self.filter.matches(&event, raw) {
+            return;
+        }
+        if self.json {
+            println!("{}", raw);
+        } else if let Some(line) = render_event(&event, self.color) {
+            println!("{}", line);
+        }
+    }
+}
+
+// One line per event: local time, a fixed-width label, then the details
+fn render_event(event: &StreamEvent, color: bool) -> Option<String> {
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let (timestamp, label, details) = match event {
+        StreamEvent::Anomaly { timestamp, severity, kind, message } => {
+            let code = match severity.as_str() {
+                "Critical" => RED,
+                "Warning" => YELLOW,
+                _ => BLUE,
+            };
+            let label = paint(code, &format!("{:<8}", severity.to_uppercase()));
+            (*timestamp, label, format!("{}: {}", kind, message))
+        }
+        StreamEvent::SecurityEvent { timestamp, kind, user, source_ip } => {
+            let code = if kind.contains("Fail") { RED } else { MAGENTA };
+            let from = source_ip.as_ref().map(|ip| format!(" from {}", ip)).unwrap_or_default();
+            (*timestamp, paint(code, "SECURITY"), format!("{} {}{}", kind, user, from))
+        }
+        StreamEvent::ProcessLifecycle { timestamp, kind, pid, name } => {
+            let code = if kind == "Started" { GREEN } else { DIM };
+            (*timestamp, paint(code, "PROCESS "), format!("{} {} ({})", kind, name, pid))
+        }
+        StreamEvent::FileSystemEvent { timestamp, kind, path, count } => {
+            let repeats = count.filter(|c| *c > 1).map(|c| format!(" (x{})", c)).unwrap_or_default();
+            (*timestamp, paint(CYAN, "FILE    "), format!("{} {}{}", kind, path, repeats))
+        }
+        StreamEvent::Other => return None,
+    };
+
+    let time = chrono::DateTime::from_timestamp_millis(timestamp)?
+        .with_timezone(&chrono::Local)
+        .format("%H:%M:%S");
+    Some(format!("{} {} {}", paint(DIM, &time.to_string()), label, details))
+}
+
+fn websocket_url(url: &str) -> Result<String> {
+    let base = url.trim_end_matches('/');
+    let ws_base = if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        anyhow::bail!("URL must start with http:// or https://: {}", url);
+    };
+    Ok(format!("{}/ws", ws_base))
+}
+
+// Print events from the live stream until Ctrl+C, reconnecting with backoff when the socket drops.
+// Like the web UI, a reconnect asks to be caught up from the last event seen.
+pub fn run_follow(
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    insecure: bool,
+    output: FollowOutput,
+) -> Result<()> {
+    let ws_url = websocket_url(&url)?;
+    if insecure {
+        eprintln!("Warning: TLS certificate verification is disabled (--insecure)");
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let stop = stop.clone();
+        ctrlc::set_handler(move || stop.store(true, Ordering::SeqCst))?;
+    }
+
+    let mut backoff = Duration::from_secs(1);
+    let mut last_seq = None;
+    eprintln!("Following {} (Ctrl+C to stop)", ws_url);
+
+    while !stop.load(Ordering::SeqCst) {
+        match connect_websocket(&ws_url, &username, &password, insecure) {
+            Ok(mut socket) => {
+                eprintln!("Connected");
+                backoff = Duration::from_secs(1);
+                match stream_events(&mut socket, &output, &mut last_seq, &stop) {
+                    Ok(()) => {
+                        let _ = socket.close(None);
+                        let _ = socket.flush();
+                        break;
+                    }
+                    Err(e) => eprintln!("Connection lost: {} (reconnecting in {}s)", e, backoff.as_secs()),
+                }
+            }
+            Err(tungstenite::Error::Http(response)) if response.status() == 401 => {
+                anyhow::bail!("Server rejected the credentials, check --username and --password");
+            }
+            Err(e) => eprintln!("Failed to connect: {} (retrying in {}s)", e, backoff.as_secs()),
+        }
+
+        // Sleep in short steps so Ctrl+C isn't held up by a long backoff
+        let mut waited = Duration::ZERO;
+        while waited < backoff && !stop.load(Ordering::SeqCst) {
+            thread::sleep(FOLLOW_POLL_INTERVAL);
+            waited += FOLLOW_POLL_INTERVAL;
+        }
+        backoff = (backoff * 2).min(FOLLOW_MAX_BACKOFF);
+    }
+
+    Ok(())
+}
+
+fn connect_websocket(
+    ws_url: &str,
+    username: &Option<String>,
+    password: &Option<String>,
+    insecure: bool,
+) -> tungstenite::Result<FollowSocket> {
+    let mut request = ws_url.into_client_request()?;
+    // Same Basic credentials the other subcommands send; the server accepts them on /ws too
+    if let (Some(u), Some(p)) = (username, password) {
+        let credentials = general_purpose::STANDARD.encode(format!("{}:{}", u, p));
+        if let Ok(value) = format!("Basic {}", credentials).parse() {
+            request.headers_mut().insert("Authorization", value);
+        }
+    }
+
+    let uri = request.uri();
+    let host = uri.host().unwrap_or("localhost").trim_start_matches('[').trim_end_matches(']');
+    let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("wss") { 443 } else { 80 });
+    let addr = std::net::ToSocketAddrs::to_socket_addrs(&(host, port))?
+        .next()
+        .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, format!("Cannot resolve {}", host)))?;
+    let stream = TcpStream::connect_timeout(&addr, FOLLOW_CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(FOLLOW_CONNECT_TIMEOUT))?;
+    let timeouts = stream.try_clone()?;
+
+    let connector = insecure.then(|| Connector::Rustls(Arc::new(insecure_tls_config())));
+    let (socket, _) = match tungstenite::client_tls_with_config(request, stream, None, connector) {
+        Ok(connected) => connected,
+        Err(HandshakeError::Failure(e)) => return Err(e),
+        Err(HandshakeError::Interrupted(_)) => {
+            return Err(std::io::Error::new(ErrorKind::TimedOut, "WebSocket handshake timed out").into());
+        }
+    };
+
+    // Short read timeout from here on so the read loop can notice Ctrl+C
+    timeouts.set_read_timeout(Some(FOLLOW_POLL_INTERVAL))?;
+    Ok(socket)
+}
+
+// Ok(()) when stopped by Ctrl+C, Err when the connection drops. `last_seq` is the seq of the
+// last event received, kept across reconnects.
+fn stream_events(
+    socket: &mut FollowSocket,
+    output: &FollowOutput,
+    last_seq: &mut Option<String>,
+    stop: &AtomicBool,
+) -> Result<()> {
+    if let Some(seq) = last_seq {
+        socket.send(Message::Text(serde_json::json!({"resume_from": seq}).to_string().into()))?;
+    }
+
+    while !stop.load(Ordering::SeqCst) {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => anyhow::bail!("server closed the connection"),
+            Ok(_) => continue, // Pings are answered by tungstenite
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) =>
+            {
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(text.as_str()) else {
+            continue;
+        };
+        if let Some(seq) = value.get("seq").and_then(|seq| seq.as_str()) {
+            *last_seq = Some(seq.to_string());
+        }
+        match value.get("type").and_then(|t| t.as_str()) {
+            Some("Resumed") => eprintln!("Caught up on {} missed events", value["replayed"]),
+            Some("ResumeGap") => eprintln!("{}", value["message"].as_str().unwrap_or_default()),
+            _ => output.print(value, text.as_str()),
+        }
+    }
+    Ok(())
+}
+
+// Accepts any server certificate, for --insecure against self-signed certificates
+#[derive(Debug)]
+struct NoCertificateVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn insecure_tls_config() -> rustls::ClientConfig {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default protocol versions")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(NoCertificateVerification(provider)))
+        .with_no_client_auth()
+}
+
 fn perform_export(
-    client: &Client,
-    api_url: &str,
+    server: &super::ServerApi,
     export_dir: &str,
     username: &Option<String>,
     password: &Option<String>,
@@ -132,12 +495,12 @@ fn perform_export(
 
     eprintln!("  Exporting to: {}", filepath.display());
 
-    let response = super::with_auth(client.get(api_url), username, password)
-        .send()
+    let response = server
+        .get("/api/events", username, password)
         .context("Failed to fetch events from API")?;
 
-    if !response.status().is_success() {
-        anyhow::bail!("API returned status {}", response.status());
+    if !response.status.is_success() {
+        anyhow::bail!("API returned status {}", response.status);
     }
 
     let events: serde_json::Value = response.json().context("Failed to parse events JSON")?;
@@ -200,3 +563,51 @@ fn cleanup_old_exports(export_dir: &str, keep_count: usize) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: serde_json::Value) -> (StreamEvent, String) {
+        let raw = json.to_string();
+        (serde_json::from_str(&raw).unwrap(), raw)
+    }
+
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(websocket_url("http://localhost:8080").unwrap(), "ws://localhost:8080/ws");
+        assert_eq!(websocket_url("https://server:8443/").unwrap(), "wss://server:8443/ws");
+        assert!(websocket_url("server:8080").is_err());
+    }
+
+    #[test]
+    fn test_follow_filter_and_render() {
+        let (warning, warning_raw) = parse(serde_json::json!({
+            "type": "Anomaly", "timestamp": 1_767_600_000_000i64, "severity": "Warning",
+            "kind": "CpuSpike", "message": "CPU at 97%",
+        }));
+        let (login, login_raw) = parse(serde_json::json!({
+            "type": "SecurityEvent", "timestamp": 1_767_600_000_000i64, "kind": "SshLoginFailure",
+            "user": "root", "source_ip": "203.0.113.7", "message": "Failed password",
+        }));
+        let (metrics, metrics_raw) = parse(serde_json::json!({"type": "SystemMetrics", "timestamp": 0}));
+
+        let all = FollowFilter::new(Vec::new(), None, None);
+        assert!(all.matches(&warning, &warning_raw));
+        assert!(all.matches(&login, &login_raw));
+        assert!(!all.matches(&metrics, &metrics_raw));
+
+        let critical_only = FollowFilter::new(Vec::new(), Some(FollowSeverity::Critical), None);
+        assert!(!critical_only.matches(&warning, &warning_raw));
+        assert!(critical_only.matches(&login, &login_raw)); // Severity only applies to anomalies
+
+        let anomalies = FollowFilter::new(vec![FollowEventType::Anomaly], None, Some("CPU".to_string()));
+        assert!(anomalies.matches(&warning, &warning_raw));
+        assert!(!anomalies.matches(&login, &login_raw));
+
+        let line = render_event(&login, false).unwrap();
+        assert!(line.ends_with(" SECURITY SshLoginFailure root from 203.0.113.7"), "{}", line);
+        let line = render_event(&warning, true).unwrap();
+        assert!(line.contains(&format!("{}WARNING {}", YELLOW, RESET)), "{}", line);
+    }
+}
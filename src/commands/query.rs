@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::path::Path;
+use time::OffsetDateTime;
+
+use super::export::{matches_event_type, parse_timestamp};
+use crate::event::{Event, ProcessLifecycleKind};
+use crate::indexed_reader::IndexedReader;
+use crate::syslog;
+use crate::timezone;
+
+// Events for `query`, after the time range the reader already applied
+struct QueryFilter {
+    event_type: Option<String>,
+    pattern: Option<Regex>, // Over the event's JSON
+}
+
+impl QueryFilter {
+    fn matches(&self, event: &Event, json: &str) -> bool {
+        self.event_type.as_deref().is_none_or(|t| matches_event_type(event, t))
+            && self.pattern.as_ref().is_none_or(|pattern| pattern.is_match(json))
+    }
+}
+
+// Print the events recorded between start and end, read with the segment indexes so only the
+// blocks in range are decoded. Needs nothing but the data directory: no config, no server.
+pub fn run_query(
+    data_dir: Option<String>,
+    start: String,
+    end: Option<String>,
+    event_type: Option<String>,
+    grep: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let data_dir = data_dir.unwrap_or_else(|| "./data".to_string());
+    if !Path::new(&data_dir).is_dir() {
+        anyhow::bail!("Data directory {} not found", data_dir);
+    }
+    let start = parse_timestamp(&start)?;
+    let end = match end {
+        Some(end) => parse_timestamp(&end)?,
+        None => OffsetDateTime::now_utc().unix_timestamp(),
+    };
+    if start > end {
+        anyhow::bail!("--start must not be after --end");
+    }
+    let filter = QueryFilter {
+        event_type,
+        pattern: grep.as_deref().map(Regex::new).transpose().context("Invalid --grep pattern")?,
+    };
+    // TZ, or UTC without it, as the recorder's own console lines use
+    timezone::init(None);
+
+    let events = read_range(Path::new(&data_dir), start, end)?;
+    let mut shown = 0;
+    for event in &events {
+        let event_json = serde_json::to_string(event).context("Failed to serialize event to JSON")?;
+        if !filter.matches(event, &event_json) {
+            continue;
+        }
+        if json {
+            println!("{}", event_json);
+        } else {
+            println!("{}", describe(event));
+        }
+        shown += 1;
+    }
+    eprintln!("{} of {} events in range", shown, events.len());
+    Ok(())
+}
+
+// Events stamped from the start of second `start` to the end of second `end`. A segment cut
+// off mid-record by a crash reads up to its last whole record.
+fn read_range(data_dir: &Path, start: i64, end: i64) -> Result<Vec<Event>> {
+    let reader = IndexedReader::new(data_dir).context("Failed to index segments")?;
+    let start_ns = start as i128 * 1_000_000_000;
+    let end_ns = (end as i128 + 1) * 1_000_000_000 - 1;
+    reader.read_time_range(Some(start_ns), Some(end_ns))
+}
+
+// One line per event: time in the display zone, type, then what happened
+fn describe(event: &Event) -> String {
+    let details = match event {
+        Event::SystemMetrics(m) => format!(
+            "cpu {:.1}%, mem {:.1}%, swap {:.1}%, disk {:.1}%, load {:.2}",
+            m.cpu_usage_percent, m.mem_usage_percent, m.swap_usage_percent, m.disk_usage_percent, m.load_avg_1m
+        ),
+        Event::ProcessLifecycle(p) => {
+            let exit = match (&p.kind, p.exit_code) {
+                (ProcessLifecycleKind::Exited, Some(code)) => format!(" exit code {}", code),
+                _ => String::new(),
+            };
+            format!("{:?} {} ({}){}: {}", p.kind, p.name, p.pid, exit, p.cmdline)
+        }
+        Event::ProcessSnapshot(s) => format!("{} processes, {} running", s.total_processes, s.running_processes),
+        Event::SecurityEvent(s) => {
+            let from = s.source_ip.as_ref().map(|ip| format!(" from {}", ip)).unwrap_or_default();
+            format!("{:?} {}{}: {}", s.kind, s.user, from, s.message)
+        }
+        Event::Anomaly(a) => format!("{:?} {:?}: {}", a.severity, a.kind, a.message),
+        Event::FileSystemEvent(f) => {
+            let repeats = if f.count > 1 { format!(" (x{})", f.count) } else { String::new() };
+            format!("{:?} {}{}", f.kind, f.path, repeats)
+        }
+        Event::Annotation(a) => format!("{}: {}", a.author, a.text),
+        Event::DiskHealth(d) => {
+            let health = match d.health_passed {
+                Some(true) => "passed",
+                Some(false) => "FAILED",
+                None => "unknown",
+            };
+            format!("{} health {}", d.device, health)
+        }
+        Event::ServiceStateChange(s) => format!("{} {} -> {}", s.unit, s.old_state, s.new_state),
+        Event::SystemEvent(s) => format!("{}: {}", s.kind.name(), s.message),
+        Event::OperatorAction(a) => format!(
+            "{} sent {} to {} ({}): {}",
+            a.user,
+            a.action,
+            a.name.as_deref().unwrap_or("unknown"),
+            a.pid,
+            a.outcome
+        ),
+        Event::NetworkTraffic(t) => match t.top_remotes.first() {
+            Some(busiest) => format!("{} remotes over {}s, busiest {}", t.top_remotes.len(), t.interval_secs, busiest.remote),
+            None => format!("no remote traffic over {}s", t.interval_secs),
+        },
+    };
+
+    let ts = event.timestamp();
+    let time = chrono::DateTime::from_timestamp(ts.unix_timestamp(), ts.nanosecond())
+        .map(|utc| utc.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S%.3f %:z").to_string())
+        .unwrap_or_default();
+    format!("{} {:<18} {}", time, syslog::event_type(event), details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, Anomaly, AnomalyKind, AnomalySeverity};
+    use crate::recorder::Recorder;
+    use crate::storage::{find_segment_files, RecordHeader, RetentionPolicy};
+    use std::io::Write;
+
+    fn anomaly(ts: i64, message: &str) -> Event {
+        Event::Anomaly(Anomaly {
+            ts: OffsetDateTime::from_unix_timestamp(ts).unwrap(),
+            severity: AnomalySeverity::Critical,
+            kind: AnomalyKind::MemorySpike,
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_query_range_with_truncated_final_segment() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let mut recorder = Recorder::open_with_config(dir.path(), retention, None, None).unwrap();
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        recorder.append(&anomaly(now, "memory at 97%")).unwrap();
+        recorder.append(&Event::SystemMetrics(sample_metrics())).unwrap();
+        recorder.close().unwrap();
+
+        // The machine died part way through the next record
+        let payload = bincode::serialize(&anomaly(now, "never finished")).unwrap();
+        let header = bincode::serialize(&RecordHeader {
+            timestamp_unix_ns: OffsetDateTime::now_utc().unix_timestamp_nanos(),
+            payload_len: payload.len() as u32,
+        })
+        .unwrap();
+        let (_, path) = find_segment_files(dir.path()).pop().unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&header).unwrap();
+        file.write_all(&payload[..payload.len() / 2]).unwrap();
+        drop(file);
+
+        let events = read_range(dir.path(), now - 60, now + 60).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(read_range(dir.path(), now - 7200, now - 3600).unwrap().is_empty());
+
+        let anomalies = QueryFilter { event_type: Some("anomaly".to_string()), pattern: None };
+        let memory = QueryFilter { event_type: None, pattern: Some(Regex::new("memory at 9[0-9]%").unwrap()) };
+        for filter in [anomalies, memory] {
+            let shown: Vec<&Event> =
+                events.iter().filter(|e| filter.matches(e, &serde_json::to_string(e).unwrap())).collect();
+            assert_eq!(shown.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        let line = describe(&anomaly(1_767_600_000, "Memory usage at 97.0%"));
+        assert!(line.ends_with(" Anomaly            Critical MemorySpike: Memory usage at 97.0%"), "{}", line);
+        let line = describe(&Event::SystemMetrics(sample_metrics()));
+        assert!(line.contains(" SystemMetrics      cpu "), "{}", line);
+    }
+}
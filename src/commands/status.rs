@@ -1,7 +1,6 @@
 use anyhow::{Context, Result};
-use reqwest::blocking::Client;
 use serde::Deserialize;
-use std::time::Duration;
+use std::collections::BTreeMap;
 
 use crate::cli::StatusFormat;
 
@@ -13,40 +12,264 @@ struct HealthResponse {
     storage_bytes_max: u64,
     storage_percent: f32,
     timestamp: String,
+    // Older servers don't report these
+    #[serde(default)]
+    last_event_timestamp: Option<String>,
+    #[serde(default)]
+    last_event_age_seconds: Option<i64>,
+    #[serde(default)]
+    segment_count: usize,
+    #[serde(default)]
+    anomalies_last_hour: BTreeMap<String, BTreeMap<String, usize>>, // severity -> kind -> count
+    #[serde(default)]
+    security_events_last_hour: BTreeMap<String, usize>,
+    #[serde(default)]
+    last_critical_anomaly: Option<String>,
+    #[serde(default)]
+    remote_streaming: Option<String>,
+    // Filled in from /api/health
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    collectors: Vec<CollectorHealth>,
+}
+
+#[derive(Deserialize, serde::Serialize)]
+struct CollectorHealth {
+    name: String,
+    last_success: Option<String>,
+    last_success_age_seconds: Option<i64>,
+    consecutive_failures: u64,
+    total_failures: u64,
+    last_error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CollectorHealthResponse {
+    collectors: Vec<CollectorHealth>,
+}
+
+// Thresholds for `status --check`
+pub struct CheckThresholds {
+    pub max_event_age_secs: u64,
+    pub critical_window_minutes: u64,
+}
+
+// Exit code for a failed check, CRITICAL in Nagios terms
+const CHECK_FAILED_EXIT_CODE: i32 = 2;
+
+// Warning and critical levels for one metric, in percent
+#[derive(Default, Clone, Copy)]
+pub struct Threshold {
+    pub warn: Option<f32>,
+    pub crit: Option<f32>,
+}
+
+// Thresholds for `status --format nagios`
+#[derive(Default)]
+pub struct MetricThresholds {
+    pub cpu: Threshold,
+    pub mem: Threshold,
+    pub disk: Threshold,
+}
+
+// The latest sample from /api/latest, all absent before the first
+#[derive(Deserialize, Default)]
+struct LatestMetrics {
+    cpu: Option<f32>,
+    mem: Option<f32>,
+    disk: Option<f32>,
+}
+
+// Nagios plugin states, the exit code is the discriminant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NagiosState {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl NagiosState {
+    fn label(self) -> &'static str {
+        match self {
+            NagiosState::Ok => "OK",
+            NagiosState::Warning => "WARNING",
+            NagiosState::Critical => "CRITICAL",
+            NagiosState::Unknown => "UNKNOWN",
+        }
+    }
 }
 
 pub fn run_status(
-    url: String,
+    server: super::ServerApi,
     username: Option<String>,
     password: Option<String>,
     format: StatusFormat,
+    check: Option<CheckThresholds>,
+    thresholds: MetricThresholds,
 ) -> Result<()> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()?;
-
-    let health_url = format!("{}/health", url.trim_end_matches('/'));
+    if let StatusFormat::Nagios = format {
+        // Any failure to get an answer is UNKNOWN, not the exit code 1 of an error
+        let (state, message) = nagios_status(&server, &username, &password, check.as_ref(), &thresholds)
+            .unwrap_or_else(|e| (NagiosState::Unknown, format!("{:#}", e)));
+        println!("BLACK BOX {} - {}", state.label(), message);
+        std::process::exit(state as i32);
+    }
 
-    let response = super::with_auth(client.get(&health_url), &username, &password)
-        .send()
-        .context("Failed to connect to black box server")?;
+    let mut health = fetch_health(&server, &username, &password, check.as_ref())?;
 
-    if !response.status().is_success() {
-        anyhow::bail!("Server returned status: {}", response.status());
+    // Older servers have no /api/health, the section is left out
+    if let Ok(response) = server.get("/api/health", &username, &password)
+        && response.status.is_success()
+        && let Ok(collectors) = response.json::<CollectorHealthResponse>()
+    {
+        health.collectors = collectors.collectors;
     }
 
-    let health: HealthResponse = response
-        .json()
-        .context("Failed to parse health response")?;
+    if let Some(check) = check {
+        let problems = check_problems(&health, &check);
+        match format {
+            StatusFormat::Human if problems.is_empty() => println!(
+                "OK - last event {}s ago, {} segments, storage {:.1}%",
+                health.last_event_age_seconds.unwrap_or(0),
+                health.segment_count,
+                health.storage_percent
+            ),
+            StatusFormat::Human => println!("CRITICAL - {}", problems.join("; ")),
+            StatusFormat::Json | StatusFormat::Nagios => print_json_status(&health)?,
+        }
+        if !problems.is_empty() {
+            std::process::exit(CHECK_FAILED_EXIT_CODE);
+        }
+        return Ok(());
+    }
 
     match format {
         StatusFormat::Human => print_human_status(&health),
-        StatusFormat::Json => print_json_status(&health)?,
+        StatusFormat::Json | StatusFormat::Nagios => print_json_status(&health)?,
     }
 
     Ok(())
 }
 
+fn fetch_health(
+    server: &super::ServerApi,
+    username: &Option<String>,
+    password: &Option<String>,
+    check: Option<&CheckThresholds>,
+) -> Result<HealthResponse> {
+    let mut health_path = "/health".to_string();
+    if let Some(check) = check {
+        health_path.push_str(&format!("?critical_window_minutes={}", check.critical_window_minutes));
+    }
+
+    let response = server
+        .get(&health_path, username, password)
+        .context("Failed to connect to black box server")?;
+
+    if !response.status.is_success() {
+        anyhow::bail!("Server returned status: {}", response.status);
+    }
+
+    response.json().context("Failed to parse health response")
+}
+
+// State and message of the Nagios status line. With --check its problems are CRITICAL too.
+fn nagios_status(
+    server: &super::ServerApi,
+    username: &Option<String>,
+    password: &Option<String>,
+    check: Option<&CheckThresholds>,
+    thresholds: &MetricThresholds,
+) -> Result<(NagiosState, String)> {
+    let problems = match check {
+        Some(check) => check_problems(&fetch_health(server, username, password, Some(check))?, check),
+        None => Vec::new(),
+    };
+
+    let mut response = server
+        .get("/api/latest", username, password)
+        .context("Failed to connect to black box server")?;
+    // Older servers only have the page's initial state, read from the recording
+    if response.status == reqwest::StatusCode::NOT_FOUND {
+        response = server
+            .get("/api/initial-state", username, password)
+            .context("Failed to connect to black box server")?;
+    }
+    if !response.status.is_success() {
+        anyhow::bail!("Server returned status: {}", response.status);
+    }
+    let metrics: LatestMetrics = response.json().context("Failed to parse latest metrics")?;
+    Ok(evaluate_metrics(&metrics, thresholds, problems))
+}
+
+// Worst state of the metrics against their thresholds, what breached them (or every value when
+// none did), then performance data after the |
+fn evaluate_metrics(
+    metrics: &LatestMetrics,
+    thresholds: &MetricThresholds,
+    problems: Vec<String>,
+) -> (NagiosState, String) {
+    let values = [
+        ("cpu", metrics.cpu, thresholds.cpu),
+        ("mem", metrics.mem, thresholds.mem),
+        ("disk", metrics.disk, thresholds.disk),
+    ];
+    if values.iter().all(|(_, value, _)| value.is_none()) {
+        return (NagiosState::Unknown, "no metrics recorded yet".to_string());
+    }
+
+    let mut state = if problems.is_empty() { NagiosState::Ok } else { NagiosState::Critical };
+    let mut breaches = problems;
+    let mut summary = Vec::new();
+    let mut perf_data = Vec::new();
+    for (name, value, threshold) in values {
+        let Some(value) = value else { continue };
+        summary.push(format!("{} {:.1}%", name, value));
+        perf_data.push(format!(
+            "{}={:.1}%;{};{};0;100",
+            name,
+            value,
+            threshold.warn.map(|t| t.to_string()).unwrap_or_default(),
+            threshold.crit.map(|t| t.to_string()).unwrap_or_default()
+        ));
+        let breached = match (threshold.crit, threshold.warn) {
+            (Some(crit), _) if value > crit => Some((NagiosState::Critical, crit)),
+            (_, Some(warn)) if value > warn => Some((NagiosState::Warning, warn)),
+            _ => None,
+        };
+        if let Some((breach, limit)) = breached {
+            state = state.max(breach);
+            breaches.push(format!("{} {:.1}% > {}%", name, value, limit));
+        }
+    }
+
+    let message = if breaches.is_empty() { summary.join(", ") } else { breaches.join("; ") };
+    (state, format!("{} | {}", message, perf_data.join(" ")))
+}
+
+// Reasons the check fails, empty when healthy
+fn check_problems(health: &HealthResponse, check: &CheckThresholds) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    match health.last_event_age_seconds {
+        Some(age) if age > check.max_event_age_secs as i64 => problems.push(format!(
+            "last event {}s ago (threshold {}s), collector may be stuck",
+            age, check.max_event_age_secs
+        )),
+        Some(_) => {}
+        None => problems.push("no events recorded".to_string()),
+    }
+
+    if let Some(ref ts) = health.last_critical_anomaly {
+        problems.push(format!(
+            "critical anomaly at {} (within the last {}m)",
+            ts, check.critical_window_minutes
+        ));
+    }
+
+    problems
+}
+
 fn print_human_status(health: &HealthResponse) {
     println!("Black Box Status");
     println!("================");
@@ -58,10 +281,45 @@ fn print_human_status(health: &HealthResponse) {
         format_bytes(health.storage_bytes_used),
         format_bytes(health.storage_bytes_max)
     );
+    println!("Segments:     {}", health.segment_count);
+    println!("Last Event:   {}", match (&health.last_event_timestamp, health.last_event_age_seconds) {
+        (Some(ts), Some(age)) => format!("{} ({} ago)", ts, format_duration(age.max(0) as u64)),
+        _ => "none".to_string(),
+    });
+    if let Some(ref remote) = health.remote_streaming {
+        println!("Remote:       {}", remote);
+    }
     println!("Last Update:  {}", health.timestamp);
     println!();
 
+    println!("Anomalies (last hour):");
+    if health.anomalies_last_hour.is_empty() {
+        println!("  none");
+    }
+    for (severity, kinds) in &health.anomalies_last_hour {
+        let kinds: Vec<String> = kinds.iter().map(|(kind, count)| format!("{} x{}", kind, count)).collect();
+        println!("  {:<9} {}", severity, kinds.join(", "));
+    }
+    println!("Security events (last hour):");
+    if health.security_events_last_hour.is_empty() {
+        println!("  none");
+    }
+    for (kind, count) in &health.security_events_last_hour {
+        println!("  {:<24} {}", kind, count);
+    }
+    if !health.collectors.is_empty() {
+        println!("Collectors:");
+    }
+    for collector in &health.collectors {
+        println!("  {:<16} {}", collector.name, describe_collector(collector));
+    }
+    println!();
+
     // Status indicator
+    let failing = health.collectors.iter().filter(|c| c.consecutive_failures > 0 && c.last_success.is_some()).count();
+    if failing > 0 {
+        println!("⚠ {} collector{} failing", failing, if failing == 1 { "" } else { "s" });
+    }
     if health.storage_percent > 95.0 {
         println!("⚠ WARNING: Storage nearly full");
     } else if health.storage_percent > 80.0 {
@@ -71,6 +329,22 @@ fn print_human_status(health: &HealthResponse) {
     }
 }
 
+fn describe_collector(collector: &CollectorHealth) -> String {
+    let last_success = match collector.last_success_age_seconds {
+        Some(age) => format!("last read {} ago", format_duration(age.max(0) as u64)),
+        None => "never read".to_string(),
+    };
+    if collector.consecutive_failures == 0 {
+        return format!("ok, {}", last_success);
+    }
+    format!(
+        "FAILING x{}, {}: {}",
+        collector.consecutive_failures,
+        last_success,
+        collector.last_error.as_deref().unwrap_or("unknown error")
+    )
+}
+
 fn print_json_status(health: &HealthResponse) -> Result<()> {
     let json = serde_json::to_string_pretty(health)?;
     println!("{}", json);
@@ -105,3 +379,85 @@ fn format_bytes(bytes: u64) -> String {
         format!("{:.1}GB", bytes as f64 / 1024.0 / 1024.0 / 1024.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(last_event_age_seconds: Option<i64>, last_critical_anomaly: Option<&str>) -> HealthResponse {
+        let json = serde_json::json!({
+            "uptime_seconds": 60,
+            "event_count": 10,
+            "storage_bytes_used": 0,
+            "storage_bytes_max": 1,
+            "storage_percent": 0.0,
+            "timestamp": "now",
+            "last_event_age_seconds": last_event_age_seconds,
+            "last_critical_anomaly": last_critical_anomaly,
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_check_problems() {
+        let check = CheckThresholds { max_event_age_secs: 120, critical_window_minutes: 15 };
+
+        assert!(check_problems(&health(Some(5), None), &check).is_empty());
+        assert_eq!(check_problems(&health(Some(600), None), &check).len(), 1);
+        assert_eq!(check_problems(&health(None, None), &check).len(), 1);
+
+        let problems = check_problems(&health(Some(5), Some("2026-01-05T09:00:00Z")), &check);
+        assert_eq!(problems, vec!["critical anomaly at 2026-01-05T09:00:00Z (within the last 15m)"]);
+    }
+
+    #[test]
+    fn test_evaluate_metrics_for_nagios() {
+        let thresholds = MetricThresholds {
+            cpu: Threshold { warn: Some(80.0), crit: Some(95.0) },
+            mem: Threshold { warn: None, crit: Some(95.0) },
+            disk: Threshold { warn: None, crit: Some(90.0) },
+        };
+        let metrics = |cpu: f32, disk: f32| LatestMetrics { cpu: Some(cpu), mem: Some(40.0), disk: Some(disk) };
+
+        let (state, message) = evaluate_metrics(&metrics(12.0, 55.0), &thresholds, Vec::new());
+        assert_eq!(state, NagiosState::Ok);
+        assert_eq!(
+            message,
+            "cpu 12.0%, mem 40.0%, disk 55.0% | cpu=12.0%;80;95;0;100 mem=40.0%;;95;0;100 disk=55.0%;;90;0;100"
+        );
+
+        let (state, message) = evaluate_metrics(&metrics(85.5, 55.0), &thresholds, Vec::new());
+        assert_eq!(state, NagiosState::Warning);
+        assert!(message.starts_with("cpu 85.5% > 80% |"), "{}", message);
+
+        // The worst breach decides
+        let (state, message) = evaluate_metrics(&metrics(85.5, 97.0), &thresholds, Vec::new());
+        assert_eq!(state, NagiosState::Critical);
+        assert!(message.starts_with("cpu 85.5% > 80%; disk 97.0% > 90% |"), "{}", message);
+
+        let (state, message) = evaluate_metrics(&metrics(12.0, 55.0), &thresholds, vec!["no events recorded".to_string()]);
+        assert_eq!(state, NagiosState::Critical);
+        assert!(message.starts_with("no events recorded |"));
+
+        let (state, message) = evaluate_metrics(&LatestMetrics::default(), &thresholds, Vec::new());
+        assert_eq!((state, message.as_str()), (NagiosState::Unknown, "no metrics recorded yet"));
+    }
+
+    #[test]
+    fn test_describe_collector() {
+        let collector = |age: Option<i64>, failures: u64| CollectorHealth {
+            name: "auth_log".to_string(),
+            last_success: age.map(|_| "2026-01-05T09:00:00Z".to_string()),
+            last_success_age_seconds: age,
+            consecutive_failures: failures,
+            total_failures: failures,
+            last_error: (failures > 0).then(|| "No auth log found".to_string()),
+        };
+        assert_eq!(describe_collector(&collector(Some(3), 0)), "ok, last read 3s ago");
+        assert_eq!(
+            describe_collector(&collector(Some(7200), 1440)),
+            "FAILING x1440, last read 2h 0m 0s ago: No auth log found"
+        );
+        assert_eq!(describe_collector(&collector(None, 2)), "FAILING x2, never read: No auth log found");
+    }
+}
@@ -140,11 +140,9 @@ fn generate_service_content(
     export_on_stop: bool,
     export_dir: &str,
 ) -> String {
-    let exec_stop_post = if export_on_stop {
-        format!(
-            "ExecStopPost={} export --data-dir {} --output {}/emergency-export-$(date +%%Y%%m%%d-%%H%%M%%S).json.gz --compress\n",
-            binary_path, data_dir, export_dir
-        )
+    // The recorder writes the archive itself once it has flushed on SIGTERM
+    let export_args = if export_on_stop {
+        format!(" --export-on-stop --export-dir {}", export_dir)
     } else {
         String::new()
     };
@@ -157,7 +155,7 @@ Documentation=https://github.com/yourusername/black-box
 
 [Service]
 Type=simple
-ExecStart={binary_path} run --protected
+ExecStart={binary_path} --protected{export_args}
 WorkingDirectory={working_dir}
 Restart=always
 RestartSec=5s
@@ -173,9 +171,8 @@ ProtectHome=true
 ReadWritePaths={data_dir}
 ReadWritePaths={working_dir}
 {export_dir_rw}
-# Auto-export on service stop (emergency backup)
-{exec_stop_post}
-# Graceful shutdown
+
+# Graceful shutdown (flushes the current segment, then exports if enabled)
 TimeoutStopSec=30s
 KillMode=mixed
 KillSignal=SIGTERM
@@ -191,7 +188,7 @@ WantedBy=multi-user.target
         } else {
             String::new()
         },
-        exec_stop_post = exec_stop_post,
+        export_args = export_args,
     )
 }
 
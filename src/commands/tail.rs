@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use inotify::{Inotify, WatchMask};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::monitor::FollowOutput;
+use crate::reader::{FollowPosition, LogReader};
+use crate::webui::event_to_json;
+
+// How often inotify is checked for writes to the data directory, and Ctrl+C with it
+const TAIL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Follow the recording in `data_dir` as the local recorder writes it, printing new events until
+// Ctrl+C. Whatever was appended is read when inotify reports a write, from where the last read
+// stopped.
+pub fn run_tail_local(data_dir: &str, output: FollowOutput) -> Result<()> {
+    if !Path::new(data_dir).is_dir() {
+        anyhow::bail!("Data directory {} not found", data_dir);
+    }
+    let reader = LogReader::new(data_dir);
+
+    let mut inotify = Inotify::init().context("Failed to initialise inotify")?;
+    inotify
+        .watches()
+        .add(data_dir, WatchMask::MODIFY | WatchMask::CREATE | WatchMask::MOVED_TO)
+        .with_context(|| format!("Failed to watch {}", data_dir))?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let stop = stop.clone();
+        ctrlc::set_handler(move || stop.store(true, Ordering::SeqCst))?;
+    }
+
+    // Start from the end, like tail -f
+    let mut position: Option<FollowPosition> = None;
+    reader.read_new_events(&mut position)?;
+    eprintln!("Following {} (Ctrl+C to stop)", data_dir);
+
+    let mut buffer = [0u8; 4096];
+    while !stop.load(Ordering::SeqCst) {
+        thread::sleep(TAIL_POLL_INTERVAL);
+        let written = match inotify.read_events(&mut buffer) {
+            Ok(mut events) => events.next().is_some(),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => false,
+            Err(e) => return Err(e.into()),
+        };
+        if !written {
+            continue;
+        }
+
+        // A segment being sealed can fail to read part way through, the next write retries it
+        let events = match reader.read_new_events(&mut position) {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("Warning: Failed to read new events: {:#}", e);
+                continue;
+            }
+        };
+        for event in events {
+            // The JSON the WebSocket would have sent, so --json output is the same either way
+            let mut json = event_to_json(&event);
+            if let Some(object) = json.as_object_mut() {
+                object.insert("seq".to_string(), event.timestamp().unix_timestamp_nanos().to_string().into());
+            }
+            let raw = json.to_string();
+            output.print(json, &raw);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, Event};
+    use crate::recorder::Recorder;
+    use crate::storage::RetentionPolicy;
+    use time::OffsetDateTime;
+
+    fn record(dir: &Path, messages: &[&str]) {
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let mut recorder = Recorder::open_with_config(dir, retention, None, None).unwrap();
+        for message in messages {
+            recorder
+                .append(&Event::Anomaly(Anomaly {
+                    ts: OffsetDateTime::now_utc(),
+                    severity: AnomalySeverity::Warning,
+                    kind: AnomalyKind::CpuSpike,
+                    message: message.to_string(),
+                }))
+                .unwrap();
+        }
+        recorder.close().unwrap();
+    }
+
+    fn messages(events: Vec<Event>) -> Vec<String> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Anomaly(anomaly) => Some(anomaly.message),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_follow_returns_only_new_events() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let reader = LogReader::new(dir.path());
+        let mut position = None;
+        assert!(reader.read_new_events(&mut position).unwrap().is_empty());
+
+        record(dir.path(), &["first", "second"]);
+        assert_eq!(messages(reader.read_new_events(&mut position).unwrap()), ["first", "second"]);
+        assert!(reader.read_new_events(&mut position).unwrap().is_empty());
+
+        // Reopening may start another segment, either way only the new event follows
+        record(dir.path(), &["third"]);
+        assert_eq!(messages(reader.read_new_events(&mut position).unwrap()), ["third"]);
+    }
+}
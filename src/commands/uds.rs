@@ -0,0 +1,137 @@
+// Plain HTTP/1.1 GET over a Unix socket, for servers listening on unix_socket_path
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+pub struct RawResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+pub fn get(socket: &Path, path: &str, auth: Option<(&str, &str)>, timeout: Duration) -> Result<RawResponse> {
+    let mut stream = UnixStream::connect(socket).with_context(|| format!("Failed to connect to {}", socket.display()))?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    // Connection: close, so the response ends where the stream does
+    let mut request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n", path);
+    if let Some((username, password)) = auth {
+        let credentials = general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        request.push_str(&format!("Authorization: Basic {}\r\n", credentials));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).context("Failed to read response")?;
+    parse_response(&raw)
+}
+
+fn parse_response(raw: &[u8]) -> Result<RawResponse> {
+    let header_end = find(raw, b"\r\n\r\n").context("Incomplete HTTP response")?;
+    let head = std::str::from_utf8(&raw[..header_end]).context("Invalid HTTP response headers")?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .context("Invalid HTTP status line")?;
+
+    let mut chunked = false;
+    let mut content_length = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "transfer-encoding" => chunked = value.trim().eq_ignore_ascii_case("chunked"),
+            "content-length" => content_length = value.trim().parse::<usize>().ok(),
+            _ => {}
+        }
+    }
+
+    let body = &raw[header_end + 4..];
+    let body = if chunked {
+        decode_chunked(body)?
+    } else if let Some(len) = content_length {
+        body.get(..len).context("Truncated HTTP response body")?.to_vec()
+    } else {
+        body.to_vec()
+    };
+    Ok(RawResponse { status, body })
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = find(data, b"\r\n").context("Truncated chunked response")?;
+        let size_line = std::str::from_utf8(&data[..line_end]).context("Invalid chunk size")?;
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16).with_context(|| format!("Invalid chunk size {:?}", size_hex))?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        body.extend_from_slice(data.get(..size).context("Truncated chunked response")?);
+        data = data.get(size + 2..).context("Truncated chunked response")?;
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n{}").unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"{}");
+
+        let chunked = b"HTTP/1.1 401 Unauthorized\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let response = parse_response(chunked).unwrap();
+        assert_eq!(response.status, 401);
+        assert_eq!(response.body, b"hello world");
+
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\n{}").is_err());
+        assert!(parse_response(b"garbage").is_err());
+    }
+
+    #[test]
+    fn test_get_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("web.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                request.push(line.trim_end().to_string());
+            }
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 11\r\n\r\n{\"ok\":true}").unwrap();
+            request
+        });
+
+        let response = get(&socket, "/health", Some(("admin", "secret")), Duration::from_secs(5)).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"{\"ok\":true}");
+
+        let request = server.join().unwrap();
+        assert_eq!(request[0], "GET /health HTTP/1.1");
+        assert!(request.contains(&"Authorization: Basic YWRtaW46c2VjcmV0".to_string()));
+    }
+}
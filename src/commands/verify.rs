@@ -0,0 +1,140 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::integrity::{SegmentFormat, SegmentReport, SigningKey};
+use crate::reader::LogReader;
+use crate::storage::parse_segment_id;
+
+// Check every segment without exporting anything. Fails if any segment was changed, cut short,
+// or is missing from between the oldest and newest.
+pub fn run_verify(data_dir: Option<String>) -> Result<()> {
+    // Signatures can only be checked with the key from config.toml
+    let config = Config::load()?;
+    let data_dir = data_dir.unwrap_or_else(|| config.server.data_dir.clone());
+    if !Path::new(&data_dir).is_dir() {
+        anyhow::bail!("Data directory {} not found", data_dir);
+    }
+    let signing_key = SigningKey::from_config(&config.protection);
+    let has_key = signing_key.is_some();
+
+    let reader = LogReader::new(&data_dir).with_signing_key(signing_key);
+    let failed = report_integrity(&reader, has_key)?;
+    if failed > 0 {
+        anyhow::bail!("Integrity check failed for {} segment(s)", failed);
+    }
+    eprintln!("All segments verified");
+    Ok(())
+}
+
+// Print the integrity of each segment to stderr, returning how many failed
+pub fn report_integrity(reader: &LogReader, has_key: bool) -> Result<usize> {
+    let reports = reader.verify_segments()?;
+    eprintln!("Integrity of {} segment(s):", reports.len());
+
+    let mut failed = 0;
+    let mut first_problem = None;
+    for (i, report) in reports.iter().enumerate() {
+        // Every segment but the newest has been sealed
+        let sealed = i + 1 < reports.len();
+        let (status, ok) = integrity_status(report, has_key, sealed);
+        let name = report.path.file_name().unwrap_or_default().to_string_lossy();
+        eprintln!("  {}  {:<11}  {:>6} records  {}", name, report.format.map_or("unknown", SegmentFormat::name), report.records, status);
+        for (offset, reason) in &report.violations {
+            eprintln!("      offset {}: {}", offset, reason);
+        }
+        if !ok {
+            failed += 1;
+            if first_problem.is_none() {
+                first_problem = report.first_problem(sealed).map(|problem| format!("{}, {}", name, problem));
+            }
+        }
+    }
+
+    // Only the oldest segments are ever deleted, so a gap means one was removed by hand
+    for id in missing_segments(&reports) {
+        eprintln!("  segment_{:05}.dat  missing, deleted out of order", id);
+        failed += 1;
+        first_problem.get_or_insert_with(|| format!("segment_{:05}.dat is missing", id));
+    }
+
+    if let Some(problem) = first_problem {
+        eprintln!("First problem: {}", problem);
+    }
+    Ok(failed)
+}
+
+// Status line for one segment, and whether it passed
+fn integrity_status(report: &SegmentReport, has_key: bool, sealed: bool) -> (String, bool) {
+    let Some(format) = report.format else {
+        return (format!("FAILED: {}", report.unknown_format()), false);
+    };
+    if !report.violations.is_empty() {
+        return (format!("FAILED: {} violation(s)", report.violations.len()), false);
+    }
+    if sealed && report.trailing_bytes > 0 {
+        return (format!("FAILED: truncated at offset {}", report.end), false);
+    }
+
+    let mut status = match format {
+        SegmentFormat::Plain => "not verifiable (written before checksums)".to_string(),
+        SegmentFormat::Checksummed => "OK (checksums only, not signed)".to_string(),
+        SegmentFormat::Signed if has_key => "OK".to_string(),
+        SegmentFormat::Signed => "checksums OK, signatures not checked (no signing_key)".to_string(),
+    };
+    if report.trailing_bytes > 0 {
+        status.push_str(&format!(", {} trailing bytes from an interrupted write", report.trailing_bytes));
+    }
+    (status, true)
+}
+
+// Segment ids between the oldest and newest with no file
+fn missing_segments(reports: &[SegmentReport]) -> Vec<u64> {
+    let ids: Vec<u64> = reports
+        .iter()
+        .filter_map(|report| parse_segment_id(&report.path.file_name()?.to_string_lossy()))
+        .collect();
+    ids.windows(2).flat_map(|pair| pair[0] + 1..pair[1]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn report(id: u64, format: Option<SegmentFormat>, violations: Vec<(u64, &'static str)>) -> SegmentReport {
+        SegmentReport {
+            path: PathBuf::from(format!("segment_{:05}.dat", id)),
+            format,
+            newer_version: None,
+            records: 10,
+            violations,
+            end: 4096,
+            trailing_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_integrity_status() {
+        assert_eq!(integrity_status(&report(1, Some(SegmentFormat::Signed), vec![]), true, true), ("OK".to_string(), true));
+        assert!(integrity_status(&report(1, Some(SegmentFormat::Signed), vec![]), false, true).0.contains("not checked"));
+        assert!(integrity_status(&report(1, Some(SegmentFormat::Plain), vec![]), true, true).1);
+
+        let (status, ok) = integrity_status(&report(1, Some(SegmentFormat::Signed), vec![(120, "checksum mismatch")]), true, true);
+        assert!(!ok);
+        assert!(status.contains("1 violation"));
+        assert!(!integrity_status(&report(1, None, vec![]), true, true).1);
+
+        // A partial record is an interrupted write in the segment being written, and a cut in a sealed one
+        let torn = SegmentReport { trailing_bytes: 12, ..report(1, Some(SegmentFormat::Signed), vec![]) };
+        assert!(integrity_status(&torn, true, false).1);
+        assert_eq!(integrity_status(&torn, true, true), ("FAILED: truncated at offset 4096".to_string(), false));
+    }
+
+    #[test]
+    fn test_missing_segments() {
+        let reports: Vec<SegmentReport> = [3, 4, 7, 8].map(|id| report(id, Some(SegmentFormat::Signed), vec![])).into();
+        assert_eq!(missing_segments(&reports), vec![5, 6]);
+        assert!(missing_segments(&reports[..2]).is_empty());
+    }
+}
@@ -1,6 +1,10 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use std::net::IpAddr;
+
+use crate::storage::{DownsamplePolicy, FsyncPolicy, RetentionPolicy, SEGMENT_SIZE};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProtectionMode {
@@ -18,27 +22,323 @@ pub struct Config {
     pub protection: ProtectionConfig,
     #[serde(default)]
     pub file_watch: FileWatchConfig,
+    #[serde(default)]
+    pub anomalies: AnomalyConfig,
+    #[serde(default)]
+    pub process_tracking: ProcessTrackingConfig,
+    #[serde(default)]
+    pub collection: CollectionConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub smart: SmartConfig,
+    #[serde(default)]
+    pub services: ServiceMonitorConfig,
+    #[serde(default)]
+    pub traffic_accounting: TrafficAccountingConfig,
+    #[serde(default)]
+    pub actions: ActionsConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AuthConfig {
     pub enabled: bool,
     pub username: String,
+    // argon2 (or legacy bcrypt) hash
+    #[serde(default)]
     pub password_hash: String,
+    // Plaintext password, replaced by password_hash the next time the config is loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    // Read-only tokens for scripts and dashboards, added with `config add-token`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_tokens: Vec<ApiToken>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiToken {
+    pub name: String, // Recorded when the token is used
+    // SHA-256 of the token. Tokens are random, so unlike passwords a fast hash is enough.
+    pub token_hash: String,
+}
+
+impl AuthConfig {
+    pub fn verify(&self, username: &str, password: &str) -> bool {
+        username == self.username && verify_password(password, &self.password_hash)
+    }
+
+    /// Name of the API token `token` is, if it's one
+    pub fn api_token_name(&self, token: &str) -> Option<&str> {
+        let hash = hash_api_token(token);
+        self.api_tokens.iter().find(|t| t.token_hash == hash).map(|t| t.name.as_str())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (i, token) in self.api_tokens.iter().enumerate() {
+            if token.name.trim().is_empty() {
+                anyhow::bail!("api_tokens need a name");
+            }
+            if self.api_tokens[..i].iter().any(|t| t.name == token.name) {
+                anyhow::bail!("api token '{}' is listed twice", token.name);
+            }
+            if token.token_hash.len() != 64 || !token.token_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                anyhow::bail!("api token '{}' needs a token_hash of 64 hex digits, from `config add-token`", token.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+// "bbt_" and 32 random bytes, with its hash for config.toml
+pub fn generate_api_token() -> (String, String) {
+    use base64::{engine::general_purpose, Engine as _};
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    let token = format!("bbt_{}", general_purpose::URL_SAFE_NO_PAD.encode(bytes));
+    let hash = hash_api_token(&token);
+    (token, hash)
+}
+
+pub fn hash_api_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    use argon2::password_hash::{PasswordHasher, SaltString};
+    use rand::RngCore;
+
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt)
+        .map_err(|e| anyhow::anyhow!("Failed to encode salt: {}", e))?;
+    let hash = argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+// Accepts argon2 hashes and bcrypt hashes from older configs
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+
+    if hash.starts_with("$argon2") {
+        PasswordHash::new(hash)
+            .map(|parsed| argon2::Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .unwrap_or(false)
+    } else {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ServerConfig {
     pub port: u16,
+    // Address the web UI listens on, 127.0.0.1 to only accept local connections
+    #[serde(default = "default_bind_address", alias = "bind_addr")]
+    pub bind_address: String,
+    // Path prefix the web UI is served under, e.g. "/black-box" behind a reverse proxy that
+    // forwards that path unchanged. Empty serves it at the root.
+    #[serde(default)]
+    pub base_path: String,
+    // Listen on this Unix socket instead of a TCP port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket_path: Option<String>,
     pub data_dir: String,
     #[serde(default = "default_max_storage_mb")]
     pub max_storage_mb: u64,
+    // Takes precedence over max_storage_mb when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_storage_bytes: Option<u64>,
+    // Delete segments whose newest event is older than this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retention_days: Option<u64>,
+    // Size a segment is sealed (and compressed) at. The oldest whole segment is what gets overwritten.
+    #[serde(default = "default_max_segment_mb")]
+    pub max_segment_mb: u64,
+    // zstd level for full segments, 0 keeps them uncompressed
+    #[serde(default = "default_segment_compression_level")]
+    pub segment_compression_level: i32,
+    // "tick" fsyncs every batch of records, "segment" only full segments and on shutdown
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+    // PEM certificate chain and private key. HTTPS is used when both are set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_key: Option<String>,
+    // How far back a reconnecting WebSocket client is caught up, beyond either it resumes live
+    #[serde(default = "default_resume_max_secs")]
+    pub resume_max_secs: u64,
+    #[serde(default = "default_resume_max_events")]
+    pub resume_max_events: usize,
+    // IANA zone times are shown in, e.g. "Europe/London". Unset uses TZ, or UTC without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    // Roll SystemMetrics in segments older than this up into one record per downsample_window_secs.
+    // Other events are kept as recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downsample_after_hours: Option<u64>,
+    #[serde(default = "default_downsample_window_secs")]
+    pub downsample_window_secs: u64,
+}
+
+impl ServerConfig {
+    // Effective storage limit in bytes. A max_storage_mb too large to count in bytes fails
+    // validate, this only keeps it from wrapping before then.
+    pub fn storage_limit_bytes(&self) -> u64 {
+        self.max_storage_bytes.unwrap_or_else(|| self.max_storage_mb.saturating_mul(1024 * 1024))
+    }
+
+    pub fn segment_size_bytes(&self) -> u64 {
+        self.max_segment_mb.saturating_mul(1024 * 1024)
+    }
+
+    // --max-storage, in place of whatever config.toml sets
+    pub fn override_storage_limit(&mut self, max_storage_mb: u64) {
+        self.max_storage_mb = max_storage_mb;
+        self.max_storage_bytes = None;
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(MIN_SEGMENT_MB..=MAX_SEGMENT_MB).contains(&self.max_segment_mb) {
+            anyhow::bail!(
+                "max_segment_mb must be between {} and {}, got {}",
+                MIN_SEGMENT_MB,
+                MAX_SEGMENT_MB,
+                self.max_segment_mb
+            );
+        }
+        if self.max_storage_bytes.is_none() && self.max_storage_mb.checked_mul(1024 * 1024).is_none() {
+            anyhow::bail!(
+                "max_storage_mb must be at most {} (the limit in bytes has to fit in 64 bits), got {}",
+                u64::MAX / (1024 * 1024),
+                self.max_storage_mb
+            );
+        }
+        if self.storage_limit_bytes() < MIN_STORAGE_SEGMENTS * self.segment_size_bytes() {
+            anyhow::bail!(
+                "storage limit must be at least {} segments of max_segment_mb ({} bytes)",
+                MIN_STORAGE_SEGMENTS,
+                MIN_STORAGE_SEGMENTS * self.segment_size_bytes()
+            );
+        }
+        if self.max_retention_days == Some(0) {
+            anyhow::bail!("max_retention_days must be at least 1");
+        }
+        if self.downsample_after_hours == Some(0) {
+            anyhow::bail!("downsample_after_hours must be at least 1");
+        }
+        if !(2..=3600).contains(&self.downsample_window_secs) {
+            anyhow::bail!("downsample_window_secs must be between 2 and 3600, got {}", self.downsample_window_secs);
+        }
+        if !(0..=22).contains(&self.segment_compression_level) {
+            anyhow::bail!(
+                "segment_compression_level must be between 0 and 22, got {}",
+                self.segment_compression_level
+            );
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            anyhow::bail!("tls_cert and tls_key must be set together");
+        }
+        if self.bind_address.parse::<std::net::IpAddr>().is_err() {
+            anyhow::bail!(
+                "bind_address must be an IP address such as 127.0.0.1 or ::1, got {:?}",
+                self.bind_address
+            );
+        }
+        if let Some(path) = &self.unix_socket_path {
+            if path.is_empty() {
+                anyhow::bail!("unix_socket_path must not be empty");
+            }
+            if self.tls_cert.is_some() {
+                anyhow::bail!("tls_cert and tls_key can't be used with unix_socket_path, the socket is plain HTTP");
+            }
+        }
+        if let Some(timezone) = &self.timezone {
+            crate::timezone::validate(timezone)?;
+        }
+        let base_path = self.base_path();
+        if !base_path.is_empty()
+            && (!base_path.starts_with('/')
+                || !base_path.chars().all(|c| c.is_ascii_alphanumeric() || "/-_.~".contains(c)))
+        {
+            anyhow::bail!(
+                "base_path must start with / and contain only letters, digits, '/', '-', '_', '.' or '~', got {:?}",
+                self.base_path
+            );
+        }
+        Ok(())
+    }
+
+    // base_path without a trailing slash, "" for the root
+    pub fn base_path(&self) -> &str {
+        self.base_path.trim_end_matches('/')
+    }
+
+    // (cert, key) paths when HTTPS is configured
+    pub fn tls_paths(&self) -> Option<(&str, &str)> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            _ => None,
+        }
+    }
+
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_bytes: self.storage_limit_bytes(),
+            max_age_secs: self.max_retention_days.map(|days| days * 24 * 60 * 60),
+        }
+    }
+
+    pub fn downsample_policy(&self) -> Option<DownsamplePolicy> {
+        self.downsample_after_hours.map(|hours| DownsamplePolicy {
+            after_secs: hours * 60 * 60,
+            window_secs: self.downsample_window_secs,
+        })
+    }
 }
 
 fn default_max_storage_mb() -> u64 {
     100 // 100MB default
 }
 
+fn default_max_segment_mb() -> u64 {
+    SEGMENT_SIZE / 1024 / 1024
+}
+
+// Sealed segments are decompressed whole to be read, so they are kept small enough for that,
+// and the ring buffer has to hold the segment being written and at least one before it
+const MIN_SEGMENT_MB: u64 = 1;
+const MAX_SEGMENT_MB: u64 = 256;
+const MIN_STORAGE_SEGMENTS: u64 = 2;
+
+fn default_segment_compression_level() -> i32 {
+    3
+}
+
+fn default_downsample_window_secs() -> u64 {
+    60
+}
+
+fn default_bind_address() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_resume_max_secs() -> u64 {
+    600
+}
+
+fn default_resume_max_events() -> usize {
+    5000
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ProtectionConfig {
     #[serde(default)]
@@ -49,6 +349,19 @@ pub struct ProtectionConfig {
     pub sign_events: bool,
     #[serde(default)]
     pub signing_key: Option<String>,
+    // Attribute given to full segments, defaults to immutable when hardened, append-only otherwise
+    #[serde(default)]
+    pub sealed_attribute: Option<SegmentAttribute>,
+    // Switch to this user once the web port is bound and the data directory is open
+    #[serde(default)]
+    pub run_as_user: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentAttribute {
+    AppendOnly,
+    Immutable,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -58,12 +371,496 @@ pub struct RemoteSyslogConfig {
     pub port: u16,
     #[serde(default)]
     pub protocol: String, // "tcp" or "udp"
+    #[serde(default)]
+    pub format: SyslogFormat,
+    // Syslog facility code (0-23), defaults to local0
+    #[serde(default = "default_syslog_facility")]
+    pub facility: u8,
+    // How syslog messages are delimited over TCP, JSON is always newline-delimited
+    #[serde(default)]
+    pub framing: SyslogFraming,
+    // Wrap the TCP connection in TLS. The server is verified against ca_cert, or the public
+    // roots without one; client_cert and client_key are presented when both are set.
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub ca_cert: Option<String>,
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    #[serde(default)]
+    pub client_key: Option<String>,
+    // Cap for the on-disk spool used while the remote end is unreachable, 0 disables it
+    #[serde(default = "default_spool_max_mb")]
+    pub spool_max_mb: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogFormat {
+    // Newline-delimited event JSON, not understood by rsyslog/syslog-ng
+    #[default]
+    Json,
+    Rfc3164,
+    Rfc5424,
+}
+
+impl std::str::FromStr for SyslogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(Self::Json),
+            "rfc3164" => Ok(Self::Rfc3164),
+            "rfc5424" => Ok(Self::Rfc5424),
+            _ => anyhow::bail!("format must be 'json', 'rfc3164' or 'rfc5424', got '{}'", s),
+        }
+    }
+}
+
+impl std::fmt::Display for SyslogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::Rfc3164 => "rfc3164",
+            Self::Rfc5424 => "rfc5424",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogFraming {
+    // Length-prefixed (RFC 6587 section 3.4.1), safe for any message
+    #[default]
+    OctetCounting,
+    // One message per line, for receivers that don't support octet counting
+    Lf,
+}
+
+impl std::str::FromStr for SyslogFraming {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "octet_counting" => Ok(Self::OctetCounting),
+            "lf" => Ok(Self::Lf),
+            _ => anyhow::bail!("framing must be 'octet_counting' or 'lf', got '{}'", s),
+        }
+    }
+}
+
+impl std::fmt::Display for SyslogFraming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::OctetCounting => "octet_counting",
+            Self::Lf => "lf",
+        })
+    }
+}
+
+fn default_syslog_facility() -> u8 {
+    16 // local0
+}
+
+fn default_spool_max_mb() -> u64 {
+    64
+}
+
+impl RemoteSyslogConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.facility > 23 {
+            anyhow::bail!("facility must be between 0 and 23, got {}", self.facility);
+        }
+        if self.tls && self.protocol != "tcp" {
+            anyhow::bail!("tls needs protocol = \"tcp\", got '{}'", self.protocol);
+        }
+        let certs_set = [&self.ca_cert, &self.client_cert, &self.client_key].iter().any(|path| path.is_some());
+        if certs_set && !self.tls {
+            anyhow::bail!("ca_cert, client_cert and client_key are only used with tls = true");
+        }
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            anyhow::bail!("client_cert and client_key must be set together");
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FileWatchConfig {
     pub enabled: bool,
+    // Directories watched non-recursively for every kind of change. Kept for older
+    // configs, [[file_watch.watch]] entries allow per-path rules.
+    #[serde(default)]
     pub watch_dirs: Vec<String>,
+    // Files whose content is hashed (and diffed) on change. Patterns containing '/'
+    // match the full path, others just the file name.
+    #[serde(default = "default_content_patterns")]
+    pub content_patterns: Vec<String>,
+    // Larger files are hashed but not diffed
+    #[serde(default = "default_content_max_bytes")]
+    pub content_max_bytes: u64,
+    // Longer diffs are truncated, 0 disables diffs
+    #[serde(default = "default_diff_max_lines")]
+    pub diff_max_lines: usize,
+    // gitignore-style globs for paths that never produce events. Same matching as
+    // content_patterns, a leading '!' re-includes and the last matching pattern wins.
+    #[serde(default = "default_ignore_patterns")]
+    pub ignore_patterns: Vec<String>,
+    // Events per watched directory per minute before the rest are summarised, 0 is unlimited
+    #[serde(default = "default_max_events_per_minute_per_dir")]
+    pub max_events_per_minute_per_dir: u32,
+    // Built-in profiles added to the watch list, see WatchPreset
+    #[serde(default)]
+    pub presets: Vec<WatchPreset>,
+    // Per-path watch profiles. Last so the array of tables serializes after the plain values.
+    #[serde(default)]
+    pub watch: Vec<WatchEntry>,
+}
+
+// One [[file_watch.watch]] profile. Events in a directory covered by several entries
+// follow the one with the longest path. A path with a glob in it, "/etc/**/*.conf", watches
+// the directory before the first glob and adds the whole path to include.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WatchEntry {
+    pub path: String,
+    #[serde(default)]
+    pub recursive: bool,
+    // Changes that are recorded, everything by default
+    #[serde(default = "default_watch_kinds")]
+    pub kinds: Vec<WatchKind>,
+    // Extra ignore globs for this path, same syntax as ignore_patterns
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    // Globs files must match to be recorded, everything when empty. Same matching as
+    // content_patterns. Directories are always followed.
+    #[serde(default)]
+    pub include: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchPreset {
+    // All of /etc, every text file diffed, less the files the system rewrites on its own
+    Etc,
+}
+
+impl WatchPreset {
+    fn entry(self) -> WatchEntry {
+        match self {
+            WatchPreset::Etc => WatchEntry {
+                path: "/etc".to_string(),
+                recursive: true,
+                kinds: default_watch_kinds(),
+                // Mount table, DHCP client and resolvconf rewrites, ldconfig and hwclock
+                ignore: ["mtab", "resolv.conf", "resolv.conf.*", "ld.so.cache", "adjtime", "blkid.tab*"]
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect(),
+                include: Vec::new(),
+            },
+        }
+    }
+
+    // Added to content_patterns
+    fn content_patterns(self) -> &'static [&'static str] {
+        match self {
+            WatchPreset::Etc => &["/etc/*"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+impl WatchKind {
+    pub const ALL: [WatchKind; 4] = [WatchKind::Created, WatchKind::Modified, WatchKind::Deleted, WatchKind::Renamed];
+}
+
+impl std::fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            WatchKind::Created => "created",
+            WatchKind::Modified => "modified",
+            WatchKind::Deleted => "deleted",
+            WatchKind::Renamed => "renamed",
+        };
+        f.write_str(name)
+    }
+}
+
+fn default_watch_kinds() -> Vec<WatchKind> {
+    WatchKind::ALL.to_vec()
+}
+
+fn validate_ignore_glob(field: &str, pattern: &str) -> Result<()> {
+    let glob = pattern.strip_prefix('!').unwrap_or(pattern).trim_end_matches('/');
+    glob::Pattern::new(glob).with_context(|| format!("{} entry '{}' is not a valid glob", field, pattern))?;
+    Ok(())
+}
+
+impl FileWatchConfig {
+    pub fn validate(&self) -> Result<()> {
+        for pattern in &self.content_patterns {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("content_patterns entry '{}' is not a valid glob", pattern))?;
+        }
+        for pattern in &self.ignore_patterns {
+            validate_ignore_glob("ignore_patterns", pattern)?;
+        }
+
+        let profiles = self.profiles();
+        for (i, entry) in profiles.iter().enumerate() {
+            if entry.path.is_empty() {
+                anyhow::bail!("watch entries need a path, and a glob path needs a directory before the first glob");
+            }
+            if entry.kinds.is_empty() {
+                anyhow::bail!("watch entry {} has no kinds, so nothing would be recorded", entry.path);
+            }
+            for pattern in &entry.ignore {
+                validate_ignore_glob(&format!("watch entry {} ignore", entry.path), pattern)?;
+            }
+            for pattern in &entry.include {
+                glob::Pattern::new(pattern)
+                    .with_context(|| format!("watch entry {} include '{}' is not a valid glob", entry.path, pattern))?;
+            }
+
+            for other in &profiles[..i] {
+                if other.path == entry.path {
+                    anyhow::bail!(
+                        "{} is watched by more than one entry (check watch_dirs and presets too); merge them into one",
+                        entry.path
+                    );
+                }
+                // A non-recursive entry inside a recursive one would hand its own subdirectories
+                // back to the outer entry's rules
+                for (inner, outer) in [(entry, other), (other, entry)] {
+                    if outer.recursive && !inner.recursive && is_within(&inner.path, &outer.path) {
+                        anyhow::bail!(
+                            "watch entry {} is inside recursive entry {} but isn't recursive itself; make it recursive or remove it",
+                            inner.path,
+                            outer.path
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Stricter checks for `config validate`: paths that don't exist at startup are only warned about
+    pub fn check_paths(&self) -> Result<()> {
+        for entry in self.profiles() {
+            if !std::path::Path::new(&entry.path).is_dir() {
+                anyhow::bail!("watch path {} does not exist or is not a directory", entry.path);
+            }
+        }
+        Ok(())
+    }
+
+    // content_patterns plus those of the presets
+    pub fn content_globs(&self) -> Vec<String> {
+        let presets = self.presets.iter().flat_map(|preset| preset.content_patterns().iter().map(|p| p.to_string()));
+        self.content_patterns.iter().cloned().chain(presets).collect()
+    }
+
+    // watch_dirs, presets and [[file_watch.watch]] entries as one list, trailing slashes removed
+    pub fn profiles(&self) -> Vec<WatchEntry> {
+        let legacy = self.watch_dirs.iter().map(|dir| WatchEntry {
+            path: dir.clone(),
+            recursive: false,
+            kinds: default_watch_kinds(),
+            ignore: Vec::new(),
+            include: Vec::new(),
+        });
+        legacy
+            .chain(self.presets.iter().map(|preset| preset.entry()))
+            .chain(self.watch.iter().cloned())
+            .map(|mut entry| {
+                if let Some((dir, recursive)) = split_glob_path(&entry.path) {
+                    entry.include.push(std::mem::replace(&mut entry.path, dir));
+                    entry.recursive |= recursive;
+                }
+                if entry.path.len() > 1 {
+                    entry.path = entry.path.trim_end_matches('/').to_string();
+                }
+                entry
+            })
+            .collect()
+    }
+}
+
+// The directory before the first glob, and whether the glob reaches below it; None without one
+fn split_glob_path(path: &str) -> Option<(String, bool)> {
+    let components: Vec<&str> = path.split('/').collect();
+    let first_glob = components.iter().position(|c| c.contains(['*', '?', '[']))?;
+    let rest = &components[first_glob..];
+    let dir = match components[..first_glob].join("/") {
+        dir if dir.is_empty() && path.starts_with('/') => "/".to_string(),
+        dir => dir,
+    };
+    Some((dir, rest.len() > 1 || rest.contains(&"**")))
+}
+
+// Whether path is strictly below dir
+fn is_within(path: &str, dir: &str) -> bool {
+    std::path::Path::new(path).starts_with(dir) && path != dir
+}
+
+fn default_content_patterns() -> Vec<String> {
+    [
+        "*.conf", "*.cfg", "*.cnf", "*.ini", "*.toml", "*.yaml", "*.yml", "*.json", "*.service",
+        "passwd", "group", "sudoers", "hosts", "crontab", "authorized_keys",
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
+}
+
+fn default_content_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_diff_max_lines() -> usize {
+    50
+}
+
+fn default_ignore_patterns() -> Vec<String> {
+    // Editor swap files, lock files and SQLite journals
+    ["*.swp", "*.swx", "*~", ".#*", "*.tmp", "*.lock", "*-wal", "*-shm", "*-journal"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+fn default_max_events_per_minute_per_dir() -> u32 {
+    600
+}
+
+// Per-kind anomaly thresholds. Units: percent for cpu/mem/swap/disk_full/inode_usage,
+// cpu_throttling (current frequency as a share of max), tcp_retransmits (of segments sent),
+// file_handles (of fs.file-max), process_fds (of the process's open file limit) and
+// pressure_stall (share of time every task was stalled on CPU, memory or IO),
+// MB/s for disk_spike/network_spike, events per second for the rest.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AnomalyConfig {
+    pub ongoing_interval_secs: u64, // Repeat a spike that is still going this often, 0 to report only start and end
+    pub resolve_hysteresis_percent: f64, // How far under its threshold a spike has to drop to be resolved
+    pub cpu_spike: AnomalyThreshold,
+    pub mem_spike: AnomalyThreshold,
+    pub swap_usage: AnomalyThreshold,
+    pub disk_full: AnomalyThreshold,
+    pub disk_spike: AnomalyThreshold,
+    pub network_spike: AnomalyThreshold,
+    pub context_switch_spike: AnomalyThreshold,
+    pub network_errors: AnomalyThreshold,
+    pub network_drops: AnomalyThreshold,
+    pub cpu_throttling: AnomalyThreshold,
+    pub inode_usage: AnomalyThreshold,
+    pub tcp_retransmits: AnomalyThreshold,
+    pub file_handles: AnomalyThreshold,
+    pub process_fds: AnomalyThreshold,
+    pub pressure_stall: AnomalyThreshold,
+    pub disk_full_mounts: BTreeMap<String, f64>, // Mount point -> disk_full threshold for that mount
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+pub struct AnomalyThreshold {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub threshold: f64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl AnomalyThreshold {
+    fn new(threshold: f64) -> Self {
+        Self { enabled: true, threshold }
+    }
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            ongoing_interval_secs: 0,
+            resolve_hysteresis_percent: 10.0,
+            cpu_spike: AnomalyThreshold::new(90.0),
+            mem_spike: AnomalyThreshold::new(90.0),
+            swap_usage: AnomalyThreshold::new(50.0),
+            disk_full: AnomalyThreshold::new(90.0),
+            disk_spike: AnomalyThreshold::new(100.0),
+            network_spike: AnomalyThreshold::new(500.0),
+            context_switch_spike: AnomalyThreshold::new(50000.0),
+            network_errors: AnomalyThreshold::new(0.0),
+            network_drops: AnomalyThreshold::new(0.0),
+            cpu_throttling: AnomalyThreshold::new(60.0),
+            inode_usage: AnomalyThreshold::new(90.0),
+            tcp_retransmits: AnomalyThreshold::new(5.0),
+            file_handles: AnomalyThreshold::new(80.0),
+            process_fds: AnomalyThreshold::new(90.0),
+            pressure_stall: AnomalyThreshold::new(10.0),
+            disk_full_mounts: BTreeMap::new(),
+        }
+    }
+}
+
+impl AnomalyConfig {
+    // (name, threshold, unit) for display and validation
+    pub fn entries(&self) -> [(&'static str, &AnomalyThreshold, &'static str); 15] {
+        [
+            ("cpu_spike", &self.cpu_spike, "%"),
+            ("mem_spike", &self.mem_spike, "%"),
+            ("swap_usage", &self.swap_usage, "%"),
+            ("disk_full", &self.disk_full, "%"),
+            ("disk_spike", &self.disk_spike, "MB/s"),
+            ("network_spike", &self.network_spike, "MB/s"),
+            ("context_switch_spike", &self.context_switch_spike, "/s"),
+            ("network_errors", &self.network_errors, "/s"),
+            ("network_drops", &self.network_drops, "/s"),
+            ("cpu_throttling", &self.cpu_throttling, "%"),
+            ("inode_usage", &self.inode_usage, "%"),
+            ("tcp_retransmits", &self.tcp_retransmits, "%"),
+            ("file_handles", &self.file_handles, "%"),
+            ("process_fds", &self.process_fds, "%"),
+            ("pressure_stall", &self.pressure_stall, "%"),
+        ]
+    }
+
+    pub fn disk_full_threshold(&self, mount_point: &str) -> f64 {
+        self.disk_full_mounts.get(mount_point).copied().unwrap_or(self.disk_full.threshold)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.resolve_hysteresis_percent.is_finite() || !(0.0..=100.0).contains(&self.resolve_hysteresis_percent) {
+            anyhow::bail!("anomalies.resolve_hysteresis_percent must be a percentage between 0 and 100");
+        }
+        for (name, entry, unit) in self.entries() {
+            if !entry.threshold.is_finite() || entry.threshold < 0.0 {
+                anyhow::bail!("anomalies.{}.threshold must be a non-negative number", name);
+            }
+            if unit == "%" && entry.threshold > 100.0 {
+                anyhow::bail!("anomalies.{}.threshold is a percentage and must be <= 100", name);
+            }
+        }
+        for (mount_point, threshold) in &self.disk_full_mounts {
+            // Matched exactly against mount points as the kernel reports them
+            if !mount_point.starts_with('/') || (mount_point.len() > 1 && mount_point.ends_with('/')) {
+                anyhow::bail!("anomalies.disk_full_mounts: '{}' must be an absolute path without a trailing slash", mount_point);
+            }
+            if !threshold.is_finite() || !(0.0..=100.0).contains(threshold) {
+                anyhow::bail!("anomalies.disk_full_mounts.\"{}\" must be a percentage between 0 and 100", mount_point);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for FileWatchConfig {
@@ -71,7 +868,28 @@ impl Default for FileWatchConfig {
         Self {
             enabled: false,
             watch_dirs: vec![],
+            content_patterns: default_content_patterns(),
+            content_max_bytes: default_content_max_bytes(),
+            diff_max_lines: default_diff_max_lines(),
+            ignore_patterns: default_ignore_patterns(),
+            max_events_per_minute_per_dir: default_max_events_per_minute_per_dir(),
+            presets: Vec::new(),
+            watch: Vec::new(),
+        }
+    }
+}
+
+impl ProtectionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.sign_events && self.signing_key.as_deref().is_none_or(str::is_empty) {
+            anyhow::bail!("sign_events needs a signing_key to sign segment records with");
         }
+        if let Some(user) = &self.run_as_user
+            && (user.is_empty() || user == "root")
+        {
+            anyhow::bail!("run_as_user must name an unprivileged user, got {:?}", user);
+        }
+        Ok(())
     }
 }
 
@@ -82,11 +900,372 @@ impl Default for ProtectionConfig {
             remote_syslog: None,
             sign_events: false,
             signing_key: None,
+            sealed_attribute: None,
+            run_as_user: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProcessTrackingConfig {
+    pub mode: ProcessTrackingMode,
+    // Processes never recorded as started or exited, or listed as top processes. Each entry
+    // matches a process name, or the start of a command line when it has arguments or a path.
+    // Black Box itself and the commands it runs are always left out.
+    pub ignore_processes: Vec<String>,
+    // Length of each top processes list, by CPU and by memory. A snapshot holds their union.
+    pub top_processes: usize,
+    // Exits of one command (name and command line) within the window reported as a crash loop, 0 to disable
+    pub crash_loop_threshold: usize,
+    pub crash_loop_window_secs: u64,
+}
+
+impl Default for ProcessTrackingConfig {
+    fn default() -> Self {
+        Self {
+            mode: ProcessTrackingMode::default(),
+            ignore_processes: vec![],
+            top_processes: 10,
+            crash_loop_threshold: 5,
+            crash_loop_window_secs: 60,
+        }
+    }
+}
+
+// Each listed process has its details and sockets read, keep snapshots small
+const MAX_TOP_PROCESSES: usize = 100;
+
+impl ProcessTrackingConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.ignore_processes.iter().any(|entry| entry.trim().is_empty()) {
+            anyhow::bail!("ignore_processes entries must not be empty");
         }
+        if !(1..=MAX_TOP_PROCESSES).contains(&self.top_processes) {
+            anyhow::bail!("top_processes must be between 1 and {}", MAX_TOP_PROCESSES);
+        }
+        if self.crash_loop_window_secs == 0 {
+            anyhow::bail!("crash_loop_window_secs must be greater than 0");
+        }
+        Ok(())
     }
 }
 
-const CONFIG_PATH: &str = "./config.toml";
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessTrackingMode {
+    // Diff /proc every collection interval; misses processes shorter than the interval
+    #[default]
+    Poll,
+    // Kernel proc connector events, falls back to Poll without CAP_NET_ADMIN
+    Netlink,
+}
+
+// Collectors that are off unless turned on
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CollectionConfig {
+    // Record every exec and exit from the kernel's process events as well as the /proc diff,
+    // so processes shorter than the interval are seen. Needs root or CAP_NET_ADMIN.
+    pub exec_tracing: bool,
+}
+
+// A simpler switch for [server] segment_compression_level
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    // false turns compression off, true turns it on at the default level if the level is 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>,
+}
+
+// SMART disk health via smartctl, skipped quietly where smartctl is missing or not permitted
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SmartConfig {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+}
+
+impl SmartConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_minutes == 0 {
+            anyhow::bail!("interval_minutes must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+impl Default for SmartConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: 15,
+        }
+    }
+}
+
+// systemd service failures and restarts, skipped quietly without systemd
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServiceMonitorConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+}
+
+impl ServiceMonitorConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_secs == 0 {
+            anyhow::bail!("interval_secs must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+impl Default for ServiceMonitorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 10,
+        }
+    }
+}
+
+// Bytes per remote host from the TCP counters in `ss -tin`, named in network spike anomalies.
+// Off by default since it lists every TCP connection each interval.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TrafficAccountingConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub group_by: TrafficGrouping,
+}
+
+impl TrafficAccountingConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_secs == 0 {
+            anyhow::bail!("interval_secs must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+impl Default for TrafficAccountingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 10,
+            group_by: TrafficGrouping::Subnet,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrafficGrouping {
+    // Remote /24 for IPv4, /64 for IPv6
+    #[default]
+    Subnet,
+    // Exact remote IP address
+    Host,
+}
+
+// Things the web UI may do to the machine rather than just observe it. All off by default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ActionsConfig {
+    pub allow_kill: bool, // SIGTERM/SIGKILL processes from the process tables
+}
+
+// Inbound port scan detection from /proc/net/tcp and tcp6
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SecurityConfig {
+    pub port_scan_threshold: usize, // Distinct local ports one remote IP must reach within the window
+    pub port_scan_window_secs: u64,
+    pub port_scan_allowlist: Vec<String>, // CIDRs that never trigger, e.g. "10.20.0.0/16" for monitoring
+}
+
+impl SecurityConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.port_scan_threshold < 2 {
+            anyhow::bail!("port_scan_threshold must be at least 2");
+        }
+        if self.port_scan_window_secs == 0 {
+            anyhow::bail!("port_scan_window_secs must be at least 1");
+        }
+        for cidr in &self.port_scan_allowlist {
+            Cidr::parse(cidr)?;
+        }
+        Ok(())
+    }
+
+    // Entries that don't parse were rejected by validate(), so they're skipped here
+    pub fn allowlist(&self) -> Vec<Cidr> {
+        self.port_scan_allowlist.iter().filter_map(|c| Cidr::parse(c).ok()).collect()
+    }
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            port_scan_threshold: 20,
+            port_scan_window_secs: 60,
+            port_scan_allowlist: Vec::new(),
+        }
+    }
+}
+
+// Notifications sent out for anomalies as they're recorded
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub email: Option<EmailAlertConfig>,
+    #[serde(default)]
+    pub webhook: Option<WebhookAlertConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+// Anomalies and security events POSTed as JSON, e.g. to a Slack incoming webhook. Anything at
+// least min_severity is sent, and so are the listed kinds whatever their severity.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookAlertConfig {
+    pub enabled: bool,
+    pub urls: Vec<String>,
+    #[serde(default = "default_webhook_min_severity")]
+    pub min_severity: AlertSeverity,
+    // Anomaly or security event kinds, e.g. "BruteForceAttempt" or "SudoersModified"
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    // The same kind isn't sent again within this many seconds
+    #[serde(default = "default_webhook_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_webhook_min_severity() -> AlertSeverity {
+    AlertSeverity::Critical
+}
+
+fn default_webhook_cooldown_secs() -> u64 {
+    300
+}
+
+impl WebhookAlertConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.urls.is_empty() {
+            anyhow::bail!("urls must list at least one webhook");
+        }
+        for url in &self.urls {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                anyhow::bail!("{:?} is not an http(s) URL", url);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Critical anomalies are emailed straight away, Warnings collected into a digest
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmailAlertConfig {
+    pub enabled: bool,
+    pub smtp_host: String,
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    // Upgrade with STARTTLS and refuse to send without it. Off only for a trusted local relay.
+    #[serde(default = "default_true")]
+    pub starttls: bool,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    // Warnings are sent at most this often
+    #[serde(default = "default_digest_interval_minutes")]
+    pub digest_interval_minutes: u64,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_digest_interval_minutes() -> u64 {
+    15
+}
+
+impl EmailAlertConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.smtp_host.is_empty() {
+            anyhow::bail!("smtp_host must be set");
+        }
+        if self.to.is_empty() {
+            anyhow::bail!("to must list at least one address");
+        }
+        for address in std::iter::once(&self.from).chain(&self.to) {
+            address
+                .parse::<lettre::message::Mailbox>()
+                .with_context(|| format!("{:?} is not an email address", address))?;
+        }
+        if self.username.is_some() != self.password.is_some() {
+            anyhow::bail!("username and password must be set together");
+        }
+        if self.digest_interval_minutes == 0 {
+            anyhow::bail!("digest_interval_minutes must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+// An address block like "192.168.0.0/16" or "fd00::/8"; a bare address is a single host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl Cidr {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .with_context(|| format!("'{}' is not an IP address or CIDR", s))?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u32>()
+                .ok()
+                .filter(|&p| p <= bits)
+                .with_context(|| format!("'{}' needs a prefix length between 0 and {}", s, bits))?,
+            None => bits,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // A shift by the full width means /0, which matches everything
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                (u32::from(net) ^ u32::from(ip)).checked_shr(32 - self.prefix).unwrap_or(0) == 0
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                (u128::from(net) ^ u128::from(ip)).checked_shr(128 - self.prefix).unwrap_or(0) == 0
+            }
+            _ => false,
+        }
+    }
+}
+
+pub const CONFIG_PATH: &str = "./config.toml";
 
 impl Config {
     // Load config from file, or create default if not exists
@@ -99,19 +1278,87 @@ impl Config {
             println!("  Username: admin");
             println!("  Password: admin");
             println!("\nPLEASE CHANGE THE DEFAULT PASSWORD IMMEDIATELY!");
-            println!("Run: cargo run --bin hashpw <your-password>");
-            println!("Then update the password_hash in config.toml\n");
+            println!("Set password = \"<your-password>\" under [auth] in config.toml;");
+            println!("it is replaced with a hash the next time Black Box starts.\n");
             return Ok(config);
         }
 
         let content = fs::read_to_string(CONFIG_PATH).context("Failed to read config.toml")?;
-        let config: Config = toml::from_str(&content).context("Failed to parse config.toml")?;
+        let mut config: Config = toml::from_str(&content).context("Failed to parse config.toml")?;
+        config.auth.validate().context("Invalid [auth] section in config.toml")?;
+        config.server.validate().context("Invalid [server] section in config.toml")?;
+        config.anomalies.validate().context("Invalid [anomalies] section in config.toml")?;
+        config.file_watch.validate().context("Invalid [file_watch] section in config.toml")?;
+        config.protection.validate().context("Invalid [protection] section in config.toml")?;
+        config.process_tracking.validate().context("Invalid [process_tracking] section in config.toml")?;
+        config.smart.validate().context("Invalid [smart] section in config.toml")?;
+        config.services.validate().context("Invalid [services] section in config.toml")?;
+        config.traffic_accounting.validate().context("Invalid [traffic_accounting] section in config.toml")?;
+        config.security.validate().context("Invalid [security] section in config.toml")?;
+        if let Some(syslog) = &config.protection.remote_syslog {
+            syslog.validate().context("Invalid [protection.remote_syslog] section in config.toml")?;
+        }
+        if let Some(email) = &config.alerts.email {
+            email.validate().context("Invalid [alerts.email] section in config.toml")?;
+        }
+        if let Some(webhook) = &config.alerts.webhook {
+            webhook.validate().context("Invalid [alerts.webhook] section in config.toml")?;
+        }
+
+        if config.migrate_plaintext_password()? {
+            let toml_content = toml::to_string_pretty(&config)
+                .context("Failed to serialize config")?;
+            match fs::write(CONFIG_PATH, toml_content) {
+                Ok(()) => println!("Replaced plaintext password in config.toml with an argon2 hash"),
+                Err(e) => eprintln!("Warning: Failed to save hashed password to config.toml: {}", e),
+            }
+        }
+
+        if config.auth.enabled && config.auth.password_hash.is_empty() {
+            anyhow::bail!("[auth] needs a password_hash (or a password to hash) when auth is enabled");
+        }
+        if config.actions.allow_kill && !config.auth.enabled {
+            anyhow::bail!("[actions] allow_kill needs [auth] enabled so every signal sent is attributed to an account");
+        }
+
+        Ok(config)
+    }
+
+    // Load with the stricter checks of `config validate`, also used when reloading a running recorder
+    pub fn load_checked() -> Result<Self> {
+        let config = Self::load()?;
+        config.file_watch.check_paths().context("Invalid [file_watch] section in config.toml")?;
         Ok(config)
     }
 
+    // Whether to start the proc connector: [collection] exec_tracing, or [process_tracking]
+    // mode = "netlink" from configs written before that switch
+    pub fn exec_tracing(&self) -> bool {
+        self.collection.exec_tracing || self.process_tracking.mode == ProcessTrackingMode::Netlink
+    }
+
+    // The zstd level for full segments, 0 for none: [server] segment_compression_level, unless
+    // [storage] compress says otherwise
+    pub fn segment_compression_level(&self) -> i32 {
+        match self.storage.compress {
+            Some(false) => 0,
+            Some(true) if self.server.segment_compression_level == 0 => default_segment_compression_level(),
+            _ => self.server.segment_compression_level,
+        }
+    }
+
+    // Hash a plaintext [auth] password in place. Returns true if the config changed.
+    fn migrate_plaintext_password(&mut self) -> Result<bool> {
+        let Some(password) = self.auth.password.take() else {
+            return Ok(false);
+        };
+        self.auth.password_hash = hash_password(&password)?;
+        Ok(true)
+    }
+
     // Create default config with admin/admin credentials and write it to disk
     fn create_default() -> Result<Self> {
-        let default_hash = bcrypt::hash("admin", bcrypt::DEFAULT_COST)
+        let default_hash = hash_password("admin")
             .context("Failed to generate default password hash")?;
 
         let config = Config {
@@ -119,14 +1366,41 @@ impl Config {
                 enabled: true,
                 username: "admin".to_string(),
                 password_hash: default_hash,
+                password: None,
+                api_tokens: Vec::new(),
             },
             server: ServerConfig {
                 port: 8080,
+                bind_address: default_bind_address(),
+                base_path: String::new(),
+                unix_socket_path: None,
                 data_dir: "./data".to_string(),
                 max_storage_mb: 100,
+                max_storage_bytes: None,
+                max_retention_days: None,
+                max_segment_mb: default_max_segment_mb(),
+                segment_compression_level: default_segment_compression_level(),
+                fsync: FsyncPolicy::default(),
+                tls_cert: None,
+                tls_key: None,
+                resume_max_secs: default_resume_max_secs(),
+                resume_max_events: default_resume_max_events(),
+                downsample_after_hours: None,
+                downsample_window_secs: default_downsample_window_secs(),
+                timezone: None,
             },
             protection: ProtectionConfig::default(),
             file_watch: FileWatchConfig::default(),
+            anomalies: AnomalyConfig::default(),
+            process_tracking: ProcessTrackingConfig::default(),
+            collection: CollectionConfig::default(),
+            storage: StorageConfig::default(),
+            smart: SmartConfig::default(),
+            services: ServiceMonitorConfig::default(),
+            traffic_accounting: TrafficAccountingConfig::default(),
+            actions: ActionsConfig::default(),
+            security: SecurityConfig::default(),
+            alerts: AlertsConfig::default(),
         };
 
         let toml_content = toml::to_string_pretty(&config)
@@ -144,14 +1418,41 @@ impl Config {
                 enabled: true,
                 username: "test".to_string(),
                 password_hash: bcrypt::hash("test", 4).unwrap(),
+                password: None,
+                api_tokens: Vec::new(),
             },
             server: ServerConfig {
                 port: 8080,
+                bind_address: default_bind_address(),
+                base_path: String::new(),
+                unix_socket_path: None,
                 data_dir: "./test_data".to_string(),
                 max_storage_mb: 100,
+                max_storage_bytes: None,
+                max_retention_days: None,
+                max_segment_mb: default_max_segment_mb(),
+                segment_compression_level: default_segment_compression_level(),
+                fsync: FsyncPolicy::default(),
+                tls_cert: None,
+                tls_key: None,
+                resume_max_secs: default_resume_max_secs(),
+                resume_max_events: default_resume_max_events(),
+                downsample_after_hours: None,
+                downsample_window_secs: default_downsample_window_secs(),
+                timezone: None,
             },
             protection: ProtectionConfig::default(),
             file_watch: FileWatchConfig::default(),
+            anomalies: AnomalyConfig::default(),
+            process_tracking: ProcessTrackingConfig::default(),
+            collection: CollectionConfig::default(),
+            storage: StorageConfig::default(),
+            smart: SmartConfig::default(),
+            services: ServiceMonitorConfig::default(),
+            traffic_accounting: TrafficAccountingConfig::default(),
+            actions: ActionsConfig::default(),
+            security: SecurityConfig::default(),
+            alerts: AlertsConfig::default(),
         }
     }
 }
@@ -168,6 +1469,65 @@ mod tests {
         assert!(toml_str.contains("password_hash"));
     }
 
+    #[test]
+    fn test_password_hash_and_verify() {
+        let hash = hash_password("s3cret").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("s3cret", &hash));
+        assert!(!verify_password("wrong", &hash));
+
+        // Older configs still carry bcrypt hashes
+        let legacy = bcrypt::hash("s3cret", 4).unwrap();
+        assert!(verify_password("s3cret", &legacy));
+        assert!(!verify_password("s3cret", ""));
+    }
+
+    #[test]
+    fn test_api_tokens() {
+        let (token, hash) = generate_api_token();
+        assert!(token.starts_with("bbt_"));
+        assert!(!hash.contains(&token));
+
+        let mut auth = Config::test_config().auth;
+        auth.api_tokens.push(ApiToken { name: "grafana".to_string(), token_hash: hash.clone() });
+        auth.validate().unwrap();
+        assert_eq!(auth.api_token_name(&token), Some("grafana"));
+        assert_eq!(auth.api_token_name(&hash), None);
+        assert_eq!(auth.api_token_name("bbt_guess"), None);
+
+        // Only the hash is ever written out
+        let toml_str = toml::to_string_pretty(&auth).unwrap();
+        assert!(toml_str.contains(&hash) && !toml_str.contains(&token));
+
+        auth.api_tokens.push(ApiToken { name: "grafana".to_string(), token_hash: hash });
+        assert!(auth.validate().unwrap_err().to_string().contains("listed twice"));
+        auth.api_tokens[1] = ApiToken { name: "scripts".to_string(), token_hash: token };
+        assert!(auth.validate().is_err());
+    }
+
+    #[test]
+    fn test_plaintext_password_migration() {
+        let toml_str = r#"
+            [auth]
+            enabled = true
+            username = "admin"
+            password = "hunter2"
+
+            [server]
+            port = 8080
+            data_dir = "./data"
+        "#;
+
+        let mut config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.migrate_plaintext_password().unwrap());
+        assert!(config.auth.password.is_none());
+        assert!(config.auth.verify("admin", "hunter2"));
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(!saved.contains("hunter2"));
+        assert!(!config.migrate_plaintext_password().unwrap());
+    }
+
     #[test]
     fn test_config_deserialization() {
         let toml_str = r#"
@@ -184,5 +1544,347 @@ mod tests {
         let config: Config = toml::from_str(toml_str).unwrap();
         assert_eq!(config.auth.username, "admin");
         assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.fsync, FsyncPolicy::Segment);
+
+        let config: Config = toml::from_str(&toml_str.replace("port = 8080", "port = 8080\nfsync = \"tick\"")).unwrap();
+        assert_eq!(config.server.fsync, FsyncPolicy::Tick);
+    }
+
+    #[test]
+    fn test_anomaly_config_partial_override() {
+        let toml_str = r#"
+            [auth]
+            enabled = true
+            username = "admin"
+            password_hash = "$2b$12$test"
+
+            [server]
+            port = 8080
+            data_dir = "./data"
+
+            [anomalies.cpu_spike]
+            enabled = false
+            threshold = 99.0
+
+            [anomalies.network_spike]
+            threshold = 1000.0
+
+            [anomalies.disk_full_mounts]
+            "/var" = 80.0
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(!config.anomalies.cpu_spike.enabled);
+        assert_eq!(config.anomalies.cpu_spike.threshold, 99.0);
+        assert!(config.anomalies.network_spike.enabled);
+        assert_eq!(config.anomalies.network_spike.threshold, 1000.0);
+        // Unspecified kinds keep their defaults
+        assert_eq!(config.anomalies.mem_spike, AnomalyThreshold::new(90.0));
+        assert_eq!(config.anomalies.disk_full_threshold("/var"), 80.0);
+        assert_eq!(config.anomalies.disk_full_threshold("/home"), 90.0);
+        assert!(config.anomalies.validate().is_ok());
+    }
+
+    #[test]
+    fn test_retention_policy_from_server_config() {
+        let mut config = Config::test_config();
+        assert_eq!(config.server.storage_limit_bytes(), 100 * 1024 * 1024);
+        assert_eq!(config.server.retention_policy().max_bytes, 100 * 1024 * 1024);
+
+        config.server.max_storage_bytes = Some(50 * 1024 * 1024);
+        config.server.max_retention_days = Some(7);
+        let policy = config.server.retention_policy();
+        assert_eq!(policy.max_bytes, 50 * 1024 * 1024);
+        assert_eq!(policy.max_age_secs, Some(7 * 24 * 60 * 60));
+        assert!(config.server.validate().is_ok());
+
+        config.server.segment_compression_level = 23;
+        assert!(config.server.validate().is_err());
+        config.server.segment_compression_level = 0;
+        assert!(config.server.validate().is_ok());
+
+        config.server.max_storage_bytes = Some(1024);
+        assert!(config.server.validate().is_err());
+
+        // --max-storage replaces both config limits
+        config.server.override_storage_limit(2 * 1024 * 1024);
+        assert_eq!(config.server.storage_limit_bytes(), 2 * 1024 * 1024 * 1024 * 1024);
+        assert!(config.server.validate().is_ok());
+
+        // Room for at least two segments, each a sane size
+        assert_eq!(config.server.segment_size_bytes(), 8 * 1024 * 1024);
+        config.server.max_segment_mb = 0;
+        assert!(config.server.validate().is_err());
+        config.server.max_segment_mb = 512;
+        assert!(config.server.validate().is_err());
+        config.server.max_segment_mb = 64;
+        config.server.override_storage_limit(100);
+        assert!(config.server.validate().unwrap_err().to_string().contains("at least 2 segments"));
+        config.server.override_storage_limit(128);
+        assert!(config.server.validate().is_ok());
+
+        // Rejected rather than wrapping round to a tiny limit
+        config.server.override_storage_limit(u64::MAX / 1024);
+        assert_eq!(config.server.storage_limit_bytes(), u64::MAX);
+        assert!(config.server.validate().unwrap_err().to_string().contains("max_storage_mb must be at most"));
+        config.server.override_storage_limit(u64::MAX / (1024 * 1024));
+        assert!(config.server.validate().is_ok());
+    }
+
+    #[test]
+    fn test_listen_address_validation() {
+        let mut config = Config::test_config();
+        assert_eq!(config.server.bind_address, "0.0.0.0");
+        config.server.bind_address = "::1".to_string();
+        assert!(config.server.validate().is_ok());
+        config.server.bind_address = "localhost".to_string();
+        let err = config.server.validate().unwrap_err();
+        assert!(err.to_string().contains("\"localhost\""));
+
+        config.server.bind_address = "127.0.0.1".to_string();
+        config.server.unix_socket_path = Some("/run/black-box/web.sock".to_string());
+        assert!(config.server.validate().is_ok());
+        config.server.tls_cert = Some("cert.pem".to_string());
+        config.server.tls_key = Some("key.pem".to_string());
+        assert!(config.server.validate().is_err());
+
+        let server: ServerConfig =
+            toml::from_str("port = 8080\ndata_dir = \"data\"\nbind_addr = \"::1\"\nbase_path = \"/black-box/\"").unwrap();
+        assert_eq!(server.bind_address, "::1");
+        assert_eq!(server.base_path(), "/black-box");
+        assert!(server.validate().is_ok());
+        let server = ServerConfig { base_path: "black-box".to_string(), ..server };
+        assert!(server.validate().is_err());
+        let server = ServerConfig { base_path: "/black box\"".to_string(), ..server };
+        assert!(server.validate().is_err());
+    }
+
+    #[test]
+    fn test_anomaly_config_validation() {
+        let mut anomalies = AnomalyConfig::default();
+        anomalies.disk_full.threshold = 150.0;
+        assert!(anomalies.validate().is_err());
+
+        let mut anomalies = AnomalyConfig::default();
+        anomalies.context_switch_spike.threshold = -1.0;
+        assert!(anomalies.validate().is_err());
+
+        let mut anomalies = AnomalyConfig::default();
+        anomalies.disk_full_mounts.insert("/var".to_string(), 80.0);
+        assert!(anomalies.validate().is_ok());
+        assert_eq!(anomalies.disk_full_threshold("/var"), 80.0);
+        assert_eq!(anomalies.disk_full_threshold("/"), 90.0);
+        anomalies.disk_full_mounts.insert("/data/".to_string(), 95.0);
+        assert!(anomalies.validate().is_err());
+
+        let mut anomalies = AnomalyConfig::default();
+        anomalies.disk_full_mounts.insert("/var".to_string(), 101.0);
+        assert!(anomalies.validate().is_err());
+    }
+
+    #[test]
+    fn test_file_watch_profiles() {
+        let toml_str = r#"
+            enabled = true
+            watch_dirs = ["/var/www/"]
+
+            [[watch]]
+            path = "/etc"
+            recursive = true
+
+            [[watch]]
+            path = "/home"
+            recursive = true
+            kinds = ["created"]
+            ignore = ["*.part"]
+        "#;
+
+        let file_watch: FileWatchConfig = toml::from_str(toml_str).unwrap();
+        let profiles = file_watch.profiles();
+        let paths: Vec<&str> = profiles.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, vec!["/var/www", "/etc", "/home"]);
+        assert_eq!(profiles[1].kinds.len(), 4);
+        assert_eq!(profiles[2].kinds, vec![WatchKind::Created]);
+        assert!(file_watch.validate().is_ok());
+
+        // Round-trips through `config show` output
+        let reparsed: FileWatchConfig = toml::from_str(&toml::to_string_pretty(&file_watch).unwrap()).unwrap();
+        assert_eq!(reparsed.watch, file_watch.watch);
+
+        let entry = |path: &str, recursive: bool| WatchEntry {
+            path: path.to_string(),
+            recursive,
+            kinds: default_watch_kinds(),
+            ignore: Vec::new(),
+            include: Vec::new(),
+        };
+        let with = |extra: Vec<WatchEntry>| FileWatchConfig {
+            watch: file_watch.watch.iter().cloned().chain(extra).collect(),
+            ..file_watch.clone()
+        };
+
+        // Narrower rules for a subdirectory are fine as long as they cover the same depth
+        assert!(with(vec![entry("/etc/ssh", true)]).validate().is_ok());
+        assert!(with(vec![entry("/etc/ssh", false)]).validate().is_err());
+        assert!(with(vec![entry("/var/www/", true)]).validate().is_err());
+        assert!(with(vec![WatchEntry { kinds: Vec::new(), ..entry("/srv", false) }]).validate().is_err());
+
+        // Glob paths watch the directory before the glob
+        let globbed = FileWatchConfig {
+            watch: vec![entry("/srv/**/*.conf", false), entry("/opt/app/*.yml", false), entry("/*.log", false)],
+            ..FileWatchConfig::default()
+        };
+        let profiles = globbed.profiles();
+        let split: Vec<(&str, bool, &str)> =
+            profiles.iter().map(|p| (p.path.as_str(), p.recursive, p.include[0].as_str())).collect();
+        assert_eq!(
+            split,
+            vec![("/srv", true, "/srv/**/*.conf"), ("/opt/app", false, "/opt/app/*.yml"), ("/", false, "/*.log")]
+        );
+        assert!(globbed.validate().is_ok());
+        assert!(with(vec![entry("**/*.conf", false)]).validate().is_err());
+
+        let preset: FileWatchConfig = toml::from_str("enabled = true\npresets = [\"etc\"]").unwrap();
+        let profiles = preset.profiles();
+        assert_eq!((profiles[0].path.as_str(), profiles[0].recursive), ("/etc", true));
+        assert!(profiles[0].ignore.contains(&"resolv.conf".to_string()));
+        assert_eq!(preset.content_globs().last().map(String::as_str), Some("/etc/*"));
+        assert!(preset.validate().is_ok());
+        // Already watched by the preset
+        assert!(FileWatchConfig { watch: vec![entry("/etc/", true)], ..preset }.validate().is_err());
+        assert!(with(vec![WatchEntry { include: vec!["[".to_string()], ..entry("/srv", false) }]).validate().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let existing = FileWatchConfig { watch: vec![entry(dir.path().to_str().unwrap(), true)], ..FileWatchConfig::default() };
+        assert!(existing.check_paths().is_ok());
+        assert!(FileWatchConfig { watch: vec![entry("/nonexistent/black-box", false)], ..FileWatchConfig::default() }
+            .check_paths()
+            .is_err());
+    }
+
+    #[test]
+    fn test_security_allowlist_cidrs() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        let net = Cidr::parse("10.20.0.0/16").unwrap();
+        assert!(net.contains(ip("10.20.3.4")));
+        assert!(!net.contains(ip("10.21.0.1")));
+        assert!(net.contains(ip("::ffff:10.20.0.9"))); // IPv4-mapped, as seen on dual-stack sockets
+
+        assert!(Cidr::parse("192.0.2.7").unwrap().contains(ip("192.0.2.7")));
+        assert!(!Cidr::parse("192.0.2.7").unwrap().contains(ip("192.0.2.8")));
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("203.0.113.1")));
+        assert!(Cidr::parse("fd00::/8").unwrap().contains(ip("fd12::1")));
+        assert!(!Cidr::parse("fd00::/8").unwrap().contains(ip("10.0.0.1")));
+
+        let security = |allowlist: &[&str]| SecurityConfig {
+            port_scan_allowlist: allowlist.iter().map(|s| s.to_string()).collect(),
+            ..SecurityConfig::default()
+        };
+        assert!(security(&["10.0.0.0/8", "fd00::/8"]).validate().is_ok());
+        assert!(security(&["10.0.0.0/33"]).validate().is_err());
+        assert!(security(&["monitoring.internal"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_protection_config() {
+        let config: ProtectionConfig = toml::from_str("").unwrap();
+        assert_eq!(config.sealed_attribute, None);
+        assert_eq!(config.run_as_user, None);
+
+        let config: ProtectionConfig =
+            toml::from_str("sealed_attribute = \"immutable\"\nrun_as_user = \"blackbox\"").unwrap();
+        assert_eq!(config.sealed_attribute, Some(SegmentAttribute::Immutable));
+        assert!(config.validate().is_ok());
+
+        let run_as = |user: &str| ProtectionConfig { run_as_user: Some(user.to_string()), ..ProtectionConfig::default() };
+        assert!(run_as("").validate().is_err());
+        assert!(run_as("root").validate().is_err());
+        assert!(toml::from_str::<ProtectionConfig>("sealed_attribute = \"hidden\"").is_err());
+    }
+
+    #[test]
+    fn test_process_tracking_config() {
+        let config: ProcessTrackingConfig = toml::from_str("ignore_processes = [\"node_exporter\"]").unwrap();
+        assert_eq!(config.top_processes, 10);
+        assert!(config.validate().is_ok());
+
+        let config: ProcessTrackingConfig = toml::from_str("top_processes = 25").unwrap();
+        assert_eq!(config.top_processes, 25);
+        assert!(ProcessTrackingConfig { top_processes: 0, ..config.clone() }.validate().is_err());
+        assert!(ProcessTrackingConfig { top_processes: 101, ..config.clone() }.validate().is_err());
+        assert_eq!((config.crash_loop_threshold, config.crash_loop_window_secs), (5, 60));
+        assert!(ProcessTrackingConfig { crash_loop_threshold: 0, ..config.clone() }.validate().is_ok());
+        assert!(ProcessTrackingConfig { crash_loop_window_secs: 0, ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn test_exec_tracing_off_by_default() {
+        let mut config = Config::test_config();
+        assert!(!config.exec_tracing());
+        config.collection = toml::from_str("exec_tracing = true").unwrap();
+        assert!(config.exec_tracing());
+
+        // The older switch still turns it on
+        config.collection = CollectionConfig::default();
+        config.process_tracking = toml::from_str("mode = \"netlink\"").unwrap();
+        assert!(config.exec_tracing());
+    }
+
+    #[test]
+    fn test_storage_compress_overrides_level() {
+        let mut config = Config::test_config();
+        assert_eq!(config.segment_compression_level(), 3);
+        config.storage = toml::from_str("compress = false").unwrap();
+        assert_eq!(config.segment_compression_level(), 0);
+
+        // On at the default level, or whatever level [server] asks for
+        config.storage = toml::from_str("compress = true").unwrap();
+        config.server.segment_compression_level = 0;
+        assert_eq!(config.segment_compression_level(), 3);
+        config.server.segment_compression_level = 9;
+        assert_eq!(config.segment_compression_level(), 9);
+
+        config.storage = StorageConfig::default();
+        assert_eq!(config.segment_compression_level(), 9);
+        assert!(toml::from_str::<StorageConfig>("compress = \"yes\"").is_err());
+    }
+
+    #[test]
+    fn test_email_alert_config() {
+        let config: AlertsConfig = toml::from_str(
+            "[email]\nenabled = true\nsmtp_host = \"smtp.example.com\"\nfrom = \"Black Box <blackbox@example.com>\"\nto = [\"ops@example.com\"]",
+        )
+        .unwrap();
+        let email = config.email.unwrap();
+        assert_eq!(email.smtp_port, 587);
+        assert!(email.starttls);
+        assert_eq!(email.digest_interval_minutes, 15);
+        assert!(email.validate().is_ok());
+
+        assert!(EmailAlertConfig { to: vec![], ..email.clone() }.validate().is_err());
+        assert!(EmailAlertConfig { to: vec!["ops".to_string()], ..email.clone() }.validate().is_err());
+        assert!(EmailAlertConfig { username: Some("ops".to_string()), ..email.clone() }.validate().is_err());
+        assert!(EmailAlertConfig { digest_interval_minutes: 0, ..email }.validate().is_err());
+    }
+
+    #[test]
+    fn test_remote_syslog_tls_config() {
+        let syslog: RemoteSyslogConfig = toml::from_str(
+            "enabled = true\nhost = \"logs.example.com\"\nport = 6514\nprotocol = \"tcp\"\ntls = true\nca_cert = \"/etc/blackbox/ca.pem\"",
+        )
+        .unwrap();
+        assert_eq!(syslog.framing, SyslogFraming::OctetCounting);
+        assert!(syslog.validate().is_ok());
+
+        let client = |cert: Option<&str>, key: Option<&str>| RemoteSyslogConfig {
+            client_cert: cert.map(str::to_string),
+            client_key: key.map(str::to_string),
+            ..syslog.clone()
+        };
+        assert!(client(Some("client.pem"), Some("client.key")).validate().is_ok());
+        assert!(client(Some("client.pem"), None).validate().is_err());
+        assert!(RemoteSyslogConfig { protocol: "udp".to_string(), ..syslog.clone() }.validate().is_err());
+        assert!(RemoteSyslogConfig { tls: false, ..syslog }.validate().is_err());
     }
 }
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, ProcessLifecycle, ProcessLifecycleKind};
+
+// Commands tracked at once. A build box runs thousands of distinct ones, the least recently
+// seen is forgotten first.
+const MAX_TRACKED_COMMANDS: usize = 1024;
+
+// Exits of one command within the window
+struct CommandExits {
+    exits: VecDeque<Instant>,
+    last_exit_code: Option<i32>,
+    last_seen: Instant,
+    reported: Option<Instant>, // So one crash loop is one anomaly per window, not one per exit
+}
+
+/// Reports a command, matched by process name and command line, that exits `threshold` times
+/// within the window. Each exit is one restart of a loop; the start is already behind it.
+pub struct CrashLoopTracker {
+    threshold: usize,
+    window: Duration,
+    commands: HashMap<(String, String), CommandExits>,
+}
+
+impl CrashLoopTracker {
+    pub fn new(threshold: usize, window_secs: u64) -> Self {
+        Self { threshold, window: Duration::from_secs(window_secs), commands: HashMap::new() }
+    }
+
+    pub fn observe(&mut self, event: &ProcessLifecycle) -> Option<Anomaly> {
+        self.observe_at(event, Instant::now())
+    }
+
+    fn observe_at(&mut self, event: &ProcessLifecycle, now: Instant) -> Option<Anomaly> {
+        if self.threshold == 0 || !matches!(event.kind, ProcessLifecycleKind::Exited) {
+            return None;
+        }
+
+        let key = (event.name.clone(), event.cmdline.clone());
+        if !self.commands.contains_key(&key) && self.commands.len() >= MAX_TRACKED_COMMANDS {
+            self.evict(now);
+        }
+        let window = self.window;
+        let command = self.commands.entry(key).or_insert_with(|| CommandExits {
+            exits: VecDeque::new(),
+            last_exit_code: None,
+            last_seen: now,
+            reported: None,
+        });
+        command.exits.push_back(now);
+        while command.exits.front().is_some_and(|at| now.duration_since(*at) >= window) {
+            command.exits.pop_front();
+        }
+        command.last_exit_code = event.exit_code;
+        command.last_seen = now;
+
+        if command.exits.len() < self.threshold || command.reported.is_some_and(|at| now.duration_since(at) < window) {
+            return None;
+        }
+        command.reported = Some(now);
+        Some(Anomaly {
+            ts: OffsetDateTime::now_utc(),
+            severity: AnomalySeverity::Warning,
+            kind: AnomalyKind::ProcessCrashLoop,
+            message: format!(
+                "{} is crash looping: exited {} times in {}s, last {} ({})",
+                event.name,
+                command.exits.len(),
+                window.as_secs(),
+                exit_reason(command.last_exit_code),
+                event.cmdline
+            ),
+        })
+    }
+
+    // Drops every command with no exit left in the window, or the least recently seen one
+    // when they're all still counting
+    fn evict(&mut self, now: Instant) {
+        let window = self.window;
+        self.commands.retain(|_, command| now.duration_since(command.last_seen) < window);
+        if self.commands.len() < MAX_TRACKED_COMMANDS {
+            return;
+        }
+        if let Some(oldest) = self.commands.iter().min_by_key(|(_, command)| command.last_seen).map(|(key, _)| key.clone()) {
+            self.commands.remove(&oldest);
+        }
+    }
+}
+
+// Exit codes over 128 are signals, see collector::exit_code_from_wait_status
+fn exit_reason(exit_code: Option<i32>) -> String {
+    match exit_code {
+        Some(code) if code > 128 => format!("killed by signal {}", code - 128),
+        Some(code) => format!("exit code {}", code),
+        None => "exit code unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exited(name: &str, cmdline: &str, exit_code: Option<i32>) -> ProcessLifecycle {
+        ProcessLifecycle {
+            ts: OffsetDateTime::now_utc(),
+            pid: 4242,
+            ppid: Some(1),
+            name: name.to_string(),
+            cmdline: cmdline.to_string(),
+            working_dir: None,
+            user: Some("app".to_string()),
+            uid: Some(1000),
+            kind: ProcessLifecycleKind::Exited,
+            exit_code,
+            container_id: None,
+            container_name: None,
+        }
+    }
+
+    #[test]
+    fn test_crash_loop_reported_once_per_window() {
+        let start = Instant::now();
+        let mut tracker = CrashLoopTracker::new(5, 60);
+        let worker = exited("worker", "/opt/app/worker --queue jobs", Some(1));
+        let mut observe = |event: &ProcessLifecycle, at: u64| tracker.observe_at(event, start + Duration::from_secs(at));
+
+        for at in 0..4 {
+            assert!(observe(&worker, at * 10).is_none());
+        }
+        // Same name, other arguments, a different command
+        assert!(observe(&exited("worker", "/opt/app/worker --queue mail", Some(1)), 40).is_none());
+        let anomaly = observe(&exited("worker", "/opt/app/worker --queue jobs", Some(137)), 45).unwrap();
+        assert_eq!(anomaly.kind, AnomalyKind::ProcessCrashLoop);
+        assert_eq!(
+            anomaly.message,
+            "worker is crash looping: exited 5 times in 60s, last killed by signal 9 (/opt/app/worker --queue jobs)"
+        );
+
+        // Still looping, but already reported in this window
+        for at in [50, 60, 70, 80, 90] {
+            assert!(observe(&worker, at).is_none());
+        }
+        assert!(observe(&worker, 105).is_some());
+
+        // Starts aren't counted on their own
+        let started = ProcessLifecycle { kind: ProcessLifecycleKind::Started, ..worker.clone() };
+        assert!(observe(&started, 106).is_none());
+        assert!(CrashLoopTracker::new(0, 60).observe_at(&worker, start).is_none());
+    }
+
+    #[test]
+    fn test_least_recently_seen_command_evicted() {
+        let start = Instant::now();
+        let mut tracker = CrashLoopTracker::new(2, 60);
+        let looping = exited("worker", "worker", None);
+        assert!(tracker.observe_at(&looping, start).is_none());
+        for i in 0..MAX_TRACKED_COMMANDS {
+            let cmdline = format!("cc -c file{}.c", i);
+            tracker.observe_at(&exited("cc", &cmdline, Some(0)), start + Duration::from_secs(1));
+        }
+        assert_eq!(tracker.commands.len(), MAX_TRACKED_COMMANDS);
+        // worker was the first seen, so it was forgotten and this is its first exit again
+        assert!(tracker.observe_at(&looping, start + Duration::from_secs(2)).is_none());
+        assert_eq!(tracker.commands.len(), MAX_TRACKED_COMMANDS);
+        assert!(tracker.observe_at(&looping, start + Duration::from_secs(3)).unwrap().message.contains("exit code unknown"));
+    }
+}
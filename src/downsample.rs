@@ -0,0 +1,282 @@
+// Downsampling of old segments. Once a sealed segment is older than downsample_after_hours, its
+// per-second SystemMetrics are replaced with one record per window, in the place of the window's
+// last sample. Every other record is copied as it was, so time range reads, the process snapshot
+// deltas and the per-minute timeline summary work the same on a downsampled segment.
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::event::{Event, MetricsRollup, SocketMetrics, SystemMetrics, TcpListenDrops};
+use crate::integrity::{self, RecordChain, SegmentFormat, SigningKey};
+use crate::process_delta::{self, Payload};
+use crate::storage::{RecordHeader, SEGMENT_HEADER_LEN};
+
+/// One SystemMetrics standing in for `samples`. Levels (CPU, memory, swap, disk usage, load) are
+/// means, I/O and network rates are peaks so short spikes still show, and static fields come from
+/// the latest sample that carried them. Samples that are already rollups count for what they
+/// were rolled up from.
+pub fn roll_up(samples: &[SystemMetrics]) -> Option<SystemMetrics> {
+    let last = samples.last()?;
+    let weight = |m: &SystemMetrics| m.rollup.map_or(1, |r| r.samples);
+    let total = samples.iter().map(weight).sum::<u32>();
+    let mean = |f: fn(&SystemMetrics) -> f64| {
+        samples.iter().map(|m| f(m) * weight(m) as f64).sum::<f64>() / total as f64
+    };
+    let peak = |f: fn(&SystemMetrics) -> u64| samples.iter().map(f).max().unwrap_or(0);
+    fn latest<T: Clone>(samples: &[SystemMetrics], f: fn(&SystemMetrics) -> &Option<T>) -> Option<T> {
+        samples.iter().rev().find_map(|m| f(m).clone())
+    }
+
+    let per_core_usage = (0..last.per_core_usage.len())
+        .map(|core| {
+            let values: Vec<f32> = samples.iter().filter_map(|m| m.per_core_usage.get(core).copied()).collect();
+            values.iter().sum::<f32>() / values.len() as f32
+        })
+        .collect();
+
+    Some(SystemMetrics {
+        ts: last.ts,
+        kernel_version: latest(samples, |m| &m.kernel_version),
+        cpu_model: latest(samples, |m| &m.cpu_model),
+        cpu_mhz: latest(samples, |m| &m.cpu_mhz),
+        mem_total_bytes: latest(samples, |m| &m.mem_total_bytes),
+        swap_total_bytes: latest(samples, |m| &m.swap_total_bytes),
+        disk_total_bytes: latest(samples, |m| &m.disk_total_bytes),
+        filesystems: latest(samples, |m| &m.filesystems),
+        filesystem_status: latest(samples, |m| &m.filesystem_status),
+        net_interface: latest(samples, |m| &m.net_interface),
+        net_ip_address: latest(samples, |m| &m.net_ip_address),
+        net_gateway: latest(samples, |m| &m.net_gateway),
+        net_dns: latest(samples, |m| &m.net_dns),
+        fans: latest(samples, |m| &m.fans),
+        logged_in_users: latest(samples, |m| &m.logged_in_users),
+        cpu_usage_percent: mean(|m| m.cpu_usage_percent as f64) as f32,
+        per_core_usage,
+        mem_used_bytes: mean(|m| m.mem_used_bytes as f64) as u64,
+        mem_usage_percent: mean(|m| m.mem_usage_percent as f64) as f32,
+        swap_used_bytes: mean(|m| m.swap_used_bytes as f64) as u64,
+        swap_usage_percent: mean(|m| m.swap_usage_percent as f64) as f32,
+        load_avg_1m: mean(|m| m.load_avg_1m as f64) as f32,
+        load_avg_5m: mean(|m| m.load_avg_5m as f64) as f32,
+        load_avg_15m: mean(|m| m.load_avg_15m as f64) as f32,
+        disk_read_bytes_per_sec: peak(|m| m.disk_read_bytes_per_sec),
+        disk_write_bytes_per_sec: peak(|m| m.disk_write_bytes_per_sec),
+        disk_used_bytes: mean(|m| m.disk_used_bytes as f64) as u64,
+        disk_usage_percent: mean(|m| m.disk_usage_percent as f64) as f32,
+        net_recv_bytes_per_sec: peak(|m| m.net_recv_bytes_per_sec),
+        net_send_bytes_per_sec: peak(|m| m.net_send_bytes_per_sec),
+        net_recv_errors_per_sec: peak(|m| m.net_recv_errors_per_sec),
+        net_send_errors_per_sec: peak(|m| m.net_send_errors_per_sec),
+        net_recv_drops_per_sec: peak(|m| m.net_recv_drops_per_sec),
+        net_send_drops_per_sec: peak(|m| m.net_send_drops_per_sec),
+        tcp_connections: mean(|m| m.tcp_connections as f64).round() as u32,
+        tcp_time_wait: mean(|m| m.tcp_time_wait as f64).round() as u32,
+        context_switches_per_sec: peak(|m| m.context_switches_per_sec),
+        // Protocol rates are peaks too, socket counts are as of the end of the window
+        sockets: last.sockets.clone().map(|end| {
+            samples.iter().filter_map(|m| m.sockets.as_ref()).fold(end, |peaks, s| SocketMetrics {
+                tcp_in_segs_per_sec: peaks.tcp_in_segs_per_sec.max(s.tcp_in_segs_per_sec),
+                tcp_out_segs_per_sec: peaks.tcp_out_segs_per_sec.max(s.tcp_out_segs_per_sec),
+                tcp_retrans_per_sec: peaks.tcp_retrans_per_sec.max(s.tcp_retrans_per_sec),
+                udp_in_datagrams_per_sec: peaks.udp_in_datagrams_per_sec.max(s.udp_in_datagrams_per_sec),
+                udp_out_datagrams_per_sec: peaks.udp_out_datagrams_per_sec.max(s.udp_out_datagrams_per_sec),
+                udp_errors_per_sec: peaks.udp_errors_per_sec.max(s.udp_errors_per_sec),
+                ..peaks
+            })
+        }),
+        tcp_listen_drops: last.tcp_listen_drops.map(|end| {
+            samples.iter().filter_map(|m| m.tcp_listen_drops).fold(end, |peaks, d| TcpListenDrops {
+                overflows_per_sec: peaks.overflows_per_sec.max(d.overflows_per_sec),
+                drops_per_sec: peaks.drops_per_sec.max(d.drops_per_sec),
+                syn_drops_per_sec: peaks.syn_drops_per_sec.max(d.syn_drops_per_sec),
+            })
+        }),
+        rollup: Some(MetricsRollup {
+            samples: total,
+            cpu_max_percent: samples
+                .iter()
+                .map(|m| m.rollup.map_or(m.cpu_usage_percent, |r| r.cpu_max_percent))
+                .fold(0.0, f32::max),
+            mem_max_percent: samples
+                .iter()
+                .map(|m| m.rollup.map_or(m.mem_usage_percent, |r| r.mem_max_percent))
+                .fold(0.0, f32::max),
+        }),
+        // Per-device breakdowns, temperatures, frequencies and the memory breakdown are as of the end of the window
+        ..last.clone()
+    })
+}
+
+/// The records of a sealed segment with its per-second SystemMetrics rolled up per
+/// `window_secs`, None if there was nothing to roll up. Every record is verified first: a
+/// segment that fails is left alone, since rewriting it would re-sign whatever was changed.
+pub fn downsample_segment(
+    data: &[u8],
+    segment_id: u64,
+    key: Option<&SigningKey>,
+    window_secs: u64,
+) -> Result<Option<Vec<u8>>> {
+    let format = SegmentFormat::detect(data)?;
+    if format == SegmentFormat::Signed && key.is_none() {
+        anyhow::bail!("signed segments can only be rewritten with the signing_key");
+    }
+
+    let mut chain = RecordChain::new(format, key.cloned(), segment_id);
+    let mut records = Vec::new();
+    let mut end = SEGMENT_HEADER_LEN as usize;
+    for record in integrity::records(data, end, format) {
+        if let Err(reason) = chain.verify(&record) {
+            anyhow::bail!("offset {}: {}", record.offset, reason);
+        }
+        end = record.offset as usize + record.bytes.len() + record.trailer.len();
+        records.push(record);
+    }
+    if end < data.len() {
+        anyhow::bail!("offset {}: truncated part way through a record", end);
+    }
+
+    // The window of each per-second sample, and the samples and last record of each window
+    let mut windows = Vec::with_capacity(records.len());
+    let mut samples: HashMap<i64, (Vec<SystemMetrics>, usize)> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        let window = match process_delta::decode_payload(record.payload) {
+            Ok(Payload::Event(event)) => match *event {
+                Event::SystemMetrics(metrics) if metrics.rollup.is_none() => {
+                    let window = metrics.ts.unix_timestamp().div_euclid(window_secs as i64);
+                    let (window_samples, last) = samples.entry(window).or_default();
+                    window_samples.push(metrics);
+                    *last = i;
+                    Some(window)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        windows.push(window);
+    }
+    if samples.values().all(|(window_samples, _)| window_samples.len() < 2) {
+        return Ok(None);
+    }
+
+    let mut rewritten = format.magic().to_le_bytes().to_vec();
+    let mut chain = RecordChain::new(format, key.cloned(), segment_id);
+    for (i, (record, window)) in records.iter().zip(&windows).enumerate() {
+        let bytes = match window.map(|window| &samples[&window]) {
+            Some((window_samples, last)) if window_samples.len() > 1 => {
+                if i != *last {
+                    continue;
+                }
+                let metrics = roll_up(window_samples).context("Empty window")?;
+                let payload = bincode::serialize(&Event::SystemMetrics(metrics))?;
+                let header = RecordHeader {
+                    timestamp_unix_ns: record.header.timestamp_unix_ns,
+                    payload_len: payload.len() as u32,
+                };
+                let mut bytes = bincode::serialize(&header)?;
+                bytes.extend_from_slice(&payload);
+                Cow::Owned(bytes)
+            }
+            _ => Cow::Borrowed(record.bytes),
+        };
+        let trailer = chain.seal(&bytes);
+        rewritten.extend_from_slice(&bytes);
+        rewritten.extend_from_slice(&trailer);
+    }
+    Ok(Some(rewritten))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, Annotation};
+    use time::OffsetDateTime;
+
+    const BASE: i64 = 1_760_608_800; // On a minute boundary
+
+    fn metrics(secs: i64, cpu: f32, disk_write: u64) -> SystemMetrics {
+        let mut metrics = sample_metrics();
+        metrics.ts = OffsetDateTime::from_unix_timestamp(secs).unwrap();
+        metrics.cpu_usage_percent = cpu;
+        metrics.mem_usage_percent = cpu / 2.0;
+        metrics.disk_write_bytes_per_sec = disk_write;
+        metrics
+    }
+
+    fn segment(events: &[Event], key: Option<&SigningKey>) -> Vec<u8> {
+        let format = if key.is_some() { SegmentFormat::Signed } else { SegmentFormat::Checksummed };
+        let mut chain = RecordChain::new(format, key.cloned(), 3);
+        let mut data = format.magic().to_le_bytes().to_vec();
+        for event in events {
+            let payload = bincode::serialize(event).unwrap();
+            let header = RecordHeader { timestamp_unix_ns: event.timestamp().unix_timestamp_nanos(), payload_len: payload.len() as u32 };
+            let mut bytes = bincode::serialize(&header).unwrap();
+            bytes.extend_from_slice(&payload);
+            let trailer = chain.seal(&bytes);
+            data.extend_from_slice(&bytes);
+            data.extend_from_slice(&trailer);
+        }
+        data
+    }
+
+    fn events_of(data: &[u8], key: Option<&SigningKey>) -> Vec<Event> {
+        let format = SegmentFormat::of_segment(data).unwrap();
+        let mut chain = RecordChain::new(format, key.cloned(), 3);
+        integrity::records(data, SEGMENT_HEADER_LEN as usize, format)
+            .map(|record| {
+                chain.verify(&record).unwrap();
+                Event::decode(record.payload).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_roll_up_weights_earlier_rollups() {
+        let minute = roll_up(&[metrics(BASE, 10.0, 100), metrics(BASE + 1, 30.0, 5000), metrics(BASE + 2, 20.0, 200)]).unwrap();
+        assert_eq!(minute.ts, OffsetDateTime::from_unix_timestamp(BASE + 2).unwrap());
+        assert_eq!(minute.cpu_usage_percent, 20.0);
+        assert_eq!(minute.disk_write_bytes_per_sec, 5000);
+        assert_eq!(minute.rollup, Some(MetricsRollup { samples: 3, cpu_max_percent: 30.0, mem_max_percent: 15.0 }));
+
+        let two = roll_up(&[minute, metrics(BASE + 60, 60.0, 0)]).unwrap();
+        assert_eq!(two.cpu_usage_percent, 30.0);
+        assert_eq!(two.rollup, Some(MetricsRollup { samples: 4, cpu_max_percent: 60.0, mem_max_percent: 30.0 }));
+        assert!(roll_up(&[]).is_none());
+    }
+
+    #[test]
+    fn test_downsample_segment_keeps_other_events_in_place() {
+        let key = SigningKey::new("test secret");
+        let annotation = Event::Annotation(Annotation {
+            ts: OffsetDateTime::from_unix_timestamp(BASE + 1).unwrap(),
+            author: "admin".to_string(),
+            text: "deploy".to_string(),
+            tags: Vec::new(),
+        });
+        let mut events: Vec<Event> = (0..3).map(|i| Event::SystemMetrics(metrics(BASE + i, 10.0 * (i + 1) as f32, 0))).collect();
+        events.insert(2, annotation);
+        events.push(Event::SystemMetrics(metrics(BASE + 60, 5.0, 0)));
+
+        let data = segment(&events, Some(&key));
+        let rewritten = downsample_segment(&data, 3, Some(&key), 60).unwrap().unwrap();
+        assert!(rewritten.len() < data.len());
+
+        let events = events_of(&rewritten, Some(&key));
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], Event::Annotation(a) if a.text == "deploy"));
+        let Event::SystemMetrics(minute) = &events[1] else {
+            panic!("expected the first minute's rollup");
+        };
+        assert_eq!(minute.cpu_usage_percent, 20.0);
+        assert_eq!(minute.rollup.map(|r| r.samples), Some(3));
+        // A window with one sample is copied as it was
+        assert!(matches!(&events[2], Event::SystemMetrics(m) if m.rollup.is_none()));
+
+        // Already downsampled
+        assert!(downsample_segment(&rewritten, 3, Some(&key), 60).unwrap().is_none());
+        // Needs the key to re-sign, and isn't rewritten if anything was changed
+        assert!(downsample_segment(&data, 3, None, 60).is_err());
+        let mut tampered = data.clone();
+        tampered[40] ^= 1;
+        assert!(downsample_segment(&tampered, 3, Some(&key), 60).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use time::OffsetDateTime;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +10,12 @@ pub enum Event {
     SecurityEvent(SecurityEvent),
     Anomaly(Anomaly),
     FileSystemEvent(FileSystemEvent),
+    Annotation(Annotation),
+    DiskHealth(DiskHealth),
+    ServiceStateChange(ServiceStateChange),
+    SystemEvent(SystemEvent),
+    OperatorAction(OperatorAction),
+    NetworkTraffic(NetworkTraffic),
 }
 
 // System-wide metrics collected each interval
@@ -60,7 +67,93 @@ pub struct SystemMetrics {
     pub tcp_time_wait: u32,
     pub context_switches_per_sec: u64,
     pub temps: TemperatureReadings,
-    pub gpu: GpuInfo,
+    pub gpu: GpuInfo, // First GPU only, every GPU is in `gpus`
+
+    // Memory breakdown from /proc/meminfo. Kept last so records from before it was added
+    // still decode, with these as None (see Event::decode)
+    pub mem_available_bytes: Option<u64>,
+    pub mem_cached_bytes: Option<u64>,
+    pub mem_buffers_bytes: Option<u64>,
+    pub mem_dirty_bytes: Option<u64>,
+    pub mem_writeback_bytes: Option<u64>,
+    pub hugepages_total: Option<u64>, // Pages, not bytes
+    pub hugepages_free: Option<u64>,
+    pub gpus: Option<Vec<GpuDevice>>, // Every GPU, None in older recordings
+    pub filesystem_status: Option<Vec<FilesystemStatus>>, // Sent with `filesystems`, None in older recordings
+    pub sockets: Option<SocketMetrics>, // None in older recordings
+    pub kernel_limits: Option<KernelLimits>, // None in older recordings
+    pub interface_packets: Option<Vec<InterfacePacketRates>>, // Same interfaces as per_interface, None in older recordings
+    pub pressure: Option<PressureMetrics>, // None without PSI and in older recordings
+    pub rollup: Option<MetricsRollup>, // Set when this record stands in for a window of samples
+    pub disk_health: Option<Vec<DiskHealthStatus>>, // Latest SMART check per disk, None before the first
+    pub raid_arrays: Option<Vec<RaidArray>>, // Software RAID from /proc/mdstat, sent with the semi-static fields
+    pub tcp_listen_drops: Option<TcpListenDrops>, // Sent with sockets, None in older recordings
+    pub per_interface: Vec<PerInterfaceMetrics>, // Empty in older recordings
+    pub per_core_freq_mhz: Option<Vec<u32>>, // None without cpufreq sysfs and in older recordings
+}
+
+// What a downsampled SystemMetrics record was rolled up from. Its other fields are means of
+// levels and peaks of rates over the window, which ends at its timestamp.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct MetricsRollup {
+    pub samples: u32,
+    pub cpu_max_percent: f32,
+    pub mem_max_percent: f32,
+}
+
+// TCP/UDP protocol rates from /proc/net/snmp and socket counts from /proc/net/sockstat
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SocketMetrics {
+    pub tcp_in_segs_per_sec: u64,
+    pub tcp_out_segs_per_sec: u64,
+    pub tcp_retrans_per_sec: u64,
+    pub udp_in_datagrams_per_sec: u64,
+    pub udp_out_datagrams_per_sec: u64,
+    pub udp_errors_per_sec: u64,
+    pub sockets_used: u64,
+    pub tcp_orphaned: u64,
+    pub socket_mem_bytes: u64,
+}
+
+// Connections turned away at TCP listeners, kept apart from SocketMetrics so older records still decode
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct TcpListenDrops {
+    pub overflows_per_sec: u64, // Accept queue full
+    pub drops_per_sec: u64,     // Everything dropped at a listener, overflows included
+    pub syn_drops_per_sec: u64, // SYN backlog full
+}
+
+// System-wide usage of kernel tables that applications fail strangely when they run out of
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct KernelLimits {
+    pub file_handles_allocated: u64, // From /proc/sys/fs/file-nr
+    pub file_handles_max: u64,
+    pub tasks: u64, // Processes and threads, each of which holds a pid
+    pub pid_max: u64,
+    pub entropy_available: Option<u64>, // Bits in the random pool, None if unreadable
+}
+
+// Share of the last 10 seconds tasks were stalled waiting for each resource, from /proc/pressure.
+// "some" is at least one task waiting, "full" is every non-idle task waiting at once.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PressureMetrics {
+    pub cpu_some_avg10: f32,
+    pub cpu_full_avg10: Option<f32>, // Only reported for CPU since Linux 5.13
+    pub memory_some_avg10: f32,
+    pub memory_full_avg10: f32,
+    pub io_some_avg10: f32,
+    pub io_full_avg10: f32,
+}
+
+impl PressureMetrics {
+    /// (resource, some, full) for each resource
+    pub fn resources(&self) -> [(&'static str, f32, Option<f32>); 3] {
+        [
+            ("CPU", self.cpu_some_avg10, self.cpu_full_avg10),
+            ("Memory", self.memory_some_avg10, Some(self.memory_full_avg10)),
+            ("IO", self.io_some_avg10, Some(self.io_full_avg10)),
+        ]
+    }
 }
 
 // Logged in user info
@@ -89,6 +182,30 @@ pub struct GpuInfo {
     pub power_watts: Option<f32>,
 }
 
+// One GPU, from nvidia-smi, rocm-smi or the amdgpu driver in /sys
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct GpuDevice {
+    pub index: u32,
+    pub name: String,
+    pub gpu_freq_mhz: Option<u32>,
+    pub mem_freq_mhz: Option<u32>,
+    pub temp_celsius: Option<f32>,
+    pub power_watts: Option<f32>,
+    pub vram_used_bytes: Option<u64>,
+    pub vram_total_bytes: Option<u64>,
+}
+
+impl From<&GpuDevice> for GpuInfo {
+    fn from(device: &GpuDevice) -> Self {
+        Self {
+            gpu_freq_mhz: device.gpu_freq_mhz,
+            mem_freq_mhz: device.mem_freq_mhz,
+            gpu_temp_celsius: device.temp_celsius,
+            power_watts: device.power_watts,
+        }
+    }
+}
+
 // Fan speed readings
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FanReading {
@@ -105,6 +222,51 @@ pub struct PerDiskMetrics {
     pub temp_celsius: Option<f32>,
 }
 
+// Per-interface network metrics (rates)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerInterfaceMetrics {
+    pub interface_name: String,
+    pub recv_bytes_per_sec: u64,
+    pub send_bytes_per_sec: u64,
+    pub recv_errors_per_sec: u64,
+    pub send_errors_per_sec: u64,
+    pub recv_drops_per_sec: u64,
+    pub send_drops_per_sec: u64,
+}
+
+// Packet rates for one interface, kept apart from PerInterfaceMetrics so older records still decode
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InterfacePacketRates {
+    pub interface_name: String,
+    pub recv_packets_per_sec: u64,
+    pub send_packets_per_sec: u64,
+}
+
+// Latest SMART check for one disk, kept apart from PerDiskMetrics so older records still decode
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiskHealthStatus {
+    pub device_name: String,
+    pub health_passed: Option<bool>,
+    pub problems: Vec<String>, // Empty for a healthy disk
+    pub reallocated_sectors: Option<u64>,
+    pub pending_sectors: Option<u64>,
+    pub media_errors: Option<u64>,
+    pub percentage_used: Option<u8>,
+}
+
+// One md software RAID array
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RaidArray {
+    pub name: String,  // e.g. "md0"
+    pub level: String, // e.g. "raid1", empty for an inactive array
+    pub active: bool,
+    pub total_devices: Option<u32>, // None for levels without redundancy (raid0, linear)
+    pub active_devices: Option<u32>,
+    pub failed_devices: Vec<String>,
+    pub sync_action: Option<String>, // "recovery", "resync", "reshape", "check" or "repair"
+    pub sync_progress_percent: Option<f32>,
+}
+
 // Filesystem usage stats (like df output)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FilesystemInfo {
@@ -115,6 +277,16 @@ pub struct FilesystemInfo {
     pub available_bytes: u64,
 }
 
+// Inodes and threshold state of a mount in `filesystems`. A separate list, since
+// FilesystemInfo can't grow without breaking records already written
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FilesystemStatus {
+    pub mount_point: String,
+    pub inodes_total: u64, // 0 where the filesystem doesn't report inodes
+    pub inodes_free: u64,
+    pub critical: bool, // Bytes or inodes were over their anomaly threshold
+}
+
 // Process lifecycle events (start/exit)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessLifecycle {
@@ -128,6 +300,8 @@ pub struct ProcessLifecycle {
     pub uid: Option<u32>,            // User ID
     pub kind: ProcessLifecycleKind,
     pub exit_code: Option<i32>,      // Exit code (only for Exited kind)
+    pub container_id: Option<String>,   // None when not in a container
+    pub container_name: Option<String>, // Resolved from docker/podman state when readable
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,13 +312,193 @@ pub enum ProcessLifecycleKind {
     Zombie,
 }
 
-// Snapshot of interesting processes (top CPU/memory consumers)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Snapshot of interesting processes (top CPU/memory consumers). Recorded as deltas from a
+// full snapshot when the processes are the same, see process_delta.rs
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(remote = "Self")]
 pub struct ProcessSnapshot {
     pub ts: OffsetDateTime,
     pub processes: Vec<ProcessInfo>,
     pub total_processes: u32,
     pub running_processes: u32,
+    pub top_cpu_pids: Vec<u32>, // Order of the top processes by CPU, highest first
+    pub top_mem_pids: Vec<u32>, // Order of the top processes by memory, highest first
+    pub per_user: Option<Vec<UserUsage>>, // Every process summed per owner, None in older recordings
+    pub per_container: Option<Vec<ContainerUsage>>, // Processes in containers summed per container
+}
+
+// JSON has every field of a process on it. Segments hold each process as it was first recorded,
+// with the fields added to ProcessInfo since in a list after the snapshot: the processes are in a
+// Vec, so ProcessInfo can't have fields appended the way SystemMetrics does.
+impl Serialize for ProcessSnapshot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            ProcessSnapshot::serialize(self, serializer)
+        } else {
+            ProcessSnapshotRecord::from(self).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ProcessSnapshot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            ProcessSnapshot::deserialize(deserializer)
+        } else {
+            ProcessSnapshotRecord::deserialize(deserializer).map(Self::from)
+        }
+    }
+}
+
+// ProcessSnapshot as laid out in a segment
+#[derive(Serialize, Deserialize)]
+struct ProcessSnapshotRecord {
+    ts: OffsetDateTime,
+    processes: Vec<ProcessRecord>,
+    total_processes: u32,
+    running_processes: u32,
+    top_cpu_pids: Vec<u32>,
+    top_mem_pids: Vec<u32>,
+    per_user: Option<Vec<UserUsage>>,
+    per_container: Option<Vec<ContainerUsage>>,
+    details: Option<Vec<ProcessDetails>>, // By pid, None in older recordings
+}
+
+// The fields of ProcessInfo segments were first written with
+#[derive(Serialize, Deserialize)]
+struct ProcessRecord {
+    pid: u32,
+    name: String,
+    cmdline: String,
+    state: String,
+    user: String,
+    cpu_percent: f32,
+    mem_bytes: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+    num_fds: u32,
+    num_threads: u32,
+}
+
+// The fields of ProcessInfo added since
+#[derive(Serialize, Deserialize)]
+struct ProcessDetails {
+    pid: u32,
+    read_bytes_per_sec: u64,
+    write_bytes_per_sec: u64,
+    connection_count: u32,
+    connections: Vec<ProcessConnection>,
+    container_id: Option<String>,
+    container_name: Option<String>,
+}
+
+impl From<&ProcessSnapshot> for ProcessSnapshotRecord {
+    fn from(snapshot: &ProcessSnapshot) -> Self {
+        let processes = snapshot
+            .processes
+            .iter()
+            .map(|p| ProcessRecord {
+                pid: p.pid,
+                name: p.name.clone(),
+                cmdline: p.cmdline.clone(),
+                state: p.state.clone(),
+                user: p.user.clone(),
+                cpu_percent: p.cpu_percent,
+                mem_bytes: p.mem_bytes,
+                read_bytes: p.read_bytes,
+                write_bytes: p.write_bytes,
+                num_fds: p.num_fds,
+                num_threads: p.num_threads,
+            })
+            .collect();
+        let details = snapshot
+            .processes
+            .iter()
+            .map(|p| ProcessDetails {
+                pid: p.pid,
+                read_bytes_per_sec: p.read_bytes_per_sec,
+                write_bytes_per_sec: p.write_bytes_per_sec,
+                connection_count: p.connection_count,
+                connections: p.connections.clone(),
+                container_id: p.container_id.clone(),
+                container_name: p.container_name.clone(),
+            })
+            .collect();
+        Self {
+            ts: snapshot.ts,
+            processes,
+            total_processes: snapshot.total_processes,
+            running_processes: snapshot.running_processes,
+            top_cpu_pids: snapshot.top_cpu_pids.clone(),
+            top_mem_pids: snapshot.top_mem_pids.clone(),
+            per_user: snapshot.per_user.clone(),
+            per_container: snapshot.per_container.clone(),
+            details: Some(details),
+        }
+    }
+}
+
+impl From<ProcessSnapshotRecord> for ProcessSnapshot {
+    fn from(record: ProcessSnapshotRecord) -> Self {
+        let mut details: HashMap<u32, ProcessDetails> =
+            record.details.unwrap_or_default().into_iter().map(|d| (d.pid, d)).collect();
+        let processes = record
+            .processes
+            .into_iter()
+            .map(|p| {
+                let detail = details.remove(&p.pid);
+                ProcessInfo {
+                    pid: p.pid,
+                    name: p.name,
+                    cmdline: p.cmdline,
+                    state: p.state,
+                    user: p.user,
+                    cpu_percent: p.cpu_percent,
+                    mem_bytes: p.mem_bytes,
+                    read_bytes: p.read_bytes,
+                    write_bytes: p.write_bytes,
+                    read_bytes_per_sec: detail.as_ref().map_or(0, |d| d.read_bytes_per_sec),
+                    write_bytes_per_sec: detail.as_ref().map_or(0, |d| d.write_bytes_per_sec),
+                    num_fds: p.num_fds,
+                    num_threads: p.num_threads,
+                    connection_count: detail.as_ref().map_or(0, |d| d.connection_count),
+                    connections: detail.as_ref().map(|d| d.connections.clone()).unwrap_or_default(),
+                    container_id: detail.as_ref().and_then(|d| d.container_id.clone()),
+                    container_name: detail.and_then(|d| d.container_name),
+                }
+            })
+            .collect();
+        Self {
+            ts: record.ts,
+            processes,
+            total_processes: record.total_processes,
+            running_processes: record.running_processes,
+            top_cpu_pids: record.top_cpu_pids,
+            top_mem_pids: record.top_mem_pids,
+            per_user: record.per_user,
+            per_container: record.per_container,
+        }
+    }
+}
+
+// Resource use of all processes owned by one user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserUsage {
+    pub user: String,
+    pub uid: u32,
+    pub cpu_percent: f32,
+    pub mem_bytes: u64,
+    pub process_count: u32,
+}
+
+// Resource use of all processes in one container
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContainerUsage {
+    pub container_id: String,
+    pub container_name: Option<String>,
+    pub cpu_percent: f32,
+    pub mem_bytes: u64,
+    pub process_count: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -158,8 +512,23 @@ pub struct ProcessInfo {
     pub mem_bytes: u64,
     pub read_bytes: u64,
     pub write_bytes: u64,
+    pub read_bytes_per_sec: u64,
+    pub write_bytes_per_sec: u64,
     pub num_fds: u32,
     pub num_threads: u32,
+    pub connection_count: u32,
+    pub connections: Vec<ProcessConnection>, // First few sockets only
+    pub container_id: Option<String>,
+    pub container_name: Option<String>,
+}
+
+// Inet socket held by a process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessConnection {
+    pub protocol: String,
+    pub local_port: u16,
+    pub remote_addr: String,
+    pub state: String,
 }
 
 // Security events
@@ -197,6 +566,13 @@ pub enum SecurityEventKind {
     PackageRemoved,
     // Sensitive file access
     SensitiveFileAccessed,
+    // Network configuration
+    DnsConfigurationChanged,
+    DefaultGatewayChanged,
+    // Recording protection
+    SegmentProtectionChanged,
+    // A read-only API token was used, recorded at most hourly per token
+    ApiTokenUsed,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -214,7 +590,7 @@ pub enum AnomalySeverity {
     Critical,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AnomalyKind {
     CpuSpike,
     MemorySpike,
@@ -230,6 +606,23 @@ pub enum AnomalyKind {
     BruteForceAttempt,
     PortScanActivity,
     UnauthorizedAccess,
+    CpuThrottling,
+    ProcessDiskWrite, // Names the process behind a disk write spike
+    IntegrityViolation, // Synthetic, stands in for a segment record that failed verification
+    DiskHealth,
+    TornWriteRecovered, // Incomplete records cut from the end of a segment on startup
+    ServiceFailed,
+    UncleanShutdown, // The previous run ended without writing a Shutdown marker
+    InodeExhaustion,
+    TcpRetransmits,
+    CollectorFailing, // A data source that was working has started returning errors
+    CollectorRecovered,
+    FileHandleExhaustion, // System-wide, FdExhaustion is one process near its own limit
+    PressureStall, // Every task stalled on CPU, memory or IO for a sustained share of the time
+    RemoteSyslogDropped, // Events lost because the remote syslog spool was full
+    RaidDegraded,
+    RaidRebuild, // A RAID rebuild started or finished
+    ProcessCrashLoop, // The same command exiting over and over
 }
 
 // File system events (file created/modified/deleted)
@@ -239,6 +632,23 @@ pub struct FileSystemEvent {
     pub kind: FileSystemEventKind,
     pub path: String,
     pub size: Option<u64>,  // File size if available
+    // SHA-256 (hex) before and after the change, for files matching file_watch.content_patterns
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+    pub diff: Option<String>,  // Unified diff, text files under content_max_bytes only
+    // Modified events coalesced into this one, or for Suppressed the number of events dropped
+    pub count: u32,
+    // Size, permissions and owner before and after, before only for files matching content_patterns
+    pub old_attributes: Option<FileAttributes>,
+    pub new_attributes: Option<FileAttributes>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FileAttributes {
+    pub size: u64,
+    pub mode: u32, // Permission bits, including setuid/setgid/sticky
+    pub uid: u32,
+    pub gid: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -247,9 +657,134 @@ pub enum FileSystemEventKind {
     Modified,
     Deleted,
     Renamed { from: String, to: String },
+    Suppressed,  // Rate limit summary, path is the watched directory
+}
+
+// Note left by an operator at a point in the recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub ts: OffsetDateTime,
+    pub author: String,
+    pub text: String,
+    pub tags: Vec<String>,
+}
+
+// A systemd service entering or leaving the failed state, or restarted automatically
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceStateChange {
+    pub ts: OffsetDateTime,
+    pub unit: String,
+    pub old_state: String,  // systemd ActiveState, e.g. "active", "failed"
+    pub new_state: String,
+    pub n_restarts: Option<u32>,  // None where systemd doesn't report NRestarts
+}
+
+// Recorder lifecycle markers, written at startup and on a graceful stop
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SystemEvent {
+    pub ts: OffsetDateTime,
+    pub kind: SystemEventKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SystemEventKind {
+    // The machine booted after the previous run's last record
+    Rebooted {
+        boot_time: OffsetDateTime,
+        last_event: OffsetDateTime, // When the previous run last wrote a record
+        clean_shutdown: bool,
+    },
+    // Black Box was stopped gracefully, so the next start knows the run ended cleanly
+    Shutdown,
+    // config.toml was re-read while running. Dotted keys that changed, e.g. "anomalies.cpu_spike.threshold"
+    ConfigReloaded {
+        applied: Vec<String>,
+        requires_restart: Vec<String>,
+    },
+}
+
+impl SystemEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SystemEventKind::Rebooted { .. } => "Rebooted",
+            SystemEventKind::Shutdown => "Shutdown",
+            SystemEventKind::ConfigReloaded { .. } => "ConfigReloaded",
+        }
+    }
+}
+
+// Something done to the machine from the web UI, recorded whether or not it worked
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OperatorAction {
+    pub ts: OffsetDateTime,
+    pub user: String,           // Authenticated account that asked for it
+    pub action: String,         // e.g. "SIGTERM"
+    pub pid: u32,
+    pub name: Option<String>,    // None when the process couldn't be read
+    pub cmdline: Option<String>,
+    pub success: bool,
+    pub outcome: String,        // What happened, or why it was refused
+}
+
+// Where TCP traffic went over the last traffic accounting interval, recorded with a network spike
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NetworkTraffic {
+    pub ts: OffsetDateTime,
+    pub interval_secs: u64,
+    pub bytes_counted: bool,  // false when only connections per remote could be counted
+    pub top_remotes: Vec<RemoteTraffic>,  // Busiest first
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteTraffic {
+    pub remote: String,  // IP address, or subnet such as "203.0.113.0/24"
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub connections: u32,
 }
 
+// SMART readings for one physical disk, collected every smart.interval_minutes.
+// ATA counters are raw values; None when the drive doesn't report the attribute.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskHealth {
+    pub ts: OffsetDateTime,
+    pub device: String,  // e.g. "sda", "nvme0n1"
+    pub model: Option<String>,
+    pub health_passed: Option<bool>,  // Overall self-assessment
+    pub failing_attributes: Vec<String>,  // ATA attributes at or below their threshold now
+    pub reallocated_sectors: Option<u64>,
+    pub pending_sectors: Option<u64>,
+    pub offline_uncorrectable: Option<u64>,
+    pub crc_errors: Option<u64>,
+    pub percentage_used: Option<u8>,  // SSD wear, NVMe Percentage Used or derived from ATA wear attributes
+    pub available_spare: Option<u8>,  // NVMe only, percent
+    pub available_spare_threshold: Option<u8>,
+    pub media_errors: Option<u64>,  // NVMe media and data integrity errors
+    pub critical_warning: Option<u8>,  // NVMe critical warning bitmask
+    pub power_on_hours: Option<u64>,
+    pub temperature_celsius: Option<f32>,
+}
+
+// Bytes of the fields appended to the end of SystemMetrics since segments were first written,
+// the most appended to any event (ProcessSnapshot has had 19, FileSystemEvent 9, ProcessLifecycle
+// 2). In bincode a missing Option is one zero byte, an empty Vec eight, so padding an old record
+// with zeros decodes them as None, empty and 0. Whatever padding is left over goes unread.
+const APPENDED_FIELD_BYTES: usize = 26;
+
 impl Event {
+    /// Decode a record payload, including SystemMetrics, ProcessSnapshots and FileSystemEvents written before fields were appended
+    pub fn decode(payload: &[u8]) -> bincode::Result<Event> {
+        match bincode::deserialize(payload) {
+            Err(e) if matches!(&*e, bincode::ErrorKind::Io(io) if io.kind() == std::io::ErrorKind::UnexpectedEof) => {
+                let mut padded = payload.to_vec();
+                padded.resize(payload.len() + APPENDED_FIELD_BYTES, 0);
+                bincode::deserialize(&padded)
+            }
+            result => result,
+        }
+    }
+
     /// Get the timestamp from any event variant
     pub fn timestamp(&self) -> OffsetDateTime {
         match self {
@@ -259,6 +794,12 @@ impl Event {
             Event::SecurityEvent(e) => e.ts,
             Event::Anomaly(e) => e.ts,
             Event::FileSystemEvent(e) => e.ts,
+            Event::Annotation(e) => e.ts,
+            Event::DiskHealth(e) => e.ts,
+            Event::ServiceStateChange(e) => e.ts,
+            Event::SystemEvent(e) => e.ts,
+            Event::OperatorAction(e) => e.ts,
+            Event::NetworkTraffic(e) => e.ts,
         }
     }
 }
@@ -281,6 +822,8 @@ pub struct Metadata {
     pub fans: Option<Vec<FanReading>>,
     pub temps: Option<TemperatureReadings>,
     pub gpu: Option<GpuInfo>,
+    pub gpus: Option<Vec<GpuDevice>>,
+    pub filesystem_status: Option<Vec<FilesystemStatus>>,
     pub logged_in_users: Option<Vec<LoggedInUserInfo>>,
     pub processes: Option<Vec<ProcessInfo>>,
     pub total_processes: Option<u32>,
@@ -306,6 +849,8 @@ impl Metadata {
             fans: m.fans.clone(),
             temps: Some(m.temps.clone()),
             gpu: Some(m.gpu.clone()),
+            gpus: m.gpus.clone(),
+            filesystem_status: m.filesystem_status.clone(),
             logged_in_users: m.logged_in_users.clone(),
             processes: None,
             total_processes: None,
@@ -316,3 +861,189 @@ impl Metadata {
 
 }
 
+// Quiet SystemMetrics sample for tests
+#[cfg(test)]
+pub fn sample_metrics() -> SystemMetrics {
+    SystemMetrics {
+        ts: OffsetDateTime::now_utc(),
+        kernel_version: Some("6.0.0-test on x86_64".to_string()),
+        cpu_model: Some("Test CPU".to_string()),
+        cpu_mhz: Some(3000),
+        mem_total_bytes: Some(0),
+        swap_total_bytes: Some(0),
+        disk_total_bytes: Some(0),
+        filesystems: Some(vec![]),
+        net_interface: None,
+        net_ip_address: None,
+        net_gateway: None,
+        net_dns: None,
+        fans: Some(vec![]),
+        logged_in_users: Some(vec![]),
+        system_uptime_seconds: 0,
+        cpu_usage_percent: 50.0,
+        per_core_usage: vec![],
+        mem_used_bytes: 0,
+        mem_usage_percent: 0.0,
+        swap_used_bytes: 0,
+        swap_usage_percent: 0.0,
+        load_avg_1m: 0.0,
+        load_avg_5m: 0.0,
+        load_avg_15m: 0.0,
+        disk_read_bytes_per_sec: 0,
+        disk_write_bytes_per_sec: 0,
+        disk_used_bytes: 0,
+        disk_usage_percent: 0.0,
+        per_disk_metrics: vec![],
+        net_recv_bytes_per_sec: 0,
+        net_send_bytes_per_sec: 0,
+        net_recv_errors_per_sec: 0,
+        net_send_errors_per_sec: 0,
+        net_recv_drops_per_sec: 0,
+        net_send_drops_per_sec: 0,
+        tcp_connections: 0,
+        tcp_time_wait: 0,
+        context_switches_per_sec: 0,
+        temps: TemperatureReadings {
+            cpu_temp_celsius: None,
+            per_core_temps: vec![],
+            gpu_temp_celsius: None,
+            motherboard_temp_celsius: None,
+        },
+        gpu: GpuInfo::default(),
+        mem_available_bytes: None,
+        mem_cached_bytes: None,
+        mem_buffers_bytes: None,
+        mem_dirty_bytes: None,
+        mem_writeback_bytes: None,
+        hugepages_total: None,
+        hugepages_free: None,
+        gpus: None,
+        filesystem_status: None,
+        sockets: None,
+        kernel_limits: None,
+        interface_packets: None,
+        pressure: None,
+        rollup: None,
+        disk_health: None,
+        raid_arrays: None,
+        tcp_listen_drops: None,
+        per_interface: vec![],
+        per_core_freq_mhz: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrity::{self, SegmentFormat};
+    use crate::storage::SEGMENT_HEADER_LEN;
+
+    // Recorded by the first release, before any field was appended to an event
+    const BASELINE_SEGMENT: &[u8] = include_bytes!("testdata/baseline_segment.dat");
+
+    fn baseline_events() -> Vec<bincode::Result<Event>> {
+        integrity::records(BASELINE_SEGMENT, SEGMENT_HEADER_LEN as usize, SegmentFormat::Plain)
+            .map(|record| Event::decode(record.payload))
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_baseline_records() {
+        let events = baseline_events();
+        assert_eq!(events.len(), 7);
+
+        for (i, cpu) in [(0, 12.5), (6, 91.0)] {
+            match &events[i] {
+                Ok(Event::SystemMetrics(m)) => {
+                    assert_eq!(m.cpu_usage_percent, cpu);
+                    assert_eq!(m.per_core_usage, [cpu, cpu / 2.0]);
+                    assert_eq!((m.mem_used_bytes, m.tcp_connections, m.context_switches_per_sec), (2 << 30, 12, 5000));
+                    assert_eq!(m.per_disk_metrics[0].device_name, "sda");
+                    assert_eq!(m.temps.per_core_temps, [Some(44.0), Some(46.0)]);
+                    assert_eq!(m.filesystems.as_ref().unwrap()[0].mount_point, "/");
+                    assert!(m.per_core_freq_mhz.is_none() && m.per_interface.is_empty());
+                    assert!(m.mem_available_bytes.is_none() && m.tcp_listen_drops.is_none());
+                }
+                other => panic!("expected SystemMetrics, got {:?}", other),
+            }
+        }
+
+        match &events[1] {
+            Ok(Event::ProcessSnapshot(s)) => {
+                assert_eq!((s.total_processes, s.running_processes), (150, 2));
+                let names: Vec<&str> = s.processes.iter().map(|p| p.name.as_str()).collect();
+                assert_eq!(names, ["nginx", "postgres"]);
+                let postgres = &s.processes[1];
+                assert_eq!((postgres.pid, postgres.mem_bytes, postgres.num_fds, postgres.num_threads), (202, 64 << 20, 16, 4));
+                assert_eq!((postgres.read_bytes_per_sec, postgres.connection_count), (0, 0));
+                assert_eq!(postgres.container_id, None);
+                assert!(s.top_cpu_pids.is_empty() && s.per_user.is_none() && s.per_container.is_none());
+            }
+            other => panic!("expected a ProcessSnapshot, got {:?}", other),
+        }
+        match &events[2] {
+            Ok(Event::ProcessLifecycle(p)) => {
+                assert_eq!((p.pid, p.name.as_str(), p.uid), (303, "cron", Some(0)));
+                assert_eq!(p.container_id, None);
+            }
+            other => panic!("expected a ProcessLifecycle, got {:?}", other),
+        }
+        assert!(matches!(&events[3], Ok(Event::SecurityEvent(e)) if e.source_ip.as_deref() == Some("203.0.113.5")));
+        assert!(matches!(&events[4], Ok(Event::Anomaly(a)) if a.message == "CPU spike: 91.0%"));
+        match &events[5] {
+            Ok(Event::FileSystemEvent(f)) => {
+                assert_eq!((f.path.as_str(), f.size), ("/etc/passwd", Some(1800)));
+                assert!(f.old_hash.is_none() && f.new_attributes.is_none());
+            }
+            other => panic!("expected a FileSystemEvent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_process_details_follow_snapshot() {
+        let process = ProcessInfo {
+            pid: 42,
+            name: "worker".to_string(),
+            cmdline: "worker --queue jobs".to_string(),
+            state: "R".to_string(),
+            user: "app".to_string(),
+            cpu_percent: 75.0,
+            mem_bytes: 1 << 30,
+            read_bytes: 10,
+            write_bytes: 20,
+            read_bytes_per_sec: 1000,
+            write_bytes_per_sec: 2000,
+            num_fds: 8,
+            num_threads: 2,
+            connection_count: 1,
+            connections: vec![ProcessConnection {
+                protocol: "tcp".to_string(),
+                local_port: 8080,
+                remote_addr: "192.0.2.1:443".to_string(),
+                state: "ESTABLISHED".to_string(),
+            }],
+            container_id: Some("abc123".to_string()),
+            container_name: Some("jobs".to_string()),
+        };
+        let snapshot = Event::ProcessSnapshot(ProcessSnapshot {
+            ts: OffsetDateTime::from_unix_timestamp(1_767_600_000).unwrap(),
+            processes: vec![process],
+            total_processes: 1,
+            running_processes: 1,
+            top_cpu_pids: vec![42],
+            top_mem_pids: vec![42],
+            per_user: None,
+            per_container: None,
+        });
+
+        let decoded = Event::decode(&bincode::serialize(&snapshot).unwrap()).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", snapshot));
+
+        // JSON keeps every field on its process
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["ProcessSnapshot"]["processes"][0]["container_name"], "jobs");
+        assert_eq!(json["ProcessSnapshot"]["processes"][0]["write_bytes_per_sec"], 2000);
+        let from_json: Event = serde_json::from_value(json).unwrap();
+        assert_eq!(format!("{:?}", from_json), format!("{:?}", snapshot));
+    }
+}
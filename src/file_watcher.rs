@@ -1,33 +1,55 @@
 use anyhow::Result;
 use crossbeam_channel::Sender;
 use inotify::{Inotify, WatchMask};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use time::OffsetDateTime;
 
-use crate::event::{Event, FileSystemEvent, FileSystemEventKind, SecurityEvent, SecurityEventKind};
+use crate::config::{FileWatchConfig, WatchEntry, WatchKind};
+use crate::event::{Event, FileAttributes, FileSystemEvent, FileSystemEventKind, SecurityEvent, SecurityEventKind};
 use crate::collector::is_sensitive_file_path;
 
+/// A running file watcher, stopped when a config reload changes what is watched
+pub struct FileWatcherHandle {
+    stop: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl FileWatcherHandle {
+    /// Stop watching and wait for the thread to finish, at most one poll interval
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
+
 /// Spawn a file watcher in a background thread
-pub fn spawn_file_watcher(watch_dirs: Vec<String>, event_sender: Sender<Event>) -> Result<()> {
-    thread::spawn(move || {
-        if let Err(e) = run_file_watcher(watch_dirs, event_sender) {
+pub fn spawn_file_watcher(config: FileWatchConfig, event_sender: Sender<Event>) -> Result<FileWatcherHandle> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let thread = thread::spawn(move || {
+        if let Err(e) = run_file_watcher(config, event_sender, &stopped) {
             eprintln!("File watcher error: {}", e);
         }
     });
 
-    Ok(())
+    Ok(FileWatcherHandle { stop, thread })
 }
 
-fn run_file_watcher(watch_dirs: Vec<String>, event_sender: Sender<Event>) -> Result<()> {
-    let mut watcher = FileWatcher::new(event_sender)?;
+fn run_file_watcher(config: FileWatchConfig, event_sender: Sender<Event>, stop: &AtomicBool) -> Result<()> {
+    let mut watcher = FileWatcher::new(event_sender, &config)?;
 
     // Add all configured directories
-    for dir in &watch_dirs {
-        if let Err(e) = watcher.watch_directory(dir) {
-            eprintln!("Failed to watch directory {}: {}", dir, e);
+    for entry in config.profiles() {
+        if let Err(e) = watcher.watch_tree(Path::new(&entry.path)) {
+            eprintln!("Failed to watch directory {}: {}", entry.path, e);
         }
     }
 
@@ -39,7 +61,7 @@ fn run_file_watcher(watch_dirs: Vec<String>, event_sender: Sender<Event>) -> Res
     println!("File watcher started, monitoring {} directories", watcher.watch_descriptors.len());
 
     // Main loop: process events every 100ms
-    loop {
+    while !stop.load(Ordering::Relaxed) {
         match watcher.process_events() {
             Ok(count) => {
                 if count > 0 {
@@ -55,37 +77,484 @@ fn run_file_watcher(watch_dirs: Vec<String>, event_sender: Sender<Event>) -> Res
         // Small sleep to avoid busy-waiting
         thread::sleep(Duration::from_millis(100));
     }
+    Ok(())
+}
+
+/// Repeated Modified events for a path within this window are sent as one event
+const COALESCE_WINDOW: Duration = Duration::from_secs(2);
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Patterns without '/' match the file name, others the full path
+fn pattern_matches(pattern: &glob::Pattern, path: &Path) -> bool {
+    if pattern.as_str().contains('/') {
+        pattern.matches_path(path)
+    } else {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        pattern.matches(file_name)
+    }
+}
+
+/// gitignore-style ignore rules: a leading '!' re-includes and the last matching rule wins
+struct IgnoreList {
+    rules: Vec<(glob::Pattern, bool)>, // (pattern, negated)
+}
+
+impl IgnoreList {
+    fn new(patterns: &[String]) -> Self {
+        let rules = patterns
+            .iter()
+            .filter_map(|p| {
+                let (glob, negated) = match p.strip_prefix('!') {
+                    Some(rest) => (rest, true),
+                    None => (p.as_str(), false),
+                };
+                // Watches aren't recursive, so a directory-only rule just matches the entry itself
+                glob::Pattern::new(glob.trim_end_matches('/')).ok().map(|g| (g, negated))
+            })
+            .collect();
+        Self { rules }
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern_matches(pattern, path))
+            .is_some_and(|(_, negated)| !negated)
+    }
+}
+
+/// A watch profile as applied to incoming events
+struct WatchRule {
+    path: PathBuf,
+    recursive: bool,
+    kinds: Vec<WatchKind>,
+    ignore: IgnoreList,
+    include: Vec<glob::Pattern>, // Empty records everything
+}
+
+impl WatchRule {
+    fn new(entry: &WatchEntry) -> Self {
+        Self {
+            path: PathBuf::from(&entry.path),
+            recursive: entry.recursive,
+            kinds: entry.kinds.clone(),
+            ignore: IgnoreList::new(&entry.ignore),
+            // Patterns are checked when the config is loaded
+            include: entry.include.iter().filter_map(|p| glob::Pattern::new(p).ok()).collect(),
+        }
+    }
+
+    // Directories are let through so new ones below a recursive glob are still watched
+    fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        self.ignore.is_ignored(path)
+            || (!is_dir && !self.include.is_empty() && !self.include.iter().any(|pattern| pattern_matches(pattern, path)))
+    }
+
+    fn covers(&self, dir: &Path) -> bool {
+        dir == self.path || (self.recursive && dir.starts_with(&self.path))
+    }
+}
+
+/// Per-directory event budget over a fixed one minute window
+struct RateLimiter {
+    max_per_window: u32, // 0 is unlimited
+    windows: HashMap<PathBuf, RateWindow>,
+}
+
+struct RateWindow {
+    started: Instant,
+    emitted: u32,
+    suppressed: u32,
+}
+
+impl RateLimiter {
+    fn new(max_per_window: u32) -> Self {
+        Self { max_per_window, windows: HashMap::new() }
+    }
+
+    fn allow(&mut self, dir: &Path, now: Instant) -> bool {
+        if self.max_per_window == 0 {
+            return true;
+        }
+        let window = self.windows.entry(dir.to_path_buf()).or_insert(RateWindow {
+            started: now,
+            emitted: 0,
+            suppressed: 0,
+        });
+        if window.emitted < self.max_per_window {
+            window.emitted += 1;
+            true
+        } else {
+            window.suppressed += 1;
+            false
+        }
+    }
+
+    /// Close finished windows, returning (dir, suppressed count) for those that dropped events
+    fn expire(&mut self, now: Instant) -> Vec<(PathBuf, u32)> {
+        let mut summaries = Vec::new();
+        self.windows.retain(|dir, window| {
+            if now.duration_since(window.started) < RATE_WINDOW {
+                return true;
+            }
+            if window.suppressed > 0 {
+                summaries.push((dir.clone(), window.suppressed));
+            }
+            false
+        });
+        summaries
+    }
+}
+
+/// Modified events for one path waiting out the coalescing window
+struct PendingModify {
+    ts: OffsetDateTime,
+    first_seen: Instant,
+    dir: PathBuf,
+    count: u32,
+}
+
+/// MOVED_FROM waiting for the MOVED_TO with the same cookie
+struct PendingMove {
+    from: PathBuf,
+    dir: PathBuf,
+    ts: OffsetDateTime,
+    ignored: bool,
+    kinds: Vec<WatchKind>,
+}
+
+/// Last seen content of a tracked file
+struct FileSnapshot {
+    hash: String,
+    text: Option<String>, // Only for UTF-8 files under the size limit, used for diffs
+    attributes: FileAttributes,
+}
+
+/// Hash/diff details attached to a FileSystemEvent
+#[derive(Debug, Default, PartialEq)]
+struct ContentChange {
+    old_hash: Option<String>,
+    new_hash: Option<String>,
+    diff: Option<String>,
+    old_attributes: Option<FileAttributes>,
+    new_attributes: Option<FileAttributes>,
+}
+
+fn file_attributes(metadata: &std::fs::Metadata) -> FileAttributes {
+    use std::os::unix::fs::MetadataExt;
+    FileAttributes { size: metadata.len(), mode: metadata.mode() & 0o7777, uid: metadata.uid(), gid: metadata.gid() }
+}
+
+/// Keeps content hashes of watched files matching the configured patterns so changes can be diffed
+struct ContentTracker {
+    patterns: Vec<glob::Pattern>,
+    max_bytes: u64,
+    diff_max_lines: usize,
+    snapshots: HashMap<PathBuf, FileSnapshot>,
+}
+
+impl ContentTracker {
+    fn new(config: &FileWatchConfig) -> Self {
+        Self {
+            // Patterns are checked when the config is loaded
+            patterns: config.content_globs().iter().filter_map(|p| glob::Pattern::new(p).ok()).collect(),
+            max_bytes: config.content_max_bytes,
+            diff_max_lines: config.diff_max_lines,
+            snapshots: HashMap::new(),
+        }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.patterns.iter().any(|pattern| pattern_matches(pattern, path))
+    }
+
+    /// Record the current content without reporting a change (baseline at startup)
+    fn snapshot(&mut self, path: &Path) {
+        if self.matches(path) && let Some(snapshot) = self.read(path) {
+            self.snapshots.insert(path.to_path_buf(), snapshot);
+        }
+    }
+
+    /// Compare the file with its last snapshot after it was created, modified or renamed over
+    fn update(&mut self, path: &Path) -> ContentChange {
+        if !self.matches(path) {
+            // Nothing earlier to compare with, but the attributes are a stat away
+            let new_attributes = std::fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| file_attributes(&m));
+            return ContentChange { new_attributes, ..Default::default() };
+        }
+        let Some(new) = self.read(path) else {
+            return self.remove(path);
+        };
+        let old = self.snapshots.remove(path);
+
+        let diff = match (&old, &new) {
+            (Some(old), new) if old.hash != new.hash && self.diff_max_lines > 0 => {
+                match (&old.text, &new.text) {
+                    (Some(old_text), Some(new_text)) => {
+                        Some(unified_diff(path, old_text, new_text, self.diff_max_lines))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let change = ContentChange {
+            old_attributes: old.as_ref().map(|o| o.attributes),
+            new_attributes: Some(new.attributes),
+            old_hash: old.map(|o| o.hash),
+            new_hash: Some(new.hash.clone()),
+            diff,
+        };
+        self.snapshots.insert(path.to_path_buf(), new);
+        change
+    }
+
+    /// Forget a deleted (or moved away) file
+    fn remove(&mut self, path: &Path) -> ContentChange {
+        let old = self.snapshots.remove(path);
+        ContentChange {
+            old_attributes: old.as_ref().map(|s| s.attributes),
+            old_hash: old.map(|s| s.hash),
+            ..Default::default()
+        }
+    }
+
+    fn read(&self, path: &Path) -> Option<FileSnapshot> {
+        let metadata = std::fs::metadata(path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        let attributes = file_attributes(&metadata);
+
+        if metadata.len() > self.max_bytes {
+            // Hash oversized files in chunks, no diff
+            let mut file = File::open(path).ok()?;
+            let mut hasher = Sha256::new();
+            let mut buffer = [0u8; 64 * 1024];
+            loop {
+                let n = file.read(&mut buffer).ok()?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..n]);
+            }
+            return Some(FileSnapshot { hash: format!("{:x}", hasher.finalize()), text: None, attributes });
+        }
+
+        let content = std::fs::read(path).ok()?;
+        let hash = format!("{:x}", Sha256::digest(&content));
+        // NUL bytes mean binary even when the rest happens to be valid UTF-8
+        let text = if content.contains(&0) { None } else { String::from_utf8(content).ok() };
+        Some(FileSnapshot { hash, text, attributes })
+    }
+}
+
+fn fs_event(
+    ts: OffsetDateTime,
+    kind: FileSystemEventKind,
+    path: String,
+    size: Option<u64>,
+    change: ContentChange,
+) -> FileSystemEvent {
+    FileSystemEvent {
+        ts,
+        kind,
+        path,
+        size,
+        old_hash: change.old_hash,
+        new_hash: change.new_hash,
+        diff: change.diff,
+        count: 1,
+        old_attributes: change.old_attributes,
+        new_attributes: change.new_attributes,
+    }
+}
+
+/// Unified diff of two versions of a file, cut off after max_lines
+fn unified_diff(path: &Path, old: &str, new: &str, max_lines: usize) -> String {
+    let name = path.to_string_lossy();
+    let diff = similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&name, &name)
+        .to_string();
+
+    let total = diff.lines().count();
+    if total <= max_lines {
+        return diff;
+    }
+    let mut truncated: String = diff.lines().take(max_lines).flat_map(|l| [l, "\n"]).collect();
+    truncated.push_str(&format!("... {} more lines\n", total - max_lines));
+    truncated
 }
 
 pub struct FileWatcher {
     inotify: Inotify,
     watch_descriptors: HashMap<i32, PathBuf>,
     event_sender: Sender<Event>,
+    content: ContentTracker,
+    ignore: IgnoreList,
+    rules: Vec<WatchRule>,
+    rate_limiter: RateLimiter,
+    pending_modifies: HashMap<PathBuf, PendingModify>,
 }
 
 impl FileWatcher {
-    pub fn new(event_sender: Sender<Event>) -> Result<Self> {
+    pub fn new(event_sender: Sender<Event>, config: &FileWatchConfig) -> Result<Self> {
         let inotify = Inotify::init()?;
 
         Ok(FileWatcher {
             inotify,
             watch_descriptors: HashMap::new(),
             event_sender,
+            content: ContentTracker::new(config),
+            ignore: IgnoreList::new(&config.ignore_patterns),
+            rules: config.profiles().iter().map(WatchRule::new).collect(),
+            rate_limiter: RateLimiter::new(config.max_events_per_minute_per_dir),
+            pending_modifies: HashMap::new(),
         })
     }
 
+    /// The most specific profile covering a directory. Directories watched directly
+    /// through watch_directory may have none, and then record everything.
+    fn rule_for(&self, dir: &Path) -> Option<&WatchRule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.covers(dir))
+            .max_by_key(|rule| rule.path.components().count())
+    }
+
+    /// Whether a path is ignored and which kinds of change are recorded for it
+    fn event_rules(&self, dir: &Path, path: &Path, is_dir: bool) -> (bool, Vec<WatchKind>) {
+        match self.rule_for(dir) {
+            Some(rule) => (
+                self.ignore.is_ignored(path) || rule.excludes(path, is_dir),
+                rule.kinds.clone(),
+            ),
+            None => (self.ignore.is_ignored(path), WatchKind::ALL.to_vec()),
+        }
+    }
+
+    /// Send a FileSystemEvent unless the directory is over its rate limit. Returns the number sent.
+    fn send(&mut self, dir: &Path, fs_event: FileSystemEvent) -> usize {
+        if !self.rate_limiter.allow(dir, Instant::now()) {
+            return 0;
+        }
+        let _ = self.event_sender.send(Event::FileSystemEvent(fs_event));
+        1
+    }
+
+    fn send_sensitive_file_event(&self, ts: OffsetDateTime, path: &str, action: &str) {
+        if is_sensitive_file_path(path) {
+            let sec_event = SecurityEvent {
+                ts,
+                kind: SecurityEventKind::SensitiveFileAccessed,
+                user: "unknown".to_string(),
+                source_ip: None,
+                message: format!("Sensitive file {}: {}", action, path),
+            };
+            let _ = self.event_sender.send(Event::SecurityEvent(sec_event));
+        }
+    }
+
+    /// Send a coalesced Modified event, hashing the content once for the whole burst
+    fn send_modify(&mut self, path: PathBuf, pending: PendingModify) -> usize {
+        let path_str = path.to_string_lossy().to_string();
+        let size = std::fs::metadata(&path).ok().map(|m| m.len());
+        let change = self.content.update(&path);
+        self.send_sensitive_file_event(pending.ts, &path_str, "modified");
+        let fs_event = FileSystemEvent {
+            count: pending.count,
+            ..fs_event(pending.ts, FileSystemEventKind::Modified, path_str, size, change)
+        };
+        self.send(&pending.dir, fs_event)
+    }
+
+    /// Send the pending Modified event for a path now, so it stays ahead of a later event for the same path
+    fn flush_modify(&mut self, path: &Path) -> usize {
+        match self.pending_modifies.remove(path) {
+            Some(pending) => self.send_modify(path.to_path_buf(), pending),
+            None => 0,
+        }
+    }
+
+    /// Send Modified events whose coalescing window has passed and rate limit summaries
+    fn flush(&mut self, now: Instant, window: Duration) -> usize {
+        let mut event_count = 0;
+
+        let due: Vec<PathBuf> = self
+            .pending_modifies
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.first_seen) >= window)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in due {
+            event_count += self.flush_modify(&path);
+        }
+
+        for (dir, suppressed) in self.rate_limiter.expire(now) {
+            let path = dir.to_string_lossy().to_string();
+            let fs_event = FileSystemEvent {
+                count: suppressed,
+                ..fs_event(OffsetDateTime::now_utc(), FileSystemEventKind::Suppressed, path, None, ContentChange::default())
+            };
+            let _ = self.event_sender.send(Event::FileSystemEvent(fs_event));
+            event_count += 1;
+        }
+
+        event_count
+    }
+
+    /// Watch a directory, and the directories below it when its profile is recursive.
+    /// Stops at the first directory that can't be watched, e.g. once the inotify watch limit is hit.
+    pub fn watch_tree(&mut self, root: &Path) -> Result<()> {
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            self.watch_directory(&dir)?;
+            if !self.rule_for(&dir).is_some_and(|rule| rule.recursive) {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                // file_type() doesn't follow symlinks, so linked directories aren't descended into
+                let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+                let path = entry.path();
+                if is_dir && !self.event_rules(&dir, &path, true).0 {
+                    pending.push(path);
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Add a directory to watch (non-recursive)
     pub fn watch_directory(&mut self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
 
         let mask = WatchMask::CREATE
             | WatchMask::MODIFY
+            | WatchMask::ATTRIB
             | WatchMask::DELETE
             | WatchMask::MOVED_FROM
             | WatchMask::MOVED_TO;
 
         let wd = self.inotify.watches().add(path, mask)?;
         self.watch_descriptors.insert(wd.get_watch_descriptor_id(), path.to_path_buf());
+
+        // Baseline hashes so the first change to an existing file has something to diff against.
+        // Not needed where only creations or deletions are recorded.
+        let diffs_recorded = self
+            .rule_for(path)
+            .is_none_or(|rule| rule.kinds.contains(&WatchKind::Modified) || rule.kinds.contains(&WatchKind::Renamed));
+        if diffs_recorded && let Ok(entries) = std::fs::read_dir(path) {
+            for entry in entries.flatten() {
+                self.content.snapshot(&entry.path());
+            }
+        }
         Ok(())
     }
 
@@ -94,121 +563,392 @@ impl FileWatcher {
         let mut buffer = [0u8; 4096];
         let mut event_count = 0;
 
-        // Read events (non-blocking)
-        let events = self.inotify.read_events(&mut buffer)?;
+        // Read events (non-blocking). The inotify fd is non-blocking, so no events is WouldBlock.
+        let events: Vec<_> = match self.inotify.read_events(&mut buffer) {
+            Ok(events) => events
+                .map(|event| (event.wd.get_watch_descriptor_id(), event.mask, event.cookie, event.name.map(|n| n.to_os_string())))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
 
-        let mut pending_moves: HashMap<u32, (PathBuf, OffsetDateTime)> = HashMap::new();
+        let mut pending_moves: HashMap<u32, PendingMove> = HashMap::new();
+
+        for (wd_id, mask, cookie, name) in events {
+            // The watched directory was deleted or unmounted
+            if mask.contains(inotify::EventMask::IGNORED) {
+                self.watch_descriptors.remove(&wd_id);
+                continue;
+            }
 
-        for event in events {
-            let wd_id = event.wd.get_watch_descriptor_id();
             let base_path = self.watch_descriptors.get(&wd_id).cloned()
                 .unwrap_or_else(|| PathBuf::from("<unknown>"));
 
-            let full_path = if let Some(name) = event.name {
+            let full_path = if let Some(name) = name {
                 base_path.join(name)
             } else {
-                base_path
+                base_path.clone()
             };
 
+            // Renames from an ignored name (e.g. "config.tmp" -> "config.conf") still need their
+            // MOVED_FROM to pair with the MOVED_TO
+            let (ignored, kinds) = self.event_rules(&base_path, &full_path, mask.contains(inotify::EventMask::ISDIR));
+            if ignored && !mask.contains(inotify::EventMask::MOVED_FROM) {
+                continue;
+            }
+
             let path_str = full_path.to_string_lossy().to_string();
             let ts = OffsetDateTime::now_utc();
 
             // Get file size if possible
             let size = std::fs::metadata(&full_path).ok().map(|m| m.len());
 
-            if event.mask.contains(inotify::EventMask::CREATE) {
-                let fs_event = FileSystemEvent {
-                    ts,
-                    kind: FileSystemEventKind::Created,
-                    path: path_str.clone(),
-                    size,
-                };
-                let _ = self.event_sender.send(Event::FileSystemEvent(fs_event));
-                event_count += 1;
+            if mask.contains(inotify::EventMask::CREATE) && kinds.contains(&WatchKind::Created) {
+                event_count += self.flush_modify(&full_path);
+                let change = self.content.update(&full_path);
+                event_count += self.send(&base_path, fs_event(ts, FileSystemEventKind::Created, path_str.clone(), size, change));
 
                 // Check for sensitive file creation
-                if is_sensitive_file_path(&path_str) {
-                    let sec_event = SecurityEvent {
-                        ts,
-                        kind: SecurityEventKind::SensitiveFileAccessed,
-                        user: "unknown".to_string(),
-                        source_ip: None,
-                        message: format!("Sensitive file created: {}", path_str),
-                    };
-                    let _ = self.event_sender.send(Event::SecurityEvent(sec_event));
-                }
+                self.send_sensitive_file_event(ts, &path_str, "created");
             }
 
-            if event.mask.contains(inotify::EventMask::MODIFY) {
-                let fs_event = FileSystemEvent {
-                    ts,
-                    kind: FileSystemEventKind::Modified,
-                    path: path_str.clone(),
-                    size,
-                };
-                let _ = self.event_sender.send(Event::FileSystemEvent(fs_event));
-                event_count += 1;
-
-                // Check for sensitive file modification
-                if is_sensitive_file_path(&path_str) {
-                    let sec_event = SecurityEvent {
+            // chmod, chown and touch are Modified too, the attributes and hash say what changed.
+            // Deleting a file also changes its link count, which is left to the Deleted event.
+            let attrib = mask.contains(inotify::EventMask::ATTRIB) && !mask.contains(inotify::EventMask::ISDIR) && size.is_some();
+            if (mask.contains(inotify::EventMask::MODIFY) || attrib) && kinds.contains(&WatchKind::Modified) {
+                // Coalesced and sent once the window passes, see flush()
+                self.pending_modifies
+                    .entry(full_path.clone())
+                    .or_insert_with(|| PendingModify {
                         ts,
-                        kind: SecurityEventKind::SensitiveFileAccessed,
-                        user: "unknown".to_string(),
-                        source_ip: None,
-                        message: format!("Sensitive file modified: {}", path_str),
-                    };
-                    let _ = self.event_sender.send(Event::SecurityEvent(sec_event));
-                }
+                        first_seen: Instant::now(),
+                        dir: base_path.clone(),
+                        count: 0,
+                    })
+                    .count += 1;
             }
 
-            if event.mask.contains(inotify::EventMask::DELETE) {
-                let fs_event = FileSystemEvent {
-                    ts,
-                    kind: FileSystemEventKind::Deleted,
-                    path: path_str.clone(),
-                    size: None,
-                };
-                let _ = self.event_sender.send(Event::FileSystemEvent(fs_event));
-                event_count += 1;
+            if mask.contains(inotify::EventMask::DELETE) && kinds.contains(&WatchKind::Deleted) {
+                event_count += self.flush_modify(&full_path);
+                let change = self.content.remove(&full_path);
+                event_count += self.send(&base_path, fs_event(ts, FileSystemEventKind::Deleted, path_str.clone(), None, change));
             }
 
             // Handle renames (MOVED_FROM + MOVED_TO with same cookie)
-            if event.mask.contains(inotify::EventMask::MOVED_FROM) {
-                let cookie = event.cookie;
-                pending_moves.insert(cookie, (full_path.clone(), ts));
+            if mask.contains(inotify::EventMask::MOVED_FROM) {
+                event_count += self.flush_modify(&full_path);
+                pending_moves.insert(cookie, PendingMove {
+                    from: full_path.clone(),
+                    dir: base_path.clone(),
+                    ts,
+                    ignored,
+                    kinds: kinds.clone(),
+                });
             }
 
-            if event.mask.contains(inotify::EventMask::MOVED_TO) {
-                let cookie = event.cookie;
-                if let Some((from_path, _)) = pending_moves.remove(&cookie) {
-                    let fs_event = FileSystemEvent {
-                        ts,
-                        kind: FileSystemEventKind::Renamed {
-                            from: from_path.to_string_lossy().to_string(),
-                            to: path_str.clone(),
-                        },
-                        path: path_str.clone(),
-                        size,
-                    };
-                    let _ = self.event_sender.send(Event::FileSystemEvent(fs_event));
-                    event_count += 1;
-                }
+            if mask.contains(inotify::EventMask::MOVED_TO)
+                && let Some(moved) = pending_moves.remove(&cookie)
+                && kinds.contains(&WatchKind::Renamed)
+            {
+                let from_path = moved.from;
+                event_count += self.flush_modify(&full_path);
+                // Editors often save by writing a temp file and renaming it over the original,
+                // so diff against what was previously at the destination
+                self.content.remove(&from_path);
+                let change = self.content.update(&full_path);
+                let kind = FileSystemEventKind::Renamed {
+                    from: from_path.to_string_lossy().to_string(),
+                    to: path_str.clone(),
+                };
+                event_count += self.send(&base_path, fs_event(ts, kind, path_str.clone(), size, change));
+            }
+
+            // New directories inside a recursive profile are watched as they appear
+            if mask.contains(inotify::EventMask::ISDIR)
+                && (mask.contains(inotify::EventMask::CREATE) || mask.contains(inotify::EventMask::MOVED_TO))
+                && self.rule_for(&full_path).is_some_and(|rule| rule.recursive)
+                && let Err(e) = self.watch_tree(&full_path)
+            {
+                eprintln!("Failed to watch directory {}: {}", full_path.display(), e);
             }
         }
 
         // Handle orphaned MOVED_FROM events (file moved out of watched directory)
-        for (from_path, ts) in pending_moves.values() {
-            let fs_event = FileSystemEvent {
-                ts: *ts,
-                kind: FileSystemEventKind::Deleted,
-                path: from_path.to_string_lossy().to_string(),
-                size: None,
-            };
-            let _ = self.event_sender.send(Event::FileSystemEvent(fs_event));
-            event_count += 1;
+        for moved in pending_moves.into_values().filter(|m| !m.ignored && m.kinds.contains(&WatchKind::Deleted)) {
+            let change = self.content.remove(&moved.from);
+            let path = moved.from.to_string_lossy().to_string();
+            event_count += self.send(&moved.dir, fs_event(moved.ts, FileSystemEventKind::Deleted, path, None, change));
         }
 
+        event_count += self.flush(Instant::now(), COALESCE_WINDOW);
+
         Ok(event_count)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(diff_max_lines: usize) -> ContentTracker {
+        let config = FileWatchConfig {
+            content_max_bytes: 64,
+            diff_max_lines,
+            ..FileWatchConfig::default()
+        };
+        ContentTracker::new(&config)
+    }
+
+    #[test]
+    fn test_content_change_has_hashes_and_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.conf");
+        std::fs::write(&path, "port = 80\nhost = a\n").unwrap();
+
+        let mut tracker = tracker(50);
+        tracker.snapshot(&path);
+        let old_hash = tracker.snapshots[&path].hash.clone();
+        assert_eq!(old_hash.len(), 64);
+
+        std::fs::write(&path, "port = 8080\nhost = a\n").unwrap();
+        let change = tracker.update(&path);
+        assert_eq!(change.old_hash, Some(old_hash));
+        assert!(change.new_hash.is_some() && change.new_hash != change.old_hash);
+        let diff = change.diff.unwrap();
+        assert!(diff.contains("-port = 80\n"));
+        assert!(diff.contains("+port = 8080\n"));
+
+        // Unchanged content: hashes only
+        let change = tracker.update(&path);
+        assert_eq!(change.old_hash, change.new_hash);
+        assert!(change.diff.is_none());
+
+        // Only the permissions changed
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        tracker.update(&path);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o4755)).unwrap();
+        let change = tracker.update(&path);
+        assert_eq!(change.old_hash, change.new_hash);
+        let (old, new) = (change.old_attributes.unwrap(), change.new_attributes.unwrap());
+        assert_eq!((old.mode, new.mode), (0o644, 0o4755));
+        assert_eq!((new.size, new.uid), (old.size, old.uid));
+
+        let change = tracker.remove(&path);
+        assert!(change.old_hash.is_some() && change.new_hash.is_none());
+    }
+
+    #[test]
+    fn test_binary_oversized_and_unmatched_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = tracker(50);
+
+        let binary = dir.path().join("blob.json");
+        std::fs::write(&binary, b"\x00\x01").unwrap();
+        tracker.snapshot(&binary);
+        std::fs::write(&binary, b"\x00\x02").unwrap();
+        let change = tracker.update(&binary);
+        assert!(change.old_hash.is_some() && change.new_hash.is_some());
+        assert!(change.diff.is_none());
+
+        let large = dir.path().join("large.yaml");
+        std::fs::write(&large, "a\n".repeat(40)).unwrap();
+        tracker.snapshot(&large);
+        assert!(tracker.snapshots[&large].text.is_none());
+        std::fs::write(&large, "b\n".repeat(40)).unwrap();
+        let change = tracker.update(&large);
+        assert!(change.new_hash.is_some() && change.diff.is_none());
+
+        let other = dir.path().join("notes.txt");
+        std::fs::write(&other, "hello").unwrap();
+        let change = tracker.update(&other);
+        assert!(change.old_hash.is_none() && change.new_hash.is_none() && change.old_attributes.is_none());
+        assert_eq!(change.new_attributes.map(|a| a.size), Some(5));
+    }
+
+    #[test]
+    fn test_diff_is_truncated() {
+        let old: String = (0..20).map(|i| format!("line {}\n", i)).collect();
+        let new: String = (0..20).map(|i| format!("changed {}\n", i)).collect();
+
+        let diff = unified_diff(Path::new("/etc/x.conf"), &old, &new, 10);
+        assert_eq!(diff.lines().count(), 11);
+        assert!(diff.ends_with("more lines\n"));
+    }
+
+    #[test]
+    fn test_ignore_list_gitignore_style() {
+        let ignore = IgnoreList::new(&[
+            "*.tmp".to_string(),
+            "/var/lib/app/cache/*".to_string(),
+            "!keep.tmp".to_string(),
+            "logs/".to_string(),
+        ]);
+
+        assert!(ignore.is_ignored(Path::new("/etc/foo.tmp")));
+        assert!(!ignore.is_ignored(Path::new("/etc/keep.tmp")));
+        assert!(ignore.is_ignored(Path::new("/var/lib/app/cache/abc")));
+        assert!(!ignore.is_ignored(Path::new("/var/lib/app/data.db")));
+        assert!(ignore.is_ignored(Path::new("/var/lib/app/logs")));
+    }
+
+    #[test]
+    fn test_rate_limiter_summarises_suppressed_events() {
+        let mut limiter = RateLimiter::new(2);
+        let start = Instant::now();
+        let busy = Path::new("/var/lib");
+        let quiet = Path::new("/etc");
+
+        assert!(limiter.allow(busy, start));
+        assert!(limiter.allow(busy, start));
+        assert!(!limiter.allow(busy, start));
+        assert!(!limiter.allow(busy, start));
+        assert!(limiter.allow(quiet, start));
+
+        assert!(limiter.expire(start + Duration::from_secs(30)).is_empty());
+        let summaries = limiter.expire(start + RATE_WINDOW);
+        assert_eq!(summaries, vec![(PathBuf::from("/var/lib"), 2)]);
+
+        // A new window starts with a fresh budget
+        assert!(limiter.allow(busy, start + RATE_WINDOW));
+        assert!(RateLimiter::new(0).allow(busy, start));
+    }
+
+    #[test]
+    fn test_watcher_coalesces_and_ignores() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let config = FileWatchConfig {
+            enabled: true,
+            watch_dirs: vec![],
+            ..FileWatchConfig::default()
+        };
+        let mut watcher = FileWatcher::new(tx, &config).unwrap();
+        watcher.watch_directory(dir.path()).unwrap();
+
+        let path = dir.path().join("app.conf");
+        std::fs::write(&path, "a = 1\n").unwrap();
+        std::fs::write(dir.path().join("scratch.tmp"), "x").unwrap();
+        watcher.process_events().unwrap();
+        // Second burst of writes to the same file before the window ends
+        std::fs::write(&path, "a = 2\n").unwrap();
+        watcher.process_events().unwrap();
+        watcher.flush(Instant::now(), Duration::ZERO);
+
+        let events: Vec<FileSystemEvent> = rx
+            .try_iter()
+            .filter_map(|e| match e {
+                Event::FileSystemEvent(f) => Some(f),
+                _ => None,
+            })
+            .collect();
+
+        assert!(events.iter().all(|e| !e.path.ends_with(".tmp")));
+        let created: Vec<_> = events.iter().filter(|e| matches!(e.kind, FileSystemEventKind::Created)).collect();
+        assert_eq!(created.len(), 1);
+        let modified: Vec<_> = events.iter().filter(|e| matches!(e.kind, FileSystemEventKind::Modified)).collect();
+        assert_eq!(modified.len(), 1);
+        assert!(modified[0].count >= 2);
+        assert!(modified[0].diff.as_deref().unwrap().contains("+a = 2"));
+    }
+
+    #[test]
+    fn test_watch_profiles_apply_kinds_ignores_and_recursion() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir(root.join("cache")).unwrap();
+        std::fs::write(root.join("cache/old.txt"), "x").unwrap();
+
+        let entry = |path: &Path, kinds: Vec<WatchKind>, ignore: &[&str]| WatchEntry {
+            path: path.to_string_lossy().to_string(),
+            recursive: true,
+            kinds,
+            ignore: ignore.iter().map(|p| p.to_string()).collect(),
+            include: Vec::new(),
+        };
+        let config = FileWatchConfig {
+            enabled: true,
+            watch: vec![
+                entry(root, vec![WatchKind::Created, WatchKind::Modified], &[]),
+                entry(&root.join("cache"), WatchKind::ALL.to_vec(), &["*.bin"]),
+            ],
+            ..FileWatchConfig::default()
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut watcher = FileWatcher::new(tx, &config).unwrap();
+        watcher.watch_tree(root).unwrap();
+        assert_eq!(watcher.watch_descriptors.len(), 2);
+
+        // A new subdirectory is picked up, deletions under the root profile are not recorded
+        std::fs::create_dir(root.join("sub")).unwrap();
+        watcher.process_events().unwrap();
+        std::fs::write(root.join("sub/new.txt"), "x").unwrap();
+        watcher.process_events().unwrap();
+        std::fs::remove_file(root.join("sub/new.txt")).unwrap();
+        std::fs::write(root.join("cache/blob.bin"), "x").unwrap();
+        std::fs::remove_file(root.join("cache/old.txt")).unwrap();
+        watcher.process_events().unwrap();
+        watcher.flush(Instant::now(), Duration::ZERO);
+
+        let mut events: Vec<(String, String)> = rx
+            .try_iter()
+            .filter_map(|e| match e {
+                Event::FileSystemEvent(f) => {
+                    let relative = Path::new(&f.path).strip_prefix(root).unwrap().to_string_lossy().to_string();
+                    Some((format!("{:?}", f.kind), relative))
+                }
+                _ => None,
+            })
+            .filter(|(kind, _)| kind != "Modified")
+            .collect();
+        events.sort();
+        assert_eq!(
+            events,
+            vec![
+                ("Created".to_string(), "sub".to_string()),
+                ("Created".to_string(), "sub/new.txt".to_string()),
+                ("Deleted".to_string(), "cache/old.txt".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_glob_path_records_matching_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let config = FileWatchConfig {
+            enabled: true,
+            watch: vec![WatchEntry {
+                path: format!("{}/**/*.conf", root.display()),
+                recursive: false,
+                kinds: WatchKind::ALL.to_vec(),
+                ignore: Vec::new(),
+                include: Vec::new(),
+            }],
+            ..FileWatchConfig::default()
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut watcher = FileWatcher::new(tx, &config).unwrap();
+        watcher.watch_tree(root).unwrap();
+
+        // The new directory doesn't match but is still watched
+        std::fs::create_dir(root.join("nginx")).unwrap();
+        watcher.process_events().unwrap();
+        std::fs::write(root.join("nginx/site.conf"), "listen 80;").unwrap();
+        std::fs::write(root.join("nginx/access.log"), "GET /").unwrap();
+        std::fs::write(root.join("app.conf"), "a = 1").unwrap();
+        watcher.process_events().unwrap();
+
+        let mut created: Vec<String> = rx
+            .try_iter()
+            .filter_map(|e| match e {
+                Event::FileSystemEvent(f) if matches!(f.kind, FileSystemEventKind::Created) => {
+                    Some(Path::new(&f.path).strip_prefix(root).unwrap().to_string_lossy().to_string())
+                }
+                _ => None,
+            })
+            .collect();
+        created.sort();
+        assert_eq!(created, vec!["app.conf", "nginx", "nginx/site.conf"]);
+    }
+}
@@ -1,11 +1,14 @@
 use anyhow::{Context, Result};
 use std::{
-    fs::{self, File},
-    io::{Read, Seek, SeekFrom},
+    fs,
     path::{Path, PathBuf},
 };
 
-use crate::storage::{find_segment_files, BlockIndex, RecordHeader, SegmentIndex, BLOCK_SIZE, MAGIC};
+use crate::integrity::{self, SegmentFormat};
+use crate::storage::{
+    find_segment_files, load_segment, BlockIndex, SegmentIndex, BLOCK_EVENTS, BLOCK_SIZE,
+    SEGMENT_HEADER_LEN,
+};
 
 /// Builds an in-memory index of all segments
 pub struct IndexBuilder {
@@ -21,10 +24,25 @@ impl IndexBuilder {
 
     /// Scan all segments and build indexes
     pub fn build_index(&self) -> Result<Vec<SegmentIndex>> {
+        self.build_index_reusing(&[])
+    }
+
+    /// Like build_index, but keeps entries from `previous` for segments that haven't changed
+    /// size since, so a refresh only indexes new segments and the one being written
+    pub fn build_index_reusing(&self, previous: &[SegmentIndex]) -> Result<Vec<SegmentIndex>> {
         let segment_files = find_segment_files(&self.dir);
+        let newest = segment_files.last().map(|(id, _)| *id);
         let mut indexes = Vec::new();
         for (segment_id, path) in segment_files {
-            if let Ok(index) = self.build_segment_index(segment_id, &path) {
+            let file_size = fs::metadata(&path).map(|m| m.len()).ok();
+            if let Some(existing) = previous
+                .iter()
+                .find(|idx| idx.segment_id == segment_id && Some(idx.file_size) == file_size)
+            {
+                indexes.push(existing.clone());
+                continue;
+            }
+            if let Ok(index) = self.build_segment_index(segment_id, &path, Some(segment_id) == newest) {
                 indexes.push(index);
             }
         }
@@ -32,8 +50,10 @@ impl IndexBuilder {
         Ok(indexes)
     }
 
-    /// Build index for a single segment (with persistent caching)
-    fn build_segment_index(&self, segment_id: u64, path: &Path) -> Result<SegmentIndex> {
+    /// Build index for a single segment (with persistent caching in a .idx sidecar).
+    /// A missing or outdated sidecar is rebuilt, so older data directories work unchanged.
+    /// The newest segment is indexed up to any torn tail, see integrity::readable.
+    pub fn build_segment_index(&self, segment_id: u64, path: &Path, newest: bool) -> Result<SegmentIndex> {
         // Try to load cached index if it exists and is up-to-date
         let index_path = path.with_extension("idx");
         if let Ok(cached_index) = self.load_cached_index(&index_path, path) {
@@ -41,7 +61,7 @@ impl IndexBuilder {
         }
 
         // Cache miss or outdated - build index by scanning segment
-        let index = self.scan_and_build_index(segment_id, path)?;
+        let index = self.scan_and_build_index(segment_id, path, newest)?;
 
         // Save index to cache file (ignore errors - caching is optional)
         let _ = self.save_index_to_cache(&index, &index_path);
@@ -69,6 +89,11 @@ impl IndexBuilder {
         let index: SegmentIndex = bincode::deserialize(&index_data)
             .context("Failed to deserialize cached index")?;
 
+        // mtime granularity can hide appends made right after the index was written
+        if index.file_size != fs::metadata(segment_path)?.len() {
+            anyhow::bail!("Index file is outdated");
+        }
+
         Ok(index)
     }
 
@@ -79,39 +104,29 @@ impl IndexBuilder {
         Ok(())
     }
 
-    /// Scan segment and build index (the original expensive operation)
-    fn scan_and_build_index(&self, segment_id: u64, path: &Path) -> Result<SegmentIndex> {
-        let mut file = File::open(path).context("Failed to open segment")?;
-        let file_size = file.metadata()?.len();
-
-        // Read and verify magic number
-        let mut magic_bytes = [0u8; 4];
-        file.read_exact(&mut magic_bytes)?;
-        let magic = u32::from_le_bytes(magic_bytes);
+    /// Scan segment record headers and build the sparse index. Payloads are skipped, other than
+    /// checksumming the newest segment's to find where its whole records end.
+    fn scan_and_build_index(&self, segment_id: u64, path: &Path, newest: bool) -> Result<SegmentIndex> {
+        // Offsets are into the uncompressed data; file_size is on disk so compressing invalidates the cache
+        let file_size = fs::metadata(path).context("Failed to open segment")?.len();
+        let mmap = load_segment(path)?;
 
-        if magic != MAGIC {
-            anyhow::bail!("Invalid magic number in segment");
-        }
+        let format = SegmentFormat::detect(&mmap)?;
 
         let mut blocks = Vec::new();
         let mut first_timestamp_ns = None;
         let mut last_timestamp_ns = 0i128;
-        let mut current_offset = 4u64; // After magic number
-        let mut block_start_offset = current_offset;
+
+        let mut block_start_offset = SEGMENT_HEADER_LEN;
         let mut block_event_count = 0u32;
         let mut block_first_timestamp = None;
 
-        loop {
-            // Record current position
-            let record_offset = current_offset;
-
-            // Try to read header
-            let header = match read_record_header(&mut file) {
-                Ok(h) => h,
-                Err(_) => break, // End of file
-            };
-
-            let header_size = bincode::serialized_size(&header)? as u64;
+        // Stops at a partially written record
+        let data = integrity::readable(&mmap, format, newest);
+        for record in integrity::records(data, SEGMENT_HEADER_LEN as usize, format) {
+            let record_offset = record.offset;
+            let header = record.header;
+            let record_end = record_offset + (record.bytes.len() + record.trailer.len()) as u64;
 
             // Update timestamps
             if first_timestamp_ns.is_none() {
@@ -119,42 +134,32 @@ impl IndexBuilder {
             }
             last_timestamp_ns = header.timestamp_unix_ns;
 
-            // Skip payload
-            file.seek(SeekFrom::Current(header.payload_len as i64))?;
-
-            block_event_count += 1;
             if block_first_timestamp.is_none() {
+                block_start_offset = record_offset;
                 block_first_timestamp = Some(header.timestamp_unix_ns);
             }
+            block_event_count += 1;
 
-            // Update current offset
-            current_offset += header_size + header.payload_len as u64;
-
-            // Create block checkpoint every BLOCK_SIZE bytes
-            if current_offset - block_start_offset >= BLOCK_SIZE {
-                if let Some(ts) = block_first_timestamp {
+            // Create a checkpoint every BLOCK_SIZE bytes or BLOCK_EVENTS events
+            if record_end - block_start_offset >= BLOCK_SIZE || block_event_count >= BLOCK_EVENTS {
+                if let Some(ts) = block_first_timestamp.take() {
                     blocks.push(BlockIndex {
                         file_offset: block_start_offset,
                         timestamp_ns: ts,
                         event_count: block_event_count,
                     });
                 }
-
-                block_start_offset = record_offset;
                 block_event_count = 0;
-                block_first_timestamp = None;
             }
         }
 
         // Add final block if it has events
-        if block_event_count > 0 {
-            if let Some(ts) = block_first_timestamp {
-                blocks.push(BlockIndex {
-                    file_offset: block_start_offset,
-                    timestamp_ns: ts,
-                    event_count: block_event_count,
-                });
-            }
+        if let Some(ts) = block_first_timestamp {
+            blocks.push(BlockIndex {
+                file_offset: block_start_offset,
+                timestamp_ns: ts,
+                event_count: block_event_count,
+            });
         }
 
         Ok(SegmentIndex {
@@ -168,12 +173,6 @@ impl IndexBuilder {
     }
 }
 
-fn read_record_header(file: &mut File) -> Result<RecordHeader> {
-    let header: RecordHeader = bincode::deserialize_from(file)
-        .context("Failed to deserialize header")?;
-    Ok(header)
-}
-
 /// Query helper: find segments that might contain events in time range
 pub fn find_relevant_segments(
     indexes: &[SegmentIndex],
@@ -1,20 +1,25 @@
-use anyhow::{Context, Result};
-use memmap2::Mmap;
+use anyhow::Result;
 use std::{
-    fs::File,
-    io::Cursor,
+    ops::ControlFlow,
     path::{Path, PathBuf},
-    sync::RwLock,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
 };
 
-use crate::event::Event;
+use crate::event::{Event, ProcessSnapshot};
 use crate::index::{find_relevant_segments, find_start_block, IndexBuilder};
-use crate::storage::{RecordHeader, SegmentIndex, MAGIC};
+use crate::integrity::{self, RecordChain, SegmentFormat, SigningKey};
+use crate::process_delta::{decode_payload, Payload, SnapshotRebuilder};
+use crate::storage::{load_segment, SegmentIndex, SEGMENT_HEADER_LEN};
 
 /// Efficient reader using memory-mapped I/O and block indexes
 pub struct IndexedReader {
     dir: PathBuf,
     indexes: RwLock<Vec<SegmentIndex>>,
+    bytes_scanned: AtomicU64, // Segment bytes walked by read_time_range, for measuring index effectiveness
+    signing_key: Option<SigningKey>,
 }
 
 impl IndexedReader {
@@ -27,13 +32,22 @@ impl IndexedReader {
         Ok(Self {
             dir: dir_path,
             indexes: RwLock::new(indexes),
+            bytes_scanned: AtomicU64::new(0),
+            signing_key: None,
         })
     }
 
-    /// Refresh the index to pick up new segments
+    /// Also check the HMAC chain of signed segments, not just record checksums
+    pub fn with_signing_key(mut self, signing_key: Option<SigningKey>) -> Self {
+        self.signing_key = signing_key;
+        self
+    }
+
+    /// Refresh the index to pick up new segments. Unchanged segments keep their existing index.
     pub fn refresh(&self) -> Result<()> {
         let builder = IndexBuilder::new(&self.dir);
-        let new_indexes = builder.build_index()?;
+        let previous = self.indexes.read().unwrap().clone();
+        let new_indexes = builder.build_index_reusing(&previous)?;
         let mut indexes = self.indexes.write().unwrap();
         *indexes = new_indexes;
         Ok(())
@@ -47,38 +61,82 @@ impl IndexedReader {
     ) -> Result<Vec<Event>> {
         let indexes = self.indexes.read().unwrap();
         let relevant_segments = find_relevant_segments(&indexes, start_ns, end_ns);
+        let newest = newest_segment(&indexes);
 
         let mut events = Vec::new();
+        let mut deleted = Vec::new();
 
         for segment in relevant_segments {
-            let segment_events = self.read_segment_range(segment, start_ns, end_ns)?;
-            events.extend(segment_events);
+            match self.read_segment_range(segment, start_ns, end_ns, Some(segment.segment_id) == newest) {
+                Ok(segment_events) => events.extend(segment_events),
+                // Segment removed by the recorder's retention since the index was built
+                Err(_) if !segment.file_path.exists() => deleted.push(segment.segment_id),
+                Err(e) => return Err(e),
+            }
+        }
+        drop(indexes);
+
+        if !deleted.is_empty() {
+            self.remove_segments(&deleted);
         }
 
         Ok(events)
     }
 
-    /// Read a segment using mmap and block index for fast seeking
+    /// Hand events in a time range to `visit` newest first, until it breaks. Segments are read
+    /// one at a time from the newest, so older ones are never parsed once `visit` has enough.
+    pub fn read_newest_first(
+        &self,
+        start_ns: Option<i128>,
+        end_ns: Option<i128>,
+        mut visit: impl FnMut(Event) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let (segments, newest): (Vec<SegmentIndex>, _) = {
+            let indexes = self.indexes.read().unwrap();
+            let segments = find_relevant_segments(&indexes, start_ns, end_ns).into_iter().cloned().collect();
+            (segments, newest_segment(&indexes))
+        };
+
+        let mut deleted = Vec::new();
+        for segment in segments.iter().rev() {
+            let events = match self.read_segment_range(segment, start_ns, end_ns, Some(segment.segment_id) == newest) {
+                Ok(events) => events,
+                Err(_) if !segment.file_path.exists() => {
+                    deleted.push(segment.segment_id);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if events.into_iter().rev().try_for_each(&mut visit).is_break() {
+                break;
+            }
+        }
+
+        if !deleted.is_empty() {
+            self.remove_segments(&deleted);
+        }
+        Ok(())
+    }
+
+    /// Drop index entries for segments that no longer exist on disk
+    pub fn remove_segments(&self, segment_ids: &[u64]) {
+        let mut indexes = self.indexes.write().unwrap();
+        indexes.retain(|idx| !segment_ids.contains(&idx.segment_id));
+    }
+
+    /// Read a segment using mmap and block index for fast seeking. The newest segment is read
+    /// up to any torn tail, see integrity::readable.
     fn read_segment_range(
         &self,
         segment: &SegmentIndex,
         start_ns: Option<i128>,
         end_ns: Option<i128>,
+        newest: bool,
     ) -> Result<Vec<Event>> {
-        let file = File::open(&segment.file_path)
-            .context("Failed to open segment file")?;
-
-        // Memory-map the file for zero-copy access
-        let mmap = unsafe { Mmap::map(&file)? };
+        let segment_data = load_segment(&segment.file_path)?;
 
-        // Verify magic number
-        if mmap.len() < 4 {
-            anyhow::bail!("Segment file too small");
-        }
-        let magic = u32::from_le_bytes([mmap[0], mmap[1], mmap[2], mmap[3]]);
-        if magic != MAGIC {
-            anyhow::bail!("Invalid magic number");
-        }
+        let format = SegmentFormat::detect(&segment_data)?;
+        let mmap = integrity::readable(&segment_data, format, newest);
 
         // Find the starting block using binary search
         let start_block_idx = if let Some(start) = start_ns {
@@ -91,53 +149,117 @@ impl IndexedReader {
         let start_offset = if start_block_idx < segment.blocks.len() {
             segment.blocks[start_block_idx].file_offset as usize
         } else {
-            4 // Just after magic number
+            SEGMENT_HEADER_LEN as usize // Just after magic number
         };
 
-        let mut events = Vec::new();
-        let mut cursor = Cursor::new(&mmap[start_offset..]);
+        // The HMAC chain carries on from the tag of the record before the block
+        let mut chain = RecordChain::new(format, self.signing_key.clone(), segment.segment_id);
+        chain.resume(mmap, start_offset);
 
-        loop {
-            // Try to read header
-            let header = match bincode::deserialize_from::<_, RecordHeader>(&mut cursor) {
-                Ok(h) => h,
-                Err(_) => break, // End of data
-            };
+        let mut rebuilder = SnapshotRebuilder::new();
+        let mut events = Vec::new();
+        let mut end_offset = start_offset;
 
+        for record in integrity::records(mmap, start_offset, format) {
             // Check if we've passed the end time
             if let Some(end) = end_ns {
-                if header.timestamp_unix_ns > end {
+                if record.header.timestamp_unix_ns > end {
                     break;
                 }
             }
+            end_offset = record.offset as usize + record.bytes.len() + record.trailer.len();
 
-            // Read payload
-            let current_pos = cursor.position() as usize;
-            let payload_end = current_pos + header.payload_len as usize;
-
-            if payload_end > cursor.get_ref().len() {
-                break; // Not enough data
+            // Records before the start of the range are skipped without verifying or deserializing them
+            if start_ns.is_some_and(|start| record.header.timestamp_unix_ns < start) {
+                chain.skip(&record);
+                continue;
             }
 
-            let payload = &cursor.get_ref()[current_pos..payload_end];
-            cursor.set_position(payload_end as u64);
+            if let Err(reason) = chain.verify(&record) {
+                events.push(integrity::violation_event(&segment.file_path, &record, reason));
+                continue;
+            }
 
-            // Deserialize event
-            if let Ok(event) = bincode::deserialize::<Event>(payload) {
-                // Filter by start time
-                if let Some(start) = start_ns {
-                    if header.timestamp_unix_ns < start {
-                        continue;
+            let Ok(payload) = decode_payload(record.payload) else {
+                continue;
+            };
+            match rebuilder.rebuild(payload) {
+                Ok(event) => events.push(event),
+                // The range starts after the delta's keyframe, look back for it
+                Err(delta) => {
+                    if let Some(keyframe) = self.find_keyframe(mmap, format, segment, delta.base_ts, record.offset as usize) {
+                        rebuilder.set_keyframe(keyframe);
+                        if let Ok(event) = rebuilder.rebuild(Payload::Delta(delta)) {
+                            events.push(event);
+                        }
                     }
                 }
-
-                events.push(event);
             }
         }
 
+        self.bytes_scanned.fetch_add((end_offset - start_offset) as u64, Ordering::Relaxed);
         Ok(events)
     }
 
+    // The full ProcessSnapshot taken at `base_ts`, from the records of `segment` before `end_offset`.
+    // Records are stamped when appended, never before their event's own timestamp.
+    fn find_keyframe(
+        &self,
+        data: &[u8],
+        format: SegmentFormat,
+        segment: &SegmentIndex,
+        base_ts: time::OffsetDateTime,
+        end_offset: usize,
+    ) -> Option<ProcessSnapshot> {
+        let base_ns = base_ts.unix_timestamp_nanos();
+        let start_offset = segment
+            .blocks
+            .get(find_start_block(segment, base_ns))
+            .map_or(SEGMENT_HEADER_LEN as usize, |block| block.file_offset as usize);
+
+        let mut chain = RecordChain::new(format, self.signing_key.clone(), segment.segment_id);
+        chain.resume(data, start_offset);
+        for record in integrity::records(data, start_offset, format) {
+            if record.offset as usize >= end_offset {
+                break;
+            }
+            if record.header.timestamp_unix_ns < base_ns {
+                chain.skip(&record);
+                continue;
+            }
+            if chain.verify(&record).is_err() {
+                continue;
+            }
+            if let Ok(Payload::Event(event)) = decode_payload(record.payload)
+                && let Event::ProcessSnapshot(snapshot) = *event
+                && snapshot.ts == base_ts
+            {
+                return Some(snapshot);
+            }
+        }
+        None
+    }
+
+    /// Total segment bytes walked by read_time_range since the reader was created
+    #[cfg(test)]
+    pub fn bytes_scanned(&self) -> u64 {
+        self.bytes_scanned.load(Ordering::Relaxed)
+    }
+
+    /// (first, last) record timestamps of each segment, oldest first
+    pub fn segment_time_ranges(&self) -> Vec<(i128, i128)> {
+        let indexes = self.indexes.read().unwrap();
+        indexes
+            .iter()
+            .map(|idx| (idx.first_timestamp_ns, idx.last_timestamp_ns))
+            .collect()
+    }
+
+    /// Directory the segments are read from
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
     /// Get the number of indexed segments without cloning
     pub fn segment_count(&self) -> usize {
         self.indexes.read().unwrap().len()
@@ -167,6 +289,11 @@ impl IndexedReader {
     }
 }
 
+// The segment the recorder may still be writing, the only one that can have a torn tail
+fn newest_segment(indexes: &[SegmentIndex]) -> Option<u64> {
+    indexes.iter().map(|idx| idx.segment_id).max()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,4 +306,142 @@ mod tests {
         assert_eq!(reader.segment_count(), 0);
         assert!(reader.get_time_range().is_none());
     }
+
+    #[test]
+    fn test_read_time_range_skips_deleted_segments() {
+        use crate::event::{Anomaly, AnomalyKind, AnomalySeverity};
+        use crate::recorder::Recorder;
+        use crate::storage::{find_segment_files, RetentionPolicy};
+        use time::OffsetDateTime;
+
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        {
+            let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+            recorder.append(&Event::Anomaly(Anomaly {
+                ts: OffsetDateTime::now_utc(),
+                severity: AnomalySeverity::Warning,
+                kind: AnomalyKind::CpuSpike,
+                message: "test".to_string(),
+            })).unwrap();
+        }
+
+        let reader = IndexedReader::new(temp_dir.path()).unwrap();
+        assert_eq!(reader.segment_count(), 1);
+        assert_eq!(reader.read_time_range(None, None).unwrap().len(), 1);
+
+        for (_, path) in find_segment_files(temp_dir.path()) {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert!(reader.read_time_range(None, None).unwrap().is_empty());
+        assert_eq!(reader.segment_count(), 0);
+    }
+
+    // Write a segment file directly so the test controls record timestamps
+    fn write_test_segment(dir: &Path, id: u64, timestamps: impl Iterator<Item = i128>) -> u64 {
+        use crate::event::{Anomaly, AnomalyKind, AnomalySeverity};
+        use crate::storage::{RecordHeader, MAGIC};
+        use std::io::Write;
+
+        let path = dir.join(format!("segment_{:05}.dat", id));
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+        file.write_all(&MAGIC.to_le_bytes()).unwrap();
+        for ts in timestamps {
+            let event = Event::Anomaly(Anomaly {
+                ts: time::OffsetDateTime::from_unix_timestamp_nanos(ts).unwrap(),
+                severity: AnomalySeverity::Info,
+                kind: AnomalyKind::CpuSpike,
+                message: format!("event at {}", ts),
+            });
+            let payload = bincode::serialize(&event).unwrap();
+            let header = RecordHeader { timestamp_unix_ns: ts, payload_len: payload.len() as u32 };
+            file.write_all(&bincode::serialize(&header).unwrap()).unwrap();
+            file.write_all(&payload).unwrap();
+        }
+        file.flush().unwrap();
+        std::fs::metadata(&path).unwrap().len()
+    }
+
+    #[test]
+    fn test_narrow_range_reads_a_fraction_of_the_data() {
+        const SECOND: i128 = 1_000_000_000;
+        const EVENTS_PER_SEGMENT: i128 = 20_000;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut total_bytes = 0;
+        for id in 0..3 {
+            let first = id as i128 * EVENTS_PER_SEGMENT;
+            total_bytes += write_test_segment(
+                temp_dir.path(),
+                id,
+                (first..first + EVENTS_PER_SEGMENT).map(|i| i * SECOND),
+            );
+        }
+
+        let reader = IndexedReader::new(temp_dir.path()).unwrap();
+        assert_eq!(reader.estimate_event_count(), 3 * EVENTS_PER_SEGMENT as u64);
+
+        // 100 seconds in the middle segment
+        let start = 30_000 * SECOND;
+        let end = 30_099 * SECOND;
+        let events = reader.read_time_range(Some(start), Some(end)).unwrap();
+        assert_eq!(events.len(), 100);
+        assert_eq!(events.first().unwrap().timestamp().unix_timestamp_nanos(), start);
+        assert_eq!(events.last().unwrap().timestamp().unix_timestamp_nanos(), end);
+
+        // At most a couple of index blocks around the window are read
+        let scanned = reader.bytes_scanned();
+        assert!(
+            scanned * 100 < total_bytes,
+            "scanned {} of {} bytes",
+            scanned,
+            total_bytes
+        );
+
+        // The sidecar index is reused on the next open
+        for id in 0..3 {
+            assert!(temp_dir.path().join(format!("segment_{:05}.idx", id)).exists());
+        }
+        let reopened = IndexedReader::new(temp_dir.path()).unwrap();
+        assert_eq!(reopened.read_time_range(Some(start), Some(end)).unwrap().len(), 100);
+    }
+
+    #[test]
+    fn test_refresh_picks_up_new_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        write_test_segment(temp_dir.path(), 0, 0..10);
+        let reader = IndexedReader::new(temp_dir.path()).unwrap();
+        assert_eq!(reader.segment_count(), 1);
+
+        write_test_segment(temp_dir.path(), 1, 10..25);
+        reader.refresh().unwrap();
+        assert_eq!(reader.segment_count(), 2);
+        assert_eq!(reader.read_time_range(Some(5), None).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn test_read_newest_first_stops_at_the_newest_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut total_bytes = 0;
+        for id in 0..3 {
+            let first = id as i128 * 1000;
+            total_bytes += write_test_segment(temp_dir.path(), id, first..first + 1000);
+        }
+        let reader = IndexedReader::new(temp_dir.path()).unwrap();
+
+        let mut seen = Vec::new();
+        reader
+            .read_newest_first(None, Some(2499), |event| {
+                seen.push(event.timestamp().unix_timestamp_nanos());
+                if seen.len() < 600 { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+            })
+            .unwrap();
+
+        // Newest first from the end of the range, and the oldest segment never read
+        assert_eq!(seen.len(), 600);
+        assert_eq!(seen[0], 2499);
+        assert_eq!(seen[599], 1900);
+        assert!(reader.bytes_scanned() * 3 < total_bytes * 2, "scanned {} of {} bytes", reader.bytes_scanned(), total_bytes);
+    }
 }
@@ -0,0 +1,478 @@
+use ring::hmac;
+use std::fmt;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+use crate::config::ProtectionConfig;
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, Event};
+use crate::storage::{RecordHeader, MAGIC, MAGIC_CHECKSUM, MAGIC_PREFIX, MAGIC_SIGNED, SEGMENT_HEADER_LEN};
+
+const CRC_LEN: usize = 4;
+const TAG_LEN: usize = 32;
+
+/// Record layout of a segment, given by the format version in its magic number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentFormat {
+    Plain,       // Written before checksums were added, nothing to verify
+    Checksummed, // CRC32 of header and payload after each record
+    Signed,      // CRC32 then an HMAC-SHA256 chained from the previous record's tag
+}
+
+impl SegmentFormat {
+    pub fn from_magic(magic: u32) -> Option<Self> {
+        match magic {
+            MAGIC => Some(Self::Plain),
+            MAGIC_CHECKSUM => Some(Self::Checksummed),
+            MAGIC_SIGNED => Some(Self::Signed),
+            _ => None,
+        }
+    }
+
+    /// Format of a segment from its first bytes, None if it isn't a segment
+    pub fn of_segment(data: &[u8]) -> Option<Self> {
+        Self::from_magic(segment_magic(data)?)
+    }
+
+    /// Format of a segment to be read. One written by a newer black box fails with
+    /// UnsupportedVersion rather than being read as something it isn't.
+    pub fn detect(data: &[u8]) -> anyhow::Result<Self> {
+        match segment_magic(data) {
+            Some(magic) if let Some(format) = Self::from_magic(magic) => Ok(format),
+            Some(magic) if magic & 0xFFFF_0000 == MAGIC_PREFIX => Err(UnsupportedVersion { version: magic as u16 }.into()),
+            _ => anyhow::bail!("Not a segment (unknown magic number)"),
+        }
+    }
+
+    pub fn version(self) -> u16 {
+        self.magic() as u16
+    }
+
+    pub fn magic(self) -> u32 {
+        match self {
+            Self::Plain => MAGIC,
+            Self::Checksummed => MAGIC_CHECKSUM,
+            Self::Signed => MAGIC_SIGNED,
+        }
+    }
+
+    /// Bytes following each record's payload
+    pub fn trailer_len(self) -> usize {
+        match self {
+            Self::Plain => 0,
+            Self::Checksummed => CRC_LEN,
+            Self::Signed => CRC_LEN + TAG_LEN,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Plain => "unchecked",
+            Self::Checksummed => "checksummed",
+            Self::Signed => "signed",
+        }
+    }
+}
+
+fn segment_magic(data: &[u8]) -> Option<u32> {
+    let magic = data.get(..SEGMENT_HEADER_LEN as usize)?;
+    Some(u32::from_le_bytes(magic.try_into().ok()?))
+}
+
+/// A segment in a format version this build doesn't know, written by a newer black box
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedVersion {
+    pub version: u16,
+}
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Segment format version {} is newer than this black box supports", self.version)
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+/// HMAC key for the per-segment record chain, from [protection] signing_key
+#[derive(Clone)]
+pub struct SigningKey(hmac::Key);
+
+impl SigningKey {
+    pub fn new(secret: &str) -> Self {
+        Self(hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()))
+    }
+
+    /// The configured key when sign_events is on
+    pub fn from_config(config: &ProtectionConfig) -> Option<Self> {
+        if !config.sign_events {
+            return None;
+        }
+        config.signing_key.as_deref().filter(|k| !k.is_empty()).map(Self::new)
+    }
+}
+
+/// A record as laid out in a segment
+pub struct RawRecord<'a> {
+    pub offset: u64,
+    pub header: RecordHeader,
+    pub bytes: &'a [u8], // Header and payload, what the trailer covers
+    pub payload: &'a [u8],
+    pub trailer: &'a [u8],
+}
+
+/// Records of a segment from `start`. Ends at the end of the data or at a partially
+/// written record (the recorder was killed mid-write).
+pub struct Records<'a> {
+    data: &'a [u8],
+    pos: usize,
+    trailer_len: usize,
+}
+
+pub fn records(data: &[u8], start: usize, format: SegmentFormat) -> Records<'_> {
+    Records { data, pos: start, trailer_len: format.trailer_len() }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = RawRecord<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.data.get(self.pos..)?;
+        let mut cursor = Cursor::new(rest);
+        let header: RecordHeader = bincode::deserialize_from(&mut cursor).ok()?;
+
+        let payload_start = cursor.position() as usize;
+        let payload_end = payload_start.checked_add(header.payload_len as usize)?;
+        let record_end = payload_end.checked_add(self.trailer_len)?;
+        if record_end > rest.len() {
+            return None;
+        }
+
+        let record = RawRecord {
+            offset: self.pos as u64,
+            header,
+            bytes: &rest[..payload_end],
+            payload: &rest[payload_start..payload_end],
+            trailer: &rest[payload_end..record_end],
+        };
+        self.pos += record_end;
+        Some(record)
+    }
+}
+
+/// Offset just past the last record whose checksum matches, where the recorder can safely
+/// carry on appending. Anything after it is a torn write: a partial record, or whole records
+/// of garbage or zeros the filesystem left behind after a power loss.
+pub fn recoverable_end(data: &[u8], format: SegmentFormat) -> usize {
+    recoverable_end_from(data, SEGMENT_HEADER_LEN as usize, format)
+}
+
+/// The same, looking only at the records from `start`, the end of one already read
+pub fn recoverable_end_from(data: &[u8], start: usize, format: SegmentFormat) -> usize {
+    let mut chain = RecordChain::new(format, None, 0);
+    let mut end = start.min(data.len());
+    for record in records(data, end, format) {
+        if chain.verify(&record).is_ok() {
+            end = record.offset as usize + record.bytes.len() + record.trailer.len();
+        }
+    }
+    end
+}
+
+/// The records of a segment readers should go through. A crash can leave the newest segment
+/// with a torn tail until the recorder reopens it and cuts it off, so there it's left out
+/// rather than reported as tampering. Older segments were sealed whole and are read to the end.
+pub fn readable(data: &[u8], format: SegmentFormat, newest: bool) -> &[u8] {
+    if newest { &data[..recoverable_end(data, format)] } else { data }
+}
+
+/// Writes and checks record trailers for one segment. The HMAC chain starts from a tag
+/// over the segment id, so records can't be moved between segments unnoticed.
+pub struct RecordChain {
+    format: SegmentFormat,
+    key: Option<SigningKey>,
+    prev_tag: [u8; TAG_LEN],
+}
+
+impl RecordChain {
+    pub fn new(format: SegmentFormat, key: Option<SigningKey>, segment_id: u64) -> Self {
+        let mut chain = Self { format, key, prev_tag: [0; TAG_LEN] };
+        chain.restart(segment_id);
+        chain
+    }
+
+    pub fn format(&self) -> SegmentFormat {
+        self.format
+    }
+
+    pub fn key(&self) -> Option<&SigningKey> {
+        self.key.as_ref()
+    }
+
+    /// Start again at the beginning of a new segment
+    pub fn restart(&mut self, segment_id: u64) {
+        if let Some(key) = &self.key {
+            let mut ctx = hmac::Context::with_key(&key.0);
+            ctx.update(b"black-box segment");
+            ctx.update(&segment_id.to_le_bytes());
+            self.prev_tag.copy_from_slice(ctx.sign().as_ref());
+        }
+    }
+
+    /// Continue from the record that ends at `offset` in `data`, for reads starting mid-segment
+    pub fn resume(&mut self, data: &[u8], offset: usize) {
+        if self.format == SegmentFormat::Signed
+            && offset > SEGMENT_HEADER_LEN as usize
+            && let Some(tag) = offset.checked_sub(TAG_LEN).and_then(|start| data.get(start..offset))
+        {
+            self.prev_tag.copy_from_slice(tag);
+        }
+    }
+
+    fn tag(&self, key: &SigningKey, bytes: &[u8]) -> hmac::Tag {
+        let mut ctx = hmac::Context::with_key(&key.0);
+        ctx.update(&self.prev_tag);
+        ctx.update(bytes);
+        ctx.sign()
+    }
+
+    /// Trailer to write after a new record's header and payload
+    pub fn seal(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut trailer = Vec::with_capacity(self.format.trailer_len());
+        if self.format == SegmentFormat::Plain {
+            return trailer;
+        }
+        trailer.extend_from_slice(&crc32fast::hash(bytes).to_le_bytes());
+
+        if self.format == SegmentFormat::Signed {
+            let key = self.key.as_ref().expect("signed segments are only written with a key");
+            let tag = self.tag(key, bytes);
+            trailer.extend_from_slice(tag.as_ref());
+            self.prev_tag.copy_from_slice(tag.as_ref());
+        }
+        trailer
+    }
+
+    /// Check a record read back from the segment. Without a key only the CRC is checked.
+    pub fn verify(&mut self, record: &RawRecord) -> Result<(), &'static str> {
+        if self.format == SegmentFormat::Plain {
+            return Ok(());
+        }
+        let (crc, tag) = record.trailer.split_at(CRC_LEN);
+        let crc_ok = crc32fast::hash(record.bytes).to_le_bytes() == crc;
+
+        let mut result = if crc_ok { Ok(()) } else { Err("checksum mismatch") };
+        if self.format == SegmentFormat::Signed {
+            if crc_ok && let Some(key) = &self.key {
+                let mut message = Vec::with_capacity(TAG_LEN + record.bytes.len());
+                message.extend_from_slice(&self.prev_tag);
+                message.extend_from_slice(record.bytes);
+                if hmac::verify(&key.0, &message, tag).is_err() {
+                    result = Err("signature mismatch (record modified, removed or reordered)");
+                }
+            }
+            // Carry on from the stored tag so one bad record is reported once
+            self.prev_tag.copy_from_slice(tag);
+        }
+        result
+    }
+
+    /// Move past a record without checking it
+    pub fn skip(&mut self, record: &RawRecord) {
+        if self.format == SegmentFormat::Signed {
+            self.prev_tag.copy_from_slice(&record.trailer[CRC_LEN..]);
+        }
+    }
+}
+
+/// Synthetic anomaly standing in for a record that failed verification
+pub fn violation_event(path: &Path, record: &RawRecord, reason: &str) -> Event {
+    let ts = OffsetDateTime::from_unix_timestamp_nanos(record.header.timestamp_unix_ns)
+        .unwrap_or_else(|_| OffsetDateTime::now_utc());
+    Event::Anomaly(Anomaly {
+        ts,
+        severity: AnomalySeverity::Critical,
+        kind: AnomalyKind::IntegrityViolation,
+        message: format!(
+            "Segment integrity violation in {} at offset {}: {}",
+            path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().to_string()),
+            record.offset,
+            reason
+        ),
+    })
+}
+
+/// Integrity of one segment file, for `export --verify`
+#[derive(Debug)]
+pub struct SegmentReport {
+    pub path: PathBuf,
+    pub format: Option<SegmentFormat>, // None if the magic number is unknown
+    pub newer_version: Option<u16>, // Format version of a segment from a newer black box
+    pub records: usize,
+    pub violations: Vec<(u64, &'static str)>, // offset, reason
+    pub end: u64, // Offset after the last complete record
+    pub trailing_bytes: usize, // After the last complete record
+}
+
+impl SegmentReport {
+    /// The first record that failed verification, or for a sealed segment (one the recorder has
+    /// moved on from, so nothing should be cut off) where it ends part way through a record
+    pub fn first_problem(&self, sealed: bool) -> Option<String> {
+        if self.format.is_none() {
+            return Some(self.unknown_format());
+        }
+        if let Some((offset, reason)) = self.violations.first() {
+            return Some(format!("offset {}: {}", offset, reason));
+        }
+        if sealed && self.trailing_bytes > 0 {
+            return Some(format!("offset {}: truncated part way through a record", self.end));
+        }
+        None
+    }
+
+    /// Why a segment with no known format can't be checked
+    pub fn unknown_format(&self) -> String {
+        match self.newer_version {
+            Some(version) => format!("format version {} is newer than this black box supports", version),
+            None => "not a segment (unknown magic number)".to_string(),
+        }
+    }
+}
+
+pub fn verify_segment(data: &[u8], path: &Path, segment_id: u64, key: Option<&SigningKey>) -> SegmentReport {
+    let format = SegmentFormat::detect(data);
+    let mut report = SegmentReport {
+        path: path.to_path_buf(),
+        newer_version: format.as_ref().err().and_then(|e| e.downcast_ref::<UnsupportedVersion>()).map(|e| e.version),
+        format: format.ok(),
+        records: 0,
+        violations: Vec::new(),
+        end: 0,
+        trailing_bytes: 0,
+    };
+    let Some(format) = report.format else {
+        return report;
+    };
+
+    let mut chain = RecordChain::new(format, key.cloned(), segment_id);
+    let mut end = SEGMENT_HEADER_LEN as usize;
+    for record in records(data, end, format) {
+        report.records += 1;
+        if let Err(reason) = chain.verify(&record) {
+            report.violations.push((record.offset, reason));
+        }
+        end = record.offset as usize + record.bytes.len() + record.trailer.len();
+    }
+    report.end = end as u64;
+    report.trailing_bytes = data.len() - end;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(format: SegmentFormat, key: Option<SigningKey>, segment_id: u64, payloads: &[&[u8]]) -> Vec<u8> {
+        let mut data = format.magic().to_le_bytes().to_vec();
+        let mut chain = RecordChain::new(format, key, segment_id);
+        for (i, payload) in payloads.iter().enumerate() {
+            let header = RecordHeader { timestamp_unix_ns: i as i128, payload_len: payload.len() as u32 };
+            let mut bytes = bincode::serialize(&header).unwrap();
+            bytes.extend_from_slice(payload);
+            let trailer = chain.seal(&bytes);
+            data.extend_from_slice(&bytes);
+            data.extend_from_slice(&trailer);
+        }
+        data
+    }
+
+    fn violations(data: &[u8], segment_id: u64, key: Option<&SigningKey>) -> Vec<(u64, &'static str)> {
+        verify_segment(data, Path::new("segment_00000.dat"), segment_id, key).violations
+    }
+
+    #[test]
+    fn test_detect_newer_format_version() {
+        let data = segment(SegmentFormat::Checksummed, None, 0, &[b"first"]);
+        assert_eq!(SegmentFormat::detect(&data).unwrap(), SegmentFormat::Checksummed);
+        assert_eq!(SegmentFormat::Checksummed.version(), 2);
+
+        let mut newer = data.clone();
+        newer[..4].copy_from_slice(&(MAGIC_PREFIX | 9).to_le_bytes());
+        let err = SegmentFormat::detect(&newer).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&UnsupportedVersion { version: 9 }));
+        let report = verify_segment(&newer, Path::new("segment_00000.dat"), 0, None);
+        assert_eq!(report.first_problem(false).unwrap(), "format version 9 is newer than this black box supports");
+
+        let err = SegmentFormat::detect(b"\x7fELF").unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedVersion>().is_none());
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let mut data = segment(SegmentFormat::Checksummed, None, 0, &[b"first", b"second", b"third"]);
+        assert!(violations(&data, 0, None).is_empty());
+
+        let second = records(&data, 4, SegmentFormat::Checksummed).nth(1).unwrap().offset as usize;
+        let pos = data.len() - CRC_LEN - 2; // In the last payload
+        data[pos] ^= 0xff;
+        let found = violations(&data, 0, None);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].0 > second as u64);
+        assert_eq!(found[0].1, "checksum mismatch");
+    }
+
+    #[test]
+    fn test_signature_chain_detects_tampering() {
+        let key = SigningKey::new("secret");
+        let data = segment(SegmentFormat::Signed, Some(key.clone()), 3, &[b"one", b"two", b"three"]);
+        assert!(violations(&data, 3, Some(&key)).is_empty());
+
+        // Wrong key, or the segment renamed to another id
+        assert_eq!(violations(&data, 3, Some(&SigningKey::new("guess"))).len(), 3);
+        assert_eq!(violations(&data, 4, Some(&key)).len(), 1);
+
+        // A record rewritten with a recomputed CRC still fails the HMAC, and only it fails
+        let forged = segment(SegmentFormat::Signed, Some(SigningKey::new("attacker")), 3, &[b"two"]);
+        let forged_record = records(&forged, 4, SegmentFormat::Signed).next().unwrap();
+        let originals: Vec<_> = records(&data, 4, SegmentFormat::Signed).collect();
+        let mut tampered = data[..originals[1].offset as usize].to_vec();
+        tampered.extend_from_slice(forged_record.bytes);
+        tampered.extend_from_slice(forged_record.trailer);
+        tampered.extend_from_slice(&data[originals[2].offset as usize..]);
+        let found = violations(&tampered, 3, Some(&key));
+        assert_eq!(found.len(), 2); // The forged record, and the next one no longer chains from it
+        assert_eq!(found[0].0, originals[1].offset);
+
+        // Removing a record breaks the chain at the one after it
+        let mut removed = data[..originals[1].offset as usize].to_vec();
+        removed.extend_from_slice(&data[originals[2].offset as usize..]);
+        let found = violations(&removed, 3, Some(&key));
+        assert_eq!(found.len(), 1);
+
+        // Without the key only checksums can be checked
+        assert!(violations(&tampered, 3, None).is_empty());
+    }
+
+    #[test]
+    fn test_torn_final_record_is_not_a_violation() {
+        let data = segment(SegmentFormat::Checksummed, None, 0, &[b"first", b"second"]);
+        let torn = &data[..data.len() - 3];
+        let report = verify_segment(torn, Path::new("segment_00000.dat"), 0, None);
+        assert_eq!(report.records, 1);
+        assert!(report.violations.is_empty());
+        assert!(report.trailing_bytes > 0);
+        assert_eq!(recoverable_end(torn, SegmentFormat::Checksummed), torn.len() - report.trailing_bytes);
+
+        // Only a problem once the recorder has moved on from the segment
+        assert_eq!(report.first_problem(false), None);
+        assert_eq!(
+            report.first_problem(true),
+            Some(format!("offset {}: truncated part way through a record", report.end))
+        );
+
+        // Zeros after the last record parse as complete records but fail their checksums
+        let mut zeroed = data.clone();
+        zeroed.extend([0u8; 100]);
+        assert!(records(&zeroed, data.len(), SegmentFormat::Checksummed).count() > 0);
+        assert_eq!(recoverable_end(&zeroed, SegmentFormat::Checksummed), data.len());
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::collector::{parse_auth_message, AuthLogEntry};
+
+const CURSOR_FILE: &str = "journal.cursor";
+
+// Identifiers that carry SSH and sudo auth messages. sshd-session is used by OpenSSH 9.8+.
+// Matches on the same field are OR'ed by journalctl.
+const AUTH_IDENTIFIERS: [&str; 3] = ["sshd", "sshd-session", "sudo"];
+
+// Reads SSH/sudo auth entries from the systemd journal for hosts without /var/log/auth.log.
+// The journal cursor is saved in the data directory so restarts pick up where they left off.
+pub struct JournalTail {
+    cursor_path: PathBuf,
+    cursor: Option<String>,
+    since: i64, // Unix seconds, only used until we have a cursor
+}
+
+impl JournalTail {
+    // None when journalctl is not installed
+    pub fn open(data_dir: impl AsRef<Path>) -> Option<Self> {
+        let available = Command::new("journalctl")
+            .arg("--version")
+            .output()
+            .map(|out| out.status.success())
+            .unwrap_or(false);
+        if !available {
+            return None;
+        }
+
+        let cursor_path = data_dir.as_ref().join(CURSOR_FILE);
+        let cursor = fs::read_to_string(&cursor_path)
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        Some(Self {
+            cursor_path,
+            cursor,
+            since: time::OffsetDateTime::now_utc().unix_timestamp(),
+        })
+    }
+
+    // Auth entries written since the last poll
+    pub fn poll(&mut self) -> Result<Vec<AuthLogEntry>> {
+        let mut cmd = Command::new("journalctl");
+        cmd.args(["--no-pager", "--quiet", "--output=json"]);
+        match &self.cursor {
+            Some(cursor) => cmd.arg(format!("--after-cursor={}", cursor)),
+            None => cmd.arg(format!("--since=@{}", self.since)),
+        };
+        for ident in AUTH_IDENTIFIERS {
+            cmd.arg(format!("SYSLOG_IDENTIFIER={}", ident));
+        }
+
+        let output = cmd.output().context("Failed to run journalctl")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "journalctl exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut entries = Vec::new();
+        let mut last_cursor = None;
+
+        for line in stdout.lines() {
+            let Ok(record) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            if let Some(cursor) = record.get("__CURSOR").and_then(|c| c.as_str()) {
+                last_cursor = Some(cursor.to_string());
+            }
+            if let Some(entry) = parse_journal_record(&record) {
+                entries.push(entry);
+            }
+        }
+
+        if let Some(cursor) = last_cursor {
+            self.save_cursor(&cursor)?;
+            self.cursor = Some(cursor);
+        }
+
+        Ok(entries)
+    }
+
+    fn save_cursor(&self, cursor: &str) -> Result<()> {
+        // Write then rename so a crash never leaves a half-written cursor
+        let tmp_path = self.cursor_path.with_extension("cursor.tmp");
+        fs::write(&tmp_path, cursor).context("Failed to write journal cursor")?;
+        fs::rename(&tmp_path, &self.cursor_path).context("Failed to save journal cursor")?;
+        Ok(())
+    }
+}
+
+// Rebuild the syslog-style "host ident[pid]: message" text the auth log parser expects
+fn parse_journal_record(record: &serde_json::Value) -> Option<AuthLogEntry> {
+    let field = |name: &str| record.get(name).and_then(|v| v.as_str());
+
+    let message = field("MESSAGE")?;
+    let ident = field("SYSLOG_IDENTIFIER").unwrap_or("unknown");
+    let host = field("_HOSTNAME").unwrap_or("localhost");
+    // sudo logs without a pid in auth.log, and the parser matches on "sudo:"
+    let line = match field("_PID") {
+        Some(pid) if ident != "sudo" => format!("{} {}[{}]: {}", host, ident, pid, message),
+        _ => format!("{} {}: {}", host, ident, message),
+    };
+
+    parse_auth_message(&line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::AuthEventType;
+
+    #[test]
+    fn test_parse_journal_record_ssh_failure() {
+        let record = serde_json::json!({
+            "__CURSOR": "s=abc;i=1",
+            "_HOSTNAME": "server",
+            "SYSLOG_IDENTIFIER": "sshd-session",
+            "_PID": "4242",
+            "MESSAGE": "Failed password for root from 203.0.113.7 port 50022 ssh2",
+        });
+        let entry = parse_journal_record(&record).unwrap();
+
+        assert_eq!(entry.event_type, AuthEventType::SshFailure);
+        assert_eq!(entry.user, "root");
+        assert_eq!(entry.source_ip, Some("203.0.113.7".to_string()));
+        assert_eq!(entry.message, "server sshd-session[4242]: Failed password for root from 203.0.113.7 port 50022 ssh2");
+    }
+
+    #[test]
+    fn test_parse_journal_record_sudo() {
+        let record = serde_json::json!({
+            "SYSLOG_IDENTIFIER": "sudo",
+            "_PID": "900",
+            "MESSAGE": "ubuntu : TTY=pts/0 ; PWD=/home/ubuntu ; USER=root ; COMMAND=/usr/bin/apt update",
+        });
+        let entry = parse_journal_record(&record).unwrap();
+
+        assert_eq!(entry.event_type, AuthEventType::SudoCommand);
+        assert_eq!(entry.user, "ubuntu");
+    }
+
+    #[test]
+    fn test_parse_journal_record_ignores_unrelated_and_binary_messages() {
+        let unrelated = serde_json::json!({
+            "SYSLOG_IDENTIFIER": "sshd",
+            "MESSAGE": "Server listening on 0.0.0.0 port 22.",
+        });
+        assert!(parse_journal_record(&unrelated).is_none());
+
+        // journalctl emits non-UTF-8 messages as byte arrays
+        let binary = serde_json::json!({
+            "SYSLOG_IDENTIFIER": "sshd",
+            "MESSAGE": [70, 97, 105, 108],
+        });
+        assert!(parse_journal_record(&binary).is_none());
+    }
+
+    #[test]
+    fn test_save_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let tail = JournalTail {
+            cursor_path: dir.path().join(CURSOR_FILE),
+            cursor: None,
+            since: 0,
+        };
+        tail.save_cursor("s=abc;i=2a").unwrap();
+
+        let saved = fs::read_to_string(dir.path().join(CURSOR_FILE)).unwrap();
+        assert_eq!(saved, "s=abc;i=2a");
+    }
+}
@@ -1,24 +1,43 @@
 #![recursion_limit = "256"]
 
+mod alerts;
+mod anomaly;
 mod broadcast;
 mod cli;
 mod collector;
+mod collector_health;
 mod commands;
 mod config;
+mod crash_loop;
+mod downsample;
 mod event;
 mod file_watcher;
 mod index;
 mod indexed_reader;
+mod integrity;
+mod journal;
+mod proc_connector;
+mod privileges;
+mod process_delta;
 mod protection;
+mod raid;
 mod reader;
 mod recorder;
+mod reload;
+mod services;
+mod smart;
 mod storage;
+mod summary;
+mod syslog;
+mod timezone;
+mod traffic;
 mod webui;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::{
+    path::Path,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     thread,
@@ -26,42 +45,49 @@ use std::{
 };
 use time::OffsetDateTime;
 
+use anomaly::{AnomalyDetector, MetricsSample};
 use broadcast::EventBroadcaster;
 use cli::{Cli, Commands};
-use config::{Config, ProtectionMode, RemoteSyslogConfig};
+use collector_health::{CollectorHealth, SharedCollectorHealth};
+use config::{Config, ProtectionMode, RemoteSyslogConfig, SegmentAttribute};
 use protection::ProtectionManager;
 
 use collector::{
     check_group_changes, check_kernel_module_changes, check_listening_port_changes,
-    check_passwd_changes, check_sudoers_changes, check_cron_changes, check_systemd_changes,
+    check_passwd_changes, refresh_uid_names, check_sudoers_changes, check_cron_changes, check_systemd_changes,
+    check_dns_changes, check_gateway_changes,
     detect_package_manager_operation,
     diff_processes, get_default_gateway,
     get_dns_server, get_primary_ip_address, get_top_processes, read_all_cpu_stats,
     read_all_filesystems, read_context_switches, read_disk_space, read_disk_stats_per_device,
     read_disk_temperatures, read_fan_speeds, read_load_avg, read_logged_in_users,
-    read_memory_stats, read_network_stats, read_per_core_temperatures, read_processes,
+    per_interface_rates, read_memory_stats, read_network_stats,
+    read_network_stats_per_interface, read_per_core_temperatures, read_processes,
     read_swap_stats, read_tcp_stats, read_temperatures, tail_auth_log, AuthEventType,
     ConnectionTracker,
 };
 use event::{
-    Anomaly, AnomalyKind, AnomalySeverity, Event, FilesystemInfo, LoggedInUserInfo,
-    Metadata, PerDiskMetrics, ProcessInfo, ProcessLifecycle, ProcessLifecycleKind,
-    ProcessSnapshot as EventProcessSnapshot, SecurityEvent, SecurityEventKind, SystemMetrics,
+    Anomaly, AnomalyKind, AnomalySeverity, DiskHealthStatus, Event, FilesystemInfo, RaidArray, GpuInfo, LoggedInUserInfo,
+    FilesystemStatus, Metadata, SocketMetrics, PerDiskMetrics, PerInterfaceMetrics, InterfacePacketRates, ProcessInfo, ProcessLifecycle, ProcessLifecycleKind,
+    ProcessSnapshot as EventProcessSnapshot, SecurityEvent, SecurityEventKind, SystemMetrics, TcpListenDrops,
     TemperatureReadings,
 };
 use recorder::Recorder;
 
 const COLLECTION_INTERVAL_SECS: u64 = 1;
-const TOP_PROCESSES_COUNT: usize = 10;
 const PROCESS_SNAPSHOT_INTERVAL: u64 = 5; // Snapshot top processes every 5 seconds
 const SECURITY_CHECK_INTERVAL: u64 = 5; // Check security events every 5 seconds
 const TEMPERATURE_CHECK_INTERVAL: u64 = 60; // Check temperatures every 60 seconds
 const FILESYSTEM_CHECK_INTERVAL: u64 = 30; // Check filesystems every 30 seconds
 const NETWORK_CONFIG_CHECK_INTERVAL: u64 = 30; // Check network config every 30 seconds
+const PROTECTION_CHECK_INTERVAL: u64 = 30; // Check segment attributes every 30 seconds
+const SEGMENT_VERIFY_INTERVAL: u64 = 300; // Verify the newest sealed segment every 5 minutes when protected
+const FD_LIMIT_CHECK_INTERVAL: u64 = 30; // Check per-process open file limits every 30 seconds
+const RAID_CHECK_INTERVAL: u64 = 10; // Check software RAID arrays every 10 seconds
 
-/// Format current time as HH:MM:SS.mmm
+/// Format current time as HH:MM:SS.mmm in the configured timezone
 fn now_timestamp() -> String {
-    let now = OffsetDateTime::now_utc();
+    let now = timezone::to_local(OffsetDateTime::now_utc());
     format!(
         "{:02}:{:02}:{:02}.{:03}",
         now.hour(),
@@ -151,6 +177,14 @@ fn update_metadata_if_changed(
                 cached.gpu = Some(metrics.gpu.clone());
                 updated = true;
             }
+            if metrics.gpus.is_some() && metrics.gpus != cached.gpus {
+                cached.gpus = metrics.gpus.clone();
+                updated = true;
+            }
+            if metrics.filesystem_status.is_some() && metrics.filesystem_status != cached.filesystem_status {
+                cached.filesystem_status = metrics.filesystem_status.clone();
+                updated = true;
+            }
             if metrics.logged_in_users.is_some() && metrics.logged_in_users != cached.logged_in_users {
                 cached.logged_in_users = metrics.logged_in_users.clone();
                 updated = true;
@@ -188,7 +222,7 @@ fn update_process_metadata(
 }
 
 fn main() -> Result<()> {
-    use cli::{Cli, Commands, ConfigCommands, SystemdCommands};
+    use cli::{AlertsCommands, Cli, Commands, ConfigCommands, SystemdCommands};
 
     let cli = Cli::parse_args();
 
@@ -201,11 +235,25 @@ fn main() -> Result<()> {
             event_type,
             start,
             end,
+            severity,
+            pattern,
+            ndjson,
             data_dir,
+            verify,
+            local_time,
         }) => {
-            return commands::export::run_export(
-                output, format, compress, event_type, start, end, data_dir,
-            );
+            let filter = commands::export::ExportFilter { event_type, start, end, severity, pattern };
+            let format = if ndjson { cli::ExportFormat::Jsonl } else { format };
+            return commands::export::run_export(output, format, compress, filter, data_dir, verify, local_time);
+        }
+        Some(Commands::Query { data_dir, start, end, event_type, grep, json }) => {
+            return commands::query::run_query(data_dir, start, end, event_type, grep, json);
+        }
+        Some(Commands::Verify { data_dir }) => {
+            return commands::verify::run_verify(data_dir);
+        }
+        Some(Commands::Migrate { data_dir }) => {
+            return commands::migrate::run_migrate(data_dir);
         }
         Some(Commands::Monitor) => {
             // Run headless recorder (no web UI)
@@ -213,23 +261,65 @@ fn main() -> Result<()> {
         }
         Some(Commands::Watch {
             url,
+            socket,
             username,
             password,
             interval,
             export_dir,
             continuous,
+            insecure,
+            follow,
+            only,
+            min_severity,
+            filter,
         }) => {
-            return commands::monitor::run_monitor(
-                url, username, password, interval, export_dir, continuous,
-            );
+            if follow {
+                if socket.is_some() {
+                    anyhow::bail!("--follow streams over a WebSocket and doesn't support --socket yet, give the server URL instead");
+                }
+                let filter = commands::monitor::FollowFilter::new(only, min_severity, filter);
+                let output = commands::monitor::FollowOutput::new(filter, false);
+                return commands::monitor::run_follow(url, username, password, insecure, output);
+            }
+            let server = commands::ServerApi::new(&url, socket, std::time::Duration::from_secs(10), insecure)?;
+            return commands::monitor::run_monitor(server, username, password, interval, export_dir, continuous);
+        }
+        Some(Commands::Tail { url, data_dir, username, password, insecure, event_type, filter, json }) => {
+            let filter = commands::monitor::FollowFilter::new(event_type, None, filter);
+            let output = commands::monitor::FollowOutput::new(filter, json);
+            return match data_dir {
+                Some(data_dir) => commands::tail::run_tail_local(&data_dir, output),
+                None => commands::monitor::run_follow(url, username, password, insecure, output),
+            };
         }
         Some(Commands::Status {
             url,
+            socket,
             username,
             password,
             format,
+            insecure,
+            check,
+            max_event_age,
+            critical_window,
+            cpu_warn,
+            cpu_crit,
+            mem_warn,
+            mem_crit,
+            disk_warn,
+            disk_crit,
         }) => {
-            return commands::status::run_status(url, username, password, format);
+            let check = check.then_some(commands::status::CheckThresholds {
+                max_event_age_secs: max_event_age,
+                critical_window_minutes: critical_window,
+            });
+            let thresholds = commands::status::MetricThresholds {
+                cpu: commands::status::Threshold { warn: cpu_warn, crit: cpu_crit },
+                mem: commands::status::Threshold { warn: mem_warn, crit: mem_crit },
+                disk: commands::status::Threshold { warn: disk_warn, crit: disk_crit },
+            };
+            let server = commands::ServerApi::new(&url, socket, std::time::Duration::from_secs(5), insecure)?;
+            return commands::status::run_status(server, username, password, format, check, thresholds);
         }
         Some(Commands::Systemd { command }) => match command {
             SystemdCommands::Generate {
@@ -271,8 +361,19 @@ fn main() -> Result<()> {
             ConfigCommands::Init { force } => {
                 return commands::config::init_config(force);
             }
-            ConfigCommands::SetupRemote { host, port, protocol } => {
-                return commands::config::setup_remote_syslog(host, port, protocol);
+            ConfigCommands::SetupRemote(args) => {
+                return commands::config::setup_remote_syslog(args);
+            }
+            ConfigCommands::SetupEmail(args) => {
+                return commands::config::setup_email(args);
+            }
+            ConfigCommands::AddToken { name } => {
+                return commands::config::add_token(name);
+            }
+        },
+        Some(Commands::Alerts { command }) => match command {
+            AlertsCommands::Test => {
+                return commands::alerts::test_alerts();
             }
         },
         None => {
@@ -297,12 +398,17 @@ fn run_recorder(cli: Cli) -> Result<()> {
     // Check for headless mode (monitor command runs without web UI)
     let disable_ui = matches!(cli.command, Some(Commands::Monitor));
 
-    // Load configuration
-    let config = Config::load()?;
+    // Load configuration, sections a reload can change are updated in place
+    let mut config = Config::load()?;
+    if let Some(max_storage_mb) = cli.max_storage {
+        config.server.override_storage_limit(max_storage_mb);
+        config.server.validate().context("Invalid --max-storage")?;
+    }
+    timezone::init(config.server.timezone.as_deref());
 
     // Create protection manager
-    let mut protection_manager = ProtectionManager::new(protection_mode, config.protection.clone());
-    protection_manager.print_info();
+    let protection_manager = ProtectionManager::new(protection_mode, config.protection.clone());
+    let run_as_user = config.protection.run_as_user.as_deref().map(privileges::lookup_user).transpose()?;
 
     // Parse port (command line overrides config)
     let port = cli.port.unwrap_or(config.server.port);
@@ -321,7 +427,7 @@ fn run_recorder(cli: Cli) -> Result<()> {
     let initial_cpu_snapshot = read_all_cpu_stats()?;
     let num_cores = initial_cpu_snapshot.per_core.len();
     let per_core_temps = read_per_core_temperatures(num_cores);
-    let gpu_info = collector::read_gpu_info();
+    let gpus = collector::read_gpus();
     let logged_in_users_list = read_logged_in_users().ok().map(|users| {
         users.into_iter().map(|u| event::LoggedInUserInfo {
             username: u.username,
@@ -361,7 +467,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             gpu_temp_celsius: temps.gpu_temp_celsius,
             motherboard_temp_celsius: temps.motherboard_temp_celsius,
         }),
-        gpu: Some(gpu_info),
+        gpu: Some(gpus.first().map(GpuInfo::from).unwrap_or_default()),
+        gpus: Some(gpus),
+        filesystem_status: None, // Set by the first check against the thresholds
         logged_in_users: logged_in_users_list,
         processes: None,
         total_processes: None,
@@ -370,17 +478,117 @@ fn run_recorder(cli: Cli) -> Result<()> {
     };
 
     let shared_metadata = Arc::new(std::sync::RwLock::new(Some(initial_metadata)));
+    let shared_collector_health = SharedCollectorHealth::default();
 
     // Create broadcast channel for event streaming
     let (broadcast_tx, broadcaster) = EventBroadcaster::new();
 
-    // Start async services (web server and remote streaming)
-    if !disable_ui || config.protection.remote_syslog.as_ref().map(|c| c.enabled).unwrap_or(false) {
+    // Annotations posted to the web UI, recorded each interval
+    let (annotation_tx, annotation_rx) = crossbeam_channel::unbounded();
+    // Processes signalled from the web UI, recorded the same way
+    let (action_tx, action_rx) = crossbeam_channel::unbounded();
+    // Failed web UI logins, recorded the same way
+    let (security_tx, security_rx) = crossbeam_channel::unbounded();
+    // Email and webhook deliveries, recorded in collector health
+    let (email_delivery_tx, email_delivery_rx) = crossbeam_channel::unbounded();
+    let (webhook_delivery_tx, webhook_delivery_rx) = crossbeam_channel::unbounded();
+    // Reloads asked for from the web UI
+    let (reload_tx, reload_rx) = crossbeam_channel::unbounded();
+    // Anomalies raised by remote streaming, recorded each interval
+    let (remote_event_tx, remote_event_rx) = crossbeam_channel::unbounded();
+    // Settings for the async services, replaced when a reload changes them
+    let (remote_syslog_tx, remote_syslog_rx) =
+        tokio::sync::watch::channel(reload::remote_syslog_settings(&config, protection_mode));
+    let (email_tx, email_rx) = tokio::sync::watch::channel(reload::email_settings(&config));
+    let (webhook_tx, webhook_rx) = tokio::sync::watch::channel(reload::webhook_settings(&config));
+
+    // Load TLS up front so a bad certificate stops startup instead of failing in the server thread
+    let tls_config = if disable_ui {
+        None
+    } else {
+        webui::load_tls_config(&config.server)?
+    };
+    let web_scheme = if tls_config.is_some() { "https" } else { "http" };
+
+    // Bind the web port or socket up front, it may be privileged and root is given up before
+    // the server starts. Failing to bind stops startup.
+    let listener = if disable_ui {
+        None
+    } else {
+        Some(webui::WebListener::bind(&config.server, port).context("Failed to start web UI")?)
+    };
+    let web_target = listener.as_ref().map(|listener| listener.describe(web_scheme));
+
+    // Clone broadcast_tx for file watcher before moving into recorder
+    let file_watcher_tx = broadcast_tx.clone();
+
+    // Size/age limits for the ring buffer
+    let retention = config.server.retention_policy();
+
+    // Run recorder in main thread with broadcasting
+    let signing_key = integrity::SigningKey::from_config(&config.protection);
+    let segments_signed = signing_key.is_some();
+    if protection_manager.is_enabled() || run_as_user.is_some() {
+        // Segments a crashed run left protected can't be recovered or handed over
+        protection::clear_leftover_protection(Path::new(&data_dir));
+    }
+    let mut recorder = Recorder::open_with_config(&data_dir, retention, Some(broadcast_tx), signing_key)?
+        .with_compression(config.segment_compression_level())
+        .with_segment_size(config.server.segment_size_bytes())
+        .with_fsync(config.server.fsync)
+        .with_downsampling(config.server.downsample_policy());
+    if let Some(user) = &run_as_user {
+        privileges::chown_data_dir(Path::new(&data_dir), user)?;
+    }
+
+    // Protect the existing segments, and each new one as it is created
+    let protection_enabled = protection_manager.is_enabled();
+    let sealed_attribute = protection_manager.sealed_attribute();
+    let protection_error = recorder.enable_protection(protection_manager).err();
+    if let Some(e) = &protection_error {
+        eprintln!("Warning: Segment protection is NOT active: {:#}", e);
+        eprintln!("  Needs root (or CAP_LINUX_IMMUTABLE) and a filesystem with file attributes, such as ext4");
+    }
+
+    // Mark a reboot or an unclean stop since the last recording
+    match collector::read_boot_time() {
+        Ok(boot_time) => recorder.record_startup(boot_time)?,
+        Err(e) => eprintln!("Warning: Could not read boot time, reboots won't be marked: {}", e),
+    }
+
+    // Event-driven process tracking, if configured and permitted
+    let netlink_connector = if config.exec_tracing() {
+        match proc_connector::ProcConnector::open() {
+            Ok(connector) => Some(connector),
+            Err(e) => {
+                eprintln!("Warning: {:#}. Exec tracing needs root or CAP_NET_ADMIN, falling back to polling", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Give up root now that the port is bound and the data directory is open, before any
+    // other thread starts so they all run as the new user
+    if let Some(user) = &run_as_user {
+        let mut keep = vec![privileges::CAP_DAC_READ_SEARCH, privileges::CAP_SYS_PTRACE];
+        if protection_enabled && protection_error.is_none() {
+            keep.push(privileges::CAP_LINUX_IMMUTABLE);
+        }
+        privileges::drop_privileges(user, &keep)
+            .with_context(|| format!("Failed to switch to user {}", user.name))?;
+    }
+
+    // Start async services (web server, remote streaming, email and webhook alerts). They always
+    // run, a reload can turn remote streaming or alerts on.
+    {
         let data_dir_clone = data_dir.clone();
         let config_clone = config.clone();
         let broadcaster = Arc::new(broadcaster);
-        let protection_config = config.protection.clone();
         let metadata_clone = shared_metadata.clone();
+        let collector_health_clone = shared_collector_health.clone();
+        let remote_status: webui::RemoteStatus = Default::default();
 
         // Spawn Tokio runtime in background thread
         std::thread::spawn(move || {
@@ -398,56 +606,56 @@ fn run_recorder(cli: Cli) -> Result<()> {
 
             // Start async services in background
             rt.block_on(async {
-                // Start remote streaming if configured
-                if let Some(ref syslog_config) = protection_config.remote_syslog {
-                    if syslog_config.enabled && protection_mode != ProtectionMode::Default {
-                        let broadcaster_clone = broadcaster.clone();
-                        let syslog_config = syslog_config.clone();
-                        tokio::spawn(async move {
-                            start_remote_streaming(broadcaster_clone, syslog_config).await;
-                        });
-                    }
-                }
+                tokio::spawn(run_remote_streaming(
+                    broadcaster.clone(),
+                    remote_syslog_rx,
+                    data_dir_clone.clone(),
+                    remote_status.clone(),
+                    remote_event_tx,
+                ));
+                tokio::spawn(alerts::run_email_alerts(broadcaster.clone(), email_rx, email_delivery_tx));
+                tokio::spawn(alerts::run_webhook_alerts(broadcaster.clone(), webhook_rx, webhook_delivery_tx));
 
                 // Start web server if not disabled
-                if !disable_ui {
+                if let Some(listener) = listener {
+                    let link = webui::CollectorLink {
+                        metadata: metadata_clone,
+                        collector_health: collector_health_clone,
+                        remote_status,
+                        annotations: annotation_tx,
+                        actions: action_tx,
+                        security: security_tx,
+                        reload: reload_tx,
+                    };
                     if let Err(e) =
-                        webui::start_server(data_dir_clone, port, broadcaster, config_clone, metadata_clone).await
+                        webui::start_server(data_dir_clone, listener, broadcaster, config_clone, tls_config, link).await
                     {
                         eprintln!("Web UI failed to start: {}", e);
                     }
                 } else {
-                    // Keep runtime alive for remote streaming
+                    // The web server runs the broadcaster bridge when there is one
+                    let bridge = (*broadcaster).clone();
+                    tokio::spawn(async move {
+                        bridge.run().await;
+                    });
+                    // Keep runtime alive for remote streaming and alerts
                     tokio::signal::ctrl_c().await.ok();
                 }
             });
         });
     }
 
-    // Clone broadcast_tx for file watcher before moving into recorder
-    let file_watcher_tx = broadcast_tx.clone();
-
-    // Calculate max segments from configured storage size
-    // Each segment is 8MB, so max_segments = max_storage_mb / 8
-    let max_segments = (config.server.max_storage_mb / 8).max(1) as usize;
-
-    // Run recorder in main thread with broadcasting
-    let mut recorder = Recorder::open_with_config(&data_dir, max_segments, Some(broadcast_tx))?;
-
     // Start file watcher if configured
-    if config.file_watch.enabled && !config.file_watch.watch_dirs.is_empty() {
-        let watch_dirs = config.file_watch.watch_dirs.clone();
-        file_watcher::spawn_file_watcher(watch_dirs, file_watcher_tx)?;
-    }
-
-    // Protect existing segment files
-    if let Ok(entries) = std::fs::read_dir(&data_dir) {
-        for entry in entries.flatten() {
-            if entry.path().extension().and_then(|s| s.to_str()) == Some("seg") {
-                let _ = protection_manager.protect_file(&entry.path());
-            }
-        }
-    }
+    let file_watcher = reload::start_file_watcher(&config, &file_watcher_tx)?;
+    let mut live_services = reload::LiveServices {
+        protection_mode,
+        file_watcher,
+        file_events: file_watcher_tx,
+        remote_syslog: remote_syslog_tx,
+        email: email_tx,
+        webhook: webhook_tx,
+        storage_override: cli.max_storage,
+    };
 
     println!("┌─────────────┐");
     println!("│  Black Box  │");
@@ -458,12 +666,62 @@ fn run_recorder(cli: Cli) -> Result<()> {
         ProtectionMode::Protected => "PROTECTED",
         ProtectionMode::Hardened => "HARDENED",
     });
+    if protection_enabled && protection_error.is_none() {
+        println!("Protection: append-only segments, {} once full", match sealed_attribute {
+            SegmentAttribute::AppendOnly => "append-only",
+            SegmentAttribute::Immutable => "immutable",
+        });
+    }
+    if let Some(user) = &run_as_user {
+        println!("Running as: {} (uid {})", user.name, user.uid);
+    }
     println!("Data directory: {}", data_dir);
-    println!("Max storage: ~{}MB (ring buffer)", config.server.max_storage_mb);
+    println!(
+        "Max storage: ~{} (ring buffer of {} segments)",
+        format_bytes(retention.max_bytes),
+        format_bytes(config.server.segment_size_bytes())
+    );
+    if let Some(days) = config.server.max_retention_days {
+        println!("Max retention: {} days", days);
+    }
+    if let Some(hours) = config.server.downsample_after_hours {
+        if protection_enabled && protection_error.is_none() {
+            println!("Downsampling: off, protected segments are kept as recorded");
+        } else {
+            println!("Downsampling: metrics older than {}h kept as {}s averages", hours, config.server.downsample_window_secs);
+        }
+    }
     println!("Collection interval: {}s", COLLECTION_INTERVAL_SECS);
+    println!("Timezone: {}", timezone::name());
     println!("Tracking: CPU, Memory, Swap, Disk, Network, TCP, Load, Temperature, Processes");
-    if !disable_ui {
-        println!("Web UI: http://localhost:{}", port);
+    if segments_signed {
+        println!("Integrity: CRC32 and HMAC-SHA256 chain per record (sign_events)");
+    } else {
+        println!("Integrity: CRC32 per record (set sign_events and signing_key to detect tampering)");
+    }
+    if netlink_connector.is_some() {
+        println!("Process tracking: exec tracing with the netlink proc connector, and polling /proc every {}s", COLLECTION_INTERVAL_SECS);
+    } else {
+        println!("Process tracking: polling /proc every {}s (shorter-lived processes are missed)", COLLECTION_INTERVAL_SECS);
+    }
+    if config.services.enabled {
+        println!("Services: systemd failures and restarts every {}s", config.services.interval_secs);
+    }
+    if config.traffic_accounting.enabled {
+        println!(
+            "Traffic accounting: bytes per remote {} every {}s (connection counts without ss)",
+            match config.traffic_accounting.group_by {
+                config::TrafficGrouping::Subnet => "subnet",
+                config::TrafficGrouping::Host => "host",
+            },
+            config.traffic_accounting.interval_secs
+        );
+    }
+    if config.smart.enabled {
+        println!("Disk health: smartctl every {} minutes (skipped without smartctl or root)", config.smart.interval_minutes);
+    }
+    if let Some(target) = &web_target {
+        println!("Web UI: {}", target);
         if config.auth.enabled {
             println!("Auth: Enabled (username: {})", config.auth.username);
         } else {
@@ -479,12 +737,49 @@ fn run_recorder(cli: Cli) -> Result<()> {
     let mut prev_cpu_snapshot = read_all_cpu_stats()?;
     let mut prev_disk_snapshot = read_disk_stats_per_device()?;
     let mut prev_network = read_network_stats()?;
+    let mut prev_interfaces = read_network_stats_per_interface()?;
     let mut prev_ctxt = read_context_switches()?;
+    let mut prev_sockets = collector::read_socket_stats().ok();
+    let mut process_origins = collector::ProcessOrigins::default();
+    let process_filter = collector::ProcessFilter::new(&config.process_tracking.ignore_processes);
+    let top_processes_count = config.process_tracking.top_processes;
+    let mut crash_loops = crash_loop::CrashLoopTracker::new(
+        config.process_tracking.crash_loop_threshold,
+        config.process_tracking.crash_loop_window_secs,
+    );
     let mut prev_processes = read_processes()?;
+    process_origins.apply(&mut prev_processes);
+
+    // Starts and exits from the proc connector thread, drained each interval. The /proc diff
+    // still runs beside it for anything the kernel drops, and the two are matched up.
+    let (proc_event_tx, proc_event_rx) = crossbeam_channel::unbounded();
+    let mut lifecycle_dedup = netlink_connector.is_some().then(proc_connector::LifecycleDedup::default);
+    if let Some(connector) = netlink_connector {
+        proc_connector::spawn_proc_connector(connector, prev_processes.clone(), process_filter.clone(), proc_event_tx);
+    }
+
+    // SMART readings, service state changes and their anomalies, drained each interval
+    let (monitor_event_tx, monitor_event_rx) = crossbeam_channel::unbounded();
+    // Latest SMART reading per disk, sent with every SystemMetrics
+    let mut disk_health: std::collections::BTreeMap<String, DiskHealthStatus> = std::collections::BTreeMap::new();
+    if config.smart.enabled {
+        smart::spawn_smart_monitor(config.smart.clone(), monitor_event_tx.clone());
+    }
+    if config.services.enabled {
+        services::spawn_service_monitor(config.services.clone(), monitor_event_tx);
+    }
+
+    // Bytes per remote host over the last interval, named in network spike anomalies
+    let remote_traffic = config
+        .traffic_accounting
+        .enabled
+        .then(|| traffic::spawn_traffic_accounting(config.traffic_accounting.clone()));
 
     // Initialize security monitoring
     let mut auth_log_position = 0u64;
-    let mut connection_tracker = ConnectionTracker::new();
+    // Prefer the journal, falling back to tailing auth.log/secure without journalctl
+    let mut journal_tail = journal::JournalTail::open(&data_dir);
+    let mut connection_tracker = ConnectionTracker::new(&config.security);
     let mut prev_logged_in_users: std::collections::HashMap<String, String> =
         std::collections::HashMap::new();
 
@@ -492,9 +787,12 @@ fn run_recorder(cli: Cli) -> Result<()> {
     let mut failed_logins: std::collections::HashMap<String, Vec<std::time::Instant>> =
         std::collections::HashMap::new();
 
-    // Track process CPU times for per-process CPU percentage calculation
-    let mut prev_process_cpu: std::collections::HashMap<u32, (u64, std::time::Instant)> =
-        std::collections::HashMap::new();
+    // Failures of each data source, so one that stops working raises an anomaly
+    let mut collector_health = CollectorHealth::new();
+
+    // Track process CPU times and I/O counters for per-process rates between snapshots
+    let mut prev_process_samples = collector::ProcessSamples::new();
+    let mut prev_own_write_bytes = collector::read_own_write_bytes();
 
     // Cached values for less frequent checks
     let mut cached_temps = read_temperatures();
@@ -502,6 +800,11 @@ fn run_recorder(cli: Cli) -> Result<()> {
     let mut cached_disk_temps = std::collections::HashMap::new();
     let mut cached_fans = Vec::new();
     let mut cached_filesystems = read_all_filesystems().unwrap_or_default();
+    let mut cached_filesystem_status: Vec<FilesystemStatus> = Vec::new();
+    // Software RAID, never read on hosts without the md driver
+    let raid_present = Path::new(raid::MDSTAT_PATH).exists();
+    let mut raid_monitor = raid::RaidMonitor::default();
+    let mut cached_raid_arrays: Vec<RaidArray> = Vec::new();
     let mut cached_net_ip = get_primary_ip_address();
     let mut cached_net_gateway = get_default_gateway();
     let mut cached_net_dns = get_dns_server();
@@ -532,15 +835,18 @@ fn run_recorder(cli: Cli) -> Result<()> {
     const SEMI_STATIC_FIELDS_INTERVAL: u64 = 60;  // 1 minute for semi-static fields
 
     // Thresholds for anomaly detection
-    let cpu_spike_threshold = 90.0;
-    let mem_spike_threshold = 90.0;
-    let swap_usage_threshold = 50.0; // Start warning if swap is used
-    let disk_full_threshold = 90.0;
-    let disk_spike_threshold = 100 * 1024 * 1024; // 100 MB/s
-    let network_spike_threshold = 500 * 1024 * 1024; // 500 MB/s
-    let ctxt_spike_threshold = 50000; // 50k context switches per second
+    let mut anomaly_detector = AnomalyDetector::new(config.anomalies.clone());
 
-    loop {
+    // Stop cleanly on SIGINT/SIGTERM so the current segment is flushed
+    let shutdown = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = shutdown.clone();
+        ctrlc::set_handler(move || shutdown.store(true, Ordering::SeqCst))?;
+    }
+    // SIGHUP reloads config.toml, like POST /api/reload
+    reload::install_sighup_handler()?;
+
+    while !shutdown.load(Ordering::SeqCst) {
         let loop_start = std::time::Instant::now();
         tick_count += 1;
 
@@ -549,6 +855,8 @@ fn run_recorder(cli: Cli) -> Result<()> {
         let per_core_usage = cpu_snapshot.per_core_usage(&prev_cpu_snapshot);
         let num_cpus = per_core_usage.len() as f32;
         let cpu_usage = cpu_snapshot.aggregate.usage_percent(&prev_cpu_snapshot.aggregate);
+        let core_freqs = collector::read_core_frequencies();
+        let cpu_freq_percent_of_max = core_freqs.as_deref().and_then(collector::freq_percent_of_max);
 
         // Disk stats
         let disk_snapshot = read_disk_stats_per_device()?;
@@ -558,6 +866,12 @@ fn run_recorder(cli: Cli) -> Result<()> {
         );
         let (disk_read_per_sec, disk_write_per_sec) =
             disk_snapshot.total.bytes_per_sec(&prev_disk_snapshot.total, COLLECTION_INTERVAL_SECS as f32);
+        let own_write_bytes = collector::read_own_write_bytes();
+        let own_write_per_sec = match (prev_own_write_bytes, own_write_bytes) {
+            (Some(prev), Some(current)) => current.saturating_sub(prev) / COLLECTION_INTERVAL_SECS,
+            _ => 0,
+        };
+        prev_own_write_bytes = own_write_bytes;
 
         // Other existing stats
         let mem_stats = read_memory_stats()?;
@@ -565,15 +879,26 @@ fn run_recorder(cli: Cli) -> Result<()> {
         let disk_space = read_disk_space()?;
         let load_avg = read_load_avg()?;
         let network_stats = read_network_stats()?;
+        let interface_stats = read_network_stats_per_interface()?;
         let ctxt_stats = read_context_switches()?;
         let tcp_stats = read_tcp_stats()?;
-        let current_processes = read_processes()?;
+        let socket_stats = collector::read_socket_stats();
+        collector_health.record("socket_stats", &socket_stats);
+        let socket_stats = socket_stats.ok();
+        let kernel_limits = collector::read_kernel_limits();
+        collector_health.record("kernel_limits", &kernel_limits);
+        let kernel_limits = kernel_limits.ok();
+        // None on kernels without PSI, which isn't a failing collector
+        let pressure = collector::read_pressure();
+        let mut current_processes = read_processes()?;
+        process_origins.apply(&mut current_processes);
 
         // Update temperatures and fans periodically (less frequent)
         static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
         let temp_count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed) + 1;
         if temp_count % TEMPERATURE_CHECK_INTERVAL == 0 {
             cached_temps = read_temperatures();
+            collector_health.record("temperatures", &cached_temps.cpu_temp_celsius.ok_or("no CPU temperature sensor could be read"));
             cached_per_core_temps = read_per_core_temperatures(per_core_usage.len());
             cached_disk_temps = read_disk_temperatures();
             cached_fans = read_fan_speeds();
@@ -588,6 +913,29 @@ fn run_recorder(cli: Cli) -> Result<()> {
             network_stats.drops_per_sec(&prev_network, COLLECTION_INTERVAL_SECS as f32);
         let net_interface = network_stats.primary_interface.clone();
 
+        // Build per-interface metrics
+        let interface_rates = per_interface_rates(&interface_stats, &prev_interfaces, COLLECTION_INTERVAL_SECS as f32);
+        let interface_packets: Vec<InterfacePacketRates> = interface_rates
+            .iter()
+            .map(|(name, rates)| InterfacePacketRates {
+                interface_name: name.clone(),
+                recv_packets_per_sec: rates.recv_packets,
+                send_packets_per_sec: rates.send_packets,
+            })
+            .collect();
+        let per_interface: Vec<PerInterfaceMetrics> = interface_rates
+            .into_iter()
+            .map(|(name, rates)| PerInterfaceMetrics {
+                interface_name: name,
+                recv_bytes_per_sec: rates.recv_bytes,
+                send_bytes_per_sec: rates.send_bytes,
+                recv_errors_per_sec: rates.recv_errors,
+                send_errors_per_sec: rates.send_errors,
+                recv_drops_per_sec: rates.recv_drops,
+                send_drops_per_sec: rates.send_drops,
+            })
+            .collect();
+
         // Update network config periodically (less frequent)
         static NET_CONFIG_COUNTER: AtomicU64 = AtomicU64::new(0);
         let net_config_count = NET_CONFIG_COUNTER.fetch_add(1, Ordering::Relaxed) + 1;
@@ -595,15 +943,126 @@ fn run_recorder(cli: Cli) -> Result<()> {
             cached_net_ip = get_primary_ip_address();
             cached_net_gateway = get_default_gateway();
             cached_net_dns = get_dns_server();
+
+            // DNS or gateway changes are typical of hijacking, ARP spoofing or DHCP trouble
+            let dns_changes = check_dns_changes();
+            collector_health.record("dns_config", &dns_changes);
+            if let Ok(Some((old, new))) = dns_changes {
+                let msg = format!(
+                    "DNS servers changed: [{}] -> [{}]",
+                    old.join(", "),
+                    new.join(", ")
+                );
+                let event = SecurityEvent {
+                    ts: OffsetDateTime::now_utc(),
+                    kind: SecurityEventKind::DnsConfigurationChanged,
+                    user: "system".to_string(),
+                    source_ip: None,
+                    message: msg.clone(),
+                };
+                recorder.append(&Event::SecurityEvent(event))?;
+                println!("{} [SEC] {}", now_timestamp(), msg);
+            }
+            if let Some((old, new)) = check_gateway_changes() {
+                let msg = format!(
+                    "Default gateway changed: {} -> {}",
+                    old.as_deref().unwrap_or("none"),
+                    new.as_deref().unwrap_or("none")
+                );
+                let event = SecurityEvent {
+                    ts: OffsetDateTime::now_utc(),
+                    kind: SecurityEventKind::DefaultGatewayChanged,
+                    user: "system".to_string(),
+                    source_ip: None,
+                    message: msg.clone(),
+                };
+                recorder.append(&Event::SecurityEvent(event))?;
+                println!("{} [SEC] {}", now_timestamp(), msg);
+            }
+        }
+
+        // Someone with root clearing a segment's attribute is the first step to editing it
+        static PROTECTION_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let protection_count = PROTECTION_COUNTER.fetch_add(1, Ordering::Relaxed) + 1;
+        if protection_count.is_multiple_of(PROTECTION_CHECK_INTERVAL) {
+            for msg in recorder.check_protection() {
+                let event = SecurityEvent {
+                    ts: OffsetDateTime::now_utc(),
+                    kind: SecurityEventKind::SegmentProtectionChanged,
+                    user: "system".to_string(),
+                    source_ip: None,
+                    message: msg.clone(),
+                };
+                recorder.append(&Event::SecurityEvent(event))?;
+                println!("{} [SEC] {}", now_timestamp(), msg);
+            }
+        }
+        if protection_enabled
+            && protection_count.is_multiple_of(SEGMENT_VERIFY_INTERVAL)
+            && let Some(anomaly) = recorder.verify_sealed_segment()
+        {
+            println!("{} [SEC] {}", now_timestamp(), anomaly.message);
+            recorder.append(&Event::Anomaly(anomaly))?;
         }
 
         let ctxt_per_sec = ctxt_stats.per_sec(&prev_ctxt, COLLECTION_INTERVAL_SECS as f32);
+        let socket_rates = socket_stats
+            .as_ref()
+            .zip(prev_sockets.as_ref())
+            .map(|(current, prev)| (current, current.per_sec(prev, COLLECTION_INTERVAL_SECS as f32)));
+        let tcp_listen_drops = socket_rates.as_ref().map(|(_, rates)| TcpListenDrops {
+            overflows_per_sec: rates.tcp_listen_overflows,
+            drops_per_sec: rates.tcp_listen_drops,
+            syn_drops_per_sec: rates.tcp_syn_drops,
+        });
+        let sockets = socket_rates.map(|(current, rates)| {
+            SocketMetrics {
+                tcp_in_segs_per_sec: rates.tcp_in_segs,
+                tcp_out_segs_per_sec: rates.tcp_out_segs,
+                tcp_retrans_per_sec: rates.tcp_retrans_segs,
+                udp_in_datagrams_per_sec: rates.udp_in_datagrams,
+                udp_out_datagrams_per_sec: rates.udp_out_datagrams,
+                udp_errors_per_sec: rates.udp_errors,
+                sockets_used: current.sockets_used,
+                tcp_orphaned: current.tcp_orphaned,
+                socket_mem_bytes: current.socket_mem_bytes,
+            }
+        });
 
-        // Update filesystems periodically (less frequent)
+        // Update filesystems periodically (less frequent) and check each mount's bytes and inodes
         static FS_COUNTER: AtomicU64 = AtomicU64::new(0);
         let fs_count = FS_COUNTER.fetch_add(1, Ordering::Relaxed) + 1;
-        if fs_count % FILESYSTEM_CHECK_INTERVAL == 0 {
-            cached_filesystems = read_all_filesystems().unwrap_or_default();
+        let mut filesystem_status_changed = false;
+        if fs_count == 1 || fs_count.is_multiple_of(FILESYSTEM_CHECK_INTERVAL) {
+            if fs_count > 1 {
+                let filesystems = read_all_filesystems();
+                collector_health.record("filesystems", &filesystems);
+                cached_filesystems = filesystems.unwrap_or_default();
+            }
+            let (anomalies, statuses) = anomaly_detector.check_filesystems(&cached_filesystems);
+            for anomaly in anomalies {
+                recorder.append(&Event::Anomaly(anomaly))?;
+            }
+            // A mount going critical is sent straight away rather than with the next semi-static fields
+            filesystem_status_changed = statuses
+                .iter()
+                .map(|s| (&s.mount_point, s.critical))
+                .ne(cached_filesystem_status.iter().map(|s| (&s.mount_point, s.critical)));
+            cached_filesystem_status = statuses;
+        }
+
+        let mut raid_changed = false;
+        if raid_present && (tick_count == 1 || tick_count.is_multiple_of(RAID_CHECK_INTERVAL)) {
+            let arrays = raid::read_mdstat();
+            collector_health.record("mdstat", &arrays);
+            if let Ok(arrays) = arrays {
+                for anomaly in raid_monitor.check(&arrays) {
+                    println!("{} [RAID] {}", now_timestamp(), anomaly.message);
+                    recorder.append(&Event::Anomaly(anomaly))?;
+                }
+                raid_changed = arrays != cached_raid_arrays;
+                cached_raid_arrays = arrays;
+            }
         }
 
         // Build per-disk metrics with temperatures
@@ -698,7 +1157,7 @@ fn run_recorder(cli: Cli) -> Result<()> {
         // Collect semi-static fields (every 5 minutes or on change)
         let net_interface_changed = net_interface != last_net_interface;
 
-        let opt_filesystems = if include_semi_static {
+        let opt_filesystems = if include_semi_static || filesystem_status_changed {
             Some(cached_filesystems
                 .iter()
                 .map(|fs| FilesystemInfo {
@@ -712,6 +1171,11 @@ fn run_recorder(cli: Cli) -> Result<()> {
         } else {
             None
         };
+        let opt_filesystem_status = opt_filesystems.as_ref().map(|_| cached_filesystem_status.clone());
+
+        // Sent straight away when an array changes, so rebuild progress shows as it happens
+        let opt_raid_arrays = (!cached_raid_arrays.is_empty() && (include_semi_static || raid_changed))
+            .then(|| cached_raid_arrays.clone());
 
         let opt_net_interface = if include_semi_static || net_interface_changed {
             last_net_interface = net_interface.clone();
@@ -750,6 +1214,7 @@ fn run_recorder(cli: Cli) -> Result<()> {
         };
 
         // Record system metrics
+        let gpus = collector::read_gpus();
         let system_metrics = SystemMetrics {
             ts: OffsetDateTime::now_utc(),
 
@@ -774,6 +1239,7 @@ fn run_recorder(cli: Cli) -> Result<()> {
             system_uptime_seconds: collector::read_system_uptime().unwrap_or(0),
             cpu_usage_percent: cpu_usage,
             per_core_usage,
+            per_core_freq_mhz: core_freqs.map(|freqs| freqs.iter().map(|f| f.cur_mhz).collect()),
             mem_used_bytes: mem_stats.used_kb() * 1024,
             mem_usage_percent: if cached_mem_total_for_pct > 0 {
                 ((mem_stats.used_kb() * 1024) as f64 / cached_mem_total_for_pct as f64 * 100.0) as f32
@@ -804,6 +1270,7 @@ fn run_recorder(cli: Cli) -> Result<()> {
             net_send_errors_per_sec,
             net_recv_drops_per_sec,
             net_send_drops_per_sec,
+            per_interface,
             tcp_connections: tcp_stats.total_connections,
             tcp_time_wait: tcp_stats.time_wait,
             context_switches_per_sec: ctxt_per_sec,
@@ -813,7 +1280,24 @@ fn run_recorder(cli: Cli) -> Result<()> {
                 gpu_temp_celsius: cached_temps.gpu_temp_celsius,
                 motherboard_temp_celsius: cached_temps.motherboard_temp_celsius,
             },
-            gpu: collector::read_gpu_info(),
+            gpu: gpus.first().map(GpuInfo::from).unwrap_or_default(),
+            mem_available_bytes: Some(mem_stats.available_kb * 1024),
+            mem_cached_bytes: Some(mem_stats.cached_kb * 1024),
+            mem_buffers_bytes: Some(mem_stats.buffers_kb * 1024),
+            mem_dirty_bytes: Some(mem_stats.dirty_kb * 1024),
+            mem_writeback_bytes: Some(mem_stats.writeback_kb * 1024),
+            hugepages_total: Some(mem_stats.hugepages_total),
+            hugepages_free: Some(mem_stats.hugepages_free),
+            gpus: Some(gpus),
+            filesystem_status: opt_filesystem_status,
+            sockets: sockets.clone(),
+            kernel_limits: kernel_limits.clone(),
+            interface_packets: Some(interface_packets),
+            pressure: pressure.clone(),
+            rollup: None,
+            disk_health: (!disk_health.is_empty()).then(|| disk_health.values().cloned().collect()),
+            raid_arrays: opt_raid_arrays,
+            tcp_listen_drops,
         };
 
         recorder.append(&Event::SystemMetrics(system_metrics.clone()))?;
@@ -822,7 +1306,79 @@ fn run_recorder(cli: Cli) -> Result<()> {
         update_metadata_if_changed(&shared_metadata, &system_metrics);
 
         // Track process lifecycle changes
-        let proc_diff = diff_processes(&prev_processes, &current_processes);
+        let mut proc_diff = diff_processes(&prev_processes, &current_processes, &process_filter);
+        for annotation in annotation_rx.try_iter() {
+            recorder.append(&Event::Annotation(annotation))?;
+        }
+        for action in action_rx.try_iter() {
+            println!("{} [ACT] {} sent {} to pid {}: {}", now_timestamp(), action.user, action.action, action.pid, action.outcome);
+            recorder.append(&Event::OperatorAction(action))?;
+        }
+        for event in security_rx.try_iter() {
+            println!("{} [SEC] {}", now_timestamp(), event.message);
+            recorder.append(&Event::SecurityEvent(event))?;
+        }
+        for event in monitor_event_rx.try_iter() {
+            if let Event::DiskHealth(health) = &event {
+                disk_health.insert(health.device.clone(), smart::status(health));
+            }
+            recorder.append(&event)?;
+        }
+        for event in remote_event_rx.try_iter() {
+            recorder.append(&event)?;
+        }
+
+        // Re-read config.toml, answering every web UI request waiting on it
+        let reload_replies: Vec<reload::ReloadReply> = reload_rx.try_iter().collect();
+        if reload::take_sighup() || !reload_replies.is_empty() {
+            let result = reload::reload(&mut config, &mut live_services, &mut anomaly_detector);
+            match &result {
+                Ok(summary) => {
+                    let event = reload::reloaded_event(summary);
+                    if let Event::SystemEvent(system) = &event {
+                        println!("{} [CFG] {}", now_timestamp(), system.message);
+                    }
+                    recorder.append(&event)?;
+                }
+                Err(e) => eprintln!("{} [CFG] Config not reloaded: {:#}", now_timestamp(), e),
+            }
+            let result = result.map_err(|e| format!("{:#}", e));
+            for reply in reload_replies {
+                let _ = reply.send(result.clone());
+            }
+        }
+
+        // Kernel events first, then whatever the /proc diff found that they didn't report
+        let now = std::time::Instant::now();
+        if let Some(dedup) = &mut lifecycle_dedup {
+            dedup.prune(now);
+        }
+        for proc_connector::KernelLifecycle { event, start_time } in proc_event_rx.try_iter() {
+            if let Some(dedup) = &mut lifecycle_dedup
+                && !dedup.is_new(event.pid, &event.kind, start_time, proc_connector::LifecycleSource::Kernel, now)
+            {
+                continue;
+            }
+            let started = matches!(event.kind, ProcessLifecycleKind::Started);
+            let (cmdline, user) = (event.cmdline.clone(), event.user.clone());
+            let crash_loop = crash_loops.observe(&event);
+            recorder.append(&Event::ProcessLifecycle(event))?;
+            if started {
+                record_package_operation(&mut recorder, &cmdline, user)?;
+            }
+            if let Some(anomaly) = crash_loop {
+                recorder.append(&Event::Anomaly(anomaly))?;
+            }
+        }
+
+        if let Some(dedup) = &mut lifecycle_dedup {
+            for (procs, kind) in [
+                (&mut proc_diff.started, ProcessLifecycleKind::Started),
+                (&mut proc_diff.exited, ProcessLifecycleKind::Exited),
+            ] {
+                procs.retain(|proc| dedup.is_new(proc.pid, &kind, proc.start_time, proc_connector::LifecycleSource::Poll, now));
+            }
+        }
 
         for proc in &proc_diff.started {
             let event = ProcessLifecycle {
@@ -836,29 +1392,11 @@ fn run_recorder(cli: Cli) -> Result<()> {
                 uid: proc.uid,
                 kind: ProcessLifecycleKind::Started,
                 exit_code: None,
+                container_id: proc.container_id.clone(),
+                container_name: proc.container_name.clone(),
             };
             recorder.append(&Event::ProcessLifecycle(event))?;
-
-            // Check for package manager operations
-            if let Some(pkg_op) = detect_package_manager_operation(&proc.cmdline) {
-                let kind = if pkg_op.operation == "install" {
-                    SecurityEventKind::PackageInstalled
-                } else {
-                    SecurityEventKind::PackageRemoved
-                };
-
-                let packages_str = pkg_op.packages.join(", ");
-                let event = SecurityEvent {
-                    ts: OffsetDateTime::now_utc(),
-                    kind,
-                    user: proc.user.clone().unwrap_or_else(|| "unknown".to_string()),
-                    source_ip: None,
-                    message: format!("{} {} packages: {}", pkg_op.package_manager, pkg_op.operation, packages_str),
-                };
-                recorder.append(&Event::SecurityEvent(event))?;
-                println!("{} [SEC] Package manager: {} {} {}",
-                    now_timestamp(), pkg_op.package_manager, pkg_op.operation, packages_str);
-            }
+            record_package_operation(&mut recorder, &proc.cmdline, proc.user.clone())?;
         }
 
         for proc in &proc_diff.exited {
@@ -872,9 +1410,16 @@ fn run_recorder(cli: Cli) -> Result<()> {
                 user: proc.user.clone(),
                 uid: proc.uid,
                 kind: ProcessLifecycleKind::Exited,
-                exit_code: None,  // Can't determine exit code without being parent
+                // Only known if we caught it as a zombie; otherwise it was reaped by its parent unseen
+                exit_code: proc.exit_code,
+                container_id: proc.container_id.clone(),
+                container_name: proc.container_name.clone(),
             };
+            let crash_loop = crash_loops.observe(&event);
             recorder.append(&Event::ProcessLifecycle(event))?;
+            if let Some(anomaly) = crash_loop {
+                recorder.append(&Event::Anomaly(anomaly))?;
+            }
         }
 
         for proc in &proc_diff.stuck {
@@ -889,6 +1434,8 @@ fn run_recorder(cli: Cli) -> Result<()> {
                 uid: proc.uid,
                 kind: ProcessLifecycleKind::Stuck,
                 exit_code: None,
+                container_id: proc.container_id.clone(),
+                container_name: proc.container_name.clone(),
             };
             recorder.append(&Event::ProcessLifecycle(event))?;
 
@@ -914,117 +1461,56 @@ fn run_recorder(cli: Cli) -> Result<()> {
                 uid: proc.uid,
                 kind: ProcessLifecycleKind::Zombie,
                 exit_code: None,
+                container_id: proc.container_id.clone(),
+                container_name: proc.container_name.clone(),
             };
             recorder.append(&Event::ProcessLifecycle(event))?;
         }
 
         // Anomaly detection
-        if cpu_usage > cpu_spike_threshold {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Warning,
-                kind: AnomalyKind::CpuSpike,
-                message: format!("CPU spike: {:.1}%", cpu_usage),
-            };
-            recorder.append(&Event::Anomaly(anomaly))?;
-        }
-
         let mem_usage_percent = mem_stats.usage_percent();
-        if mem_usage_percent > mem_spike_threshold {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Critical,
-                kind: AnomalyKind::MemorySpike,
-                message: format!("Memory spike: {:.1}%", mem_usage_percent),
-            };
+        let swap_usage_percent = if swap_stats.total_kb > 0 {
+            Some((swap_stats.used_kb() as f32 / swap_stats.total_kb as f32) * 100.0)
+        } else {
+            None
+        };
+        let sample = MetricsSample {
+            cpu_usage_percent: cpu_usage,
+            mem_usage_percent,
+            swap_usage_percent,
+            disk_write_bytes_per_sec: disk_write_per_sec,
+            own_write_bytes_per_sec: own_write_per_sec,
+            net_recv_bytes_per_sec: net_recv_per_sec,
+            net_send_bytes_per_sec: net_send_per_sec,
+            net_recv_errors_per_sec,
+            net_send_errors_per_sec,
+            net_recv_drops_per_sec,
+            net_send_drops_per_sec,
+            context_switches_per_sec: ctxt_per_sec,
+            cpu_freq_percent_of_max,
+            tcp_out_segs_per_sec: sockets.as_ref().map_or(0, |s| s.tcp_out_segs_per_sec),
+            tcp_retrans_per_sec: sockets.as_ref().map_or(0, |s| s.tcp_retrans_per_sec),
+            file_handles: kernel_limits.as_ref().map(|k| (k.file_handles_allocated, k.file_handles_max)),
+            remote_traffic: remote_traffic.as_ref().and_then(|t| t.lock().unwrap().clone()),
+            pressure,
+        };
+        for anomaly in anomaly_detector.check(&sample) {
             recorder.append(&Event::Anomaly(anomaly))?;
         }
+        if let Some(traffic) = anomaly_detector.take_spike_traffic() {
+            recorder.append(&Event::NetworkTraffic(traffic))?;
+        }
 
-        if swap_stats.total_kb > 0 {
-            let swap_usage_percent = (swap_stats.used_kb() as f32 / swap_stats.total_kb as f32) * 100.0;
-            if swap_usage_percent > swap_usage_threshold {
-                let anomaly = Anomaly {
-                    ts: OffsetDateTime::now_utc(),
-                    severity: AnomalySeverity::Warning,
-                    kind: AnomalyKind::SwapUsage,
-                    message: format!("Swap usage: {:.1}%", swap_usage_percent),
-                };
+        // Processes close to their own open file limit, named in the anomaly
+        static FD_LIMIT_COUNTER: AtomicU64 = AtomicU64::new(0);
+        if FD_LIMIT_COUNTER.fetch_add(1, Ordering::Relaxed).is_multiple_of(FD_LIMIT_CHECK_INTERVAL) {
+            let fd_usage = collector::read_fd_usage(top_processes_count);
+            collector_health.record("fd_limits", &fd_usage);
+            for anomaly in anomaly_detector.check_process_fds(&fd_usage.unwrap_or_default()) {
                 recorder.append(&Event::Anomaly(anomaly))?;
             }
         }
 
-        let disk_usage_percent = (disk_space.used_bytes as f32 / disk_space.total_bytes as f32) * 100.0;
-        if disk_usage_percent > disk_full_threshold {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Critical,
-                kind: AnomalyKind::DiskFull,
-                message: format!("Disk usage: {:.1}%", disk_usage_percent),
-            };
-            recorder.append(&Event::Anomaly(anomaly))?;
-        }
-
-        if disk_write_per_sec > disk_spike_threshold {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Warning,
-                kind: AnomalyKind::DiskSpike,
-                message: format!("Disk write spike: {}/s", format_bytes(disk_write_per_sec)),
-            };
-            recorder.append(&Event::Anomaly(anomaly))?;
-        }
-
-        if net_send_per_sec > network_spike_threshold || net_recv_per_sec > network_spike_threshold {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Warning,
-                kind: AnomalyKind::NetworkSpike,
-                message: format!(
-                    "Network spike: RX={}/s TX={}/s",
-                    format_bytes(net_recv_per_sec),
-                    format_bytes(net_send_per_sec)
-                ),
-            };
-            recorder.append(&Event::Anomaly(anomaly))?;
-        }
-
-        if ctxt_per_sec > ctxt_spike_threshold {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Warning,
-                kind: AnomalyKind::ContextSwitchSpike,
-                message: format!("Context switch spike: {}/s", ctxt_per_sec),
-            };
-            recorder.append(&Event::Anomaly(anomaly))?;
-        }
-
-        // Network errors/drops detection
-        if net_recv_errors_per_sec > 0 || net_send_errors_per_sec > 0 {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Warning,
-                kind: AnomalyKind::NetworkSpike,
-                message: format!(
-                    "Network errors detected: RX={}/s TX={}/s",
-                    net_recv_errors_per_sec, net_send_errors_per_sec
-                ),
-            };
-            recorder.append(&Event::Anomaly(anomaly))?;
-        }
-
-        if net_recv_drops_per_sec > 0 || net_send_drops_per_sec > 0 {
-            let anomaly = Anomaly {
-                ts: OffsetDateTime::now_utc(),
-                severity: AnomalySeverity::Warning,
-                kind: AnomalyKind::NetworkSpike,
-                message: format!(
-                    "Network packet drops detected: RX={}/s TX={}/s",
-                    net_recv_drops_per_sec, net_send_drops_per_sec
-                ),
-            };
-            recorder.append(&Event::Anomaly(anomaly))?;
-        }
-
         // Calculate process counts before current_processes is moved
         let total_process_count = current_processes.len() as u32;
         let running_process_count = current_processes.values().filter(|p| p.state == "R").count() as u32;
@@ -1032,7 +1518,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
         prev_cpu_snapshot = cpu_snapshot;
         prev_disk_snapshot = disk_snapshot;
         prev_network = network_stats;
+        prev_interfaces = interface_stats;
         prev_ctxt = ctxt_stats;
+        prev_sockets = socket_stats;
         prev_processes = current_processes;
 
         // Security monitoring (every N seconds to reduce overhead)
@@ -1041,7 +1529,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
 
         if security_count % SECURITY_CHECK_INTERVAL == 0 {
             // Check logged-in users
-            if let Ok(current_users) = read_logged_in_users() {
+            let logged_in_users = read_logged_in_users();
+            collector_health.record("logged_in_users", &logged_in_users);
+            if let Ok(current_users) = logged_in_users {
                 let mut current_user_map = std::collections::HashMap::new();
                 for user in &current_users {
                     let key = format!("{}@{}", user.username, user.terminal);
@@ -1095,7 +1585,17 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check auth log for SSH/sudo events
-            if let Ok(auth_entries) = tail_auth_log(&mut auth_log_position) {
+            let auth_entries = match journal_tail.as_mut().map(|j| j.poll()) {
+                Some(Ok(entries)) => Ok(entries),
+                Some(Err(e)) => {
+                    eprintln!("Warning: journal read failed, using auth log files instead: {}", e);
+                    journal_tail = None;
+                    tail_auth_log(&mut auth_log_position)
+                }
+                None => tail_auth_log(&mut auth_log_position),
+            };
+            collector_health.record("auth_log", &auth_entries);
+            if let Ok(auth_entries) = auth_entries {
                 for entry in auth_entries {
                     let (kind, severity) = match entry.event_type {
                         AuthEventType::SshSuccess => {
@@ -1106,7 +1606,7 @@ fn run_recorder(cli: Cli) -> Result<()> {
                             if let Some(ip) = &entry.source_ip {
                                 failed_logins
                                     .entry(ip.clone())
-                                    .or_insert_with(Vec::new)
+                                    .or_default()
                                     .push(std::time::Instant::now());
 
                                 // Clean old entries (>5 minutes)
@@ -1183,7 +1683,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for port scans
-            if let Ok(scan_alerts) = connection_tracker.update() {
+            let scan_result = connection_tracker.update();
+            collector_health.record("port_scan", &scan_result);
+            if let Ok(scan_alerts) = scan_result {
                 for alert in scan_alerts {
                     let anomaly = Anomaly {
                         ts: OffsetDateTime::now_utc(),
@@ -1197,7 +1699,11 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for user account changes
-            if let Ok(Some(msg)) = check_passwd_changes() {
+            let passwd_changes = check_passwd_changes();
+            collector_health.record("accounts", &passwd_changes);
+            if let Ok(Some(msg)) = passwd_changes {
+                // New or renamed accounts show under their name from the next snapshot
+                refresh_uid_names();
                 let event = SecurityEvent {
                     ts: OffsetDateTime::now_utc(),
                     kind: SecurityEventKind::UserAccountModified,
@@ -1210,7 +1716,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for group changes
-            if let Ok(Some(msg)) = check_group_changes() {
+            let group_changes = check_group_changes();
+            collector_health.record("groups", &group_changes);
+            if let Ok(Some(msg)) = group_changes {
                 let event = SecurityEvent {
                     ts: OffsetDateTime::now_utc(),
                     kind: SecurityEventKind::GroupModified,
@@ -1223,7 +1731,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for sudoers changes
-            if let Ok(Some(msg)) = check_sudoers_changes() {
+            let sudoers_changes = check_sudoers_changes();
+            collector_health.record("sudoers", &sudoers_changes);
+            if let Ok(Some(msg)) = sudoers_changes {
                 let event = SecurityEvent {
                     ts: OffsetDateTime::now_utc(),
                     kind: SecurityEventKind::SudoersModified,
@@ -1236,7 +1746,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for new/closed listening ports
-            if let Ok((new_ports, closed_ports)) = check_listening_port_changes() {
+            let port_changes = check_listening_port_changes();
+            collector_health.record("listening_ports", &port_changes);
+            if let Ok((new_ports, closed_ports)) = port_changes {
                 for (proto_addr, port) in new_ports {
                     let event = SecurityEvent {
                         ts: OffsetDateTime::now_utc(),
@@ -1263,7 +1775,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for kernel module changes
-            if let Ok((loaded, unloaded)) = check_kernel_module_changes() {
+            let module_changes = check_kernel_module_changes();
+            collector_health.record("kernel_modules", &module_changes);
+            if let Ok((loaded, unloaded)) = module_changes {
                 for module in loaded {
                     let event = SecurityEvent {
                         ts: OffsetDateTime::now_utc(),
@@ -1290,7 +1804,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for cron job changes (persistence monitoring)
-            if let Ok(Some(msg)) = check_cron_changes() {
+            let cron_changes = check_cron_changes();
+            collector_health.record("cron", &cron_changes);
+            if let Ok(Some(msg)) = cron_changes {
                 let event = SecurityEvent {
                     ts: OffsetDateTime::now_utc(),
                     kind: SecurityEventKind::CronJobModified,
@@ -1303,7 +1819,9 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
 
             // Check for systemd service changes (persistence monitoring)
-            if let Ok(Some(msg)) = check_systemd_changes() {
+            let systemd_changes = check_systemd_changes();
+            collector_health.record("systemd_units", &systemd_changes);
+            if let Ok(Some(msg)) = systemd_changes {
                 let event = SecurityEvent {
                     ts: OffsetDateTime::now_utc(),
                     kind: SecurityEventKind::SystemdServiceModified,
@@ -1321,63 +1839,56 @@ fn run_recorder(cli: Cli) -> Result<()> {
         let snapshot_count = SNAPSHOT_COUNTER.fetch_add(1, Ordering::Relaxed) + 1;
 
         if snapshot_count % PROCESS_SNAPSHOT_INTERVAL == 0 {
-            if let Ok(top_procs) = get_top_processes(TOP_PROCESSES_COUNT) {
-                let now = std::time::Instant::now();
-
-                // Calculate CPU percentages and build process infos
-                let mut proc_infos: Vec<ProcessInfo> = Vec::new();
-                let mut new_process_cpu: std::collections::HashMap<u32, (u64, std::time::Instant)> =
-                    std::collections::HashMap::new();
-
-                for p in &top_procs {
-                    // Calculate CPU percentage based on previous measurement
-                    let cpu_percent = if let Some((prev_cpu, prev_time)) = prev_process_cpu.get(&p.pid) {
-                        let elapsed_secs = now.duration_since(*prev_time).as_secs_f32();
-                        if elapsed_secs > 0.0 {
-                            let delta_cpu = p.cpu_time_jiffies.saturating_sub(*prev_cpu) as f32;
-                            // USER_HZ is typically 100 on Linux (clock ticks per second)
-                            let delta_cpu_secs = delta_cpu / 100.0;
-                            // Divide by elapsed time and normalize by number of CPUs
-                            ((delta_cpu_secs / elapsed_secs) * 100.0).min(100.0 * num_cpus)
-                        } else {
-                            0.0
-                        }
-                    } else {
-                        0.0
-                    };
-
-                    // Track for next iteration
-                    new_process_cpu.insert(p.pid, (p.cpu_time_jiffies, now));
-
-                    proc_infos.push(ProcessInfo {
-                        pid: p.pid,
-                        name: p.name.clone(),
-                        cmdline: p.cmdline.clone(),
-                        state: p.state.clone(),
-                        user: p.user.clone(),
-                        cpu_percent,
-                        mem_bytes: p.mem_bytes,
-                        read_bytes: p.read_bytes,
-                        write_bytes: p.write_bytes,
-                        num_fds: p.num_fds,
-                        num_threads: p.num_threads,
-                    });
-                }
-
-                // Update tracking map
-                prev_process_cpu = new_process_cpu;
+            let top_processes = get_top_processes(top_processes_count, &mut prev_process_samples, num_cpus, &process_filter);
+            collector_health.record("top_processes", &top_processes);
+            if let Ok(top) = top_processes {
+                let proc_infos: Vec<ProcessInfo> = top.processes.iter().map(|p| ProcessInfo {
+                    pid: p.pid,
+                    name: p.name.clone(),
+                    cmdline: p.cmdline.clone(),
+                    state: p.state.clone(),
+                    user: p.user.clone(),
+                    cpu_percent: p.cpu_percent,
+                    mem_bytes: p.mem_bytes,
+                    read_bytes: p.read_bytes,
+                    write_bytes: p.write_bytes,
+                    read_bytes_per_sec: p.read_bytes_per_sec,
+                    write_bytes_per_sec: p.write_bytes_per_sec,
+                    num_fds: p.num_fds,
+                    num_threads: p.num_threads,
+                    connection_count: p.connection_count,
+                    connections: p.connections.iter().map(|c| event::ProcessConnection {
+                        protocol: c.protocol.clone(),
+                        local_port: c.local_port,
+                        remote_addr: c.remote_addr.clone(),
+                        state: c.state.clone(),
+                    }).collect(),
+                    container_id: p.container_id.clone(),
+                    container_name: p.container_name.clone(),
+                }).collect();
 
                 let snapshot = EventProcessSnapshot {
                     ts: OffsetDateTime::now_utc(),
                     processes: proc_infos,
                     total_processes: total_process_count,
                     running_processes: running_process_count,
+                    top_cpu_pids: top.top_cpu_pids,
+                    top_mem_pids: top.top_mem_pids,
+                    per_user: Some(top.per_user),
+                    per_container: Some(top.per_container),
                 };
 
+                let top_writer = top.top_writer_pid
+                    .and_then(|pid| snapshot.processes.iter().find(|p| p.pid == pid));
+                let writer_anomaly = anomaly_detector.check_disk_writer(top.total_write_bytes_per_sec, top_writer);
+
                 // Update metadata with process snapshot
                 update_process_metadata(&shared_metadata, &snapshot);
 
                 recorder.append(&Event::ProcessSnapshot(snapshot))?;
+                if let Some(anomaly) = writer_anomaly {
+                    recorder.append(&Event::Anomaly(anomaly))?;
+                }
             }
         }
 
@@ -1441,6 +1952,23 @@ fn run_recorder(cli: Cli) -> Result<()> {
             }
         }
 
+        while let Ok(delivery) = email_delivery_rx.try_recv() {
+            collector_health.record("email_alerts", &delivery);
+        }
+        while let Ok(delivery) = webhook_delivery_rx.try_recv() {
+            collector_health.record("webhook_alerts", &delivery);
+        }
+
+        // A data source that stopped working is recorded rather than quietly missing
+        for anomaly in collector_health.take_anomalies() {
+            println!("{} [!] {}", now_timestamp(), anomaly.message);
+            recorder.append(&Event::Anomaly(anomaly))?;
+        }
+        collector_health.publish(&shared_collector_health);
+
+        // Everything recorded this tick goes to disk in one write
+        recorder.flush()?;
+
         // Adaptive sleep: only sleep for the remaining time in the interval
         // If collection took longer than the interval, continue immediately
         let elapsed = loop_start.elapsed();
@@ -1450,6 +1978,35 @@ fn run_recorder(cli: Cli) -> Result<()> {
         }
         // If elapsed >= target_interval, don't sleep - run as fast as possible
     }
+
+    println!("\n{} Shutting down...", now_timestamp());
+    recorder.shutdown()?;
+    println!("✓ Recording flushed to disk");
+
+    if cli.export_on_stop {
+        export_on_stop(&data_dir, &cli.export_dir)?;
+    }
+
+    Ok(())
+}
+
+// Write a final compressed JSON archive of everything recorded
+fn export_on_stop(data_dir: &str, export_dir: &str) -> Result<()> {
+    std::fs::create_dir_all(export_dir)?;
+    let timestamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let path = std::path::Path::new(export_dir).join(format!("blackbox-export-stop-{}.json.gz", timestamp));
+
+    commands::export::run_export(
+        Some(path.to_string_lossy().to_string()),
+        cli::ExportFormat::Json,
+        true,
+        commands::export::ExportFilter::default(),
+        Some(data_dir.to_string()),
+        false,
+        false,
+    )?;
+    println!("✓ Shutdown export written to {}", path.display());
+    Ok(())
 }
 
 fn format_bytes(bytes: u64) -> String {
@@ -1465,81 +2022,277 @@ fn format_bytes(bytes: u64) -> String {
 }
 
 // Remote streaming task - sends events to remote syslog
-async fn start_remote_streaming(broadcaster: Arc<EventBroadcaster>, config: RemoteSyslogConfig) {
-    use tokio::net::TcpStream;
-    use tokio::net::UdpSocket;
-    use tokio::io::AsyncWriteExt;
+// Remote streaming buffers events while the endpoint is unreachable and sends them in batches
+const REMOTE_MEMORY_QUEUE: usize = 1000;
+const REMOTE_BATCH_SIZE: usize = 100;
+const REMOTE_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+const REMOTE_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REMOTE_MAX_BACKOFF: Duration = Duration::from_secs(60);
+// Dropped events are reported at most this often, summed since the last report
+const REMOTE_DROP_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+// Record a security event when a started process is a package manager install/remove
+fn record_package_operation(recorder: &mut Recorder, cmdline: &str, user: Option<String>) -> Result<()> {
+    let Some(pkg_op) = detect_package_manager_operation(cmdline) else {
+        return Ok(());
+    };
 
-    println!("✓ Remote log streaming enabled: {}:{} ({})", config.host, config.port, config.protocol);
+    let kind = if pkg_op.operation == "install" {
+        SecurityEventKind::PackageInstalled
+    } else {
+        SecurityEventKind::PackageRemoved
+    };
 
-    let mut rx = broadcaster.subscribe();
-    let addr = format!("{}:{}", config.host, config.port);
+    let packages_str = pkg_op.packages.join(", ");
+    let event = SecurityEvent {
+        ts: OffsetDateTime::now_utc(),
+        kind,
+        user: user.unwrap_or_else(|| "unknown".to_string()),
+        source_ip: None,
+        message: format!("{} {} packages: {}", pkg_op.package_manager, pkg_op.operation, packages_str),
+    };
+    recorder.append(&Event::SecurityEvent(event))?;
+    println!("{} [SEC] Package manager: {} {} {}",
+        now_timestamp(), pkg_op.package_manager, pkg_op.operation, packages_str);
+    Ok(())
+}
 
-    // Try to establish connection for TCP
-    let mut tcp_stream: Option<TcpStream> = None;
-    if config.protocol == "tcp" {
-        match TcpStream::connect(&addr).await {
-            Ok(stream) => {
-                println!("✓ Connected to remote syslog via TCP");
-                tcp_stream = Some(stream);
-            }
-            Err(e) => {
-                eprintln!("⚠ Failed to connect to remote syslog: {}", e);
-                eprintln!("  Events will be buffered and retried");
+// Open the TCP connection, then the TLS session over it when configured, saying which failed
+async fn connect_remote(
+    addr: &str,
+    host: &str,
+    tls: Option<&tokio_rustls::TlsConnector>,
+) -> Result<Box<dyn tokio::io::AsyncWrite + Unpin + Send>> {
+    let stream = tokio::net::TcpStream::connect(addr).await.context("Failed to connect to remote syslog")?;
+    let Some(tls) = tls else {
+        return Ok(Box::new(stream));
+    };
+    let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
+        .with_context(|| format!("'{}' isn't a valid TLS server name", host))?;
+    let stream = tls.connect(server_name, stream).await.context("TLS handshake with remote syslog failed")?;
+    Ok(Box::new(stream))
+}
+
+fn remote_drop_anomaly(dropped: u64, spool_max_mb: u64) -> Event {
+    let message = if spool_max_mb > 0 {
+        format!("Remote syslog unreachable and its {} MB spool is full, {} oldest events dropped without being sent", spool_max_mb, dropped)
+    } else {
+        format!("Remote syslog unreachable and spooling is off, {} events dropped without being sent", dropped)
+    };
+    Event::Anomaly(Anomaly {
+        ts: OffsetDateTime::now_utc(),
+        severity: AnomalySeverity::Warning,
+        kind: AnomalyKind::RemoteSyslogDropped,
+        message,
+    })
+}
+
+// Stream with the current remote syslog settings, starting over whenever a reload changes them
+async fn run_remote_streaming(
+    broadcaster: Arc<EventBroadcaster>,
+    mut settings: tokio::sync::watch::Receiver<Option<RemoteSyslogConfig>>,
+    data_dir: String,
+    status: webui::RemoteStatus,
+    events: crossbeam_channel::Sender<Event>,
+) {
+    loop {
+        let config = settings.borrow_and_update().clone();
+        let connected = Arc::new(AtomicBool::new(false));
+        *status.write().unwrap() = config.as_ref().map(|_| connected.clone());
+        match config {
+            Some(config) => start_remote_streaming(&broadcaster, config, &data_dir, connected, &events, &mut settings).await,
+            None => {
+                let _ = settings.changed().await;
             }
         }
+        // Changed settings start it over, a closed channel or broadcaster means the recorder stopped
+        if !settings.has_changed().unwrap_or(false) {
+            break;
+        }
     }
+}
 
-    // For UDP, create socket once
-    let udp_socket = if config.protocol == "udp" {
-        match UdpSocket::bind("0.0.0.0:0").await {
-            Ok(socket) => {
-                println!("✓ Remote syslog via UDP ready");
-                Some(socket)
-            }
-            Err(e) => {
-                eprintln!("⚠ Failed to create UDP socket: {}", e);
-                None
-            }
+// Returns when the settings change, with anything unsent kept in the spool
+async fn start_remote_streaming(
+    broadcaster: &EventBroadcaster,
+    config: RemoteSyslogConfig,
+    data_dir: &str,
+    connected_status: Arc<AtomicBool>,
+    events: &crossbeam_channel::Sender<Event>,
+    settings: &mut tokio::sync::watch::Receiver<Option<RemoteSyslogConfig>>,
+) {
+    use tokio::io::{AsyncWrite, AsyncWriteExt};
+    use tokio::net::UdpSocket;
+    use std::time::Instant;
+    use tokio::sync::broadcast::error::RecvError;
+
+    println!(
+        "✓ Remote log streaming enabled: {}:{} ({}, {})",
+        config.host, config.port, config.protocol, config.format
+    );
+
+    let mut rx = broadcaster.subscribe();
+    let addr = format!("{}:{}", config.host, config.port);
+    let hostname = syslog::local_hostname();
+    let is_tcp = config.protocol == "tcp";
+
+    // Anything left in the spool from a previous run is replayed first
+    let spool_path = (config.spool_max_mb > 0).then(|| std::path::Path::new(data_dir).join(syslog::SPOOL_FILE));
+    let mut queue = syslog::RemoteQueue::new(REMOTE_MEMORY_QUEUE, spool_path, config.spool_max_mb * 1024 * 1024);
+
+    // Bad certificates stop streaming until the settings change
+    let tls = match syslog::tls_client_config(&config) {
+        Ok(tls) => tls.map(tokio_rustls::TlsConnector::from),
+        Err(e) => {
+            eprintln!("⚠ Remote log streaming disabled: {:#}", e);
+            connected_status.store(false, Ordering::Relaxed);
+            let _ = settings.changed().await;
+            return;
         }
-    } else {
-        None
     };
 
+    let mut tcp_stream: Option<Box<dyn AsyncWrite + Unpin + Send>> = None;
+    let mut udp_socket: Option<UdpSocket> = None;
+    let mut backoff = Duration::from_secs(1);
+    let mut next_attempt = Instant::now();
+    let mut connected_once = false;
+    let mut flush = tokio::time::interval(REMOTE_FLUSH_INTERVAL);
+    let mut unreported_drops = 0u64;
+    let mut last_drop_report: Option<Instant> = None;
+
     loop {
-        match rx.recv().await {
-            Ok(event) => {
-                // Serialize event to JSON
-                let json = match serde_json::to_string(&event) {
-                    Ok(j) => j,
-                    Err(_) => continue,
-                };
+        tokio::select! {
+            received = rx.recv() => match received {
+                Ok(event) => {
+                    if let Some(msg) = syslog::format_message(&event, config.format, config.facility, &hostname) {
+                        queue.push(msg);
+                    }
+                    // Otherwise wait for the next tick so events go out in batches
+                    if queue.memory_len() < REMOTE_BATCH_SIZE {
+                        continue;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("⚠ Remote streaming fell behind, {} events skipped", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = flush.tick() => {}
+            _ = settings.changed() => break,
+        }
 
-                // Send based on protocol
-                if config.protocol == "tcp" {
-                    if let Some(ref mut stream) = tcp_stream {
-                        let msg = format!("{}\n", json);
-                        if stream.write_all(msg.as_bytes()).await.is_err() {
-                            // Connection lost, try to reconnect
-                            eprintln!("⚠ Lost connection to remote syslog, reconnecting...");
-                            tcp_stream = TcpStream::connect(&addr).await.ok();
-                        }
-                    } else {
-                        // Try to reconnect periodically
-                        tcp_stream = TcpStream::connect(&addr).await.ok();
-                        if tcp_stream.is_some() {
+        unreported_drops += queue.take_dropped();
+        if unreported_drops > 0 && last_drop_report.is_none_or(|at| at.elapsed() >= REMOTE_DROP_REPORT_INTERVAL) {
+            eprintln!("⚠ Remote syslog spool full, {} events dropped", unreported_drops);
+            let _ = events.send(remote_drop_anomaly(unreported_drops, config.spool_max_mb));
+            unreported_drops = 0;
+            last_drop_report = Some(Instant::now());
+        }
+
+        if Instant::now() < next_attempt {
+            continue;
+        }
+
+        // Connect (or reconnect) with exponential backoff
+        let connected = if is_tcp {
+            if tcp_stream.is_none() {
+                match tokio::time::timeout(REMOTE_CONNECT_TIMEOUT, connect_remote(&addr, &config.host, tls.as_ref())).await {
+                    Ok(Ok(stream)) => {
+                        let via = if tls.is_some() { "TCP with TLS" } else { "TCP" };
+                        if connected_once {
                             println!("✓ Reconnected to remote syslog");
+                        } else {
+                            println!("✓ Connected to remote syslog via {}", via);
                         }
+                        tcp_stream = Some(stream);
+                    }
+                    Ok(Err(e)) => eprintln!(
+                        "⚠ {:#} (buffering, retry in {}s)",
+                        e,
+                        backoff.as_secs()
+                    ),
+                    Err(_) => eprintln!(
+                        "⚠ Timed out connecting to remote syslog (buffering, retry in {}s)",
+                        backoff.as_secs()
+                    ),
+                }
+            }
+            tcp_stream.is_some()
+        } else {
+            if udp_socket.is_none() {
+                match UdpSocket::bind("0.0.0.0:0").await {
+                    Ok(socket) => {
+                        println!("✓ Remote syslog via UDP ready");
+                        udp_socket = Some(socket);
                     }
-                } else if let Some(ref socket) = udp_socket {
-                    let _ = socket.send_to(json.as_bytes(), &addr).await;
+                    Err(e) => eprintln!("⚠ Failed to create UDP socket: {}", e),
                 }
             }
-            Err(_) => {
-                // Channel closed
+            udp_socket.is_some()
+        };
+        connected_status.store(connected, Ordering::Relaxed);
+
+        if !connected {
+            next_attempt = Instant::now() + backoff;
+            backoff = (backoff * 2).min(REMOTE_MAX_BACKOFF);
+            continue;
+        }
+        connected_once = true;
+
+        // Send queued messages, spool first, until the queue is empty or a write fails
+        loop {
+            let batch = match queue.next_batch(REMOTE_BATCH_SIZE) {
+                Ok(Some(batch)) => batch,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("⚠ Failed to read remote syslog spool: {}", e);
+                    break;
+                }
+            };
+
+            let sent = if let Some(ref mut stream) = tcp_stream {
+                let mut data = Vec::new();
+                for msg in &batch.messages {
+                    data.extend(syslog::frame(msg, config.format, config.framing, true));
+                }
+                // TLS holds writes in its session until flushed
+                stream.write_all(&data).await.is_ok() && stream.flush().await.is_ok()
+            } else if let Some(ref socket) = udp_socket {
+                let mut ok = true;
+                for msg in &batch.messages {
+                    if socket.send_to(&syslog::frame(msg, config.format, config.framing, false), &addr).await.is_err() {
+                        ok = false;
+                        break;
+                    }
+                }
+                ok
+            } else {
+                false
+            };
+
+            if !sent {
+                // Keep the batch queued and retry after the backoff
+                eprintln!("⚠ Lost connection to remote syslog, buffering events");
+                tcp_stream = None;
+                udp_socket = None;
+                connected_status.store(false, Ordering::Relaxed);
+                next_attempt = Instant::now() + backoff;
+                backoff = (backoff * 2).min(REMOTE_MAX_BACKOFF);
                 break;
             }
+
+            if let Err(e) = queue.ack(batch) {
+                eprintln!("⚠ Failed to update remote syslog spool: {}", e);
+                break;
+            }
+            backoff = Duration::from_secs(1);
         }
     }
+
+    // Keep whatever is still queued for the next run
+    if let Err(e) = queue.spill() {
+        eprintln!("⚠ {} remote syslog events not delivered: {}", queue.memory_len(), e);
+    }
 }
 
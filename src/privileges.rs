@@ -0,0 +1,126 @@
+// Switching from root to an unprivileged user once startup is done, keeping only the
+// capabilities collection still needs
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use std::path::Path;
+
+// Capability numbers from linux/capability.h
+pub const CAP_DAC_READ_SEARCH: u32 = 2; // Root-only logs such as auth.log and the journal
+pub const CAP_LINUX_IMMUTABLE: u32 = 9; // Append-only and immutable segment attributes
+pub const CAP_SYS_PTRACE: u32 = 19; // Other users' /proc/<pid>/io, fd and environ
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Look up a user in /etc/passwd
+pub fn lookup_user(name: &str) -> Result<User> {
+    let passwd = std::fs::read_to_string("/etc/passwd").context("Failed to read /etc/passwd")?;
+    parse_passwd_user(&passwd, name).with_context(|| format!("No user named {:?} in /etc/passwd", name))
+}
+
+fn parse_passwd_user(passwd: &str, name: &str) -> Option<User> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != name {
+            return None;
+        }
+        let uid = fields.nth(1)?.parse().ok()?;
+        let gid = fields.next()?.parse().ok()?;
+        Some(User { name: name.to_string(), uid, gid })
+    })
+}
+
+/// Give `user` ownership of the data directory and everything in it, so it can keep writing
+/// segments after the switch
+pub fn chown_data_dir(dir: &Path, user: &User) -> Result<()> {
+    std::os::unix::fs::chown(dir, Some(user.uid), Some(user.gid))
+        .with_context(|| format!("Failed to hand {} to {}", dir.display(), user.name))?;
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            chown_data_dir(&path, user)?;
+        } else {
+            std::os::unix::fs::lchown(&path, Some(user.uid), Some(user.gid))
+                .with_context(|| format!("Failed to hand {} to {}", path.display(), user.name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Switch to `user` and its groups, keeping only the `keep` capabilities. Applies to the
+/// calling thread and threads it starts afterwards; threads already running lose all
+/// capabilities, so call this before starting any.
+pub fn drop_privileges(user: &User, keep: &[u32]) -> Result<()> {
+    if unsafe { libc::geteuid() } != 0 {
+        anyhow::bail!("run_as_user needs Black Box to be started as root");
+    }
+    let name = CString::new(user.name.as_str())?;
+    unsafe {
+        // Keep the permitted set across setuid, the effective set is cleared and raised again below
+        if libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to keep capabilities");
+        }
+        if libc::initgroups(name.as_ptr(), user.gid) != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to set supplementary groups");
+        }
+        if libc::setgid(user.gid) != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to set group id");
+        }
+        if libc::setuid(user.uid) != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to set user id");
+        }
+        libc::prctl(libc::PR_SET_KEEPCAPS, 0, 0, 0, 0);
+    }
+
+    let mask = keep.iter().fold(0u32, |mask, cap| mask | (1 << cap));
+    let header = CapHeader { version: LINUX_CAPABILITY_VERSION_3, pid: 0 };
+    let data = [CapData { effective: mask, permitted: mask, inheritable: 0 }, CapData::default()];
+    if unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to limit capabilities");
+    }
+
+    // Make sure there's no way back
+    if unsafe { libc::setuid(0) } == 0 {
+        anyhow::bail!("Still able to regain root after switching to {}", user.name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_passwd_user() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                      blackbox:x:998:997:Black Box:/var/lib/black-box:/usr/sbin/nologin\n\
+                      broken:x:abc:1::/:/bin/sh\n";
+        assert_eq!(
+            parse_passwd_user(passwd, "blackbox"),
+            Some(User { name: "blackbox".to_string(), uid: 998, gid: 997 })
+        );
+        assert_eq!(parse_passwd_user(passwd, "root").map(|u| u.uid), Some(0));
+        assert_eq!(parse_passwd_user(passwd, "black"), None);
+        assert_eq!(parse_passwd_user(passwd, "broken"), None);
+    }
+}
@@ -0,0 +1,471 @@
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::thread;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use crate::collector::{self, ProcessFilter, ProcessInfo};
+use crate::event::{ProcessLifecycle, ProcessLifecycleKind};
+
+// From linux/connector.h and linux/cn_proc.h
+const NETLINK_CONNECTOR: libc::c_int = 11;
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_FORK: u32 = 0x0000_0001;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+const NLMSG_DONE: u16 = 3;
+
+const NLMSG_HDR_LEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+// proc_event is { what, cpu, timestamp_ns, event_data }
+const PROC_EVENT_OFFSET: usize = NLMSG_HDR_LEN + CN_MSG_LEN;
+const EVENT_DATA_OFFSET: usize = PROC_EVENT_OFFSET + 16;
+
+#[derive(Debug, PartialEq)]
+enum ProcEvent {
+    Fork { parent_tgid: u32, child_pid: u32, child_tgid: u32 },
+    Exec { pid: u32, tgid: u32 },
+    Exit { pid: u32, tgid: u32, exit_status: u32 },
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(buf.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+// One netlink datagram from the proc connector. None for events we don't track (uid/sid/comm changes, acks).
+fn parse_proc_event(msg: &[u8]) -> Option<ProcEvent> {
+    let data = |field: usize| u32_at(msg, EVENT_DATA_OFFSET + field * 4);
+
+    match u32_at(msg, PROC_EVENT_OFFSET)? {
+        PROC_EVENT_FORK => Some(ProcEvent::Fork {
+            parent_tgid: data(1)?,
+            child_pid: data(2)?,
+            child_tgid: data(3)?,
+        }),
+        PROC_EVENT_EXEC => Some(ProcEvent::Exec { pid: data(0)?, tgid: data(1)? }),
+        PROC_EVENT_EXIT => Some(ProcEvent::Exit {
+            pid: data(0)?,
+            tgid: data(1)?,
+            exit_status: data(2)?,
+        }),
+        _ => None,
+    }
+}
+
+// nlmsghdr + cn_msg + the PROC_CN_MCAST_LISTEN op
+fn subscribe_message() -> Vec<u8> {
+    let total_len = (NLMSG_HDR_LEN + CN_MSG_LEN + 4) as u32;
+    let mut msg = Vec::with_capacity(total_len as usize);
+    msg.extend(total_len.to_ne_bytes());
+    msg.extend(NLMSG_DONE.to_ne_bytes());
+    msg.extend(0u16.to_ne_bytes()); // flags
+    msg.extend(0u32.to_ne_bytes()); // seq
+    msg.extend(std::process::id().to_ne_bytes());
+    msg.extend(CN_IDX_PROC.to_ne_bytes());
+    msg.extend(CN_VAL_PROC.to_ne_bytes());
+    msg.extend(0u32.to_ne_bytes()); // seq
+    msg.extend(0u32.to_ne_bytes()); // ack
+    msg.extend(4u16.to_ne_bytes()); // payload length
+    msg.extend(0u16.to_ne_bytes()); // flags
+    msg.extend(PROC_CN_MCAST_LISTEN.to_ne_bytes());
+    msg
+}
+
+// Netlink socket subscribed to the kernel's process events (fork/exec/exit as they happen).
+// Subscribing needs root or CAP_NET_ADMIN.
+pub struct ProcConnector {
+    fd: OwnedFd,
+}
+
+impl ProcConnector {
+    pub fn open() -> Result<Self> {
+        let raw = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, NETLINK_CONNECTOR) };
+        if raw < 0 {
+            return Err(io::Error::last_os_error()).context("Failed to create netlink connector socket");
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_pid = std::process::id();
+        addr.nl_groups = CN_IDX_PROC;
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error()).context("Failed to bind to the proc connector");
+        }
+
+        let msg = subscribe_message();
+        let sent = unsafe { libc::send(fd.as_raw_fd(), msg.as_ptr() as *const libc::c_void, msg.len(), 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error()).context("Failed to subscribe to process events");
+        }
+
+        Ok(Self { fd })
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+struct TrackedProcess {
+    info: ProcessInfo,
+    started_at: OffsetDateTime,
+    announced: bool, // Started event already sent
+}
+
+// Turns fork/exec/exit into the same Started/Exited events the /proc polling produces.
+// Started is sent on exec; a process that forks and exits without exec'ing is reported on exit.
+struct ProcessTracker {
+    processes: HashMap<u32, TrackedProcess>,
+    filter: ProcessFilter, // The recorder's own helpers (df, w, journalctl) aren't worth recording
+}
+
+impl ProcessTracker {
+    // Processes already running are known so their exits can be reported
+    fn new(existing: collector::ProcessSnapshot, now: OffsetDateTime, filter: ProcessFilter) -> Self {
+        let processes = existing
+            .into_iter()
+            .map(|(pid, info)| (pid, TrackedProcess { info, started_at: now, announced: true }))
+            .collect();
+        Self { processes, filter }
+    }
+
+    fn handle(
+        &mut self,
+        event: ProcEvent,
+        now: OffsetDateTime,
+        read_process: impl Fn(u32) -> Option<ProcessInfo>,
+    ) -> Vec<KernelLifecycle> {
+        match event {
+            ProcEvent::Fork { parent_tgid, .. } if parent_tgid == self.filter.own_pid() => Vec::new(),
+            // Threads share the parent's tgid and aren't processes of their own
+            ProcEvent::Fork { parent_tgid, child_pid, child_tgid } if child_pid == child_tgid => {
+                // The child runs the parent's image until it execs, so copy rather than read /proc
+                let info = match self.processes.get(&parent_tgid) {
+                    Some(parent) => ProcessInfo {
+                        pid: child_pid,
+                        ppid: Some(parent_tgid),
+                        exit_code: None,
+                        start_time: None,
+                        ..parent.info.clone()
+                    },
+                    None => match read_process(child_pid) {
+                        Some(info) => info,
+                        None => return Vec::new(),
+                    },
+                };
+                self.processes.insert(child_pid, TrackedProcess { info, started_at: now, announced: false });
+                Vec::new()
+            }
+            ProcEvent::Exec { pid, tgid } if pid == tgid => {
+                // If it's already gone, fall back to what we knew from the fork
+                let tracked = self.processes.get(&pid).map(|p| p.info.clone());
+                let Some(info) = read_process(pid).or(tracked) else {
+                    return Vec::new();
+                };
+                // Its exit isn't wanted either
+                if self.filter.ignores(&info) {
+                    self.processes.remove(&pid);
+                    return Vec::new();
+                }
+                let event = lifecycle(&info, ProcessLifecycleKind::Started, now, None);
+                self.processes.insert(pid, TrackedProcess { info, started_at: now, announced: true });
+                vec![event]
+            }
+            ProcEvent::Exit { pid, tgid, exit_status } if pid == tgid => {
+                let Some(tracked) = self.processes.remove(&pid) else {
+                    return Vec::new();
+                };
+                let mut events = Vec::new();
+                if !tracked.announced {
+                    events.push(lifecycle(&tracked.info, ProcessLifecycleKind::Started, tracked.started_at, None));
+                }
+                let exit_code = collector::exit_code_from_wait_status(exit_status as i32);
+                events.push(lifecycle(&tracked.info, ProcessLifecycleKind::Exited, now, Some(exit_code)));
+                events
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// A start or exit from the kernel, with the start time of the process it's about so the main
+// loop can match it against the same one found by diffing /proc
+pub struct KernelLifecycle {
+    pub event: ProcessLifecycle,
+    pub start_time: Option<u64>,
+}
+
+fn lifecycle(
+    info: &ProcessInfo,
+    kind: ProcessLifecycleKind,
+    ts: OffsetDateTime,
+    exit_code: Option<i32>,
+) -> KernelLifecycle {
+    let event = ProcessLifecycle {
+        ts,
+        pid: info.pid,
+        ppid: info.ppid,
+        name: info.name.clone(),
+        cmdline: info.cmdline.clone(),
+        working_dir: info.working_dir.clone(),
+        user: info.user.clone(),
+        uid: info.uid,
+        kind,
+        exit_code,
+        container_id: info.container_id.clone(),
+        container_name: info.container_name.clone(),
+    };
+    KernelLifecycle { event, start_time: info.start_time }
+}
+
+// The two places a start or exit can come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleSource {
+    Kernel,
+    Poll,
+}
+
+// How long a start or exit waits to be matched. The /proc diff finds one at most an interval
+// before or after the kernel reports it.
+const LIFECYCLE_MATCH_WINDOW: Duration = Duration::from_secs(60);
+
+struct SeenLifecycle {
+    start_time: Option<u64>,
+    source: LifecycleSource,
+    at: Instant,
+}
+
+/// With exec tracing on, starts and exits are reported by the kernel and found by diffing /proc.
+/// Matching them by pid and start time keeps one record of each while still catching what the
+/// kernel dropped.
+#[derive(Default)]
+pub struct LifecycleDedup {
+    seen: HashMap<(u32, bool), SeenLifecycle>, // (pid, started) -> last one recorded
+}
+
+impl LifecycleDedup {
+    // Whether this start or exit should be recorded: false when the other source already
+    // reported it. A start time of None, a fork known only from its parent, matches any.
+    pub fn is_new(
+        &mut self,
+        pid: u32,
+        kind: &ProcessLifecycleKind,
+        start_time: Option<u64>,
+        source: LifecycleSource,
+        now: Instant,
+    ) -> bool {
+        let key = (pid, matches!(kind, ProcessLifecycleKind::Started));
+        if let Some(seen) = self.seen.get(&key)
+            && seen.source != source
+            && now.duration_since(seen.at) < LIFECYCLE_MATCH_WINDOW
+            && (start_time.is_none() || seen.start_time.is_none() || seen.start_time == start_time)
+        {
+            // Each report matches one from the other source, so a second exec of the same
+            // process (same pid and start time) is still recorded
+            self.seen.remove(&key);
+            return false;
+        }
+        self.seen.insert(key, SeenLifecycle { start_time, source, at: now });
+        true
+    }
+
+    // Forget what's too old to be matched, once an interval
+    pub fn prune(&mut self, now: Instant) {
+        self.seen.retain(|_, seen| now.duration_since(seen.at) < LIFECYCLE_MATCH_WINDOW);
+    }
+}
+
+// Read process events on their own thread and pass Started/Exited events to the main loop.
+// If the reader stops, the /proc diff the main loop runs anyway carries on alone.
+pub fn spawn_proc_connector(
+    connector: ProcConnector,
+    existing: collector::ProcessSnapshot,
+    filter: ProcessFilter,
+    event_sender: Sender<KernelLifecycle>,
+) {
+    thread::spawn(move || {
+        if let Err(e) = run_proc_connector(connector, existing, filter, event_sender) {
+            eprintln!("Process connector error: {:#}, falling back to polling", e);
+        }
+    });
+}
+
+fn run_proc_connector(
+    connector: ProcConnector,
+    existing: collector::ProcessSnapshot,
+    filter: ProcessFilter,
+    event_sender: Sender<KernelLifecycle>,
+) -> Result<()> {
+    let mut tracker = ProcessTracker::new(existing, OffsetDateTime::now_utc(), filter);
+    let mut buf = [0u8; 4096];
+
+    loop {
+        let len = match connector.recv(&mut buf) {
+            Ok(len) => len,
+            // The kernel drops events when we fall behind; keep going with what arrives next
+            Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                eprintln!("⚠ Process events dropped by the kernel (receive buffer full)");
+                continue;
+            }
+            Err(e) => return Err(e).context("Failed to read process events"),
+        };
+
+        let Some(event) = parse_proc_event(&buf[..len]) else {
+            continue;
+        };
+        for lifecycle in tracker.handle(event, OffsetDateTime::now_utc(), collector::read_started_process) {
+            // Unbounded channel, so this never blocks the reader
+            if event_sender.send(lifecycle).is_err() {
+                return Ok(()); // Main loop has exited
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datagram(what: u32, data: &[u32]) -> Vec<u8> {
+        let mut msg = vec![0u8; PROC_EVENT_OFFSET];
+        msg.extend(what.to_ne_bytes());
+        msg.extend(0u32.to_ne_bytes()); // cpu
+        msg.extend(0u64.to_ne_bytes()); // timestamp_ns
+        for value in data {
+            msg.extend(value.to_ne_bytes());
+        }
+        msg
+    }
+
+    fn info(pid: u32, name: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            ppid: Some(1),
+            name: name.to_string(),
+            cmdline: name.to_string(),
+            working_dir: Some("/root".to_string()),
+            user: Some("root".to_string()),
+            uid: Some(0),
+            state: "S".to_string(),
+            container_id: None,
+            container_name: None,
+            exit_code: None,
+            start_time: Some(1000 + pid as u64),
+        }
+    }
+
+    #[test]
+    fn test_parse_proc_event() {
+        assert_eq!(
+            parse_proc_event(&datagram(PROC_EVENT_FORK, &[100, 100, 200, 200])),
+            Some(ProcEvent::Fork { parent_tgid: 100, child_pid: 200, child_tgid: 200 })
+        );
+        assert_eq!(
+            parse_proc_event(&datagram(PROC_EVENT_EXEC, &[200, 200])),
+            Some(ProcEvent::Exec { pid: 200, tgid: 200 })
+        );
+        assert_eq!(
+            parse_proc_event(&datagram(PROC_EVENT_EXIT, &[200, 200, 1 << 8, 17])),
+            Some(ProcEvent::Exit { pid: 200, tgid: 200, exit_status: 1 << 8 })
+        );
+        assert_eq!(parse_proc_event(&datagram(0x0000_0004, &[200, 200, 0, 0])), None); // UID change
+        assert_eq!(parse_proc_event(&[0u8; 8]), None);
+    }
+
+    #[test]
+    fn test_short_lived_process_is_captured() {
+        let now = OffsetDateTime::now_utc();
+        let mut existing = collector::ProcessSnapshot::new();
+        existing.insert(100, info(100, "bash"));
+        let mut tracker = ProcessTracker::new(existing, now, ProcessFilter::with_own_pid(50, &["sleep".to_string()]));
+
+        // bash forks and the child execs `id`, which exits before the next /proc poll
+        let none = |_| None;
+        assert!(tracker.handle(ProcEvent::Fork { parent_tgid: 100, child_pid: 200, child_tgid: 200 }, now, none).is_empty());
+        let started = tracker.handle(ProcEvent::Exec { pid: 200, tgid: 200 }, now, |pid| Some(info(pid, "id")));
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].event.name, "id");
+        assert!(matches!(started[0].event.kind, ProcessLifecycleKind::Started));
+
+        let exited = tracker.handle(ProcEvent::Exit { pid: 200, tgid: 200, exit_status: 1 << 8 }, now, none);
+        assert_eq!(exited.len(), 1);
+        assert!(matches!(exited[0].event.kind, ProcessLifecycleKind::Exited));
+        assert_eq!(exited[0].event.exit_code, Some(1));
+
+        // A fork that never execs is reported when it exits, as a copy of its parent
+        tracker.handle(ProcEvent::Fork { parent_tgid: 100, child_pid: 201, child_tgid: 201 }, now, none);
+        let events = tracker.handle(ProcEvent::Exit { pid: 201, tgid: 201, exit_status: 0 }, now, none);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.name, "bash");
+        assert_eq!(events[0].event.ppid, Some(100));
+        assert_eq!(events[0].start_time, None);
+
+        // Children of the recorder itself are ignored
+        assert!(tracker.handle(ProcEvent::Fork { parent_tgid: 50, child_pid: 300, child_tgid: 300 }, now, none).is_empty());
+        let df = |pid| Some(ProcessInfo { ppid: Some(50), ..info(pid, "df") });
+        assert!(tracker.handle(ProcEvent::Exec { pid: 300, tgid: 300 }, now, df).is_empty());
+        assert!(tracker.handle(ProcEvent::Exit { pid: 300, tgid: 300, exit_status: 0 }, now, none).is_empty());
+
+        // So are processes in ignore_processes, from the exec on
+        tracker.handle(ProcEvent::Fork { parent_tgid: 100, child_pid: 400, child_tgid: 400 }, now, none);
+        assert!(tracker.handle(ProcEvent::Exec { pid: 400, tgid: 400 }, now, |pid| Some(info(pid, "sleep"))).is_empty());
+        assert!(tracker.handle(ProcEvent::Exit { pid: 400, tgid: 400, exit_status: 0 }, now, none).is_empty());
+
+        // Thread exits are ignored
+        assert!(tracker.handle(ProcEvent::Exit { pid: 101, tgid: 100, exit_status: 0 }, now, none).is_empty());
+        assert_eq!(tracker.processes.len(), 1);
+    }
+
+    #[test]
+    fn test_lifecycle_seen_by_both_sources_recorded_once() {
+        let mut dedup = LifecycleDedup::default();
+        let now = Instant::now();
+        let (started, exited) = (ProcessLifecycleKind::Started, ProcessLifecycleKind::Exited);
+        use LifecycleSource::{Kernel, Poll};
+
+        // `sh -c "exec foo"` execs twice as one process, the /proc diff finds it once
+        assert!(dedup.is_new(200, &started, Some(500), Kernel, now));
+        assert!(dedup.is_new(200, &started, Some(500), Kernel, now));
+        assert!(!dedup.is_new(200, &started, Some(500), Poll, now));
+
+        // A reused pid is a different process
+        assert!(dedup.is_new(201, &exited, Some(600), Poll, now));
+        assert!(dedup.is_new(201, &exited, Some(900), Kernel, now));
+
+        // Found by the diff first, and the kernel event for it arrives an interval later
+        assert!(dedup.is_new(202, &started, Some(700), Poll, now));
+        assert!(!dedup.is_new(202, &started, Some(700), Kernel, now + Duration::from_secs(1)));
+
+        // A fork that never exec'd has no start time and matches the pid
+        assert!(dedup.is_new(203, &exited, None, Kernel, now));
+        assert!(!dedup.is_new(203, &exited, Some(800), Poll, now));
+
+        // Too long ago to be the same report
+        assert!(dedup.is_new(204, &started, Some(100), Kernel, now));
+        dedup.prune(now + LIFECYCLE_MATCH_WINDOW);
+        assert!(dedup.seen.is_empty());
+        assert!(dedup.is_new(204, &started, Some(100), Poll, now + LIFECYCLE_MATCH_WINDOW));
+    }
+}
@@ -0,0 +1,383 @@
+// Delta encoding of ProcessSnapshot records. Most snapshots list the same processes as the one
+// before with only their usage numbers moved on, so the recorder writes a full snapshot (the
+// keyframe) and then deltas holding just the fields that differ from it, keyed by pid. Deltas
+// are against the keyframe rather than the previous snapshot, so any one of them can be
+// rebuilt from the keyframe alone when a read starts part way through a segment. Readers
+// turn deltas back into full snapshots, nothing past them ever sees one.
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::event::{ContainerUsage, Event, ProcessConnection, ProcessInfo, ProcessSnapshot, UserUsage};
+
+// A keyframe at least every this many snapshots, so a lost keyframe costs at most a minute
+pub const FULL_SNAPSHOT_EVERY: u32 = 12;
+
+// Payloads holding a delta start with this in place of an Event variant index
+const DELTA_TAG: u32 = u32::MAX;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessSnapshotDelta {
+    pub ts: OffsetDateTime,
+    pub base_ts: OffsetDateTime, // Timestamp of the keyframe this applies to
+    pub total_processes: u32,
+    pub running_processes: u32,
+    pub top_cpu_pids: Vec<u32>,
+    pub top_mem_pids: Vec<u32>,
+    pub per_user: Option<Vec<UserUsage>>,
+    pub order: Option<Vec<u32>>, // Pids in list order, None if it's the keyframe's
+    pub changes: Vec<ProcessChange>, // Processes with anything different from the keyframe
+    pub per_container: Option<Vec<ContainerUsage>>, // Appended, None in older recordings
+}
+
+// Fields of one process that differ from the keyframe, None where they don't
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessChange {
+    pub pid: u32,
+    pub state: Option<String>,
+    pub cpu_percent: Option<f32>,
+    pub mem_bytes: Option<u64>,
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+    pub read_bytes_per_sec: Option<u64>,
+    pub write_bytes_per_sec: Option<u64>,
+    pub num_fds: Option<u32>,
+    pub num_threads: Option<u32>,
+    pub connection_count: Option<u32>,
+    pub connections: Option<Vec<ProcessConnection>>,
+}
+
+impl ProcessSnapshotDelta {
+    /// Delta turning `keyframe` into `snapshot`, or None if it has to be written in full
+    /// because the processes aren't the same ones (by pid, name, command line, user and container)
+    pub fn between(keyframe: &ProcessSnapshot, snapshot: &ProcessSnapshot) -> Option<Self> {
+        if keyframe.processes.len() != snapshot.processes.len() {
+            return None;
+        }
+        let mut changes = Vec::new();
+        for process in &snapshot.processes {
+            let base = keyframe.processes.iter().find(|p| p.pid == process.pid)?;
+            if base.name != process.name
+                || base.cmdline != process.cmdline
+                || base.user != process.user
+                || base.container_id != process.container_id
+                || base.container_name != process.container_name
+            {
+                return None;
+            }
+            changes.extend(ProcessChange::between(base, process));
+        }
+
+        let same_order = keyframe.processes.iter().zip(&snapshot.processes).all(|(a, b)| a.pid == b.pid);
+        Some(Self {
+            ts: snapshot.ts,
+            base_ts: keyframe.ts,
+            total_processes: snapshot.total_processes,
+            running_processes: snapshot.running_processes,
+            top_cpu_pids: snapshot.top_cpu_pids.clone(),
+            top_mem_pids: snapshot.top_mem_pids.clone(),
+            per_user: snapshot.per_user.clone(),
+            per_container: snapshot.per_container.clone(),
+            order: (!same_order).then(|| snapshot.processes.iter().map(|p| p.pid).collect()),
+            changes,
+        })
+    }
+
+    /// The full snapshot, given the keyframe it was taken against
+    pub fn apply(&self, keyframe: &ProcessSnapshot) -> ProcessSnapshot {
+        let mut processes: Vec<ProcessInfo> = match &self.order {
+            Some(order) => order
+                .iter()
+                .filter_map(|pid| keyframe.processes.iter().find(|p| p.pid == *pid).cloned())
+                .collect(),
+            None => keyframe.processes.clone(),
+        };
+        for change in &self.changes {
+            if let Some(process) = processes.iter_mut().find(|p| p.pid == change.pid) {
+                change.apply(process);
+            }
+        }
+        ProcessSnapshot {
+            ts: self.ts,
+            processes,
+            total_processes: self.total_processes,
+            running_processes: self.running_processes,
+            top_cpu_pids: self.top_cpu_pids.clone(),
+            top_mem_pids: self.top_mem_pids.clone(),
+            per_user: self.per_user.clone(),
+            per_container: self.per_container.clone(),
+        }
+    }
+}
+
+impl ProcessChange {
+    fn between(base: &ProcessInfo, process: &ProcessInfo) -> Option<Self> {
+        let change = Self {
+            pid: process.pid,
+            state: changed(&base.state, &process.state),
+            cpu_percent: changed(&base.cpu_percent, &process.cpu_percent),
+            mem_bytes: changed(&base.mem_bytes, &process.mem_bytes),
+            read_bytes: changed(&base.read_bytes, &process.read_bytes),
+            write_bytes: changed(&base.write_bytes, &process.write_bytes),
+            read_bytes_per_sec: changed(&base.read_bytes_per_sec, &process.read_bytes_per_sec),
+            write_bytes_per_sec: changed(&base.write_bytes_per_sec, &process.write_bytes_per_sec),
+            num_fds: changed(&base.num_fds, &process.num_fds),
+            num_threads: changed(&base.num_threads, &process.num_threads),
+            connection_count: changed(&base.connection_count, &process.connection_count),
+            connections: changed(&base.connections, &process.connections),
+        };
+        let unchanged = Self { pid: process.pid, ..Self::none() };
+        (change != unchanged).then_some(change)
+    }
+
+    fn none() -> Self {
+        Self {
+            pid: 0,
+            state: None,
+            cpu_percent: None,
+            mem_bytes: None,
+            read_bytes: None,
+            write_bytes: None,
+            read_bytes_per_sec: None,
+            write_bytes_per_sec: None,
+            num_fds: None,
+            num_threads: None,
+            connection_count: None,
+            connections: None,
+        }
+    }
+
+    fn apply(&self, process: &mut ProcessInfo) {
+        if let Some(state) = &self.state {
+            process.state = state.clone();
+        }
+        if let Some(connections) = &self.connections {
+            process.connections = connections.clone();
+        }
+        process.cpu_percent = self.cpu_percent.unwrap_or(process.cpu_percent);
+        process.mem_bytes = self.mem_bytes.unwrap_or(process.mem_bytes);
+        process.read_bytes = self.read_bytes.unwrap_or(process.read_bytes);
+        process.write_bytes = self.write_bytes.unwrap_or(process.write_bytes);
+        process.read_bytes_per_sec = self.read_bytes_per_sec.unwrap_or(process.read_bytes_per_sec);
+        process.write_bytes_per_sec = self.write_bytes_per_sec.unwrap_or(process.write_bytes_per_sec);
+        process.num_fds = self.num_fds.unwrap_or(process.num_fds);
+        process.num_threads = self.num_threads.unwrap_or(process.num_threads);
+        process.connection_count = self.connection_count.unwrap_or(process.connection_count);
+    }
+}
+
+fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+    (old != new).then(|| new.clone())
+}
+
+/// Decides which ProcessSnapshots the recorder writes in full and which as deltas
+#[derive(Default)]
+pub struct DeltaEncoder {
+    keyframe: Option<ProcessSnapshot>,
+    deltas_since_keyframe: u32,
+}
+
+impl DeltaEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delta to write for `snapshot`, or None to write it in full as the new keyframe
+    pub fn encode(&mut self, snapshot: &ProcessSnapshot) -> Option<ProcessSnapshotDelta> {
+        if let Some(keyframe) = &self.keyframe
+            && self.deltas_since_keyframe + 1 < FULL_SNAPSHOT_EVERY
+            && let Some(delta) = ProcessSnapshotDelta::between(keyframe, snapshot)
+        {
+            self.deltas_since_keyframe += 1;
+            return Some(delta);
+        }
+        self.keyframe = Some(snapshot.clone());
+        self.deltas_since_keyframe = 0;
+        None
+    }
+
+    /// Write the next snapshot in full, so a new segment can be read without the one before
+    pub fn reset(&mut self) {
+        self.keyframe = None;
+    }
+}
+
+/// What a record's payload holds
+#[derive(Debug)]
+pub enum Payload {
+    Event(Box<Event>),
+    Delta(Box<ProcessSnapshotDelta>),
+}
+
+pub fn encode_delta(delta: &ProcessSnapshotDelta) -> bincode::Result<Vec<u8>> {
+    let mut payload = DELTA_TAG.to_le_bytes().to_vec();
+    bincode::serialize_into(&mut payload, delta)?;
+    Ok(payload)
+}
+
+pub fn decode_payload(payload: &[u8]) -> bincode::Result<Payload> {
+    match payload.strip_prefix(&DELTA_TAG.to_le_bytes()) {
+        Some(delta) => decode_delta(delta).map(|delta| Payload::Delta(Box::new(delta))),
+        None => Event::decode(payload).map(|event| Payload::Event(Box::new(event))),
+    }
+}
+
+// Deltas written before per_container end early, it's read as None like Event::decode does
+fn decode_delta(bytes: &[u8]) -> bincode::Result<ProcessSnapshotDelta> {
+    match bincode::deserialize(bytes) {
+        Err(e) if matches!(&*e, bincode::ErrorKind::Io(io) if io.kind() == std::io::ErrorKind::UnexpectedEof) => {
+            let mut padded = bytes.to_vec();
+            padded.push(0);
+            bincode::deserialize(&padded)
+        }
+        result => result,
+    }
+}
+
+/// Turns payloads back into events, rebuilding deltas from the last keyframe read
+#[derive(Default)]
+pub struct SnapshotRebuilder {
+    keyframe: Option<ProcessSnapshot>,
+}
+
+impl SnapshotRebuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The event a payload holds. A delta whose keyframe hasn't been read is handed back.
+    pub fn rebuild(&mut self, payload: Payload) -> Result<Event, Box<ProcessSnapshotDelta>> {
+        match payload {
+            Payload::Event(event) => {
+                if let Event::ProcessSnapshot(snapshot) = &*event {
+                    self.keyframe = Some(snapshot.clone());
+                }
+                Ok(*event)
+            }
+            Payload::Delta(delta) => match &self.keyframe {
+                Some(keyframe) if keyframe.ts == delta.base_ts => Ok(Event::ProcessSnapshot(delta.apply(keyframe))),
+                _ => Err(delta),
+            },
+        }
+    }
+
+    /// Use a keyframe found outside the records being read
+    pub fn set_keyframe(&mut self, keyframe: ProcessSnapshot) {
+        self.keyframe = Some(keyframe);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, cpu_percent: f32, mem_bytes: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: "java".to_string(),
+            cmdline: format!("/usr/bin/java -Xmx8g -Dservice.id={} -jar /opt/app/service.jar", pid),
+            state: "S".to_string(),
+            user: "app".to_string(),
+            cpu_percent,
+            mem_bytes,
+            read_bytes: 0,
+            write_bytes: 0,
+            read_bytes_per_sec: 0,
+            write_bytes_per_sec: 0,
+            num_fds: 100,
+            num_threads: 40,
+            connection_count: 0,
+            connections: vec![],
+            container_id: None,
+            container_name: None,
+        }
+    }
+
+    fn snapshot(secs: i64, processes: Vec<ProcessInfo>) -> ProcessSnapshot {
+        ProcessSnapshot {
+            ts: OffsetDateTime::from_unix_timestamp(1_760_000_000 + secs).unwrap(),
+            top_cpu_pids: processes.iter().map(|p| p.pid).collect(),
+            top_mem_pids: vec![],
+            processes,
+            total_processes: 300,
+            running_processes: 2,
+            per_user: None,
+            per_container: None,
+        }
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let keyframe = snapshot(0, vec![process(1, 10.0, 1000), process(2, 5.0, 2000), process(3, 1.0, 3000)]);
+
+        let mut moved = process(1, 12.5, 1000);
+        moved.state = "R".to_string();
+        let later = snapshot(5, vec![moved, process(3, 1.0, 3000), process(2, 5.0, 2500)]);
+        let delta = ProcessSnapshotDelta::between(&keyframe, &later).unwrap();
+        assert_eq!(delta.order, Some(vec![1, 3, 2]));
+        assert_eq!(delta.changes.len(), 2); // Process 3 is unchanged
+        assert_eq!(delta.changes[0].state.as_deref(), Some("R"));
+        assert_eq!(delta.changes[0].mem_bytes, None);
+        assert_eq!(delta.apply(&keyframe), later);
+
+        let payload = encode_delta(&delta).unwrap();
+        let Ok(Payload::Delta(decoded)) = decode_payload(&payload) else {
+            panic!("expected a delta");
+        };
+        assert_eq!(*decoded, delta);
+
+        // A different process set, or a pid reused by another program, needs a keyframe
+        assert!(ProcessSnapshotDelta::between(&keyframe, &snapshot(5, vec![process(1, 10.0, 1000)])).is_none());
+        let mut exec = keyframe.clone();
+        exec.processes[0].cmdline = "/usr/bin/python3 worker.py".to_string();
+        assert!(ProcessSnapshotDelta::between(&keyframe, &exec).is_none());
+    }
+
+    #[test]
+    fn test_delta_written_before_per_container_decodes() {
+        let keyframe = snapshot(0, vec![process(1, 10.0, 1000)]);
+        let delta = ProcessSnapshotDelta::between(&keyframe, &snapshot(5, vec![process(1, 20.0, 1000)])).unwrap();
+
+        // Without the trailing None, as the field was appended
+        let mut payload = encode_delta(&delta).unwrap();
+        payload.pop();
+        let Ok(Payload::Delta(decoded)) = decode_payload(&payload) else {
+            panic!("expected a delta");
+        };
+        assert_eq!(*decoded, delta);
+    }
+
+    #[test]
+    fn test_encoder_writes_periodic_keyframes() {
+        let mut encoder = DeltaEncoder::new();
+        let full: Vec<bool> = (0..26)
+            .map(|i| {
+                let processes = if i == 20 { vec![process(9, 1.0, 1)] } else { vec![process(1, i as f32, 1)] };
+                encoder.encode(&snapshot(i * 5, processes)).is_none()
+            })
+            .collect();
+        let keyframes: Vec<usize> = full.iter().enumerate().filter(|(_, full)| **full).map(|(i, _)| i).collect();
+        // Every 12th, and when the processes change (at 20, and back at 21)
+        assert_eq!(keyframes, [0, 12, 20, 21]);
+
+        encoder.reset();
+        assert!(encoder.encode(&snapshot(200, vec![process(1, 1.0, 1)])).is_none());
+    }
+
+    #[test]
+    fn test_rebuilder_needs_the_matching_keyframe() {
+        let keyframe = snapshot(0, vec![process(1, 10.0, 1000)]);
+        let later = snapshot(5, vec![process(1, 20.0, 1000)]);
+        let delta = ProcessSnapshotDelta::between(&keyframe, &later).unwrap();
+
+        let mut rebuilder = SnapshotRebuilder::new();
+        let delta = rebuilder.rebuild(Payload::Delta(Box::new(delta))).unwrap_err();
+        rebuilder.set_keyframe(snapshot(-60, vec![process(1, 10.0, 1000)])); // An older keyframe
+        let delta = rebuilder.rebuild(Payload::Delta(delta)).unwrap_err();
+
+        rebuilder.rebuild(Payload::Event(Box::new(Event::ProcessSnapshot(keyframe)))).unwrap();
+        match rebuilder.rebuild(Payload::Delta(delta)) {
+            Ok(Event::ProcessSnapshot(rebuilt)) => assert_eq!(rebuilt, later),
+            other => panic!("expected the rebuilt snapshot, got {:?}", other),
+        }
+    }
+}
@@ -1,13 +1,20 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
-use crate::config::{ProtectionConfig, ProtectionMode};
+use crate::config::{ProtectionConfig, ProtectionMode, SegmentAttribute};
+use crate::storage::find_segment_files;
+
+// Inode flags from linux/fs.h, as read and written by chattr
+const FS_IMMUTABLE_FL: libc::c_int = 0x10;
+const FS_APPEND_FL: libc::c_int = 0x20;
 
 pub struct ProtectionManager {
     mode: ProtectionMode,
     config: ProtectionConfig,
-    protected_files: Vec<PathBuf>,
+    protected_files: Vec<(PathBuf, SegmentAttribute)>,
 }
 
 impl ProtectionManager {
@@ -19,72 +26,206 @@ impl ProtectionManager {
         }
     }
 
-    /// Apply protection to a log file based on the protection mode
-    pub fn protect_file(&mut self, path: &Path) -> Result<()> {
+    /// Whether files get protected at all, Protected mode only with append_only set
+    pub fn is_enabled(&self) -> bool {
         match self.mode {
-            ProtectionMode::Default => {
-                // No protection in default mode
-                Ok(())
-            }
-            ProtectionMode::Protected | ProtectionMode::Hardened => {
-                if self.config.append_only || self.mode == ProtectionMode::Hardened {
-                    self.set_append_only(path)?;
-                    self.protected_files.push(path.to_path_buf());
-                }
-                Ok(())
-            }
+            ProtectionMode::Default => false,
+            ProtectionMode::Protected => self.config.append_only,
+            ProtectionMode::Hardened => true,
         }
     }
 
-    /// Set append-only attribute on a file using chattr
-    fn set_append_only(&self, path: &Path) -> Result<()> {
-        let output = Command::new("chattr")
-            .args(["+a", path.to_str().unwrap()])
-            .output();
+    /// Attribute given to full segments, which are never written again
+    pub fn sealed_attribute(&self) -> SegmentAttribute {
+        self.config.sealed_attribute.unwrap_or(match self.mode {
+            ProtectionMode::Hardened => SegmentAttribute::Immutable,
+            _ => SegmentAttribute::AppendOnly,
+        })
+    }
 
-        match output {
-            Ok(output) if output.status.success() => {
-                println!("✓ Set append-only protection on: {}", path.display());
-                Ok(())
-            }
-            Ok(output) => {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                eprintln!("Warning: Failed to set append-only on {}: {}", path.display(), stderr);
-                eprintln!("  Protection mode may not work correctly without root privileges");
-                Ok(()) // Don't fail, just warn
-            }
-            Err(e) => {
-                eprintln!("Warning: chattr command failed: {}", e);
-                eprintln!("  Append-only protection not available (requires Linux + e2fsprogs)");
-                Ok(()) // Don't fail, just warn
-            }
-        }
+    /// Make the segment being written append-only
+    pub fn protect_file(&mut self, path: &Path) -> Result<()> {
+        self.apply(path, SegmentAttribute::AppendOnly)
     }
 
-    /// Remove append-only attribute (for cleanup)
-    pub fn unprotect_file(&self, path: &Path) -> Result<()> {
-        if self.config.append_only || self.mode == ProtectionMode::Hardened {
-            let _ = Command::new("chattr")
-                .args(["-a", path.to_str().unwrap()])
-                .output();
+    /// Give a full segment the sealed attribute, replacing append-only
+    pub fn seal_file(&mut self, path: &Path) -> Result<()> {
+        self.apply(path, self.sealed_attribute())
+    }
+
+    fn apply(&mut self, path: &Path, attribute: SegmentAttribute) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
         }
+        set_attribute(path, Some(attribute))
+            .with_context(|| format!("Failed to set {} on {}", attribute_name(attribute), path.display()))?;
+        self.protected_files.retain(|(p, _)| p != path);
+        self.protected_files.push((path.to_path_buf(), attribute));
         Ok(())
     }
 
-    /// Print protection mode information
-    pub fn print_info(&self) {
-        // Print will be handled in main.rs - this is now a no-op
-        // Kept for backwards compatibility
+    /// Remove protection so the file can be rewritten or deleted
+    pub fn unprotect_file(&mut self, path: &Path) -> Result<()> {
+        let Some(index) = self.protected_files.iter().position(|(p, _)| p == path) else {
+            return Ok(());
+        };
+        self.protected_files.swap_remove(index);
+        set_attribute(path, None).with_context(|| format!("Failed to remove protection from {}", path.display()))
+    }
 
-        println!();
+    /// Find protected files whose attribute has been removed since it was set, putting it back
+    /// where possible. Returns a message for each.
+    pub fn check(&mut self) -> Vec<String> {
+        let mut messages = Vec::new();
+        self.protected_files.retain(|(path, attribute)| {
+            let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().to_string());
+            match get_flags(path) {
+                Ok(flags) if flags & attribute_flag(*attribute) != 0 => true,
+                Ok(_) => {
+                    let restored = match set_attribute(path, Some(*attribute)) {
+                        Ok(()) => "restored".to_string(),
+                        Err(e) => format!("could not restore: {}", e),
+                    };
+                    messages.push(format!(
+                        "{} attribute removed from protected segment {} ({})",
+                        attribute_name(*attribute),
+                        name,
+                        restored
+                    ));
+                    true
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    messages.push(format!("Protected segment {} was deleted", name));
+                    false
+                }
+                Err(_) => true, // Unreadable for some other reason, try again next check
+            }
+        });
+        messages
+    }
+
+    /// Remove protection from every file we protected
+    pub fn release_all(&mut self) {
+        for (path, _) in std::mem::take(&mut self.protected_files) {
+            let _ = set_attribute(&path, None);
+        }
     }
 }
 
 impl Drop for ProtectionManager {
     fn drop(&mut self) {
         // Clean up append-only attributes on exit (if we can)
-        for path in &self.protected_files {
-            let _ = self.unprotect_file(path);
+        self.release_all();
+    }
+}
+
+/// Clear protection left on segments by a run that didn't exit cleanly, so they can be
+/// recovered and handed to another user before being protected again
+pub fn clear_leftover_protection(dir: &Path) {
+    for (_, path) in find_segment_files(dir) {
+        let _ = set_attribute(&path, None);
+    }
+}
+
+fn attribute_flag(attribute: SegmentAttribute) -> libc::c_int {
+    match attribute {
+        SegmentAttribute::AppendOnly => FS_APPEND_FL,
+        SegmentAttribute::Immutable => FS_IMMUTABLE_FL,
+    }
+}
+
+fn attribute_name(attribute: SegmentAttribute) -> &'static str {
+    match attribute {
+        SegmentAttribute::AppendOnly => "append-only",
+        SegmentAttribute::Immutable => "immutable",
+    }
+}
+
+fn get_flags(path: &Path) -> io::Result<libc::c_int> {
+    let file = File::open(path)?;
+    get_file_flags(&file)
+}
+
+fn get_file_flags(file: &File) -> io::Result<libc::c_int> {
+    let mut flags: libc::c_int = 0;
+    // The kernel reads and writes an int despite the ioctl being declared with a long
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(flags)
+}
+
+// Set `attribute` in place of any other protection, or clear both with None. Needs root or
+// CAP_LINUX_IMMUTABLE, and a filesystem with file attributes (ext4, xfs, btrfs).
+fn set_attribute(path: &Path, attribute: Option<SegmentAttribute>) -> io::Result<()> {
+    let file = File::open(path)?;
+    let current = get_file_flags(&file)?;
+    let flags = (current & !(FS_APPEND_FL | FS_IMMUTABLE_FL)) | attribute.map_or(0, attribute_flag);
+    if flags == current {
+        return Ok(());
+    }
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hardened() -> ProtectionManager {
+        ProtectionManager::new(ProtectionMode::Hardened, ProtectionConfig::default())
+    }
+
+    #[test]
+    fn test_sealed_attribute_follows_mode() {
+        assert_eq!(hardened().sealed_attribute(), SegmentAttribute::Immutable);
+
+        let config = ProtectionConfig { append_only: true, ..ProtectionConfig::default() };
+        let protected = ProtectionManager::new(ProtectionMode::Protected, config.clone());
+        assert!(protected.is_enabled());
+        assert_eq!(protected.sealed_attribute(), SegmentAttribute::AppendOnly);
+        assert!(!ProtectionManager::new(ProtectionMode::Protected, ProtectionConfig::default()).is_enabled());
+        assert!(!ProtectionManager::new(ProtectionMode::Default, config).is_enabled());
+
+        let config = ProtectionConfig { sealed_attribute: Some(SegmentAttribute::AppendOnly), ..ProtectionConfig::default() };
+        assert_eq!(ProtectionManager::new(ProtectionMode::Hardened, config).sealed_attribute(), SegmentAttribute::AppendOnly);
+    }
+
+    #[test]
+    fn test_check_reports_and_restores_removed_attributes() {
+        let dir = tempfile::tempdir().unwrap();
+        let active = dir.path().join("segment_00001.dat");
+        let sealed = dir.path().join("segment_00000.dat");
+        std::fs::write(&active, b"active").unwrap();
+        std::fs::write(&sealed, b"sealed").unwrap();
+
+        let mut manager = hardened();
+        if let Err(e) = manager.protect_file(&active) {
+            eprintln!("Skipping, file attributes not available here: {:#}", e);
+            return;
         }
+        manager.seal_file(&sealed).unwrap();
+        assert_ne!(get_flags(&active).unwrap() & FS_APPEND_FL, 0);
+        assert_ne!(get_flags(&sealed).unwrap() & FS_IMMUTABLE_FL, 0);
+        assert!(manager.check().is_empty());
+
+        // Appending still works, rewriting doesn't
+        assert!(std::fs::OpenOptions::new().append(true).open(&active).is_ok());
+        assert!(std::fs::OpenOptions::new().write(true).open(&active).is_err());
+        assert!(std::fs::remove_file(&sealed).is_err());
+
+        set_attribute(&sealed, None).unwrap();
+        let messages = manager.check();
+        assert_eq!(messages, vec!["immutable attribute removed from protected segment segment_00000.dat (restored)"]);
+        assert_ne!(get_flags(&sealed).unwrap() & FS_IMMUTABLE_FL, 0);
+
+        manager.unprotect_file(&active).unwrap();
+        std::fs::remove_file(&active).unwrap();
+        assert!(manager.check().is_empty()); // No longer protected, deleting it is expected
+
+        manager.release_all();
+        assert_eq!(get_flags(&sealed).unwrap() & (FS_APPEND_FL | FS_IMMUTABLE_FL), 0);
     }
 }
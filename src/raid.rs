@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::io;
+use time::OffsetDateTime;
+
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, RaidArray};
+
+// Only present when the md driver is loaded, so hosts without software RAID never read it
+pub const MDSTAT_PATH: &str = "/proc/mdstat";
+
+// Sync actions that rebuild redundancy, as opposed to a "check" or "repair" scrub
+const REBUILD_ACTIONS: [&str; 3] = ["recovery", "resync", "reshape"];
+
+pub fn read_mdstat() -> io::Result<Vec<RaidArray>> {
+    Ok(parse_mdstat(&std::fs::read_to_string(MDSTAT_PATH)?))
+}
+
+/// Parse /proc/mdstat into its arrays:
+///
+/// ```text
+/// md1 : active raid5 sdd1[3] sdc1[1] sdb1[0](F)
+///       2093056 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/2] [UU_]
+///       [=====>...............]  recovery = 27.4% (287232/1046528) finish=0.6min speed=20000K/sec
+/// ```
+pub fn parse_mdstat(content: &str) -> Vec<RaidArray> {
+    let mut arrays: Vec<RaidArray> = Vec::new();
+
+    for line in content.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            let Some((name, rest)) = line.split_once(" : ") else {
+                continue;
+            };
+            if !name.starts_with("md") {
+                continue; // "Personalities", "unused devices"
+            }
+            let mut tokens = rest.split_whitespace().peekable();
+            let active = tokens.next() == Some("active");
+            // "(auto-read-only)" or "(read-only)" after the state
+            while tokens.peek().is_some_and(|t| t.starts_with('(')) {
+                tokens.next();
+            }
+            let level = match tokens.peek() {
+                Some(t) if !t.contains('[') => tokens.next().unwrap_or_default().to_string(),
+                _ => String::new(), // Inactive arrays have no level
+            };
+            let failed_devices = tokens
+                .filter(|t| t.ends_with("(F)"))
+                .filter_map(|t| t.split('[').next())
+                .map(str::to_string)
+                .collect();
+            arrays.push(RaidArray {
+                name: name.trim().to_string(),
+                level,
+                active,
+                total_devices: None,
+                active_devices: None,
+                failed_devices,
+                sync_action: None,
+                sync_progress_percent: None,
+            });
+            continue;
+        }
+
+        let Some(array) = arrays.last_mut() else {
+            continue;
+        };
+        let line = line.trim();
+        // "[3/2]": devices the array should have and devices working
+        if let Some(counts) = line
+            .split_whitespace()
+            .filter_map(|t| t.strip_prefix('[')?.strip_suffix(']'))
+            .find_map(|t| t.split_once('/'))
+            && let (Ok(total), Ok(working)) = (counts.0.parse(), counts.1.parse())
+        {
+            array.total_devices = Some(total);
+            array.active_devices = Some(working);
+        }
+        // "recovery = 27.4% (...)", "resync=DELAYED" while waiting for another array
+        for action in REBUILD_ACTIONS.iter().chain(&["check", "repair"]) {
+            let Some(rest) = line.split_once(action).map(|(_, rest)| rest.trim_start()) else {
+                continue;
+            };
+            let Some(value) = rest.strip_prefix('=') else {
+                continue;
+            };
+            array.sync_action = Some(action.to_string());
+            array.sync_progress_percent = value.trim_start().split('%').next().and_then(|p| p.parse().ok());
+            break;
+        }
+    }
+
+    arrays
+}
+
+/// Arrays with fewer working devices than they should have, or a failed member
+pub fn is_degraded(array: &RaidArray) -> bool {
+    let missing = matches!((array.total_devices, array.active_devices), (Some(total), Some(working)) if working < total);
+    missing || !array.failed_devices.is_empty()
+}
+
+fn describe(array: &RaidArray) -> String {
+    let mut text = match array.level.as_str() {
+        "" => array.name.clone(),
+        level => format!("{} ({})", array.name, level),
+    };
+    if let (Some(total), Some(working)) = (array.total_devices, array.active_devices) {
+        text.push_str(&format!(", {} of {} devices working", working, total));
+    }
+    if !array.failed_devices.is_empty() {
+        text.push_str(&format!(", failed: {}", array.failed_devices.join(", ")));
+    }
+    text
+}
+
+// The last state seen of each array, compared with each read
+#[derive(Default)]
+pub struct RaidMonitor {
+    arrays: HashMap<String, RaidArray>,
+}
+
+impl RaidMonitor {
+    /// A Critical anomaly when an array becomes degraded or loses another member, and Info ones
+    /// when a rebuild starts or finishes
+    pub fn check(&mut self, arrays: &[RaidArray]) -> Vec<Anomaly> {
+        let ts = OffsetDateTime::now_utc();
+        let anomaly = |severity, kind, message| Anomaly { ts, severity, kind, message };
+        let mut anomalies = Vec::new();
+
+        for array in arrays {
+            let previous = self.arrays.get(&array.name);
+            let lost_member = previous.is_some_and(|prev| {
+                array.active_devices < prev.active_devices
+                    || array.failed_devices.iter().any(|d| !prev.failed_devices.contains(d))
+            });
+            if is_degraded(array) && (lost_member || !previous.is_some_and(is_degraded)) {
+                anomalies.push(anomaly(
+                    AnomalySeverity::Critical,
+                    AnomalyKind::RaidDegraded,
+                    format!("RAID array {} degraded: {}", array.name, describe(array)),
+                ));
+            }
+
+            let rebuilding = |a: &RaidArray| a.sync_action.clone().filter(|action| REBUILD_ACTIONS.contains(&action.as_str()));
+            match (previous.and_then(rebuilding), rebuilding(array)) {
+                (None, Some(action)) => anomalies.push(anomaly(
+                    AnomalySeverity::Info,
+                    AnomalyKind::RaidRebuild,
+                    match array.sync_progress_percent {
+                        Some(progress) => format!("RAID array {} {} started, {:.1}% done", array.name, action, progress),
+                        None => format!("RAID array {} {} started", array.name, action),
+                    },
+                )),
+                (Some(action), None) => anomalies.push(anomaly(
+                    AnomalySeverity::Info,
+                    AnomalyKind::RaidRebuild,
+                    format!("RAID array {} {} finished: {}", array.name, action, describe(array)),
+                )),
+                _ => {}
+            }
+        }
+
+        for name in self.arrays.keys() {
+            if !arrays.iter().any(|a| &a.name == name) {
+                anomalies.push(anomaly(
+                    AnomalySeverity::Warning,
+                    AnomalyKind::RaidDegraded,
+                    format!("RAID array {} is no longer running", name),
+                ));
+            }
+        }
+
+        self.arrays = arrays.iter().map(|a| (a.name.clone(), a.clone())).collect();
+        anomalies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEALTHY: &str = "\
+Personalities : [raid1] [raid6] [raid5] [raid4]
+md1 : active raid5 sdd1[3] sdc1[1] sdb1[0]
+      2093056 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/3] [UUU]
+
+md0 : active raid1 sdb2[1] sda2[0]
+      1046528 blocks super 1.2 [2/2] [UU]
+      bitmap: 0/1 pages [0KB], 65536KB chunk
+
+md2 : inactive sde1[0](S)
+      1046528 blocks super 1.2
+
+unused devices: <none>
+";
+
+    const DEGRADED: &str = "\
+Personalities : [raid1] [raid6] [raid5] [raid4]
+md1 : active raid5 sdd1[3] sdc1[1] sdb1[0](F)
+      2093056 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/2] [_UU]
+
+md0 : active raid1 sdb2[1] sda2[0]
+      1046528 blocks super 1.2 [2/2] [UU]
+      bitmap: 0/1 pages [0KB], 65536KB chunk
+
+unused devices: <none>
+";
+
+    const REBUILDING: &str = "\
+Personalities : [raid1] [raid6] [raid5] [raid4]
+md1 : active raid5 sde1[4] sdd1[3] sdc1[1]
+      2093056 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/2] [_UU]
+      [=====>...............]  recovery = 27.4% (287232/1046528) finish=0.6min speed=20000K/sec
+
+md0 : active raid1 sdb2[1] sda2[0]
+      1046528 blocks super 1.2 [2/2] [UU]
+      resync=DELAYED
+
+unused devices: <none>
+";
+
+    #[test]
+    fn test_parse_mdstat() {
+        let arrays = parse_mdstat(HEALTHY);
+        assert_eq!(arrays.len(), 3);
+        assert_eq!(arrays[0].name, "md1");
+        assert_eq!(arrays[0].level, "raid5");
+        assert!(arrays[0].active);
+        assert_eq!((arrays[0].total_devices, arrays[0].active_devices), (Some(3), Some(3)));
+        assert!(!is_degraded(&arrays[0]));
+        assert_eq!(arrays[2].level, "");
+        assert!(!arrays[2].active);
+        assert_eq!(arrays[2].total_devices, None);
+
+        let arrays = parse_mdstat(DEGRADED);
+        assert_eq!(arrays[0].failed_devices, vec!["sdb1"]);
+        assert_eq!(arrays[0].active_devices, Some(2));
+        assert!(is_degraded(&arrays[0]));
+
+        let arrays = parse_mdstat(REBUILDING);
+        assert_eq!(arrays[0].sync_action.as_deref(), Some("recovery"));
+        assert_eq!(arrays[0].sync_progress_percent, Some(27.4));
+        // Waiting for md1 to finish, no progress yet
+        assert_eq!(arrays[1].sync_action.as_deref(), Some("resync"));
+        assert_eq!(arrays[1].sync_progress_percent, None);
+
+        assert!(parse_mdstat("Personalities : \nunused devices: <none>\n").is_empty());
+    }
+
+    #[test]
+    fn test_degraded_and_rebuild_anomalies() {
+        let mut monitor = RaidMonitor::default();
+        assert!(monitor.check(&parse_mdstat(HEALTHY)).is_empty());
+
+        // md2 was stopped as md1 lost a member
+        let anomalies = monitor.check(&parse_mdstat(DEGRADED));
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0].kind, AnomalyKind::RaidDegraded);
+        assert!(matches!(anomalies[0].severity, AnomalySeverity::Critical));
+        assert_eq!(
+            anomalies[0].message,
+            "RAID array md1 degraded: md1 (raid5), 2 of 3 devices working, failed: sdb1"
+        );
+        assert!(matches!(anomalies[1].severity, AnomalySeverity::Warning));
+        // Still degraded, nothing new
+        assert!(monitor.check(&parse_mdstat(DEGRADED)).is_empty());
+
+        let anomalies = monitor.check(&parse_mdstat(REBUILDING));
+        let messages: Vec<&str> = anomalies.iter().map(|a| a.message.as_str()).collect();
+        assert_eq!(messages, vec!["RAID array md1 recovery started, 27.4% done", "RAID array md0 resync started"]);
+        assert!(anomalies.iter().all(|a| a.kind == AnomalyKind::RaidRebuild));
+
+        let done = HEALTHY.replace("md2 : inactive sde1[0](S)\n      1046528 blocks super 1.2\n", "");
+        let anomalies = monitor.check(&parse_mdstat(&done));
+        assert_eq!(anomalies[0].message, "RAID array md1 recovery finished: md1 (raid5), 3 of 3 devices working");
+        assert_eq!(anomalies.len(), 2);
+    }
+}
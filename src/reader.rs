@@ -1,41 +1,71 @@
 use anyhow::{Context, Result};
-use std::{
-    fs::File,
-    io::Read,
-    path::Path,
-};
+use std::path::Path;
 
 use crate::event::Event;
-use crate::storage::{find_segment_files, RecordHeader, MAGIC};
+use crate::integrity::{self, RecordChain, SegmentFormat, SegmentReport, SigningKey};
+use crate::process_delta::{decode_payload, SnapshotRebuilder};
+use crate::storage::{find_segment_files, load_segment, SEGMENT_HEADER_LEN};
+
+/// Where following the recording has got to: a segment, the end of the last whole record read
+/// from it, and the chain and keyframe the records after it carry on from
+pub struct FollowPosition {
+    segment_id: u64,
+    offset: usize,
+    chain: Option<RecordChain>, // Started once the segment's format is known
+    rebuilder: SnapshotRebuilder,
+}
+
+impl FollowPosition {
+    fn start_of(segment_id: u64) -> Self {
+        Self { segment_id, offset: SEGMENT_HEADER_LEN as usize, chain: None, rebuilder: SnapshotRebuilder::new() }
+    }
+}
 
 pub struct LogReader {
     dir: String,
+    signing_key: Option<SigningKey>,
 }
 
 impl LogReader {
     pub fn new(dir: impl AsRef<Path>) -> Self {
         Self {
             dir: dir.as_ref().to_string_lossy().to_string(),
+            signing_key: None,
         }
     }
 
+    /// Also check the HMAC chain of signed segments, not just record checksums
+    pub fn with_signing_key(mut self, signing_key: Option<SigningKey>) -> Self {
+        self.signing_key = signing_key;
+        self
+    }
+
     pub fn read_all_events(&self) -> Result<Vec<Event>> {
-        let segments = find_segment_files(self.dir.as_ref());
         let mut all_events = Vec::new();
+        self.for_each_event(|event| {
+            all_events.push(event);
+            Ok(())
+        })?;
+        Ok(all_events)
+    }
 
-        for (_id, path) in segments {
+    /// Pass every event to `f` in order, holding only one segment in memory at a time.
+    /// Stops at the first error from `f`.
+    pub fn for_each_event(&self, mut f: impl FnMut(Event) -> Result<()>) -> Result<()> {
+        let segments = find_segment_files(self.dir.as_ref());
+        let newest = segments.last().map(|(id, _)| *id);
+        for (id, path) in segments {
             // Skip segments that fail to deserialize (e.g., corrupted or old format)
             // This prevents one bad segment from breaking all playback
-            match self.read_segment(&path) {
-                Ok(events) => all_events.extend(events),
+            match self.read_segment(id, &path, Some(id) == newest) {
+                Ok(events) => events.into_iter().try_for_each(&mut f)?,
                 Err(e) => {
                     eprintln!("Warning: Skipping segment {:?} due to error: {}", path, e);
                     continue;
                 }
             }
         }
-
-        Ok(all_events)
+        Ok(())
     }
 
     /// Read only the most recent segment file (for initial state loading)
@@ -47,10 +77,10 @@ impl LogReader {
             return Ok(Vec::new());
         }
 
-        let (_id, path) = segments.last().unwrap();
+        let (id, path) = segments.last().unwrap();
 
         // Try to read the segment, but if it fails (e.g., old format), return empty
-        match self.read_segment(path) {
+        match self.read_segment(*id, path, true) {
             Ok(events) => Ok(events),
             Err(e) => {
                 eprintln!("Warning: Failed to read recent segment: {}", e);
@@ -59,68 +89,351 @@ impl LogReader {
         }
     }
 
-    fn read_segment(&self, path: &Path) -> Result<Vec<Event>> {
-        let mut file = File::open(path).context("Failed to open segment")?;
-
-        // Read and verify magic number
-        let mut magic_bytes = [0u8; 4];
-        file.read_exact(&mut magic_bytes)?;
-        let magic = u32::from_le_bytes(magic_bytes);
+    /// Events recorded after `position`, oldest first, for following the recording as it's
+    /// written. Only records appended since are decoded, so each call costs what was written
+    /// rather than the size of the segment. Without a position it starts at the newest segment.
+    pub fn read_new_events(&self, position: &mut Option<FollowPosition>) -> Result<Vec<Event>> {
+        let segments = find_segment_files(self.dir.as_ref());
+        let newest = segments.last().map(|(id, _)| *id);
+        let first = match position {
+            Some(position) => position.segment_id,
+            None => newest.unwrap_or_default(),
+        };
 
-        if magic != MAGIC {
-            anyhow::bail!("Invalid magic number in segment");
+        let mut events = Vec::new();
+        for (id, path) in segments.into_iter().filter(|(id, _)| *id >= first) {
+            // What's left of the segment being followed, then each one after it from the start
+            if position.as_ref().is_some_and(|position| position.segment_id != id) {
+                *position = None;
+            }
+            let position = position.get_or_insert_with(|| FollowPosition::start_of(id));
+            self.read_segment_from(&path, Some(id) == newest, position, &mut events)?;
         }
+        Ok(events)
+    }
 
+    fn read_segment(&self, segment_id: u64, path: &Path, newest: bool) -> Result<Vec<Event>> {
         let mut events = Vec::new();
+        self.read_segment_from(path, newest, &mut FollowPosition::start_of(segment_id), &mut events)?;
+        Ok(events)
+    }
 
-        loop {
-            // Try to read header
-            let header = match read_record_header(&mut file) {
-                Ok(h) => h,
-                Err(_) => break, // End of file
-            };
+    // The records from `position` on, which is moved past the last whole one. Records failing
+    // verification are replaced by an IntegrityViolation anomaly. Every segment starts with a
+    // full ProcessSnapshot, so its deltas can be rebuilt on their own. The newest segment is
+    // read up to any torn tail a crash left, see integrity::readable.
+    fn read_segment_from(
+        &self,
+        path: &Path,
+        newest: bool,
+        position: &mut FollowPosition,
+        events: &mut Vec<Event>,
+    ) -> Result<()> {
+        let segment_data = load_segment(path)?;
+        let format = SegmentFormat::detect(&segment_data)?;
+        let FollowPosition { segment_id, offset, chain, rebuilder } = position;
+        let end = if newest {
+            integrity::recoverable_end_from(&segment_data, *offset, format)
+        } else {
+            segment_data.len()
+        };
+        let chain = chain.get_or_insert_with(|| RecordChain::new(format, self.signing_key.clone(), *segment_id));
 
-            // Read payload
-            let mut payload = vec![0u8; header.payload_len as usize];
-            file.read_exact(&mut payload)?;
+        for record in integrity::records(&segment_data[..end], *offset, format) {
+            *offset = record.offset as usize + record.bytes.len() + record.trailer.len();
+            if let Err(reason) = chain.verify(&record) {
+                events.push(integrity::violation_event(path, &record, reason));
+                continue;
+            }
+
+            // Unchecksummed segments can end in a torn record that still looks complete;
+            // keep the events that decode rather than failing the whole segment. Deltas whose
+            // keyframe was lost are dropped too.
+            if let Ok(payload) = decode_payload(record.payload)
+                && let Ok(event) = rebuilder.rebuild(payload)
+            {
+                events.push(event);
+            }
+        }
 
-            // Deserialize event
-            let event: Event = bincode::deserialize(&payload)
-                .context("Failed to deserialize event")?;
+        Ok(())
+    }
 
-            events.push(event);
+    /// Integrity of every segment, oldest first
+    pub fn verify_segments(&self) -> Result<Vec<SegmentReport>> {
+        let mut reports = Vec::new();
+        for (id, path) in find_segment_files(self.dir.as_ref()) {
+            let data = load_segment(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            reports.push(integrity::verify_segment(&data, &path, id, self.signing_key.as_ref()));
         }
+        Ok(reports)
+    }
+}
 
-        Ok(events)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Anomaly, AnomalyKind, AnomalySeverity};
+    use crate::recorder::Recorder;
+    use crate::storage::{RecordHeader, RetentionPolicy};
+    use std::io::Write;
+    use tempfile::TempDir;
+    use time::OffsetDateTime;
+
+    fn test_event(message: &str) -> Event {
+        Event::Anomaly(Anomaly {
+            ts: OffsetDateTime::now_utc(),
+            severity: AnomalySeverity::Warning,
+            kind: AnomalyKind::CpuSpike,
+            message: message.to_string(),
+        })
     }
 
-    pub fn read_events_range(
-        &self,
-        start_time: Option<i64>,
-        end_time: Option<i64>,
-    ) -> Result<Vec<Event>> {
-        let all_events = self.read_all_events()?;
+    #[test]
+    fn test_read_segment_with_torn_final_record() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("first")).unwrap();
+        recorder.append(&test_event("second")).unwrap();
+        recorder.close().unwrap();
 
-        let filtered: Vec<Event> = all_events
-            .into_iter()
-            .filter(|event| {
-                let ts = event.timestamp().unix_timestamp();
+        // Simulate being killed part way through writing a third record
+        let payload = bincode::serialize(&test_event("third")).unwrap();
+        let header = bincode::serialize(&RecordHeader {
+            timestamp_unix_ns: OffsetDateTime::now_utc().unix_timestamp_nanos(),
+            payload_len: payload.len() as u32,
+        })
+        .unwrap();
+        let (_, path) = find_segment_files(temp_dir.path()).pop().unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&header).unwrap();
+        file.write_all(&payload[..payload.len() / 2]).unwrap();
+        drop(file);
+
+        let events = LogReader::new(temp_dir.path()).read_all_events().unwrap();
+        assert_eq!(events.len(), 2);
+    }
 
-                let after_start = start_time.map_or(true, |s| ts >= s);
-                let before_end = end_time.map_or(true, |e| ts <= e);
+    #[test]
+    fn test_torn_tail_of_newest_segment_is_not_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
 
-                after_start && before_end
-            })
-            .collect();
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("first")).unwrap();
+        recorder.append(&test_event("second")).unwrap();
+        recorder.close().unwrap();
+        let (_, path) = find_segment_files(temp_dir.path()).pop().unwrap();
+        let whole = std::fs::read(&path).unwrap();
 
-        Ok(filtered)
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("third")).unwrap();
+        recorder.close().unwrap();
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.len() > whole.len() && written.starts_with(&whole));
+
+        // Cut off anywhere inside the third record, or with zeros where it should have been
+        let mut torn: Vec<Vec<u8>> = (whole.len() + 1..written.len()).map(|end| written[..end].to_vec()).collect();
+        torn.push([whole.as_slice(), &vec![0; written.len() - whole.len()]].concat());
+        for data in torn {
+            std::fs::write(&path, &data).unwrap();
+            let events = LogReader::new(temp_dir.path()).read_all_events().unwrap();
+            assert_eq!(events.len(), 2, "torn at {}", data.len());
+            assert!(!events.iter().any(|e| matches!(e, Event::Anomaly(a) if matches!(a.kind, AnomalyKind::IntegrityViolation))));
+
+            let indexed = crate::indexed_reader::IndexedReader::new(temp_dir.path()).unwrap();
+            assert_eq!(indexed.read_time_range(None, None).unwrap().len(), 2, "torn at {}", data.len());
+            let (first_ns, last_ns) = indexed.get_time_range().unwrap();
+            assert!(first_ns > 0 && last_ns >= first_ns, "torn at {}", data.len());
+        }
     }
-}
 
-fn read_record_header(file: &mut File) -> Result<RecordHeader> {
-    // bincode will read exactly as many bytes as needed
-    let header: RecordHeader = bincode::deserialize_from(file)
-        .context("Failed to deserialize header")?;
+    #[test]
+    fn test_follow_reads_on_from_the_last_whole_record() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("first")).unwrap();
+        recorder.append(&test_event("second")).unwrap();
+        recorder.close().unwrap();
+        let (_, path) = find_segment_files(temp_dir.path()).pop().unwrap();
+        let whole = std::fs::read(&path).unwrap();
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("third")).unwrap();
+        recorder.close().unwrap();
+        let written = std::fs::read(&path).unwrap();
 
-    Ok(header)
+        // The third record part way through being written
+        std::fs::write(&path, &written[..whole.len() + 5]).unwrap();
+        let reader = LogReader::new(temp_dir.path());
+        let mut position = None;
+        assert_eq!(reader.read_new_events(&mut position).unwrap().len(), 2);
+
+        // Once it's complete only it is read: the records before aren't looked at again
+        let mut rest = vec![0; whole.len()];
+        rest[..SEGMENT_HEADER_LEN as usize].copy_from_slice(&whole[..SEGMENT_HEADER_LEN as usize]);
+        rest.extend_from_slice(&written[whole.len()..]);
+        std::fs::write(&path, &rest).unwrap();
+        match &reader.read_new_events(&mut position).unwrap()[..] {
+            [Event::Anomaly(anomaly)] => assert_eq!(anomaly.message, "third"),
+            other => panic!("expected only the third event, got {:?}", other),
+        }
+        assert!(reader.read_new_events(&mut position).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reads_segment_recorded_by_first_release() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("segment_00000.dat"), include_bytes!("testdata/baseline_segment.dat")).unwrap();
+
+        let events = LogReader::new(temp_dir.path()).read_all_events().unwrap();
+        assert_eq!(events.len(), 7);
+        match &events[..] {
+            [Event::SystemMetrics(m), Event::ProcessSnapshot(s), .., Event::SystemMetrics(last)] => {
+                assert_eq!(m.mem_used_bytes, 2 << 30);
+                assert_eq!(m.mem_available_bytes, None);
+                assert_eq!(m.hugepages_free, None);
+                assert_eq!(s.processes.len(), 2);
+                assert_eq!(last.cpu_usage_percent, 91.0);
+            }
+            other => panic!("expected metrics and a process snapshot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_unchecksummed_segment_with_garbage_final_record() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Without checksums a torn record can have a complete length but not decode
+        let mut data = crate::storage::MAGIC.to_le_bytes().to_vec();
+        let payloads = [bincode::serialize(&test_event("first")).unwrap(), vec![0xFF; 12]];
+        for payload in &payloads {
+            data.extend(bincode::serialize(&RecordHeader { timestamp_unix_ns: 0, payload_len: payload.len() as u32 }).unwrap());
+            data.extend(payload);
+        }
+        std::fs::write(temp_dir.path().join("segment_00000.dat"), &data).unwrap();
+
+        let events = LogReader::new(temp_dir.path()).read_all_events().unwrap();
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_tampered_record_becomes_integrity_anomaly() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let key = SigningKey::new("test-key");
+
+        let mut recorder =
+            Recorder::open_with_config(temp_dir.path(), retention, None, Some(key.clone())).unwrap();
+        for message in ["first", "second", "third"] {
+            recorder.append(&test_event(message)).unwrap();
+        }
+        recorder.close().unwrap();
+
+        let (_, path) = find_segment_files(temp_dir.path()).pop().unwrap();
+        let mut data = std::fs::read(&path).unwrap();
+        let pos = data.windows(6).position(|w| w == b"second").unwrap();
+        data[pos..pos + 6].copy_from_slice(b"SECOND");
+        std::fs::write(&path, &data).unwrap();
+
+        let reader = LogReader::new(temp_dir.path()).with_signing_key(Some(key));
+        let events = reader.read_all_events().unwrap();
+        assert_eq!(events.len(), 3);
+        match &events[1] {
+            Event::Anomaly(a) => {
+                assert!(matches!(a.kind, AnomalyKind::IntegrityViolation));
+                assert!(a.message.contains("checksum mismatch"), "{}", a.message);
+            }
+            other => panic!("expected an integrity anomaly, got {:?}", other),
+        }
+
+        let reports = reader.verify_segments().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].format, Some(SegmentFormat::Signed));
+        assert_eq!(reports[0].records, 3);
+        assert_eq!(reports[0].violations.len(), 1);
+    }
+
+    #[test]
+    fn test_recorder_starts_new_segment_after_legacy_segment() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+
+        // A segment from before checksums were added
+        let legacy = bincode::serialize(&test_event("legacy")).unwrap();
+        let mut data = crate::storage::MAGIC.to_le_bytes().to_vec();
+        data.extend(bincode::serialize(&RecordHeader { timestamp_unix_ns: 0, payload_len: legacy.len() as u32 }).unwrap());
+        data.extend(&legacy);
+        std::fs::write(temp_dir.path().join("segment_00000.dat"), &data).unwrap();
+
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("new")).unwrap();
+        recorder.close().unwrap();
+        assert_eq!(find_segment_files(temp_dir.path()).len(), 2);
+
+        // Reopening appends to the checksummed segment...
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("resumed")).unwrap();
+        recorder.close().unwrap();
+        assert_eq!(find_segment_files(temp_dir.path()).len(), 2);
+
+        // ...even when it ends in a torn record, which is cut off and reported
+        let (_, path) = find_segment_files(temp_dir.path()).pop().unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[1, 2, 3]).unwrap();
+        drop(file);
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&test_event("after torn")).unwrap();
+        recorder.close().unwrap();
+        assert_eq!(find_segment_files(temp_dir.path()).len(), 2);
+
+        let reader = LogReader::new(temp_dir.path());
+        let events = reader.read_all_events().unwrap();
+        assert_eq!(events.len(), 5);
+        assert!(matches!(&events[3], Event::Anomaly(a) if matches!(a.kind, AnomalyKind::TornWriteRecovered)));
+        assert!(reader.verify_segments().unwrap().iter().all(|r| r.violations.is_empty()));
+    }
+
+    #[test]
+    fn test_compressed_segment_reads_like_uncompressed() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let key = SigningKey::new("test-key");
+
+        let mut recorder =
+            Recorder::open_with_config(temp_dir.path(), retention, None, Some(key.clone())).unwrap();
+        for i in 0..500 {
+            recorder.append(&test_event(&format!("event {}", i))).unwrap();
+        }
+        recorder.close().unwrap();
+
+        let reader = LogReader::new(temp_dir.path()).with_signing_key(Some(key.clone()));
+        let indexed = crate::indexed_reader::IndexedReader::new(temp_dir.path()).unwrap();
+        let before = reader.read_all_events().unwrap();
+        let indexed_before = indexed.read_time_range(None, None).unwrap();
+
+        let (_, path) = find_segment_files(temp_dir.path()).pop().unwrap();
+        let uncompressed_len = std::fs::metadata(&path).unwrap().len();
+        crate::storage::compress_segment(&path, 3).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() < uncompressed_len);
+
+        let after = reader.read_all_events().unwrap();
+        assert_eq!(after.len(), 500);
+        assert_eq!(format!("{:?}", after), format!("{:?}", before));
+        assert!(reader.verify_segments().unwrap().iter().all(|r| r.violations.is_empty()));
+
+        // The cached index no longer matches the file and is rebuilt from the decompressed data
+        let indexed = crate::indexed_reader::IndexedReader::new(temp_dir.path()).unwrap();
+        let indexed_after = indexed.read_time_range(None, None).unwrap();
+        assert_eq!(format!("{:?}", indexed_after), format!("{:?}", indexed_before));
+
+        // A compressed segment is never appended to
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, Some(key)).unwrap();
+        recorder.append(&test_event("after compression")).unwrap();
+        recorder.close().unwrap();
+        assert_eq!(find_segment_files(temp_dir.path()).len(), 2);
+        assert_eq!(reader.read_all_events().unwrap().len(), 501);
+    }
 }
@@ -1,67 +1,250 @@
 use std::{
     fs::{File, OpenOptions},
-    io::{BufWriter, Seek, SeekFrom, Write},
+    io::{Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
 use anyhow::Result;
 use time::OffsetDateTime;
 
 use crate::broadcast::SyncSender;
-use crate::event::Event;
-use crate::storage::{find_segment_files, RecordHeader, FLUSH_INTERVAL_SECONDS, MAGIC, SEGMENT_SIZE};
+use crate::downsample;
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, Event, SystemEvent, SystemEventKind};
+use crate::index::IndexBuilder;
+use crate::integrity::{self, RecordChain, SegmentFormat, SigningKey};
+use crate::process_delta::{self, DeltaEncoder};
+use crate::protection::ProtectionManager;
+use crate::summary::SummaryWriter;
+use crate::storage::{
+    compress_segment, find_segment_files, load_segment, rewrite_segment, DownsamplePolicy, FsyncPolicy, RecordHeader,
+    RetentionPolicy, FLUSH_INTERVAL_SECONDS, MAX_PENDING_BYTES, SEGMENT_HEADER_LEN, SEGMENT_SIZE,
+};
+
+// Newest segment the downsampler has been through, so a restart doesn't start again from the oldest
+const DOWNSAMPLE_STATE_FILE: &str = "downsample.state";
 
 pub struct Recorder {
     dir: PathBuf,
     current_segment: u64,
     oldest_segment: u64,
-    max_segments: usize,
-    file: BufWriter<File>,
-    offset: u64,
+    retention: RetentionPolicy,
+    segment_size: u64, // A segment is sealed once the next record would take it past this
+    file: File,
+    pending: Vec<u8>, // Records appended since the last flush, written to `file` in one go
+    offset: u64,      // End of the segment including pending records
     broadcast_tx: Option<SyncSender>,
+    fsync: FsyncPolicy,
     last_flush: OffsetDateTime,
+    last_retention_check: OffsetDateTime,
+    summary: SummaryWriter,
+    chain: RecordChain,
+    compression_level: i32,
+    previous_run: Option<PreviousRun>,
+    protection: Option<ProtectionManager>,
+    process_deltas: DeltaEncoder,
+    failed_verification: Option<u64>, // Sealed segment last reported by verify_sealed_segment
+    downsample: Option<DownsamplePolicy>,
+    downsampled_through: Option<u64>,
+    last_downsample_check: OffsetDateTime,
+}
+
+/// How the recording before this one ended, from the last record of the newest segment
+struct PreviousRun {
+    last_record: OffsetDateTime,
+    clean_shutdown: bool,
 }
 
 impl Recorder {
     pub fn open_with_config(
         dir: impl AsRef<Path>,
-        max_segments: usize,
+        retention: RetentionPolicy,
         broadcast_tx: Option<SyncSender>,
+        signing_key: Option<SigningKey>,
     ) -> Result<Self> {
         let dir = dir.as_ref();
         std::fs::create_dir_all(dir)?;
 
         // Find existing segments to resume from
-        let (current_segment, oldest_segment) = Self::find_segment_range(dir)?;
+        let (mut current_segment, oldest_segment) = Self::find_segment_range(dir)?;
 
+        let format = if signing_key.is_some() { SegmentFormat::Signed } else { SegmentFormat::Checksummed };
+        let mut chain = RecordChain::new(format, signing_key, current_segment);
+
+        // Append to the last segment if it has our format, otherwise start a new one rather
+        // than rewriting it. A record torn by a crash or power loss is cut off first.
+        let path = segment_path(dir, current_segment);
+        let mut torn_bytes = 0;
+        let mut previous_run = None;
+        if let Ok(data) = std::fs::read(&path)
+            && !data.is_empty()
+        {
+            if SegmentFormat::of_segment(&data) == Some(format) {
+                let end = integrity::recoverable_end(&data, format);
+                if end < data.len() {
+                    let file = OpenOptions::new().write(true).open(&path)?;
+                    file.set_len(end as u64)?;
+                    file.sync_all()?;
+                    torn_bytes = data.len() - end;
+                }
+                previous_run = integrity::records(&data[..end], SEGMENT_HEADER_LEN as usize, format)
+                    .last()
+                    .and_then(|record| {
+                        let last_record = OffsetDateTime::from_unix_timestamp_nanos(record.header.timestamp_unix_ns).ok()?;
+                        let clean_shutdown = matches!(
+                            Event::decode(record.payload),
+                            Ok(Event::SystemEvent(SystemEvent { kind: SystemEventKind::Shutdown, .. }))
+                        );
+                        Some(PreviousRun { last_record, clean_shutdown })
+                    });
+                chain.resume(&data, end);
+            } else {
+                current_segment += 1;
+                chain.restart(current_segment);
+            }
+        }
         let path = segment_path(dir, current_segment);
 
-        let raw_file = OpenOptions::new()
+        // O_APPEND, which append-only segments can't be opened without
+        let mut file = OpenOptions::new()
             .create(true)
             .read(true)
-            .write(true)
+            .append(true)
             .open(&path)?;
 
-        let mut offset = raw_file.metadata()?.len();
-        let mut file = BufWriter::new(raw_file);
+        let mut offset = file.metadata()?.len();
 
         if offset == 0 {
-            file.write_all(&MAGIC.to_le_bytes())?;
-            file.flush()?;
-            offset = 4;
+            file.write_all(&format.magic().to_le_bytes())?;
+            offset = SEGMENT_HEADER_LEN;
         } else {
             file.seek(SeekFrom::Start(offset))?;
         }
 
-        Ok(Self {
+        // Per-minute timeline aggregates, brought up to date with what's already recorded
+        let summary = SummaryWriter::open(dir)?;
+
+        let mut recorder = Self {
             dir: dir.to_path_buf(),
             current_segment,
             oldest_segment,
-            max_segments,
+            retention,
+            segment_size: SEGMENT_SIZE,
             file,
+            pending: Vec::new(),
             offset,
             broadcast_tx,
+            fsync: FsyncPolicy::default(),
             last_flush: OffsetDateTime::now_utc(),
+            last_retention_check: OffsetDateTime::now_utc(),
+            summary,
+            chain,
+            compression_level: 0,
+            previous_run,
+            protection: None,
+            process_deltas: DeltaEncoder::new(),
+            failed_verification: None,
+            downsample: None,
+            downsampled_through: None,
+            last_downsample_check: OffsetDateTime::now_utc(),
+        };
+
+        // Apply the configured limits to whatever is already on disk
+        recorder.enforce_retention();
+
+        if torn_bytes > 0 {
+            let message = format!(
+                "Recovered {} after a torn write: removed {} bytes of incomplete records",
+                path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().to_string()),
+                torn_bytes
+            );
+            eprintln!("Warning: {}", message);
+            recorder.append(&Event::Anomaly(Anomaly {
+                ts: OffsetDateTime::now_utc(),
+                severity: AnomalySeverity::Warning,
+                kind: AnomalyKind::TornWriteRecovered,
+                message,
+            }))?;
+        }
+
+        Ok(recorder)
+    }
+
+    /// Compress segments with zstd at `level` once they are full (0 leaves them as written)
+    pub fn with_compression(mut self, level: i32) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// Seal segments at `bytes` rather than the default SEGMENT_SIZE
+    pub fn with_segment_size(mut self, bytes: u64) -> Self {
+        self.segment_size = bytes;
+        self
+    }
+
+    /// Roll up the SystemMetrics of sealed segments once they are older than the policy allows
+    pub fn with_downsampling(mut self, policy: Option<DownsamplePolicy>) -> Self {
+        self.downsample = policy;
+        self.downsampled_through = std::fs::read_to_string(self.dir.join(DOWNSAMPLE_STATE_FILE))
+            .ok()
+            .and_then(|id| id.trim().parse().ok());
+        self
+    }
+
+    /// fsync after every flush rather than only when a segment is sealed
+    pub fn with_fsync(mut self, fsync: FsyncPolicy) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Protect every segment, the current one append-only and full ones with the sealed
+    /// attribute, then each new segment as it is created. On failure recording carries on
+    /// unprotected.
+    pub fn enable_protection(&mut self, mut protection: ProtectionManager) -> Result<()> {
+        if !protection.is_enabled() {
+            return Ok(());
+        }
+        for (id, path) in find_segment_files(&self.dir) {
+            let result = if id < self.current_segment {
+                protection.seal_file(&path)
+            } else {
+                protection.protect_file(&path)
+            };
+            // Dropping the manager releases whatever was protected so far
+            result?;
+        }
+        self.protection = Some(protection);
+        Ok(())
+    }
+
+    /// Messages for protected segments whose attributes have been changed behind our back
+    pub fn check_protection(&mut self) -> Vec<String> {
+        self.protection.as_mut().map(ProtectionManager::check).unwrap_or_default()
+    }
+
+    /// Check the newest sealed segment still verifies, since protection attributes only stop
+    /// edits by anyone without root. Each failing segment is reported once.
+    pub fn verify_sealed_segment(&mut self) -> Option<Anomaly> {
+        if self.current_segment == self.oldest_segment {
+            return None;
+        }
+        let id = self.current_segment - 1;
+        if self.failed_verification == Some(id) {
+            return None;
+        }
+        let path = segment_path(&self.dir, id);
+        let problem = match load_segment(&path) {
+            Ok(data) => integrity::verify_segment(&data, &path, id, self.chain.key()).first_problem(true)?,
+            // A deleted segment is reported by check_protection
+            Err(_) if !path.exists() => return None,
+            Err(e) => format!("could not be read: {:#}", e),
+        };
+        self.failed_verification = Some(id);
+        Some(Anomaly {
+            ts: OffsetDateTime::now_utc(),
+            severity: AnomalySeverity::Critical,
+            kind: AnomalyKind::IntegrityViolation,
+            message: format!("Sealed segment segment_{:05}.dat failed verification ({})", id, problem),
         })
     }
 
@@ -75,69 +258,810 @@ impl Recorder {
     }
 
     pub fn append(&mut self, event: &Event) -> Result<()> {
-        let payload = bincode::serialize(event)?;
+        let mut record = self.encode_record(event)?;
+        let trailer_len = self.chain.format().trailer_len();
+
+        if self.offset + (record.len() + trailer_len) as u64 > self.segment_size {
+            self.rotate_segment()?;
+            // A snapshot encoded as a delta becomes the new segment's keyframe
+            record = self.encode_record(event)?;
+        }
+        let record_len = record.len() + trailer_len;
+
+        let trailer = self.chain.seal(&record);
+        self.pending.extend_from_slice(&record);
+        self.pending.extend_from_slice(&trailer);
+
+        self.offset += record_len as u64;
+
+        if let Err(e) = self.summary.add(event) {
+            eprintln!("Warning: Failed to update timeline summary: {}", e);
+        }
+
+        // Broadcast event to WebSocket clients (non-blocking), without waiting for the flush
+        if let Some(tx) = &self.broadcast_tx {
+            let _ = tx.try_send(event.clone());
+        }
+
+        // The collection loop flushes every tick, this covers anything else appending
+        if self.pending.len() >= MAX_PENDING_BYTES
+            || (OffsetDateTime::now_utc() - self.last_flush).whole_seconds() >= FLUSH_INTERVAL_SECONDS
+        {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // Header and payload of the record for `event`, with ProcessSnapshots stored as deltas
+    // from the last full one where they can be
+    fn encode_record(&mut self, event: &Event) -> Result<Vec<u8>> {
+        let payload = match event {
+            Event::ProcessSnapshot(snapshot) => match self.process_deltas.encode(snapshot) {
+                Some(delta) => process_delta::encode_delta(&delta)?,
+                None => bincode::serialize(event)?,
+            },
+            _ => bincode::serialize(event)?,
+        };
 
         let header = RecordHeader {
             timestamp_unix_ns: OffsetDateTime::now_utc().unix_timestamp_nanos(),
             payload_len: payload.len() as u32,
         };
 
-        let header_bytes = bincode::serialize(&header)?;
-        let record_len = header_bytes.len() + payload.len();
+        let mut record = bincode::serialize(&header)?;
+        record.extend_from_slice(&payload);
+        Ok(record)
+    }
 
-        if self.offset + record_len as u64 > SEGMENT_SIZE {
-            self.rotate_segment()?;
+    /// Write the records appended since the last flush in one write, making them visible
+    /// to playback, and fsync them if the policy is per tick
+    pub fn flush(&mut self) -> Result<()> {
+        let now = OffsetDateTime::now_utc();
+        self.last_flush = now;
+        if !self.pending.is_empty() {
+            self.write_pending()?;
+            if self.fsync == FsyncPolicy::Tick {
+                self.file.sync_data()?;
+            }
         }
 
-        self.file.write_all(&header_bytes)?;
-        self.file.write_all(&payload)?;
+        // Age-based retention can expire segments long before the size limit is hit
+        if self.retention.max_age_secs.is_some()
+            && (now - self.last_retention_check).whole_seconds() >= FLUSH_INTERVAL_SECONDS
+        {
+            self.last_retention_check = now;
+            self.enforce_retention();
+        }
+        if self.downsample.is_some() && (now - self.last_downsample_check).whole_seconds() >= FLUSH_INTERVAL_SECONDS {
+            self.last_downsample_check = now;
+            self.downsample_next_segment();
+        }
+        Ok(())
+    }
 
-        self.offset += record_len as u64;
+    // Roll up the oldest sealed segment that's past full resolution and hasn't been yet. One
+    // segment per call so the collection loop isn't held up. Segments under protection are
+    // left as they were recorded.
+    fn downsample_next_segment(&mut self) {
+        let Some(policy) = self.downsample else {
+            return;
+        };
+        if self.protection.is_some() {
+            return;
+        }
+        let id = self.downsampled_through.map_or(self.oldest_segment, |id| (id + 1).max(self.oldest_segment));
+        if id >= self.current_segment {
+            return;
+        }
+        let path = segment_path(&self.dir, id);
+        let cutoff = SystemTime::now().checked_sub(Duration::from_secs(policy.after_secs));
+        // Segment mtime is the time of its newest record, and segments age in order
+        if let Ok(modified) = std::fs::metadata(&path).and_then(|meta| meta.modified())
+            && cutoff.is_none_or(|cutoff| modified >= cutoff)
+        {
+            return;
+        }
+
+        let result = load_segment(&path).and_then(|data| {
+            let Some(rewritten) = downsample::downsample_segment(&data, id, self.chain.key(), policy.window_secs)? else {
+                return Ok(());
+            };
+            rewrite_segment(&path, &rewritten, self.compression_level)?;
+            let _ = std::fs::remove_file(path.with_extension("idx"));
+            let _ = IndexBuilder::new(&self.dir).build_segment_index(id, &path, false);
+            Ok(())
+        });
+        // A missing segment was deleted by retention, one that fails is left as evidence
+        if let Err(e) = result
+            && path.exists()
+        {
+            eprintln!("Warning: segment_{:05}.dat not downsampled: {:#}", id, e);
+        }
+        self.downsampled_through = Some(id);
+        if let Err(e) = std::fs::write(self.dir.join(DOWNSAMPLE_STATE_FILE), id.to_string()) {
+            eprintln!("Warning: Failed to save downsampling progress: {}", e);
+        }
+    }
 
-        // Periodic flush every 30 seconds to make recent data available for playback
+    fn write_pending(&mut self) -> Result<()> {
+        self.file.write_all(&self.pending)?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Compare the machine's boot time with the end of the previous recording. Records a
+    /// reboot marker if it booted since, and a Critical anomaly if the previous run never
+    /// wrote its Shutdown marker. Does nothing on the first run in a data directory.
+    pub fn record_startup(&mut self, boot_time: OffsetDateTime) -> Result<()> {
+        let Some(previous) = self.previous_run.take() else {
+            return Ok(());
+        };
         let now = OffsetDateTime::now_utc();
-        if (now - self.last_flush).whole_seconds() >= FLUSH_INTERVAL_SECONDS {
-            self.file.flush()?;
-            self.last_flush = now;
+        let rebooted = boot_time > previous.last_record;
+
+        if rebooted {
+            let message = format!(
+                "System rebooted at {} after recording stopped at {} ({} shutdown)",
+                format_utc(boot_time),
+                format_utc(previous.last_record),
+                if previous.clean_shutdown { "clean" } else { "unclean" }
+            );
+            self.append(&Event::SystemEvent(SystemEvent {
+                ts: now,
+                kind: SystemEventKind::Rebooted {
+                    boot_time,
+                    last_event: previous.last_record,
+                    clean_shutdown: previous.clean_shutdown,
+                },
+                message,
+            }))?;
         }
 
-        // Broadcast event to WebSocket clients (non-blocking)
-        if let Some(tx) = &self.broadcast_tx {
-            let _ = tx.try_send(event.clone());
+        if !previous.clean_shutdown {
+            let message = if rebooted {
+                format!(
+                    "Unclean shutdown: the system went down after {} without Black Box stopping cleanly (crash, power loss or forced reset)",
+                    format_utc(previous.last_record)
+                )
+            } else {
+                format!(
+                    "Unclean shutdown: Black Box stopped without shutting down cleanly after {}, the system did not reboot",
+                    format_utc(previous.last_record)
+                )
+            };
+            eprintln!("Warning: {}", message);
+            self.append(&Event::Anomaly(Anomaly {
+                ts: now,
+                severity: AnomalySeverity::Critical,
+                kind: AnomalyKind::UncleanShutdown,
+                message,
+            }))?;
         }
+        Ok(())
+    }
 
+    /// Write the Shutdown marker for the next start to find, then close
+    pub fn shutdown(mut self) -> Result<()> {
+        self.append(&Event::SystemEvent(SystemEvent {
+            ts: OffsetDateTime::now_utc(),
+            kind: SystemEventKind::Shutdown,
+            message: "Black Box stopped".to_string(),
+        }))?;
+        self.close()
+    }
+
+    /// Flush buffered records and fsync the current segment before shutdown
+    pub fn close(mut self) -> Result<()> {
+        self.write_pending()?;
+        self.file.sync_all()?;
         Ok(())
     }
 
     fn rotate_segment(&mut self) -> Result<()> {
+        // Seal the full segment and write its time index now, so readers don't have to scan it
+        self.write_pending()?;
+        self.file.sync_all()?;
+        let sealed_path = segment_path(&self.dir, self.current_segment);
+        if self.compression_level > 0 {
+            // Compressing replaces the file, which its append-only attribute would prevent
+            if let Some(protection) = &mut self.protection
+                && let Err(e) = protection.unprotect_file(&sealed_path)
+            {
+                eprintln!("Warning: {:#}", e);
+            }
+            if let Err(e) = compress_segment(&sealed_path, self.compression_level) {
+                eprintln!("Warning: Failed to compress {}: {}", sealed_path.display(), e);
+            }
+        }
+        let _ = IndexBuilder::new(&self.dir).build_segment_index(self.current_segment, &sealed_path, false);
+        if let Some(protection) = &mut self.protection
+            && let Err(e) = protection.seal_file(&sealed_path)
+        {
+            eprintln!("Warning: {:#}", e);
+        }
+
         self.current_segment += 1;
         self.offset = 0;
 
-        // Enforce ring buffer: delete oldest segment if we exceed max
-        let segment_count = (self.current_segment - self.oldest_segment + 1) as usize;
-        if segment_count > self.max_segments {
-            let old_path = segment_path(&self.dir, self.oldest_segment);
-            let _ = std::fs::remove_file(old_path); // Ignore errors if file doesn't exist
-            self.oldest_segment += 1;
-        }
+        // Enforce ring buffer: delete oldest segments if we exceed the limits
+        self.enforce_retention();
 
         let path = segment_path(&self.dir, self.current_segment);
-        self.file = BufWriter::new(OpenOptions::new()
+        self.file = OpenOptions::new()
             .create(true)
             .read(true)
-            .write(true)
-            .open(&path)?);
+            .append(true)
+            .open(&path)?;
+
+        self.chain.restart(self.current_segment);
+        self.process_deltas.reset();
+        self.file.write_all(&self.chain.format().magic().to_le_bytes())?;
+        self.offset += SEGMENT_HEADER_LEN;
 
-        self.file.write_all(&MAGIC.to_le_bytes())?;
-        self.file.flush()?;  // Ensure magic number is written to disk
-        self.last_flush = OffsetDateTime::now_utc();
-        self.offset += 4;
+        if let Some(protection) = &mut self.protection
+            && let Err(e) = protection.protect_file(&path)
+        {
+            eprintln!("Warning: {:#}", e);
+        }
 
         Ok(())
     }
+
+    // Delete the oldest segments until both the size and age limits hold.
+    // The current segment is never deleted.
+    fn enforce_retention(&mut self) {
+        let cutoff = self
+            .retention
+            .max_age_secs
+            .and_then(|secs| SystemTime::now().checked_sub(Duration::from_secs(secs)));
+
+        // Sealed segments count at their size on disk (compressed or not), and the
+        // current one at the size it will grow to
+        let mut total_bytes = self.segment_size
+            + (self.oldest_segment..self.current_segment)
+                .filter_map(|id| std::fs::metadata(segment_path(&self.dir, id)).ok())
+                .map(|meta| meta.len())
+                .sum::<u64>();
+
+        while self.oldest_segment < self.current_segment {
+            let old_path = segment_path(&self.dir, self.oldest_segment);
+            let meta = std::fs::metadata(&old_path);
+
+            // Segment mtime is the time of its newest record
+            let expired = match (cutoff, &meta) {
+                (Some(cutoff), Ok(meta)) => meta.modified().map(|m| m < cutoff).unwrap_or(false),
+                (_, Err(_)) => true, // Already gone
+                (None, Ok(_)) => false,
+            };
+
+            if total_bytes <= self.retention.max_bytes && !expired {
+                break;
+            }
+
+            if let Some(protection) = &mut self.protection {
+                let _ = protection.unprotect_file(&old_path);
+            }
+            let _ = std::fs::remove_file(&old_path); // Ignore errors if file doesn't exist
+            let _ = std::fs::remove_file(old_path.with_extension("idx"));
+            total_bytes -= meta.map(|meta| meta.len()).unwrap_or(0);
+            self.oldest_segment += 1;
+        }
+    }
+}
+
+// Records still pending when the recorder is dropped (an error returned from the main
+// loop, say) are written out rather than lost
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.write_pending();
+    }
+}
+
+fn format_utc(ts: OffsetDateTime) -> String {
+    let ts = ts.replace_nanosecond(0).unwrap_or(ts);
+    ts.format(&time::format_description::well_known::Rfc3339).unwrap_or_else(|_| ts.to_string())
 }
 
 fn segment_path(dir: &Path, id: u64) -> PathBuf {
     dir.join(format!("segment_{:05}.dat", id))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_segment(dir: &Path, id: u64, age_secs: u64) {
+        let path = segment_path(dir, id);
+        std::fs::write(&path, crate::storage::MAGIC_CHECKSUM.to_le_bytes()).unwrap();
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age_secs)).unwrap();
+    }
+
+    #[test]
+    fn test_retention_deletes_expired_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        write_segment(temp_dir.path(), 0, 10 * 24 * 60 * 60);
+        write_segment(temp_dir.path(), 1, 3 * 24 * 60 * 60);
+        write_segment(temp_dir.path(), 2, 0);
+
+        let retention = RetentionPolicy {
+            max_bytes: 100 * SEGMENT_SIZE,
+            max_age_secs: Some(7 * 24 * 60 * 60),
+        };
+        let recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+
+        assert_eq!(recorder.oldest_segment, 1);
+        assert!(!segment_path(temp_dir.path(), 0).exists());
+        assert!(segment_path(temp_dir.path(), 1).exists());
+    }
+
+    // Pad sealed segments out to their on-disk size
+    fn set_segment_len(dir: &Path, id: u64, len: u64) {
+        File::options().write(true).open(segment_path(dir, id)).unwrap().set_len(len).unwrap();
+    }
+
+    #[test]
+    fn test_retention_enforces_segment_count() {
+        let temp_dir = TempDir::new().unwrap();
+        for id in 0..5 {
+            write_segment(temp_dir.path(), id, 0);
+        }
+        for id in 0..4 {
+            set_segment_len(temp_dir.path(), id, SEGMENT_SIZE);
+        }
+
+        let retention = RetentionPolicy {
+            max_bytes: 2 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+
+        assert_eq!(recorder.oldest_segment, 3);
+        assert_eq!(find_segment_files(temp_dir.path()).len(), 2);
+    }
+
+    #[test]
+    fn test_open_recovers_torn_final_record() {
+        let retention = RetentionPolicy {
+            max_bytes: 100 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let source = TempDir::new().unwrap();
+        let mut recorder = Recorder::open_with_config(source.path(), retention, None, None).unwrap();
+        recorder.append(&cpu_spike("first")).unwrap();
+        recorder.append(&cpu_spike("second")).unwrap();
+        let second_end = recorder.offset;
+        recorder.append(&cpu_spike("third")).unwrap();
+        recorder.close().unwrap();
+        let data = std::fs::read(segment_path(source.path(), 0)).unwrap();
+
+        // Cut the third record inside its header, payload and checksum, or pad the end with zeros
+        let mut cases: Vec<Vec<u8>> = [second_end + 1, second_end + 10, data.len() as u64 - 10, data.len() as u64 - 1]
+            .iter()
+            .map(|&len| data[..len as usize].to_vec())
+            .collect();
+        cases.push([&data[..second_end as usize], &[0u8; 64][..]].concat());
+
+        for torn in cases {
+            let temp_dir = TempDir::new().unwrap();
+            let path = segment_path(temp_dir.path(), 0);
+            std::fs::write(&path, &torn).unwrap();
+
+            let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+            recorder.append(&cpu_spike("after")).unwrap();
+            recorder.close().unwrap();
+
+            assert_eq!(find_segment_files(temp_dir.path()).len(), 1);
+            let recovered = std::fs::read(&path).unwrap();
+            assert_eq!(recovered[..second_end as usize], data[..second_end as usize]);
+
+            let events = crate::reader::LogReader::new(temp_dir.path()).read_all_events().unwrap();
+            let messages: Vec<String> = events
+                .iter()
+                .map(|e| match e {
+                    Event::Anomaly(a) => a.message.clone(),
+                    other => panic!("unexpected event {:?}", other),
+                })
+                .collect();
+            assert_eq!(messages.len(), 4, "{:?}", messages);
+            assert_eq!(messages[..2], ["first", "second"]);
+            assert!(messages[2].starts_with("Recovered segment_00000.dat"), "{}", messages[2]);
+            assert_eq!(messages[3], "after");
+        }
+    }
+
+    fn cpu_spike(message: &str) -> Event {
+        Event::Anomaly(Anomaly {
+            ts: OffsetDateTime::now_utc(),
+            severity: AnomalySeverity::Info,
+            kind: AnomalyKind::CpuSpike,
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_flush_writes_a_tick_of_records_at_once() {
+        let retention = RetentionPolicy {
+            max_bytes: 100 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let path = segment_path(temp_dir.path(), 0);
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, Some(tx), None)
+            .unwrap()
+            .with_fsync(FsyncPolicy::Tick);
+
+        for i in 0..3 {
+            recorder.append(&cpu_spike(&format!("spike {}", i))).unwrap();
+        }
+        // Live clients see every event straight away, the segment only after the flush
+        assert_eq!(rx.try_iter().count(), 3);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), SEGMENT_HEADER_LEN);
+
+        recorder.flush().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), recorder.offset);
+        assert_eq!(crate::reader::LogReader::new(temp_dir.path()).read_all_events().unwrap().len(), 3);
+
+        // A flush with nothing pending writes nothing
+        recorder.flush().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), recorder.offset);
+    }
+
+    #[test]
+    fn test_segment_size_sets_rotation_and_retention() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy {
+            max_bytes: 4 * 1024,
+            max_age_secs: None,
+        };
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None)
+            .unwrap()
+            .with_compression(0)
+            .with_segment_size(1024);
+
+        for i in 0..100 {
+            recorder.append(&cpu_spike(&format!("spike {}", i))).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        // Sealed at the configured size, and only as many kept as fit in the limit
+        let segments = find_segment_files(temp_dir.path());
+        assert!(recorder.current_segment > 4, "{}", recorder.current_segment);
+        assert!(segments.len() <= 4, "{:?}", segments);
+        for (_, path) in &segments {
+            assert!(std::fs::metadata(path).unwrap().len() <= 1024);
+        }
+    }
+
+    #[test]
+    fn test_verify_sealed_segment_reports_a_changed_segment_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy {
+            max_bytes: 100 * 1024,
+            max_age_secs: None,
+        };
+        let key = SigningKey::new("test secret");
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, Some(key))
+            .unwrap()
+            .with_compression(0)
+            .with_segment_size(1024);
+        assert!(recorder.verify_sealed_segment().is_none());
+
+        while recorder.current_segment < 2 {
+            recorder.append(&cpu_spike("spike")).unwrap();
+        }
+        recorder.flush().unwrap();
+        assert!(recorder.verify_sealed_segment().is_none());
+
+        // Flip a byte in the middle of the newest sealed segment
+        let path = segment_path(temp_dir.path(), 1);
+        let mut data = std::fs::read(&path).unwrap();
+        let middle = data.len() / 2;
+        data[middle] ^= 0xff;
+        std::fs::write(&path, data).unwrap();
+
+        let anomaly = recorder.verify_sealed_segment().unwrap();
+        assert_eq!(anomaly.severity, AnomalySeverity::Critical);
+        assert!(
+            anomaly.message.starts_with("Sealed segment segment_00001.dat failed verification (offset "),
+            "{}",
+            anomaly.message
+        );
+        assert!(recorder.verify_sealed_segment().is_none());
+    }
+
+    #[test]
+    fn test_old_sealed_segment_downsampled_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let retention = RetentionPolicy {
+            max_bytes: 1024 * 1024,
+            max_age_secs: None,
+        };
+        let policy = DownsamplePolicy { after_secs: 60 * 60, window_secs: 60 };
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None)
+            .unwrap()
+            .with_compression(3)
+            .with_segment_size(8 * 1024)
+            .with_downsampling(Some(policy));
+
+        let start = OffsetDateTime::now_utc() - time::Duration::hours(2);
+        let mut appended = 0;
+        while recorder.current_segment < 1 {
+            let mut metrics = crate::event::sample_metrics();
+            metrics.ts = start + time::Duration::seconds(appended);
+            recorder.append(&Event::SystemMetrics(metrics)).unwrap();
+            appended += 1;
+        }
+        recorder.flush().unwrap();
+
+        // Sealed just now, so still at full resolution
+        recorder.downsample_next_segment();
+        assert_eq!(recorder.downsampled_through, None);
+
+        let path = segment_path(temp_dir.path(), 0);
+        let sealed_at = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        File::options().write(true).open(&path).unwrap().set_modified(sealed_at).unwrap();
+        recorder.downsample_next_segment();
+        assert_eq!(recorder.downsampled_through, Some(0));
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), sealed_at);
+        assert_eq!(std::fs::read_to_string(temp_dir.path().join(DOWNSAMPLE_STATE_FILE)).unwrap(), "0");
+
+        // Every sample is still accounted for, most of them in rollups
+        let events = crate::indexed_reader::IndexedReader::new(temp_dir.path()).unwrap().read_time_range(None, None).unwrap();
+        let rollups: Vec<u32> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::SystemMetrics(m) => Some(m.rollup.map_or(1, |r| r.samples)),
+                _ => None,
+            })
+            .collect();
+        assert!(rollups.len() < appended as usize / 2);
+        assert_eq!(rollups.iter().sum::<u32>(), appended as u32);
+    }
+
+    // Records per second with one write per event (the old behaviour) against one write
+    // per tick of 200 events. cargo test --release bench_append_throughput -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_append_throughput() {
+        let retention = RetentionPolicy {
+            max_bytes: 1000 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let events: Vec<Event> = (0..200).map(|i| cpu_spike(&format!("process {} started", i))).collect();
+        let ticks = 200;
+
+        for (label, per_event, fsync) in [
+            ("write per event", true, FsyncPolicy::Segment),
+            ("write per tick", false, FsyncPolicy::Segment),
+            ("write per tick, fsync per tick", false, FsyncPolicy::Tick),
+        ] {
+            let temp_dir = TempDir::new().unwrap();
+            let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None)
+                .unwrap()
+                .with_fsync(fsync);
+            let start = std::time::Instant::now();
+            for _ in 0..ticks {
+                for event in &events {
+                    recorder.append(event).unwrap();
+                    if per_event {
+                        recorder.write_pending().unwrap();
+                    }
+                }
+                recorder.flush().unwrap();
+            }
+            recorder.close().unwrap();
+            let elapsed = start.elapsed().as_secs_f64();
+            println!("{:<32} {:>10.0} records/s", label, (ticks * events.len()) as f64 / elapsed);
+        }
+    }
+
+    #[test]
+    fn test_record_startup_marks_reboots_and_unclean_shutdowns() {
+        let retention = RetentionPolicy {
+            max_bytes: 100 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let read_kinds = || -> Vec<String> {
+            crate::reader::LogReader::new(temp_dir.path())
+                .read_all_events()
+                .unwrap()
+                .iter()
+                .map(|e| match e {
+                    Event::SystemEvent(s) => format!("{:?}", s.kind).split(' ').next().unwrap().to_string(),
+                    Event::Anomaly(a) => format!("{:?}", a.kind),
+                    other => panic!("unexpected event {:?}", other),
+                })
+                .collect()
+        };
+
+        // Nothing to compare against on the first run
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.record_startup(OffsetDateTime::now_utc()).unwrap();
+        recorder.shutdown().unwrap();
+        assert_eq!(read_kinds(), ["Shutdown"]);
+
+        // Booted after a clean stop
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.record_startup(OffsetDateTime::now_utc() + time::Duration::seconds(1)).unwrap();
+        recorder.close().unwrap();
+        assert_eq!(read_kinds(), ["Shutdown", "Rebooted"]);
+
+        // Stopped without a Shutdown marker, same boot
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.record_startup(OffsetDateTime::UNIX_EPOCH).unwrap();
+        recorder.close().unwrap();
+        assert_eq!(read_kinds(), ["Shutdown", "Rebooted", "UncleanShutdown"]);
+
+        // Boot markers are kept for the timeline
+        let boots = crate::summary::load_boots(temp_dir.path()).unwrap();
+        assert_eq!(boots.len(), 1);
+        assert!(matches!(boots[0].kind, SystemEventKind::Rebooted { clean_shutdown: true, .. }));
+    }
+
+    #[test]
+    fn test_retention_counts_compressed_size() {
+        let temp_dir = TempDir::new().unwrap();
+        for id in 0..5 {
+            write_segment(temp_dir.path(), id, 0);
+        }
+        // Two full segments and two that compressed to a quarter of the size
+        set_segment_len(temp_dir.path(), 0, SEGMENT_SIZE);
+        set_segment_len(temp_dir.path(), 1, SEGMENT_SIZE);
+        set_segment_len(temp_dir.path(), 2, SEGMENT_SIZE / 4);
+        set_segment_len(temp_dir.path(), 3, SEGMENT_SIZE / 4);
+
+        let retention = RetentionPolicy {
+            max_bytes: 3 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+
+        assert_eq!(recorder.oldest_segment, 1);
+        assert_eq!(find_segment_files(temp_dir.path()).len(), 4);
+    }
+
+    #[test]
+    fn test_protection_covers_rotated_and_expired_segments() {
+        use crate::config::{ProtectionConfig, ProtectionMode};
+
+        let temp_dir = TempDir::new().unwrap();
+        for id in 0..3 {
+            write_segment(temp_dir.path(), id, 0);
+            set_segment_len(temp_dir.path(), id, SEGMENT_SIZE);
+        }
+        let retention = RetentionPolicy {
+            max_bytes: 100 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        let protection = ProtectionManager::new(ProtectionMode::Hardened, ProtectionConfig::default());
+        if let Err(e) = recorder.enable_protection(protection) {
+            eprintln!("Skipping, file attributes not available here: {:#}", e);
+            return;
+        }
+        let can_append = |id| File::options().append(true).open(segment_path(temp_dir.path(), id)).is_ok();
+        let can_rewrite = |id| File::options().write(true).open(segment_path(temp_dir.path(), id)).is_ok();
+
+        // Full segments are immutable, the current one only takes appends
+        assert!(!can_append(0) && !can_append(1));
+        assert!(can_append(2) && !can_rewrite(2));
+
+        recorder.append(&cpu_spike("before rotation")).unwrap();
+        recorder.rotate_segment().unwrap();
+        assert!(!can_append(2));
+        assert!(can_append(3) && !can_rewrite(3));
+        assert!(recorder.check_protection().is_empty());
+
+        // The recorder can still expire what it protected
+        recorder.retention.max_bytes = 2 * SEGMENT_SIZE;
+        recorder.enforce_retention();
+        assert_eq!(recorder.oldest_segment, 2);
+        assert!(!segment_path(temp_dir.path(), 0).exists());
+        assert!(recorder.check_protection().is_empty());
+    }
+
+    fn java_snapshot(tick: i64) -> crate::event::ProcessSnapshot {
+        use crate::event::{ProcessInfo, ProcessSnapshot};
+
+        // Twenty services with long command lines, their usage moving every tick. One is
+        // replaced every 40 ticks, changing the process set.
+        let processes = (0..20u32)
+            .map(|i| {
+                let pid = 1000 + i + if i == 0 { (tick / 40) as u32 * 100 } else { 0 };
+                let busy = (tick + i as i64) % 3 == 0;
+                ProcessInfo {
+                    pid,
+                    name: "java".to_string(),
+                    cmdline: format!("/usr/lib/jvm/java-21/bin/java -Dservice.pid={} {}", pid, "-Dopt=value ".repeat(150)),
+                    state: if busy { "R" } else { "S" }.to_string(),
+                    user: "app".to_string(),
+                    cpu_percent: if busy { 50.0 + tick as f32 } else { 1.5 },
+                    mem_bytes: 2_000_000_000 + (tick as u64 / 6) * 4096,
+                    read_bytes: 0,
+                    write_bytes: tick as u64 * 1024 * i as u64,
+                    read_bytes_per_sec: 0,
+                    write_bytes_per_sec: 1024 * i as u64,
+                    num_fds: 200,
+                    num_threads: 80,
+                    connection_count: 4,
+                    connections: vec![],
+                    container_id: None,
+                    container_name: None,
+                }
+            })
+            .collect::<Vec<_>>();
+        ProcessSnapshot {
+            ts: OffsetDateTime::now_utc(),
+            top_cpu_pids: processes.iter().take(10).map(|p| p.pid).collect(),
+            top_mem_pids: processes.iter().skip(10).map(|p| p.pid).collect(),
+            processes,
+            total_processes: 350,
+            running_processes: 3,
+            per_user: None,
+            per_container: None,
+        }
+    }
+
+    #[test]
+    fn test_process_snapshot_deltas_cut_storage_and_read_back_unchanged() {
+        let retention = RetentionPolicy {
+            max_bytes: 100 * SEGMENT_SIZE,
+            max_age_secs: None,
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        let trailer_len = recorder.chain.format().trailer_len() as u64;
+
+        let snapshots: Vec<_> = (0..120).map(java_snapshot).collect();
+        let mut full_bytes = SEGMENT_HEADER_LEN;
+        let mut mid_range_start = 0;
+        for (i, snapshot) in snapshots.iter().enumerate() {
+            if i == 50 {
+                mid_range_start = OffsetDateTime::now_utc().unix_timestamp_nanos();
+            }
+            let event = Event::ProcessSnapshot(snapshot.clone());
+            full_bytes += bincode::serialize(&event).unwrap().len() as u64 + 20 + trailer_len;
+            recorder.append(&event).unwrap();
+        }
+        let delta_bytes = recorder.offset;
+
+        // Take the rest into a new segment, part way between keyframes
+        recorder.rotate_segment().unwrap();
+        let later: Vec<_> = (120..130).map(java_snapshot).collect();
+        for snapshot in &later {
+            recorder.append(&Event::ProcessSnapshot(snapshot.clone())).unwrap();
+        }
+        recorder.close().unwrap();
+
+        assert!(
+            delta_bytes * 5 < full_bytes,
+            "{} bytes with deltas, {} without",
+            delta_bytes,
+            full_bytes
+        );
+
+        let as_snapshots = |events: Vec<Event>| -> Vec<crate::event::ProcessSnapshot> {
+            events
+                .into_iter()
+                .map(|e| match e {
+                    Event::ProcessSnapshot(s) => s,
+                    other => panic!("expected a ProcessSnapshot, got {:?}", other),
+                })
+                .collect()
+        };
+        let all: Vec<_> = snapshots.iter().chain(&later).cloned().collect();
+        let reader = crate::reader::LogReader::new(temp_dir.path());
+        assert!(as_snapshots(reader.read_all_events().unwrap()) == all);
+        assert!(as_snapshots(reader.read_recent_segment().unwrap()) == later);
+
+        // A range starting on a delta finds its keyframe earlier in the segment
+        let indexed = crate::indexed_reader::IndexedReader::new(temp_dir.path()).unwrap();
+        assert!(as_snapshots(indexed.read_time_range(Some(mid_range_start), None).unwrap()) == all[50..]);
+    }
+}
@@ -0,0 +1,238 @@
+// Live config reload, asked for with SIGHUP or POST /api/reload. config.toml is re-read and
+// checked like `config validate`, the sections the recorder can swap while running are applied,
+// and any other change is reported as needing a restart.
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use time::OffsetDateTime;
+use toml::Value;
+
+use crate::anomaly::AnomalyDetector;
+use crate::config::{Config, EmailAlertConfig, ProtectionMode, RemoteSyslogConfig, WebhookAlertConfig, CONFIG_PATH};
+use crate::event::{Event, SystemEvent, SystemEventKind};
+use crate::file_watcher::{self, FileWatcherHandle};
+
+// Changes under these are applied without a restart
+const RUNTIME_SECTIONS: [&str; 4] = ["anomalies", "file_watch", "alerts", "protection.remote_syslog"];
+
+static SIGHUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Dotted keys that changed, e.g. "anomalies.cpu_spike.threshold", split by whether they took effect
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ReloadSummary {
+    pub applied: Vec<String>,
+    pub requires_restart: Vec<String>,
+}
+
+// A reload asked for from the web UI, answered once the main loop has tried it
+pub type ReloadReply = Sender<Result<ReloadSummary, String>>;
+pub type ReloadSender = Sender<ReloadReply>;
+
+/// Reload on SIGHUP instead of stopping, which is what the ctrlc handler would do with it
+pub fn install_sighup_handler() -> Result<()> {
+    extern "C" fn on_sighup(_: libc::c_int) {
+        SIGHUP_RECEIVED.store(true, Ordering::SeqCst);
+    }
+    let handler = on_sighup as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    if unsafe { libc::signal(libc::SIGHUP, handler) } == libc::SIG_ERR {
+        return Err(std::io::Error::last_os_error()).context("Failed to install SIGHUP handler");
+    }
+    Ok(())
+}
+
+/// Whether a SIGHUP has come in since the last call
+pub fn take_sighup() -> bool {
+    SIGHUP_RECEIVED.swap(false, Ordering::SeqCst)
+}
+
+/// Remote syslog settings to stream with, None when off. Streaming needs a protection mode.
+pub fn remote_syslog_settings(config: &Config, mode: ProtectionMode) -> Option<RemoteSyslogConfig> {
+    config.protection.remote_syslog.clone().filter(|syslog| syslog.enabled && mode != ProtectionMode::Default)
+}
+
+pub fn email_settings(config: &Config) -> Option<EmailAlertConfig> {
+    config.alerts.email.clone().filter(|email| email.enabled)
+}
+
+pub fn webhook_settings(config: &Config) -> Option<WebhookAlertConfig> {
+    config.alerts.webhook.clone().filter(|webhook| webhook.enabled)
+}
+
+/// Start the file watcher if anything is to be watched
+pub fn start_file_watcher(config: &Config, event_sender: &Sender<Event>) -> Result<Option<FileWatcherHandle>> {
+    if !config.file_watch.enabled || config.file_watch.profiles().is_empty() {
+        return Ok(None);
+    }
+    file_watcher::spawn_file_watcher(config.file_watch.clone(), event_sender.clone()).map(Some)
+}
+
+/// The parts of the running recorder a reload can change
+pub struct LiveServices {
+    pub protection_mode: ProtectionMode,
+    pub file_watcher: Option<FileWatcherHandle>,
+    pub file_events: Sender<Event>,
+    pub remote_syslog: tokio::sync::watch::Sender<Option<RemoteSyslogConfig>>,
+    pub email: tokio::sync::watch::Sender<Option<EmailAlertConfig>>,
+    pub webhook: tokio::sync::watch::Sender<Option<WebhookAlertConfig>>,
+    pub storage_override: Option<u64>, // --max-storage, which config.toml doesn't change
+}
+
+/// Re-read config.toml and apply what can change at runtime to `running` and the services.
+/// Nothing is changed if the file doesn't load.
+pub fn reload(
+    running: &mut Config,
+    services: &mut LiveServices,
+    anomaly_detector: &mut AnomalyDetector,
+) -> Result<ReloadSummary> {
+    // Config::load would write a default one
+    if !Path::new(CONFIG_PATH).exists() {
+        anyhow::bail!("config.toml not found");
+    }
+    let mut new = Config::load_checked()?;
+    if let Some(max_storage_mb) = services.storage_override {
+        new.server.override_storage_limit(max_storage_mb);
+    }
+    let summary = classify(changed_keys(running, &new)?);
+    let changed = |section: &str| summary.applied.iter().any(|key| is_within(key, section));
+
+    if changed("anomalies") {
+        anomaly_detector.set_config(new.anomalies.clone());
+        running.anomalies = new.anomalies;
+    }
+    if changed("file_watch") {
+        if let Some(watcher) = services.file_watcher.take() {
+            watcher.stop();
+        }
+        running.file_watch = new.file_watch;
+        services.file_watcher = start_file_watcher(running, &services.file_events)?;
+    }
+    if changed("alerts") {
+        running.alerts = new.alerts;
+        services.email.send_replace(email_settings(running));
+        services.webhook.send_replace(webhook_settings(running));
+    }
+    if changed("protection.remote_syslog") {
+        running.protection.remote_syslog = new.protection.remote_syslog;
+        services.remote_syslog.send_replace(remote_syslog_settings(running, services.protection_mode));
+    }
+
+    Ok(summary)
+}
+
+/// SystemEvent recording a reload and what it changed
+pub fn reloaded_event(summary: &ReloadSummary) -> Event {
+    let mut message = match summary.applied.len() {
+        0 => "Config reloaded, nothing applied".to_string(),
+        _ => format!("Config reloaded, applied {}", summary.applied.join(", ")),
+    };
+    if !summary.requires_restart.is_empty() {
+        message.push_str(&format!("; needs a restart: {}", summary.requires_restart.join(", ")));
+    }
+    Event::SystemEvent(SystemEvent {
+        ts: OffsetDateTime::now_utc(),
+        kind: SystemEventKind::ConfigReloaded {
+            applied: summary.applied.clone(),
+            requires_restart: summary.requires_restart.clone(),
+        },
+        message,
+    })
+}
+
+/// Dotted keys whose values differ, in order. A table added or removed is one key.
+pub fn changed_keys(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let old = Value::try_from(old).context("Failed to compare configs")?;
+    let new = Value::try_from(new).context("Failed to compare configs")?;
+    let mut keys = Vec::new();
+    diff("", Some(&old), Some(&new), &mut keys);
+    Ok(keys)
+}
+
+fn diff(key: &str, old: Option<&Value>, new: Option<&Value>, keys: &mut Vec<String>) {
+    match (old, new) {
+        (Some(Value::Table(old)), Some(Value::Table(new))) => {
+            let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let child = if key.is_empty() { name.clone() } else { format!("{}.{}", key, name) };
+                diff(&child, old.get(name), new.get(name), keys);
+            }
+        }
+        (old, new) if old != new => keys.push(key.to_string()),
+        _ => {}
+    }
+}
+
+fn classify(keys: Vec<String>) -> ReloadSummary {
+    let (applied, requires_restart) = keys
+        .into_iter()
+        .partition(|key| RUNTIME_SECTIONS.iter().any(|section| is_within(key, section)));
+    ReloadSummary { applied, requires_restart }
+}
+
+// "protection.remote_syslog.port" is within "protection.remote_syslog", "protection" isn't
+fn is_within(key: &str, section: &str) -> bool {
+    key.strip_prefix(section).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SyslogFormat, SyslogFraming};
+
+    #[test]
+    fn test_changed_keys_are_split_by_what_applies_at_runtime() {
+        let old = Config::test_config();
+        assert_eq!(changed_keys(&old, &old).unwrap(), Vec::<String>::new());
+
+        let mut new = old.clone();
+        new.anomalies.cpu_spike.threshold += 5.0;
+        new.server.port += 1;
+        new.file_watch.enabled = !old.file_watch.enabled;
+        new.protection.append_only = !old.protection.append_only;
+        new.protection.remote_syslog = Some(RemoteSyslogConfig {
+            enabled: true,
+            host: "logs.example.com".to_string(),
+            port: 514,
+            protocol: "tcp".to_string(),
+            format: SyslogFormat::Rfc5424,
+            facility: 16,
+            framing: SyslogFraming::OctetCounting,
+            tls: false,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            spool_max_mb: 0,
+        });
+
+        let summary = classify(changed_keys(&old, &new).unwrap());
+        assert_eq!(summary.applied, vec![
+            "anomalies.cpu_spike.threshold",
+            "file_watch.enabled",
+            "protection.remote_syslog",
+        ]);
+        assert_eq!(summary.requires_restart, vec!["protection.append_only", "server.port"]);
+
+        assert!(is_within("alerts.email.to", "alerts"));
+        assert!(!is_within("alertsx", "alerts"));
+        assert!(!is_within("protection.mode", "protection.remote_syslog"));
+    }
+
+    #[test]
+    fn test_reloaded_event_message() {
+        let summary = ReloadSummary {
+            applied: vec!["anomalies.cpu_spike.threshold".to_string()],
+            requires_restart: vec!["server.port".to_string()],
+        };
+        let Event::SystemEvent(event) = reloaded_event(&summary) else {
+            panic!("expected a SystemEvent");
+        };
+        assert_eq!(
+            event.message,
+            "Config reloaded, applied anomalies.cpu_spike.threshold; needs a restart: server.port"
+        );
+        assert!(matches!(event.kind, SystemEventKind::ConfigReloaded { .. }));
+    }
+}
@@ -0,0 +1,194 @@
+use crossbeam_channel::Sender;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::config::ServiceMonitorConfig;
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, Event, ServiceStateChange};
+
+// One entry of `systemctl list-units --output=json`
+#[derive(Deserialize)]
+struct UnitEntry {
+    unit: String,
+    active: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ServiceState {
+    active: String,
+    n_restarts: Option<u32>,
+}
+
+/// Poll systemd every interval for services entering or leaving the failed state and for
+/// automatic restarts. Stops quietly where systemctl is missing or systemd isn't running.
+pub fn spawn_service_monitor(config: ServiceMonitorConfig, event_sender: Sender<Event>) {
+    thread::spawn(move || {
+        let interval = Duration::from_secs(config.interval_secs);
+        // First poll is the baseline, services already failed at startup aren't reported
+        let Some(mut previous) = read_service_states() else {
+            return;
+        };
+
+        loop {
+            thread::sleep(interval);
+            let Some(current) = read_service_states() else {
+                continue;
+            };
+
+            for change in diff_service_states(&previous, &current, OffsetDateTime::now_utc()) {
+                let anomaly = (change.new_state == "failed").then(|| Anomaly {
+                    ts: change.ts,
+                    severity: AnomalySeverity::Warning,
+                    kind: AnomalyKind::ServiceFailed,
+                    message: format!("Service {} failed (was {})", change.unit, change.old_state),
+                });
+
+                if event_sender.send(Event::ServiceStateChange(change)).is_err() {
+                    return;
+                }
+                if let Some(anomaly) = anomaly
+                    && event_sender.send(Event::Anomaly(anomaly)).is_err()
+                {
+                    return;
+                }
+            }
+            previous = current;
+        }
+    });
+}
+
+// None when systemctl can't be run or can't reach systemd
+fn read_service_states() -> Option<HashMap<String, ServiceState>> {
+    let units = systemctl(&["list-units", "--type=service", "--all", "--output=json", "--no-pager"])?;
+    let restarts = systemctl(&["show", "--property=Id,NRestarts", "--no-pager", "*.service"]).unwrap_or_default();
+    Some(parse_service_states(&units, &restarts))
+}
+
+fn systemctl(args: &[&str]) -> Option<String> {
+    let output = Command::new("systemctl").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Active state from the list-units JSON, restart counts from `systemctl show` blocks
+// of Id=/NRestarts= lines separated by blank lines
+fn parse_service_states(units_json: &str, restarts: &str) -> HashMap<String, ServiceState> {
+    let mut n_restarts = HashMap::new();
+    for block in restarts.split("\n\n") {
+        let mut id = None;
+        let mut count = None;
+        for line in block.lines() {
+            if let Some(value) = line.strip_prefix("Id=") {
+                id = Some(value.to_string());
+            } else if let Some(value) = line.strip_prefix("NRestarts=") {
+                count = value.trim().parse::<u32>().ok();
+            }
+        }
+        if let (Some(id), Some(count)) = (id, count) {
+            n_restarts.insert(id, count);
+        }
+    }
+
+    let units: Vec<UnitEntry> = serde_json::from_str(units_json).unwrap_or_default();
+    units
+        .into_iter()
+        .map(|entry| {
+            let state = ServiceState {
+                active: entry.active,
+                n_restarts: n_restarts.get(&entry.unit).copied(),
+            };
+            (entry.unit, state)
+        })
+        .collect()
+}
+
+// Changes worth recording: into or out of failed, and restart counter increases.
+// Units that were unloaded are dropped, new ones only count if they are already failed.
+fn diff_service_states(
+    previous: &HashMap<String, ServiceState>,
+    current: &HashMap<String, ServiceState>,
+    ts: OffsetDateTime,
+) -> Vec<ServiceStateChange> {
+    let mut changes: Vec<ServiceStateChange> = current
+        .iter()
+        .filter_map(|(unit, state)| {
+            let old = previous.get(unit);
+            let old_state = old.map_or("inactive", |o| o.active.as_str());
+            let entered_or_left_failed = (old_state == "failed") != (state.active == "failed");
+            let restarted = match (old.and_then(|o| o.n_restarts), state.n_restarts) {
+                (Some(before), Some(now)) => now > before,
+                _ => false,
+            };
+
+            (entered_or_left_failed || restarted).then(|| ServiceStateChange {
+                ts,
+                unit: unit.clone(),
+                old_state: old_state.to_string(),
+                new_state: state.active.clone(),
+                n_restarts: state.n_restarts,
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.unit.cmp(&b.unit));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNITS: &str = r#"[
+        {"unit":"nginx.service","load":"loaded","active":"active","sub":"running","description":"A high performance web server"},
+        {"unit":"postgresql.service","load":"loaded","active":"active","sub":"running","description":"PostgreSQL RDBMS"},
+        {"unit":"backup.service","load":"loaded","active":"inactive","sub":"dead","description":"Nightly backup"}
+    ]"#;
+
+    const RESTARTS: &str = "Id=nginx.service\nNRestarts=0\n\nId=postgresql.service\nNRestarts=2\n\nId=backup.service\nNRestarts=0\n";
+
+    #[test]
+    fn test_parse_service_states() {
+        let states = parse_service_states(UNITS, RESTARTS);
+        assert_eq!(states.len(), 3);
+        assert_eq!(states["postgresql.service"], ServiceState { active: "active".to_string(), n_restarts: Some(2) });
+        assert_eq!(states["backup.service"].active, "inactive");
+
+        // Without restart counts (old systemd) states are still tracked
+        assert_eq!(parse_service_states(UNITS, "")["nginx.service"].n_restarts, None);
+        assert!(parse_service_states("not json", RESTARTS).is_empty());
+    }
+
+    #[test]
+    fn test_diff_service_states() {
+        let ts = OffsetDateTime::UNIX_EPOCH;
+        let before = parse_service_states(UNITS, RESTARTS);
+        assert!(diff_service_states(&before, &before, ts).is_empty());
+
+        let units = UNITS
+            .replace(r#""nginx.service","load":"loaded","active":"active""#, r#""nginx.service","load":"loaded","active":"failed""#)
+            .replace(r#""backup.service","load":"loaded","active":"inactive""#, r#""backup.service","load":"loaded","active":"active""#);
+        let after = parse_service_states(&units, &RESTARTS.replace("NRestarts=2", "NRestarts=3"));
+
+        let changes = diff_service_states(&before, &after, ts);
+        let summary: Vec<(&str, &str, &str, Option<u32>)> = changes
+            .iter()
+            .map(|c| (c.unit.as_str(), c.old_state.as_str(), c.new_state.as_str(), c.n_restarts))
+            .collect();
+        // backup.service going inactive -> active is routine and not reported
+        assert_eq!(
+            summary,
+            vec![
+                ("nginx.service", "active", "failed", Some(0)),
+                ("postgresql.service", "active", "active", Some(3)),
+            ]
+        );
+
+        let recovered = diff_service_states(&after, &before, ts);
+        assert_eq!(recovered[0].unit, "nginx.service");
+        assert_eq!((recovered[0].old_state.as_str(), recovered[0].new_state.as_str()), ("failed", "active"));
+    }
+}
@@ -0,0 +1,475 @@
+use crossbeam_channel::Sender;
+use std::collections::HashMap;
+use std::io;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::collector;
+use crate::config::SmartConfig;
+use crate::event::{Anomaly, AnomalyKind, AnomalySeverity, DiskHealth, DiskHealthStatus, Event};
+
+// smartctl exit status bits (see its man page). Bits 0-1 mean nothing was read from the
+// device, the others report disk problems alongside normal output.
+const EXIT_COMMAND_LINE_ERROR: i32 = 1 << 0;
+const EXIT_DEVICE_OPEN_FAILED: i32 = 1 << 1;
+
+// SSD wear (percentage of rated endurance used) treated as end of life
+const WEAR_LIMIT_PERCENT: u8 = 90;
+
+/// Run smartctl on every physical disk each interval, sending a DiskHealth event per disk and
+/// a Critical anomaly whenever a disk's list of problems changes. Stops quietly without smartctl.
+pub fn spawn_smart_monitor(config: SmartConfig, event_sender: Sender<Event>) {
+    thread::spawn(move || {
+        let interval = Duration::from_secs(config.interval_minutes * 60);
+        let mut reported: HashMap<String, Vec<String>> = HashMap::new();
+
+        loop {
+            for disk in collector::get_physical_disks().unwrap_or_default() {
+                let health = match read_disk_health(&disk) {
+                    Ok(Some(health)) => health,
+                    Ok(None) => continue, // No permission, or the device has no SMART data
+                    Err(_) => return,      // smartctl isn't installed
+                };
+
+                let problems = health_problems(&health);
+                let previous = reported.insert(disk.clone(), problems.clone()).unwrap_or_default();
+                let anomaly = (!problems.is_empty() && problems != previous).then(|| Anomaly {
+                    ts: health.ts,
+                    severity: AnomalySeverity::Critical,
+                    kind: AnomalyKind::DiskHealth,
+                    message: format!("Disk {} failing: {}", describe(&health), problems.join(", ")),
+                });
+
+                if event_sender.send(Event::DiskHealth(health)).is_err() {
+                    return;
+                }
+                if let Some(anomaly) = anomaly
+                    && event_sender.send(Event::Anomaly(anomaly)).is_err()
+                {
+                    return;
+                }
+            }
+            thread::sleep(interval);
+        }
+    });
+}
+
+fn describe(health: &DiskHealth) -> String {
+    match &health.model {
+        Some(model) => format!("{} ({})", health.device, model),
+        None => health.device.clone(),
+    }
+}
+
+// Err only when smartctl can't be run at all
+fn read_disk_health(disk: &str) -> io::Result<Option<DiskHealth>> {
+    let output = Command::new("smartctl")
+        .args(["-i", "-H", "-A", &format!("/dev/{}", disk)])
+        .output()?;
+
+    let status = output.status.code().unwrap_or(EXIT_COMMAND_LINE_ERROR);
+    if status & (EXIT_COMMAND_LINE_ERROR | EXIT_DEVICE_OPEN_FAILED) != 0 {
+        return Ok(None);
+    }
+
+    let health = parse_smartctl(disk, &String::from_utf8_lossy(&output.stdout), OffsetDateTime::now_utc());
+    let has_data = health.health_passed.is_some()
+        || health.reallocated_sectors.is_some()
+        || health.percentage_used.is_some()
+        || health.temperature_celsius.is_some();
+    Ok(has_data.then_some(health))
+}
+
+// Leading integer of a value, ignoring thousands separators and suffixes
+// ("1,234", "36 (Min/Max 20/55)", "12345h+23m+45.678s", "3%")
+fn leading_number(value: &str) -> Option<u64> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .filter(|c| *c != ',')
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Parse the output of `smartctl -i -H -A` for an ATA (HDD or SSD) or NVMe drive
+pub fn parse_smartctl(device: &str, output: &str, ts: OffsetDateTime) -> DiskHealth {
+    let mut health = DiskHealth {
+        ts,
+        device: device.to_string(),
+        model: None,
+        health_passed: None,
+        failing_attributes: Vec::new(),
+        reallocated_sectors: None,
+        pending_sectors: None,
+        offline_uncorrectable: None,
+        crc_errors: None,
+        percentage_used: None,
+        available_spare: None,
+        available_spare_threshold: None,
+        media_errors: None,
+        critical_warning: None,
+        power_on_hours: None,
+        temperature_celsius: None,
+    };
+    let mut in_attribute_table = false;
+
+    for line in output.lines() {
+        if line.starts_with("ID#") {
+            in_attribute_table = true;
+            continue;
+        }
+        if in_attribute_table {
+            if line.trim().is_empty() {
+                in_attribute_table = false;
+            } else {
+                parse_ata_attribute(&mut health, line);
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Device Model" | "Model Number" => health.model = Some(value.to_string()),
+            "SMART overall-health self-assessment test result" => {
+                health.health_passed = Some(value == "PASSED");
+            }
+            "SMART Health Status" => health.health_passed = Some(value == "OK"), // SCSI/SAS
+            // NVMe SMART/Health Information log
+            "Critical Warning" => {
+                health.critical_warning = u8::from_str_radix(value.trim_start_matches("0x"), 16).ok();
+            }
+            "Temperature" => health.temperature_celsius = leading_number(value).map(|t| t as f32),
+            "Available Spare" => health.available_spare = leading_number(value).map(|v| v as u8),
+            "Available Spare Threshold" => {
+                health.available_spare_threshold = leading_number(value).map(|v| v as u8);
+            }
+            "Percentage Used" => health.percentage_used = leading_number(value).map(|v| v.min(255) as u8),
+            "Power On Hours" => health.power_on_hours = leading_number(value),
+            "Media and Data Integrity Errors" => health.media_errors = leading_number(value),
+            _ => {}
+        }
+    }
+
+    health
+}
+
+// One row of the ATA attribute table:
+// ID# ATTRIBUTE_NAME FLAG VALUE WORST THRESH TYPE UPDATED WHEN_FAILED RAW_VALUE
+fn parse_ata_attribute(health: &mut DiskHealth, line: &str) {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 10 {
+        return;
+    }
+    let name = fields[1];
+    let normalized = fields[3].parse::<u64>().ok();
+    let raw = leading_number(&fields[9..].join(" "));
+
+    if fields[8] == "FAILING_NOW" {
+        health.failing_attributes.push(name.to_string());
+    }
+
+    match name {
+        "Reallocated_Sector_Ct" => health.reallocated_sectors = raw,
+        "Current_Pending_Sector" => health.pending_sectors = raw,
+        "Offline_Uncorrectable" => health.offline_uncorrectable = raw,
+        "UDMA_CRC_Error_Count" => health.crc_errors = raw,
+        "Power_On_Hours" => health.power_on_hours = raw,
+        "Temperature_Celsius" => health.temperature_celsius = raw.map(|t| t as f32),
+        "Airflow_Temperature_Cel" if health.temperature_celsius.is_none() => {
+            health.temperature_celsius = raw.map(|t| t as f32);
+        }
+        // SSD life left as a normalized value counting down from 100, by vendor
+        "Wear_Leveling_Count" | "Media_Wearout_Indicator" | "SSD_Life_Left" | "Percent_Lifetime_Remain" => {
+            health.percentage_used = normalized.map(|remaining| 100 - remaining.min(100) as u8);
+        }
+        _ => {}
+    }
+}
+
+/// The reading as shown alongside the disk's throughput
+pub fn status(health: &DiskHealth) -> DiskHealthStatus {
+    DiskHealthStatus {
+        device_name: health.device.clone(),
+        health_passed: health.health_passed,
+        problems: health_problems(health),
+        reallocated_sectors: health.reallocated_sectors,
+        pending_sectors: health.pending_sectors,
+        media_errors: health.media_errors,
+        percentage_used: health.percentage_used,
+    }
+}
+
+/// Readings that indicate the disk is failing or about to, empty for a healthy disk.
+/// Any reallocated, pending or uncorrectable sector counts: drives with them fail far more often.
+pub fn health_problems(health: &DiskHealth) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if health.health_passed == Some(false) {
+        problems.push("SMART health assessment failed".to_string());
+    }
+    if !health.failing_attributes.is_empty() {
+        problems.push(format!("attributes failing now: {}", health.failing_attributes.join(", ")));
+    }
+
+    let counters = [
+        ("reallocated sectors", health.reallocated_sectors),
+        ("pending sectors", health.pending_sectors),
+        ("offline uncorrectable sectors", health.offline_uncorrectable),
+        ("interface CRC errors", health.crc_errors),
+        ("media errors", health.media_errors),
+    ];
+    for (label, count) in counters {
+        if let Some(count) = count.filter(|c| *c > 0) {
+            problems.push(format!("{} {}", count, label));
+        }
+    }
+
+    if let Some(used) = health.percentage_used.filter(|u| *u >= WEAR_LIMIT_PERCENT) {
+        problems.push(format!("{}% of rated endurance used", used));
+    }
+    if let (Some(spare), Some(threshold)) = (health.available_spare, health.available_spare_threshold)
+        && spare < threshold
+    {
+        problems.push(format!("available spare {}% below {}%", spare, threshold));
+    }
+    if let Some(warning) = health.critical_warning.filter(|w| *w != 0) {
+        problems.push(format!("NVMe critical warning 0x{:02x}", warning));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SATA_HDD: &str = "\
+smartctl 7.3 2022-02-28 r5338 [x86_64-linux-6.1.0-18-amd64] (local build)
+Copyright (C) 2002-22, Bruce Allen, Christian Franke, www.smartmontools.org
+
+=== START OF INFORMATION SECTION ===
+Model Family:     Seagate BarraCuda 3.5 (SMR)
+Device Model:     ST4000DM004-2CV104
+Serial Number:    ZFN0ABCD
+LU WWN Device Id: 5 000c50 0b1234567
+Firmware Version: 0001
+User Capacity:    4,000,787,030,016 bytes [4.00 TB]
+Sector Sizes:     512 bytes logical, 4096 bytes physical
+Rotation Rate:    5425 rpm
+Form Factor:      3.5 inches
+Device is:        In smartctl database 7.3/5319
+ATA Version is:   ACS-3 T13/2161-D revision 5
+SATA Version is:  SATA 3.1, 6.0 Gb/s (current: 6.0 Gb/s)
+Local Time is:    Tue Mar  5 10:12:44 2024 UTC
+SMART support is: Available - device has SMART capability.
+SMART support is: Enabled
+
+=== START OF READ SMART DATA SECTION ===
+SMART overall-health self-assessment test result: PASSED
+
+SMART Attributes Data Structure revision number: 10
+Vendor Specific SMART Attributes with Thresholds:
+ID# ATTRIBUTE_NAME          FLAG     VALUE WORST THRESH TYPE      UPDATED  WHEN_FAILED RAW_VALUE
+  1 Raw_Read_Error_Rate     0x000f   083   064   006    Pre-fail  Always       -       208740344
+  3 Spin_Up_Time            0x0003   096   096   000    Pre-fail  Always       -       0
+  4 Start_Stop_Count        0x0032   100   100   020    Old_age   Always       -       312
+  5 Reallocated_Sector_Ct   0x0033   100   100   010    Pre-fail  Always       -       16
+  7 Seek_Error_Rate         0x000f   086   060   045    Pre-fail  Always       -       412874123
+  9 Power_On_Hours          0x0032   068   068   000    Old_age   Always       -       28613 (57 38 0)
+ 10 Spin_Retry_Count        0x0013   100   100   097    Pre-fail  Always       -       0
+ 12 Power_Cycle_Count       0x0032   100   100   020    Old_age   Always       -       311
+187 Reported_Uncorrect      0x0032   100   100   000    Old_age   Always       -       0
+188 Command_Timeout         0x0032   100   100   000    Old_age   Always       -       0 0 0
+190 Airflow_Temperature_Cel 0x0022   064   049   040    Old_age   Always       -       36 (Min/Max 21/41)
+192 Power-Off_Retract_Count 0x0032   100   100   000    Old_age   Always       -       402
+193 Load_Cycle_Count        0x0032   098   098   000    Old_age   Always       -       4512
+194 Temperature_Celsius     0x0022   036   051   000    Old_age   Always       -       36 (0 19 0 0 0)
+197 Current_Pending_Sector  0x0012   100   100   000    Old_age   Always       -       8
+198 Offline_Uncorrectable   0x0010   100   100   000    Old_age   Offline      -       8
+199 UDMA_CRC_Error_Count    0x003e   200   200   000    Old_age   Always       -       0
+240 Head_Flying_Hours       0x0000   100   253   000    Old_age   Offline      -       21702h+14m+01.912s
+241 Total_LBAs_Written      0x0000   100   253   000    Old_age   Offline      -       38829374612
+242 Total_LBAs_Read         0x0000   100   253   000    Old_age   Offline      -       162847210088
+
+";
+
+    const SATA_SSD: &str = "\
+smartctl 7.2 2020-12-30 r5155 [x86_64-linux-5.15.0-94-generic] (local build)
+Copyright (C) 2002-20, Bruce Allen, Christian Franke, www.smartmontools.org
+
+=== START OF INFORMATION SECTION ===
+Model Family:     Samsung based SSDs
+Device Model:     Samsung SSD 860 EVO 500GB
+Serial Number:    S3Z1NB0K123456A
+LU WWN Device Id: 5 002538 e40123456
+Firmware Version: RVT03B6Q
+User Capacity:    500,107,862,016 bytes [500 GB]
+Sector Size:      512 bytes logical/physical
+Rotation Rate:    Solid State Device
+Form Factor:      2.5 inches
+TRIM Command:     Available, deterministic, zeroed
+Device is:        In smartctl database [for details use: -P show]
+ATA Version is:   ACS-4 T13/BSR INCITS 529 revision 5
+SATA Version is:  SATA 3.2, 6.0 Gb/s (current: 6.0 Gb/s)
+Local Time is:    Tue Mar  5 10:15:02 2024 UTC
+SMART support is: Available - device has SMART capability.
+SMART support is: Enabled
+
+=== START OF READ SMART DATA SECTION ===
+SMART overall-health self-assessment test result: PASSED
+
+SMART Attributes Data Structure revision number: 1
+Vendor Specific SMART Attributes with Thresholds:
+ID# ATTRIBUTE_NAME          FLAG     VALUE WORST THRESH TYPE      UPDATED  WHEN_FAILED RAW_VALUE
+  5 Reallocated_Sector_Ct   0x0033   100   100   010    Pre-fail  Always       -       0
+  9 Power_On_Hours          0x0032   094   094   000    Old_age   Always       -       25118
+ 12 Power_Cycle_Count       0x0032   099   099   000    Old_age   Always       -       431
+177 Wear_Leveling_Count     0x0013   007   007   000    Pre-fail  Always       -       1847
+179 Used_Rsvd_Blk_Cnt_Tot   0x0013   100   100   010    Pre-fail  Always       -       0
+181 Program_Fail_Cnt_Total  0x0032   100   100   010    Old_age   Always       -       0
+182 Erase_Fail_Count_Total  0x0032   100   100   010    Old_age   Always       -       0
+183 Runtime_Bad_Block       0x0013   100   100   010    Pre-fail  Always       -       0
+187 Uncorrectable_Error_Cnt 0x0032   100   100   000    Old_age   Always       -       0
+190 Airflow_Temperature_Cel 0x0032   069   049   000    Old_age   Always       -       31
+195 ECC_Error_Rate          0x001a   200   200   000    Old_age   Always       -       0
+199 CRC_Error_Count         0x003e   099   099   000    Old_age   Always       -       3
+235 POR_Recovery_Count      0x0012   099   099   000    Old_age   Always       -       27
+241 Total_LBAs_Written      0x0032   099   099   000    Old_age   Always       -       352114523367
+
+";
+
+    const NVME: &str = "\
+smartctl 7.4 2023-08-01 r5530 [x86_64-linux-6.7.5-arch1-1] (local build)
+Copyright (C) 2002-23, Bruce Allen, Christian Franke, www.smartmontools.org
+
+=== START OF INFORMATION SECTION ===
+Model Number:                       Samsung SSD 970 EVO Plus 1TB
+Serial Number:                      S4EWNX0R123456K
+Firmware Version:                   2B2QEXM7
+PCI Vendor/Subsystem ID:            0x144d
+IEEE OUI Identifier:                0x002538
+Total NVM Capacity:                 1,000,204,886,016 [1.00 TB]
+Unallocated NVM Capacity:           0
+Controller ID:                      4
+NVMe Version:                       1.3
+Number of Namespaces:               1
+Namespace 1 Size/Capacity:          1,000,204,886,016 [1.00 TB]
+Namespace 1 Utilization:            612,309,127,168 [612 GB]
+Namespace 1 Formatted LBA Size:     512
+Local Time is:                      Tue Mar  5 10:18:31 2024 UTC
+
+=== START OF SMART DATA SECTION ===
+SMART overall-health self-assessment test result: PASSED
+
+SMART/Health Information (NVMe Log 0x02)
+Critical Warning:                   0x00
+Temperature:                        41 Celsius
+Available Spare:                    100%
+Available Spare Threshold:          10%
+Percentage Used:                    3%
+Data Units Read:                    31,842,112 [16.3 TB]
+Data Units Written:                 42,318,004 [21.6 TB]
+Host Read Commands:                 412,847,221
+Host Write Commands:                781,442,905
+Controller Busy Time:               1,847
+Power Cycles:                       1,204
+Power On Hours:                     6,812
+Unsafe Shutdowns:                   87
+Media and Data Integrity Errors:    0
+Error Information Log Entries:      2,418
+Warning  Comp. Temperature Time:    0
+Critical Comp. Temperature Time:    0
+Temperature Sensor 1:               41 Celsius
+Temperature Sensor 2:               47 Celsius
+
+";
+
+    fn parse(output: &str) -> DiskHealth {
+        parse_smartctl("sda", output, OffsetDateTime::UNIX_EPOCH)
+    }
+
+    #[test]
+    fn test_parse_sata_hdd() {
+        let health = parse(SATA_HDD);
+        assert_eq!(health.model.as_deref(), Some("ST4000DM004-2CV104"));
+        assert_eq!(health.health_passed, Some(true));
+        assert_eq!(health.reallocated_sectors, Some(16));
+        assert_eq!(health.pending_sectors, Some(8));
+        assert_eq!(health.offline_uncorrectable, Some(8));
+        assert_eq!(health.crc_errors, Some(0));
+        assert_eq!(health.power_on_hours, Some(28613));
+        assert_eq!(health.temperature_celsius, Some(36.0));
+        assert_eq!(health.percentage_used, None);
+        assert!(health.failing_attributes.is_empty());
+
+        // Passing the overall check doesn't hide the bad sectors
+        assert_eq!(
+            health_problems(&health),
+            vec!["16 reallocated sectors", "8 pending sectors", "8 offline uncorrectable sectors"]
+        );
+
+        let status = status(&health);
+        assert_eq!(status.device_name, health.device);
+        assert_eq!(status.problems.len(), 3);
+        assert_eq!((status.reallocated_sectors, status.pending_sectors, status.media_errors), (Some(16), Some(8), None));
+    }
+
+    #[test]
+    fn test_parse_sata_ssd() {
+        let health = parse(SATA_SSD);
+        assert_eq!(health.model.as_deref(), Some("Samsung SSD 860 EVO 500GB"));
+        assert_eq!(health.health_passed, Some(true));
+        assert_eq!(health.reallocated_sectors, Some(0));
+        assert_eq!(health.percentage_used, Some(93));
+        assert_eq!(health.temperature_celsius, Some(31.0));
+        assert_eq!(health.power_on_hours, Some(25118));
+        // Samsung names it CRC_Error_Count rather than UDMA_CRC_Error_Count
+        assert_eq!(health.crc_errors, None);
+        assert_eq!(health_problems(&health), vec!["93% of rated endurance used"]);
+    }
+
+    #[test]
+    fn test_parse_nvme() {
+        let health = parse(NVME);
+        assert_eq!(health.model.as_deref(), Some("Samsung SSD 970 EVO Plus 1TB"));
+        assert_eq!(health.health_passed, Some(true));
+        assert_eq!(health.critical_warning, Some(0));
+        assert_eq!(health.temperature_celsius, Some(41.0));
+        assert_eq!(health.available_spare, Some(100));
+        assert_eq!(health.available_spare_threshold, Some(10));
+        assert_eq!(health.percentage_used, Some(3));
+        assert_eq!(health.power_on_hours, Some(6812));
+        assert_eq!(health.media_errors, Some(0));
+        assert!(health_problems(&health).is_empty());
+    }
+
+    #[test]
+    fn test_failed_assessment_and_failing_attributes() {
+        let output = SATA_SSD
+            .replace("test result: PASSED", "test result: FAILED!")
+            .replace(
+                "179 Used_Rsvd_Blk_Cnt_Tot   0x0013   100   100   010    Pre-fail  Always       -       0",
+                "179 Used_Rsvd_Blk_Cnt_Tot   0x0013   005   005   010    Pre-fail  Always   FAILING_NOW 412",
+            );
+        let health = parse(&output);
+        assert_eq!(health.health_passed, Some(false));
+        assert_eq!(health.failing_attributes, vec!["Used_Rsvd_Blk_Cnt_Tot"]);
+
+        let problems = health_problems(&health);
+        assert_eq!(problems[0], "SMART health assessment failed");
+        assert_eq!(problems[1], "attributes failing now: Used_Rsvd_Blk_Cnt_Tot");
+
+        let nvme = parse(&NVME.replace("Critical Warning:                   0x00", "Critical Warning:                   0x04")
+            .replace("Available Spare:                    100%", "Available Spare:                    6%"));
+        assert_eq!(
+            health_problems(&nvme),
+            vec!["available spare 6% below 10%", "NVMe critical warning 0x04"]
+        );
+    }
+}
@@ -1,10 +1,21 @@
+use anyhow::{Context, Result};
+use memmap2::Mmap;
 use serde::{Serialize, Deserialize};
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
-pub const MAGIC: u32 = 0xBB10_0001;
-pub const BLOCK_SIZE: u64 = 512 * 1024; // 512KB blocks for sparse index
+pub const MAGIC_PREFIX: u32 = 0xBB10_0000; // High half of every segment's magic number, the low half is its format version
+pub const MAGIC: u32 = 0xBB10_0001; // Records without checksums (segments from older versions)
+pub const MAGIC_CHECKSUM: u32 = 0xBB10_0002; // Each record followed by a CRC32
+pub const MAGIC_SIGNED: u32 = 0xBB10_0003; // CRC32 and HMAC chain tag, see integrity.rs
+pub const SEGMENT_HEADER_LEN: u64 = 4; // Magic number, records follow
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD]; // Sealed segment compressed as one zstd frame
+pub const BLOCK_SIZE: u64 = 64 * 1024; // Sparse index checkpoint every 64KB...
+pub const BLOCK_EVENTS: u32 = 256; // ...or every 256 events, whichever comes first
 pub const SEGMENT_SIZE: u64 = 8 * 1024 * 1024; // 8MB per segment
-pub const FLUSH_INTERVAL_SECONDS: i64 = 30; // Flush to disk every 30 seconds
+pub const FLUSH_INTERVAL_SECONDS: i64 = 30; // Longest records wait in the write buffer if nothing calls flush()
+pub const MAX_PENDING_BYTES: usize = 1024 * 1024; // Write buffer size that forces an early flush
 
 pub fn parse_segment_id(name: &str) -> Option<u64> {
     name.strip_prefix("segment_")
@@ -26,6 +37,91 @@ pub fn find_segment_files(dir: &Path) -> Vec<(u64, PathBuf)> {
     segments
 }
 
+/// Limits applied when deciding which old segments to delete
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_bytes: u64,
+    pub max_age_secs: Option<u64>,
+}
+
+/// When sealed segments have their SystemMetrics rolled up, see downsample.rs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DownsamplePolicy {
+    pub after_secs: u64, // Age of a segment's newest record
+    pub window_secs: u64,
+}
+
+/// When the recorder fsyncs the segment it is writing
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    Tick, // After each collection tick's batch of records is written
+    #[default]
+    Segment, // When a segment is sealed and on shutdown
+}
+
+/// Contents of a segment file, decompressed if it was sealed with compression
+pub enum SegmentData {
+    Mapped(Mmap),
+    Decompressed(Vec<u8>),
+}
+
+impl std::ops::Deref for SegmentData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Mapped(mmap) => mmap,
+            Self::Decompressed(data) => data,
+        }
+    }
+}
+
+/// Open a segment for reading. Record offsets are the same whether or not it's compressed.
+pub fn load_segment(path: &Path) -> Result<SegmentData> {
+    let file = File::open(path).context("Failed to open segment")?;
+    // Memory-map the file for zero-copy access
+    let mmap = unsafe { Mmap::map(&file)? };
+    if mmap.starts_with(&ZSTD_MAGIC) {
+        let data = zstd::decode_all(&mmap[..]).context("Failed to decompress segment")?;
+        return Ok(SegmentData::Decompressed(data));
+    }
+    Ok(SegmentData::Mapped(mmap))
+}
+
+/// Replace a sealed segment with a zstd-compressed copy. The copy is synced before the
+/// rename, so a crash leaves either the original or the complete compressed file.
+pub fn compress_segment(path: &Path, level: i32) -> Result<()> {
+    let data = fs::read(path).context("Failed to read segment")?;
+    let compressed = zstd::encode_all(&data[..], level).context("Failed to compress segment")?;
+
+    let tmp_path = path.with_extension("dat.tmp");
+    let mut tmp = File::create(&tmp_path).context("Failed to create compressed segment")?;
+    tmp.write_all(&compressed)?;
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path).context("Failed to replace segment with compressed copy")?;
+    Ok(())
+}
+
+/// Replace a sealed segment with `data`, compressed at `level` (0 leaves it uncompressed). Its
+/// mtime is kept, since retention takes that as the time of the newest record.
+pub fn rewrite_segment(path: &Path, data: &[u8], level: i32) -> Result<()> {
+    let modified = fs::metadata(path).and_then(|meta| meta.modified()).context("Failed to read segment")?;
+    let data = if level > 0 {
+        zstd::encode_all(data, level).context("Failed to compress segment")?
+    } else {
+        data.to_vec()
+    };
+
+    let tmp_path = path.with_extension("dat.tmp");
+    let mut tmp = File::create(&tmp_path).context("Failed to create rewritten segment")?;
+    tmp.write_all(&data)?;
+    tmp.set_modified(modified)?;
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path).context("Failed to replace segment")?;
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RecordHeader {
     pub timestamp_unix_ns: i128,
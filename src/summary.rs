@@ -0,0 +1,394 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::event::{Annotation, Event, SystemEvent, SystemEventKind};
+use crate::indexed_reader::IndexedReader;
+
+pub const SUMMARY_FILE: &str = "timeline.summary";
+pub const ANNOTATIONS_FILE: &str = "timeline.annotations";
+pub const BOOTS_FILE: &str = "timeline.boots";
+// Annotations may be placed this far outside the recorded range
+pub const ANNOTATION_SLACK_SECS: i64 = 5 * 60;
+
+const RECORD_SIZE: usize = 32;
+const NS_PER_MINUTE: i128 = 60_000_000_000;
+
+/// Aggregates for one minute of recorded events, used by the timeline instead of re-reading segments
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MinuteSummary {
+    pub minute: i64, // Unix time in minutes
+    pub count: u32,
+    pub metrics_count: u32, // SystemMetrics events in cpu/mem sums
+    pub cpu_sum: f32,
+    pub cpu_max: f32,
+    pub mem_sum: f32,
+    pub mem_max: f32,
+}
+
+impl MinuteSummary {
+    fn new(minute: i64) -> Self {
+        Self { minute, ..Default::default() }
+    }
+
+    fn add(&mut self, event: &Event) {
+        self.count += 1;
+        if let Event::SystemMetrics(m) = event {
+            self.metrics_count += 1;
+            self.cpu_sum += m.cpu_usage_percent;
+            self.cpu_max = self.cpu_max.max(m.cpu_usage_percent);
+            self.mem_sum += m.mem_usage_percent;
+            self.mem_max = self.mem_max.max(m.mem_usage_percent);
+        }
+    }
+
+    // Fixed-size little-endian record so the file can be appended to and read without framing
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&self.minute.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.count.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.metrics_count.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.cpu_sum.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.cpu_max.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.mem_sum.to_le_bytes());
+        bytes[28..32].copy_from_slice(&self.mem_max.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let f32_at = |i: usize| f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        Self {
+            minute: i64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            count: u32_at(8),
+            metrics_count: u32_at(12),
+            cpu_sum: f32_at(16),
+            cpu_max: f32_at(20),
+            mem_sum: f32_at(24),
+            mem_max: f32_at(28),
+        }
+    }
+}
+
+/// Completed minutes from the summary file, oldest first. Empty if there is no summary yet.
+pub fn load(dir: impl AsRef<Path>) -> Result<Vec<MinuteSummary>> {
+    let data = match fs::read(dir.as_ref().join(SUMMARY_FILE)) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read timeline summary"),
+    };
+
+    // A trailing partial record (killed mid-write) is ignored
+    Ok(data.chunks_exact(RECORD_SIZE).map(MinuteSummary::decode).collect())
+}
+
+/// Recorded annotations in the order they were added, so timeline markers don't need a scan of every segment
+pub fn load_annotations(dir: impl AsRef<Path>) -> Result<Vec<Annotation>> {
+    load_json_lines(&dir.as_ref().join(ANNOTATIONS_FILE), "timeline annotations")
+}
+
+/// Recorded reboot markers, oldest first, for drawing boot boundaries on the timeline
+pub fn load_boots(dir: impl AsRef<Path>) -> Result<Vec<SystemEvent>> {
+    load_json_lines(&dir.as_ref().join(BOOTS_FILE), "timeline boots")
+}
+
+// One JSON object per line, a partly written last line is ignored
+fn load_json_lines<T: serde::de::DeserializeOwned>(path: &Path, what: &str) -> Result<Vec<T>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", what)),
+    };
+    Ok(data.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+fn rewrite_json_lines<T: serde::Serialize>(path: &Path, items: &[T], what: &str) -> Result<()> {
+    let mut data = String::new();
+    for item in items {
+        data.push_str(&serde_json::to_string(item)?);
+        data.push('\n');
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, data).with_context(|| format!("Failed to write {}", what))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", what))?;
+    Ok(())
+}
+
+fn append_json_line<T: serde::Serialize>(path: &Path, item: &T, what: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", what))?;
+    writeln!(file, "{}", serde_json::to_string(item)?)?;
+    Ok(())
+}
+
+fn minute_of(event: &Event) -> i64 {
+    event.timestamp().unix_timestamp_nanos().div_euclid(NS_PER_MINUTE) as i64
+}
+
+/// Keeps the summary file up to date as the Recorder appends events. Each minute is
+/// written once it has ended; the minute in progress is rebuilt from segments after a restart.
+pub struct SummaryWriter {
+    path: PathBuf,
+    current: Option<MinuteSummary>,
+    last_written: Option<i64>,
+    annotations_path: PathBuf,
+    annotations: Vec<Annotation>,
+    boots_path: PathBuf,
+    boots: Vec<SystemEvent>,
+}
+
+impl SummaryWriter {
+    /// Open the summary in `dir`, dropping minutes whose segments are gone and
+    /// summarising any recorded events it doesn't cover yet
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let path = dir.join(SUMMARY_FILE);
+        let reader = IndexedReader::new(dir)?;
+
+        let mut summaries = load(dir)?;
+        let first_ns = reader.get_time_range().map(|(first_ns, _)| first_ns);
+        let oldest_minute = first_ns.map(|first_ns| first_ns.div_euclid(NS_PER_MINUTE) as i64);
+        let before = summaries.len();
+        summaries.retain(|s| oldest_minute.is_some_and(|oldest| s.minute >= oldest));
+        if summaries.len() != before {
+            Self::rewrite(&path, &summaries)?;
+        }
+
+        // Annotations go with the segments they were recorded in
+        let annotations_path = dir.join(ANNOTATIONS_FILE);
+        let mut annotations = load_annotations(dir)?;
+        let before = annotations.len();
+        let slack_ns = ANNOTATION_SLACK_SECS as i128 * 1_000_000_000;
+        annotations.retain(|a| first_ns.is_some_and(|first| a.ts.unix_timestamp_nanos() >= first - slack_ns));
+        if annotations.len() != before {
+            rewrite_json_lines(&annotations_path, &annotations, "timeline annotations")?;
+        }
+
+        // Boot markers are dropped with the segments too
+        let boots_path = dir.join(BOOTS_FILE);
+        let mut boots = load_boots(dir)?;
+        let before = boots.len();
+        boots.retain(|b| first_ns.is_some_and(|first| b.ts.unix_timestamp_nanos() >= first));
+        if boots.len() != before {
+            rewrite_json_lines(&boots_path, &boots, "timeline boots")?;
+        }
+
+        let mut writer = Self {
+            path,
+            current: None,
+            last_written: summaries.last().map(|s| s.minute),
+            annotations_path,
+            annotations,
+            boots_path,
+            boots,
+        };
+        writer.catch_up(&reader)?;
+        Ok(writer)
+    }
+
+    fn rewrite(path: &Path, summaries: &[MinuteSummary]) -> Result<()> {
+        let data: Vec<u8> = summaries.iter().flat_map(|s| s.encode()).collect();
+        let tmp_path = path.with_extension("summary.tmp");
+        fs::write(&tmp_path, data).context("Failed to write timeline summary")?;
+        fs::rename(&tmp_path, path).context("Failed to replace timeline summary")?;
+        Ok(())
+    }
+
+    // Summarise recorded events after the last written minute, one segment at a time
+    fn catch_up(&mut self, reader: &IndexedReader) -> Result<()> {
+        let mut start_ns = self.last_written.map(|m| (m as i128 + 1) * NS_PER_MINUTE);
+        for (first_ns, last_ns) in reader.segment_time_ranges() {
+            if start_ns.is_some_and(|start| last_ns < start) {
+                continue;
+            }
+            let from = start_ns.map_or(first_ns, |start| start.max(first_ns));
+            for event in reader.read_time_range(Some(from), Some(last_ns))? {
+                self.add(&event)?;
+            }
+            start_ns = Some(last_ns + 1);
+        }
+        Ok(())
+    }
+
+    pub fn add(&mut self, event: &Event) -> Result<()> {
+        match event {
+            Event::Annotation(annotation) => self.add_annotation(annotation)?,
+            Event::SystemEvent(system) if matches!(system.kind, SystemEventKind::Rebooted { .. }) => {
+                self.add_boot(system)?
+            }
+            _ => {}
+        }
+
+        let minute = minute_of(event);
+        if self.last_written.is_some_and(|last| minute <= last) {
+            return Ok(()); // Already summarised
+        }
+
+        match &mut self.current {
+            // Events stamped slightly before the current minute (slow collection) count towards it
+            Some(current) if minute <= current.minute => current.add(event),
+            _ => {
+                self.write_current()?;
+                let mut summary = MinuteSummary::new(minute);
+                summary.add(event);
+                self.current = Some(summary);
+            }
+        }
+        Ok(())
+    }
+
+    // Annotations can be placed in the past, so they're kept whatever minute they're for.
+    // Catching up after a restart can see one again, which is skipped.
+    fn add_annotation(&mut self, annotation: &Annotation) -> Result<()> {
+        let seen = self.annotations.iter().any(|a| {
+            a.ts == annotation.ts && a.author == annotation.author && a.text == annotation.text
+        });
+        if seen {
+            return Ok(());
+        }
+
+        append_json_line(&self.annotations_path, annotation, "timeline annotations")?;
+        self.annotations.push(annotation.clone());
+        Ok(())
+    }
+
+    // Skipped if catching up after a restart sees it again
+    fn add_boot(&mut self, boot: &SystemEvent) -> Result<()> {
+        if self.boots.contains(boot) {
+            return Ok(());
+        }
+        append_json_line(&self.boots_path, boot, "timeline boots")?;
+        self.boots.push(boot.clone());
+        Ok(())
+    }
+
+    fn write_current(&mut self) -> Result<()> {
+        let Some(summary) = self.current.take() else {
+            return Ok(());
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Failed to open timeline summary")?;
+        file.write_all(&summary.encode())?;
+        self.last_written = Some(summary.minute);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::sample_metrics;
+    use crate::recorder::Recorder;
+    use crate::storage::RetentionPolicy;
+    use tempfile::TempDir;
+    use time::OffsetDateTime;
+
+    fn metrics_at(unix_secs: i64, cpu: f32) -> Event {
+        let mut metrics = sample_metrics();
+        metrics.ts = OffsetDateTime::from_unix_timestamp(unix_secs).unwrap();
+        metrics.cpu_usage_percent = cpu;
+        metrics.mem_usage_percent = 50.0;
+        Event::SystemMetrics(metrics)
+    }
+
+    fn record(dir: &Path, events: &[Event]) {
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let mut recorder = Recorder::open_with_config(dir, retention, None, None).unwrap();
+        for event in events {
+            recorder.append(event).unwrap();
+        }
+        recorder.close().unwrap();
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        let summary = MinuteSummary {
+            minute: 29_460_068,
+            count: 75,
+            metrics_count: 60,
+            cpu_sum: 1234.5,
+            cpu_max: 99.5,
+            mem_sum: 3000.0,
+            mem_max: 51.25,
+        };
+        assert_eq!(MinuteSummary::decode(&summary.encode()), summary);
+    }
+
+    #[test]
+    fn test_recorder_writes_completed_minutes() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = 1_767_604_080; // Start of a minute
+        record(temp_dir.path(), &[
+            metrics_at(base, 10.0),
+            metrics_at(base + 30, 30.0),
+            metrics_at(base + 60, 90.0),
+            metrics_at(base + 120, 5.0), // Minute in progress, not written yet
+        ]);
+
+        let summaries = load(temp_dir.path()).unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].minute, base / 60);
+        assert_eq!(summaries[0].count, 2);
+        assert_eq!(summaries[0].cpu_sum / summaries[0].metrics_count as f32, 20.0);
+        assert_eq!(summaries[0].cpu_max, 30.0);
+        assert_eq!(summaries[1].minute, base / 60 + 1);
+        assert_eq!(summaries[1].cpu_max, 90.0);
+    }
+
+    #[test]
+    fn test_missing_summary_is_rebuilt_from_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = 1_767_604_080;
+        let events: Vec<Event> = (0..180).map(|i| metrics_at(base + i, i as f32 / 2.0)).collect();
+        record(temp_dir.path(), &events);
+        let written = load(temp_dir.path()).unwrap();
+        assert_eq!(written.len(), 2);
+
+        fs::remove_file(temp_dir.path().join(SUMMARY_FILE)).unwrap();
+        let mut writer = SummaryWriter::open(temp_dir.path()).unwrap();
+        assert_eq!(load(temp_dir.path()).unwrap(), written);
+
+        // The minute in progress was rebuilt in memory and is written once it ends
+        writer.add(&metrics_at(base + 180, 0.0)).unwrap();
+        let summaries = load(temp_dir.path()).unwrap();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[2].count, 60);
+    }
+
+    #[test]
+    fn test_annotations_are_kept_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = 1_767_604_080;
+        let annotation = Event::Annotation(Annotation {
+            ts: OffsetDateTime::now_utc() - time::Duration::minutes(2), // Placed before the recording
+            author: "admin".to_string(),
+            text: "deployed v2.3.1".to_string(),
+            tags: vec!["deploy".to_string()],
+        });
+        record(temp_dir.path(), &[metrics_at(base, 10.0), annotation, metrics_at(base + 60, 20.0)]);
+
+        let annotations = load_annotations(temp_dir.path()).unwrap();
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].text, "deployed v2.3.1");
+
+        // Rebuilding the summary replays the annotation without duplicating it
+        fs::remove_file(temp_dir.path().join(SUMMARY_FILE)).unwrap();
+        SummaryWriter::open(temp_dir.path()).unwrap();
+        assert_eq!(load_annotations(temp_dir.path()).unwrap().len(), 1);
+
+        fs::remove_file(temp_dir.path().join(ANNOTATIONS_FILE)).unwrap();
+        fs::remove_file(temp_dir.path().join(SUMMARY_FILE)).unwrap();
+        SummaryWriter::open(temp_dir.path()).unwrap();
+        assert_eq!(load_annotations(temp_dir.path()).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,616 @@
+use anyhow::{Context, Result};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::config::{RemoteSyslogConfig, SyslogFormat, SyslogFraming};
+use crate::event::{AnomalySeverity, Event, FileSystemEventKind, ProcessLifecycleKind, SecurityEventKind};
+
+const APP_NAME: &str = "blackbox";
+// SD-ID for the event payload, 32473 is the enterprise number reserved for examples (RFC 5612)
+const SD_ID: &str = "blackbox@32473";
+
+pub const SPOOL_FILE: &str = "remote_spool.log";
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Syslog severity codes (RFC 5424 section 6.2.1)
+pub const SEVERITY_CRITICAL: u8 = 2;
+pub const SEVERITY_WARNING: u8 = 4;
+const SEVERITY_NOTICE: u8 = 5;
+pub const SEVERITY_INFO: u8 = 6;
+
+// Security events go to auth (RFC 5424 section 6.2.1) so receivers file them with the system's own
+const FACILITY_AUTH: u8 = 4;
+
+// Facility an event is sent with, `configured` for everything but security events
+pub fn event_facility(event: &Event, configured: u8) -> u8 {
+    match event {
+        Event::SecurityEvent(_) => FACILITY_AUTH,
+        _ => configured,
+    }
+}
+
+pub fn severity(event: &Event) -> u8 {
+    match event {
+        Event::Anomaly(a) => match a.severity {
+            AnomalySeverity::Critical => SEVERITY_CRITICAL,
+            AnomalySeverity::Warning => SEVERITY_WARNING,
+            AnomalySeverity::Info => SEVERITY_INFO,
+        },
+        Event::SecurityEvent(s) => match s.kind {
+            SecurityEventKind::SshLoginFailure
+            | SecurityEventKind::FailedAuth
+            | SecurityEventKind::FailedSuAttempt
+            | SecurityEventKind::PortScanDetected
+            | SecurityEventKind::SegmentProtectionChanged => SEVERITY_WARNING,
+            SecurityEventKind::UserAccountModified
+            | SecurityEventKind::GroupModified
+            | SecurityEventKind::SudoersModified
+            | SecurityEventKind::NewListeningPort
+            | SecurityEventKind::KernelModuleLoaded
+            | SecurityEventKind::KernelModuleUnloaded
+            | SecurityEventKind::CronJobModified
+            | SecurityEventKind::SystemdServiceModified
+            | SecurityEventKind::SensitiveFileAccessed
+            | SecurityEventKind::DnsConfigurationChanged
+            | SecurityEventKind::DefaultGatewayChanged => SEVERITY_NOTICE,
+            _ => SEVERITY_INFO,
+        },
+        Event::ProcessLifecycle(p) => match p.kind {
+            ProcessLifecycleKind::Stuck | ProcessLifecycleKind::Zombie => SEVERITY_WARNING,
+            _ => SEVERITY_INFO,
+        },
+        Event::FileSystemEvent(f) => match f.kind {
+            FileSystemEventKind::Deleted => SEVERITY_NOTICE,
+            _ => SEVERITY_INFO,
+        },
+        Event::Annotation(_) => SEVERITY_NOTICE,
+        // Failing disks also raise a Critical anomaly, the reading itself is only a warning
+        Event::DiskHealth(d) if d.health_passed == Some(false) => SEVERITY_WARNING,
+        Event::ServiceStateChange(s) if s.new_state == "failed" => SEVERITY_WARNING,
+        Event::ServiceStateChange(_) => SEVERITY_NOTICE,
+        Event::SystemEvent(_) => SEVERITY_NOTICE,
+        Event::OperatorAction(_) => SEVERITY_WARNING,
+        Event::SystemMetrics(_) | Event::ProcessSnapshot(_) | Event::DiskHealth(_) | Event::NetworkTraffic(_) => SEVERITY_INFO,
+    }
+}
+
+pub fn event_type(event: &Event) -> &'static str {
+    match event {
+        Event::SystemMetrics(_) => "SystemMetrics",
+        Event::ProcessLifecycle(_) => "ProcessLifecycle",
+        Event::ProcessSnapshot(_) => "ProcessSnapshot",
+        Event::SecurityEvent(_) => "SecurityEvent",
+        Event::Anomaly(_) => "Anomaly",
+        Event::FileSystemEvent(_) => "FileSystemEvent",
+        Event::Annotation(_) => "Annotation",
+        Event::DiskHealth(_) => "DiskHealth",
+        Event::ServiceStateChange(_) => "ServiceStateChange",
+        Event::SystemEvent(_) => "SystemEvent",
+        Event::OperatorAction(_) => "OperatorAction",
+        Event::NetworkTraffic(_) => "NetworkTraffic",
+    }
+}
+
+// Build one message in the configured format (without transport framing)
+pub fn format_message(event: &Event, format: SyslogFormat, facility: u8, hostname: &str) -> Option<String> {
+    let json = serde_json::to_string(event).ok()?;
+    let pri = event_facility(event, facility) as u16 * 8 + severity(event) as u16;
+    let ts = event.timestamp().to_offset(UtcOffset::UTC);
+    let hostname = if hostname.is_empty() { "-" } else { hostname };
+
+    Some(match format {
+        SyslogFormat::Json => json,
+        // <PRI>Mmm dd hh:mm:ss HOSTNAME TAG: MSG
+        SyslogFormat::Rfc3164 => format!(
+            "<{}>{} {:>2} {:02}:{:02}:{:02} {} {}: {}",
+            pri,
+            MONTHS[ts.month() as usize - 1],
+            ts.day(),
+            ts.hour(),
+            ts.minute(),
+            ts.second(),
+            hostname,
+            APP_NAME,
+            json
+        ),
+        // <PRI>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]
+        // The event JSON goes in structured data, MSG is its one-line message if it has one
+        SyslogFormat::Rfc5424 => {
+            let mut line = format!(
+                "<{}>1 {} {} {} - {} [{} event=\"{}\"]",
+                pri,
+                rfc5424_timestamp(ts),
+                hostname,
+                APP_NAME,
+                event_type(event),
+                SD_ID,
+                escape_param_value(&json)
+            );
+            if let Some(text) = message_text(event) {
+                line.push(' ');
+                line.push_str(&text.replace(['\r', '\n'], " "));
+            }
+            line
+        }
+    })
+}
+
+fn message_text(event: &Event) -> Option<&str> {
+    match event {
+        Event::Anomaly(a) => Some(&a.message),
+        Event::SecurityEvent(s) => Some(&s.message),
+        Event::SystemEvent(s) => Some(&s.message),
+        _ => None,
+    }
+}
+
+// '"', backslash and ']' are escaped inside a PARAM-VALUE (RFC 5424 section 6.3.3)
+fn escape_param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn rfc5424_timestamp(ts: OffsetDateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        ts.year(),
+        ts.month() as u8,
+        ts.day(),
+        ts.hour(),
+        ts.minute(),
+        ts.second(),
+        ts.microsecond()
+    )
+}
+
+// Bytes to write for one message. Syslog over TCP uses octet counting (RFC 6587 section 3.4.1)
+// unless LF framing is asked for; JSON keeps its newline-delimited framing.
+pub fn frame(message: &str, format: SyslogFormat, framing: SyslogFraming, tcp: bool) -> Vec<u8> {
+    match (format, framing, tcp) {
+        (_, _, false) => message.as_bytes().to_vec(),
+        (SyslogFormat::Json, _, true) | (_, SyslogFraming::Lf, true) => format!("{}\n", message).into_bytes(),
+        (_, SyslogFraming::OctetCounting, true) => format!("{} {}", message.len(), message).into_bytes(),
+    }
+}
+
+// Client TLS settings for the remote connection, None when tls is off. The server is verified
+// against ca_cert or the public roots, and client_cert/client_key are presented for mutual TLS.
+pub fn tls_client_config(config: &RemoteSyslogConfig) -> Result<Option<Arc<rustls::ClientConfig>>> {
+    if !config.tls {
+        return Ok(None);
+    }
+
+    let mut roots = rustls::RootCertStore::empty();
+    match &config.ca_cert {
+        Some(path) => {
+            let certs = read_certs(path).with_context(|| format!("Failed to read ca_cert = \"{}\"", path))?;
+            for cert in certs {
+                roots.add(cert).with_context(|| format!("Unusable CA certificate in ca_cert = \"{}\"", path))?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .context("Failed to set up TLS protocol versions")?
+        .with_root_certificates(roots);
+    let tls = match (&config.client_cert, &config.client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            let certs = read_certs(cert_path).with_context(|| format!("Failed to read client_cert = \"{}\"", cert_path))?;
+            let key = PrivateKeyDer::from_pem_file(key_path)
+                .map_err(|e| anyhow::anyhow!("{}", e))
+                .with_context(|| format!("Failed to read client_key = \"{}\"", key_path))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .context("client_cert and client_key don't match or aren't supported")?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    Ok(Some(Arc::new(tls)))
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    if certs.is_empty() {
+        anyhow::bail!("No PEM certificates found");
+    }
+    Ok(certs)
+}
+
+pub fn local_hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_default()
+}
+
+// Messages waiting to go to the remote endpoint, oldest first. Up to memory_limit are kept in
+// memory; past that the whole in-memory queue is appended to the spool file, so everything in
+// the spool is always older than everything in memory and replay stays in order. A full spool
+// loses its oldest messages first.
+// Formatted messages never contain raw newlines, so the spool is one message per line.
+pub struct RemoteQueue {
+    memory: VecDeque<String>,
+    memory_limit: usize,
+    spool_path: Option<PathBuf>,
+    spool_max_bytes: u64,
+    spool_offset: u64, // Bytes of the spool already sent
+    dropped: u64,
+}
+
+// Messages handed out by next_batch, removed from the queue on ack
+pub struct Batch {
+    pub messages: Vec<String>,
+    spool_bytes: Option<u64>, // Some when read from the spool
+}
+
+impl RemoteQueue {
+    pub fn new(memory_limit: usize, spool_path: Option<PathBuf>, spool_max_bytes: u64) -> Self {
+        Self {
+            memory: VecDeque::new(),
+            memory_limit,
+            spool_path,
+            spool_max_bytes,
+            spool_offset: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, message: String) {
+        if self.memory.len() >= self.memory_limit && self.spill().is_err() {
+            // No room on disk either, keep the newest events
+            self.memory.pop_front();
+            self.dropped += 1;
+        }
+        self.memory.push_back(message);
+    }
+
+    pub fn memory_len(&self) -> usize {
+        self.memory.len()
+    }
+
+    // Messages dropped because the spool was full, or there was no spool and memory was
+    pub fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+
+    // Append the in-memory queue to the spool file, evicting the oldest spooled messages to make
+    // room. Fails without writing anything when there is no spool or the queue alone is over the cap.
+    pub fn spill(&mut self) -> io::Result<()> {
+        if self.memory.is_empty() {
+            return Ok(());
+        }
+        let Some(path) = self.spool_path.clone() else {
+            return Err(io::Error::other("spool disabled"));
+        };
+
+        let mut data = String::new();
+        for message in &self.memory {
+            data.push_str(message);
+            data.push('\n');
+        }
+
+        let incoming = data.len() as u64;
+        if incoming > self.spool_max_bytes {
+            return Err(io::Error::other("spool full"));
+        }
+        let current = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if current + incoming > self.spool_max_bytes {
+            self.evict_oldest(&path, incoming)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(data.as_bytes())?;
+        self.memory.clear();
+        Ok(())
+    }
+
+    // Up to max of the oldest messages, taken from the spool before memory
+    pub fn next_batch(&mut self, max: usize) -> io::Result<Option<Batch>> {
+        if let Some(batch) = self.read_spool(max)? {
+            return Ok(Some(batch));
+        }
+        if self.memory.is_empty() {
+            return Ok(None);
+        }
+        let messages = self.memory.iter().take(max).cloned().collect();
+        Ok(Some(Batch { messages, spool_bytes: None }))
+    }
+
+    // Mark a batch from next_batch as delivered
+    pub fn ack(&mut self, batch: Batch) -> io::Result<()> {
+        match batch.spool_bytes {
+            Some(bytes) => {
+                self.spool_offset += bytes;
+                if let Some(path) = &self.spool_path {
+                    let len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                    if self.spool_offset >= len {
+                        // Fully replayed
+                        fs::remove_file(path)?;
+                        self.spool_offset = 0;
+                    }
+                }
+            }
+            None => {
+                self.memory.drain(..batch.messages.len());
+            }
+        }
+        Ok(())
+    }
+
+    // Rewrite the spool without its oldest unsent messages, leaving room for `incoming` bytes and
+    // freeing at least a quarter of the cap so a full spool isn't rewritten on every spill
+    fn evict_oldest(&mut self, path: &Path, incoming: u64) -> io::Result<()> {
+        let target = self.spool_max_bytes.saturating_sub(incoming).min(self.spool_max_bytes / 4 * 3);
+
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(self.spool_offset))?;
+        let mut unsent = Vec::new();
+        io::Read::read_to_end(&mut file, &mut unsent)?;
+
+        let mut start = 0;
+        while (unsent.len() - start) as u64 > target {
+            let end = unsent[start..].iter().position(|&b| b == b'\n').map_or(unsent.len(), |i| start + i + 1);
+            if end > start + 1 {
+                self.dropped += 1;
+            }
+            start = end;
+        }
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, &unsent[start..])?;
+        fs::rename(&tmp, path)?;
+        self.spool_offset = 0;
+        Ok(())
+    }
+
+    fn read_spool(&self, max: usize) -> io::Result<Option<Batch>> {
+        let Some(path) = &self.spool_path else {
+            return Ok(None);
+        };
+        let mut file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        file.seek(SeekFrom::Start(self.spool_offset))?;
+
+        let mut reader = BufReader::new(file);
+        let mut messages = Vec::new();
+        let mut bytes = 0u64;
+        let mut line = String::new();
+        while messages.len() < max {
+            line.clear();
+            let n = reader.read_line(&mut line)?;
+            if n == 0 {
+                break;
+            }
+            bytes += n as u64;
+            let message = line.trim_end_matches('\n');
+            if !message.is_empty() {
+                messages.push(message.to_string());
+            }
+        }
+
+        if bytes == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Batch { messages, spool_bytes: Some(bytes) }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, Anomaly, AnomalyKind, SecurityEvent};
+
+    fn fixed_ts() -> OffsetDateTime {
+        // 2026-01-05T09:08:07.123456Z
+        OffsetDateTime::from_unix_timestamp_nanos(1_767_604_087_123_456_000).unwrap()
+    }
+
+    #[test]
+    fn test_rfc5424_system_metrics() {
+        let mut metrics = sample_metrics();
+        metrics.ts = fixed_ts();
+        let event = Event::SystemMetrics(metrics);
+        let json = serde_json::to_string(&event).unwrap();
+
+        let msg = format_message(&event, SyslogFormat::Rfc5424, 16, "web1").unwrap();
+        // local0 (16) * 8 + info (6) = 134, metrics have no message of their own
+        assert_eq!(
+            msg,
+            format!(
+                "<134>1 2026-01-05T09:08:07.123456Z web1 blackbox - SystemMetrics [blackbox@32473 event=\"{}\"]",
+                json.replace('"', "\\\"").replace(']', "\\]")
+            )
+        );
+    }
+
+    #[test]
+    fn test_rfc3164_anomaly() {
+        let event = Event::Anomaly(Anomaly {
+            ts: fixed_ts(),
+            severity: AnomalySeverity::Critical,
+            kind: AnomalyKind::DiskFull,
+            message: "Disk usage: 97.0%".to_string(),
+        });
+        let json = serde_json::to_string(&event).unwrap();
+
+        let msg = format_message(&event, SyslogFormat::Rfc3164, 16, "web1").unwrap();
+        // local0 (16) * 8 + crit (2) = 130, single-digit day is space padded
+        assert_eq!(msg, format!("<130>Jan  5 09:08:07 web1 blackbox: {}", json));
+    }
+
+    #[test]
+    fn test_pri_mapping() {
+        let anomaly = |severity| {
+            Event::Anomaly(Anomaly { ts: fixed_ts(), severity, kind: AnomalyKind::CpuSpike, message: String::new() })
+        };
+        let security = |kind| {
+            Event::SecurityEvent(SecurityEvent {
+                ts: fixed_ts(),
+                kind,
+                user: "root".to_string(),
+                source_ip: None,
+                message: String::new(),
+            })
+        };
+        let pri = |event: &Event| event_facility(event, 16) as u16 * 8 + severity(event) as u16;
+
+        assert_eq!(pri(&anomaly(AnomalySeverity::Critical)), 130); // local0.crit
+        assert_eq!(pri(&anomaly(AnomalySeverity::Warning)), 132); // local0.warning
+        assert_eq!(pri(&anomaly(AnomalySeverity::Info)), 134); // local0.info
+        // Security events ignore the configured facility
+        assert_eq!(pri(&security(SecurityEventKind::SshLoginFailure)), 36); // auth.warning
+        assert_eq!(pri(&security(SecurityEventKind::SudoersModified)), 37); // auth.notice
+        assert_eq!(pri(&security(SecurityEventKind::SshLoginSuccess)), 38); // auth.info
+        assert_eq!(pri(&Event::SystemMetrics(sample_metrics())), 134);
+    }
+
+    #[test]
+    fn test_rfc5424_security_event_tcp_framing() {
+        let event = Event::SecurityEvent(SecurityEvent {
+            ts: fixed_ts(),
+            kind: SecurityEventKind::SshLoginFailure,
+            user: "root".to_string(),
+            source_ip: Some("203.0.113.7".to_string()),
+            message: "Failed password\nfor root]".to_string(),
+        });
+        let json = serde_json::to_string(&event).unwrap();
+        let escaped = json.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]");
+
+        // auth (4) * 8 + warning (4) = 36, whatever facility is configured
+        let msg = format_message(&event, SyslogFormat::Rfc5424, 16, "").unwrap();
+        let expected = format!(
+            "<36>1 2026-01-05T09:08:07.123456Z - blackbox - SecurityEvent [blackbox@32473 event=\"{}\"] Failed password for root]",
+            escaped
+        );
+        assert_eq!(msg, expected);
+        assert!(!msg.contains('\n'));
+
+        let framed = String::from_utf8(frame(&msg, SyslogFormat::Rfc5424, SyslogFraming::OctetCounting, true)).unwrap();
+        assert_eq!(framed, format!("{} {}", expected.len(), expected));
+        let framed = frame(&msg, SyslogFormat::Rfc5424, SyslogFraming::Lf, true);
+        assert_eq!(framed, format!("{}\n", expected).into_bytes());
+
+        // UDP datagrams carry the bare message
+        assert_eq!(frame(&msg, SyslogFormat::Rfc5424, SyslogFraming::OctetCounting, false), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_json_format_keeps_newline_framing() {
+        let event = Event::SystemMetrics(sample_metrics());
+        let msg = format_message(&event, SyslogFormat::Json, 16, "web1").unwrap();
+        assert_eq!(msg, serde_json::to_string(&event).unwrap());
+        assert_eq!(frame(&msg, SyslogFormat::Json, SyslogFraming::OctetCounting, true), format!("{}\n", msg).into_bytes());
+    }
+
+    fn drain(queue: &mut RemoteQueue) -> Vec<String> {
+        let mut sent = Vec::new();
+        while let Some(batch) = queue.next_batch(2).unwrap() {
+            sent.extend(batch.messages.iter().cloned());
+            queue.ack(batch).unwrap();
+        }
+        sent
+    }
+
+    #[test]
+    fn test_remote_queue_spools_and_replays_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = dir.path().join(SPOOL_FILE);
+        let mut queue = RemoteQueue::new(3, Some(spool.clone()), 1024 * 1024);
+
+        for i in 0..8 {
+            queue.push(format!("msg{}", i));
+        }
+        // Two spills of three messages, the newest two still in memory
+        assert!(spool.exists());
+        assert_eq!(queue.memory_len(), 2);
+
+        // A failed send leaves the batch queued
+        let batch = queue.next_batch(2).unwrap().unwrap();
+        assert_eq!(batch.messages, vec!["msg0", "msg1"]);
+        drop(batch);
+
+        let expected: Vec<String> = (0..8).map(|i| format!("msg{}", i)).collect();
+        assert_eq!(drain(&mut queue), expected);
+        assert!(!spool.exists());
+        assert_eq!(queue.take_dropped(), 0);
+    }
+
+    #[test]
+    fn test_remote_queue_drops_oldest_when_spool_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = dir.path().join(SPOOL_FILE);
+        // Room for three 5-byte lines, so every spill of two past the first evicts
+        let mut queue = RemoteQueue::new(2, Some(spool.clone()), 18);
+
+        for i in 0..8 {
+            queue.push(format!("msg{}", i));
+        }
+        // msg0-msg1 spooled, msg2-msg3 spilled after evicting msg0, msg4-msg5 after evicting
+        // msg1-msg2 (at least a quarter of the cap is freed)
+        assert_eq!(queue.take_dropped(), 3);
+        assert_eq!(fs::read_to_string(&spool).unwrap(), "msg3\nmsg4\nmsg5\n");
+
+        // A partly replayed spool evicts from what hasn't been sent
+        let batch = queue.next_batch(1).unwrap().unwrap();
+        assert_eq!(batch.messages, vec!["msg3"]);
+        queue.ack(batch).unwrap();
+        for i in 8..10 {
+            queue.push(format!("msg{}", i));
+        }
+        assert_eq!(queue.take_dropped(), 1);
+        assert_eq!(drain(&mut queue), vec!["msg5", "msg6", "msg7", "msg8", "msg9"]);
+    }
+
+    #[test]
+    fn test_remote_queue_without_spool_is_bounded() {
+        let mut queue = RemoteQueue::new(2, None, 0);
+        for i in 0..5 {
+            queue.push(format!("msg{}", i));
+        }
+        assert_eq!(queue.take_dropped(), 3);
+        assert_eq!(drain(&mut queue), vec!["msg3", "msg4"]);
+    }
+
+    #[test]
+    fn test_tls_client_config() {
+        let mut config: RemoteSyslogConfig =
+            toml::from_str("enabled = true\nhost = \"logs.example.com\"\nport = 6514\nprotocol = \"tcp\"").unwrap();
+        assert!(tls_client_config(&config).unwrap().is_none());
+
+        // Without a CA the public roots are used
+        config.tls = true;
+        assert!(tls_client_config(&config).unwrap().is_some());
+
+        let dir = tempfile::tempdir().unwrap();
+        config.ca_cert = Some(dir.path().join("missing.pem").to_string_lossy().into_owned());
+        let err = tls_client_config(&config).unwrap_err();
+        assert!(format!("{:#}", err).contains("ca_cert"));
+
+        let garbage = dir.path().join("garbage.pem");
+        fs::write(&garbage, "not a certificate").unwrap();
+        config.ca_cert = Some(garbage.to_string_lossy().into_owned());
+        let err = tls_client_config(&config).unwrap_err();
+        assert!(format!("{:#}", err).contains("No PEM certificates"));
+    }
+}
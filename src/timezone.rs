@@ -0,0 +1,84 @@
+// Zone timestamps are shown in. Everything is recorded in UTC, this only changes how times are
+// presented: console lines, the web UI and `export --local-time`.
+use anyhow::Result;
+use std::path::Path;
+use time::{OffsetDateTime, UtcOffset};
+
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// Point TZ at the configured zone, which chrono's Local reads on every conversion. Without one
+/// an existing TZ is kept, otherwise UTC is used rather than the system zone.
+/// Must be called before any threads are started.
+pub fn init(configured: Option<&str>) {
+    let tz = match configured {
+        Some(name) => name.to_string(),
+        None => match std::env::var("TZ") {
+            Ok(tz) if !tz.is_empty() => return,
+            _ => "UTC".to_string(),
+        },
+    };
+    // Safety: called from main before the recorder or export spawns any threads
+    unsafe { std::env::set_var("TZ", tz) };
+}
+
+/// Reject names that aren't in the zoneinfo database, since chrono silently falls back to UTC
+pub fn validate(name: &str) -> Result<()> {
+    if name == "UTC" {
+        return Ok(());
+    }
+    if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+        anyhow::bail!("timezone must be an IANA zone name like Europe/London, got {:?}", name);
+    }
+    let dir = std::env::var("TZDIR").unwrap_or_else(|_| ZONEINFO_DIR.to_string());
+    let path = Path::new(&dir).join(name);
+    let is_tzif = std::fs::read(&path).is_ok_and(|data| data.starts_with(b"TZif"));
+    if !is_tzif {
+        anyhow::bail!("unknown timezone {:?} (not found in {})", name, dir);
+    }
+    Ok(())
+}
+
+/// Name of the zone in use, for the web UI to format times with
+pub fn name() -> String {
+    zone_name(std::env::var("TZ").ok().as_deref())
+}
+
+// TZ may be a zone name, ":Europe/London" or a path into the zoneinfo database
+fn zone_name(tz: Option<&str>) -> String {
+    let tz = tz.unwrap_or_default().trim_start_matches(':');
+    let tz = tz.strip_prefix(ZONEINFO_DIR).map_or(tz, |rest| rest.trim_start_matches('/'));
+    if tz.is_empty() { "UTC".to_string() } else { tz.to_string() }
+}
+
+/// Offset from UTC of the display zone at `ts`
+pub fn offset_at(ts: OffsetDateTime) -> UtcOffset {
+    let seconds = chrono::DateTime::from_timestamp(ts.unix_timestamp(), 0)
+        .map(|utc| utc.with_timezone(&chrono::Local).offset().local_minus_utc())
+        .unwrap_or(0);
+    UtcOffset::from_whole_seconds(seconds).unwrap_or(UtcOffset::UTC)
+}
+
+/// `ts` in the display zone
+pub fn to_local(ts: OffsetDateTime) -> OffsetDateTime {
+    ts.to_offset(offset_at(ts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_name_and_validate() {
+        assert_eq!(zone_name(None), "UTC");
+        assert_eq!(zone_name(Some("")), "UTC");
+        assert_eq!(zone_name(Some("Europe/London")), "Europe/London");
+        assert_eq!(zone_name(Some(":America/New_York")), "America/New_York");
+        assert_eq!(zone_name(Some("/usr/share/zoneinfo/Asia/Tokyo")), "Asia/Tokyo");
+
+        assert!(validate("UTC").is_ok());
+        assert!(validate("").is_err());
+        assert!(validate("/etc/passwd").is_err());
+        assert!(validate("../../etc/passwd").is_err());
+        assert!(validate("Not/A_Zone").is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::collector;
+use crate::config::{TrafficAccountingConfig, TrafficGrouping};
+use crate::event::{NetworkTraffic, RemoteTraffic};
+
+// Remotes named in a network spike anomaly and kept in its NetworkTraffic event
+const TOP_REMOTES: usize = 5;
+
+/// Latest accounting interval, None until two snapshots have been taken
+pub type SharedNetworkTraffic = Arc<Mutex<Option<NetworkTraffic>>>;
+
+// One TCP connection from `ss -tin`
+#[derive(Debug, Clone, PartialEq)]
+struct SsConnection {
+    local: String,
+    peer: String,
+    remote_ip: IpAddr,
+    bytes_sent: Option<u64>, // Acked bytes, None where the kernel doesn't report them
+    bytes_received: Option<u64>,
+}
+
+/// Snapshot TCP connections every interval and total the bytes each remote sent and received
+/// since the previous snapshot. Where ss is missing or reports no byte counters, connections
+/// per remote are counted from /proc/net/tcp instead.
+pub fn spawn_traffic_accounting(config: TrafficAccountingConfig) -> SharedNetworkTraffic {
+    let latest = SharedNetworkTraffic::default();
+    let shared = latest.clone();
+
+    thread::spawn(move || {
+        let interval = Duration::from_secs(config.interval_secs);
+        let mut previous: Option<HashMap<(String, String), (u64, u64)>> = None;
+
+        loop {
+            let report = if let Some(connections) = read_ss().filter(|c| c.is_empty() || has_byte_counters(c)) {
+                let counters = byte_counters(&connections);
+                let report = previous
+                    .as_ref()
+                    .map(|previous| traffic_since(previous, &connections, config.group_by));
+                previous = Some(counters);
+                report.map(|top_remotes| (true, top_remotes))
+            } else {
+                previous = None;
+                Some((false, connection_counts(&read_tcp_tables(), config.group_by)))
+            };
+
+            if let Some((bytes_counted, top_remotes)) = report {
+                *shared.lock().unwrap() = Some(NetworkTraffic {
+                    ts: OffsetDateTime::now_utc(),
+                    interval_secs: config.interval_secs,
+                    bytes_counted,
+                    top_remotes,
+                });
+            }
+            thread::sleep(interval);
+        }
+    });
+
+    latest
+}
+
+/// e.g. "203.0.113.0/24 1.2GB, 198.51.100.7 300.0MB", or connection counts without bytes
+pub fn summarize(traffic: &NetworkTraffic) -> String {
+    traffic
+        .top_remotes
+        .iter()
+        .map(|r| {
+            if traffic.bytes_counted {
+                format!("{} {}", r.remote, crate::format_bytes(r.bytes_sent + r.bytes_received))
+            } else {
+                format!("{} {} connection{}", r.remote, r.connections, if r.connections == 1 { "" } else { "s" })
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn read_ss() -> Option<Vec<SsConnection>> {
+    let output = Command::new("ss").args(["-t", "-i", "-n"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_ss(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// `ss -tin` prints each connection on a line starting with its state, followed by an indented
+// line of TCP info such as "bytes_sent:2711539 bytes_acked:2711539 bytes_received:33383525"
+fn parse_ss(output: &str) -> Vec<SsConnection> {
+    let mut connections: Vec<SsConnection> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(connection) = connections.last_mut() {
+                for field in line.split_whitespace() {
+                    let Some((name, value)) = field.split_once(':') else {
+                        continue;
+                    };
+                    match name {
+                        "bytes_acked" => connection.bytes_sent = value.parse().ok(),
+                        "bytes_sent" if connection.bytes_sent.is_none() => connection.bytes_sent = value.parse().ok(),
+                        "bytes_received" => connection.bytes_received = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+            continue;
+        }
+
+        // State Recv-Q Send-Q Local:Port Peer:Port, with a header line first
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 5 || parts[0] == "State" {
+            continue;
+        }
+        let Some(remote_ip) = parse_ss_ip(parts[4]) else {
+            continue;
+        };
+        connections.push(SsConnection {
+            local: parts[3].to_string(),
+            peer: parts[4].to_string(),
+            remote_ip,
+            bytes_sent: None,
+            bytes_received: None,
+        });
+    }
+    connections
+}
+
+// "203.0.113.7:443", "[2001:db8::1]:443", "[::ffff:203.0.113.7]:22" or "[fe80::1]%eth0:22"
+fn parse_ss_ip(addr: &str) -> Option<IpAddr> {
+    let (host, _port) = addr.rsplit_once(':')?;
+    let host = host.split('%').next()?.trim_start_matches('[').trim_end_matches(']');
+    Some(host.parse::<IpAddr>().ok()?.to_canonical())
+}
+
+fn has_byte_counters(connections: &[SsConnection]) -> bool {
+    connections.iter().any(|c| c.bytes_sent.is_some() || c.bytes_received.is_some())
+}
+
+fn byte_counters(connections: &[SsConnection]) -> HashMap<(String, String), (u64, u64)> {
+    connections
+        .iter()
+        .map(|c| {
+            let counters = (c.bytes_sent.unwrap_or(0), c.bytes_received.unwrap_or(0));
+            ((c.local.clone(), c.peer.clone()), counters)
+        })
+        .collect()
+}
+
+// Bytes moved per remote since the previous snapshot. Connections opened since then count in
+// full; ones that closed in between are missed, so the totals are approximate.
+fn traffic_since(
+    previous: &HashMap<(String, String), (u64, u64)>,
+    connections: &[SsConnection],
+    group_by: TrafficGrouping,
+) -> Vec<RemoteTraffic> {
+    let mut by_remote: HashMap<String, RemoteTraffic> = HashMap::new();
+    for c in connections.iter().filter(|c| is_remote(c.remote_ip)) {
+        let (sent, received) = (c.bytes_sent.unwrap_or(0), c.bytes_received.unwrap_or(0));
+        let (prev_sent, prev_received) = previous.get(&(c.local.clone(), c.peer.clone())).copied().unwrap_or((0, 0));
+        let remote = remote_entry(&mut by_remote, c.remote_ip, group_by);
+        // A counter going backwards is a new connection reusing the same addresses
+        remote.bytes_sent += if sent >= prev_sent { sent - prev_sent } else { sent };
+        remote.bytes_received += if received >= prev_received { received - prev_received } else { received };
+        remote.connections += 1;
+    }
+
+    let mut top: Vec<RemoteTraffic> = by_remote.into_values().collect();
+    top.sort_by(|a, b| {
+        (b.bytes_sent + b.bytes_received)
+            .cmp(&(a.bytes_sent + a.bytes_received))
+            .then(b.connections.cmp(&a.connections))
+            .then(a.remote.cmp(&b.remote))
+    });
+    top.truncate(TOP_REMOTES);
+    top
+}
+
+fn read_tcp_tables() -> Vec<String> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .collect()
+}
+
+// Established connections per remote from /proc/net/tcp and tcp6 contents
+fn connection_counts(tables: &[String], group_by: TrafficGrouping) -> Vec<RemoteTraffic> {
+    let mut by_remote: HashMap<String, RemoteTraffic> = HashMap::new();
+    for content in tables {
+        for line in content.lines().skip(1) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            // 01 = ESTABLISHED
+            if parts.len() < 4 || parts[3] != "01" {
+                continue;
+            }
+            if let Some((ip, _)) = collector::parse_socket_addr(parts[2])
+                && is_remote(ip)
+            {
+                remote_entry(&mut by_remote, ip, group_by).connections += 1;
+            }
+        }
+    }
+
+    let mut top: Vec<RemoteTraffic> = by_remote.into_values().collect();
+    top.sort_by(|a, b| b.connections.cmp(&a.connections).then(a.remote.cmp(&b.remote)));
+    top.truncate(TOP_REMOTES);
+    top
+}
+
+// Loopback is this machine talking to itself
+fn is_remote(ip: IpAddr) -> bool {
+    !ip.is_loopback() && !ip.is_unspecified()
+}
+
+fn remote_entry(by_remote: &mut HashMap<String, RemoteTraffic>, ip: IpAddr, group_by: TrafficGrouping) -> &mut RemoteTraffic {
+    let remote = remote_group(ip, group_by);
+    by_remote.entry(remote.clone()).or_insert_with(|| RemoteTraffic {
+        remote,
+        bytes_sent: 0,
+        bytes_received: 0,
+        connections: 0,
+    })
+}
+
+fn remote_group(ip: IpAddr, group_by: TrafficGrouping) -> String {
+    match (group_by, ip) {
+        (TrafficGrouping::Host, ip) => ip.to_string(),
+        (TrafficGrouping::Subnet, IpAddr::V4(v4)) => {
+            let [a, b, c, _] = v4.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        (TrafficGrouping::Subnet, IpAddr::V6(v6)) => {
+            let s = v6.segments();
+            format!("{}/64", Ipv6Addr::new(s[0], s[1], s[2], s[3], 0, 0, 0, 0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SS_OUTPUT: &str = "\
+State Recv-Q Send-Q      Local Address:Port         Peer Address:Port Process
+ESTAB 0      0               10.0.0.5:22          203.0.113.7:51234
+\t cubic wscale:7,7 rto:204 rtt:1.5/0.75 mss:1448 bytes_sent:5000 bytes_acked:4000 bytes_received:1000 segs_out:20
+ESTAB 0      36              10.0.0.5:443         203.0.113.9:40000
+\t cubic wscale:7,7 rto:204 bytes_acked:1000000 bytes_received:200 segs_out:700
+ESTAB 0      0      [::ffff:10.0.0.5]:8080  [2001:db8:1:2::1]:50000
+\t cubic bytes_sent:300 bytes_received:0
+ESTAB 0      0              127.0.0.1:5432          127.0.0.1:40100
+\t cubic bytes_acked:999999999 bytes_received:999999999
+SYN-SENT 0   1               10.0.0.5:41000    [fe80::1]%eth0:22
+TIME-WAIT 0  0               10.0.0.5:41002        198.51.100.1:80
+";
+
+    #[test]
+    fn test_parse_ss() {
+        let connections = parse_ss(SS_OUTPUT);
+        assert_eq!(connections.len(), 6);
+
+        assert_eq!(connections[0].peer, "203.0.113.7:51234");
+        assert_eq!(connections[0].remote_ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+        assert_eq!(connections[0].bytes_sent, Some(4000)); // Acked rather than sent
+        assert_eq!(connections[0].bytes_received, Some(1000));
+        assert_eq!(connections[1].bytes_sent, Some(1000000));
+        assert_eq!(connections[2].remote_ip, "2001:db8:1:2::1".parse::<IpAddr>().unwrap());
+        assert_eq!(connections[2].bytes_sent, Some(300)); // Old kernels only report bytes_sent
+        assert_eq!(connections[4].remote_ip, "fe80::1".parse::<IpAddr>().unwrap());
+        assert_eq!(connections[4].bytes_sent, None);
+        assert_eq!(connections[5].bytes_received, None);
+    }
+
+    #[test]
+    fn test_traffic_since_previous_snapshot() {
+        let connections = parse_ss(SS_OUTPUT);
+        let mut previous = HashMap::new();
+        previous.insert(("10.0.0.5:22".to_string(), "203.0.113.7:51234".to_string()), (3000, 1000));
+
+        // Only the new bytes of a known connection count, and loopback is left out
+        let top = traffic_since(&previous, &connections, TrafficGrouping::Subnet);
+        assert_eq!(top.len(), 4);
+        assert_eq!(top[0].remote, "203.0.113.0/24");
+        assert_eq!((top[0].bytes_sent, top[0].bytes_received, top[0].connections), (1001000, 200, 2));
+        assert_eq!(top[1].remote, "2001:db8:1:2::/64");
+        assert!(top.iter().all(|r| !r.remote.starts_with("127.")));
+
+        let top = traffic_since(&previous, &connections, TrafficGrouping::Host);
+        assert_eq!(top[0].remote, "203.0.113.9");
+        assert_eq!(top[1].remote, "203.0.113.7");
+        assert_eq!(top[1].bytes_sent, 1000);
+
+        let traffic = NetworkTraffic { ts: OffsetDateTime::now_utc(), interval_secs: 10, bytes_counted: true, top_remotes: top[..2].to_vec() };
+        assert_eq!(summarize(&traffic), "203.0.113.9 976.8KB, 203.0.113.7 1000B");
+    }
+
+    #[test]
+    fn test_connection_counts_without_byte_counters() {
+        let table = "  sl  local_address rem_address   st\n\
+            0: 0500000A:0016 077100CB:C822 01\n\
+            1: 0500000A:0017 097100CB:C823 01\n\
+            2: 0500000A:0018 097100CB:C824 01\n\
+            3: 0100007F:0019 0100007F:C825 01\n\
+            4: 0500000A:0020 097100CB:C826 06\n";
+        let top = connection_counts(&[table.to_string()], TrafficGrouping::Host);
+        assert_eq!(top.len(), 2);
+        assert_eq!((top[0].remote.as_str(), top[0].connections), ("203.0.113.9", 2));
+        assert_eq!((top[1].remote.as_str(), top[1].connections), ("203.0.113.7", 1));
+
+        let traffic = NetworkTraffic { ts: OffsetDateTime::now_utc(), interval_secs: 10, bytes_counted: false, top_remotes: top };
+        assert_eq!(summarize(&traffic), "203.0.113.9 2 connections, 203.0.113.7 1 connection");
+    }
+}
@@ -0,0 +1,185 @@
+use actix_web::{http::StatusCode, web, HttpResponse};
+use crossbeam_channel::Sender;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::collector;
+use crate::config::Config;
+use crate::event::OperatorAction;
+
+// Operator actions go to the recorder in the main thread, like annotations
+pub type ActionSender = Sender<OperatorAction>;
+
+#[derive(Deserialize)]
+pub struct SignalRequest {
+    signal: String, // "SIGTERM" or "SIGKILL"
+}
+
+// Signal number and canonical name, for the only two signals the UI may send
+fn parse_signal(name: &str) -> Option<(libc::c_int, &'static str)> {
+    let name = name.trim().to_ascii_uppercase();
+    match name.strip_prefix("SIG").unwrap_or(&name) {
+        "TERM" => Some((libc::SIGTERM, "SIGTERM")),
+        "KILL" => Some((libc::SIGKILL, "SIGKILL")),
+        _ => None,
+    }
+}
+
+// Refuse pids kill() would treat as a process group, init, and Black Box itself.
+// `tgid` is the thread group of `pid`, so signalling one of our own threads is refused too.
+fn check_target(pid: u32, tgid: Option<u32>, own_pid: u32) -> Result<(), String> {
+    if pid == 0 || pid > i32::MAX as u32 {
+        return Err(format!("{} is not a valid pid", pid));
+    }
+    if pid == 1 {
+        return Err("refusing to signal init (pid 1)".to_string());
+    }
+    if pid == own_pid || tgid == Some(own_pid) {
+        return Err("refusing to signal Black Box itself".to_string());
+    }
+    Ok(())
+}
+
+fn read_tgid(pid: u32) -> Option<u32> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Tgid:"))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+// Check and send the signal. Every outcome, refusals included, comes back as an action to record.
+fn signal_process(config: &Config, pid: u32, requested: &str, own_pid: u32) -> (StatusCode, OperatorAction) {
+    let mut action = OperatorAction {
+        ts: OffsetDateTime::now_utc(),
+        user: config.auth.username.clone(),
+        action: requested.trim().chars().take(32).collect(),
+        pid,
+        name: None,
+        cmdline: None,
+        success: false,
+        outcome: String::new(),
+    };
+
+    // Read before anything is refused, so the record says what was targeted
+    let details = collector::read_process_details(pid).ok();
+    if let Some(details) = &details {
+        action.name = Some(details.name.clone());
+        action.cmdline = Some(details.cmdline.clone());
+    }
+
+    let refuse = |mut action: OperatorAction, status, outcome: String| {
+        action.outcome = outcome;
+        (status, action)
+    };
+
+    if !config.actions.allow_kill || !config.auth.enabled {
+        return refuse(action, StatusCode::FORBIDDEN, "refused: process actions are disabled".to_string());
+    }
+    let Some((signal, signal_name)) = parse_signal(requested) else {
+        return refuse(action, StatusCode::BAD_REQUEST, "refused: only SIGTERM and SIGKILL are allowed".to_string());
+    };
+    action.action = signal_name.to_string();
+    if let Err(e) = check_target(pid, read_tgid(pid), own_pid) {
+        return refuse(action, StatusCode::FORBIDDEN, format!("refused: {}", e));
+    }
+    if details.is_none() {
+        return refuse(action, StatusCode::NOT_FOUND, format!("refused: no process with pid {}", pid));
+    }
+
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        let error = std::io::Error::last_os_error();
+        let status = match error.raw_os_error() {
+            Some(libc::ESRCH) => StatusCode::NOT_FOUND, // Exited since it was read
+            Some(libc::EPERM) => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        return refuse(action, status, format!("failed: {}", error));
+    }
+
+    action.success = true;
+    action.outcome = format!("sent {}", signal_name);
+    (StatusCode::OK, action)
+}
+
+pub async fn api_process_signal(
+    config: web::Data<Config>,
+    sender: web::Data<ActionSender>,
+    path: web::Path<u32>,
+    body: web::Json<SignalRequest>,
+) -> HttpResponse {
+    let (status, action) = signal_process(&config, path.into_inner(), &body.signal, std::process::id());
+
+    let response = serde_json::json!({
+        "pid": action.pid,
+        "name": action.name,
+        "signal": action.action,
+        "success": action.success,
+        "outcome": action.outcome,
+    });
+
+    // The signal has already gone (or been refused), so a stopped recorder can't undo it
+    if sender.send(action).is_err() {
+        eprintln!("Warning: Recorder is not running, operator action was not recorded");
+    }
+
+    HttpResponse::build(status).json(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    fn config(allow_kill: bool) -> Config {
+        let mut config = Config::test_config();
+        config.actions.allow_kill = allow_kill;
+        config
+    }
+
+    #[test]
+    fn test_parse_signal_and_check_target() {
+        assert_eq!(parse_signal("SIGTERM"), Some((libc::SIGTERM, "SIGTERM")));
+        assert_eq!(parse_signal(" kill "), Some((libc::SIGKILL, "SIGKILL")));
+        assert_eq!(parse_signal("SIGHUP"), None);
+        assert_eq!(parse_signal("9"), None);
+
+        assert!(check_target(4242, Some(4242), 100).is_ok());
+        assert!(check_target(0, None, 100).is_err());
+        assert!(check_target(u32::MAX, None, 100).is_err()); // -1 would signal everything
+        assert!(check_target(1, Some(1), 100).is_err());
+        assert!(check_target(100, Some(100), 100).is_err());
+        assert!(check_target(101, Some(100), 100).is_err()); // One of our threads
+    }
+
+    #[test]
+    fn test_signal_process_gated_and_recorded() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let own_pid = std::process::id();
+
+        // Gate off: refused, but still recorded with what was targeted
+        let (status, action) = signal_process(&config(false), child.id(), "SIGTERM", own_pid);
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(!action.success);
+        assert_eq!(action.name.as_deref(), Some("sleep"));
+        assert_eq!(action.user, "test");
+
+        let (status, action) = signal_process(&config(true), child.id(), "SIGSTOP", own_pid);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(action.outcome.contains("only SIGTERM and SIGKILL"), "{}", action.outcome);
+
+        let (status, _) = signal_process(&config(true), own_pid, "SIGKILL", own_pid);
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, action) = signal_process(&config(true), child.id(), "sigterm", own_pid);
+        assert_eq!(status, StatusCode::OK, "{}", action.outcome);
+        assert!(action.success);
+        assert_eq!(action.action, "SIGTERM");
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+
+        // Reaped, so it's gone now
+        let (status, action) = signal_process(&config(true), child.id(), "SIGKILL", own_pid);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(action.name, None);
+    }
+}
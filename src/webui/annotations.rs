@@ -0,0 +1,154 @@
+use actix_web::{web, HttpResponse};
+use crossbeam_channel::Sender;
+use serde::Deserialize;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::config::Config;
+use crate::event::Annotation;
+use crate::indexed_reader::IndexedReader;
+use crate::summary::ANNOTATION_SLACK_SECS;
+
+const MAX_TEXT_LEN: usize = 2000;
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LEN: usize = 32;
+
+// Annotations go to the recorder in the main thread, which appends and broadcasts them
+pub type AnnotationSender = Sender<Annotation>;
+
+#[derive(Deserialize)]
+pub struct AnnotationRequest {
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    timestamp: Option<i64>, // Unix seconds, defaults to now
+    author: Option<String>, // Only used when auth is disabled
+}
+
+// Check the request and build the annotation. `recorded` is the (first, last) recorded
+// timestamp in nanoseconds, if anything has been recorded yet.
+fn build_annotation(
+    request: AnnotationRequest,
+    author: String,
+    recorded: Option<(i128, i128)>,
+    now: OffsetDateTime,
+) -> Result<Annotation, String> {
+    let text = request.text.trim().to_string();
+    if text.is_empty() {
+        return Err("text must not be empty".to_string());
+    }
+    if text.chars().count() > MAX_TEXT_LEN {
+        return Err(format!("text must be at most {} characters", MAX_TEXT_LEN));
+    }
+
+    let tags: Vec<String> = request
+        .tags
+        .iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tags.len() > MAX_TAGS {
+        return Err(format!("at most {} tags are allowed", MAX_TAGS));
+    }
+    if let Some(tag) = tags.iter().find(|t| t.chars().count() > MAX_TAG_LEN) {
+        return Err(format!("tag '{}' is longer than {} characters", tag, MAX_TAG_LEN));
+    }
+
+    let ts = match request.timestamp {
+        Some(secs) => OffsetDateTime::from_unix_timestamp(secs).map_err(|_| "timestamp is out of range".to_string())?,
+        None => now,
+    };
+
+    // Nothing recorded yet means the range is just this moment
+    let now_ns = now.unix_timestamp_nanos();
+    let (first_ns, last_ns) = recorded.unwrap_or((now_ns, now_ns));
+    let slack_ns = ANNOTATION_SLACK_SECS as i128 * 1_000_000_000;
+    let ts_ns = ts.unix_timestamp_nanos();
+    if ts_ns < first_ns - slack_ns || ts_ns > last_ns.max(now_ns) + slack_ns {
+        return Err("timestamp is outside the recorded range".to_string());
+    }
+
+    Ok(Annotation { ts, author, text, tags })
+}
+
+pub async fn api_create_annotation(
+    indexed_reader: web::Data<Arc<IndexedReader>>,
+    config: web::Data<Config>,
+    sender: web::Data<AnnotationSender>,
+    body: web::Json<AnnotationRequest>,
+) -> HttpResponse {
+    let request = body.into_inner();
+
+    // With auth on there is only one account, so the note is theirs
+    let author = if config.auth.enabled {
+        config.auth.username.clone()
+    } else {
+        request
+            .author
+            .as_deref()
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .unwrap_or("anonymous")
+            .to_string()
+    };
+
+    let _ = indexed_reader.refresh();
+    let annotation = match build_annotation(request, author, indexed_reader.get_time_range(), OffsetDateTime::now_utc()) {
+        Ok(annotation) => annotation,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": e})),
+    };
+
+    let response = serde_json::json!({
+        "timestamp": annotation.ts.unix_timestamp(),
+        "author": annotation.author,
+        "text": annotation.text,
+        "tags": annotation.tags,
+    });
+
+    if sender.send(annotation).is_err() {
+        return HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({"error": "Recorder is not running"}));
+    }
+
+    HttpResponse::Accepted().json(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(text: &str, timestamp: Option<i64>) -> AnnotationRequest {
+        AnnotationRequest {
+            text: text.to_string(),
+            tags: vec![" deploy ".to_string(), String::new()],
+            timestamp,
+            author: None,
+        }
+    }
+
+    #[test]
+    fn test_build_annotation_validates_text_and_range() {
+        let now = OffsetDateTime::from_unix_timestamp(1_767_600_000).unwrap();
+        let hour_ago = now.unix_timestamp() - 3600;
+        let recorded = Some((hour_ago as i128 * 1_000_000_000, now.unix_timestamp_nanos()));
+        let build = |req| build_annotation(req, "admin".to_string(), recorded, now);
+
+        let annotation = build(request("  deployed v2.3.1  ", Some(hour_ago + 60))).unwrap();
+        assert_eq!(annotation.text, "deployed v2.3.1");
+        assert_eq!(annotation.tags, vec!["deploy"]);
+        assert_eq!(annotation.ts.unix_timestamp(), hour_ago + 60);
+        assert_eq!(build(request("now", None)).unwrap().ts, now);
+
+        assert!(build(request("   ", None)).is_err());
+        assert!(build(request(&"x".repeat(MAX_TEXT_LEN + 1), None)).is_err());
+
+        // A few minutes either side of the recording is fine, further is not
+        assert!(build(request("early", Some(hour_ago - 120))).is_ok());
+        assert!(build(request("too early", Some(hour_ago - 3600))).is_err());
+        assert!(build(request("too late", Some(now.unix_timestamp() + 3600))).is_err());
+
+        let mut tagged = request("tagged", None);
+        tagged.tags = vec!["a".repeat(MAX_TAG_LEN + 1)];
+        assert!(build(tagged).is_err());
+    }
+}
@@ -1,88 +1,442 @@
 use actix_web::{
     body::EitherBody,
+    cookie::{time::Duration as CookieDuration, Cookie, SameSite},
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpResponse,
+    http::Method,
+    web, Error, HttpRequest, HttpResponse,
 };
+use anyhow::{Context, Result};
 use base64::{engine::general_purpose, Engine as _};
+use crossbeam_channel::Sender;
 use futures_util::future::LocalBoxFuture;
+use rand::RngCore;
+use ring::hmac;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
 use std::future::{ready, Ready};
+use std::io::Write;
+use std::net::IpAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
 
 use crate::config::AuthConfig;
+use crate::event::{SecurityEvent, SecurityEventKind};
 
-// HTTP Basic Auth middleware
-pub struct BasicAuth {
-    config: AuthConfig,
+pub const SESSION_COOKIE: &str = "bb_session";
+const SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+// Key sessions are signed with, in the data directory so they survive a restart
+const SESSION_KEY_FILE: &str = "session.key";
+const SESSION_KEY_LEN: usize = 32;
+
+// Failed logins from one address within the window before it's locked out, and for how long
+const MAX_FAILED_LOGINS: usize = 5;
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(15 * 60);
+const LOGIN_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+// Attacker supplied, so only this much of it is recorded
+const MAX_RECORDED_USERNAME_LEN: usize = 64;
+// Each API token's use is recorded this often at most, not on every request
+const TOKEN_USE_RECORD_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Failed UI logins go to the recorder in the main thread, like annotations
+pub type SecurityEventSender = Sender<SecurityEvent>;
+
+// Reachable without a session so the page can load and show its login form
+const PUBLIC_PATHS: [&str; 4] = ["/", "/api/login", "/api/logout", "/api/session"];
+
+// Session tokens are "<expiry>.<nonce>.<signature>", with the expiry in Unix seconds and an HMAC
+// over the rest, so any token the key signed is valid until it expires. Logging out revokes a
+// token, but only until the server restarts.
+pub struct SessionStore {
+    key: hmac::Key,
+    revoked: Mutex<HashMap<String, i64>>, // token -> expiry
 }
 
-impl BasicAuth {
-    pub fn new(config: AuthConfig) -> Self {
-        Self { config }
+pub type SharedSessions = Arc<SessionStore>;
+
+// A key of its own, for when the data directory can't hold one. Sessions then end with the server.
+impl Default for SessionStore {
+    fn default() -> Self {
+        let mut secret = [0u8; SESSION_KEY_LEN];
+        rand::rng().fill_bytes(&mut secret);
+        Self::with_secret(&secret)
     }
+}
 
-    fn check_auth(&self, auth_header: Option<&str>) -> bool {
-        let auth_header = match auth_header {
-            Some(h) => h,
-            None => return false,
-        };
+impl SessionStore {
+    fn with_secret(secret: &[u8]) -> Self {
+        Self { key: hmac::Key::new(hmac::HMAC_SHA256, secret), revoked: Mutex::new(HashMap::new()) }
+    }
 
-        // Check if it starts with "Basic "
-        if !auth_header.starts_with("Basic ") {
-            return false;
+    /// Sign sessions with the key in `data_dir`, creating it (readable only by its owner) on first run
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(SESSION_KEY_FILE);
+        match std::fs::read(&path) {
+            Ok(secret) if secret.len() >= SESSION_KEY_LEN => return Ok(Self::with_secret(&secret)),
+            Ok(_) => anyhow::bail!("{} is too short to be a session key", path.display()),
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+            Err(_) => {}
         }
 
-        // Decode base64 credentials
-        let credentials = match general_purpose::STANDARD.decode(&auth_header[6..]) {
-            Ok(c) => c,
-            Err(_) => return false,
-        };
+        let mut secret = [0u8; SESSION_KEY_LEN];
+        rand::rng().fill_bytes(&mut secret);
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .and_then(|mut file| file.write_all(&secret))
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self::with_secret(&secret))
+    }
+
+    pub fn create(&self) -> String {
+        self.create_at(OffsetDateTime::now_utc().unix_timestamp())
+    }
+
+    fn create_at(&self, now: i64) -> String {
+        let mut nonce = [0u8; 16];
+        rand::rng().fill_bytes(&mut nonce);
+        let payload = format!("{}.{}", now + SESSION_TTL.as_secs() as i64, general_purpose::URL_SAFE_NO_PAD.encode(nonce));
+        let signature = hmac::sign(&self.key, payload.as_bytes());
+        format!("{}.{}", payload, general_purpose::URL_SAFE_NO_PAD.encode(signature.as_ref()))
+    }
+
+    pub fn is_valid(&self, token: &str) -> bool {
+        self.is_valid_at(token, OffsetDateTime::now_utc().unix_timestamp())
+    }
+
+    fn is_valid_at(&self, token: &str, now: i64) -> bool {
+        self.expiry(token).is_some_and(|expiry| expiry > now) && !self.revoked.lock().unwrap().contains_key(token)
+    }
 
-        let credentials_str = match String::from_utf8(credentials) {
-            Ok(s) => s,
-            Err(_) => return false,
+    // The expiry of a token this key signed
+    fn expiry(&self, token: &str) -> Option<i64> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let signature = general_purpose::URL_SAFE_NO_PAD.decode(signature).ok()?;
+        hmac::verify(&self.key, payload.as_bytes(), &signature).ok()?;
+        payload.split_once('.')?.0.parse().ok()
+    }
+
+    pub fn remove(&self, token: &str) {
+        let Some(expiry) = self.expiry(token) else {
+            return;
         };
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut revoked = self.revoked.lock().unwrap();
+        revoked.retain(|_, expiry| *expiry > now);
+        revoked.insert(token.to_string(), expiry);
+    }
+}
+
+// Recent failed logins from one address
+struct FailedLogins {
+    failures: VecDeque<Instant>,
+    locked_until: Option<Instant>,
+}
+
+/// Locks an address out of logging in after MAX_FAILED_LOGINS failures within the window
+#[derive(Default)]
+pub struct LoginLimiter {
+    addresses: Mutex<HashMap<Option<IpAddr>, FailedLogins>>, // None for the Unix socket
+}
 
-        // Split username:password
-        let parts: Vec<&str> = credentials_str.splitn(2, ':').collect();
-        if parts.len() != 2 {
+pub type SharedLoginLimiter = Arc<LoginLimiter>;
+
+impl LoginLimiter {
+    // How much longer the address is locked out for
+    fn locked_for(&self, addr: Option<IpAddr>, now: Instant) -> Option<Duration> {
+        let addresses = self.addresses.lock().unwrap();
+        let locked_until = addresses.get(&addr)?.locked_until?;
+        (locked_until > now).then(|| locked_until - now)
+    }
+
+    // Count a failure, returning whether it locked the address out
+    fn record_failure(&self, addr: Option<IpAddr>, now: Instant) -> bool {
+        let mut addresses = self.addresses.lock().unwrap();
+        // Forget addresses that have gone quiet so scanning from many can't grow this forever
+        addresses.retain(|_, logins| {
+            logins.locked_until.is_some_and(|until| until > now)
+                || logins.failures.back().is_some_and(|at| now.duration_since(*at) < FAILED_LOGIN_WINDOW)
+        });
+        let logins = addresses
+            .entry(addr)
+            .or_insert_with(|| FailedLogins { failures: VecDeque::new(), locked_until: None });
+        logins.failures.push_back(now);
+        while logins.failures.front().is_some_and(|at| now.duration_since(*at) >= FAILED_LOGIN_WINDOW) {
+            logins.failures.pop_front();
+        }
+        if logins.failures.len() < MAX_FAILED_LOGINS {
             return false;
         }
+        logins.failures.clear();
+        logins.locked_until = Some(now + LOGIN_LOCKOUT);
+        true
+    }
+
+    fn record_success(&self, addr: Option<IpAddr>) {
+        self.addresses.lock().unwrap().remove(&addr);
+    }
+}
+
+/// Records the use of each API token as a security event, at most once per interval
+pub struct ApiTokenUsage {
+    sender: SecurityEventSender,
+    last_recorded: Mutex<HashMap<String, Instant>>, // Token name -> when
+}
+
+pub type SharedApiTokenUsage = Arc<ApiTokenUsage>;
+
+impl ApiTokenUsage {
+    pub fn new(sender: SecurityEventSender) -> Self {
+        Self { sender, last_recorded: Mutex::new(HashMap::new()) }
+    }
+
+    fn record(&self, name: &str, addr: Option<IpAddr>, path: &str, now: Instant) {
+        let mut last_recorded = self.last_recorded.lock().unwrap();
+        if last_recorded.get(name).is_some_and(|at| now.duration_since(*at) < TOKEN_USE_RECORD_INTERVAL) {
+            return;
+        }
+        last_recorded.insert(name.to_string(), now);
+        let source = addr.map_or_else(|| "the Unix socket".to_string(), |ip| ip.to_string());
+        let _ = self.sender.send(SecurityEvent {
+            ts: OffsetDateTime::now_utc(),
+            kind: SecurityEventKind::ApiTokenUsed,
+            user: name.to_string(),
+            source_ip: addr.map(|ip| ip.to_string()),
+            message: format!("API token {} used from {} for {}", name, source, path),
+        });
+    }
+}
+
+// `via` is how they tried: "web UI login" or "HTTP Basic login"
+fn failed_login_event(via: &str, username: &str, addr: Option<IpAddr>, locked_out: bool) -> SecurityEvent {
+    let user: String = username.chars().take(MAX_RECORDED_USERNAME_LEN).collect();
+    let source = addr.map_or_else(|| "the Unix socket".to_string(), |ip| ip.to_string());
+    let mut message = format!("Failed {} for {} from {}", via, user, source);
+    if locked_out {
+        message.push_str(&format!(", locked out for {} minutes", LOGIN_LOCKOUT.as_secs() / 60));
+    }
+    SecurityEvent {
+        ts: OffsetDateTime::now_utc(),
+        kind: SecurityEventKind::FailedAuth,
+        user,
+        source_ip: addr.map(|ip| ip.to_string()),
+        message,
+    }
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+}
+
+// Session token from the cookie or an "Authorization: Bearer" header
+fn session_token(req: &HttpRequest) -> Option<String> {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        return Some(cookie.value().to_string());
+    }
+    bearer_token(req).map(|t| t.to_string())
+}
+
+// HTTP Basic credentials, still accepted for the CLI and Prometheus scrapers. Only parsed here,
+// checking them goes through the same lockout as /api/login.
+fn basic_credentials(auth_header: Option<&str>) -> Option<(String, String)> {
+    let encoded = auth_header?.strip_prefix("Basic ")?;
+    let credentials = String::from_utf8(general_purpose::STANDARD.decode(encoded).ok()?).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+fn too_many_failed_logins(remaining: Duration) -> HttpResponse {
+    let minutes = remaining.as_secs().div_ceil(60);
+    HttpResponse::TooManyRequests()
+        .insert_header(("Retry-After", remaining.as_secs().max(1).to_string()))
+        .json(serde_json::json!({"error": format!("Too many failed logins, try again in {} minutes", minutes)}))
+}
+
+fn authentication_required() -> HttpResponse {
+    // Bearer rather than Basic so browsers don't pop up their own login dialog
+    HttpResponse::Unauthorized()
+        .insert_header(("WWW-Authenticate", "Bearer realm=\"Black Box\""))
+        .json(serde_json::json!({"error": "Authentication required"}))
+}
+
+fn session_cookie(req: &HttpRequest, value: String, max_age: CookieDuration) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, value)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        // Only send it back over HTTPS when the server is running with TLS
+        .secure(req.connection_info().scheme() == "https")
+        .max_age(max_age)
+        .finish()
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+pub async fn api_login(
+    req: HttpRequest,
+    config: web::Data<crate::config::Config>,
+    sessions: web::Data<SharedSessions>,
+    limiter: web::Data<SharedLoginLimiter>,
+    security: web::Data<SecurityEventSender>,
+    body: web::Json<LoginRequest>,
+) -> HttpResponse {
+    if !config.auth.enabled {
+        return HttpResponse::Ok().json(serde_json::json!({"auth_enabled": false}));
+    }
+
+    // The peer, not X-Forwarded-For, which anyone can set. Behind a proxy every login shares one address.
+    let addr = req.peer_addr().map(|a| a.ip());
+    if let Some(remaining) = limiter.locked_for(addr, Instant::now()) {
+        return too_many_failed_logins(remaining);
+    }
+
+    // Password hashing is deliberately slow, keep it off the async workers
+    let auth = config.auth.clone();
+    let LoginRequest { username, password } = body.into_inner();
+    let attempted = username.clone();
+    let valid = web::block(move || auth.verify(&username, &password))
+        .await
+        .unwrap_or(false);
+
+    if !valid {
+        let locked_out = limiter.record_failure(addr, Instant::now());
+        let _ = security.send(failed_login_event("web UI login", &attempted, addr, locked_out));
+        return HttpResponse::Unauthorized()
+            .json(serde_json::json!({"error": "Invalid username or password"}));
+    }
+
+    limiter.record_success(addr);
+    let token = sessions.create();
+    HttpResponse::Ok()
+        .cookie(session_cookie(
+            &req,
+            token.clone(),
+            CookieDuration::seconds(SESSION_TTL.as_secs() as i64),
+        ))
+        .json(serde_json::json!({
+            "token": token,
+            "expires_in": SESSION_TTL.as_secs(),
+        }))
+}
+
+pub async fn api_logout(req: HttpRequest, sessions: web::Data<SharedSessions>) -> HttpResponse {
+    if let Some(token) = session_token(&req) {
+        sessions.remove(&token);
+    }
+
+    HttpResponse::Ok()
+        .cookie(session_cookie(&req, String::new(), CookieDuration::ZERO))
+        .json(serde_json::json!({"logged_out": true}))
+}
+
+// Lets the page decide whether to show the login form before opening the WebSocket
+pub async fn api_session(
+    req: HttpRequest,
+    config: web::Data<crate::config::Config>,
+    sessions: web::Data<SharedSessions>,
+) -> HttpResponse {
+    let authenticated = !config.auth.enabled
+        || session_token(&req).is_some_and(|t| sessions.is_valid(&t));
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "auth_enabled": config.auth.enabled,
+        "authenticated": authenticated,
+        "allow_kill": authenticated && config.auth.enabled && config.actions.allow_kill,
+    }))
+}
+
+// Rejects requests without a valid session (or Basic credentials) with 401. API tokens are
+// accepted for reading only, anything else with one gets 403. Failed Basic credentials count
+// towards the same lockout as /api/login and are recorded the same way.
+pub struct RequireAuth {
+    config: AuthConfig,
+    sessions: SharedSessions,
+    token_usage: SharedApiTokenUsage,
+    login_limiter: SharedLoginLimiter,
+    security: SecurityEventSender,
+    base_path: String,
+}
 
-        let (username, password) = (parts[0], parts[1]);
+impl RequireAuth {
+    pub fn new(
+        config: AuthConfig,
+        sessions: SharedSessions,
+        token_usage: SharedApiTokenUsage,
+        login_limiter: SharedLoginLimiter,
+        security: SecurityEventSender,
+    ) -> Self {
+        Self { config, sessions, token_usage, login_limiter, security, base_path: String::new() }
+    }
 
-        // Verify username and password hash
-        username == self.config.username
-            && bcrypt::verify(password, &self.config.password_hash).unwrap_or(false)
+    // Routes are mounted under this prefix, public paths are matched after it
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        self.base_path = base_path.to_string();
+        self
     }
 }
 
-impl<S, B> Transform<S, ServiceRequest> for BasicAuth
+impl<S, B> Transform<S, ServiceRequest> for RequireAuth
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
     type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type InitError = ();
-    type Transform = BasicAuthMiddleware<S>;
+    type Transform = RequireAuthMiddleware<S>;
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(BasicAuthMiddleware {
-            service,
+        ready(Ok(RequireAuthMiddleware {
+            service: Rc::new(service),
             config: self.config.clone(),
+            sessions: self.sessions.clone(),
+            token_usage: self.token_usage.clone(),
+            login_limiter: self.login_limiter.clone(),
+            security: self.security.clone(),
+            base_path: self.base_path.clone(),
         }))
     }
 }
 
-pub struct BasicAuthMiddleware<S> {
-    service: S,
+pub struct RequireAuthMiddleware<S> {
+    service: Rc<S>, // Shared with the future that checks Basic credentials before calling it
     config: AuthConfig,
+    sessions: SharedSessions,
+    token_usage: SharedApiTokenUsage,
+    login_limiter: SharedLoginLimiter,
+    security: SecurityEventSender,
+    base_path: String,
+}
+
+impl<S> RequireAuthMiddleware<S> {
+    fn is_public(&self, path: &str) -> bool {
+        match path.strip_prefix(self.base_path.as_str()) {
+            Some("") => true, // The index without its trailing slash
+            Some(path) => PUBLIC_PATHS.contains(&path),
+            None => false,
+        }
+    }
 }
 
-impl<S, B> Service<ServiceRequest> for BasicAuthMiddleware<S>
+impl<S, B> Service<ServiceRequest> for RequireAuthMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -93,29 +447,56 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        // Skip auth if disabled in config
-        if !self.config.enabled {
-            let fut = self.service.call(req);
+        let mut is_authenticated = !self.config.enabled
+            || self.is_public(req.path())
+            || session_token(req.request()).is_some_and(|t| self.sessions.is_valid(&t));
+
+        if !is_authenticated
+            && let Some(name) = bearer_token(req.request()).and_then(|t| self.config.api_token_name(t))
+        {
+            if !matches!(*req.method(), Method::GET | Method::HEAD) {
+                let response = HttpResponse::Forbidden()
+                    .json(serde_json::json!({"error": "API tokens are read-only"}))
+                    .map_into_right_body();
+                return Box::pin(async { Ok(ServiceResponse::new(req.into_parts().0, response)) });
+            }
+            self.token_usage.record(name, req.peer_addr().map(|a| a.ip()), req.path(), Instant::now());
+            is_authenticated = true;
+        }
+
+        if !is_authenticated
+            && let Some((username, password)) =
+                basic_credentials(req.headers().get("Authorization").and_then(|h| h.to_str().ok()))
+        {
+            let addr = req.peer_addr().map(|a| a.ip());
+            if let Some(remaining) = self.login_limiter.locked_for(addr, Instant::now()) {
+                let response = too_many_failed_logins(remaining).map_into_right_body();
+                return Box::pin(async { Ok(ServiceResponse::new(req.into_parts().0, response)) });
+            }
+
+            let service = self.service.clone();
+            let auth = self.config.clone();
+            let limiter = self.login_limiter.clone();
+            let security = self.security.clone();
             return Box::pin(async move {
-                let res = fut.await?;
+                // Password hashing is deliberately slow, keep it off the async workers
+                let attempted = username.clone();
+                let valid = web::block(move || auth.verify(&username, &password))
+                    .await
+                    .unwrap_or(false);
+                if !valid {
+                    let locked_out = limiter.record_failure(addr, Instant::now());
+                    let _ = security.send(failed_login_event("HTTP Basic login", &attempted, addr, locked_out));
+                    return Ok(ServiceResponse::new(req.into_parts().0, authentication_required().map_into_right_body()));
+                }
+                limiter.record_success(addr);
+                let res = service.call(req).await?;
                 Ok(res.map_into_left_body())
             });
         }
 
-        let auth_header = req
-            .headers()
-            .get("Authorization")
-            .and_then(|h| h.to_str().ok());
-
-        let auth = BasicAuth::new(self.config.clone());
-        let is_authenticated = auth.check_auth(auth_header);
-
         if !is_authenticated {
-            let response = HttpResponse::Unauthorized()
-                .insert_header(("WWW-Authenticate", "Basic realm=\"Black Box\""))
-                .finish()
-                .map_into_right_body();
-
+            let response = authentication_required().map_into_right_body();
             return Box::pin(async { Ok(ServiceResponse::new(req.into_parts().0, response)) });
         }
 
@@ -127,3 +508,320 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use actix_web::{http::StatusCode, test, App};
+
+    fn test_app_config(enabled: bool) -> Config {
+        let mut config = Config::test_config();
+        config.auth.enabled = enabled;
+        config
+    }
+
+    macro_rules! init_app {
+        ($config:expr, $sessions:expr) => {
+            init_app!($config, $sessions, crossbeam_channel::unbounded().0)
+        };
+        ($config:expr, $sessions:expr, $security:expr) => {{
+            let security: SecurityEventSender = $security;
+            let limiter = SharedLoginLimiter::default();
+            test::init_service(
+                App::new()
+                    .app_data(web::Data::new($config.clone()))
+                    .app_data(web::Data::new($sessions.clone()))
+                    .app_data(web::Data::new(limiter.clone()))
+                    .app_data(web::Data::new(security.clone()))
+                    .wrap(RequireAuth::new(
+                        $config.auth.clone(),
+                        $sessions.clone(),
+                        Arc::new(ApiTokenUsage::new(security.clone())),
+                        limiter,
+                        security,
+                    ))
+                    .route("/", web::get().to(HttpResponse::Ok))
+                    .route("/api/login", web::post().to(api_login))
+                    .route("/api/logout", web::post().to(api_logout))
+                    .route("/api/timeline", web::get().to(HttpResponse::Ok))
+                    .route("/api/export", web::get().to(HttpResponse::Ok))
+                    .route("/api/annotations", web::post().to(HttpResponse::Ok))
+                    .route("/ws", web::get().to(HttpResponse::Ok)),
+            )
+            .await
+        }};
+    }
+
+    #[actix_rt::test]
+    async fn test_auth_disabled_allows_everything() {
+        let config = test_app_config(false);
+        let sessions: SharedSessions = Default::default();
+        let app = init_app!(config, sessions);
+
+        for path in ["/api/timeline", "/ws"] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", path);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_auth_enabled_requires_session() {
+        let config = test_app_config(true);
+        let sessions: SharedSessions = Default::default();
+        let app = init_app!(config, sessions);
+
+        // The page itself loads so it can show the login form
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for path in ["/api/timeline", "/api/export?format=csv&type=anomaly", "/ws"] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", path);
+        }
+
+        let bad_login = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(serde_json::json!({"username": "test", "password": "wrong"}))
+            .to_request();
+        let resp = test::call_service(&app, bad_login).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let login = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(serde_json::json!({"username": "test", "password": "test"}))
+            .to_request();
+        let resp = test::call_service(&app, login).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let cookie = resp
+            .response()
+            .cookies()
+            .find(|c| c.name() == SESSION_COOKIE)
+            .expect("session cookie")
+            .into_owned();
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/api/timeline").cookie(cookie.clone()).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Logging out invalidates the token server-side
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::post().uri("/api/logout").cookie(cookie.clone()).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/api/timeline").cookie(cookie).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn test_basic_auth_still_accepted() {
+        let config = test_app_config(true);
+        let sessions: SharedSessions = Default::default();
+        let app = init_app!(config, sessions);
+
+        let credentials = general_purpose::STANDARD.encode("test:test");
+        let req = test::TestRequest::get()
+            .uri("/api/timeline")
+            .insert_header(("Authorization", format!("Basic {}", credentials)))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_failed_basic_auth_recorded_and_locked_out() {
+        let config = test_app_config(true);
+        let sessions: SharedSessions = Default::default();
+        let (security_tx, security_rx) = crossbeam_channel::unbounded();
+        let app = init_app!(config, sessions, security_tx);
+        let request = |credentials: &str| {
+            test::TestRequest::get()
+                .uri("/api/timeline")
+                .peer_addr("203.0.113.8:51234".parse().unwrap())
+                .insert_header(("Authorization", format!("Basic {}", general_purpose::STANDARD.encode(credentials))))
+                .to_request()
+        };
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            let resp = test::call_service(&app, request("admin:guess")).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        }
+        let events: Vec<SecurityEvent> = security_rx.try_iter().collect();
+        assert_eq!(events.len(), MAX_FAILED_LOGINS);
+        assert!(matches!(events[0].kind, SecurityEventKind::FailedAuth));
+        assert_eq!(events[0].message, "Failed HTTP Basic login for admin from 203.0.113.8");
+        assert!(events[MAX_FAILED_LOGINS - 1].message.ends_with(", locked out for 15 minutes"));
+
+        // The right credentials aren't checked until the lockout ends, and neither is the login form
+        let resp = test::call_service(&app, request("test:test")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key("Retry-After"));
+        let login = test::TestRequest::post()
+            .uri("/api/login")
+            .peer_addr("203.0.113.8:51235".parse().unwrap())
+            .set_json(serde_json::json!({"username": "test", "password": "test"}))
+            .to_request();
+        let resp = test::call_service(&app, login).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(security_rx.try_recv().is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_public_paths_under_base_path() {
+        let config = test_app_config(true);
+        let sessions: SharedSessions = Default::default();
+        let app = test::init_service(
+            App::new()
+                .wrap(
+                    RequireAuth::new(
+                        config.auth.clone(),
+                        sessions,
+                        Arc::new(ApiTokenUsage::new(crossbeam_channel::unbounded().0)),
+                        SharedLoginLimiter::default(),
+                        crossbeam_channel::unbounded().0,
+                    )
+                    .with_base_path("/black-box"),
+                )
+                .service(
+                    web::scope("/black-box")
+                        .route("", web::get().to(HttpResponse::Ok))
+                        .route("/", web::get().to(HttpResponse::Ok))
+                        .route("/api/session", web::get().to(HttpResponse::Ok))
+                        .route("/api/timeline", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        for path in ["/black-box", "/black-box/", "/black-box/api/session"] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", path);
+        }
+        // Public only under the prefix
+        for path in ["/black-box/api/timeline", "/api/session", "/black-boxes"] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", path);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_failed_logins_recorded_and_locked_out() {
+        let config = test_app_config(true);
+        let sessions: SharedSessions = Default::default();
+        let (security_tx, security_rx) = crossbeam_channel::unbounded();
+        let app = init_app!(config, sessions, security_tx);
+        let login = |password: &str| {
+            test::TestRequest::post()
+                .uri("/api/login")
+                .peer_addr("203.0.113.7:51234".parse().unwrap())
+                .set_json(serde_json::json!({"username": "admin", "password": password}))
+                .to_request()
+        };
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            let resp = test::call_service(&app, login("guess")).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        }
+        let events: Vec<SecurityEvent> = security_rx.try_iter().collect();
+        assert_eq!(events.len(), MAX_FAILED_LOGINS);
+        assert!(matches!(events[0].kind, SecurityEventKind::FailedAuth));
+        assert_eq!(events[0].source_ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(events[0].message, "Failed web UI login for admin from 203.0.113.7");
+        assert!(events[MAX_FAILED_LOGINS - 1].message.ends_with(", locked out for 15 minutes"));
+
+        // Locked out, even with the right password, and the password isn't checked
+        let resp = test::call_service(&app, login("test")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key("Retry-After"));
+        assert!(security_rx.try_recv().is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_api_tokens_read_only_and_use_recorded() {
+        let (token, token_hash) = crate::config::generate_api_token();
+        let mut config = test_app_config(true);
+        config.auth.api_tokens.push(crate::config::ApiToken { name: "grafana".to_string(), token_hash });
+        let sessions: SharedSessions = Default::default();
+        let (security_tx, security_rx) = crossbeam_channel::unbounded();
+        let app = init_app!(config, sessions, security_tx);
+        let request = |req: test::TestRequest, token: &str| {
+            req.peer_addr("192.0.2.10:40000".parse().unwrap())
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        for _ in 0..3 {
+            let resp = test::call_service(&app, request(test::TestRequest::get().uri("/api/timeline"), &token)).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let resp = test::call_service(&app, request(test::TestRequest::post().uri("/api/annotations"), &token)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = test::call_service(&app, request(test::TestRequest::get().uri("/api/timeline"), "bbt_wrong")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        // Recorded once for the hour, not per request
+        let events: Vec<SecurityEvent> = security_rx.try_iter().collect();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].kind, SecurityEventKind::ApiTokenUsed));
+        assert_eq!(events[0].user, "grafana");
+        assert_eq!(events[0].message, "API token grafana used from 192.0.2.10 for /api/timeline");
+    }
+
+    #[actix_rt::test]
+    async fn test_lockout_expires_and_success_resets() {
+        let limiter = LoginLimiter::default();
+        let addr = Some("198.51.100.2".parse().unwrap());
+        let start = Instant::now();
+        for i in 0..MAX_FAILED_LOGINS - 1 {
+            assert!(!limiter.record_failure(addr, start + Duration::from_secs(i as u64)));
+        }
+        limiter.record_success(addr);
+        assert!(!limiter.record_failure(addr, start + Duration::from_secs(10)));
+        assert!(limiter.locked_for(addr, start + Duration::from_secs(10)).is_none());
+
+        for i in 0..MAX_FAILED_LOGINS as u64 - 1 {
+            limiter.record_failure(addr, start + Duration::from_secs(20 + i));
+        }
+        let locked_at = start + Duration::from_secs(30);
+        assert!(limiter.locked_for(addr, locked_at).is_some());
+        assert!(limiter.locked_for(None, locked_at).is_none());
+        assert!(limiter.locked_for(addr, locked_at + LOGIN_LOCKOUT).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_signed_sessions_survive_restart() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore::open(dir.path()).unwrap();
+        let token = store.create();
+        assert!(store.is_valid(&token));
+
+        let reopened = SessionStore::open(dir.path()).unwrap();
+        assert!(reopened.is_valid(&token));
+        assert!(!SessionStore::default().is_valid(&token));
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.path().join(SESSION_KEY_FILE)).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Changing the expiry breaks the signature
+        let (expiry, rest) = token.split_once('.').unwrap();
+        let extended = format!("{}.{}", expiry.parse::<i64>().unwrap() + 3600, rest);
+        assert!(!reopened.is_valid(&extended));
+        assert!(!reopened.is_valid("not-a-token"));
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let old = reopened.create_at(now - SESSION_TTL.as_secs() as i64 - 1);
+        assert!(!reopened.is_valid_at(&old, now));
+
+        reopened.remove(&token);
+        assert!(!reopened.is_valid(&token));
+    }
+}
@@ -0,0 +1,190 @@
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use std::io::{self, BufWriter, Write};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+
+use crate::cli::ExportFormat;
+use crate::commands::export::{ExportFilter, ExportStream};
+use crate::reader::LogReader;
+
+// The body goes out in chunks this size, with a few waiting at most while the client reads
+const CHUNK_SIZE: usize = 64 * 1024;
+const CHUNKS_IN_FLIGHT: usize = 4;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum DownloadFormat {
+    #[default]
+    Json,
+    Jsonl,
+    Csv,
+}
+
+impl DownloadFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            DownloadFormat::Json => "json",
+            DownloadFormat::Jsonl => "jsonl",
+            DownloadFormat::Csv => "csv",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            DownloadFormat::Json => "application/json",
+            DownloadFormat::Jsonl => "application/x-ndjson",
+            DownloadFormat::Csv => "text/csv",
+        }
+    }
+}
+
+impl From<DownloadFormat> for ExportFormat {
+    fn from(format: DownloadFormat) -> Self {
+        match format {
+            DownloadFormat::Json => ExportFormat::Json,
+            DownloadFormat::Jsonl => ExportFormat::Jsonl,
+            DownloadFormat::Csv => ExportFormat::Csv,
+        }
+    }
+}
+
+// ?start=&end=&format=json|jsonl|csv&type=&compress=1, filtered like `black-box export`
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ExportQuery {
+    start: Option<String>, // Unix seconds or RFC3339
+    end: Option<String>,
+    #[serde(default)]
+    format: DownloadFormat,
+    #[serde(rename = "type")]
+    event_type: Option<String>,
+    compress: Option<String>, // "1" or "true"
+}
+
+// Hands what the export writes to the response body. Writes block while the client is behind,
+// and fail once it has gone so the export stops reading segments.
+struct BodyWriter(mpsc::Sender<Bytes>);
+
+impl Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Bytes::copy_from_slice(buf))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "download closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_export(export: &ExportStream, reader: &LogReader, body: BodyWriter, compress: bool) -> anyhow::Result<()> {
+    let mut writer = BufWriter::with_capacity(CHUNK_SIZE, body);
+    if compress {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        export.write(reader, &mut encoder)?;
+        encoder.finish()?.flush()?;
+    } else {
+        export.write(reader, &mut writer)?;
+    }
+    Ok(())
+}
+
+/// Download recorded events, streamed as they're read so a large range is never held in memory
+pub async fn api_export(reader: web::Data<LogReader>, query: web::Query<ExportQuery>) -> HttpResponse {
+    let query = query.into_inner();
+    let compress = matches!(query.compress.as_deref(), Some("1" | "true"));
+    let filter = ExportFilter { event_type: query.event_type, start: query.start, end: query.end, ..Default::default() };
+    let export = match ExportStream::new(filter, query.format.into()) {
+        Ok(export) => export,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("{:#}", e)})),
+    };
+
+    let (tx, rx) = mpsc::channel(CHUNKS_IN_FLIGHT);
+    tokio::task::spawn_blocking(move || {
+        // Too late for an error status, a download that stops short is the sign
+        if let Err(e) = write_export(&export, &reader, BodyWriter(tx), compress) {
+            eprintln!("Export download ended early: {:#}", e);
+        }
+    });
+
+    let mut filename = format!("black-box-export.{}", query.format.extension());
+    let content_type = if compress {
+        filename.push_str(".gz");
+        "application/gzip"
+    } else {
+        query.format.content_type()
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+        .streaming(ReceiverStream::new(rx).map(Ok::<_, actix_web::Error>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, Anomaly, AnomalyKind, AnomalySeverity, Event};
+    use crate::recorder::Recorder;
+    use crate::storage::RetentionPolicy;
+    use actix_web::{body, http::StatusCode, test, App};
+    use std::io::Read;
+    use time::OffsetDateTime;
+
+    #[actix_rt::test]
+    async fn test_export_streams_filtered_download() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let mut recorder = Recorder::open_with_config(dir.path(), retention, None, None).unwrap();
+        for i in 0..3 {
+            recorder.append(&Event::SystemMetrics(sample_metrics())).unwrap();
+            recorder
+                .append(&Event::Anomaly(Anomaly {
+                    ts: OffsetDateTime::now_utc(),
+                    severity: AnomalySeverity::Warning,
+                    kind: AnomalyKind::CpuSpike,
+                    message: format!("CPU spike {}", i),
+                }))
+                .unwrap();
+        }
+        recorder.close().unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(LogReader::new(dir.path())))
+                .route("/api/export", web::get().to(api_export)),
+        )
+        .await;
+
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/api/export?type=anomaly").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = body::to_bytes(resp.into_body()).await.unwrap();
+        let events: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2]["Anomaly"]["message"], "CPU spike 2");
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/api/export?type=anomaly&format=csv&compress=1").to_request(),
+        )
+        .await;
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/gzip");
+        let body = body::to_bytes(resp.into_body()).await.unwrap();
+        let mut csv = String::new();
+        flate2::read::GzDecoder::new(&body[..]).read_to_string(&mut csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "timestamp,severity,kind,message");
+        assert_eq!(lines.len(), 4);
+
+        // CSV is a single table, so it needs a type
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/api/export?format=csv").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/api/export?start=yesterday").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,15 +1,77 @@
 use actix_web::{web, HttpResponse};
+use serde::Deserialize;
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Instant;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
+use crate::collector_health::SharedCollectorHealth;
 use crate::config::Config;
+use crate::event::{AnomalySeverity, Event};
+use crate::indexed_reader::IndexedReader;
 use crate::reader::LogReader;
 
+// Anomaly and security event counts cover this window
+const RECENT_WINDOW_SECS: i64 = 3600;
+
+#[derive(Deserialize)]
+pub struct HealthQuery {
+    // How far back to look for the last Critical anomaly (used by `status --check`)
+    critical_window_minutes: Option<i64>,
+}
+
+// Remote streaming state shared with the streaming task. None when streaming is disabled,
+// which a config reload can change.
+pub type RemoteStatus = Arc<RwLock<Option<Arc<AtomicBool>>>>;
+
+#[derive(Debug, Default, PartialEq)]
+struct RecentActivity {
+    anomalies: BTreeMap<String, BTreeMap<String, usize>>, // severity -> kind -> count
+    security_events: BTreeMap<String, usize>,             // kind -> count
+    last_critical_anomaly: Option<OffsetDateTime>,
+}
+
+// Group events from the last hour; Critical anomalies are looked for back to `critical_since`
+fn summarize_recent(events: &[Event], now: OffsetDateTime, critical_since: OffsetDateTime) -> RecentActivity {
+    let recent_since = now - time::Duration::seconds(RECENT_WINDOW_SECS);
+    let mut activity = RecentActivity::default();
+
+    for event in events {
+        match event {
+            Event::Anomaly(a) => {
+                if a.severity == AnomalySeverity::Critical && a.ts >= critical_since {
+                    activity.last_critical_anomaly = activity.last_critical_anomaly.max(Some(a.ts));
+                }
+                if a.ts >= recent_since {
+                    *activity
+                        .anomalies
+                        .entry(format!("{:?}", a.severity))
+                        .or_default()
+                        .entry(format!("{:?}", a.kind))
+                        .or_default() += 1;
+                }
+            }
+            Event::SecurityEvent(s) if s.ts >= recent_since => {
+                *activity.security_events.entry(format!("{:?}", s.kind)).or_default() += 1;
+            }
+            _ => {}
+        }
+    }
+
+    activity
+}
+
 pub async fn health_check(
     reader: web::Data<LogReader>,
+    indexed_reader: web::Data<Arc<IndexedReader>>,
     start_time: web::Data<Instant>,
     config: web::Data<Config>,
     data_dir: web::Data<String>,
+    remote: web::Data<RemoteStatus>,
+    query: web::Query<HealthQuery>,
 ) -> HttpResponse {
     // Calculate uptime
     let uptime_secs = start_time.elapsed().as_secs();
@@ -22,26 +84,80 @@ pub async fn health_check(
 
     // Calculate storage usage
     let storage_bytes_used = calculate_storage_usage(data_dir.get_ref());
-    let max_storage_bytes = config.server.max_storage_mb * 1024 * 1024;
+    let max_storage_bytes = config.server.storage_limit_bytes();
     let storage_percent = if max_storage_bytes > 0 {
         (storage_bytes_used as f64 / max_storage_bytes as f64) * 100.0
     } else {
         0.0
     };
 
+    // A stale last event means the collector is wedged even though the server answers
+    let _ = indexed_reader.refresh();
+    let now = OffsetDateTime::now_utc();
+    let last_event = indexed_reader
+        .get_time_range()
+        .and_then(|(_, last_ns)| OffsetDateTime::from_unix_timestamp_nanos(last_ns).ok());
+
+    let critical_window_secs = query.critical_window_minutes.unwrap_or(60).max(0) * 60;
+    let critical_since = now - time::Duration::seconds(critical_window_secs);
+    let read_since = critical_since.min(now - time::Duration::seconds(RECENT_WINDOW_SECS));
+    let events = indexed_reader
+        .read_time_range(Some(read_since.unix_timestamp_nanos()), None)
+        .unwrap_or_default();
+    let recent = summarize_recent(&events, now, critical_since);
+
+    let remote_streaming = match remote.read().unwrap().as_ref() {
+        Some(connected) if connected.load(Ordering::Relaxed) => "connected",
+        Some(_) => "disconnected",
+        None => "disabled",
+    };
+
     let health_status = json!({
         "status": "healthy",
         "uptime_seconds": uptime_secs,
         "event_count": event_count,
+        "last_event_timestamp": last_event.and_then(|ts| ts.format(&Rfc3339).ok()),
+        "last_event_age_seconds": last_event.map(|ts| (now - ts).whole_seconds().max(0)),
+        "segment_count": indexed_reader.segment_count(),
         "storage_bytes_used": storage_bytes_used,
         "storage_bytes_max": max_storage_bytes,
-        "storage_percent": format!("{:.2}", storage_percent),
-        "timestamp": time::OffsetDateTime::now_utc().to_string(),
+        "storage_percent": (storage_percent * 100.0).round() / 100.0,
+        "anomalies_last_hour": recent.anomalies,
+        "security_events_last_hour": recent.security_events,
+        "last_critical_anomaly": recent.last_critical_anomaly.and_then(|ts| ts.format(&Rfc3339).ok()),
+        "remote_streaming": remote_streaming,
+        "timestamp": now.to_string(),
     });
 
     HttpResponse::Ok().json(health_status)
 }
 
+// Each collector's last successful read and run of failures, to spot a data source that
+// has stopped working
+pub async fn api_collector_health(collectors: web::Data<SharedCollectorHealth>) -> HttpResponse {
+    let collectors = collectors.read().map(|c| c.clone()).unwrap_or_default();
+    let now = OffsetDateTime::now_utc();
+    let collectors: Vec<_> = collectors
+        .iter()
+        .map(|c| {
+            json!({
+                "name": c.name,
+                "healthy": c.consecutive_failures == 0,
+                "last_success": c.last_success.and_then(|ts| ts.format(&Rfc3339).ok()),
+                "last_success_age_seconds": c.last_success.map(|ts| (now - ts).whole_seconds().max(0)),
+                "consecutive_failures": c.consecutive_failures,
+                "total_failures": c.total_failures,
+                "last_error": c.last_error,
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(json!({
+        "collectors": collectors,
+        "timestamp": now.to_string(),
+    }))
+}
+
 fn calculate_storage_usage(data_dir: &str) -> u64 {
     match std::fs::read_dir(data_dir) {
         Ok(entries) => entries
@@ -52,3 +168,44 @@ fn calculate_storage_usage(data_dir: &str) -> u64 {
         Err(_) => 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Anomaly, AnomalyKind, SecurityEvent, SecurityEventKind};
+
+    fn anomaly(ts: OffsetDateTime, severity: AnomalySeverity, kind: AnomalyKind) -> Event {
+        Event::Anomaly(Anomaly { ts, severity, kind, message: String::new() })
+    }
+
+    #[test]
+    fn test_summarize_recent_groups_last_hour() {
+        let now = OffsetDateTime::from_unix_timestamp(1_767_600_000).unwrap();
+        let minutes_ago = |m: i64| now - time::Duration::minutes(m);
+        let events = vec![
+            anomaly(minutes_ago(90), AnomalySeverity::Warning, AnomalyKind::CpuSpike), // Too old
+            anomaly(minutes_ago(30), AnomalySeverity::Warning, AnomalyKind::CpuSpike),
+            anomaly(minutes_ago(20), AnomalySeverity::Warning, AnomalyKind::CpuSpike),
+            anomaly(minutes_ago(40), AnomalySeverity::Critical, AnomalyKind::DiskFull),
+            anomaly(minutes_ago(5), AnomalySeverity::Info, AnomalyKind::SwapUsage),
+            Event::SecurityEvent(SecurityEvent {
+                ts: minutes_ago(10),
+                kind: SecurityEventKind::SshLoginFailure,
+                user: "root".to_string(),
+                source_ip: None,
+                message: String::new(),
+            }),
+        ];
+
+        let recent = summarize_recent(&events, now, minutes_ago(60));
+        assert_eq!(recent.anomalies["Warning"]["CpuSpike"], 2);
+        assert_eq!(recent.anomalies["Critical"]["DiskFull"], 1);
+        assert_eq!(recent.anomalies["Info"]["SwapUsage"], 1);
+        assert_eq!(recent.security_events["SshLoginFailure"], 1);
+        assert_eq!(recent.last_critical_anomaly, Some(minutes_ago(40)));
+
+        // The Critical anomaly is outside a 15 minute check window
+        let recent = summarize_recent(&events, now, minutes_ago(15));
+        assert_eq!(recent.last_critical_anomaly, None);
+    }
+}
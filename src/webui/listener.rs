@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+
+use crate::config::ServerConfig;
+
+// Only the owner and group can connect to the socket
+const UNIX_SOCKET_MODE: u32 = 0o660;
+
+/// Socket the web UI is served on. Bound before privileges are dropped, since the port may be
+/// privileged or the socket directory root-owned.
+pub enum WebListener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl WebListener {
+    /// Bind `unix_socket_path` if set, otherwise `bind_address` on `port`
+    pub fn bind(config: &ServerConfig, port: u16) -> Result<Self> {
+        if let Some(path) = &config.unix_socket_path {
+            return bind_unix(Path::new(path));
+        }
+        let ip: IpAddr = config
+            .bind_address
+            .parse()
+            .with_context(|| format!("Invalid bind_address {:?}", config.bind_address))?;
+        let addr = SocketAddr::new(ip, port);
+        let listener = TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+        Ok(WebListener::Tcp(listener))
+    }
+
+    /// Where clients connect, e.g. "https://127.0.0.1:8080" or "unix:/run/black-box.sock"
+    pub fn describe(&self, scheme: &str) -> String {
+        match self {
+            WebListener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => format!("{}://{}", scheme, addr),
+                Err(_) => format!("{}://(unknown address)", scheme),
+            },
+            WebListener::Unix(_, path) => format!("unix:{}", path.display()),
+        }
+    }
+}
+
+fn bind_unix(path: &Path) -> Result<WebListener> {
+    // A socket left by a previous run would make bind fail. Anything else there is left alone.
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("unix_socket_path {} exists and is not a socket", path.display());
+        }
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove old socket {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(path).with_context(|| format!("Failed to listen on {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(UNIX_SOCKET_MODE))
+        .with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+    Ok(WebListener::Unix(listener, path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_bind_address_and_unix_socket() {
+        let mut config = Config::test_config().server;
+        config.bind_address = "127.0.0.1".to_string();
+        let listener = WebListener::bind(&config, 0).unwrap();
+        assert!(listener.describe("http").starts_with("http://127.0.0.1:"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("web.sock");
+        config.unix_socket_path = Some(path.to_string_lossy().to_string());
+        let listener = WebListener::bind(&config, 0).unwrap();
+        assert_eq!(listener.describe("http"), format!("unix:{}", path.display()));
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, UNIX_SOCKET_MODE);
+
+        // The stale socket from a previous run is replaced, a regular file isn't
+        drop(listener);
+        assert!(WebListener::bind(&config, 0).is_ok());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&path, "not a socket").unwrap();
+        assert!(WebListener::bind(&config, 0).is_err());
+    }
+}
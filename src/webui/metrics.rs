@@ -0,0 +1,285 @@
+use actix_web::{web, HttpResponse};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::broadcast::EventBroadcaster;
+use crate::event::{Event, SystemMetrics};
+
+// Latest metrics and event counters for the Prometheus /metrics endpoint
+#[derive(Default)]
+pub struct MetricsState {
+    latest: Option<SystemMetrics>,
+    anomalies_by_kind: BTreeMap<String, u64>,
+    security_events_by_kind: BTreeMap<String, u64>,
+}
+
+pub type SharedMetricsState = Arc<Mutex<MetricsState>>;
+
+impl MetricsState {
+    pub fn record(&mut self, event: &Event) {
+        match event {
+            Event::SystemMetrics(m) => {
+                let mut m = m.clone();
+                // Static fields are only sent periodically, keep the last known values
+                if let Some(prev) = &self.latest {
+                    m.mem_total_bytes = m.mem_total_bytes.or(prev.mem_total_bytes);
+                    m.swap_total_bytes = m.swap_total_bytes.or(prev.swap_total_bytes);
+                    m.disk_total_bytes = m.disk_total_bytes.or(prev.disk_total_bytes);
+                }
+                self.latest = Some(m);
+            }
+            Event::Anomaly(a) => {
+                *self.anomalies_by_kind.entry(format!("{:?}", a.kind)).or_insert(0) += 1;
+            }
+            Event::SecurityEvent(s) => {
+                *self.security_events_by_kind.entry(format!("{:?}", s.kind)).or_insert(0) += 1;
+            }
+            _ => {}
+        }
+    }
+
+    // Whether a SystemMetrics sample has come in yet
+    pub fn has_metrics(&self) -> bool {
+        self.latest.is_some()
+    }
+
+    // Headline numbers of the newest sample, for `status --format nagios`. Empty before the first.
+    pub fn latest_status(&self) -> serde_json::Value {
+        match &self.latest {
+            Some(m) => serde_json::json!({
+                "timestamp": m.ts.unix_timestamp_nanos() / 1_000_000,
+                "cpu": m.cpu_usage_percent,
+                "mem": m.mem_usage_percent,
+                "disk": m.disk_usage_percent,
+            }),
+            None => serde_json::json!({}),
+        }
+    }
+
+    // Render in Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(m) = &self.latest {
+            gauge(&mut out, "blackbox_system_uptime_seconds", "System uptime in seconds", &[("", m.system_uptime_seconds as f64)]);
+            gauge(&mut out, "blackbox_cpu_usage_percent", "Total CPU usage", &[("", m.cpu_usage_percent as f64)]);
+
+            let cores: Vec<(String, f64)> = m.per_core_usage.iter().enumerate()
+                .map(|(i, u)| (format!("core=\"{}\"", i), *u as f64))
+                .collect();
+            gauge_labeled(&mut out, "blackbox_per_core_usage", "Per-core CPU usage percent", &cores);
+
+            if let Some(freqs) = &m.per_core_freq_mhz {
+                let cores: Vec<(String, f64)> = freqs.iter().enumerate()
+                    .map(|(i, mhz)| (format!("core=\"{}\"", i), *mhz as f64))
+                    .collect();
+                gauge_labeled(&mut out, "blackbox_per_core_freq_mhz", "Per-core CPU frequency in MHz", &cores);
+            }
+
+            gauge(&mut out, "blackbox_mem_used_bytes", "Memory in use", &[("", m.mem_used_bytes as f64)]);
+            if let Some(total) = m.mem_total_bytes {
+                gauge(&mut out, "blackbox_mem_total_bytes", "Total memory", &[("", total as f64)]);
+            }
+            gauge(&mut out, "blackbox_mem_usage_percent", "Memory usage", &[("", m.mem_usage_percent as f64)]);
+            let breakdown = [
+                ("blackbox_mem_available_bytes", "Memory available without swapping", m.mem_available_bytes),
+                ("blackbox_mem_cached_bytes", "Page cache", m.mem_cached_bytes),
+                ("blackbox_mem_buffers_bytes", "Block device buffers", m.mem_buffers_bytes),
+                ("blackbox_mem_dirty_bytes", "Memory waiting to be written back to disk", m.mem_dirty_bytes),
+                ("blackbox_mem_writeback_bytes", "Memory being written back to disk", m.mem_writeback_bytes),
+                ("blackbox_hugepages_total", "Huge pages in the pool", m.hugepages_total),
+                ("blackbox_hugepages_free", "Huge pages not allocated", m.hugepages_free),
+            ];
+            for (name, help, value) in breakdown {
+                if let Some(value) = value {
+                    gauge(&mut out, name, help, &[("", value as f64)]);
+                }
+            }
+            gauge(&mut out, "blackbox_swap_used_bytes", "Swap in use", &[("", m.swap_used_bytes as f64)]);
+            if let Some(total) = m.swap_total_bytes {
+                gauge(&mut out, "blackbox_swap_total_bytes", "Total swap", &[("", total as f64)]);
+            }
+            gauge(&mut out, "blackbox_load_avg_1m", "1 minute load average", &[("", m.load_avg_1m as f64)]);
+            gauge(&mut out, "blackbox_load_avg_5m", "5 minute load average", &[("", m.load_avg_5m as f64)]);
+            gauge(&mut out, "blackbox_load_avg_15m", "15 minute load average", &[("", m.load_avg_15m as f64)]);
+
+            gauge(&mut out, "blackbox_disk_used_bytes", "Root filesystem space in use", &[("", m.disk_used_bytes as f64)]);
+            if let Some(total) = m.disk_total_bytes {
+                gauge(&mut out, "blackbox_disk_total_bytes", "Root filesystem size", &[("", total as f64)]);
+            }
+            let reads: Vec<(String, f64)> = m.per_disk_metrics.iter()
+                .map(|d| (format!("device=\"{}\"", escape_label(&d.device_name)), d.read_bytes_per_sec as f64))
+                .collect();
+            gauge_labeled(&mut out, "blackbox_disk_read_bytes_per_sec", "Disk read throughput", &reads);
+            let writes: Vec<(String, f64)> = m.per_disk_metrics.iter()
+                .map(|d| (format!("device=\"{}\"", escape_label(&d.device_name)), d.write_bytes_per_sec as f64))
+                .collect();
+            gauge_labeled(&mut out, "blackbox_disk_write_bytes_per_sec", "Disk write throughput", &writes);
+
+            gauge(&mut out, "blackbox_net_recv_bytes_per_sec", "Network receive rate (all interfaces)", &[("", m.net_recv_bytes_per_sec as f64)]);
+            gauge(&mut out, "blackbox_net_send_bytes_per_sec", "Network send rate (all interfaces)", &[("", m.net_send_bytes_per_sec as f64)]);
+            let iface_recv: Vec<(String, f64)> = m.per_interface.iter()
+                .map(|i| (format!("interface=\"{}\"", escape_label(&i.interface_name)), i.recv_bytes_per_sec as f64))
+                .collect();
+            gauge_labeled(&mut out, "blackbox_interface_recv_bytes_per_sec", "Network receive rate per interface", &iface_recv);
+            let iface_send: Vec<(String, f64)> = m.per_interface.iter()
+                .map(|i| (format!("interface=\"{}\"", escape_label(&i.interface_name)), i.send_bytes_per_sec as f64))
+                .collect();
+            gauge_labeled(&mut out, "blackbox_interface_send_bytes_per_sec", "Network send rate per interface", &iface_send);
+
+            gauge(&mut out, "blackbox_tcp_connections", "Established TCP connections", &[("", m.tcp_connections as f64)]);
+            gauge(&mut out, "blackbox_tcp_time_wait", "TCP connections in TIME_WAIT", &[("", m.tcp_time_wait as f64)]);
+            gauge(&mut out, "blackbox_context_switches_per_sec", "Context switches per second", &[("", m.context_switches_per_sec as f64)]);
+
+            let mut temps: Vec<(String, f64)> = Vec::new();
+            let named = [
+                ("cpu", m.temps.cpu_temp_celsius),
+                ("gpu", m.temps.gpu_temp_celsius.or(m.gpu.gpu_temp_celsius)),
+                ("motherboard", m.temps.motherboard_temp_celsius),
+            ];
+            for (sensor, value) in named {
+                if let Some(t) = value {
+                    temps.push((format!("sensor=\"{}\"", sensor), t as f64));
+                }
+            }
+            for (i, t) in m.temps.per_core_temps.iter().enumerate() {
+                if let Some(t) = t {
+                    temps.push((format!("sensor=\"core{}\"", i), *t as f64));
+                }
+            }
+            for d in &m.per_disk_metrics {
+                if let Some(t) = d.temp_celsius {
+                    temps.push((format!("sensor=\"{}\"", escape_label(&d.device_name)), t as f64));
+                }
+            }
+            gauge_labeled(&mut out, "blackbox_temp_celsius", "Temperature readings", &temps);
+        }
+
+        counter(&mut out, "blackbox_anomalies_total", "Anomalies seen since the server started", &self.anomalies_by_kind);
+        counter(&mut out, "blackbox_security_events_total", "Security events seen since the server started", &self.security_events_by_kind);
+
+        out
+    }
+}
+
+// Keep the metrics state up to date from the broadcaster
+pub async fn run_metrics_collector(broadcaster: Arc<EventBroadcaster>, state: SharedMetricsState) {
+    let mut rx = broadcaster.subscribe();
+    loop {
+        match rx.recv().await {
+            Ok(event) => {
+                if let Ok(mut state) = state.lock() {
+                    state.record(&event);
+                }
+            }
+            // Missed some events under load, carry on from the newest
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+pub async fn prometheus_metrics(state: web::Data<SharedMetricsState>) -> HttpResponse {
+    let body = match state.lock() {
+        // A scrape before the first sample would record every gauge as missing
+        Ok(state) if !state.has_metrics() => {
+            return HttpResponse::ServiceUnavailable().body("No metrics collected yet");
+        }
+        Ok(state) => state.render(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(body)
+}
+
+// The sample this server last collected, from memory rather than the recording, so a monitoring
+// check polling it costs nothing and is never behind
+pub async fn api_latest(state: web::Data<SharedMetricsState>) -> HttpResponse {
+    match state.lock() {
+        Ok(state) => HttpResponse::Ok().json(state.latest_status()),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, values: &[(&str, f64)]) {
+    let values: Vec<(String, f64)> = values.iter().map(|(l, v)| (l.to_string(), *v)).collect();
+    gauge_labeled(out, name, help, &values);
+}
+
+fn gauge_labeled(out: &mut String, name: &str, help: &str, values: &[(String, f64)]) {
+    if values.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (labels, value) in values {
+        write_sample(out, name, labels, *value);
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, by_kind: &BTreeMap<String, u64>) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (kind, count) in by_kind {
+        write_sample(out, name, &format!("kind=\"{}\"", escape_label(kind)), *count as f64);
+    }
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &str, value: f64) {
+    if labels.is_empty() {
+        let _ = writeln!(out, "{} {}", name, value);
+    } else {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Anomaly, AnomalyKind, AnomalySeverity};
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_anomaly_counters_by_kind() {
+        let mut state = MetricsState::default();
+        for kind in [AnomalyKind::CpuSpike, AnomalyKind::CpuSpike, AnomalyKind::DiskFull] {
+            state.record(&Event::Anomaly(Anomaly {
+                ts: OffsetDateTime::now_utc(),
+                severity: AnomalySeverity::Warning,
+                kind,
+                message: String::new(),
+            }));
+        }
+
+        let out = state.render();
+        assert!(out.contains("# TYPE blackbox_anomalies_total counter"));
+        assert!(out.contains("blackbox_anomalies_total{kind=\"CpuSpike\"} 2"));
+        assert!(out.contains("blackbox_anomalies_total{kind=\"DiskFull\"} 1"));
+        // No metrics seen yet, so no gauges
+        assert!(!state.has_metrics());
+        assert!(!out.contains("blackbox_cpu_usage_percent"));
+
+        assert_eq!(state.latest_status(), serde_json::json!({}));
+
+        state.record(&Event::SystemMetrics(crate::event::sample_metrics()));
+        assert!(state.has_metrics());
+        assert!(state.render().contains("# TYPE blackbox_cpu_usage_percent gauge"));
+        let status = state.latest_status();
+        assert_eq!(status["cpu"], serde_json::json!(crate::event::sample_metrics().cpu_usage_percent));
+        assert!(status["mem"].is_number() && status["disk"].is_number());
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("sda"), "sda");
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -1,8 +1,22 @@
+mod actions;
+mod annotations;
 mod auth;
+mod export;
 mod health;
+mod listener;
+mod metrics;
 mod playback;
+mod query;
+mod resume;
 mod routes;
 mod server;
+mod stream;
+mod subscription;
+mod tls;
 mod websocket;
 
-pub use server::start_server;
+pub use health::RemoteStatus;
+pub use listener::WebListener;
+pub use server::{start_server, CollectorLink};
+pub use tls::load_tls_config;
+pub use websocket::event_to_json;
@@ -9,26 +9,49 @@
 //   - Returns all events between timestamps S and E (up to L total events)
 //   - Useful for export, analysis, or when you need events in a specific timeframe
 //   - Returns whatever events exist in that range (may be less than limit)
+//   - Add &step=N for fast playback: SystemMetrics are averaged into one per N seconds
+//
+// Baselines (/api/baseline?timestamp=T&window=W) compare the W seconds around T with the
+// same window a day and a week earlier.
 
 use actix_web::{web, HttpResponse};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use time::OffsetDateTime;
 
+use crate::config::Config;
+use crate::downsample;
 use crate::event::Metadata;
-use crate::event::Event;
+use crate::event::{Event, SystemEventKind, SystemMetrics};
 use crate::indexed_reader::IndexedReader;
 use crate::reader::LogReader;
+use crate::storage::find_segment_files;
+use super::websocket::{disks_json, filesystems_json, interfaces_json};
 
 const MIN_HISTORY_LOOKBACK_SECS: i64 = 600;
 const HISTORY_LOOKBACK_MULTIPLIER_SECS: i64 = 10;
+const BASELINE_DEFAULT_WINDOW_SECS: i64 = 600;
+// Caps how much each baseline request reads: three windows of at most an hour
+const BASELINE_MAX_WINDOW_SECS: i64 = 3600;
+const BASELINE_OFFSETS: [(&str, i64); 3] = [("current", 0), ("yesterday", 86_400), ("last_week", 7 * 86_400)];
 
 struct PlaybackResult {
     events: Vec<Event>,
+    windows: Vec<MetricsWindow>, // Aggregated SystemMetrics in step mode
     metadata: serde_json::Value,
     fallback: bool,
 }
 
+/// Stands in for all the SystemMetrics samples in one step window of fast playback
+struct MetricsWindow {
+    metrics: SystemMetrics,
+    samples: usize,
+    events: usize, // Other events recorded in the window
+    cpu_max: f32,
+    mem_max: f32,
+}
+
 #[derive(Deserialize)]
 pub struct PlaybackQuery {
     // Mode 1: Get last N SystemMetrics before a timestamp
@@ -48,6 +71,32 @@ pub struct PlaybackQuery {
     end_timestamp: Option<i64>,    // Unix seconds - range end
     #[serde(rename = "limit")]
     limit: Option<usize>,          // Max total events to return
+    #[serde(rename = "step")]
+    step: Option<i64>,             // Seconds per aggregated SystemMetrics (fast playback)
+}
+
+/// Mean and 95th percentile of one metric across a window
+#[derive(Debug, PartialEq)]
+struct MetricStats {
+    avg: f64,
+    p95: f64,
+}
+
+/// Summary of the SystemMetrics samples in one baseline window
+struct WindowStats {
+    samples: usize,
+    cpu: MetricStats,
+    mem: MetricStats,
+    net_recv: MetricStats,
+    net_send: MetricStats,
+    disk_read: MetricStats,
+    disk_write: MetricStats,
+}
+
+#[derive(Deserialize)]
+pub struct BaselineQuery {
+    timestamp: i64,      // Unix seconds - centre of the window
+    window: Option<i64>, // Window length in seconds (default 600, max 3600)
 }
 
 #[derive(Deserialize)]
@@ -127,10 +176,21 @@ async fn fetch_events_by_count(
 /// Get time range metadata
 pub async fn api_playback_info(
     reader: web::Data<Arc<IndexedReader>>,
+    config: web::Data<Config>,
+    data_dir: web::Data<String>,
 ) -> HttpResponse {
     // Refresh index to pick up any new segments written since server start
     let _ = reader.refresh();
 
+    // Retention limits and current usage so the timeline can show how far back data can go
+    let storage_bytes_used: u64 = find_segment_files(std::path::Path::new(data_dir.get_ref()))
+        .iter()
+        .filter_map(|(_, path)| std::fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum();
+    let storage_bytes_max = config.server.storage_limit_bytes();
+    let max_retention_days = config.server.max_retention_days;
+
     if let Some((first_ns, last_ns)) = reader.get_time_range() {
         let first_secs = (first_ns / 1_000_000_000) as i64;
         let last_secs = (last_ns / 1_000_000_000) as i64;
@@ -145,6 +205,10 @@ pub async fn api_playback_info(
             "last_timestamp_iso": last_dt.map(|dt| dt.to_string()),
             "segment_count": reader.segment_count(),
             "estimated_event_count": reader.estimate_event_count(),
+            "storage_bytes_used": storage_bytes_used,
+            "storage_bytes_max": storage_bytes_max,
+            "max_retention_days": max_retention_days,
+            "boots": boot_boundaries_json(reader.dir()),
         }))
     } else {
         HttpResponse::Ok().json(serde_json::json!({
@@ -152,10 +216,31 @@ pub async fn api_playback_info(
             "last_timestamp": null,
             "segment_count": 0,
             "estimated_event_count": 0,
+            "storage_bytes_used": storage_bytes_used,
+            "storage_bytes_max": storage_bytes_max,
+            "max_retention_days": max_retention_days,
+            "boots": boot_boundaries_json(reader.dir()),
         }))
     }
 }
 
+/// Reboots as gaps in the recording: it stopped at `stopped` and the machine came back at `timestamp`
+fn boot_boundaries_json(dir: &std::path::Path) -> Vec<serde_json::Value> {
+    crate::summary::load_boots(dir)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|boot| match &boot.kind {
+            SystemEventKind::Rebooted { boot_time, last_event, clean_shutdown } => Some(serde_json::json!({
+                "timestamp": boot_time.unix_timestamp(),
+                "stopped": last_event.unix_timestamp(),
+                "clean_shutdown": clean_shutdown,
+                "reason": boot.message,
+            })),
+            SystemEventKind::Shutdown | SystemEventKind::ConfigReloaded { .. } => None,
+        })
+        .collect()
+}
+
 /// Get event density timeline (events per minute) for visualization
 pub async fn api_timeline(
     reader: web::Data<Arc<IndexedReader>>,
@@ -163,108 +248,104 @@ pub async fn api_timeline(
     // Refresh index to pick up any new segments written since server start
     let _ = reader.refresh();
 
-    if let Some((first_ns, last_ns)) = reader.get_time_range() {
-        // Read all events (this might be expensive for very large datasets)
-        match reader.read_time_range(Some(first_ns), Some(last_ns)) {
-            Ok(events) => {
-                // Bucket events by minute
-                let first_minute = (first_ns / 60_000_000_000) as i64; // Convert ns to minutes
-                let last_minute = (last_ns / 60_000_000_000) as i64;
-
-                let mut buckets = std::collections::HashMap::new();
-                let mut cpu_buckets: std::collections::HashMap<i64, Vec<f32>> = std::collections::HashMap::new();
-                let mut mem_buckets: std::collections::HashMap<i64, Vec<f32>> = std::collections::HashMap::new();
-
-                // Count events per minute and collect CPU/memory metrics
-                for event in events.iter() {
-                    let ts_ns = event.timestamp().unix_timestamp_nanos();
-                    let minute = (ts_ns / 60_000_000_000) as i64;
-                    *buckets.entry(minute).or_insert(0u32) += 1;
-
-                    // Collect CPU and memory usage from SystemMetrics events
-                    if let Event::SystemMetrics(m) = event {
-                        cpu_buckets.entry(minute).or_insert_with(Vec::new).push(m.cpu_usage_percent);
-                        mem_buckets.entry(minute).or_insert_with(Vec::new).push(m.mem_usage_percent);
-                    }
-                }
+    let Some((first_ns, last_ns)) = reader.get_time_range() else {
+        return HttpResponse::Ok().json(serde_json::json!({
+            "timeline": [],
+            "annotations": [],
+            "boots": [],
+            "first_timestamp": null,
+            "last_timestamp": null,
+        }));
+    };
 
-                // Build timeline array with all minutes (including empty ones for smooth visualization)
-                let mut timeline = Vec::new();
-
-                // Exclude the current incomplete minute to avoid misleading drop-off at the end
-                let now_minute = (OffsetDateTime::now_utc().unix_timestamp() / 60) as i64;
-                let effective_last_minute = if last_minute >= now_minute {
-                    // Exclude current minute if it's incomplete
-                    now_minute - 1
-                } else {
-                    last_minute
-                };
-
-                let total_minutes = (effective_last_minute - first_minute + 1) as usize;
-
-                // If we have too many minutes (>500), downsample to keep response size reasonable
-                let step = if total_minutes > 500 {
-                    (total_minutes / 500).max(1)
-                } else {
-                    1
-                };
-
-                for minute in (first_minute..=effective_last_minute).step_by(step) {
-                    // When downsampling, aggregate counts for the step range
-                    let mut count = 0u32;
-                    let mut cpu_values = Vec::new();
-                    let mut mem_values = Vec::new();
-
-                    for m in minute..(minute + step as i64).min(last_minute + 1) {
-                        count += buckets.get(&m).copied().unwrap_or(0);
-                        if let Some(cpus) = cpu_buckets.get(&m) {
-                            cpu_values.extend_from_slice(cpus);
-                        }
-                        if let Some(mems) = mem_buckets.get(&m) {
-                            mem_values.extend_from_slice(mems);
-                        }
-                    }
+    // Served from the per-minute summary the recorder maintains, not the segments
+    let summaries = match crate::summary::load(reader.dir()) {
+        Ok(summaries) => summaries,
+        Err(e) => {
+            eprintln!("Failed to read timeline: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to read timeline"
+            }));
+        }
+    };
 
-                    // Calculate averages
-                    let cpu_avg = if !cpu_values.is_empty() {
-                        Some(cpu_values.iter().sum::<f32>() / cpu_values.len() as f32)
-                    } else {
-                        None
-                    };
-                    let mem_avg = if !mem_values.is_empty() {
-                        Some(mem_values.iter().sum::<f32>() / mem_values.len() as f32)
-                    } else {
-                        None
-                    };
-
-                    timeline.push(serde_json::json!({
-                        "timestamp": minute * 60, // Convert back to seconds
-                        "count": count,
-                        "cpu": cpu_avg,
-                        "mem": mem_avg,
-                    }));
-                }
+    let first_minute = (first_ns / 60_000_000_000) as i64; // Convert ns to minutes
+    let last_minute = (last_ns / 60_000_000_000) as i64;
+    let buckets: std::collections::HashMap<i64, crate::summary::MinuteSummary> = summaries
+        .into_iter()
+        .filter(|s| s.minute >= first_minute && s.minute <= last_minute)
+        .map(|s| (s.minute, s))
+        .collect();
 
-                HttpResponse::Ok().json(serde_json::json!({
-                    "timeline": timeline,
-                    "first_timestamp": (first_ns / 1_000_000_000) as i64,
-                    "last_timestamp": effective_last_minute * 60, // Use effective last minute (excluding incomplete)
-                }))
-            }
-            Err(e) => {
-                eprintln!("Failed to read timeline: {}", e);
-                HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to read timeline"
-                }))
+    // Build timeline array with all minutes (including empty ones for smooth visualization)
+    let mut timeline = Vec::new();
+
+    // Exclude the current incomplete minute to avoid misleading drop-off at the end
+    let now_minute = OffsetDateTime::now_utc().unix_timestamp() / 60;
+    let effective_last_minute = if last_minute >= now_minute {
+        // Exclude current minute if it's incomplete
+        now_minute - 1
+    } else {
+        last_minute
+    };
+
+    let total_minutes = (effective_last_minute - first_minute + 1).max(0) as usize;
+
+    // If we have too many minutes (>500), downsample to keep response size reasonable
+    let step = if total_minutes > 500 {
+        (total_minutes / 500).max(1)
+    } else {
+        1
+    };
+
+    for minute in (first_minute..=effective_last_minute).step_by(step) {
+        // When downsampling, aggregate the minutes in the step range
+        let mut count = 0u32;
+        let mut metrics_count = 0u32;
+        let (mut cpu_sum, mut cpu_max, mut mem_sum, mut mem_max) = (0f32, 0f32, 0f32, 0f32);
+
+        for m in minute..(minute + step as i64).min(last_minute + 1) {
+            if let Some(summary) = buckets.get(&m) {
+                count += summary.count;
+                metrics_count += summary.metrics_count;
+                cpu_sum += summary.cpu_sum;
+                cpu_max = cpu_max.max(summary.cpu_max);
+                mem_sum += summary.mem_sum;
+                mem_max = mem_max.max(summary.mem_max);
             }
         }
-    } else {
-        HttpResponse::Ok().json(serde_json::json!({
-            "timeline": [],
-            "first_timestamp": null,
-            "last_timestamp": null,
-        }))
+
+        let has_metrics = metrics_count > 0;
+        timeline.push(serde_json::json!({
+            "timestamp": minute * 60, // Convert back to seconds
+            "count": count,
+            "cpu": has_metrics.then(|| cpu_sum / metrics_count as f32),
+            "cpu_max": has_metrics.then_some(cpu_max),
+            "mem": has_metrics.then(|| mem_sum / metrics_count as f32),
+            "mem_max": has_metrics.then_some(mem_max),
+        }));
     }
+
+    // Markers for notes left by operators
+    let mut annotations = crate::summary::load_annotations(reader.dir()).unwrap_or_default();
+    annotations.sort_by_key(|a| a.ts);
+    let annotations: Vec<serde_json::Value> = annotations
+        .iter()
+        .map(|a| serde_json::json!({
+            "timestamp": a.ts.unix_timestamp(),
+            "author": a.author,
+            "text": a.text,
+            "tags": a.tags,
+        }))
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "timeline": timeline,
+        "annotations": annotations,
+        "boots": boot_boundaries_json(reader.dir()),
+        "first_timestamp": (first_ns / 1_000_000_000) as i64,
+        "last_timestamp": effective_last_minute * 60, // Use effective last minute (excluding incomplete)
+    }))
 }
 
 /// Get events for playback
@@ -273,6 +354,7 @@ pub async fn api_timeline(
 /// 1. Count mode: ?timestamp=T&count=N - Get last N SystemMetrics before timestamp T
 ///    Add &before=true to get events BEFORE timestamp (for progressive loading)
 /// 2. Range mode: ?start=S&end=E&limit=L - Get all events between S and E (up to L events)
+///    Add &step=N to get one averaged SystemMetrics per N seconds instead of every sample
 pub async fn api_playback_events(
     log_reader: web::Data<LogReader>,
     indexed_reader: web::Data<Arc<IndexedReader>>,
@@ -315,6 +397,7 @@ pub async fn api_playback_jump(
         start_timestamp: Some(timestamp),
         end_timestamp: Some(timestamp + forward_seconds),
         limit: Some(2000),
+        step: None,
     };
 
     let forward_result = match collect_events_by_range(&indexed_reader, &forward_query) {
@@ -333,6 +416,98 @@ pub async fn api_playback_jump(
     }))
 }
 
+/// Compare a window with the same window 24 hours and 7 days earlier.
+/// Windows with no recorded metrics (e.g. already overwritten by the ring buffer) are null.
+pub async fn api_baseline(
+    indexed_reader: web::Data<Arc<IndexedReader>>,
+    query: web::Query<BaselineQuery>,
+) -> HttpResponse {
+    let _ = indexed_reader.refresh();
+
+    let window = query
+        .window
+        .unwrap_or(BASELINE_DEFAULT_WINDOW_SECS)
+        .clamp(60, BASELINE_MAX_WINDOW_SECS);
+    let start = query.timestamp - window / 2;
+
+    let mut response = serde_json::json!({
+        "timestamp": query.timestamp,
+        "window": window,
+    });
+    for (name, offset) in BASELINE_OFFSETS {
+        let (window_start, window_end) = (start - offset, start - offset + window);
+        let stats = read_window_stats(&indexed_reader, window_start, window_end);
+        response[name] = window_stats_json(stats.as_ref(), window_start, window_end);
+    }
+
+    HttpResponse::Ok().json(response)
+}
+
+/// None when the window holds no metrics or its segments are gone
+fn read_window_stats(indexed_reader: &IndexedReader, start: i64, end: i64) -> Option<WindowStats> {
+    let (start_ns, end_ns) = ((start as i128) * 1_000_000_000, (end as i128) * 1_000_000_000);
+    let (first_ns, last_ns) = indexed_reader.get_time_range()?;
+    if end_ns < first_ns || start_ns > last_ns {
+        return None;
+    }
+
+    let samples: Vec<SystemMetrics> = indexed_reader
+        .read_time_range(Some(start_ns), Some(end_ns))
+        .ok()?
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::SystemMetrics(m) => Some(m),
+            _ => None,
+        })
+        .collect();
+    window_stats(&samples)
+}
+
+/// Average and p95 of the levels and rates most worth comparing between windows
+fn window_stats(samples: &[SystemMetrics]) -> Option<WindowStats> {
+    if samples.is_empty() {
+        return None;
+    }
+    let stats = |f: fn(&SystemMetrics) -> f64| {
+        let mut values: Vec<f64> = samples.iter().map(f).collect();
+        values.sort_by(f64::total_cmp);
+        // Nearest-rank percentile
+        let rank = ((values.len() as f64) * 0.95).ceil() as usize;
+        MetricStats {
+            avg: values.iter().sum::<f64>() / values.len() as f64,
+            p95: values[rank.saturating_sub(1)],
+        }
+    };
+
+    Some(WindowStats {
+        samples: samples.len(),
+        cpu: stats(|m| m.cpu_usage_percent as f64),
+        mem: stats(|m| m.mem_usage_percent as f64),
+        net_recv: stats(|m| m.net_recv_bytes_per_sec as f64),
+        net_send: stats(|m| m.net_send_bytes_per_sec as f64),
+        disk_read: stats(|m| m.disk_read_bytes_per_sec as f64),
+        disk_write: stats(|m| m.disk_write_bytes_per_sec as f64),
+    })
+}
+
+fn window_stats_json(stats: Option<&WindowStats>, start: i64, end: i64) -> serde_json::Value {
+    let Some(stats) = stats else {
+        return serde_json::Value::Null;
+    };
+    let metric = |m: &MetricStats| serde_json::json!({ "avg": m.avg, "p95": m.p95 });
+    serde_json::json!({
+        "start": start,
+        "end": end,
+        "samples": stats.samples,
+        "cpu": metric(&stats.cpu),
+        "mem": metric(&stats.mem),
+        "net_recv": metric(&stats.net_recv),
+        "net_send": metric(&stats.net_send),
+        "disk_read": metric(&stats.disk_read),
+        "disk_write": metric(&stats.disk_write),
+    })
+}
+
 /// Mode 2: Fetch all events in a time range (start to end)
 async fn fetch_events_by_range(
     _log_reader: &LogReader,
@@ -414,6 +589,7 @@ fn collect_events_by_count(
 
     Ok(PlaybackResult {
         events: final_events,
+        windows: Vec::new(),
         metadata,
         fallback: false,
     })
@@ -445,6 +621,15 @@ fn collect_events_by_range(
         serde_json::json!({})
     };
 
+    let windows = match query.step {
+        Some(step) if step > 1 => {
+            let (windows, others) = aggregate_by_step(events, step);
+            events = others;
+            windows
+        }
+        _ => Vec::new(),
+    };
+
     if let Some(limit) = query.limit {
         if events.len() > limit {
             events = events.into_iter().rev().take(limit).rev().collect();
@@ -453,18 +638,81 @@ fn collect_events_by_range(
 
     Ok(PlaybackResult {
         events,
+        windows,
         metadata,
         fallback: used_fallback,
     })
 }
 
+/// Split events into `step_secs` windows, replacing each window's SystemMetrics with one
+/// aggregate. Other events are kept as they are, apart from ProcessSnapshots where only
+/// the latest in each window is kept.
+fn aggregate_by_step(events: Vec<Event>, step_secs: i64) -> (Vec<MetricsWindow>, Vec<Event>) {
+    let mut samples: BTreeMap<i64, Vec<SystemMetrics>> = BTreeMap::new();
+    let mut event_counts: BTreeMap<i64, usize> = BTreeMap::new();
+    let mut snapshots = BTreeMap::new();
+    let mut others = Vec::new();
+
+    for event in events {
+        let window = event.timestamp().unix_timestamp().div_euclid(step_secs);
+        match event {
+            Event::SystemMetrics(m) => samples.entry(window).or_default().push(m),
+            Event::ProcessSnapshot(_) => {
+                snapshots.insert(window, event);
+            }
+            event => {
+                *event_counts.entry(window).or_default() += 1;
+                others.push(event);
+            }
+        }
+    }
+
+    others.extend(snapshots.into_values());
+    others.sort_by_key(|e| e.timestamp().unix_timestamp_nanos());
+
+    let windows = samples
+        .into_iter()
+        .filter_map(|(window, samples)| {
+            aggregate_metrics(&samples, event_counts.get(&window).copied().unwrap_or(0))
+        })
+        .collect();
+    (windows, others)
+}
+
+/// Average one window of samples, see downsample::roll_up
+fn aggregate_metrics(samples: &[SystemMetrics], events: usize) -> Option<MetricsWindow> {
+    let metrics = downsample::roll_up(samples)?;
+    let rollup = metrics.rollup?;
+    Some(MetricsWindow {
+        samples: rollup.samples as usize,
+        events,
+        cpu_max: rollup.cpu_max_percent,
+        mem_max: rollup.mem_max_percent,
+        metrics,
+    })
+}
+
 fn playback_result_json(result: &PlaybackResult) -> serde_json::Value {
-    let formatted_events: Vec<serde_json::Value> = result
+    let mut formatted_events: Vec<serde_json::Value> = result
         .events
         .iter()
         .map(format_event_for_api)
         .collect();
 
+    if !result.windows.is_empty() {
+        formatted_events.extend(result.windows.iter().map(|w| {
+            let mut json = format_event_for_api(&Event::SystemMetrics(w.metrics.clone()));
+            json["window"] = serde_json::json!({
+                "samples": w.samples,
+                "events": w.events,
+                "cpu_max": w.cpu_max,
+                "mem_max": w.mem_max,
+            });
+            json
+        }));
+        formatted_events.sort_by_key(|e| e["timestamp"].as_i64().unwrap_or(0));
+    }
+
     serde_json::json!({
         "count": formatted_events.len(),
         "events": formatted_events,
@@ -552,14 +800,8 @@ fn find_missing_metadata(reader: &IndexedReader, events: &[Event], end_time_ns:
                 has_disk_total = true;
             }
             if !has_filesystems && m.filesystems.is_some() {
-                let filesystems: Vec<_> = m.filesystems.as_ref().unwrap().iter().map(|fs| serde_json::json!({
-                    "filesystem": fs.filesystem,
-                    "mount_point": fs.mount_point,
-                    "total_bytes": fs.total_bytes,
-                    "used_bytes": fs.used_bytes,
-                    "available_bytes": fs.available_bytes,
-                })).collect();
-                metadata["filesystems"] = serde_json::json!(filesystems);
+                metadata["filesystems"] =
+                    serde_json::json!(filesystems_json(m.filesystems.as_deref(), m.filesystem_status.as_deref()));
                 has_filesystems = true;
             }
             if !has_net_interface && m.net_interface.is_some() {
@@ -604,7 +846,18 @@ fn find_missing_metadata(reader: &IndexedReader, events: &[Event], end_time_ns:
                     "user": proc.user,
                     "cpu_percent": proc.cpu_percent,
                     "mem_bytes": proc.mem_bytes,
+                    "read_bytes_per_sec": proc.read_bytes_per_sec,
+                    "write_bytes_per_sec": proc.write_bytes_per_sec,
                     "num_threads": proc.num_threads,
+                    "container_id": proc.container_id,
+                    "container_name": proc.container_name,
+                    "connection_count": proc.connection_count,
+                    "connections": proc.connections.iter().map(|c| serde_json::json!({
+                        "protocol": c.protocol,
+                        "local_port": c.local_port,
+                        "remote_addr": c.remote_addr,
+                        "state": c.state,
+                    })).collect::<Vec<_>>(),
                 })).collect();
                 metadata["processes"] = serde_json::json!(processes);
                 metadata["total_processes"] = serde_json::json!(p.total_processes);
@@ -674,13 +927,7 @@ fn format_metadata_as_initial_state(metadata: &Metadata) -> serde_json::Value {
         "disk_read": 0,
         "disk_write": 0,
         "per_disk": [],
-        "filesystems": metadata.filesystems.as_ref().map(|fs_list| fs_list.iter().map(|fs| serde_json::json!({
-            "filesystem": fs.filesystem,
-            "mount_point": fs.mount_point,
-            "total_bytes": fs.total_bytes,
-            "used_bytes": fs.used_bytes,
-            "available_bytes": fs.available_bytes,
-        })).collect::<Vec<_>>()).unwrap_or_default(),
+        "filesystems": filesystems_json(metadata.filesystems.as_deref(), metadata.filesystem_status.as_deref()),
         "users": metadata.logged_in_users.as_ref().map(|user_list| user_list.iter().map(|u| serde_json::json!({
             "username": u.username,
             "terminal": u.terminal,
@@ -692,6 +939,7 @@ fn format_metadata_as_initial_state(metadata: &Metadata) -> serde_json::Value {
         "net_send_errors": 0,
         "net_recv_drops": 0,
         "net_send_drops": 0,
+        "per_interface": [],
         "net_interface": metadata.net_interface,
         "net_ip": metadata.net_ip_address,
         "net_gateway": metadata.net_gateway,
@@ -711,6 +959,7 @@ fn format_metadata_as_initial_state(metadata: &Metadata) -> serde_json::Value {
         "gpu_mem_freq": metadata.gpu.as_ref().and_then(|g| g.mem_freq_mhz),
         "gpu_temp2": metadata.gpu.as_ref().and_then(|g| g.gpu_temp_celsius),
         "gpu_power": metadata.gpu.as_ref().and_then(|g| g.power_watts),
+        "gpus": metadata.gpus,
     })
 }
 
@@ -728,9 +977,17 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
                 "system_uptime_seconds": m.system_uptime_seconds,
                 "cpu": m.cpu_usage_percent,
                 "per_core_cpu": m.per_core_usage,
+                "per_core_freq_mhz": m.per_core_freq_mhz,
                 "mem": m.mem_usage_percent,
                 "mem_used": m.mem_used_bytes,
                 "mem_total": m.mem_total_bytes,
+                "mem_available": m.mem_available_bytes,
+                "mem_cached": m.mem_cached_bytes,
+                "mem_buffers": m.mem_buffers_bytes,
+                "mem_dirty": m.mem_dirty_bytes,
+                "mem_writeback": m.mem_writeback_bytes,
+                "hugepages_total": m.hugepages_total,
+                "hugepages_free": m.hugepages_free,
                 "swap": m.swap_usage_percent,
                 "swap_used": m.swap_used_bytes,
                 "swap_total": m.swap_total_bytes,
@@ -742,19 +999,9 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
                 "disk_total": m.disk_total_bytes,
                 "disk_read": m.disk_read_bytes_per_sec,
                 "disk_write": m.disk_write_bytes_per_sec,
-                "per_disk": m.per_disk_metrics.iter().map(|d| serde_json::json!({
-                    "device": d.device_name,
-                    "read": d.read_bytes_per_sec,
-                    "write": d.write_bytes_per_sec,
-                    "temp": d.temp_celsius,
-                })).collect::<Vec<_>>(),
-                "filesystems": m.filesystems.as_ref().map(|fs_list| fs_list.iter().map(|fs| serde_json::json!({
-                    "filesystem": fs.filesystem,
-                    "mount_point": fs.mount_point,
-                    "total_bytes": fs.total_bytes,
-                    "used_bytes": fs.used_bytes,
-                    "available_bytes": fs.available_bytes,
-                })).collect::<Vec<_>>()).unwrap_or_default(),
+                "per_disk": disks_json(&m.per_disk_metrics, m.disk_health.as_deref()),
+                "filesystems": filesystems_json(m.filesystems.as_deref(), m.filesystem_status.as_deref()),
+                "raid": m.raid_arrays,
                 "users": m.logged_in_users.as_ref().map(|user_list| user_list.iter().map(|u| serde_json::json!({
                     "username": u.username,
                     "terminal": u.terminal,
@@ -766,6 +1013,7 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
                 "net_send_errors": m.net_send_errors_per_sec,
                 "net_recv_drops": m.net_recv_drops_per_sec,
                 "net_send_drops": m.net_send_drops_per_sec,
+                "per_interface": interfaces_json(&m.per_interface, m.interface_packets.as_deref()),
                 "net_interface": m.net_interface,
                 "net_ip": m.net_ip_address,
                 "net_gateway": m.net_gateway,
@@ -781,6 +1029,12 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
                 "gpu_mem_freq": m.gpu.mem_freq_mhz,
                 "gpu_temp2": m.gpu.gpu_temp_celsius,
                 "gpu_power": m.gpu.power_watts,
+                "gpus": m.gpus,
+                "sockets": m.sockets,
+                "tcp_listen_drops": m.tcp_listen_drops,
+                "kernel_limits": m.kernel_limits,
+                "pressure": m.pressure,
+                "rollup": m.rollup,
                 "fans": m.fans.as_ref().map(|fan_list| fan_list.iter().map(|f| serde_json::json!({
                     "label": f.label,
                     "rpm": f.rpm,
@@ -799,6 +1053,8 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
             "user": p.user,
             "uid": p.uid,
             "exit_code": p.exit_code,
+            "container_id": p.container_id,
+            "container_name": p.container_name,
         }),
         Event::ProcessSnapshot(p) => serde_json::json!({
             "type": "ProcessSnapshot",
@@ -806,6 +1062,10 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
             "count": p.processes.len(),
             "total_processes": p.total_processes,
             "running_processes": p.running_processes,
+            "top_cpu_pids": p.top_cpu_pids,
+            "top_mem_pids": p.top_mem_pids,
+            "per_user": p.per_user,
+            "per_container": p.per_container,
             "processes": p.processes.iter().map(|proc| serde_json::json!({
                 "pid": proc.pid,
                 "name": proc.name,
@@ -814,7 +1074,18 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
                 "user": proc.user,
                 "cpu_percent": proc.cpu_percent,
                 "mem_bytes": proc.mem_bytes,
+                "read_bytes_per_sec": proc.read_bytes_per_sec,
+                "write_bytes_per_sec": proc.write_bytes_per_sec,
                 "num_threads": proc.num_threads,
+                "container_id": proc.container_id,
+                "container_name": proc.container_name,
+                "connection_count": proc.connection_count,
+                "connections": proc.connections.iter().map(|c| serde_json::json!({
+                    "protocol": c.protocol,
+                    "local_port": c.local_port,
+                    "remote_addr": c.remote_addr,
+                    "state": c.state,
+                })).collect::<Vec<_>>(),
             })).collect::<Vec<_>>(),
         }),
         Event::SecurityEvent(s) => serde_json::json!({
@@ -838,6 +1109,231 @@ fn format_event_for_api(event: &Event) -> serde_json::Value {
             "kind": format!("{:?}", fse.kind),
             "path": fse.path,
             "size": fse.size,
+            "old_hash": fse.old_hash,
+            "new_hash": fse.new_hash,
+            "old_attributes": fse.old_attributes,
+            "new_attributes": fse.new_attributes,
+            "diff": fse.diff,
+            "count": fse.count,
+        }),
+        Event::Annotation(a) => serde_json::json!({
+            "type": "Annotation",
+            "timestamp": a.ts.unix_timestamp_nanos() / 1_000_000, // ms
+            "author": a.author,
+            "text": a.text,
+            "tags": a.tags,
         }),
+        Event::DiskHealth(d) => serde_json::json!({
+            "type": "DiskHealth",
+            "timestamp": d.ts.unix_timestamp_nanos() / 1_000_000, // ms,
+            "device": d.device,
+            "model": d.model,
+            "health_passed": d.health_passed,
+            "failing_attributes": d.failing_attributes,
+            "reallocated_sectors": d.reallocated_sectors,
+            "pending_sectors": d.pending_sectors,
+            "offline_uncorrectable": d.offline_uncorrectable,
+            "crc_errors": d.crc_errors,
+            "percentage_used": d.percentage_used,
+            "available_spare": d.available_spare,
+            "available_spare_threshold": d.available_spare_threshold,
+            "media_errors": d.media_errors,
+            "critical_warning": d.critical_warning,
+            "power_on_hours": d.power_on_hours,
+            "temperature": d.temperature_celsius,
+        }),
+        Event::ServiceStateChange(s) => serde_json::json!({
+            "type": "ServiceStateChange",
+            "timestamp": s.ts.unix_timestamp_nanos() / 1_000_000, // ms,
+            "unit": s.unit,
+            "old_state": s.old_state,
+            "new_state": s.new_state,
+            "n_restarts": s.n_restarts,
+        }),
+        Event::SystemEvent(s) => {
+            let mut json = serde_json::json!({
+                "type": "SystemEvent",
+                "timestamp": s.ts.unix_timestamp_nanos() / 1_000_000, // ms,
+                "kind": s.kind.name(),
+                "message": s.message,
+            });
+            match &s.kind {
+                SystemEventKind::Rebooted { boot_time, last_event, clean_shutdown } => {
+                    json["boot_time"] = serde_json::json!(boot_time.unix_timestamp_nanos() / 1_000_000);
+                    json["last_event"] = serde_json::json!(last_event.unix_timestamp_nanos() / 1_000_000);
+                    json["clean_shutdown"] = (*clean_shutdown).into();
+                }
+                SystemEventKind::ConfigReloaded { applied, requires_restart } => {
+                    json["applied"] = serde_json::json!(applied);
+                    json["requires_restart"] = serde_json::json!(requires_restart);
+                }
+                SystemEventKind::Shutdown => {}
+            }
+            json
+        }
+        Event::OperatorAction(a) => serde_json::json!({
+            "type": "OperatorAction",
+            "timestamp": a.ts.unix_timestamp_nanos() / 1_000_000, // ms
+            "user": a.user,
+            "action": a.action,
+            "pid": a.pid,
+            "name": a.name,
+            "cmdline": a.cmdline,
+            "success": a.success,
+            "outcome": a.outcome,
+        }),
+        Event::NetworkTraffic(t) => serde_json::json!({
+            "type": "NetworkTraffic",
+            "timestamp": t.ts.unix_timestamp_nanos() / 1_000_000, // ms
+            "interval_secs": t.interval_secs,
+            "bytes_counted": t.bytes_counted,
+            "top_remotes": t.top_remotes,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, Anomaly, AnomalyKind, AnomalySeverity, ProcessSnapshot};
+
+    const BASE: i64 = 1_767_604_080; // Multiple of 10
+
+    fn at(secs: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(secs).unwrap()
+    }
+
+    fn metrics(secs: i64, cpu: f32, disk_write: u64) -> Event {
+        let mut m = sample_metrics();
+        m.ts = at(secs);
+        m.cpu_usage_percent = cpu;
+        m.mem_usage_percent = cpu / 2.0;
+        m.disk_write_bytes_per_sec = disk_write;
+        m.per_core_usage = vec![cpu, 0.0];
+        m.kernel_version = None;
+        Event::SystemMetrics(m)
+    }
+
+    fn anomaly(secs: i64) -> Event {
+        Event::Anomaly(Anomaly {
+            ts: at(secs),
+            severity: AnomalySeverity::Warning,
+            kind: AnomalyKind::CpuSpike,
+            message: String::new(),
+        })
+    }
+
+    fn snapshot(secs: i64) -> Event {
+        Event::ProcessSnapshot(ProcessSnapshot {
+            ts: at(secs),
+            processes: Vec::new(),
+            total_processes: 0,
+            running_processes: 0,
+            top_cpu_pids: Vec::new(),
+            top_mem_pids: Vec::new(),
+            per_user: None,
+            per_container: None,
+        })
+    }
+
+    #[test]
+    fn test_aggregate_metrics_means_levels_and_peaks_rates() {
+        let mut samples: Vec<SystemMetrics> = [(10.0, 100), (30.0, 5000), (20.0, 200)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(cpu, write))| match metrics(BASE + i as i64, cpu, write) {
+                Event::SystemMetrics(m) => m,
+                _ => unreachable!(),
+            })
+            .collect();
+        samples[1].kernel_version = Some("6.1".to_string());
+
+        let window = aggregate_metrics(&samples, 4).unwrap();
+        assert_eq!(window.samples, 3);
+        assert_eq!(window.events, 4);
+        assert_eq!(window.metrics.ts, at(BASE + 2));
+        assert_eq!(window.metrics.cpu_usage_percent, 20.0);
+        assert_eq!(window.metrics.mem_usage_percent, 10.0);
+        assert_eq!(window.metrics.per_core_usage, vec![20.0, 0.0]);
+        assert_eq!(window.cpu_max, 30.0);
+        assert_eq!(window.mem_max, 15.0);
+        assert_eq!(window.metrics.disk_write_bytes_per_sec, 5000);
+        assert_eq!(window.metrics.kernel_version.as_deref(), Some("6.1"));
+
+        assert!(aggregate_metrics(&[], 0).is_none());
+    }
+
+    #[test]
+    fn test_aggregate_by_step_keeps_other_events() {
+        let events = vec![
+            metrics(BASE, 10.0, 0),
+            snapshot(BASE + 1),
+            anomaly(BASE + 2),
+            metrics(BASE + 5, 20.0, 0),
+            snapshot(BASE + 6),
+            metrics(BASE + 10, 50.0, 0),
+            anomaly(BASE + 11),
+            anomaly(BASE + 12),
+            anomaly(BASE + 25), // Window with no metrics
+        ];
+
+        let (windows, others) = aggregate_by_step(events, 10);
+        assert_eq!(windows.len(), 2);
+        assert_eq!((windows[0].samples, windows[0].events), (2, 1));
+        assert_eq!(windows[0].metrics.cpu_usage_percent, 15.0);
+        assert_eq!((windows[1].samples, windows[1].events), (1, 2));
+
+        // Only the latest snapshot of the first window survives
+        let kinds: Vec<(&str, i64)> = others
+            .iter()
+            .map(|e| {
+                let kind = if matches!(e, Event::Anomaly(_)) { "anomaly" } else { "snapshot" };
+                (kind, e.timestamp().unix_timestamp() - BASE)
+            })
+            .collect();
+        assert_eq!(kinds, vec![("anomaly", 2), ("snapshot", 6), ("anomaly", 11), ("anomaly", 12), ("anomaly", 25)]);
+    }
+
+    #[test]
+    fn test_window_stats_average_and_p95() {
+        let samples: Vec<SystemMetrics> = (1..=20)
+            .map(|i| match metrics(BASE + i, i as f32, (i * 100) as u64) {
+                Event::SystemMetrics(m) => m,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let stats = window_stats(&samples).unwrap();
+        assert_eq!(stats.samples, 20);
+        assert_eq!(stats.cpu, MetricStats { avg: 10.5, p95: 19.0 });
+        assert_eq!(stats.mem.p95, 9.5);
+        assert_eq!(stats.disk_write, MetricStats { avg: 1050.0, p95: 1900.0 });
+        assert!(window_stats(&[]).is_none());
+    }
+
+    #[test]
+    fn test_read_window_stats_is_none_outside_recorded_range() {
+        use crate::recorder::Recorder;
+        use crate::storage::RetentionPolicy;
+
+        // Ranges are matched against recording time, so this is all written now
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        {
+            let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+            for i in 0..10 {
+                recorder.append(&metrics(now, 40.0 + i as f32, 0)).unwrap();
+            }
+        }
+        let reader = IndexedReader::new(temp_dir.path()).unwrap();
+
+        let current = read_window_stats(&reader, now - 5, now + 5).unwrap();
+        assert_eq!(current.samples, 10);
+        assert_eq!(current.cpu, MetricStats { avg: 44.5, p95: 49.0 });
+
+        // A day earlier was never recorded (or has been overwritten)
+        assert!(read_window_stats(&reader, now - 86_400, now - 86_390).is_none());
+        assert_eq!(window_stats_json(None, 0, 10), serde_json::Value::Null);
     }
 }
@@ -0,0 +1,318 @@
+// Historical query API - aggregates of a few metrics over a time range, without reading
+// every event into the browser:
+//
+//   /api/query?start=S&end=E&metrics=cpu,mem,net_recv&agg=avg,max&bucket=60
+//
+// Each metric gets one array per aggregate with a value per bucket, null where nothing was
+// recorded. Long ranges widen the bucket the way the timeline widens its step, so a response
+// never has more than MAX_BUCKETS entries per array.
+
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::event::{Event, SystemMetrics};
+use crate::indexed_reader::IndexedReader;
+use time::OffsetDateTime;
+
+const DEFAULT_BUCKET_SECS: i64 = 60;
+const MAX_BUCKETS: i64 = 1000;
+
+// A name accepted in ?metrics= and the value each sample contributes
+type Metric = (&'static str, fn(&SystemMetrics) -> f64);
+
+const METRICS: [Metric; 10] = [
+    ("cpu", |m| m.cpu_usage_percent as f64),
+    ("mem", |m| m.mem_usage_percent as f64),
+    ("swap", |m| m.swap_usage_percent as f64),
+    ("disk", |m| m.disk_usage_percent as f64),
+    ("load", |m| m.load_avg_1m as f64),
+    ("disk_read", |m| m.disk_read_bytes_per_sec as f64),
+    ("disk_write", |m| m.disk_write_bytes_per_sec as f64),
+    ("net_recv", |m| m.net_recv_bytes_per_sec as f64),
+    ("net_send", |m| m.net_send_bytes_per_sec as f64),
+    ("context_switches", |m| m.context_switches_per_sec as f64),
+];
+
+#[derive(Deserialize, Debug)]
+pub struct QueryParams {
+    start: i64, // Unix seconds
+    end: i64,   // Inclusive
+    metrics: String, // Comma separated
+    agg: Option<String>, // Comma separated, avg when absent
+    bucket: Option<i64>, // Seconds
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aggregate {
+    Avg,
+    Min,
+    Max,
+    P95,
+}
+
+impl Aggregate {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "avg" => Some(Self::Avg),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "p95" => Some(Self::P95),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Avg => "avg",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::P95 => "p95",
+        }
+    }
+}
+
+// One sample's value in a bucket. Downsampled records stand in for several samples, so they
+// weigh that much in the average, and their rolled up peak is the max.
+struct Point {
+    value: f64,
+    peak: f64,
+    weight: u32,
+}
+
+fn point(metric: &str, value_of: fn(&SystemMetrics) -> f64, m: &SystemMetrics) -> Point {
+    let value = value_of(m);
+    let peak = match (metric, &m.rollup) {
+        ("cpu", Some(rollup)) => rollup.cpu_max_percent as f64,
+        ("mem", Some(rollup)) => rollup.mem_max_percent as f64,
+        _ => value,
+    };
+    Point { value, peak, weight: m.rollup.as_ref().map_or(1, |r| r.samples.max(1)) }
+}
+
+fn aggregate(points: &mut [Point], agg: Aggregate) -> Option<f64> {
+    if points.is_empty() {
+        return None;
+    }
+    Some(match agg {
+        Aggregate::Avg => {
+            let weight: f64 = points.iter().map(|p| p.weight as f64).sum();
+            points.iter().map(|p| p.value * p.weight as f64).sum::<f64>() / weight
+        }
+        Aggregate::Min => points.iter().map(|p| p.value).fold(f64::INFINITY, f64::min),
+        Aggregate::Max => points.iter().map(|p| p.peak).fold(f64::NEG_INFINITY, f64::max),
+        Aggregate::P95 => {
+            points.sort_by(|a, b| a.value.total_cmp(&b.value));
+            let rank = ((points.len() as f64 * 0.95).ceil() as usize).clamp(1, points.len());
+            points[rank - 1].value
+        }
+    })
+}
+
+// Both ends a time OffsetDateTime can hold, in order, so nothing computed from them overflows
+fn check_range(start: i64, end: i64) -> Result<(), String> {
+    if start > end {
+        return Err("start must not be after end".to_string());
+    }
+    for (name, secs) in [("start", start), ("end", end)] {
+        if OffsetDateTime::from_unix_timestamp(secs).is_err() {
+            return Err(format!("{} {} is out of range", name, secs));
+        }
+    }
+    Ok(())
+}
+
+// The requested bucket, widened until the range fits in MAX_BUCKETS. None if the range
+// can't be counted in seconds.
+fn bucket_secs(start: i64, end: i64, requested: i64) -> Option<i64> {
+    let range = end.checked_sub(start)?.checked_add(1)?;
+    if range / requested > MAX_BUCKETS {
+        Some((range + MAX_BUCKETS - 1) / MAX_BUCKETS)
+    } else {
+        Some(requested)
+    }
+}
+
+/// Bucket the SystemMetrics in `events` from `start` and aggregate each metric, one array
+/// per metric and aggregate
+fn aggregate_buckets(
+    events: &[Event],
+    start: i64,
+    end: i64,
+    bucket: i64,
+    metrics: &[Metric],
+    aggs: &[Aggregate],
+) -> serde_json::Value {
+    let count = (end.saturating_sub(start) / bucket.max(1)).saturating_add(1);
+    let count = count.clamp(1, MAX_BUCKETS) as usize;
+    let mut buckets: Vec<Vec<&SystemMetrics>> = vec![Vec::new(); count];
+    for event in events {
+        if let Event::SystemMetrics(m) = event {
+            let index = m.ts.unix_timestamp().saturating_sub(start).div_euclid(bucket);
+            if let Some(samples) = usize::try_from(index).ok().and_then(|i| buckets.get_mut(i)) {
+                samples.push(m);
+            }
+        }
+    }
+
+    let mut series = serde_json::Map::new();
+    for &(metric, value_of) in metrics {
+        let mut arrays = serde_json::Map::new();
+        for &agg in aggs {
+            let values: Vec<Option<f64>> = buckets
+                .iter()
+                .map(|samples| {
+                    let mut points: Vec<Point> = samples.iter().map(|m| point(metric, value_of, m)).collect();
+                    aggregate(&mut points, agg)
+                })
+                .collect();
+            arrays.insert(agg.name().to_string(), serde_json::json!(values));
+        }
+        series.insert(metric.to_string(), arrays.into());
+    }
+
+    let timestamps: Vec<i64> = (0..count as i64).map(|i| start.saturating_add(i.saturating_mul(bucket))).collect();
+    serde_json::json!({
+        "start": start,
+        "end": end,
+        "bucket": bucket,
+        "timestamps": timestamps,
+        "series": series,
+    })
+}
+
+fn parse_list<T>(list: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Vec<T>, String> {
+    let mut items = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        items.push(parse(name).ok_or_else(|| name.to_string())?);
+    }
+    Ok(items)
+}
+
+fn bad_request(message: String) -> HttpResponse {
+    HttpResponse::BadRequest().json(serde_json::json!({"error": message}))
+}
+
+/// Bucketed aggregates of the requested metrics between start and end
+pub async fn api_query(
+    reader: web::Data<Arc<IndexedReader>>,
+    query: web::Query<QueryParams>,
+) -> HttpResponse {
+    if let Err(message) = check_range(query.start, query.end) {
+        return bad_request(message);
+    }
+    let requested = query.bucket.unwrap_or(DEFAULT_BUCKET_SECS);
+    if requested <= 0 {
+        return bad_request("bucket must be at least 1 second".to_string());
+    }
+    let metrics = match parse_list(&query.metrics, |name| METRICS.iter().find(|(n, _)| *n == name).copied()) {
+        Ok(metrics) if !metrics.is_empty() => metrics,
+        Ok(_) => return bad_request("metrics must name at least one metric".to_string()),
+        Err(name) => {
+            let known: Vec<&str> = METRICS.iter().map(|(n, _)| *n).collect();
+            return bad_request(format!("Unknown metric '{}', expected one of {}", name, known.join(", ")));
+        }
+    };
+    let aggs = match parse_list(query.agg.as_deref().unwrap_or("avg"), Aggregate::parse) {
+        Ok(aggs) if !aggs.is_empty() => aggs,
+        Ok(_) => vec![Aggregate::Avg],
+        Err(name) => return bad_request(format!("Unknown aggregate '{}', expected avg, min, max or p95", name)),
+    };
+
+    let (start, end) = (query.start, query.end);
+    let Some(bucket) = bucket_secs(start, end, requested) else {
+        return bad_request("start to end is too long a range".to_string());
+    };
+    let _ = reader.refresh();
+    let reader = reader.get_ref().clone();
+    let start_ns = (start as i128) * 1_000_000_000;
+    let end_ns = (end as i128 + 1) * 1_000_000_000 - 1;
+    let events = match web::block(move || reader.read_time_range(Some(start_ns), Some(end_ns))).await {
+        Ok(Ok(events)) => events,
+        Ok(Err(e)) => {
+            eprintln!("Error reading query range: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to read events: {}", e)}));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to read events: {}", e)}));
+        }
+    };
+
+    HttpResponse::Ok().json(aggregate_buckets(&events, start, end, bucket, &metrics, &aggs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, MetricsRollup};
+
+    fn metrics_at(ts: i64, cpu: f32, net_recv: u64) -> Event {
+        Event::SystemMetrics(SystemMetrics {
+            ts: OffsetDateTime::from_unix_timestamp(ts).unwrap(),
+            cpu_usage_percent: cpu,
+            net_recv_bytes_per_sec: net_recv,
+            ..sample_metrics()
+        })
+    }
+
+    #[test]
+    fn test_aggregate_buckets() {
+        let start = 1_760_576_400; // 02:00
+        let mut events: Vec<Event> = (0..20).map(|i| metrics_at(start + i, (i + 1) as f32, 1000)).collect();
+        // A downsampled record of 4 samples peaking at 90%
+        let mut rolled_up = sample_metrics();
+        rolled_up.ts = OffsetDateTime::from_unix_timestamp(start + 130).unwrap();
+        rolled_up.cpu_usage_percent = 40.0;
+        rolled_up.rollup = Some(MetricsRollup { samples: 4, cpu_max_percent: 90.0, mem_max_percent: 50.0 });
+        events.push(Event::SystemMetrics(rolled_up));
+        events.push(metrics_at(start + 140, 20.0, 0));
+        events.push(metrics_at(start + 500, 99.0, 0)); // After the end
+
+        let metrics: Vec<_> = METRICS.iter().copied().filter(|(n, _)| ["cpu", "net_recv"].contains(n)).collect();
+        let aggs = [Aggregate::Avg, Aggregate::Min, Aggregate::Max, Aggregate::P95];
+        let json = aggregate_buckets(&events, start, start + 179, 60, &metrics, &aggs);
+
+        assert_eq!(json["timestamps"], serde_json::json!([start, start + 60, start + 120]));
+        let cpu = &json["series"]["cpu"];
+        assert_eq!(cpu["avg"], serde_json::json!([10.5, null, 36.0]));
+        assert_eq!(cpu["min"], serde_json::json!([1.0, null, 20.0]));
+        assert_eq!(cpu["max"], serde_json::json!([20.0, null, 90.0]));
+        assert_eq!(cpu["p95"][0], serde_json::json!(19.0));
+        assert_eq!(json["series"]["net_recv"]["max"][0], serde_json::json!(1000.0));
+    }
+
+    #[test]
+    fn test_bucket_widened_to_cap() {
+        assert_eq!(bucket_secs(0, 3599, 60), Some(60));
+        // A week of 1 second buckets is far too many
+        let bucket = bucket_secs(0, 7 * 86_400 - 1, 1).unwrap();
+        assert_eq!(bucket, 605);
+        assert!(7 * 86_400 / bucket <= MAX_BUCKETS);
+        assert_eq!(Aggregate::parse("p99"), None);
+        assert!(parse_list("cpu, mem,", |n| Some(n.to_string())).unwrap().len() == 2);
+    }
+
+    #[test]
+    fn test_extreme_ranges_rejected() {
+        assert!(check_range(0, 3599).is_ok());
+        assert!(check_range(3600, 0).unwrap_err().contains("after end"));
+        assert!(check_range(0, i64::MAX).unwrap_err().contains("end"));
+        assert!(check_range(i64::MIN, 0).unwrap_err().contains("start"));
+        assert!(check_range(i64::MIN, i64::MAX).is_err());
+        assert_eq!(bucket_secs(i64::MIN, i64::MAX, 60), None);
+
+        // The widest range that passes still gets at most MAX_BUCKETS buckets
+        let (min, max) = (time::PrimitiveDateTime::MIN.assume_utc(), time::PrimitiveDateTime::MAX.assume_utc());
+        let (start, end) = (min.unix_timestamp(), max.unix_timestamp());
+        assert!(check_range(start, end).is_ok());
+        let bucket = bucket_secs(start, end, 1).unwrap();
+        let json = aggregate_buckets(&[], start, end, bucket, &METRICS[..1], &[Aggregate::Avg]);
+        assert_eq!(json["timestamps"].as_array().unwrap().len(), MAX_BUCKETS as usize);
+
+        // Even with a bucket that wasn't widened for the range
+        let json = aggregate_buckets(&[], 0, i64::MAX, 1, &METRICS[..1], &[Aggregate::Avg]);
+        assert_eq!(json["timestamps"].as_array().unwrap().len(), MAX_BUCKETS as usize);
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{Context, Result};
+use time::OffsetDateTime;
+
+use crate::event::Event;
+use crate::indexed_reader::IndexedReader;
+
+// Catching a reconnecting WebSocket client up on what it missed. Every event message carries
+// "seq", the event's timestamp in nanoseconds as a string (it doesn't fit a JS number). A
+// client that reconnects sends {"resume_from": "<last seq>"} and is sent the recorded
+// events after it, followed by a "Resumed" message, before the live stream carries on.
+//
+// The cursor is a timestamp, not a record position, and events aren't always appended in
+// timestamp order (each collector thread sends its own as they come). An event
+// stamped at or before the cursor that was recorded after the client disconnected is not
+// replayed.
+
+// How much a client can be caught up on, from [server] in config.toml
+#[derive(Debug, Clone, Copy)]
+pub struct ResumeLimits {
+    pub max_secs: u64,
+    pub max_events: usize,
+}
+
+#[derive(Debug)]
+pub enum Replay {
+    Events(Vec<Event>),
+    GapTooLarge(String), // Why nothing was replayed, shown to the user
+}
+
+// The cursor of a resume message, or None if the message is something else (a subscription)
+pub fn parse_resume(text: &str) -> Option<Result<i128>> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    let cursor = value.as_object()?.get("resume_from")?;
+    let seq = match cursor {
+        serde_json::Value::String(s) => s.parse().with_context(|| format!("Invalid resume_from {:?}", s)),
+        serde_json::Value::Number(n) => n.as_i64().map(i128::from).context("resume_from must be a whole number of nanoseconds"),
+        _ => Err(anyhow::anyhow!("resume_from must be the seq of the last event received")),
+    };
+    Some(seq.and_then(|seq| {
+        OffsetDateTime::from_unix_timestamp_nanos(seq)
+            .map(|_| seq)
+            .map_err(|_| anyhow::anyhow!("resume_from {} is out of range", seq))
+    }))
+}
+
+pub fn event_seq(event: &Event) -> i128 {
+    event.timestamp().unix_timestamp_nanos()
+}
+
+// Recorded events after `from_ns`, oldest first, unless the gap is beyond the limits
+pub fn replay_since(reader: &IndexedReader, from_ns: i128, now_ns: i128, limits: ResumeLimits) -> Result<Replay> {
+    // A cursor ahead of now is no gap at all
+    let gap_secs = u64::try_from(now_ns.saturating_sub(from_ns).max(0) / 1_000_000_000).unwrap_or(u64::MAX);
+    if gap_secs > limits.max_secs {
+        return Ok(Replay::GapTooLarge(format!(
+            "Disconnected for {}, more than the {} that can be caught up on. Showing live",
+            format_gap(gap_secs),
+            format_gap(limits.max_secs)
+        )));
+    }
+
+    // Records are stamped when appended, never before their event's own timestamp
+    let _ = reader.refresh();
+    let events: Vec<Event> = reader
+        .read_time_range(Some(from_ns.saturating_add(1)), None)?
+        .into_iter()
+        .filter(|event| event_seq(event) > from_ns)
+        .collect();
+    if events.len() > limits.max_events {
+        return Ok(Replay::GapTooLarge(format!(
+            "Missed {} events while disconnected, more than the {} that can be caught up on. Showing live",
+            events.len(),
+            limits.max_events
+        )));
+    }
+    Ok(Replay::Events(events))
+}
+
+fn format_gap(secs: u64) -> String {
+    if secs < 120 {
+        format!("{}s", secs)
+    } else {
+        format!("{} minutes", secs / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Anomaly, AnomalyKind, AnomalySeverity};
+    use crate::recorder::Recorder;
+    use crate::storage::RetentionPolicy;
+    use time::Duration;
+
+    fn anomaly(ts: OffsetDateTime, message: &str) -> Event {
+        Event::Anomaly(Anomaly {
+            ts,
+            severity: AnomalySeverity::Warning,
+            kind: AnomalyKind::CpuSpike,
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_parse_resume() {
+        assert_eq!(parse_resume(r#"{"resume_from": "1760000000123456789"}"#).unwrap().unwrap(), 1760000000123456789);
+        assert_eq!(parse_resume(r#"{"resume_from": 1760000000123456789}"#).unwrap().unwrap(), 1760000000123456789);
+        assert!(parse_resume(r#"{"resume_from": "yesterday"}"#).unwrap().is_err());
+        assert!(parse_resume(r#"{"resume_from": null}"#).unwrap().is_err());
+        assert!(parse_resume(r#"{"types": ["Anomaly"]}"#).is_none());
+        assert!(parse_resume("not json").is_none());
+
+        // Beyond what a timestamp can hold
+        let too_late = parse_resume(&format!(r#"{{"resume_from": "{}"}}"#, i128::MAX)).unwrap().unwrap_err();
+        assert_eq!(too_late.to_string(), format!("resume_from {} is out of range", i128::MAX));
+        assert!(parse_resume(&format!(r#"{{"resume_from": {}}}"#, i64::MIN)).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_replay_since_returns_missed_events_within_limits() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let now = OffsetDateTime::now_utc();
+        {
+            let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+            let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+            for (i, secs_ago) in [30, 20, 10, 5].into_iter().enumerate() {
+                recorder.append(&anomaly(now - Duration::seconds(secs_ago), &format!("event {}", i))).unwrap();
+            }
+        }
+        let reader = IndexedReader::new(temp_dir.path()).unwrap();
+        let limits = ResumeLimits { max_secs: 600, max_events: 5000 };
+        let seq_of = |secs_ago: i64| (now - Duration::seconds(secs_ago)).unix_timestamp_nanos();
+        let now_ns = now.unix_timestamp_nanos();
+
+        // Everything after the last event the client saw, not including it
+        let Replay::Events(events) = replay_since(&reader, seq_of(20), now_ns, limits).unwrap() else {
+            panic!("expected a replay");
+        };
+        let messages: Vec<_> = events
+            .iter()
+            .map(|e| match e {
+                Event::Anomaly(a) => a.message.as_str(),
+                _ => "",
+            })
+            .collect();
+        assert_eq!(messages, ["event 2", "event 3"]);
+
+        // Up to date already
+        let Replay::Events(events) = replay_since(&reader, seq_of(5), now_ns, limits).unwrap() else {
+            panic!("expected a replay");
+        };
+        assert!(events.is_empty());
+
+        let Replay::GapTooLarge(message) =
+            replay_since(&reader, seq_of(30), now_ns, ResumeLimits { max_events: 2, ..limits }).unwrap()
+        else {
+            panic!("expected too many events");
+        };
+        assert_eq!(message, "Missed 3 events while disconnected, more than the 2 that can be caught up on. Showing live");
+
+        let Replay::GapTooLarge(message) = replay_since(&reader, seq_of(900), now_ns, limits).unwrap() else {
+            panic!("expected too long a gap");
+        };
+        assert_eq!(message, "Disconnected for 15 minutes, more than the 10 minutes that can be caught up on. Showing live");
+    }
+
+    #[test]
+    fn test_replay_since_skips_late_recorded_events_before_cursor() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let now = OffsetDateTime::now_utc();
+        let retention = RetentionPolicy { max_bytes: 100 * 1024 * 1024, max_age_secs: None };
+        let mut recorder = Recorder::open_with_config(temp_dir.path(), retention, None, None).unwrap();
+        recorder.append(&anomaly(now - Duration::seconds(20), "seen")).unwrap();
+        recorder.append(&anomaly(now - Duration::seconds(10), "last seen")).unwrap();
+        // Recorded after the client disconnected, but stamped before its cursor
+        recorder.append(&anomaly(now - Duration::seconds(15), "late")).unwrap();
+        recorder.append(&anomaly(now - Duration::seconds(5), "missed")).unwrap();
+        drop(recorder);
+
+        let reader = IndexedReader::new(temp_dir.path()).unwrap();
+        let limits = ResumeLimits { max_secs: 600, max_events: 5000 };
+        let cursor = (now - Duration::seconds(10)).unix_timestamp_nanos();
+        let Replay::Events(events) = replay_since(&reader, cursor, now.unix_timestamp_nanos(), limits).unwrap() else {
+            panic!("expected a replay");
+        };
+        let messages: Vec<_> = events
+            .iter()
+            .map(|e| match e {
+                Event::Anomaly(a) => a.message.as_str(),
+                _ => "",
+            })
+            .collect();
+        assert_eq!(messages, ["missed"]);
+
+        // A cursor from the far future or past doesn't overflow
+        let Replay::Events(events) = replay_since(&reader, i128::MAX, now.unix_timestamp_nanos(), limits).unwrap() else {
+            panic!("expected a replay");
+        };
+        assert!(events.is_empty());
+        assert!(matches!(
+            replay_since(&reader, i128::MIN, now.unix_timestamp_nanos(), limits).unwrap(),
+            Replay::GapTooLarge(_)
+        ));
+    }
+}
@@ -1,49 +1,134 @@
 use actix_web::{web, HttpResponse};
 use serde::Deserialize;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::config::Config;
 use crate::event::Event;
-use crate::reader::LogReader;
+use crate::indexed_reader::IndexedReader;
+use crate::reload::ReloadSender;
+use super::websocket::{disks_json, filesystems_json, interfaces_json};
+
+const DEFAULT_EVENT_LIMIT: usize = 1000;
+const MAX_EVENT_LIMIT: usize = 10000;
+// The main loop picks up a reload on its next tick
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Deserialize)]
 pub struct EventQueryParams {
     filter: Option<String>,
     #[serde(rename = "type")]
     event_type: Option<String>,
+    start: Option<i64>,    // Unix seconds, inclusive
+    end: Option<i64>,      // Unix seconds, inclusive
+    limit: Option<usize>,  // Most recent matching events to return (default: 1000)
+    before: Option<i64>,   // Unix milliseconds, exclusive, for the page before one already shown
+    samples: Option<bool>, // false leaves out SystemMetrics and ProcessSnapshot, as the event log does
 }
 
-pub async fn index() -> HttpResponse {
-    let html = include_str!("assets/index.html");
+// The page's API and WebSocket URLs are relative to BASE_PATH, filled in with server.base_path
+const BASE_PATH_PLACEHOLDER: &str = "'__BASE_PATH__'";
+
+pub async fn index(config: web::Data<Config>) -> HttpResponse {
+    let html = render_index(config.server.base_path());
     HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html)
 }
 
+fn render_index(base_path: &str) -> String {
+    let html = include_str!("assets/index.html");
+    // A JSON string is a valid JS one, validation keeps quotes and < out anyway
+    html.replacen(BASE_PATH_PLACEHOLDER, &serde_json::Value::from(base_path).to_string(), 1)
+}
+
+// Settings the UI needs to present recorded data, currently the zone to show times in.
+// The offset is a fallback for browsers that don't know the zone name.
+pub async fn api_config() -> HttpResponse {
+    let offset = crate::timezone::offset_at(time::OffsetDateTime::now_utc());
+    HttpResponse::Ok().json(serde_json::json!({
+        "timezone": crate::timezone::name(),
+        "utc_offset_seconds": offset.whole_seconds(),
+    }))
+}
+
+// Re-read config.toml in the recorder and report which changes took effect. Only with auth on,
+// so every reload is made by the account; SIGHUP works either way.
+pub async fn api_reload(config: web::Data<Config>, sender: web::Data<ReloadSender>) -> HttpResponse {
+    if !config.auth.enabled {
+        return HttpResponse::Forbidden()
+            .json(serde_json::json!({"error": "Reloading from the web UI needs [auth] enabled, send SIGHUP instead"}));
+    }
+
+    let (reply_tx, reply_rx) = crossbeam_channel::bounded(1);
+    if sender.send(reply_tx).is_err() {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": "Recorder is not running"}));
+    }
+    match web::block(move || reply_rx.recv_timeout(RELOAD_TIMEOUT)).await {
+        Ok(Ok(Ok(summary))) => HttpResponse::Ok().json(summary),
+        Ok(Ok(Err(e))) => HttpResponse::BadRequest().json(serde_json::json!({"error": e})),
+        _ => HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": "Recorder did not answer"})),
+    }
+}
+
 pub async fn api_events(
-    reader: web::Data<LogReader>,
+    indexed_reader: web::Data<Arc<IndexedReader>>,
     query: web::Query<EventQueryParams>,
 ) -> HttpResponse {
     let filter = query.filter.as_ref().map(|s| s.to_lowercase());
     let event_type = query.event_type.as_deref();
+    let limit = query.limit.unwrap_or(DEFAULT_EVENT_LIMIT).min(MAX_EVENT_LIMIT);
+    let samples = query.samples.unwrap_or(true);
 
-    let events = match reader.read_all_events() {
-        Ok(e) => e,
-        Err(e) => {
-            eprintln!("Error reading events: {}", e);
-            return HttpResponse::InternalServerError()
-                .json(serde_json::json!({"error": format!("Failed to read events: {}", e)}));
-        }
+    if let (Some(start), Some(end)) = (query.start, query.end)
+        && start > end
+    {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "start must not be after end"}));
+    }
+
+    let _ = indexed_reader.refresh();
+    let start_ns = query.start.map(|s| (s as i128) * 1_000_000_000);
+    // End second is inclusive, `before` is not
+    let end_ns = query.end.map(|s| (s as i128 + 1) * 1_000_000_000 - 1);
+    let before_ns = query.before.map(|ms| (ms as i128) * 1_000_000 - 1);
+    let end_ns = match (end_ns, before_ns) {
+        (Some(end), Some(before)) => Some(end.min(before)),
+        (end, before) => end.or(before),
     };
 
-    // Convert to JSON-serializable format
+    // Newest matching events first, reading only as far back as the limit needs. A page doesn't
+    // end part way through a millisecond, so `before` its oldest timestamp carries on from it.
     let mut json_events = Vec::new();
-
-    for event in events.iter().rev().take(1000) {
-        if let Some(json_event) = event_to_json(event, &filter, event_type) {
-            json_events.push(json_event);
+    let mut last_ms = None;
+    let mut truncated = false;
+    let result = indexed_reader.read_newest_first(start_ns, end_ns, |event| {
+        if !samples && matches!(event, Event::SystemMetrics(_) | Event::ProcessSnapshot(_)) {
+            return ControlFlow::Continue(());
         }
-    }
+        let ms = event.timestamp().unix_timestamp_nanos() / 1_000_000;
+        if json_events.len() >= limit && last_ms != Some(ms) {
+            truncated = true;
+            return ControlFlow::Break(());
+        }
+        if let Some(json) = event_to_json(&event, &filter, event_type) {
+            json_events.push(json);
+            last_ms = Some(ms);
+        }
+        ControlFlow::Continue(())
+    });
 
+    if let Err(e) = result {
+        eprintln!("Error reading events: {}", e);
+        return HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": format!("Failed to read events: {}", e)}));
+    }
     json_events.reverse();
 
-    HttpResponse::Ok().json(json_events)
+    HttpResponse::Ok().json(serde_json::json!({
+        "events": json_events,
+        "count": json_events.len(),
+        "truncated": truncated,
+    }))
 }
 
 fn event_to_json(
@@ -70,28 +155,26 @@ fn event_to_json(
                 "system_uptime_seconds": m.system_uptime_seconds,
                 "cpu": m.cpu_usage_percent,
                 "per_core_cpu": m.per_core_usage,
+                "per_core_freq_mhz": m.per_core_freq_mhz,
                 "mem": m.mem_usage_percent,
                 "mem_used": m.mem_used_bytes,
                 "mem_total": m.mem_total_bytes,
+                "mem_available": m.mem_available_bytes,
+                "mem_cached": m.mem_cached_bytes,
+                "mem_buffers": m.mem_buffers_bytes,
+                "mem_dirty": m.mem_dirty_bytes,
+                "mem_writeback": m.mem_writeback_bytes,
+                "hugepages_total": m.hugepages_total,
+                "hugepages_free": m.hugepages_free,
                 "load": m.load_avg_1m,
                 "load5": m.load_avg_5m,
                 "load15": m.load_avg_15m,
                 "disk": m.disk_usage_percent.round(),
                 "disk_used": m.disk_used_bytes,
                 "disk_total": m.disk_total_bytes,
-                "per_disk": m.per_disk_metrics.iter().map(|d| serde_json::json!({
-                    "device": d.device_name,
-                    "read": d.read_bytes_per_sec,
-                    "write": d.write_bytes_per_sec,
-                    "temp": d.temp_celsius,
-                })).collect::<Vec<_>>(),
-                "filesystems": m.filesystems.as_ref().map(|fs_list| fs_list.iter().map(|fs| serde_json::json!({
-                    "filesystem": fs.filesystem,
-                    "mount_point": fs.mount_point,
-                    "total_bytes": fs.total_bytes,
-                    "used_bytes": fs.used_bytes,
-                    "available_bytes": fs.available_bytes,
-                })).collect::<Vec<_>>()).unwrap_or_default(),
+                "per_disk": disks_json(&m.per_disk_metrics, m.disk_health.as_deref()),
+                "filesystems": filesystems_json(m.filesystems.as_deref(), m.filesystem_status.as_deref()),
+                "raid": m.raid_arrays,
                 "tcp": m.tcp_connections,
                 "tcp_wait": m.tcp_time_wait,
                 "net_recv": m.net_recv_bytes_per_sec,
@@ -100,6 +183,7 @@ fn event_to_json(
                 "net_send_errors": m.net_send_errors_per_sec,
                 "net_recv_drops": m.net_recv_drops_per_sec,
                 "net_send_drops": m.net_send_drops_per_sec,
+                "per_interface": interfaces_json(&m.per_interface, m.interface_packets.as_deref()),
                 "net_interface": m.net_interface,
                 "net_ip": m.net_ip_address,
                 "net_gateway": m.net_gateway,
@@ -112,6 +196,12 @@ fn event_to_json(
                 "gpu_mem_freq": m.gpu.mem_freq_mhz,
                 "gpu_temp2": m.gpu.gpu_temp_celsius,
                 "gpu_power": m.gpu.power_watts,
+                "gpus": m.gpus,
+                "sockets": m.sockets,
+                "tcp_listen_drops": m.tcp_listen_drops,
+                "kernel_limits": m.kernel_limits,
+                "pressure": m.pressure,
+                "rollup": m.rollup,
                 "fans": m.fans.as_ref().map(|fan_list| fan_list.iter().map(|f| serde_json::json!({
                     "label": f.label,
                     "rpm": f.rpm,
@@ -128,7 +218,7 @@ fn event_to_json(
                 return None;
             }
 
-            let text = format!("{:?} {} {}", p.kind, p.name, p.pid);
+            let text = format!("{:?} {} {} {}", p.kind, p.name, p.pid, p.container_name.as_deref().unwrap_or(""));
             if let Some(f) = filter {
                 if !text.to_lowercase().contains(f) {
                     return None;
@@ -147,6 +237,8 @@ fn event_to_json(
                 "user": p.user,
                 "uid": p.uid,
                 "exit_code": p.exit_code,
+                "container_id": p.container_id,
+                "container_name": p.container_name,
             }))
         }
         Event::SecurityEvent(s) => {
@@ -201,6 +293,10 @@ fn event_to_json(
                 "count": p.processes.len(),
                 "total_processes": p.total_processes,
                 "running_processes": p.running_processes,
+                "top_cpu_pids": p.top_cpu_pids,
+                "top_mem_pids": p.top_mem_pids,
+                "per_user": p.per_user,
+                "per_container": p.per_container,
                 "processes": p.processes.iter().map(|proc| serde_json::json!({
                     "pid": proc.pid,
                     "name": proc.name,
@@ -209,7 +305,18 @@ fn event_to_json(
                     "user": proc.user,
                     "cpu_percent": proc.cpu_percent,
                     "mem_bytes": proc.mem_bytes,
+                    "read_bytes_per_sec": proc.read_bytes_per_sec,
+                    "write_bytes_per_sec": proc.write_bytes_per_sec,
                     "num_threads": proc.num_threads,
+                    "container_id": proc.container_id,
+                    "container_name": proc.container_name,
+                    "connection_count": proc.connection_count,
+                    "connections": proc.connections.iter().map(|c| serde_json::json!({
+                        "protocol": c.protocol,
+                        "local_port": c.local_port,
+                        "remote_addr": c.remote_addr,
+                        "state": c.state,
+                    })).collect::<Vec<_>>(),
                 })).collect::<Vec<serde_json::Value>>(),
             }))
         }
@@ -229,8 +336,189 @@ fn event_to_json(
                 "type": "FileSystemEvent",
                 "timestamp": fse.ts.format(&Rfc3339).ok()?,
                 "kind": format!("{:?}", fse.kind),
-                "path": fse.path
+                "path": fse.path,
+                "size": fse.size,
+                "old_hash": fse.old_hash,
+                "new_hash": fse.new_hash,
+                "old_attributes": fse.old_attributes,
+                "new_attributes": fse.new_attributes,
+                "diff": fse.diff,
+                "count": fse.count,
             }))
         }
+        Event::Annotation(a) => {
+            if event_type_filter.is_some() && event_type_filter != Some("annotation") {
+                return None;
+            }
+
+            let text = format!("{} {} {}", a.author, a.text, a.tags.join(" "));
+            if let Some(f) = filter
+                && !text.to_lowercase().contains(f)
+            {
+                return None;
+            }
+
+            Some(serde_json::json!({
+                "type": "Annotation",
+                "timestamp": a.ts.format(&Rfc3339).ok()?,
+                "author": a.author,
+                "text": a.text,
+                "tags": a.tags,
+            }))
+        }
+        Event::DiskHealth(d) => {
+            if event_type_filter.is_some() && event_type_filter != Some("disk_health") {
+                return None;
+            }
+
+            let text = format!("{} {}", d.device, d.model.as_deref().unwrap_or(""));
+            if let Some(f) = filter
+                && !text.to_lowercase().contains(f)
+            {
+                return None;
+            }
+
+            Some(serde_json::json!({
+                "type": "DiskHealth",
+                "timestamp": d.ts.format(&Rfc3339).ok()?,
+                "device": d.device,
+                "model": d.model,
+                "health_passed": d.health_passed,
+                "failing_attributes": d.failing_attributes,
+                "reallocated_sectors": d.reallocated_sectors,
+                "pending_sectors": d.pending_sectors,
+                "offline_uncorrectable": d.offline_uncorrectable,
+                "crc_errors": d.crc_errors,
+                "percentage_used": d.percentage_used,
+                "available_spare": d.available_spare,
+                "available_spare_threshold": d.available_spare_threshold,
+                "media_errors": d.media_errors,
+                "critical_warning": d.critical_warning,
+                "power_on_hours": d.power_on_hours,
+                "temperature": d.temperature_celsius,
+            }))
+        }
+        Event::ServiceStateChange(s) => {
+            if event_type_filter.is_some() && event_type_filter != Some("service") {
+                return None;
+            }
+
+            if let Some(f) = filter
+                && !s.unit.to_lowercase().contains(f)
+            {
+                return None;
+            }
+
+            Some(serde_json::json!({
+                "type": "ServiceStateChange",
+                "timestamp": s.ts.format(&Rfc3339).ok()?,
+                "unit": s.unit,
+                "old_state": s.old_state,
+                "new_state": s.new_state,
+                "n_restarts": s.n_restarts,
+            }))
+        }
+        Event::SystemEvent(s) => {
+            if event_type_filter.is_some() && event_type_filter != Some("system") {
+                return None;
+            }
+
+            if let Some(f) = filter
+                && !s.message.to_lowercase().contains(f)
+            {
+                return None;
+            }
+
+            let mut json = serde_json::json!({
+                "type": "SystemEvent",
+                "timestamp": s.ts.format(&Rfc3339).ok()?,
+                "kind": s.kind.name(),
+                "message": s.message,
+            });
+            match &s.kind {
+                crate::event::SystemEventKind::Rebooted { boot_time, last_event, clean_shutdown } => {
+                    json["boot_time"] = boot_time.format(&Rfc3339).ok()?.into();
+                    json["last_event"] = last_event.format(&Rfc3339).ok()?.into();
+                    json["clean_shutdown"] = (*clean_shutdown).into();
+                }
+                crate::event::SystemEventKind::ConfigReloaded { applied, requires_restart } => {
+                    json["applied"] = serde_json::json!(applied);
+                    json["requires_restart"] = serde_json::json!(requires_restart);
+                }
+                crate::event::SystemEventKind::Shutdown => {}
+            }
+            Some(json)
+        }
+        Event::OperatorAction(a) => {
+            if event_type_filter.is_some() && event_type_filter != Some("operator") {
+                return None;
+            }
+
+            if let Some(f) = filter
+                && !a.outcome.to_lowercase().contains(f)
+                && !a.name.as_deref().is_some_and(|n| n.to_lowercase().contains(f))
+                && !a.user.to_lowercase().contains(f)
+            {
+                return None;
+            }
+
+            Some(serde_json::json!({
+                "type": "OperatorAction",
+                "timestamp": a.ts.format(&Rfc3339).ok()?,
+                "user": a.user,
+                "action": a.action,
+                "pid": a.pid,
+                "name": a.name,
+                "cmdline": a.cmdline,
+                "success": a.success,
+                "outcome": a.outcome,
+            }))
+        }
+        Event::NetworkTraffic(t) => {
+            if event_type_filter.is_some() && event_type_filter != Some("traffic") {
+                return None;
+            }
+
+            if let Some(f) = filter
+                && !t.top_remotes.iter().any(|r| r.remote.to_lowercase().contains(f))
+            {
+                return None;
+            }
+
+            Some(serde_json::json!({
+                "type": "NetworkTraffic",
+                "timestamp": t.ts.format(&Rfc3339).ok()?,
+                "interval_secs": t.interval_secs,
+                "bytes_counted": t.bytes_counted,
+                "top_remotes": t.top_remotes,
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_rendered_with_base_path() {
+        let html = render_index("/black-box");
+        assert!(html.contains("const BASE_PATH = \"/black-box\";"));
+        assert!(!html.contains("__BASE_PATH__"));
+        assert!(render_index("").contains("const BASE_PATH = \"\";"));
+    }
+
+    #[actix_rt::test]
+    async fn test_events_with_extreme_bounds() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let reader = web::Data::new(Arc::new(IndexedReader::new(temp_dir.path()).unwrap()));
+        let query = |q: &str| web::Query::<EventQueryParams>::from_query(q).unwrap();
+
+        let resp = api_events(reader.clone(), query(&format!("start=0&end={}&before={}", i64::MAX, i64::MAX))).await;
+        assert_eq!(resp.status(), 200);
+        let resp = api_events(reader.clone(), query(&format!("start={}&end={}", i64::MIN, i64::MIN))).await;
+        assert_eq!(resp.status(), 200);
+        let resp = api_events(reader, query(&format!("start={}&end=0", i64::MAX))).await;
+        assert_eq!(resp.status(), 400);
     }
 }
@@ -1,27 +1,44 @@
 use actix_web::{middleware, web, App, HttpServer};
 use anyhow::Result;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
 
 use crate::broadcast::EventBroadcaster;
 use crate::config::Config;
 use crate::indexed_reader::IndexedReader;
+use crate::integrity::SigningKey;
 use crate::reader::LogReader;
 
-use super::{auth, health, playback, routes, websocket};
+use super::listener::WebListener;
+use super::{actions, annotations, auth, export, health, metrics, playback, query, routes, stream, websocket};
+
+/// Shared with the collector in the main thread
+pub struct CollectorLink {
+    pub metadata: Arc<std::sync::RwLock<Option<crate::event::Metadata>>>,
+    pub collector_health: crate::collector_health::SharedCollectorHealth,
+    pub remote_status: health::RemoteStatus,
+    pub annotations: annotations::AnnotationSender,
+    pub actions: actions::ActionSender,
+    pub security: auth::SecurityEventSender,
+    pub reload: crate::reload::ReloadSender,
+}
 
 pub async fn start_server(
     data_dir: String,
-    port: u16,
+    listener: WebListener,
     broadcaster: Arc<EventBroadcaster>,
     config: Config,
-    metadata: Arc<std::sync::RwLock<Option<crate::event::Metadata>>>,
+    tls: Option<rustls::ServerConfig>,
+    link: CollectorLink,
 ) -> Result<()> {
-    let reader = web::Data::new(LogReader::new(&data_dir));
+    let CollectorLink { metadata, collector_health, remote_status, annotations, actions, security, reload } = link;
+    let signing_key = SigningKey::from_config(&config.protection);
+    let reader = web::Data::new(LogReader::new(&data_dir).with_signing_key(signing_key.clone()));
 
     // Build indexed reader for time-travel queries
     let indexed_reader = match IndexedReader::new(&data_dir) {
-        Ok(r) => Arc::new(r),
+        Ok(r) => Arc::new(r.with_signing_key(signing_key)),
         Err(e) => {
             eprintln!("Warning: Failed to build index: {}. Time-travel features disabled.", e);
             Arc::new(IndexedReader::new(std::env::temp_dir()).unwrap())
@@ -30,20 +47,41 @@ pub async fn start_server(
     let indexed_reader_data = web::Data::new(indexed_reader);
 
     let broadcaster_clone = (*broadcaster).clone();
+    let metrics_state: metrics::SharedMetricsState = Default::default();
+    let metrics_state_data = web::Data::new(metrics_state.clone());
+    let metrics_broadcaster = broadcaster.clone();
     let broadcaster_data = web::Data::from(broadcaster);
     let config_data = web::Data::new(config.clone());
     let start_time = web::Data::new(Instant::now());
     let data_dir_data = web::Data::new(data_dir.clone());
     let metadata_data = web::Data::from(metadata);
+    let collector_health_data = web::Data::new(collector_health);
+    let sessions: auth::SharedSessions = match auth::SessionStore::open(Path::new(&data_dir)) {
+        Ok(sessions) => Arc::new(sessions),
+        Err(e) => {
+            eprintln!("Warning: {:#}. Sessions will end when the server restarts.", e);
+            Default::default()
+        }
+    };
+    let sessions_data = web::Data::new(sessions.clone());
+    let login_limiter_data = web::Data::new(auth::SharedLoginLimiter::default());
+    let token_usage: auth::SharedApiTokenUsage = Arc::new(auth::ApiTokenUsage::new(security.clone()));
+    let security_data = web::Data::new(security);
+    let remote_status_data = web::Data::new(remote_status);
+    let annotations_data = web::Data::new(annotations);
+    let actions_data = web::Data::new(actions);
+    let reload_data = web::Data::new(reload);
 
     // Spawn the broadcaster bridge (crossbeam -> tokio broadcast)
     tokio::spawn(async move {
         broadcaster_clone.run().await;
     });
 
-    println!("Server listening on http://localhost:{}", port);
+    // Track the latest metrics for the Prometheus endpoint
+    tokio::spawn(metrics::run_metrics_collector(metrics_broadcaster, metrics_state));
 
-    HttpServer::new(move || {
+    let base_path = config.server.base_path().to_string();
+    let server = HttpServer::new(move || {
         App::new()
             .app_data(reader.clone())
             .app_data(indexed_reader_data.clone())
@@ -52,20 +90,79 @@ pub async fn start_server(
             .app_data(start_time.clone())
             .app_data(data_dir_data.clone())
             .app_data(metadata_data.clone())
+            .app_data(collector_health_data.clone())
+            .app_data(metrics_state_data.clone())
+            .app_data(sessions_data.clone())
+            .app_data(login_limiter_data.clone())
+            .app_data(security_data.clone())
+            .app_data(remote_status_data.clone())
+            .app_data(annotations_data.clone())
+            .app_data(actions_data.clone())
+            .app_data(reload_data.clone())
             .wrap(middleware::Logger::default())
-            .wrap(auth::BasicAuth::new(config.auth.clone()))
-            .route("/", web::get().to(routes::index))
-            .route("/api/events", web::get().to(routes::api_events))
-            .route("/api/playback/info", web::get().to(playback::api_playback_info))
-            .route("/api/playback/events", web::get().to(playback::api_playback_events))
-            .route("/api/playback/jump", web::get().to(playback::api_playback_jump))
-            .route("/api/initial-state", web::get().to(playback::api_initial_state))
-            .route("/api/timeline", web::get().to(playback::api_timeline))
-            .route("/ws", web::get().to(websocket::ws_handler))
-            .route("/health", web::get().to(health::health_check))
-    })
-    .bind(("0.0.0.0", port))?
-    .run()
-    .await
-    .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+            .wrap(
+                auth::RequireAuth::new(
+                    config.auth.clone(),
+                    sessions.clone(),
+                    token_usage.clone(),
+                    login_limiter_data.get_ref().clone(),
+                    security_data.get_ref().clone(),
+                )
+                .with_base_path(&base_path),
+            )
+            .service(app_routes(&base_path))
+    });
+
+    // Bound by the caller, before any privileges are dropped
+    let server = match (listener, tls) {
+        (WebListener::Tcp(listener), Some(tls)) => {
+            println!("Server listening on https://{} (TLS)", listener.local_addr()?);
+            server.listen_rustls_0_23(listener, tls)?
+        }
+        (WebListener::Tcp(listener), None) => {
+            println!("Server listening on http://{} (plain HTTP)", listener.local_addr()?);
+            server.listen(listener)?
+        }
+        // Config validation rules out TLS on a socket
+        (WebListener::Unix(listener, path), _) => {
+            println!("Server listening on unix:{}", path.display());
+            server.listen_uds(listener)?
+        }
+    };
+
+    server
+        .run()
+        .await
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+// Every route, under base_path when the UI sits behind a proxy at a sub-path
+fn app_routes(base_path: &str) -> actix_web::Scope {
+    let scope = web::scope(base_path);
+    // "/black-box" as well as "/black-box/" for the index
+    let scope = if base_path.is_empty() { scope } else { scope.route("", web::get().to(routes::index)) };
+    scope
+        .route("/", web::get().to(routes::index))
+        .route("/api/login", web::post().to(auth::api_login))
+        .route("/api/logout", web::post().to(auth::api_logout))
+        .route("/api/session", web::get().to(auth::api_session))
+        .route("/api/config", web::get().to(routes::api_config))
+        .route("/api/reload", web::post().to(routes::api_reload))
+        .route("/api/events", web::get().to(routes::api_events))
+        .route("/api/playback/info", web::get().to(playback::api_playback_info))
+        .route("/api/playback/events", web::get().to(playback::api_playback_events))
+        .route("/api/playback/jump", web::get().to(playback::api_playback_jump))
+        .route("/api/initial-state", web::get().to(playback::api_initial_state))
+        .route("/api/timeline", web::get().to(playback::api_timeline))
+        .route("/api/baseline", web::get().to(playback::api_baseline))
+        .route("/api/query", web::get().to(query::api_query))
+        .route("/api/export", web::get().to(export::api_export))
+        .route("/api/annotations", web::post().to(annotations::api_create_annotation))
+        .route("/api/process/{pid}/signal", web::post().to(actions::api_process_signal))
+        .route("/api/stream", web::get().to(stream::api_stream))
+        .route("/ws", web::get().to(websocket::ws_handler))
+        .route("/health", web::get().to(health::health_check))
+        .route("/api/health", web::get().to(health::api_collector_health))
+        .route("/metrics", web::get().to(metrics::prometheus_metrics))
+        .route("/api/latest", web::get().to(metrics::api_latest))
 }
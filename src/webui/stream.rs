@@ -0,0 +1,119 @@
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+
+use crate::broadcast::EventBroadcaster;
+use crate::event::Event;
+use crate::syslog;
+use super::resume;
+use super::subscription::{self, Subscription, SubscriptionFilter, SubscriptionSeverity};
+use super::websocket::event_to_json;
+
+// A comment line now and then so proxies don't close a quiet stream
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// The WebSocket subscription as query parameters, e.g. ?types=anomaly,security&min_severity=warning
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct StreamQuery {
+    types: Option<String>, // Comma separated
+    min_severity: Option<SubscriptionSeverity>,
+    #[serde(default, deserialize_with = "subscription::metrics_interval")]
+    metrics_interval: Option<u64>,
+    #[serde(default)]
+    compact: bool,
+}
+
+impl From<StreamQuery> for Subscription {
+    fn from(query: StreamQuery) -> Self {
+        Subscription {
+            types: query
+                .types
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect(),
+            min_severity: query.min_severity,
+            metrics_interval: query.metrics_interval,
+            compact: query.compact,
+        }
+    }
+}
+
+// One SSE message named after the event type, with the same JSON the WebSocket sends
+fn sse_message(event: &Event, mut json: serde_json::Value) -> Bytes {
+    if let Some(object) = json.as_object_mut() {
+        object.insert("seq".to_string(), resume::event_seq(event).to_string().into());
+    }
+    Bytes::from(format!("event: {}\ndata: {}\n\n", syslog::event_type(event), json))
+}
+
+// Server-Sent Events for clients behind proxies that break WebSocket upgrades
+pub async fn api_stream(query: web::Query<StreamQuery>, broadcaster: web::Data<EventBroadcaster>) -> HttpResponse {
+    let mut filter = SubscriptionFilter::new(query.into_inner().into());
+    let events = BroadcastStream::new(broadcaster.subscribe()).filter_map(move |received| {
+        let message = match received {
+            Ok(event) => filter.apply(&event, event_to_json).map(|json| sse_message(&event, json)),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                Some(Bytes::from(format!(": lagged, {} events skipped\n\n", skipped)))
+            }
+        };
+        std::future::ready(message)
+    });
+    let keepalive = stream::unfold(tokio::time::interval(KEEPALIVE_INTERVAL), |mut interval| async move {
+        interval.tick().await;
+        Some((Bytes::from_static(b": keepalive\n\n"), interval))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // nginx buffers responses unless told not to
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream::select(events, keepalive).map(Ok::<_, actix_web::Error>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, Anomaly, AnomalyKind, AnomalySeverity, SecurityEvent, SecurityEventKind};
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_query_filters_types_and_formats_sse() {
+        let query = web::Query::<StreamQuery>::from_query("types=anomaly,%20security&min_severity=warning").unwrap();
+        let mut filter = SubscriptionFilter::new(query.into_inner().into());
+
+        let anomaly = Event::Anomaly(Anomaly {
+            ts: OffsetDateTime::from_unix_timestamp(1_760_608_800).unwrap(),
+            severity: AnomalySeverity::Critical,
+            kind: AnomalyKind::DiskFull,
+            message: "Disk usage on /: 97.0%".to_string(),
+        });
+        let json = filter.apply(&anomaly, event_to_json).unwrap();
+        let message = String::from_utf8(sse_message(&anomaly, json).to_vec()).unwrap();
+        assert!(message.starts_with("event: Anomaly\ndata: {"), "{}", message);
+        assert!(message.contains("\"message\":\"Disk usage on /: 97.0%\""));
+        assert!(message.ends_with("}\n\n"));
+
+        let failed_login = Event::SecurityEvent(SecurityEvent {
+            ts: OffsetDateTime::from_unix_timestamp(1_760_608_800).unwrap(),
+            kind: SecurityEventKind::SshLoginFailure,
+            user: "root".to_string(),
+            source_ip: Some("10.0.0.5".to_string()),
+            message: "Failed password for root".to_string(),
+        });
+        let json = filter.apply(&failed_login, event_to_json).unwrap();
+        assert!(String::from_utf8(sse_message(&failed_login, json).to_vec()).unwrap().starts_with("event: SecurityEvent\n"));
+        assert!(filter.apply(&Event::SystemMetrics(sample_metrics()), event_to_json).is_none());
+        assert!(web::Query::<StreamQuery>::from_query("type=anomaly").is_err());
+        assert!(web::Query::<StreamQuery>::from_query("metrics_interval=0").is_err());
+        assert_eq!(web::Query::<StreamQuery>::from_query("metrics_interval=5").unwrap().metrics_interval, Some(5));
+    }
+}
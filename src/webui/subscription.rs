@@ -0,0 +1,200 @@
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::event::Event;
+use crate::syslog;
+
+// SystemMetrics arrays that grow with core, disk and interface count
+const HEAVY_METRICS_FIELDS: &[&str] = &[
+    "per_core_cpu",
+    "per_core_freq_mhz",
+    "per_core_temps",
+    "per_disk",
+    "per_interface",
+    "filesystems",
+    "users",
+    "fans",
+];
+
+// Longest metrics_interval a client can ask for, a day
+const MAX_METRICS_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+// What a WebSocket client asked for, e.g.
+// {"types":["SecurityEvent","Anomaly"],"min_severity":"warning","metrics_interval":5,"compact":true}
+// Every field is optional; a client that never sends one gets everything.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Subscription {
+    #[serde(default, alias = "subscribe")]
+    pub types: Vec<String>, // Event type names as sent in "type", "Event" suffix optional, empty means all
+    pub min_severity: Option<SubscriptionSeverity>,
+    #[serde(default, deserialize_with = "metrics_interval")]
+    pub metrics_interval: Option<u64>, // Seconds between SystemMetrics
+    #[serde(default)]
+    pub compact: bool, // Drop per-core, per-disk and per-interface arrays from SystemMetrics
+}
+
+// A metrics_interval between 1 second and a day
+pub(super) fn metrics_interval<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let interval = Option::<u64>::deserialize(deserializer)?;
+    if let Some(secs) = interval
+        && !(1..=MAX_METRICS_INTERVAL_SECS).contains(&secs)
+    {
+        return Err(serde::de::Error::custom(format!(
+            "metrics_interval must be between 1 and {} seconds",
+            MAX_METRICS_INTERVAL_SECS
+        )));
+    }
+    Ok(interval)
+}
+
+// Applies a subscription to the broadcast stream of one client
+#[derive(Default)]
+pub struct SubscriptionFilter {
+    subscription: Subscription,
+    last_metrics: Option<OffsetDateTime>,
+}
+
+impl SubscriptionFilter {
+    pub fn new(subscription: Subscription) -> Self {
+        Self {
+            subscription,
+            last_metrics: None,
+        }
+    }
+
+    // JSON to send for this event, or None if the client didn't ask for it
+    pub fn apply(&mut self, event: &Event, to_json: impl FnOnce(&Event) -> serde_json::Value) -> Option<serde_json::Value> {
+        let sub = &self.subscription;
+        let event_type = syslog::event_type(event);
+        // "security" is short for SecurityEvent
+        let short_type = event_type.strip_suffix("Event").unwrap_or(event_type);
+        if !sub.types.is_empty()
+            && !sub.types.iter().any(|t| t.eq_ignore_ascii_case(event_type) || t.eq_ignore_ascii_case(short_type))
+        {
+            return None;
+        }
+        if let Some(min) = sub.min_severity {
+            let max_level = match min {
+                SubscriptionSeverity::Critical => syslog::SEVERITY_CRITICAL,
+                SubscriptionSeverity::Warning => syslog::SEVERITY_WARNING,
+                SubscriptionSeverity::Info => syslog::SEVERITY_INFO,
+            };
+            // Syslog levels count down as severity goes up
+            if syslog::severity(event) > max_level {
+                return None;
+            }
+        }
+
+        let Event::SystemMetrics(m) = event else {
+            return Some(to_json(event));
+        };
+
+        if let Some(interval) = sub.metrics_interval.filter(|&i| i > 1) {
+            // Half a second of slack so collection jitter doesn't stretch 5s into 6s
+            if let Some(last) = self.last_metrics
+                && m.ts >= last
+                && ((m.ts - last).whole_milliseconds() as u64) < interval.saturating_mul(1000).saturating_sub(500)
+            {
+                return None;
+            }
+            self.last_metrics = Some(m.ts);
+        }
+
+        let mut json = to_json(event);
+        if sub.compact
+            && let Some(object) = json.as_object_mut()
+        {
+            for field in HEAVY_METRICS_FIELDS {
+                object.remove(*field);
+            }
+        }
+        Some(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{sample_metrics, Anomaly, AnomalyKind, AnomalySeverity, SystemMetrics};
+    use time::Duration;
+
+    fn to_json(event: &Event) -> serde_json::Value {
+        let mut json = serde_json::json!({ "type": syslog::event_type(event) });
+        if matches!(event, Event::SystemMetrics(_)) {
+            json["cpu"] = serde_json::json!(12.5);
+            json["per_core_cpu"] = serde_json::json!([10.0, 15.0]);
+        }
+        json
+    }
+
+    fn metrics(ts: OffsetDateTime) -> Event {
+        Event::SystemMetrics(SystemMetrics { ts, ..sample_metrics() })
+    }
+
+    fn anomaly(severity: AnomalySeverity) -> Event {
+        Event::Anomaly(Anomaly {
+            ts: OffsetDateTime::UNIX_EPOCH,
+            severity,
+            kind: AnomalyKind::CpuSpike,
+            message: "CPU spike".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_default_subscription_passes_everything() {
+        let mut filter = SubscriptionFilter::default();
+        let start = OffsetDateTime::UNIX_EPOCH;
+        for i in 0..3 {
+            let json = filter.apply(&metrics(start + Duration::seconds(i)), to_json).unwrap();
+            assert!(json.get("per_core_cpu").is_some());
+        }
+        assert!(filter.apply(&anomaly(AnomalySeverity::Info), to_json).is_some());
+    }
+
+    #[test]
+    fn test_subscription_filters_types_severity_and_metrics() {
+        let subscription: Subscription =
+            serde_json::from_str(r#"{"types":["anomaly","SystemMetrics"],"min_severity":"warning","metrics_interval":5,"compact":true}"#)
+                .unwrap();
+        let mut filter = SubscriptionFilter::new(subscription);
+
+        assert!(filter.apply(&anomaly(AnomalySeverity::Info), to_json).is_none());
+        assert!(filter.apply(&anomaly(AnomalySeverity::Critical), to_json).is_some());
+
+        // Metrics are Info, so min_severity drops them even though the type is wanted
+        assert!(filter.apply(&metrics(OffsetDateTime::UNIX_EPOCH), to_json).is_none());
+
+        let subscription: Subscription = serde_json::from_str(r#"{"metrics_interval":5,"compact":true}"#).unwrap();
+        let mut filter = SubscriptionFilter::new(subscription);
+        let start = OffsetDateTime::UNIX_EPOCH;
+        let sent: Vec<i64> = (0..12)
+            .filter(|&i| filter.apply(&metrics(start + Duration::seconds(i)), to_json).is_some())
+            .collect();
+        assert_eq!(sent, vec![0, 5, 10]);
+
+        let json = filter.apply(&metrics(start + Duration::seconds(20)), to_json).unwrap();
+        assert_eq!(json["cpu"], 12.5);
+        assert!(json.get("per_core_cpu").is_none());
+
+        assert!(serde_json::from_str::<Subscription>(r#"{"min_severity":"loud"}"#).is_err());
+        assert!(serde_json::from_str::<Subscription>(r#"{"metrics_interval":0}"#).is_err());
+        assert!(serde_json::from_str::<Subscription>(r#"{"metrics_interval":18446744073709551615}"#).is_err());
+        assert_eq!(serde_json::from_str::<Subscription>(r#"{"metrics_interval":86400}"#).unwrap().metrics_interval, Some(86400));
+        assert_eq!(serde_json::from_str::<Subscription>(r#"{"metrics_interval":null}"#).unwrap().metrics_interval, None);
+
+        let subscription: Subscription = serde_json::from_str(r#"{"subscribe":["Anomaly","SecurityEvent"]}"#).unwrap();
+        assert_eq!(subscription.types, vec!["Anomaly", "SecurityEvent"]);
+        let mut filter = SubscriptionFilter::new(subscription);
+        assert!(filter.apply(&anomaly(AnomalySeverity::Info), to_json).is_some());
+        assert!(filter.apply(&metrics(start), to_json).is_none());
+    }
+}
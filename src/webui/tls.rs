@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::sync::Arc;
+
+use crate::config::ServerConfig;
+
+// Build the rustls config from [server] tls_cert/tls_key. None means plain HTTP.
+// Called before the web server thread starts so bad paths or PEM fail startup with a clear error.
+pub fn load_tls_config(server: &ServerConfig) -> Result<Option<rustls::ServerConfig>> {
+    let Some((cert_path, key_path)) = server.tls_paths() else {
+        return Ok(None);
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .with_context(|| format!("Failed to read TLS certificate from tls_cert = \"{}\"", cert_path))?;
+    if certs.is_empty() {
+        anyhow::bail!("No PEM certificates found in tls_cert = \"{}\"", cert_path);
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .with_context(|| format!("Failed to read TLS private key from tls_key = \"{}\"", key_path))?;
+
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .context("Failed to set up TLS protocol versions")?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .context("TLS certificate and private key don't match or aren't supported")?;
+
+    Ok(Some(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_config(cert: &str, key: &str) -> ServerConfig {
+        let mut config = crate::config::Config::test_config().server;
+        config.tls_cert = Some(cert.to_string());
+        config.tls_key = Some(key.to_string());
+        config
+    }
+
+    #[test]
+    fn test_no_tls_paths_means_plain_http() {
+        let config = crate::config::Config::test_config().server;
+        assert!(load_tls_config(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bad_tls_paths_give_actionable_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        let missing = missing.to_str().unwrap();
+
+        let err = load_tls_config(&server_config(missing, missing)).unwrap_err();
+        assert!(format!("{:#}", err).contains("tls_cert"));
+
+        let garbage = dir.path().join("garbage.pem");
+        std::fs::write(&garbage, "not a certificate").unwrap();
+        let garbage = garbage.to_str().unwrap();
+        let err = load_tls_config(&server_config(garbage, garbage)).unwrap_err();
+        assert!(format!("{:#}", err).contains("No PEM certificates"));
+    }
+}
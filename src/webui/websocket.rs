@@ -1,4 +1,4 @@
-use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix::{Actor, ActorContext, ActorFutureExt, AsyncContext, StreamHandler, WrapFuture};
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use std::sync::Arc;
@@ -7,13 +7,18 @@ use time::OffsetDateTime;
 use tokio_stream::wrappers::BroadcastStream;
 
 use crate::broadcast::EventBroadcaster;
+use crate::config::Config;
+use crate::event::{DiskHealthStatus, Event, FilesystemInfo, FilesystemStatus, InterfacePacketRates, PerDiskMetrics, PerInterfaceMetrics};
+use crate::indexed_reader::IndexedReader;
+use super::resume::{self, Replay, ResumeLimits};
+use super::subscription::{Subscription, SubscriptionFilter};
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
-// Format current time as HH:MM:SS.mmm
+// Format current time as HH:MM:SS.mmm in the configured timezone
 fn now_timestamp() -> String {
-    let now = OffsetDateTime::now_utc();
+    let now = crate::timezone::to_local(OffsetDateTime::now_utc());
     format!(
         "{:02}:{:02}:{:02}.{:03}",
         now.hour(),
@@ -28,14 +33,92 @@ pub struct WsSession {
     hb: Instant,
     broadcaster: Arc<EventBroadcaster>,
     metadata: Arc<std::sync::RwLock<Option<crate::event::Metadata>>>,
+    filter: SubscriptionFilter, // Everything until the client sends a subscription
+    reader: Arc<IndexedReader>,
+    resume_limits: ResumeLimits,
+    first_live_seq: Option<i128>, // Seq of the first live event sent, replays stop short of it
+    held_back: Option<Vec<Event>>, // Live events arriving while a replay is read, sent after it
 }
 
 impl WsSession {
-    fn new(broadcaster: Arc<EventBroadcaster>, metadata: Arc<std::sync::RwLock<Option<crate::event::Metadata>>>) -> Self {
+    fn new(
+        broadcaster: Arc<EventBroadcaster>,
+        metadata: Arc<std::sync::RwLock<Option<crate::event::Metadata>>>,
+        reader: Arc<IndexedReader>,
+        resume_limits: ResumeLimits,
+    ) -> Self {
         Self {
             hb: Instant::now(),
             broadcaster,
             metadata,
+            filter: SubscriptionFilter::default(),
+            reader,
+            resume_limits,
+            first_live_seq: None,
+            held_back: None,
+        }
+    }
+
+    // Send an event the client's subscription asks for, tagged with its seq
+    fn send_event(&mut self, event: &Event, ctx: &mut ws::WebsocketContext<Self>) {
+        let Some(mut json) = self.filter.apply(event, event_to_json) else {
+            return;
+        };
+        if let Some(object) = json.as_object_mut() {
+            object.insert("seq".to_string(), resume::event_seq(event).to_string().into());
+        }
+        match serde_json::to_string(&json) {
+            Ok(json) => ctx.text(json),
+            Err(e) => {
+                eprintln!("Failed to serialize event: {}", e);
+            }
+        }
+    }
+
+    // Read what the client missed on a blocking thread, so other clients aren't held up,
+    // holding back live events until it has been sent
+    fn start_resume(&mut self, from_seq: i128, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.held_back.is_some() {
+            return; // Already catching up
+        }
+        self.held_back = Some(Vec::new());
+        let reader = self.reader.clone();
+        let limits = self.resume_limits;
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos();
+        let replay = web::block(move || resume::replay_since(&reader, from_seq, now, limits));
+        ctx.spawn(replay.into_actor(self).map(|result, act, ctx| {
+            let replay = result
+                .map_err(anyhow::Error::from)
+                .and_then(|replay| replay)
+                .unwrap_or_else(|e| Replay::GapTooLarge(format!("Could not read missed events: {}. Showing live", e)));
+            act.finish_resume(replay, ctx);
+        }));
+    }
+
+    fn finish_resume(&mut self, replay: Replay, ctx: &mut ws::WebsocketContext<Self>) {
+        let mut last_sent = None;
+        let message = match replay {
+            Replay::Events(events) => {
+                // Anything from the first live event on has been sent already
+                let events: Vec<Event> = events
+                    .into_iter()
+                    .filter(|event| self.first_live_seq.is_none_or(|first| resume::event_seq(event) < first))
+                    .collect();
+                for event in &events {
+                    self.send_event(event, ctx);
+                }
+                last_sent = events.last().map(resume::event_seq);
+                serde_json::json!({ "type": "Resumed", "replayed": events.len() })
+            }
+            Replay::GapTooLarge(message) => serde_json::json!({ "type": "ResumeGap", "message": message }),
+        };
+        ctx.text(message.to_string());
+
+        // Live events that came in meanwhile, less any that were on disk in time to be replayed
+        for event in self.held_back.take().unwrap_or_default() {
+            if last_sent.is_none_or(|last| resume::event_seq(&event) > last) {
+                self.send_event(&event, ctx);
+            }
         }
     }
 
@@ -83,7 +166,7 @@ impl Actor for WsSession {
                     "mem_total": metadata.mem_total_bytes,
                     "swap_total": metadata.swap_total_bytes,
                     "disk_total": metadata.disk_total_bytes,
-                    "filesystems": metadata.filesystems,
+                    "filesystems": filesystems_json(metadata.filesystems.as_deref(), metadata.filesystem_status.as_deref()),
                     "net_interface": metadata.net_interface,
                     "net_ip": metadata.net_ip_address,
                     "net_gateway": metadata.net_gateway,
@@ -97,6 +180,7 @@ impl Actor for WsSession {
                     "gpu_mem_freq": metadata.gpu.as_ref().and_then(|g| g.mem_freq_mhz),
                     "gpu_temp2": metadata.gpu.as_ref().and_then(|g| g.gpu_temp_celsius),
                     "gpu_power": metadata.gpu.as_ref().and_then(|g| g.power_watts),
+                    "gpus": metadata.gpus,
                     "users": metadata.logged_in_users,
                     "processes": metadata.processes,
                     "total_processes": metadata.total_processes,
@@ -128,8 +212,30 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
             Ok(ws::Message::Pong(_)) => {
                 self.hb = Instant::now();
             }
-            Ok(ws::Message::Text(_text)) => {
-                // Ignore text messages from client (we only push events)
+            Ok(ws::Message::Text(text)) => {
+                // A reconnecting client asking for what it missed
+                match resume::parse_resume(&text) {
+                    Some(Ok(from_seq)) => return self.start_resume(from_seq, ctx),
+                    Some(Err(e)) => {
+                        let error = serde_json::json!({
+                            "type": "Error",
+                            "message": format!("Invalid resume: {:#}", e),
+                        });
+                        return ctx.text(error.to_string());
+                    }
+                    None => {}
+                }
+                // Otherwise a subscription, replacing any earlier one
+                match serde_json::from_str::<Subscription>(&text) {
+                    Ok(subscription) => self.filter = SubscriptionFilter::new(subscription),
+                    Err(e) => {
+                        let error = serde_json::json!({
+                            "type": "Error",
+                            "message": format!("Invalid subscription: {}", e),
+                        });
+                        ctx.text(error.to_string());
+                    }
+                }
             }
             Ok(ws::Message::Binary(_)) => {
                 // Ignore binary messages
@@ -148,13 +254,12 @@ impl StreamHandler<Result<crate::event::Event, tokio_stream::wrappers::errors::B
     fn handle(&mut self, msg: Result<crate::event::Event, tokio_stream::wrappers::errors::BroadcastStreamRecvError>, ctx: &mut Self::Context) {
         match msg {
             Ok(event) => {
-                // Serialize and send event
-                match event_to_json_string(&event) {
-                    Ok(json) => ctx.text(json),
-                    Err(e) => {
-                        eprintln!("Failed to serialize event: {}", e);
-                    }
+                if let Some(held_back) = &mut self.held_back {
+                    held_back.push(event);
+                    return;
                 }
+                self.first_live_seq.get_or_insert_with(|| resume::event_seq(&event));
+                self.send_event(&event, ctx);
             }
             Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(skipped)) => {
                 eprintln!("{} WebSocket client lagged, skipped {} events", now_timestamp(), skipped);
@@ -170,20 +275,100 @@ pub async fn ws_handler(
     stream: web::Payload,
     broadcaster: web::Data<EventBroadcaster>,
     metadata: web::Data<std::sync::RwLock<Option<crate::event::Metadata>>>,
+    indexed_reader: web::Data<Arc<IndexedReader>>,
+    config: web::Data<Config>,
 ) -> Result<HttpResponse, Error> {
     let metadata_arc = Arc::clone(&metadata.into_inner());
-    let session = WsSession::new(Arc::new(broadcaster.get_ref().clone()), metadata_arc);
+    let limits = ResumeLimits {
+        max_secs: config.server.resume_max_secs,
+        max_events: config.server.resume_max_events,
+    };
+    let session = WsSession::new(
+        Arc::new(broadcaster.get_ref().clone()),
+        metadata_arc,
+        indexed_reader.get_ref().clone(),
+        limits,
+    );
     ws::start(session, &req, stream)
 }
 
-// Optimized: Serialize event directly to JSON string
-fn event_to_json_string(event: &crate::event::Event) -> Result<String, serde_json::Error> {
-    // Convert to serde_json::Value then serialize (optimized with pre-sized allocations)
-    serde_json::to_string(&event_to_json(event))
+// Filesystems with each mount's inode counts and critical flag merged in, null where not recorded
+pub(super) fn filesystems_json(
+    filesystems: Option<&[FilesystemInfo]>,
+    statuses: Option<&[FilesystemStatus]>,
+) -> Vec<serde_json::Value> {
+    filesystems
+        .unwrap_or_default()
+        .iter()
+        .map(|fs| {
+            let status = statuses.and_then(|statuses| statuses.iter().find(|s| s.mount_point == fs.mount_point));
+            serde_json::json!({
+                "filesystem": &fs.filesystem,
+                "mount_point": &fs.mount_point,
+                "total_bytes": fs.total_bytes,
+                "used_bytes": fs.used_bytes,
+                "available_bytes": fs.available_bytes,
+                "inodes_total": status.map(|s| s.inodes_total),
+                "inodes_free": status.map(|s| s.inodes_free),
+                "inodes_used_percent": status
+                    .filter(|s| s.inodes_total > 0)
+                    .map(|s| (s.inodes_total.saturating_sub(s.inodes_free) as f64 / s.inodes_total as f64 * 1000.0).round() / 10.0),
+                "critical": status.map(|s| s.critical),
+            })
+        })
+        .collect()
+}
+
+// Per-disk throughput with the latest SMART check merged in, null for disks without one
+pub(super) fn disks_json(disks: &[PerDiskMetrics], health: Option<&[DiskHealthStatus]>) -> Vec<serde_json::Value> {
+    disks
+        .iter()
+        .map(|d| {
+            let health = health.and_then(|health| health.iter().find(|h| h.device_name == d.device_name));
+            serde_json::json!({
+                "device": &d.device_name,
+                "read": d.read_bytes_per_sec,
+                "write": d.write_bytes_per_sec,
+                "temp": d.temp_celsius,
+                "health": health.map(|h| serde_json::json!({
+                    "passed": h.health_passed,
+                    "problems": &h.problems,
+                    "reallocated_sectors": h.reallocated_sectors,
+                    "pending_sectors": h.pending_sectors,
+                    "media_errors": h.media_errors,
+                    "percentage_used": h.percentage_used,
+                })),
+            })
+        })
+        .collect()
+}
+
+// Per-interface rates with packet rates merged in, null where not recorded
+pub(super) fn interfaces_json(
+    interfaces: &[PerInterfaceMetrics],
+    packets: Option<&[InterfacePacketRates]>,
+) -> Vec<serde_json::Value> {
+    interfaces
+        .iter()
+        .map(|i| {
+            let packets = packets.and_then(|packets| packets.iter().find(|p| p.interface_name == i.interface_name));
+            serde_json::json!({
+                "interface": &i.interface_name,
+                "recv": i.recv_bytes_per_sec,
+                "send": i.send_bytes_per_sec,
+                "recv_packets": packets.map(|p| p.recv_packets_per_sec),
+                "send_packets": packets.map(|p| p.send_packets_per_sec),
+                "recv_errors": i.recv_errors_per_sec,
+                "send_errors": i.send_errors_per_sec,
+                "recv_drops": i.recv_drops_per_sec,
+                "send_drops": i.send_drops_per_sec,
+            })
+        })
+        .collect()
 }
 
 // Convert Event to JSON format (same as API) - kept for large events
-fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
+pub fn event_to_json(event: &Event) -> serde_json::Value {
     use crate::event::Event;
 
     match event {
@@ -191,32 +376,11 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
             // Percentages are now calculated every second in main.rs using cached totals
 
             // Pre-compute nested arrays outside json! macro
-            let mut disks = Vec::with_capacity(m.per_disk_metrics.len());
-            for d in &m.per_disk_metrics {
-                disks.push(serde_json::json!({
-                    "device": &d.device_name,
-                    "read": d.read_bytes_per_sec,
-                    "write": d.write_bytes_per_sec,
-                    "temp": d.temp_celsius,
-                }));
-            }
+            let disks = disks_json(&m.per_disk_metrics, m.disk_health.as_deref());
 
-            let filesystems = match &m.filesystems {
-                Some(fs_list) => {
-                    let mut filesystems = Vec::with_capacity(fs_list.len());
-                    for fs in fs_list {
-                        filesystems.push(serde_json::json!({
-                            "filesystem": &fs.filesystem,
-                            "mount_point": &fs.mount_point,
-                            "total_bytes": fs.total_bytes,
-                            "used_bytes": fs.used_bytes,
-                            "available_bytes": fs.available_bytes,
-                        }));
-                    }
-                    filesystems
-                },
-                None => Vec::new()
-            };
+            let interfaces = interfaces_json(&m.per_interface, m.interface_packets.as_deref());
+
+            let filesystems = filesystems_json(m.filesystems.as_deref(), m.filesystem_status.as_deref());
 
             let users = match &m.logged_in_users {
                 Some(user_list) => {
@@ -256,9 +420,17 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
                 "system_uptime_seconds": m.system_uptime_seconds,
                 "cpu": m.cpu_usage_percent,
                 "per_core_cpu": m.per_core_usage,
+                "per_core_freq_mhz": m.per_core_freq_mhz,
                 "mem": m.mem_usage_percent,
                 "mem_used": m.mem_used_bytes,
                 "mem_total": m.mem_total_bytes,
+                "mem_available": m.mem_available_bytes,
+                "mem_cached": m.mem_cached_bytes,
+                "mem_buffers": m.mem_buffers_bytes,
+                "mem_dirty": m.mem_dirty_bytes,
+                "mem_writeback": m.mem_writeback_bytes,
+                "hugepages_total": m.hugepages_total,
+                "hugepages_free": m.hugepages_free,
                 "swap": m.swap_usage_percent,
                 "swap_used": m.swap_used_bytes,
                 "swap_total": m.swap_total_bytes,
@@ -272,6 +444,7 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
                 "disk_write": m.disk_write_bytes_per_sec,
                 "per_disk": disks,
                 "filesystems": filesystems,
+                "raid": m.raid_arrays,
                 "users": users,
                 "net_interface": m.net_interface,
                 "net_ip": m.net_ip_address,
@@ -279,6 +452,7 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
                 "net_dns": m.net_dns,
                 "net_recv": m.net_recv_bytes_per_sec,
                 "net_send": m.net_send_bytes_per_sec,
+                "per_interface": interfaces,
                 "tcp": m.tcp_connections,
                 "tcp_wait": m.tcp_time_wait,
                 "ctxt": m.context_switches_per_sec,
@@ -290,6 +464,12 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
                 "gpu_mem_freq": m.gpu.mem_freq_mhz,
                 "gpu_temp2": m.gpu.gpu_temp_celsius,
                 "gpu_power": m.gpu.power_watts,
+                "gpus": m.gpus,
+                "sockets": m.sockets,
+                "tcp_listen_drops": m.tcp_listen_drops,
+                "kernel_limits": m.kernel_limits,
+                "pressure": m.pressure,
+                "rollup": m.rollup,
                 "fans": fans,
             });
 
@@ -302,6 +482,8 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
             "pid": p.pid,
             "name": p.name,
             "cmdline": p.cmdline,
+            "container_id": p.container_id,
+            "container_name": p.container_name,
         }),
         Event::SecurityEvent(s) => serde_json::json!({
             "type": "SecurityEvent",
@@ -329,7 +511,18 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
                     "user": &proc.user,
                     "cpu_percent": proc.cpu_percent,
                     "mem_bytes": proc.mem_bytes,
+                    "read_bytes_per_sec": proc.read_bytes_per_sec,
+                    "write_bytes_per_sec": proc.write_bytes_per_sec,
                     "num_threads": proc.num_threads,
+                    "container_id": proc.container_id,
+                    "container_name": proc.container_name,
+                    "connection_count": proc.connection_count,
+                    "connections": proc.connections.iter().map(|c| serde_json::json!({
+                        "protocol": c.protocol,
+                        "local_port": c.local_port,
+                        "remote_addr": c.remote_addr,
+                        "state": c.state,
+                    })).collect::<Vec<_>>(),
                 }));
             }
             serde_json::json!({
@@ -338,6 +531,10 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
                 "count": p.processes.len(),
                 "total_processes": p.total_processes,
                 "running_processes": p.running_processes,
+                "top_cpu_pids": p.top_cpu_pids,
+                "top_mem_pids": p.top_mem_pids,
+                "per_user": p.per_user,
+                "per_container": p.per_container,
                 "processes": processes,
             })
         },
@@ -345,7 +542,87 @@ fn event_to_json(event: &crate::event::Event) -> serde_json::Value {
             "type": "FileSystemEvent",
             "timestamp": f.ts.unix_timestamp_nanos() / 1_000_000,
             "kind": format!("{:?}", f.kind),
-            "path": f.path
+            "path": f.path,
+            "size": f.size,
+            "old_hash": f.old_hash,
+            "new_hash": f.new_hash,
+            "old_attributes": f.old_attributes,
+            "new_attributes": f.new_attributes,
+            "diff": f.diff,
+            "count": f.count,
+        }),
+        Event::Annotation(a) => serde_json::json!({
+            "type": "Annotation",
+            "timestamp": a.ts.unix_timestamp_nanos() / 1_000_000,
+            "author": a.author,
+            "text": a.text,
+            "tags": a.tags,
+        }),
+        Event::DiskHealth(d) => serde_json::json!({
+            "type": "DiskHealth",
+            "timestamp": d.ts.unix_timestamp_nanos() / 1_000_000,
+            "device": d.device,
+            "model": d.model,
+            "health_passed": d.health_passed,
+            "failing_attributes": d.failing_attributes,
+            "reallocated_sectors": d.reallocated_sectors,
+            "pending_sectors": d.pending_sectors,
+            "offline_uncorrectable": d.offline_uncorrectable,
+            "crc_errors": d.crc_errors,
+            "percentage_used": d.percentage_used,
+            "available_spare": d.available_spare,
+            "available_spare_threshold": d.available_spare_threshold,
+            "media_errors": d.media_errors,
+            "critical_warning": d.critical_warning,
+            "power_on_hours": d.power_on_hours,
+            "temperature": d.temperature_celsius,
+        }),
+        Event::ServiceStateChange(s) => serde_json::json!({
+            "type": "ServiceStateChange",
+            "timestamp": s.ts.unix_timestamp_nanos() / 1_000_000,
+            "unit": s.unit,
+            "old_state": s.old_state,
+            "new_state": s.new_state,
+            "n_restarts": s.n_restarts,
+        }),
+        Event::SystemEvent(s) => {
+            let mut json = serde_json::json!({
+                "type": "SystemEvent",
+                "timestamp": s.ts.unix_timestamp_nanos() / 1_000_000,
+                "kind": s.kind.name(),
+                "message": s.message,
+            });
+            match &s.kind {
+                crate::event::SystemEventKind::Rebooted { boot_time, last_event, clean_shutdown } => {
+                    json["boot_time"] = serde_json::json!(boot_time.unix_timestamp_nanos() / 1_000_000);
+                    json["last_event"] = serde_json::json!(last_event.unix_timestamp_nanos() / 1_000_000);
+                    json["clean_shutdown"] = (*clean_shutdown).into();
+                }
+                crate::event::SystemEventKind::ConfigReloaded { applied, requires_restart } => {
+                    json["applied"] = serde_json::json!(applied);
+                    json["requires_restart"] = serde_json::json!(requires_restart);
+                }
+                crate::event::SystemEventKind::Shutdown => {}
+            }
+            json
+        }
+        Event::OperatorAction(a) => serde_json::json!({
+            "type": "OperatorAction",
+            "timestamp": a.ts.unix_timestamp_nanos() / 1_000_000,
+            "user": a.user,
+            "action": a.action,
+            "pid": a.pid,
+            "name": a.name,
+            "cmdline": a.cmdline,
+            "success": a.success,
+            "outcome": a.outcome,
+        }),
+        Event::NetworkTraffic(t) => serde_json::json!({
+            "type": "NetworkTraffic",
+            "timestamp": t.ts.unix_timestamp_nanos() / 1_000_000,
+            "interval_secs": t.interval_secs,
+            "bytes_counted": t.bytes_counted,
+            "top_remotes": t.top_remotes,
         }),
     }
 }